@@ -0,0 +1,29 @@
+/* C bindings to the CHIP-8 interpreter, see src/lib.rs for what each function does. */
+
+#ifndef CHIP8_H
+#define CHIP8_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define CHIP8_WIDTH 64
+#define CHIP8_HEIGHT 32
+
+typedef struct Machine Chip8;
+
+Chip8 *chip8_create(void);
+void chip8_destroy(Chip8 *machine);
+
+/* Returns 0, or -1 when a pointer is null. */
+int32_t chip8_load_rom(Chip8 *machine, const uint8_t *rom, size_t length);
+
+void chip8_step(Chip8 *machine);
+/* Call at 60 Hz. */
+void chip8_update_timers(Chip8 *machine);
+
+/* A byte per pixel, CHIP8_WIDTH * CHIP8_HEIGHT of them. Returns the number copied, or 0
+ * when length is too short. */
+size_t chip8_get_framebuffer(const Chip8 *machine, uint8_t *pixels, size_t length);
+void chip8_set_key(Chip8 *machine, uint8_t key, int32_t down);
+
+#endif
@@ -0,0 +1,156 @@
+//! C bindings to the interpreter, for front ends that are not written in Rust: C, Python
+//! through ctypes, or a game engine.
+//!
+//! A machine is created with `chip8_create` and handed back to every other function, until
+//! `chip8_destroy` frees it. The embedder drives it: `chip8_step` executes an instruction,
+//! `chip8_update_timers` counts the timers down and should be called at 60 Hz, and the keys
+//! are held down and released with `chip8_set_key`. `chip8.h` declares the functions.
+
+use std::slice;
+
+use chip8::{Chip8, WIDTH, HEIGHT};
+
+/// A machine and the keys held down on its keypad.
+pub struct Machine {
+    chip8: Chip8,
+    keys: [bool; 16],
+}
+
+/// A new machine without a ROM, to be freed with `chip8_destroy`.
+#[no_mangle]
+pub extern "C" fn chip8_create() -> *mut Machine {
+    Box::into_raw(Box::new(Machine { chip8: Chip8::new(), keys: [false; 16] }))
+}
+
+/// Free a machine. Null is ignored.
+///
+/// # Safety
+///
+/// The machine must come from `chip8_create` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_destroy(machine: *mut Machine) {
+    if !machine.is_null() {
+        drop(Box::from_raw(machine));
+    }
+}
+
+/// Load a ROM from `length` bytes at `rom` and reset the machine to run it. Returns 0, or
+/// -1 when a pointer is null.
+///
+/// # Safety
+///
+/// The machine must come from `chip8_create`, and `rom` point to at least `length` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(machine: *mut Machine, rom: *const u8, length: usize)
+        -> i32 {
+    match (machine.as_mut(), rom.is_null()) {
+        (Some(machine), false) => {
+            machine.chip8.load_bytes(slice::from_raw_parts(rom, length));
+            0
+        },
+        _ => -1,
+    }
+}
+
+/// Execute a single instruction, with the keys as they are held down now.
+///
+/// # Safety
+///
+/// The machine must come from `chip8_create`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(machine: *mut Machine) {
+    if let Some(machine) = machine.as_mut() {
+        machine.chip8.set_keys(machine.keys);
+        machine.chip8.cycle();
+    }
+}
+
+/// Count the delay and sound timers down by a tick, at the display interrupt.
+///
+/// # Safety
+///
+/// The machine must come from `chip8_create`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_update_timers(machine: *mut Machine) {
+    if let Some(machine) = machine.as_mut() {
+        machine.chip8.update_timers();
+    }
+}
+
+/// Copy the display into `pixels`, a byte per pixel row by row, 1 for a lit pixel and 0
+/// otherwise. Returns the number of pixels copied, 64 * 32, or 0 when `length` is too
+/// short for them.
+///
+/// # Safety
+///
+/// The machine must come from `chip8_create`, and `pixels` point to at least `length`
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_framebuffer(machine: *const Machine, pixels: *mut u8,
+        length: usize) -> usize {
+    let machine = match machine.as_ref() {
+        Some(machine) if !pixels.is_null() && length >= WIDTH * HEIGHT => machine,
+        _ => return 0,
+    };
+    let pixels = slice::from_raw_parts_mut(pixels, WIDTH * HEIGHT);
+    let display = machine.chip8.frame();
+
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        *pixel = display.is_lit(i % WIDTH, i / WIDTH) as u8;
+    }
+
+    WIDTH * HEIGHT
+}
+
+/// Hold a key of the keypad down, or release it when `down` is 0. Keys past F are ignored.
+///
+/// # Safety
+///
+/// The machine must come from `chip8_create`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_key(machine: *mut Machine, key: u8, down: i32) {
+    if let Some(machine) = machine.as_mut() {
+        if let Some(held) = machine.keys.get_mut(key as usize) {
+            *held = down != 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    #[test]
+    fn test_run_rom() {
+        // LD V1, K; LD F, V1; DRW V0, V0, 5; JP 0x206
+        let rom = [0xF1, 0x0A, 0xF1, 0x29, 0xD0, 0x05, 0x12, 0x06];
+        let mut pixels = [0xFF; WIDTH * HEIGHT];
+
+        unsafe {
+            let machine = chip8_create();
+            assert_eq!(chip8_load_rom(machine, rom.as_ptr(), rom.len()), 0);
+            assert_eq!(chip8_load_rom(machine, ptr::null(), 0), -1);
+
+            // Waits for a key, and draws its digit once it goes down
+            chip8_step(machine);
+            chip8_set_key(machine, 0x7, 1);
+            chip8_set_key(machine, 0x10, 1);
+            for _ in 0..4 {
+                chip8_step(machine);
+                chip8_update_timers(machine);
+            }
+
+            assert_eq!(chip8_get_framebuffer(machine, pixels.as_mut_ptr(), 16), 0);
+            assert_eq!(chip8_get_framebuffer(machine, pixels.as_mut_ptr(), pixels.len()),
+                WIDTH * HEIGHT);
+            chip8_destroy(machine);
+            chip8_destroy(ptr::null_mut());
+        }
+
+        // The top row of the 7, 0xF0
+        assert_eq!(&pixels[..8], &[1, 1, 1, 1, 0, 0, 0, 0]);
+        assert_eq!(pixels[WIDTH..WIDTH + 4], [0, 0, 0, 1]);
+        assert_eq!(pixels.iter().filter(|&&pixel| pixel == 1).count(), 8);
+    }
+}
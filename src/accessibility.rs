@@ -0,0 +1,222 @@
+//! Display options for players who find the default display hard to see.
+//!
+//! Inverting swaps light and dark. The large scale doubles the size of the window and keeps
+//! the pixels sharp squares by always filtering with nearest neighbour. Frame blending shows
+//! every frame mixed with the one before, so a sprite that is erased and redrawn every other
+//! frame is shown steadily at half brightness instead of blinking.
+//!
+//! Flicker reduction is for players sensitive to flashing. No pixel changes more often than
+//! every `HOLD_FRAMES` frames, and a pixel lit at any time in between stays lit, so sprites
+//! drawn with XOR every other frame are shown steadily on. Only what is shown is changed,
+//! the machine runs as it would otherwise.
+
+use crate::{WIDTH, HEIGHT};
+use crate::chip8x::ColorBoard;
+use crate::display::{Display, LIT};
+use crate::filters;
+use crate::screen::Buffer;
+
+/// Frames a pixel stays as shown after it changed, which keeps the display to at most three
+/// flashes a second at 60 Hz.
+const HOLD_FRAMES: u8 = 10;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Accessibility {
+    pub invert_colors: bool,
+    pub large_scale: bool,
+    pub blend_frames: bool,
+    /// Takes the place of frame blending when both are enabled.
+    pub reduce_flicker: bool,
+}
+
+/// Draws the display blended with the previous frame.
+pub struct FrameBlender {
+    previous: [u64; HEIGHT],
+    // The current and previous rows as they were last drawn
+    drawn: [(u64, u64); HEIGHT],
+}
+
+impl FrameBlender {
+    pub fn new() -> FrameBlender {
+        FrameBlender {
+            previous: [0; HEIGHT],
+            drawn: [(0, 0); HEIGHT],
+        }
+    }
+
+    /// Draw the rows whose blend changed into a buffer of the display's size, called once a
+    /// frame in place of `Display::render_changes`.
+    pub fn render(
+            &mut self, display: &mut Display, buffer: &mut Buffer, colors: Option<&ColorBoard>) {
+        let (rows, dirty_rows) = display.take_shown_rows();
+
+        for y in 0..HEIGHT {
+            let blend = (rows[y], self.previous[y]);
+            if blend == self.drawn[y] && dirty_rows >> y & 1 == 0 {
+                continue;
+            }
+
+            for x in 0..WIDTH {
+                let bit = 1 << (WIDTH - 1 - x);
+                let (lit, unlit) = match colors {
+                    Some(colors) => (colors.color(x, y, true), colors.color(x, y, false)),
+                    None => (LIT, 0),
+                };
+
+                let color = match (blend.0 & bit != 0, blend.1 & bit != 0) {
+                    (true, true) => lit,
+                    (false, false) => unlit,
+                    _ => filters::dim(lit) + filters::dim(unlit),
+                };
+                buffer.set_pixel(x, y, color);
+            }
+
+            self.drawn[y] = blend;
+        }
+
+        self.previous = rows;
+    }
+}
+
+/// Draws the display with the rate at which pixels change capped.
+pub struct FlickerFilter {
+    shown: [u64; HEIGHT],
+    // The pixels lit since they last changed, or since the last frame once they may change
+    seen: [u64; HEIGHT],
+    // Frames since each pixel last changed, up to `HOLD_FRAMES`
+    age: [[u8; WIDTH]; HEIGHT],
+}
+
+impl FlickerFilter {
+    pub fn new() -> FlickerFilter {
+        FlickerFilter {
+            shown: [0; HEIGHT],
+            seen: [0; HEIGHT],
+            age: [[HOLD_FRAMES; WIDTH]; HEIGHT],
+        }
+    }
+
+    /// Draw the rows whose shown pixels changed into a buffer of the display's size, called
+    /// once a frame in place of `Display::render_changes`.
+    pub fn render(
+            &mut self, display: &mut Display, buffer: &mut Buffer, colors: Option<&ColorBoard>) {
+        let (rows, dirty_rows) = display.take_shown_rows();
+
+        for y in 0..HEIGHT {
+            let before = self.shown[y];
+            self.seen[y] |= rows[y];
+
+            for x in 0..WIDTH {
+                let bit = 1 << (WIDTH - 1 - x);
+                let age = &mut self.age[y][x];
+
+                if *age < HOLD_FRAMES {
+                    *age += 1;
+                    continue;
+                }
+
+                if (self.seen[y] ^ self.shown[y]) & bit != 0 {
+                    self.shown[y] ^= bit;
+                    *age = 0;
+                }
+                self.seen[y] = self.seen[y] & !bit | rows[y] & bit;
+            }
+
+            if self.shown[y] == before && dirty_rows >> y & 1 == 0 {
+                continue;
+            }
+
+            for x in 0..WIDTH {
+                let lit = self.shown[y] & 1 << (WIDTH - 1 - x) != 0;
+                let color = match colors {
+                    Some(colors) => colors.color(x, y, lit),
+                    None if lit => LIT,
+                    None => 0,
+                };
+                buffer.set_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flicker_is_blended() {
+        let mut display = Display::new();
+        let mut buffer = Buffer::new(WIDTH, HEIGHT, None);
+        let mut blender = FrameBlender::new();
+
+        display.draw_sprite(0, 0, &[0x80]);
+        blender.render(&mut display, &mut buffer, None);
+        assert_eq!(buffer.pixels()[0], filters::dim(LIT));
+
+        display.draw_sprite(0, 0, &[0x80]);
+        blender.render(&mut display, &mut buffer, None);
+        assert_eq!(buffer.pixels()[0], filters::dim(LIT));
+
+        blender.render(&mut display, &mut buffer, None);
+        assert_eq!(buffer.pixels()[0], 0);
+    }
+
+    #[test]
+    fn test_steady_pixels_are_full() {
+        let mut display = Display::new();
+        let mut buffer = Buffer::new(WIDTH, HEIGHT, None);
+        let mut blender = FrameBlender::new();
+
+        display.draw_sprite(3, 2, &[0x80]);
+        blender.render(&mut display, &mut buffer, None);
+        blender.render(&mut display, &mut buffer, None);
+
+        assert_eq!(buffer.pixels()[3 + 2 * WIDTH], LIT);
+    }
+
+    #[test]
+    fn test_flicker_is_held_on() {
+        let mut display = Display::new();
+        let mut buffer = Buffer::new(WIDTH, HEIGHT, None);
+        let mut filter = FlickerFilter::new();
+
+        // Erased and redrawn every other frame
+        for _ in 0..30 {
+            display.draw_sprite(0, 0, &[0x80]);
+            filter.render(&mut display, &mut buffer, None);
+            assert_eq!(buffer.pixels()[0], LIT);
+        }
+
+        // Once the pixel stays off, it goes off after the hold
+        let frames_lit = (0..HOLD_FRAMES as usize * 2)
+            .take_while(|_| {
+                filter.render(&mut display, &mut buffer, None);
+                buffer.pixels()[0] == LIT
+            })
+            .count();
+        assert!(frames_lit < HOLD_FRAMES as usize);
+        assert_eq!(buffer.pixels()[0], 0);
+    }
+
+    #[test]
+    fn test_changes_are_spaced() {
+        let mut display = Display::new();
+        let mut buffer = Buffer::new(WIDTH, HEIGHT, None);
+        let mut filter = FlickerFilter::new();
+
+        // Lit for two frames and off for two, too fast to show
+        let mut shown = Vec::new();
+        for frame in 0..40 {
+            if frame % 2 == 0 {
+                display.draw_sprite(0, 0, &[0x80]);
+            }
+            filter.render(&mut display, &mut buffer, None);
+            shown.push(buffer.pixels()[0]);
+        }
+
+        let changes: Vec<usize> = (1..shown.len()).filter(|&f| shown[f] != shown[f - 1]).collect();
+        for pair in changes.windows(2) {
+            assert!(pair[1] - pair[0] > HOLD_FRAMES as usize);
+        }
+    }
+}
@@ -0,0 +1,261 @@
+//! Achievements: milestones of a ROM that pop up on the game display once reached, such as
+//! scoring 10 in Pong. Just for fun, and a showcase for the cycle hooks and memory
+//! conditions.
+//!
+//! The achievements of a ROM are listed in its metadata directory, one per line with the
+//! condition that unlocks it, written like a breakpoint condition, and its name:
+//!
+//! ```text
+//! [0x2F0] >= 10: Score 10 in Pong
+//! ```
+//!
+//! The conditions are checked after every instruction by a post-cycle hook. Achievements
+//! unlock only once: they are remembered per ROM in the data directory.
+
+use std::{fs, io::{self, Write}, mem, path::{Path, PathBuf}};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::Chip8;
+use crate::debugger::Condition;
+use crate::locale::{tr, Text};
+use crate::screen::{Buffer, Point};
+use crate::text::{text_width, LINE_HEIGHT};
+
+const TITLE_COLOR: u32 = 0xFFD040;
+const TEXT_COLOR: u32 = 0xFFFFFF;
+const BACKGROUND_COLOR: u32 = 0x202020;
+const TOAST_TIME: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Achievement {
+    pub condition: Condition,
+    pub name: String,
+}
+
+impl Achievement {
+    /// Parse a line of an achievements file, such as `[0x2F0] >= 10: Score 10 in Pong`.
+    pub fn parse(line: &str) -> Result<Achievement, String> {
+        let idx = line.find(':').ok_or(format!("Expected 'condition: name' in '{}'", line))?;
+
+        let condition = line[..idx].trim().parse()?;
+        let name = line[idx + 1..].trim();
+        if name.is_empty() {
+            return Err(format!("Missing name in '{}'", line));
+        }
+
+        Ok(Achievement { condition, name: name.to_string() })
+    }
+}
+
+// Shared with the hook, which moves achievements from locked to unlocked
+#[derive(Default)]
+struct Progress {
+    locked: Vec<Achievement>,
+    unlocked: Vec<String>,
+}
+
+/// The achievements of the current ROM, and the toasts of those just unlocked.
+pub struct Achievements {
+    progress: Arc<Mutex<Progress>>,
+    // Where the names of the unlocked achievements are remembered
+    path: Option<PathBuf>,
+    toasts: VecDeque<String>,
+    // When the first toast was shown
+    shown_at: Option<Instant>,
+}
+
+impl Achievements {
+    pub fn new() -> Achievements {
+        Achievements {
+            progress: Arc::new(Mutex::new(Progress::default())),
+            path: None,
+            toasts: VecDeque::new(),
+            shown_at: None,
+        }
+    }
+
+    /// Check the achievements after every instruction of the machine. The hook keeps
+    /// checking those of the ROM loaded last.
+    pub fn attach(&self, chip8: &mut Chip8) {
+        let progress = Arc::clone(&self.progress);
+
+        chip8.on_post_cycle(move |chip8, _, _| {
+            let mut progress = progress.lock().unwrap();
+            let Progress { locked, unlocked } = &mut *progress;
+
+            let mut idx = 0;
+            while idx < locked.len() {
+                if locked[idx].condition.evaluate(chip8) {
+                    unlocked.push(locked.remove(idx).name);
+                } else {
+                    idx += 1;
+                }
+            }
+        });
+    }
+
+    /// Read the achievements of a ROM from the metadata directory, leaving out those
+    /// unlocked before. Lines that do not parse are skipped.
+    pub fn load(&mut self, metadata_dir: &Path, unlocks_dir: &Path, rom_name: &str) {
+        let path = metadata_dir.join(format!("{}.achievements", rom_name));
+        let contents = fs::read_to_string(&path).unwrap_or_default();
+
+        let unlocks = unlocks_dir.join(format!("{}.unlocked", rom_name));
+        let unlocked = fs::read_to_string(&unlocks).unwrap_or_default();
+        let unlocked: Vec<&str> = unlocked.lines().map(str::trim).collect();
+
+        let locked = contents.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| match Achievement::parse(line) {
+                Ok(achievement) => Some(achievement),
+                Err(e) => {
+                    println!("Skipping achievement: {}", e);
+                    None
+                },
+            })
+            .filter(|achievement| !unlocked.contains(&achievement.name.as_str()))
+            .collect();
+
+        *self.progress.lock().unwrap() = Progress { locked, unlocked: Vec::new() };
+        self.path = Some(unlocks);
+        self.toasts.clear();
+        self.shown_at = None;
+    }
+
+    /// Remember the achievements unlocked since the previous call and queue their toasts,
+    /// and let the toast that was shown long enough go.
+    pub fn update(&mut self) {
+        let unlocked = mem::take(&mut self.progress.lock().unwrap().unlocked);
+
+        for name in unlocked {
+            println!("Achievement unlocked: {}", name);
+            if let Err(e) = self.remember(&name) {
+                println!("Could not store achievement: {}", e);
+            }
+
+            self.toasts.push_back(name);
+        }
+
+        if self.shown_at.map_or(false, |shown_at| shown_at.elapsed() >= TOAST_TIME) {
+            self.toasts.pop_front();
+            self.shown_at = None;
+        }
+        if self.shown_at.is_none() && !self.toasts.is_empty() {
+            self.shown_at = Some(Instant::now());
+        }
+    }
+
+    fn remember(&self, name: &str) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", name)
+    }
+
+    pub fn is_showing(&self) -> bool {
+        !self.toasts.is_empty()
+    }
+
+    /// Draw the current toast over the bottom of the game display, with its name broken
+    /// into lines that fit.
+    pub fn render(&self, buffer: &mut Buffer) {
+        let name = match self.toasts.front() {
+            Some(name) => name.to_uppercase(),
+            None => return,
+        };
+
+        let mut lines = vec!(String::from(tr(Text::Unlocked)));
+        for word in name.split_whitespace() {
+            let last = lines.len() - 1;
+            let joined = format!("{} {}", lines[last], word);
+
+            if last > 0 && text_width(&joined) < buffer.width() {
+                lines[last] = joined;
+            } else {
+                lines.push(word.to_string());
+            }
+        }
+
+        let top = buffer.height().saturating_sub(lines.len() * LINE_HEIGHT + 1);
+        for y in top..buffer.height() {
+            for x in 0..buffer.width() {
+                buffer.set_pixel(x, y, BACKGROUND_COLOR);
+            }
+        }
+
+        for (idx, line) in lines.iter().enumerate() {
+            let color = if idx == 0 { TITLE_COLOR } else { TEXT_COLOR };
+            buffer.draw_text(line, Point::new(1, top + 1 + idx * LINE_HEIGHT), color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::Cpu;
+
+    #[test]
+    fn test_parse() {
+        let achievement = Achievement::parse("[0x2F0] >= 10: Score 10 in Pong").unwrap();
+        assert_eq!(achievement.name, "Score 10 in Pong");
+        assert_eq!(achievement.condition, "[0x2F0] >= 10".parse().unwrap());
+
+        assert!(Achievement::parse("[0x2F0] >= 10 Score 10").is_err());
+        assert!(Achievement::parse("[0x2F0] >= 10:").is_err());
+        assert!(Achievement::parse("[0x2F0] => 10: Score 10").is_err());
+    }
+
+    #[test]
+    fn test_unlocks_once() {
+        let dir = std::env::temp_dir()
+            .join(format!("chip8-achievements-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("pong.achievements"), "V1 == 2: Two\nV1 == 3: Three\n").unwrap();
+
+        let mut chip8 = Chip8::new();
+        // ADD V1, 1 over and over
+        chip8.load_bytes(&[0x71, 0x01, 0x71, 0x01, 0x71, 0x01]);
+        let mut achievements = Achievements::new();
+        achievements.attach(&mut chip8);
+        achievements.load(&dir, &dir, "pong");
+
+        chip8.cycle();
+        achievements.update();
+        assert!(!achievements.is_showing());
+
+        chip8.cycle();
+        achievements.update();
+        assert_eq!(achievements.toasts, vec!("Two"));
+
+        // Unlocked achievements do not come back on the next run
+        achievements.load(&dir, &dir, "pong");
+        chip8.set_register(1, 2);
+        chip8.cycle();
+        achievements.update();
+        assert_eq!(achievements.toasts, vec!("Three"));
+        assert_eq!(fs::read_to_string(dir.join("pong.unlocked")).unwrap(), "Two\nThree\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_render_breaks_lines() {
+        let mut achievements = Achievements::new();
+        achievements.toasts.push_back(String::from("Score 100 in Pong"));
+
+        let mut buffer = Buffer::new(64, 32, None);
+        achievements.render(&mut buffer);
+
+        // The title and two lines of the name
+        let top = 32 - 3 * LINE_HEIGHT - 1;
+        assert_eq!(buffer.pixels()[top * 64], BACKGROUND_COLOR);
+        assert_eq!(buffer.pixels()[(top - 1) * 64], 0);
+    }
+}
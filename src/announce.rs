@@ -0,0 +1,150 @@
+//! Announcing game state such as the score or the lives left, for players who cannot see
+//! the display well and for streamers.
+//!
+//! The bytes to announce are listed per ROM in its metadata directory, one per line with
+//! the address and what to say. `{}` is replaced by the value of the byte, or the value
+//! follows the text when there is none:
+//!
+//! ```text
+//! 2F0 Score
+//! 2F1 {} lives left
+//! ```
+//!
+//! A byte is announced when its value at the end of a frame differs from the frame before.
+//! The announcements are printed, and spoken when a speech command such as `espeak` or
+//! `say` is configured, which is run with the text as its argument.
+
+use std::{fs, path::PathBuf, process::Command};
+
+use crate::MEMORY;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Announcement {
+    pub address: u16,
+    pub text: String,
+}
+
+impl Announcement {
+    pub fn parse(line: &str) -> Result<Announcement, String> {
+        let mut fields = line.splitn(2, char::is_whitespace);
+
+        let address = fields.next().unwrap_or("");
+        let address = u16::from_str_radix(address, 16).ok()
+            .filter(|&address| (address as usize) < MEMORY)
+            .ok_or(format!("Invalid address '{}'", address))?;
+        let text = fields.next().map(str::trim).filter(|text| !text.is_empty())
+            .ok_or(format!("Nothing to announce for {:03X}", address))?;
+
+        Ok(Announcement { address, text: text.to_string() })
+    }
+
+    fn say(&self, value: u8) -> String {
+        if self.text.contains("{}") {
+            self.text.replace("{}", &value.to_string())
+        } else {
+            format!("{} {}", self.text, value)
+        }
+    }
+}
+
+/// The announcements of a ROM, with the values they were last made for.
+pub struct Announcer {
+    announcements: Vec<Announcement>,
+    values: Vec<Option<u8>>,
+    speech_command: Option<String>,
+}
+
+impl Announcer {
+    /// Read the announcements of a ROM, skipping lines that do not parse.
+    pub fn load(dir: PathBuf, rom_name: &str, speech_command: Option<String>) -> Announcer {
+        let path = dir.join(format!("{}.announce", rom_name));
+        let contents = fs::read_to_string(&path).unwrap_or_default();
+
+        let announcements: Vec<Announcement> = contents.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| match Announcement::parse(line) {
+                Ok(announcement) => Some(announcement),
+                Err(e) => {
+                    println!("Skipping announcement: {}", e);
+                    None
+                },
+            })
+            .collect();
+
+        Announcer {
+            values: vec!(None; announcements.len()),
+            announcements,
+            speech_command,
+        }
+    }
+
+    /// What to announce about the memory at the end of a frame. Nothing is announced for
+    /// the first frame, which only takes note of the values.
+    pub fn poll(&mut self, memory: &[u8]) -> Vec<String> {
+        let mut said = Vec::new();
+
+        for (announcement, last) in self.announcements.iter().zip(self.values.iter_mut()) {
+            let value = memory[announcement.address as usize];
+
+            if last.map_or(false, |last| last != value) {
+                said.push(announcement.say(value));
+            }
+            *last = Some(value);
+        }
+
+        said
+    }
+
+    /// Print the announcements and pass them to the speech command, if there is one.
+    pub fn announce(&mut self, memory: &[u8]) {
+        for text in self.poll(memory) {
+            println!("{}", text);
+
+            if let Some(command) = &self.speech_command {
+                if let Err(e) = Command::new(command).arg(&text).spawn() {
+                    println!("Could not run {}: {}", command, e);
+                    self.speech_command = None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Announcement::parse("2F1 {} lives left"), Ok(Announcement {
+            address: 0x2F1,
+            text: String::from("{} lives left"),
+        }));
+        assert!(Announcement::parse("1000 Score").is_err());
+        assert!(Announcement::parse("2F0").is_err());
+    }
+
+    #[test]
+    fn test_announces_changes() {
+        let mut announcer = Announcer {
+            announcements: vec!(
+                Announcement::parse("2F0 Score").unwrap(),
+                Announcement::parse("2F1 {} lives left").unwrap(),
+            ),
+            values: vec!(None; 2),
+            speech_command: None,
+        };
+        let mut memory = [0; MEMORY];
+        memory[0x2F1] = 3;
+
+        assert!(announcer.poll(&memory).is_empty());
+
+        memory[0x2F0] = 10;
+        assert_eq!(announcer.poll(&memory), vec!("Score 10"));
+        assert!(announcer.poll(&memory).is_empty());
+
+        memory[0x2F1] = 2;
+        assert_eq!(announcer.poll(&memory), vec!("2 lives left"));
+    }
+}
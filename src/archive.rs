@@ -0,0 +1,92 @@
+//! Reading ROMs that are stored compressed, so ROM collections can stay archived on disk.
+//!
+//! A path ending in `.gz` is decompressed with gzip. A path to a `.zip` archive loads the
+//! first `.ch8` entry, or a named entry when written as `roms.zip:games/pong.ch8`. A path
+//! ending in `.8o` is Octo source, which is assembled, see `assembler::octo`. All other
+//! paths are read as plain ROM files.
+
+use std::{fs, io};
+
+use crate::assembler::octo;
+
+/// Split a path into the archive and the name of the requested entry, if any.
+pub fn split_entry(path: &str) -> (&str, Option<&str>) {
+    match path.to_ascii_lowercase().find(".zip:") {
+        Some(idx) => (&path[..idx + 4], Some(&path[idx + 5..])),
+        None => (path, None),
+    }
+}
+
+fn has_extension(path: &str, extension: &str) -> bool {
+    path.to_ascii_lowercase().ends_with(extension)
+}
+
+/// Read the ROM at `path`, decompressing it if it is stored in a `.zip` or `.gz` file and
+/// assembling it if it is Octo source.
+pub fn read_rom(path: &str) -> io::Result<Vec<u8>> {
+    let (file, entry) = split_entry(path);
+
+    if has_extension(file, ".8o") {
+        octo::assemble(&fs::read_to_string(file)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", file, e)))
+    } else if has_extension(file, ".zip") {
+        read_zip(file, entry)
+    } else if has_extension(file, ".gz") {
+        read_gzip(file)
+    } else {
+        fs::read(file)
+    }
+}
+
+#[cfg(feature = "archives")]
+fn read_zip(path: &str, entry: Option<&str>) -> io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut archive = zip::ZipArchive::new(fs::File::open(path)?)?;
+
+    let name = match entry {
+        Some(name) => name.to_string(),
+        None => (0..archive.len())
+            .filter_map(|idx| archive.by_index(idx).ok().map(|file| file.name().to_string()))
+            .find(|name| has_extension(name, ".ch8"))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No .ch8 file in archive"))?,
+    };
+
+    let mut rom = Vec::new();
+    archive.by_name(&name)?.read_to_end(&mut rom)?;
+
+    Ok(rom)
+}
+
+#[cfg(feature = "archives")]
+fn read_gzip(path: &str) -> io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut rom = Vec::new();
+    flate2::read::GzDecoder::new(fs::File::open(path)?).read_to_end(&mut rom)?;
+
+    Ok(rom)
+}
+
+#[cfg(not(feature = "archives"))]
+fn read_zip(path: &str, _entry: Option<&str>) -> io::Result<Vec<u8>> {
+    read_gzip(path)
+}
+
+#[cfg(not(feature = "archives"))]
+fn read_gzip(path: &str) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(io::ErrorKind::Other,
+        format!("Cannot open {}: built without the `archives` feature", path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_entry() {
+        assert_eq!(split_entry("roms.zip:games/pong.ch8"), ("roms.zip", Some("games/pong.ch8")));
+        assert_eq!(split_entry("ROMS.ZIP"), ("ROMS.ZIP", None));
+        assert_eq!(split_entry("pong.ch8"), ("pong.ch8", None));
+    }
+}
@@ -0,0 +1,168 @@
+//! Assembling single instructions, written the way the disassembler prints them.
+//!
+//! The mnemonics are those of Cowgod's Chip-8 technical reference, such as `LD V3, 0x1F` or
+//! `DRW V1, V2, 5`, in upper or lower case. Numbers are decimal, or hexadecimal with `0x`.
+//! `DW 0x5121` gives any opcode, like the disassembler writes opcodes it does not know.
+
+pub mod octo;
+
+use crate::debugger::parse_number;
+
+const MNEMONICS: &[&str] = &[
+    "CLS", "RET", "SCD", "SCU", "SCR", "SCL", "SYS", "JP", "CALL", "SE", "SNE", "LD", "ADD",
+    "OR", "AND", "XOR", "SUB", "SHR", "SUBN", "SHL", "RND", "DRW", "SKP", "SKNP", "AUDIO",
+    "PITCH", "DW",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operand {
+    Register(u16),
+    Number(u16),
+    I,
+    /// `[I]`, the memory I points to.
+    IndirectI,
+    DelayTimer,
+    SoundTimer,
+    Key,
+    Font,
+    Bcd,
+}
+
+impl Operand {
+    fn parse(s: &str) -> Result<Operand, String> {
+        let upper = s.to_uppercase();
+
+        let operand = match upper.as_str() {
+            "I" => Operand::I,
+            "[I]" => Operand::IndirectI,
+            "DT" => Operand::DelayTimer,
+            "ST" => Operand::SoundTimer,
+            "K" => Operand::Key,
+            "F" => Operand::Font,
+            "B" => Operand::Bcd,
+            _ if upper.starts_with('V') && upper.len() == 2 => {
+                let x = u16::from_str_radix(&upper[1..], 16)
+                    .map_err(|_| format!("Invalid register '{}'", s))?;
+
+                Operand::Register(x)
+            },
+            _ => Operand::Number(parse_number(s)?),
+        };
+
+        Ok(operand)
+    }
+}
+
+/// Whether a word is a mnemonic the assembler knows.
+pub fn is_mnemonic(word: &str) -> bool {
+    MNEMONICS.contains(&word.to_uppercase().as_str())
+}
+
+// A number that fits in the given number of bits
+fn number(value: u16, bits: u32, s: &str) -> Result<u16, String> {
+    if value >> bits != 0 {
+        return Err(format!("{} does not fit in {} bits in '{}'", value, bits, s));
+    }
+
+    Ok(value)
+}
+
+/// Assemble an instruction into its opcode.
+pub fn assemble(s: &str) -> Result<u16, String> {
+    let s = s.trim();
+    let (mnemonic, operands) = match s.find(char::is_whitespace) {
+        Some(idx) => (&s[..idx], s[idx..].trim()),
+        None => (s, ""),
+    };
+
+    let operands = if operands.is_empty() {
+        Vec::new()
+    } else {
+        operands.split(',').map(|operand| Operand::parse(operand.trim()))
+            .collect::<Result<Vec<Operand>, String>>()?
+    };
+
+    let addr = |value| number(value, 12, s);
+    let byte = |value| number(value, 8, s);
+    let nibble = |value| number(value, 4, s);
+
+    use Operand::*;
+
+    let opcode = match (mnemonic.to_uppercase().as_str(), operands.as_slice()) {
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("SCD", [Number(n)]) => 0x00C0 | nibble(*n)?,
+        ("SCU", [Number(n)]) => 0x00D0 | nibble(*n)?,
+        ("SCR", []) => 0x00FB,
+        ("SCL", []) => 0x00FC,
+        ("SYS", [Number(nnn)]) => addr(*nnn)?,
+        ("JP", [Number(nnn)]) => 0x1000 | addr(*nnn)?,
+        ("JP", [Register(0), Number(nnn)]) => 0xB000 | addr(*nnn)?,
+        ("CALL", [Number(nnn)]) => 0x2000 | addr(*nnn)?,
+        ("SE", [Register(x), Number(kk)]) => 0x3000 | x << 8 | byte(*kk)?,
+        ("SNE", [Register(x), Number(kk)]) => 0x4000 | x << 8 | byte(*kk)?,
+        ("SE", [Register(x), Register(y)]) => 0x5000 | x << 8 | y << 4,
+        ("LD", [Register(x), Number(kk)]) => 0x6000 | x << 8 | byte(*kk)?,
+        ("ADD", [Register(x), Number(kk)]) => 0x7000 | x << 8 | byte(*kk)?,
+        ("LD", [Register(x), Register(y)]) => 0x8000 | x << 8 | y << 4,
+        ("OR", [Register(x), Register(y)]) => 0x8001 | x << 8 | y << 4,
+        ("AND", [Register(x), Register(y)]) => 0x8002 | x << 8 | y << 4,
+        ("XOR", [Register(x), Register(y)]) => 0x8003 | x << 8 | y << 4,
+        ("ADD", [Register(x), Register(y)]) => 0x8004 | x << 8 | y << 4,
+        ("SUB", [Register(x), Register(y)]) => 0x8005 | x << 8 | y << 4,
+        ("SHR", [Register(x)]) => 0x8006 | x << 8,
+        ("SHR", [Register(x), Register(y)]) => 0x8006 | x << 8 | y << 4,
+        ("SUBN", [Register(x), Register(y)]) => 0x8007 | x << 8 | y << 4,
+        ("SHL", [Register(x)]) => 0x800E | x << 8,
+        ("SHL", [Register(x), Register(y)]) => 0x800E | x << 8 | y << 4,
+        ("SNE", [Register(x), Register(y)]) => 0x9000 | x << 8 | y << 4,
+        ("LD", [I, Number(nnn)]) => 0xA000 | addr(*nnn)?,
+        ("RND", [Register(x), Number(kk)]) => 0xC000 | x << 8 | byte(*kk)?,
+        ("DRW", [Register(x), Register(y), Number(n)]) => 0xD000 | x << 8 | y << 4 | nibble(*n)?,
+        ("SKP", [Register(x)]) => 0xE09E | x << 8,
+        ("SKNP", [Register(x)]) => 0xE0A1 | x << 8,
+        ("AUDIO", []) => 0xF002,
+        ("LD", [Register(x), DelayTimer]) => 0xF007 | x << 8,
+        ("LD", [Register(x), Key]) => 0xF00A | x << 8,
+        ("LD", [DelayTimer, Register(x)]) => 0xF015 | x << 8,
+        ("LD", [SoundTimer, Register(x)]) => 0xF018 | x << 8,
+        ("ADD", [I, Register(x)]) => 0xF01E | x << 8,
+        ("LD", [Font, Register(x)]) => 0xF029 | x << 8,
+        ("LD", [Bcd, Register(x)]) => 0xF033 | x << 8,
+        ("PITCH", [Register(x)]) => 0xF03A | x << 8,
+        ("LD", [IndirectI, Register(x)]) => 0xF055 | x << 8,
+        ("LD", [Register(x), IndirectI]) => 0xF065 | x << 8,
+        ("DW", [Number(opcode)]) => *opcode,
+        (upper, _) if is_mnemonic(upper) => return Err(format!("Invalid operands in '{}'", s)),
+        _ => return Err(format!("Unknown mnemonic '{}'", mnemonic)),
+    };
+
+    Ok(opcode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassembler::disassemble;
+
+    #[test]
+    fn test_assemble() {
+        assert_eq!(assemble("LD V3, 0x1F"), Ok(0x631F));
+        assert_eq!(assemble("drw v1,v2,5"), Ok(0xD125));
+        assert_eq!(assemble("  CLS "), Ok(0x00E0));
+        assert_eq!(assemble("LD [I], VA"), Ok(0xFA55));
+        assert_eq!(assemble("SHR V4"), Ok(0x8406));
+
+        assert!(assemble("LD V3, 0x100").is_err());
+        assert!(assemble("DRW V1, V2, 16").is_err());
+        assert!(assemble("LD K, V3").unwrap_err().starts_with("Invalid operands"));
+        assert!(assemble("MOV V1, V2").unwrap_err().starts_with("Unknown mnemonic"));
+    }
+
+    #[test]
+    fn test_disassembly_round_trip() {
+        for opcode in 0..=0xFFFF {
+            assert_eq!(assemble(&disassemble(opcode)), Ok(opcode), "{}", disassemble(opcode));
+        }
+    }
+}
@@ -0,0 +1,536 @@
+//! Assembling whole programs written in Octo, the assembly language most homebrew CHIP-8
+//! games are written in, so `.8o` source files can be run without assembling them first.
+//!
+//! The statements of Octo that this interpreter can execute are supported, such as
+//! `v3 := 0x1F`, `i := sprite`, `sprite v1 v2 5`, `if v0 == 3 then v1 += 1` and a bare
+//! label to call a subroutine, together with:
+//!
+//! - labels, written `: name`, which may be used before they are defined,
+//! - `:const name value` and `:alias name v4`,
+//! - `if ... begin ... else ... end` blocks,
+//! - `loop ... again` loops, left with `while` conditions,
+//! - bytes of data as bare numbers or with `:byte`, and `:org` to skip to an address.
+//!
+//! The program starts at `main`. When code or data comes before it, a jump to it is put in
+//! front of the program like Octo does. SCHIP and XO-CHIP instructions, the comparison
+//! pseudo-ops such as `<` and directives like `:macro` give an error instead.
+
+use std::collections::HashMap;
+
+use crate::PROGRAM_START;
+
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+/// The words of the source with their line numbers, without the comments.
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    source.lines().enumerate().flat_map(|(idx, line)| {
+        let code = line.find('#').map_or(line, |comment| &line[..comment]);
+        code.split_whitespace().map(move |text| Token { text, line: idx + 1 })
+    }).collect()
+}
+
+fn parse_literal(s: &str) -> Option<i32> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, s),
+    };
+
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i32::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i32::from_str_radix(binary, 2).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+/// The register a word names, such as `v3` or `VA`.
+fn parse_register(s: &str) -> Option<u16> {
+    match s.as_bytes() {
+        [b'v', digit] | [b'V', digit] => (*digit as char).to_digit(16).map(|x| x as u16),
+        _ => None,
+    }
+}
+
+/// A condition of `if` or `while`.
+enum Condition {
+    Byte { x: u16, value: u16, equal: bool },
+    Registers { x: u16, y: u16, equal: bool },
+    Key { x: u16, pressed: bool },
+}
+
+impl Condition {
+    /// The instruction that skips the next one when the condition does not hold.
+    fn skip_unless(&self) -> u16 {
+        match *self {
+            Condition::Byte { x, value, equal } => {
+                (if equal { 0x4000 } else { 0x3000 }) | x << 8 | value
+            },
+            Condition::Registers { x, y, equal } => {
+                (if equal { 0x9000 } else { 0x5000 }) | x << 8 | y << 4
+            },
+            Condition::Key { x, pressed } => (if pressed { 0xE0A1 } else { 0xE09E }) | x << 8,
+        }
+    }
+
+    fn negate(self) -> Condition {
+        match self {
+            Condition::Byte { x, value, equal } => Condition::Byte { x, value, equal: !equal },
+            Condition::Registers { x, y, equal } => {
+                Condition::Registers { x, y, equal: !equal }
+            },
+            Condition::Key { x, pressed } => Condition::Key { x, pressed: !pressed },
+        }
+    }
+}
+
+/// A block of control flow that is still open.
+enum Block {
+    /// A loop from its first instruction, with the jumps out of it of its `while`s.
+    Loop { start: u16, exits: Vec<usize> },
+    /// An `if ... begin` or `else`, with the jump past it.
+    Conditional { jump: usize, has_else: bool },
+}
+
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    position: usize,
+    rom: Vec<u8>,
+    labels: HashMap<&'a str, u16>,
+    constants: HashMap<&'a str, i32>,
+    aliases: HashMap<&'a str, u16>,
+    // Instructions that take the address of a label that was not defined yet
+    fixups: Vec<(usize, &'a str, usize)>,
+    blocks: Vec<Block>,
+}
+
+impl<'a> Assembler<'a> {
+    fn address(&self) -> u16 {
+        (PROGRAM_START + self.rom.len()) as u16
+    }
+
+    fn line(&self) -> usize {
+        let idx = self.position.min(self.tokens.len()).saturating_sub(1);
+        self.tokens.get(idx).map_or(0, |token| token.line)
+    }
+
+    fn next(&mut self) -> Result<&'a str, String> {
+        let token = self.tokens.get(self.position).map(|token| token.text)
+            .ok_or_else(|| String::from("The program ends too early"))?;
+        self.position += 1;
+
+        Ok(token)
+    }
+
+    /// The next word, without taking it.
+    fn peek(&self) -> &'a str {
+        self.tokens.get(self.position).map_or("", |token| token.text)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        match self.next()? {
+            word if word == expected => Ok(()),
+            word => Err(format!("Expected '{}' instead of '{}'", expected, word)),
+        }
+    }
+
+    fn emit(&mut self, opcode: u16) {
+        self.rom.extend_from_slice(&opcode.to_be_bytes());
+    }
+
+    fn register(&self, word: &str) -> Option<u16> {
+        parse_register(word).or_else(|| self.aliases.get(word).copied())
+    }
+
+    fn expect_register(&mut self) -> Result<u16, String> {
+        let word = self.next()?;
+        self.register(word).ok_or_else(|| format!("Expected a register instead of '{}'", word))
+    }
+
+    fn value(&self, word: &str) -> Option<i32> {
+        parse_literal(word).or_else(|| self.constants.get(word).copied())
+    }
+
+    /// A number that fits in `bits` bits, where negative bytes count down from 256.
+    fn number(&mut self, bits: u32) -> Result<u16, String> {
+        let word = self.next()?;
+        let value = self.value(word)
+            .ok_or_else(|| format!("Expected a number instead of '{}'", word))?;
+        let limit = 1 << bits;
+
+        match value {
+            _ if value >= limit || value < -(limit / 2) => {
+                Err(format!("{} does not fit in {} bits", word, bits))
+            },
+            _ if value < 0 => Ok((value + limit) as u16),
+            _ => Ok(value as u16),
+        }
+    }
+
+    /// An instruction with an address, which may be a label that is defined later on.
+    fn emit_address(&mut self, opcode: u16) -> Result<(), String> {
+        let word = self.next()?;
+
+        let address = self.value(word).or_else(|| self.labels.get(word).map(|&a| a as i32));
+        let address = match address {
+            Some(address) if (0..0x1000).contains(&address) => address as u16,
+            Some(_) => return Err(format!("Address {} does not fit in 12 bits", word)),
+            None => {
+                self.fixups.push((self.rom.len(), word, self.line()));
+                0
+            },
+        };
+
+        self.emit(opcode | address);
+        Ok(())
+    }
+
+    /// Point the jump at `offset` in the program at `address`.
+    fn patch(&mut self, offset: usize, address: u16) {
+        self.rom[offset] |= (address >> 8) as u8;
+        self.rom[offset + 1] = address as u8;
+    }
+
+    fn condition(&mut self) -> Result<Condition, String> {
+        let x = self.expect_register()?;
+
+        let condition = match self.next()? {
+            "key" => Condition::Key { x, pressed: true },
+            "-key" => Condition::Key { x, pressed: false },
+            comparison @ ("==" | "!=") => {
+                let equal = comparison == "==";
+
+                match self.register(self.peek()) {
+                    Some(y) => {
+                        self.position += 1;
+                        Condition::Registers { x, y, equal }
+                    },
+                    None => Condition::Byte { x, value: self.number(8)?, equal },
+                }
+            },
+            other => return Err(format!("Unsupported comparison '{}'", other)),
+        };
+
+        Ok(condition)
+    }
+
+    /// The statements that start with a register, such as `v3 += v4`.
+    fn register_statement(&mut self, x: u16) -> Result<(), String> {
+        let operator = self.next()?;
+        let word = self.peek();
+
+        if let Some(y) = self.register(word) {
+            self.position += 1;
+
+            let low = match operator {
+                ":=" => 0x0,
+                "|=" => 0x1,
+                "&=" => 0x2,
+                "^=" => 0x3,
+                "+=" => 0x4,
+                "-=" => 0x5,
+                ">>=" => 0x6,
+                "=-" => 0x7,
+                "<<=" => 0xE,
+                _ => return Err(format!("Unsupported operator '{}'", operator)),
+            };
+            self.emit(0x8000 | x << 8 | y << 4 | low);
+
+            return Ok(());
+        }
+
+        let opcode = match (operator, word) {
+            (":=", "delay") | (":=", "key") | (":=", "random") => {
+                self.position += 1;
+
+                match word {
+                    "delay" => 0xF007 | x << 8,
+                    "key" => 0xF00A | x << 8,
+                    _ => 0xC000 | x << 8 | self.number(8)?,
+                }
+            },
+            (":=", _) => 0x6000 | x << 8 | self.number(8)?,
+            ("+=", _) => 0x7000 | x << 8 | self.number(8)?,
+            // Subtracting is adding the two's complement
+            ("-=", _) => 0x7000 | x << 8 | ((0x100 - self.number(8)?) & 0xFF),
+            _ => return Err(format!("Unsupported operator '{}' for '{}'", operator, word)),
+        };
+        self.emit(opcode);
+
+        Ok(())
+    }
+
+    fn statement(&mut self, word: &'a str) -> Result<(), String> {
+        match word {
+            ":" => {
+                let name = self.next()?;
+                if self.labels.insert(name, self.address()).is_some() {
+                    return Err(format!("Label '{}' is defined twice", name));
+                }
+            },
+            ":const" => {
+                let name = self.next()?;
+                let word = self.next()?;
+                let value = self.value(word)
+                    .ok_or_else(|| format!("Expected a number instead of '{}'", word))?;
+                self.constants.insert(name, value);
+            },
+            ":alias" => {
+                let name = self.next()?;
+                let x = self.expect_register()?;
+                self.aliases.insert(name, x);
+            },
+            ":byte" => {
+                let byte = self.number(8)?;
+                self.rom.push(byte as u8);
+            },
+            ":org" => {
+                let address = self.number(12)?;
+                if address < self.address() {
+                    return Err(format!("Cannot go back to address {:#05X}", address));
+                }
+                self.rom.resize(address as usize - PROGRAM_START, 0);
+            },
+            ":call" => self.emit_address(0x2000)?,
+            "return" | ";" => self.emit(0x00EE),
+            "clear" => self.emit(0x00E0),
+            "native" => self.emit_address(0x0000)?,
+            "jump" => self.emit_address(0x1000)?,
+            "jump0" => self.emit_address(0xB000)?,
+            "bcd" => {
+                let x = self.expect_register()?;
+                self.emit(0xF033 | x << 8);
+            },
+            "save" => {
+                let x = self.expect_register()?;
+                self.emit(0xF055 | x << 8);
+            },
+            "load" => {
+                let x = self.expect_register()?;
+                self.emit(0xF065 | x << 8);
+            },
+            "sprite" => {
+                let x = self.expect_register()?;
+                let y = self.expect_register()?;
+                let n = self.number(4)?;
+                self.emit(0xD000 | x << 8 | y << 4 | n);
+            },
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let x = self.expect_register()?;
+                self.emit((if word == "delay" { 0xF015 } else { 0xF018 }) | x << 8);
+            },
+            "i" => match self.next()? {
+                ":=" if self.peek() == "hex" => {
+                    self.position += 1;
+                    let x = self.expect_register()?;
+                    self.emit(0xF029 | x << 8);
+                },
+                ":=" => self.emit_address(0xA000)?,
+                "+=" => {
+                    let x = self.expect_register()?;
+                    self.emit(0xF01E | x << 8);
+                },
+                other => return Err(format!("Unsupported operator '{}' for i", other)),
+            },
+            "if" => {
+                let condition = self.condition()?;
+
+                match self.next()? {
+                    "then" => self.emit(condition.skip_unless()),
+                    "begin" => {
+                        self.emit(condition.negate().skip_unless());
+                        let jump = self.rom.len();
+                        self.blocks.push(Block::Conditional { jump, has_else: false });
+                        self.emit(0x1000);
+                    },
+                    other => {
+                        return Err(format!("Expected 'then' or 'begin' instead of '{}'", other));
+                    },
+                }
+            },
+            "else" => match self.blocks.pop() {
+                Some(Block::Conditional { jump, has_else: false }) => {
+                    let end = self.rom.len();
+                    self.blocks.push(Block::Conditional { jump: end, has_else: true });
+                    self.emit(0x1000);
+                    let address = self.address();
+                    self.patch(jump, address);
+                },
+                _ => return Err(String::from("'else' without 'if ... begin'")),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::Conditional { jump, .. }) => {
+                    let address = self.address();
+                    self.patch(jump, address);
+                },
+                _ => return Err(String::from("'end' without 'if ... begin'")),
+            },
+            "loop" => self.blocks.push(Block::Loop { start: self.address(), exits: Vec::new() }),
+            "while" => {
+                let condition = self.condition()?;
+                self.emit(condition.negate().skip_unless());
+
+                let jump = self.rom.len();
+                let innermost = self.blocks.iter_mut().rev()
+                    .find(|block| matches!(block, Block::Loop { .. }));
+                match innermost {
+                    Some(Block::Loop { exits, .. }) => exits.push(jump),
+                    _ => return Err(String::from("'while' outside of a loop")),
+                }
+                self.emit(0x1000);
+            },
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop { start, exits }) => {
+                    self.emit(0x1000 | start);
+                    let address = self.address();
+                    for jump in exits {
+                        self.patch(jump, address);
+                    }
+                },
+                _ => return Err(String::from("'again' without 'loop'")),
+            },
+            _ if word.starts_with(':') => {
+                return Err(format!("Octo directive '{}' is not supported", word));
+            },
+            _ => {
+                if let Some(x) = self.register(word) {
+                    self.register_statement(x)?;
+                } else if self.value(word).is_some() {
+                    self.position -= 1;
+                    let byte = self.number(8)?;
+                    self.rom.push(byte as u8);
+                } else {
+                    // A bare label calls the subroutine
+                    self.position -= 1;
+                    self.emit_address(0x2000)?;
+                }
+            },
+        }
+
+        Ok(())
+    }
+}
+
+/// Assemble an Octo program into a ROM that is loaded at the program start.
+pub fn assemble(source: &str) -> Result<Vec<u8>, String> {
+    let mut assembler = Assembler {
+        tokens: tokenize(source),
+        position: 0,
+        rom: Vec::new(),
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        fixups: Vec::new(),
+        blocks: Vec::new(),
+    };
+
+    // Whether main comes before the first byte of the program, or a jump to it was put first
+    let mut started = false;
+
+    while assembler.position < assembler.tokens.len() {
+        let word = assembler.next()?;
+
+        if !started && word == ":" && assembler.peek() == "main" {
+            started = true;
+        } else if !started && word != ":const" && word != ":alias" {
+            assembler.fixups.push((0, "main", 0));
+            assembler.emit(0x1000);
+            started = true;
+        }
+        assembler.statement(word).map_err(|e| format!("Line {}: {}", assembler.line(), e))?;
+    }
+
+    if !assembler.blocks.is_empty() {
+        return Err(String::from("The program ends inside a loop or an 'if ... begin'"));
+    }
+    if !assembler.labels.contains_key("main") {
+        return Err(String::from("The program has no 'main' label to start at"));
+    }
+
+    for (offset, label, line) in std::mem::take(&mut assembler.fixups) {
+        let address = *assembler.labels.get(label)
+            .ok_or_else(|| format!("Line {}: Unknown label '{}'", line, label))?;
+        assembler.patch(offset, address);
+    }
+
+    Ok(assembler.rom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opcodes(rom: &[u8]) -> Vec<u16> {
+        rom.chunks(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect()
+    }
+
+    #[test]
+    fn test_statements() {
+        let rom = assemble("
+            : main          # The program starts here
+                clear
+                v3 := 0x1F
+                v3 += -1
+                v4 -= 2
+                va ^= v3
+                i := sprite
+                sprite v3 va 5
+                if v3 != 0 then v3 := random 0b111
+                v0 := key
+                delay := v0
+                draw
+            : draw
+                i := hex v0
+                ;
+            : sprite
+                0x3C 0x42
+        ").unwrap();
+
+        assert_eq!(opcodes(&rom), vec!(0x00E0, 0x631F, 0x73FF, 0x74FE, 0x8A33, 0xA21C, 0xD3A5,
+            0x3300, 0xC307, 0xF00A, 0xF015, 0x2218, 0xF029, 0x00EE, 0x3C42));
+    }
+
+    #[test]
+    fn test_blocks() {
+        let rom = assemble("
+            :const LIMIT 8
+            :alias counter v1
+            : main
+                loop
+                    counter += 1
+                    while counter != LIMIT
+                    if v2 key begin
+                        v0 := 1
+                    else
+                        v0 := 2
+                    end
+                again
+        ").unwrap();
+
+        assert_eq!(opcodes(&rom), vec!(0x7101, 0x4108, 0x1212, 0xE29E, 0x120E, 0x6001, 0x1210,
+            0x6002, 0x1200));
+    }
+
+    #[test]
+    fn test_jump_to_main() {
+        let rom = assemble(": data 0x01 0x02\n: main jump main").unwrap();
+        assert_eq!(opcodes(&rom), vec!(0x1204, 0x0102, 0x1204));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(assemble(": main\n  v0 := 256"),
+            Err(String::from("Line 2: 256 does not fit in 8 bits")));
+        assert_eq!(assemble(": main\n  i := nowhere"),
+            Err(String::from("Line 2: Unknown label 'nowhere'")));
+        assert!(assemble(": main loop v0 += 1").is_err());
+        assert!(assemble(": main :macro dup x { x x }").is_err());
+        assert!(assemble(": main if v0 < 3 then v0 := 1").is_err());
+    }
+}
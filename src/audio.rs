@@ -0,0 +1,363 @@
+//! Sound synthesis for the buzzer.
+//!
+//! Standard CHIP-8 ROMs get a fixed tone in the chosen waveform. XO-CHIP ROMs that load an
+//! audio pattern instead hear that 128-bit pattern, played at the programmed pitch.
+//!
+//! The machine plays sound through an `AudioSink`, so the frontend decides where it goes:
+//! the synthesiser on an audio device, nowhere, or a recording that tests can inspect.
+
+use std::{str::FromStr, sync::{Arc, Mutex}};
+#[cfg(feature = "audio")]
+use std::f32::consts::PI;
+
+use crate::Chip8;
+
+#[cfg(feature = "audio")]
+const BEEP_HZ: f32 = 440.0;
+#[cfg(feature = "audio")]
+const VOLUME: f32 = 0.2;
+// Pattern playback rate at the default pitch of 64, in bits per second
+#[cfg(feature = "audio")]
+const PATTERN_RATE: f32 = 4000.0;
+#[cfg(feature = "audio")]
+const PATTERN_BITS: f32 = 128.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sine,
+    Noise,
+}
+
+impl FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Waveform, String> {
+        match s {
+            "square" => Ok(Waveform::Square),
+            "triangle" => Ok(Waveform::Triangle),
+            "sine" => Ok(Waveform::Sine),
+            "noise" => Ok(Waveform::Noise),
+            _ => Err(format!("Unknown waveform '{}'", s)),
+        }
+    }
+}
+
+/// Receives what a ROM plays.
+pub trait AudioSink: Send {
+    /// The sound timer started running.
+    fn start_tone(&mut self);
+    /// The sound timer reached zero.
+    fn stop_tone(&mut self);
+    /// The XO-CHIP audio pattern or pitch changed. `None` goes back to the plain tone, for
+    /// example after a reset.
+    fn queue_pattern(&mut self, pattern: Option<[u8; 16]>, pitch: u8);
+}
+
+/// Play the sound of a machine on a sink, replacing its sound callbacks.
+pub fn attach<S: AudioSink + 'static>(sink: Arc<Mutex<S>>, chip8: &mut Chip8) {
+    let (start, stop) = (sink.clone(), sink.clone());
+
+    chip8.on_sound_start(move || start.lock().unwrap().start_tone());
+    chip8.on_sound_stop(move || stop.lock().unwrap().stop_tone());
+    let pattern_sink = sink.clone();
+    chip8.on_audio_pattern(move |pattern, pitch| {
+        pattern_sink.lock().unwrap().queue_pattern(pattern, pitch);
+    });
+
+    // Silence a tone that is still playing, so the output does not stop in the middle of it
+    chip8.on_shutdown(move |chip8, _| {
+        if let (true, Ok(mut sink)) = (chip8.timers.sounding(), sink.lock()) {
+            sink.stop_tone();
+        }
+    });
+}
+
+/// Drops all sound, for headless runs.
+pub struct NullSink;
+
+impl AudioSink for NullSink {
+    fn start_tone(&mut self) {}
+    fn stop_tone(&mut self) {}
+    fn queue_pattern(&mut self, _pattern: Option<[u8; 16]>, _pitch: u8) {}
+}
+
+/// Prints BEEP whenever the tone starts, when there is no audio output.
+pub struct PrintSink;
+
+impl AudioSink for PrintSink {
+    fn start_tone(&mut self) {
+        println!("BEEP");
+    }
+
+    fn stop_tone(&mut self) {}
+    fn queue_pattern(&mut self, _pattern: Option<[u8; 16]>, _pitch: u8) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AudioEvent {
+    ToneStarted,
+    ToneStopped,
+    Pattern(Option<[u8; 16]>, u8),
+}
+
+/// Keeps everything played in order, so tests can check what a ROM tried to play.
+#[derive(Debug, Default)]
+pub struct RecordingSink {
+    pub events: Vec<AudioEvent>,
+}
+
+impl RecordingSink {
+    /// Whether the tone was started at any point.
+    pub fn played_tone(&self) -> bool {
+        self.events.contains(&AudioEvent::ToneStarted)
+    }
+}
+
+impl AudioSink for RecordingSink {
+    fn start_tone(&mut self) {
+        self.events.push(AudioEvent::ToneStarted);
+    }
+
+    fn stop_tone(&mut self) {
+        self.events.push(AudioEvent::ToneStopped);
+    }
+
+    fn queue_pattern(&mut self, pattern: Option<[u8; 16]>, pitch: u8) {
+        self.events.push(AudioEvent::Pattern(pattern, pitch));
+    }
+}
+
+/// Generates the buzzer samples, shared between the emulator and the audio thread.
+#[cfg(feature = "audio")]
+pub struct Synth {
+    pub waveform: Waveform,
+    pub playing: bool,
+    /// The XO-CHIP audio pattern, played instead of the waveform once a ROM loads one.
+    pub pattern: Option<[u8; 16]>,
+    pub pitch: u8,
+
+    // Position in the current period, from 0 to 1 for the tone and 0 to 128 for patterns
+    phase: f32,
+    noise: u32,
+}
+
+#[cfg(feature = "audio")]
+impl Synth {
+    pub fn new(waveform: Waveform) -> Synth {
+        Synth {
+            waveform,
+            playing: false,
+            pattern: None,
+            pitch: 64,
+            phase: 0.0,
+            noise: 0x1234_5678,
+        }
+    }
+
+    /// Playback rate of the audio pattern in bits per second.
+    pub fn pattern_rate(&self) -> f32 {
+        PATTERN_RATE * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+
+    /// Produce the next sample at the given sample rate.
+    pub fn sample(&mut self, sample_rate: u32) -> f32 {
+        if !self.playing {
+            return 0.0;
+        }
+
+        if let Some(pattern) = self.pattern {
+            let bit = self.phase as usize;
+            let high = pattern[bit / 8] >> (7 - bit % 8) & 1 == 1;
+
+            self.phase = (self.phase + self.pattern_rate() / sample_rate as f32) % PATTERN_BITS;
+
+            return if high { VOLUME } else { -VOLUME };
+        }
+
+        let phase = self.phase;
+        self.phase = (self.phase + BEEP_HZ / sample_rate as f32) % 1.0;
+
+        let value = match self.waveform {
+            Waveform::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+            Waveform::Triangle => 4.0 * (phase - 0.5).abs() - 1.0,
+            Waveform::Sine => (2.0 * PI * phase).sin(),
+            Waveform::Noise => {
+                // Xorshift, which is plenty random for a hiss
+                self.noise ^= self.noise << 13;
+                self.noise ^= self.noise >> 17;
+                self.noise ^= self.noise << 5;
+
+                self.noise as f32 / std::u32::MAX as f32 * 2.0 - 1.0
+            },
+        };
+
+        value * VOLUME
+    }
+}
+
+#[cfg(feature = "audio")]
+impl AudioSink for Synth {
+    fn start_tone(&mut self) {
+        self.playing = true;
+    }
+
+    fn stop_tone(&mut self) {
+        self.playing = false;
+    }
+
+    fn queue_pattern(&mut self, pattern: Option<[u8; 16]>, pitch: u8) {
+        self.pattern = pattern;
+        self.pitch = pitch;
+    }
+}
+
+/// Plays the synthesiser on the default output device.
+#[cfg(feature = "audio")]
+pub struct AudioOutput {
+    pub synth: Arc<Mutex<Synth>>,
+    _stream: cpal::Stream,
+}
+
+#[cfg(feature = "audio")]
+impl AudioOutput {
+    /// Open the default output device, buffering `latency` milliseconds of sound if given.
+    pub fn new(waveform: Waveform, latency: Option<u32>) -> Result<AudioOutput, String> {
+        use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or("No audio output device")?;
+        let mut config: cpal::StreamConfig = device.default_output_config()
+            .map_err(|e| e.to_string())?
+            .into();
+        if let Some(ms) = latency {
+            let frames = (config.sample_rate.0 * ms / 1000).max(1);
+            config.buffer_size = cpal::BufferSize::Fixed(frames);
+        }
+
+        let sample_rate = config.sample_rate.0;
+        let channels = config.channels as usize;
+        let synth = Arc::new(Mutex::new(Synth::new(waveform)));
+        let stream_synth = synth.clone();
+
+        let stream = device
+            .build_output_stream(
+                &config,
+                move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                    let mut synth = stream_synth.lock().unwrap();
+
+                    for frame in data.chunks_mut(channels) {
+                        let sample = synth.sample(sample_rate);
+                        frame.iter_mut().for_each(|channel| *channel = sample);
+                    }
+                },
+                |e| println!("Audio error: {}", e))
+            .map_err(|e| e.to_string())?;
+
+        stream.play().map_err(|e| e.to_string())?;
+
+        Ok(AudioOutput { synth, _stream: stream })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shutdown::ShutdownReason;
+
+    #[cfg(feature = "audio")]
+    #[test]
+    fn test_silent_when_stopped() {
+        let mut synth = Synth::new(Waveform::Sine);
+
+        assert_eq!(synth.sample(44100), 0.0);
+    }
+
+    #[cfg(feature = "audio")]
+    #[test]
+    fn test_square_wave() {
+        let mut synth = Synth::new(Waveform::Square);
+        synth.playing = true;
+
+        // Four samples per period of the 440 Hz tone
+        assert_eq!(synth.sample(1760), VOLUME);
+        assert_eq!(synth.sample(1760), VOLUME);
+        assert_eq!(synth.sample(1760), -VOLUME);
+    }
+
+    #[test]
+    fn test_recording_sink() {
+        let sink = Arc::new(Mutex::new(RecordingSink::default()));
+        let mut chip8 = Chip8::new();
+        attach(sink.clone(), &mut chip8);
+
+        // LD I, 0x208; LD PATTERN; LD V0, 2; LD ST, V0
+        chip8.load_bytes(&[0xA2, 0x08, 0xF0, 0x02, 0x60, 0x02, 0xF0, 0x18]);
+        chip8.memory[0x208..0x218].copy_from_slice(&[0xF0; 16]);
+        for _ in 0..4 {
+            chip8.cycle();
+        }
+        for _ in 0..3 {
+            chip8.update_timers();
+        }
+
+        assert_eq!(sink.lock().unwrap().events, vec!(
+            AudioEvent::Pattern(None, 64),
+            AudioEvent::Pattern(Some([0xF0; 16]), 64),
+            AudioEvent::ToneStarted,
+            AudioEvent::ToneStopped,
+        ));
+    }
+
+    #[test]
+    fn test_tone_lasts_the_ticks_loaded() {
+        let sink = Arc::new(Mutex::new(RecordingSink::default()));
+        let mut chip8 = Chip8::new();
+        attach(sink.clone(), &mut chip8);
+
+        // LD V0, 3; LD ST, V0; JP 0x204
+        chip8.load_bytes(&[0x60, 0x03, 0xF0, 0x18, 0x12, 0x04]);
+        chip8.cycle();
+        chip8.cycle();
+        assert_eq!(sink.lock().unwrap().events.last(), Some(&AudioEvent::Pattern(None, 64)));
+
+        // The tick each event was played at
+        let mut played = Vec::new();
+        for tick in 1..=6 {
+            chip8.cycle();
+            chip8.update_timers();
+            let events = &mut sink.lock().unwrap().events;
+            played.extend(events.drain(1..).map(|event| (tick, event)));
+        }
+
+        assert_eq!(played, vec!((1, AudioEvent::ToneStarted), (4, AudioEvent::ToneStopped)));
+        assert_eq!(chip8.timers.sound, 0);
+    }
+
+    #[test]
+    fn test_tone_stops_on_shutdown() {
+        let sink = Arc::new(Mutex::new(RecordingSink::default()));
+        let mut chip8 = Chip8::new();
+        attach(sink.clone(), &mut chip8);
+        chip8.timers.sound = 30;
+
+        chip8.shutdown(ShutdownReason::Interrupted);
+        assert_eq!(sink.lock().unwrap().events.last(), Some(&AudioEvent::ToneStopped));
+    }
+
+    #[cfg(feature = "audio")]
+    #[test]
+    fn test_pattern_playback() {
+        let mut synth = Synth::new(Waveform::Square);
+        synth.playing = true;
+        synth.pattern = Some([0b1010_0000; 16]);
+
+        assert_eq!(synth.pattern_rate(), PATTERN_RATE);
+
+        // One sample per bit at the default pitch
+        let samples: Vec<f32> = (0..4).map(|_| synth.sample(4000)).collect();
+        assert_eq!(samples, vec!(VOLUME, -VOLUME, VOLUME, -VOLUME));
+    }
+}
@@ -0,0 +1,198 @@
+use std::{fs, time::Duration};
+
+use crate::{Chip8, MEMORY, State};
+use crate::emulator::Emulator;
+use crate::timers::ManualClock;
+use crate::trace::registers_json;
+
+const USAGE: &str = "Usage: chip8 run rom.ch8 [--cycles N] [--exit-on-halt] \
+    [--dump-display out.pgm] [--dump-registers out.json] [--cpu-hz N] [--timer-hz N] \
+    [--exit-code-address ADDR]";
+
+/// The SCHIP EXIT instruction, which ends a run. Test ROMs leave their result in V0, or at
+/// the exit code address, to become the exit status of the emulator.
+pub const EXIT_OPCODE: u16 = 0x00FD;
+
+/// Options of the `run` subcommand, which runs a ROM without opening a window.
+#[derive(Debug, PartialEq)]
+pub struct BatchOptions {
+    pub rom: String,
+    pub cycles: u64,
+    pub exit_on_halt: bool,
+    pub dump_display: Option<String>,
+    pub dump_registers: Option<String>,
+    pub cpu_hz: u32,
+    pub timer_hz: u32,
+    /// Where the ROM leaves its result, in hex.
+    pub exit_code_address: Option<u16>,
+}
+
+impl BatchOptions {
+    pub fn parse(args: &[String]) -> Result<BatchOptions, String> {
+        let mut rom = None;
+        let mut options = BatchOptions {
+            rom: String::new(),
+            cycles: 100_000,
+            exit_on_halt: false,
+            dump_display: None,
+            dump_registers: None,
+            cpu_hz: 500,
+            timer_hz: 60,
+            exit_code_address: None,
+        };
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            if arg == "--exit-on-halt" {
+                options.exit_on_halt = true;
+                continue;
+            }
+
+            if !arg.starts_with("--") {
+                rom = Some(arg.clone());
+                continue;
+            }
+
+            let value = args.next().ok_or(format!("{} requires a value", arg))?;
+            let number = || value.parse().map_err(|_| format!("Invalid number '{}'", value));
+
+            match arg.as_str() {
+                "--cycles" => options.cycles = number()?,
+                "--cpu-hz" => options.cpu_hz = number()? as u32,
+                "--timer-hz" => options.timer_hz = number()? as u32,
+                "--dump-display" => options.dump_display = Some(value.clone()),
+                "--dump-registers" => options.dump_registers = Some(value.clone()),
+                "--exit-code-address" => options.exit_code_address = Some(
+                    u16::from_str_radix(value.trim_start_matches("0x"), 16).ok()
+                        .filter(|&address| (address as usize) < MEMORY)
+                        .ok_or(format!("Invalid address '{}'", value))?),
+                _ => return Err(format!("Unknown argument '{}'\n{}", arg, USAGE)),
+            }
+        }
+
+        if options.cpu_hz == 0 || options.timer_hz == 0 {
+            return Err(String::from("Frequencies must be positive"));
+        }
+
+        options.rom = rom.ok_or(USAGE)?;
+
+        Ok(options)
+    }
+}
+
+/// Whether the machine can make no further progress on its own: it is waiting for a key,
+/// or the instruction at `pc` is a jump to itself.
+pub fn is_halted(chip8: &Chip8, pc: u16, opcode: u16) -> bool {
+    let jumps_to_self = opcode & 0xF000 == 0x1000 && opcode & 0x0FFF == pc;
+
+    jumps_to_self || chip8.state != State::Running
+}
+
+/// Run the machine for up to `options.cycles` cycles or until it reaches `EXIT_OPCODE`,
+/// returning the number of cycles run.
+///
+/// The timers are driven by virtual time derived from the CPU frequency, so results do
+/// not depend on the speed of the host.
+pub fn run(chip8: &mut Chip8, options: &BatchOptions) -> u64 {
+    let cycle_time = Duration::from_secs(1) / options.cpu_hz;
+    let clock = ManualClock::new();
+    chip8.timers.set_clock(options.timer_hz, Box::new(clock.clone()));
+
+    for cycle in 0..options.cycles {
+        let pc = chip8.pc;
+        let opcode = chip8.fetch();
+
+        if opcode == EXIT_OPCODE && chip8.state == State::Running {
+            return cycle;
+        }
+
+        chip8.cycle();
+
+        clock.advance(cycle_time);
+        chip8.run_timers();
+
+        if options.exit_on_halt && is_halted(chip8, pc, opcode) {
+            return cycle + 1;
+        }
+    }
+
+    options.cycles
+}
+
+/// The result a ROM left for the exit status: the byte at the exit code address, or V0
+/// when it stopped at `EXIT_OPCODE`.
+pub fn exit_code(chip8: &Chip8, options: &BatchOptions) -> Option<u8> {
+    match options.exit_code_address {
+        Some(address) => Some(chip8.memory[address as usize]),
+        None if chip8.try_fetch() == Ok(EXIT_OPCODE) => Some(chip8.registers[0]),
+        None => None,
+    }
+}
+
+/// The `run` subcommand, returning the exit status of the emulator.
+pub fn run_command(args: &[String]) -> Result<i32, String> {
+    let options = BatchOptions::parse(args)?;
+
+    let mut chip8 = Emulator::builder()
+        .cpu_hz(options.cpu_hz)
+        .timer_hz(options.timer_hz)
+        .rom_file(&options.rom)
+        .build()?
+        .chip8;
+
+    let cycles = run(&mut chip8, &options);
+    println!("Ran {} cycles, stopped at {:#05X}", cycles, chip8.pc);
+
+    if let Some(path) = &options.dump_display {
+        fs::write(path, chip8.display.to_buffer().to_pgm())
+            .map_err(|e| format!("Could not write {}: {}", path, e))?;
+    }
+
+    if let Some(path) = &options.dump_registers {
+        fs::write(path, registers_json(&chip8))
+            .map_err(|e| format!("Could not write {}: {}", path, e))?;
+    }
+
+    let code = exit_code(&chip8, &options);
+    if let Some(code) = code {
+        println!("Exit code {}", code);
+    }
+
+    Ok(code.map_or(0, i32::from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_exits_on_halt() {
+        // LD V0, 0x05; JP 0x202
+        let mut chip8 = Chip8::new();
+        chip8.load_bytes(&[0x60, 0x05, 0x12, 0x02]);
+
+        let args: Vec<String> = vec!("rom.ch8", "--exit-on-halt")
+            .into_iter().map(String::from).collect();
+        let options = BatchOptions::parse(&args).unwrap();
+
+        assert_eq!(run(&mut chip8, &options), 2);
+        assert_eq!(chip8.registers[0], 5);
+    }
+
+    #[test]
+    fn test_exit_code() {
+        // LD V0, 3; EXIT; LD V0, 4
+        let mut chip8 = Chip8::new();
+        chip8.load_bytes(&[0x60, 0x03, 0x00, 0xFD, 0x60, 0x04]);
+
+        let args: Vec<String> = vec!("rom.ch8").into_iter().map(String::from).collect();
+        let mut options = BatchOptions::parse(&args).unwrap();
+
+        assert_eq!(run(&mut chip8, &options), 1);
+        assert_eq!(exit_code(&chip8, &options), Some(3));
+
+        chip8.memory[0x300] = 0xFF;
+        options.exit_code_address = Some(0x300);
+        assert_eq!(exit_code(&chip8, &options), Some(0xFF));
+    }
+}
@@ -0,0 +1,205 @@
+//! Synthetic ROMs that stress one part of an interpreter, for timing the emulator and
+//! comparing it against others.
+//!
+//! ```text
+//! chip8 gen-bench --kind drw --len 2000 -o drw.ch8
+//! ```
+//!
+//! A ROM executes `len` instructions of its kind and then halts in a loop of jumps:
+//!
+//! - `alu`: the arithmetic and logic instructions of 7xkk and 8xyn, feeding each other.
+//! - `drw`: sprites drawn all over the display, wrapping around its edges.
+//! - `bcd`: Fx33 on registers counting up, into the same three bytes.
+//!
+//! The instructions are unrolled as far as needed to run them in a loop of at most 255
+//! times, counted down in VD, followed by those left over. The jumps land on a `LD VE, VE`,
+//! which does nothing, so the ROMs behave the same whether a jump lands on its target or on
+//! the instruction after it.
+
+use std::{fs, str::FromStr};
+
+use crate::{MEMORY, PROGRAM_START};
+
+const USAGE: &str = "Usage: chip8 gen-bench --kind alu|drw|bcd --len N -o bench.ch8";
+// A jump target that does nothing: LD VE, VE
+const PAD: u16 = 0x8EE0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BenchKind {
+    Alu,
+    Drw,
+    Bcd,
+}
+
+impl FromStr for BenchKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<BenchKind, String> {
+        match s {
+            "alu" => Ok(BenchKind::Alu),
+            "drw" => Ok(BenchKind::Drw),
+            "bcd" => Ok(BenchKind::Bcd),
+            _ => Err(format!("Unknown benchmark '{}', expected alu, drw or bcd", s)),
+        }
+    }
+}
+
+impl BenchKind {
+    /// The instructions that are repeated, in order.
+    fn pattern(self) -> &'static [u16] {
+        match self {
+            // ADD V1, 0x11; ADD V2, V1; SUB V3, V2; XOR V4, V3; OR V5, V4; AND V6, V5;
+            // SHR V7, V7; SHL V8, V8; SUBN V9, V8
+            BenchKind::Alu => &[0x7111, 0x8214, 0x8325, 0x8433, 0x8541, 0x8652, 0x8776,
+                0x888E, 0x8987],
+            // DRW V1, V2, 5; ADD V1, 7; DRW V1, V2, 5; ADD V2, 3
+            BenchKind::Drw => &[0xD125, 0x7107, 0xD125, 0x7203],
+            // LD B, V3; ADD V3, 37; LD B, V4; ADD V4, 91
+            BenchKind::Bcd => &[0xF333, 0x7325, 0xF433, 0x745B],
+        }
+    }
+
+    /// The data I points to: a sprite to draw, or the bytes BCD is stored in.
+    fn data(self) -> &'static [u8] {
+        match self {
+            BenchKind::Alu => &[],
+            BenchKind::Drw => &[0xF0, 0x90, 0xF0, 0x90, 0xF0],
+            BenchKind::Bcd => &[0, 0, 0],
+        }
+    }
+}
+
+/// Generate the ROM of a benchmark that executes `len` instructions of its kind.
+pub fn generate(kind: BenchKind, len: usize) -> Result<Vec<u8>, String> {
+    let pattern = kind.pattern();
+    // Whole repetitions of the pattern, so every iteration runs all of it
+    let unrolled = len.div_ceil(255).div_ceil(pattern.len()).max(1) * pattern.len();
+    let (iterations, tail) = (len / unrolled, len % unrolled);
+    let mut opcodes = Vec::new();
+
+    // LD I, data, filled in once the size of the code is known
+    if !kind.data().is_empty() {
+        opcodes.push(0xA000);
+    }
+
+    if iterations > 0 {
+        opcodes.push(0x6D00 | iterations as u16);
+        let start = PROGRAM_START + 2 * opcodes.len();
+        opcodes.push(PAD);
+        opcodes.extend((0..unrolled).map(|idx| pattern[idx % pattern.len()]));
+        // ADD VD, -1; SE VD, 0; JP start
+        opcodes.extend(&[0x7DFF, 0x3D00, 0x1000 | start as u16]);
+    }
+    opcodes.extend((0..tail).map(|idx| pattern[idx % pattern.len()]));
+
+    // Two jumps to the first, so the ROM stays here either way
+    let halt = (PROGRAM_START + 2 * opcodes.len()) as u16;
+    opcodes.extend(&[0x1000 | halt, 0x1000 | halt]);
+
+    let data = PROGRAM_START + 2 * opcodes.len();
+    if !kind.data().is_empty() {
+        opcodes[0] = 0xA000 | data as u16;
+    }
+
+    let mut rom: Vec<u8> = opcodes.iter().flat_map(|opcode| opcode.to_be_bytes().to_vec())
+        .collect();
+    rom.extend(kind.data());
+
+    if PROGRAM_START + rom.len() > MEMORY {
+        return Err(format!("A ROM of {} instructions does not fit in memory", len));
+    }
+
+    Ok(rom)
+}
+
+/// The `gen-bench` subcommand.
+pub fn run_command(args: &[String]) -> Result<(), String> {
+    let mut kind = None;
+    let mut len = None;
+    let mut output = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or(format!("{} requires a value", arg))?;
+
+        match arg.as_str() {
+            "--kind" => kind = Some(value.parse()?),
+            "--len" => len = Some(value.parse()
+                .map_err(|_| format!("Invalid number '{}'", value))?),
+            "-o" => output = Some(value),
+            _ => return Err(format!("Unknown argument '{}'\n{}", arg, USAGE)),
+        }
+    }
+
+    let (kind, len, output) = match (kind, len, output) {
+        (Some(kind), Some(len), Some(output)) => (kind, len, output),
+        _ => return Err(String::from(USAGE)),
+    };
+
+    let rom = generate(kind, len)?;
+    fs::write(output, &rom).map_err(|e| format!("Could not write {}: {}", output, e))?;
+    println!("Wrote {} bytes to {}", rom.len(), output);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+    use crate::ops::Cpu;
+
+    // The instructions of its kind a benchmark executes before it halts
+    fn executed(kind: BenchKind, len: usize) -> usize {
+        let rom = generate(kind, len).unwrap();
+        let halt = PROGRAM_START + rom.len() - kind.data().len() - 4;
+        let mut chip8 = Chip8::new();
+        chip8.load_bytes(&rom);
+
+        let mut count = 0;
+        for _ in 0..2 * len + 1000 {
+            if chip8.pc() as usize >= halt {
+                break;
+            }
+
+            let opcode = chip8.cycle();
+            if kind.pattern().contains(&opcode) {
+                count += 1;
+            }
+        }
+
+        assert!(chip8.pc() as usize >= halt, "{:?} did not halt", kind);
+        assert!(chip8.bounds_fault.is_none() && chip8.unknown_opcode.is_none());
+        count
+    }
+
+    #[test]
+    fn test_executes_len_instructions() {
+        for &kind in [BenchKind::Alu, BenchKind::Drw, BenchKind::Bcd].iter() {
+            for &len in [0, 1, 7, 255, 256, 2000].iter() {
+                assert_eq!(executed(kind, len), len, "{:?} {}", kind, len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stays_halted() {
+        let rom = generate(BenchKind::Drw, 10).unwrap();
+        let mut chip8 = Chip8::new();
+        chip8.load_bytes(&rom);
+        for _ in 0..100 {
+            chip8.cycle();
+        }
+
+        let halt = PROGRAM_START + rom.len() - 5 - 4;
+        assert!((halt..halt + 4).contains(&(chip8.pc() as usize)));
+        assert!(chip8.display().lit_count() > 0);
+    }
+
+    #[test]
+    fn test_too_long() {
+        assert!(generate(BenchKind::Alu, 200_000).is_ok());
+        assert!(generate(BenchKind::Alu, 1_000_000).is_err());
+        assert!("fpu".parse::<BenchKind>().is_err());
+    }
+}
@@ -0,0 +1,18 @@
+//! `chip8-test manifest.txt`, a shorthand for `chip8 test manifest.txt` for use in
+//! continuous integration. It runs the `chip8` binary installed next to it.
+
+use std::{env, process::{self, Command}};
+
+fn main() {
+    let exe = env::current_exe()
+        .map(|exe| exe.with_file_name(format!("chip8{}", env::consts::EXE_SUFFIX)))
+        .unwrap_or_else(|e| panic!("Could not find the chip8 binary: {}", e));
+
+    let status = Command::new(&exe)
+        .arg("test")
+        .args(env::args_os().skip(1))
+        .status()
+        .unwrap_or_else(|e| panic!("Could not run {}: {}", exe.display(), e));
+
+    process::exit(status.code().unwrap_or(1));
+}
@@ -0,0 +1,107 @@
+//! Screen bookmarks: fast forward to a screen of a ROM on every run.
+//!
+//! F7 bookmarks the screen as it is shown, such as a title screen or the start of a level,
+//! by storing the hash of the display in the ROM's metadata. When the ROM is started again
+//! the smart turbo runs until the display matches the bookmark and the machine pauses
+//! there, F5 resumes. Shift+F7 removes the bookmark.
+//!
+//! The turbo gives up when the ROM waits for a key before reaching the screen, or when it
+//! is not reached within a minute of machine time.
+
+use std::{fs, io, path::{Path, PathBuf}};
+
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::display::Display;
+use crate::replay::frame_hash;
+
+/// What F7 asks for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Request {
+    Set,
+    Clear,
+}
+
+/// The bookmarked screen of a ROM, stored as the hexadecimal hash of its display.
+pub struct ScreenBookmark {
+    path: PathBuf,
+    hash: Option<u64>,
+}
+
+impl ScreenBookmark {
+    /// Read the bookmark of a ROM, if one was stored.
+    pub fn load(dir: &Path, rom_name: &str) -> ScreenBookmark {
+        let path = dir.join(format!("{}.bookmark", rom_name));
+        let hash = fs::read_to_string(&path).ok()
+            .and_then(|contents| u64::from_str_radix(contents.trim(), 16).ok());
+
+        ScreenBookmark { path, hash }
+    }
+
+    pub fn hash(&self) -> Option<u64> {
+        self.hash
+    }
+
+    /// Bookmark the screen on the display.
+    pub fn set(&mut self, display: &Display) -> io::Result<()> {
+        let hash = frame_hash(display);
+        self.hash = Some(hash);
+
+        fs::write(&self.path, format!("{:016X}\n", hash))
+    }
+
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.hash = None;
+
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// F7 sets the bookmark, Shift+F7 clears it.
+    pub fn handle_input(window: &Window) -> Option<Request> {
+        if !window.is_key_pressed(Key::F7, KeyRepeat::No) {
+            return None;
+        }
+
+        if window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift) {
+            Some(Request::Clear)
+        } else {
+            Some(Request::Set)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+    use crate::Chip8;
+
+    #[test]
+    fn test_store_bookmark() {
+        let dir = env::temp_dir()
+            .join(format!("chip8-bookmark-{}-test_store_bookmark", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut chip8 = Chip8::new();
+        // LD I, line; DRW V0, V0, 1; line: a line of 8 pixels
+        chip8.load_bytes(&[0xA2, 0x04, 0xD0, 0x01, 0xFF]);
+        chip8.cycle();
+        chip8.cycle();
+
+        assert_ne!(frame_hash(&chip8.display), frame_hash(&Chip8::new().display));
+
+        let mut bookmark = ScreenBookmark::load(&dir, "rom");
+        assert_eq!(bookmark.hash(), None);
+        bookmark.set(&chip8.display).unwrap();
+        assert_eq!(ScreenBookmark::load(&dir, "rom").hash(), Some(frame_hash(&chip8.display)));
+
+        bookmark.clear().unwrap();
+        assert_eq!(ScreenBookmark::load(&dir, "rom").hash(), None);
+        bookmark.clear().unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
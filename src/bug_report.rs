@@ -0,0 +1,216 @@
+//! Bug reports that can be reproduced, with `--bug-report`.
+//!
+//! While it is enabled, the most recent instructions and the keys pressed are kept, and
+//! Ctrl+B saves a ZIP file in the `bug-reports` data directory with everything needed to
+//! look into a problem with the emulator:
+//!
+//! - `report.txt`, the hash of the ROM as it was loaded, the command line and the settings
+//!   that affect emulation, such as the variant, speeds and quirks,
+//! - `chip8.toml`, the config file, when there is one,
+//! - `trace.txt`, the last 10000 instructions executed,
+//! - `input.replay`, the keys pressed since the ROM started, see `replay`,
+//! - `state.c8s`, a save state of the machine when the report was made.
+//!
+//! The ROM itself is left out, the hash tells which ROM it was. The ZIP file is written
+//! without compression, so no ZIP library is needed.
+
+use std::{env, fs, path::PathBuf, sync::{Arc, Mutex}, time::{SystemTime, UNIX_EPOCH}};
+
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::Chip8;
+use crate::config::Config;
+use crate::paths::{self, DataKind};
+use crate::replay::Fnv;
+use crate::savestate::SaveState;
+use crate::trace::TraceBuffer;
+
+const TRACE_LENGTH: usize = 10_000;
+
+/// Keeps what goes into a bug report from the start of the ROM on.
+pub struct BugReporter {
+    trace: Arc<Mutex<TraceBuffer>>,
+}
+
+impl BugReporter {
+    pub fn new(chip8: &mut Chip8) -> BugReporter {
+        let trace = Arc::new(Mutex::new(TraceBuffer::new(TRACE_LENGTH)));
+        let recorder = trace.clone();
+        chip8.on_post_cycle(move |_, address, opcode| {
+            recorder.lock().unwrap().record(address, opcode);
+        });
+
+        BugReporter { trace }
+    }
+
+    /// Whether Ctrl+B was pressed to save a report.
+    pub fn is_requested(window: &Window) -> bool {
+        let ctrl = window.is_key_down(Key::LeftCtrl) || window.is_key_down(Key::RightCtrl);
+        ctrl && window.is_key_pressed(Key::B, KeyRepeat::No)
+    }
+
+    fn report(&self, chip8: &Chip8, config: &Config, frame: u64) -> String {
+        let mut rom_hash = Fnv::new();
+        rom_hash.feed(&chip8.rom);
+        let quirks: Vec<&str> = ["key-release", "vf-reset", "display-wait"].iter()
+            .copied()
+            .filter(|quirk| config.quirks.is_enabled(quirk))
+            .collect();
+
+        let mut lines = vec!(
+            format!("version {}", env!("CARGO_PKG_VERSION")),
+            format!("rom {}", config.rom),
+            format!("rom hash {:016X}", rom_hash.finish()),
+            format!("command {}", env::args().collect::<Vec<_>>().join(" ")),
+            format!("frame {}", frame),
+            format!("variant {:?}", config.variant),
+            format!("cpu hz {}", config.cpu_hz),
+            format!("timer hz {}", config.timer_hz),
+            format!("stack depth {}", config.stack_depth),
+            format!("quirks {}", if quirks.is_empty() { String::from("none") } else {
+                quirks.join(",")
+            }),
+            format!("seed {}", config.seed),
+            format!("deterministic {}", config.deterministic),
+            format!("authentic timing {}", config.authentic_timing),
+        );
+        lines.push(String::new());
+
+        lines.join("\n")
+    }
+
+    /// The files of the report, by name.
+    fn files(&self, chip8: &Chip8, config: &Config, replay: &str, frame: u64)
+            -> Vec<(&'static str, Vec<u8>)> {
+        let trace: String = self.trace.lock().unwrap().iter()
+            .map(|event| event.describe() + "\n")
+            .collect();
+
+        let mut files = vec!(("report.txt", self.report(chip8, config, frame).into_bytes()));
+        if let Ok(contents) = fs::read(paths::config_file()) {
+            files.push(("chip8.toml", contents));
+        }
+        files.push(("trace.txt", trace.into_bytes()));
+        files.push(("input.replay", replay.as_bytes().to_vec()));
+        files.push(("state.c8s", SaveState::capture(chip8).to_bytes()));
+
+        files
+    }
+
+    /// Save a report of the machine as it is now, with the replay of the keys pressed,
+    /// returning where it was saved.
+    pub fn save(&self, chip8: &Chip8, config: &Config, replay: &str, frame: u64, rom_name: &str)
+            -> Result<PathBuf, String> {
+        let dir = paths::data_dir(DataKind::BugReports)
+            .map_err(|e| format!("Could not create the bug report directory: {}", e))?;
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let path = dir.join(format!("{}-{}.zip", rom_name, seconds));
+
+        fs::write(&path, zip(&self.files(chip8, config, replay, frame)))
+            .map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+
+        Ok(path)
+    }
+}
+
+/// The CRC-32 of the bytes, as ZIP files check their contents with.
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            if crc & 1 == 1 { crc >> 1 ^ 0xEDB8_8320 } else { crc >> 1 }
+        })
+    })
+}
+
+/// A ZIP file with the files stored as they are.
+fn zip(files: &[(&str, Vec<u8>)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut directory = Vec::new();
+
+    for (name, contents) in files {
+        let offset = out.len() as u32;
+        // Version 1.0, no flags, stored, changed at midnight on 1 January 1980, the CRC and
+        // the sizes
+        let mut header = vec!(10, 0, 0, 0, 0, 0, 0, 0, 0x21, 0);
+        header.extend_from_slice(&crc32(contents).to_le_bytes());
+        header.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        // No extra field
+        header.extend_from_slice(&[0, 0]);
+
+        out.extend_from_slice(b"PK\x03\x04");
+        out.extend_from_slice(&header);
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(contents);
+
+        // Made by version 1.0, then the local header, no comment, the first disk and no
+        // attributes
+        directory.extend_from_slice(b"PK\x01\x02\x0A\x00");
+        directory.extend_from_slice(&header);
+        directory.extend_from_slice(&[0; 10]);
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+
+    let directory_offset = out.len() as u32;
+    out.extend_from_slice(&directory);
+
+    let count = (files.len() as u16).to_le_bytes();
+    out.extend_from_slice(b"PK\x05\x06\x00\x00\x00\x00");
+    out.extend_from_slice(&count);
+    out.extend_from_slice(&count);
+    out.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    out.extend_from_slice(&directory_offset.to_le_bytes());
+    // No comment
+    out.extend_from_slice(&[0, 0]);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn test_zip() {
+        let zip = zip(&[("a.txt", b"hi".to_vec()), ("b.bin", vec!(1, 2, 3))]);
+
+        // Two local headers of 30 bytes, the names and the contents come first
+        assert!(zip.starts_with(b"PK\x03\x04"));
+        assert_eq!(&zip[30..37], b"a.txthi");
+        let directory = 2 * 30 + 10 + 5;
+        assert_eq!(&zip[directory..directory + 4], b"PK\x01\x02");
+
+        // The end record points at the central directory of two 51 byte entries
+        let end = &zip[zip.len() - 22..];
+        assert_eq!(&end[..4], b"PK\x05\x06");
+        assert_eq!(&end[10..12], &[2, 0]);
+        assert_eq!(u32::from_le_bytes([end[12], end[13], end[14], end[15]]), 2 * 51);
+        assert_eq!(u32::from_le_bytes([end[16], end[17], end[18], end[19]]), directory as u32);
+    }
+
+    #[test]
+    fn test_report_files() {
+        let mut chip8 = Chip8::new();
+        chip8.load_bytes(&[0x60, 0x05, 0x12, 0x00]);
+        let reporter = BugReporter::new(&mut chip8);
+        for _ in 0..3 {
+            chip8.cycle();
+        }
+
+        let files = reporter.files(&chip8, &Config::default(), "seed 0\nend 1\n", 1);
+        let names: Vec<&str> = files.iter().map(|(name, _)| *name)
+            .filter(|&name| name != "chip8.toml").collect();
+        assert_eq!(names, vec!("report.txt", "trace.txt", "input.replay", "state.c8s"));
+
+        let trace = &files.iter().find(|(name, _)| *name == "trace.txt").unwrap().1;
+        assert_eq!(String::from_utf8_lossy(trace).lines().count(), 3);
+    }
+}
@@ -0,0 +1,215 @@
+//! Finding a CPU speed per ROM at which its gameplay is paced like on typical interpreters.
+//!
+//! Most games wait for the delay timer in a busy loop such as `LD V0, DT; SE V0, 0;
+//! JP loop`. A game that never gets to wait is held back by the CPU and plays too slowly,
+//! one that hardly does anything else could be run slower without changing its pace.
+//! Every second the share of cycles spent waiting is measured and the speed is moved
+//! towards the one at which `TARGET_WAITING` of the time is spent waiting. The result is
+//! stored in the metadata directory, so the next run starts at the calibrated speed.
+
+use std::{fs, io, path::PathBuf};
+
+/// Frames between two adjustments, a second at 60 Hz.
+const WINDOW: u32 = 60;
+/// Most cycles between two reads of the delay timer by the same instruction for them to
+/// count as a busy wait.
+const MAX_LOOP_LENGTH: u32 = 4;
+/// The share of the cycles a game is expected to spend waiting on typical interpreters.
+const TARGET_WAITING: f32 = 0.25;
+const MIN_HZ: u32 = 200;
+const MAX_HZ: u32 = 2000;
+
+/// Measures how much of the time a ROM waits for the delay timer, and adjusts the CPU
+/// speed to match.
+pub struct SpeedCalibrator {
+    path: PathBuf,
+    cpu_hz: u32,
+    // Whether a busy wait has been seen at all, as games that do not use the delay timer
+    // for pacing tell nothing about the speed
+    paced: bool,
+    cycles: u32,
+    waiting: u32,
+    frames: u32,
+    // Address of the last LD Vx, DT and the cycle it ran at
+    last_timer_read: Option<(u16, u32)>,
+}
+
+impl SpeedCalibrator {
+    /// Start from the speed stored for a ROM, or from `cpu_hz` when it was not calibrated yet.
+    pub fn load(dir: PathBuf, rom_name: &str, cpu_hz: u32) -> SpeedCalibrator {
+        let path = dir.join(format!("{}.speed", rom_name));
+        let stored = fs::read_to_string(&path).ok()
+            .and_then(|contents| contents.trim().parse().ok());
+
+        SpeedCalibrator {
+            path,
+            cpu_hz: stored.unwrap_or(cpu_hz),
+            paced: false,
+            cycles: 0,
+            waiting: 0,
+            frames: 0,
+            last_timer_read: None,
+        }
+    }
+
+    pub fn cpu_hz(&self) -> u32 {
+        self.cpu_hz
+    }
+
+    /// Take note of an executed instruction.
+    pub fn observe(&mut self, pc: u16, opcode: u16) {
+        self.cycles += 1;
+
+        if opcode & 0xF0FF != 0xF007 {
+            return;
+        }
+
+        if let Some((address, cycle)) = self.last_timer_read {
+            if address == pc && self.cycles - cycle <= MAX_LOOP_LENGTH {
+                self.waiting += self.cycles - cycle;
+                self.paced = true;
+            }
+        }
+        self.last_timer_read = Some((pc, self.cycles));
+    }
+
+    /// Finish a frame, returning the new speed when it was adjusted.
+    pub fn end_frame(&mut self) -> Option<u32> {
+        self.frames += 1;
+        if self.frames < WINDOW {
+            return None;
+        }
+
+        let (cycles, waiting) = (self.cycles, self.waiting);
+        self.frames = 0;
+        self.cycles = 0;
+        self.waiting = 0;
+        self.last_timer_read = None;
+
+        if !self.paced || cycles == 0 {
+            return None;
+        }
+
+        let target = if waiting == 0 {
+            // Never waiting means the game needs more than it gets, by an unknown amount
+            self.cpu_hz * 3 / 2
+        } else {
+            let busy = 1.0 - waiting as f32 / cycles as f32;
+            (self.cpu_hz as f32 * busy / (1.0 - TARGET_WAITING)) as u32
+        };
+        let target = target.max(MIN_HZ).min(MAX_HZ);
+
+        // Close enough, so the speed settles instead of jittering
+        if (target as i64 - self.cpu_hz as i64).abs() * 20 <= self.cpu_hz as i64 {
+            return None;
+        }
+
+        self.cpu_hz = target;
+        if let Err(e) = self.store() {
+            println!("Could not store the calibrated speed: {}", e);
+        }
+
+        Some(target)
+    }
+
+    fn store(&self) -> io::Result<()> {
+        fs::write(&self.path, format!("{}\n", self.cpu_hz))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, path::Path, process};
+    use crate::Chip8;
+
+    // Run a second of frames, each `work` instructions followed by `wait` iterations of a
+    // three instruction busy loop on the delay timer
+    fn run_second(calibrator: &mut SpeedCalibrator, work: u32, wait: u32) -> Option<u32> {
+        let mut adjusted = None;
+
+        for _ in 0..WINDOW {
+            for _ in 0..work {
+                calibrator.observe(0x200, 0x6001);
+            }
+            for _ in 0..wait {
+                calibrator.observe(0x300, 0xF007);
+                calibrator.observe(0x302, 0x3000);
+                calibrator.observe(0x304, 0x1300);
+            }
+            adjusted = adjusted.or(calibrator.end_frame());
+        }
+
+        adjusted
+    }
+
+    // A directory of its own for every test, so runs never see each other's speeds
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir()
+            .join(format!("chip8-calibration-{}-{}", process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn calibrator(dir: &Path) -> SpeedCalibrator {
+        SpeedCalibrator::load(dir.to_path_buf(), "rom", 600)
+    }
+
+    #[test]
+    fn test_slows_down_when_mostly_waiting() {
+        let dir = test_dir("slow");
+        let mut calibrator = calibrator(&dir);
+
+        // 27 of 31 cycles are spent waiting
+        assert_eq!(run_second(&mut calibrator, 1, 10), Some(MIN_HZ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_speeds_up_when_never_waiting() {
+        let dir = test_dir("fast");
+        let mut calibrator = calibrator(&dir);
+
+        // A quarter of the cycles are spent waiting, as expected
+        assert_eq!(run_second(&mut calibrator, 15, 3), None);
+        assert_eq!(run_second(&mut calibrator, 10, 0), Some(900));
+        assert_eq!(SpeedCalibrator::load(dir.clone(), "rom", 500).cpu_hz(), 900);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_ignores_roms_without_busy_waits() {
+        let dir = test_dir("unpaced");
+        let mut calibrator = calibrator(&dir);
+
+        assert_eq!(run_second(&mut calibrator, 10, 0), None);
+        assert_eq!(calibrator.cpu_hz(), 600);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_detects_delay_loop() {
+        let dir = test_dir("delay_loop");
+        let mut calibrator = calibrator(&dir);
+
+        let mut chip8 = Chip8::new();
+        // LD V0, 30; LD DT, V0; wait: LD V1, DT; SE V1, 0; JP wait; JP 0x200
+        chip8.load_bytes(&[0x60, 0x1E, 0xF0, 0x15, 0xF1, 0x07, 0x31, 0x00, 0x12, 0x04,
+            0x12, 0x00]);
+
+        // 10 cycles a frame at 600 Hz, nearly all of them waiting for the delay timer
+        let mut adjusted = None;
+        for _ in 0..WINDOW {
+            for _ in 0..10 {
+                let (pc, opcode) = (chip8.pc, chip8.fetch());
+                chip8.cycle();
+                calibrator.observe(pc, opcode);
+            }
+            chip8.update_timers();
+            adjusted = adjusted.or(calibrator.end_frame());
+        }
+
+        assert_eq!(adjusted, Some(MIN_HZ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,305 @@
+//! Slow-motion captures of a ROM as animated GIFs, for material that explains how CHIP-8
+//! programs work.
+//!
+//! ```text
+//! chip8 capture game.ch8 -o game.gif --frames 120 --speed 0.25 --captions --input play.replay
+//! ```
+//!
+//! The ROM runs headlessly at 60 emulated frames per second, and every frame is written to
+//! be shown `1 / speed` times as long. With captions, the last instruction of every frame
+//! and the registers are shown under the display, with the registers that frame changed
+//! highlighted. Viewers tend to slow down frames shown for less than 2/100 of a second, so
+//! speeds above a half do not play back as intended.
+
+use std::{fs, sync::{Arc, Mutex}};
+
+use crate::{Chip8, WIDTH, HEIGHT};
+use crate::archive::read_rom;
+use crate::disassembler::disassemble;
+use crate::emulator::Emulator;
+use crate::gif::GifEncoder;
+use crate::golden::run_frame;
+use crate::replay::Replay;
+use crate::screen::{Buffer, Point};
+use crate::text::{CHAR_WIDTH, LINE_HEIGHT};
+
+const DEFAULT_FRAMES: u64 = 300;
+const USAGE: &str = "Usage: chip8 capture rom.ch8 -o out.gif [--frames N] [--speed 0.25] \
+    [--captions] [--input play.replay] [--seed N]";
+
+const BACKGROUND: u32 = 0x000000;
+const FOREGROUND: u32 = 0xFFFFFF;
+const CAPTION_BACKGROUND: u32 = 0x202020;
+const CAPTION_COLOR: u32 = 0xA0A0A0;
+const CHANGED_COLOR: u32 = 0xFFD040;
+const PALETTE: [u32; 5] =
+    [BACKGROUND, FOREGROUND, CAPTION_BACKGROUND, CAPTION_COLOR, CHANGED_COLOR];
+
+// The display is drawn twice its size so a line of captions fits under it, and the whole
+// frame twice again so the captions can be read
+const DISPLAY_SCALE: usize = 2;
+const FRAME_SCALE: usize = 2;
+const CAPTION_LINES: usize = 4;
+const CAPTION_HEIGHT: usize = CAPTION_LINES * LINE_HEIGHT + 2;
+
+/// How a capture is made.
+pub struct CaptureOptions {
+    pub frames: u64,
+    /// The playback speed, a quarter for four times slower than the game ran.
+    pub speed: f64,
+    pub captions: bool,
+    pub seed: u64,
+}
+
+/// The registers shown in the captions, to tell which ones a frame changed.
+#[derive(Clone, Copy, PartialEq)]
+struct Registers {
+    v: [u8; 16],
+    i: u16,
+    delay: u8,
+    sound: u8,
+}
+
+impl Registers {
+    fn of(chip8: &Chip8) -> Registers {
+        Registers {
+            v: chip8.registers,
+            i: chip8.i,
+            delay: chip8.timers.delay,
+            sound: chip8.timers.sound,
+        }
+    }
+}
+
+/// Draw a frame: the display, and the captions under it when there are any.
+fn compose(chip8: &Chip8, captions: Option<(Option<(u16, u16)>, Registers)>) -> Buffer {
+    let width = WIDTH * DISPLAY_SCALE;
+    let display_height = HEIGHT * DISPLAY_SCALE;
+    let height = display_height + if captions.is_some() { CAPTION_HEIGHT } else { 0 };
+    let mut frame = Buffer::new(width, height, Some(vec!(BACKGROUND; width * height)));
+
+    for y in 0..display_height {
+        for x in 0..width {
+            if chip8.display.is_lit(x / DISPLAY_SCALE, y / DISPLAY_SCALE) {
+                frame.set_pixel(x, y, FOREGROUND);
+            }
+        }
+    }
+
+    if let Some((instruction, before)) = captions {
+        for y in display_height..height {
+            for x in 0..width {
+                frame.set_pixel(x, y, CAPTION_BACKGROUND);
+            }
+        }
+
+        let after = Registers::of(chip8);
+        let top = |line: usize| display_height + 2 + line * LINE_HEIGHT;
+
+        let instruction = match instruction {
+            Some((pc, opcode)) => format!("{:03X} {:04X} {}", pc, opcode, disassemble(opcode)),
+            None => String::from("WAITING"),
+        };
+        frame.draw_text(&instruction, Point::new(1, top(0)), CAPTION_COLOR);
+
+        // Every register as a column of its own, so changed ones can stand out
+        let mut fields: Vec<(String, bool)> = (0..16)
+            .map(|x| (format!("{:02X}", after.v[x]), after.v[x] != before.v[x]))
+            .collect();
+        fields.push((format!("I {:03X}", after.i), after.i != before.i));
+        fields.push((format!("DT {:02X}", after.delay), after.delay != before.delay));
+        fields.push((format!("ST {:02X}", after.sound), after.sound != before.sound));
+
+        let rows = [(1, "V0", &fields[..8]), (2, "V8", &fields[8..16]), (3, "", &fields[16..])];
+        for &(row, label, fields) in rows.iter() {
+            let mut x = 1;
+            if !label.is_empty() {
+                frame.draw_text(label, Point::new(x, top(row)), CAPTION_COLOR);
+                x += 3 * CHAR_WIDTH;
+            }
+
+            for (text, changed) in fields {
+                let color = if *changed { CHANGED_COLOR } else { CAPTION_COLOR };
+                frame.draw_text(text, Point::new(x, top(row)), color);
+                x += (text.len() + 1) * CHAR_WIDTH;
+            }
+        }
+    }
+
+    scale(&frame, FRAME_SCALE)
+}
+
+fn scale(buffer: &Buffer, factor: usize) -> Buffer {
+    let (width, height) = (buffer.width() * factor, buffer.height() * factor);
+    let pixels = (0..width * height)
+        .map(|idx| buffer.pixels()[idx % width / factor + idx / width / factor * buffer.width()])
+        .collect();
+
+    Buffer::new(width, height, Some(pixels))
+}
+
+/// Run a ROM and encode its frames as a GIF. Frames that look the same as the one before
+/// are merged into it, showing it for longer.
+pub fn capture(rom: &[u8], options: &CaptureOptions, input: Option<&Replay>)
+        -> Result<Vec<u8>, String> {
+    if options.speed.is_nan() || options.speed <= 0.0 {
+        return Err(format!("Invalid speed {}, it has to be above zero", options.speed));
+    }
+    if options.frames == 0 {
+        return Err(String::from("Nothing to capture, no frames were run"));
+    }
+
+    let emulator = Emulator::builder().seed(options.seed).rom_bytes(rom).build()?;
+    let cycles_per_frame = emulator.cycles_per_frame();
+    let mut chip8 = emulator.chip8;
+
+    let last_instruction = Arc::new(Mutex::new(None));
+    let recorder = last_instruction.clone();
+    chip8.on_post_cycle(move |_, pc, opcode| *recorder.lock().unwrap() = Some((pc, opcode)));
+
+    // The time in hundredths of a second at which a frame is shown
+    let shown_at = |frame: u64| (frame as f64 * 100.0 / 60.0 / options.speed).round() as u64;
+
+    let caption_height = if options.captions { CAPTION_HEIGHT } else { 0 };
+    let mut encoder = GifEncoder::new(WIDTH * DISPLAY_SCALE * FRAME_SCALE,
+        (HEIGHT * DISPLAY_SCALE + caption_height) * FRAME_SCALE, &PALETTE)?;
+    let mut pending: Option<(Buffer, u64)> = None;
+
+    for frame in 0..options.frames {
+        if let Some(input) = input {
+            chip8.set_keys(input.keys_at(frame));
+        }
+
+        let before = Registers::of(&chip8);
+        *last_instruction.lock().unwrap() = None;
+        run_frame(&mut chip8, cycles_per_frame);
+
+        let captions = if options.captions {
+            Some((*last_instruction.lock().unwrap(), before))
+        } else {
+            None
+        };
+        let image = compose(&chip8, captions);
+
+        if let Some((shown, start)) = &pending {
+            if shown.pixels() == image.pixels() {
+                continue;
+            }
+
+            encoder.add_frame(shown, delay(shown_at(frame) - shown_at(*start)))?;
+        }
+        pending = Some((image, frame));
+    }
+
+    if let Some((shown, start)) = pending {
+        encoder.add_frame(&shown, delay(shown_at(options.frames) - shown_at(start)))?;
+    }
+
+    Ok(encoder.finish())
+}
+
+fn delay(hundredths: u64) -> u16 {
+    hundredths.min(u16::MAX as u64) as u16
+}
+
+/// The `capture` subcommand.
+pub fn run_command(args: &[String]) -> Result<(), String> {
+    let mut path = None;
+    let mut output = None;
+    let mut input = None;
+    let mut options =
+        CaptureOptions { frames: DEFAULT_FRAMES, speed: 0.25, captions: false, seed: 0 };
+    let mut seed = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--captions" {
+            options.captions = true;
+            continue;
+        }
+
+        if !arg.starts_with('-') {
+            path = Some(arg);
+            continue;
+        }
+
+        let value = args.next().ok_or(format!("{} requires a value", arg))?;
+        let invalid = || format!("Invalid number '{}'", value);
+
+        match arg.as_str() {
+            "-o" => output = Some(value),
+            "--frames" => options.frames = value.parse().map_err(|_| invalid())?,
+            "--speed" => options.speed = value.parse().map_err(|_| invalid())?,
+            "--seed" => seed = Some(value.parse().map_err(|_| invalid())?),
+            "--input" => {
+                let contents = fs::read_to_string(value)
+                    .map_err(|e| format!("Could not read {}: {}", value, e))?;
+                input = Some(Replay::parse(&contents)?);
+            },
+            _ => return Err(format!("Unknown argument '{}'\n{}", arg, USAGE)),
+        }
+    }
+
+    let (path, output) = match (path, output) {
+        (Some(path), Some(output)) => (path, output),
+        _ => return Err(String::from(USAGE)),
+    };
+    let rom = read_rom(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    options.seed = seed.or(input.as_ref().map(|input| input.seed)).unwrap_or(0);
+
+    let gif = capture(&rom, &options, input.as_ref())?;
+    fs::write(output, &gif).map_err(|e| format!("Could not write {}: {}", output, e))?;
+
+    println!("Captured {} frames to {}, {} bytes", options.frames, output, gif.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(captions: bool) -> CaptureOptions {
+        CaptureOptions { frames: 4, speed: 0.5, captions, seed: 0 }
+    }
+
+    // The delays of the frames of a GIF
+    fn delays(gif: &[u8]) -> Vec<u16> {
+        gif.windows(6)
+            .filter(|bytes| bytes[..4] == [0x21, 0xF9, 0x04, 0x00])
+            .map(|bytes| u16::from_le_bytes([bytes[4], bytes[5]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_still_frames_are_merged() {
+        // LD V0, K: nothing changes while waiting
+        let gif = capture(&[0xF0, 0x0A], &options(false), None).unwrap();
+
+        // Four frames at half speed, a single image shown for 4 / 30 of a second
+        assert_eq!(delays(&gif), vec!(13));
+        assert_eq!(&gif[6..10], &[0x00, 0x01, 0x80, 0x00]);
+    }
+
+    #[test]
+    fn test_captions() {
+        // ADD V3, 1; JP 0x200 (which lands on 0x202)
+        let gif = capture(&[0x73, 0x01, 0x12, 0x00], &options(true), None).unwrap();
+        let height = (HEIGHT * DISPLAY_SCALE + CAPTION_HEIGHT) * FRAME_SCALE;
+        assert_eq!(u16::from_le_bytes([gif[8], gif[9]]) as usize, height);
+
+        let mut chip8 = Chip8::new();
+        chip8.registers[3] = 1;
+        let before = Registers::of(&chip8);
+        chip8.registers[3] = 2;
+
+        let frame = compose(&chip8, Some((Some((0x200, 0x7301)), before)));
+        assert!(frame.pixels().contains(&CHANGED_COLOR));
+        assert!(compose(&chip8, None).pixels().iter().all(|&pixel| pixel == BACKGROUND));
+    }
+
+    #[test]
+    fn test_invalid_speed() {
+        let options = CaptureOptions { speed: 0.0, ..options(false) };
+        assert!(capture(&[0xF0, 0x0A], &options, None).is_err());
+    }
+}
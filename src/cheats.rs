@@ -0,0 +1,354 @@
+//! Cheats: bytes of memory that are frozen at a value or poked once.
+//!
+//! The cheats of a ROM are kept in its metadata directory, one per line:
+//!
+//! ```text
+//! freeze 2F0 01 Slow ball
+//! poke 2F4 09 Nine lives
+//! ```
+//!
+//! Frozen bytes are written back after every instruction, so the game can never change
+//! them. Pokes write their byte once, when triggered from the cheat list.
+//!
+//! The cheat list also has a page for memory search, to find the addresses to freeze.
+
+use std::{fs, io, path::PathBuf};
+
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::{Chip8, MEMORY};
+use crate::debugger::{Breakpoint, Debugger};
+use crate::search::{Filter, MemorySearch};
+use crate::screen::{Buffer, Point};
+use crate::text::LINE_HEIGHT;
+
+const TITLE_COLOR: u32 = 0xFFFFFF;
+const ENABLED_COLOR: u32 = 0x80FF80;
+const DISABLED_COLOR: u32 = 0x808080;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheatKind {
+    Freeze,
+    Poke,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cheat {
+    pub kind: CheatKind,
+    pub address: u16,
+    pub value: u8,
+    pub name: String,
+    /// Whether a freeze is active. Pokes are never enabled, they only fire.
+    pub enabled: bool,
+}
+
+impl Cheat {
+    /// Parse a line of a cheat file, such as `freeze 2F0 01 Slow ball`.
+    pub fn parse(line: &str) -> Result<Cheat, String> {
+        let mut fields = line.splitn(4, ' ').filter(|field| !field.is_empty());
+
+        let kind = match fields.next() {
+            Some("freeze") => CheatKind::Freeze,
+            Some("poke") => CheatKind::Poke,
+            _ => return Err(format!("Expected freeze or poke in '{}'", line)),
+        };
+
+        let address = fields.next().ok_or("Missing address")?;
+        let address = u16::from_str_radix(address, 16)
+            .ok()
+            .filter(|&address| (address as usize) < MEMORY)
+            .ok_or(format!("Invalid address '{}'", address))?;
+
+        let value = fields.next().ok_or("Missing value")?;
+        let value = u8::from_str_radix(value, 16)
+            .map_err(|_| format!("Invalid value '{}'", value))?;
+
+        let name = fields.next().map_or(String::new(), |name| name.trim().to_string());
+
+        Ok(Cheat { kind, address, value, name, enabled: kind == CheatKind::Freeze })
+    }
+
+    fn label(&self) -> String {
+        format!("{:03X}={:02X} {}", self.address, self.value, self.name.to_uppercase())
+    }
+}
+
+impl std::fmt::Display for Cheat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let kind = match self.kind {
+            CheatKind::Freeze => "freeze",
+            CheatKind::Poke => "poke",
+        };
+
+        write!(f, "{} {:03X} {:02X}", kind, self.address, self.value)?;
+
+        if !self.name.is_empty() {
+            write!(f, " {}", self.name)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The cheats of a ROM, with an overlay to toggle them.
+///
+/// F12 opens and closes the list. Up and Down select a cheat and Enter toggles a freeze or
+/// fires a poke. Tab switches to the memory search page, where N starts a new search and
+/// C, U, I and D keep the addresses that changed, stayed unchanged, increased or decreased
+/// since the last snapshot. Close the overlay to play in between. F freezes the selected
+/// address at its current value and W adds a watchpoint that breaks when it changes.
+pub struct CheatList {
+    path: PathBuf,
+    pub cheats: Vec<Cheat>,
+    open: bool,
+    selected: usize,
+    searching: bool,
+    search: MemorySearch,
+    search_selected: usize,
+    message: String,
+}
+
+impl CheatList {
+    /// Read the cheats of a ROM, skipping lines that do not parse.
+    pub fn load(dir: PathBuf, rom_name: &str) -> CheatList {
+        let path = dir.join(format!("{}.cheats", rom_name));
+        let contents = fs::read_to_string(&path).unwrap_or_default();
+
+        let cheats = contents.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| match Cheat::parse(line) {
+                Ok(cheat) => Some(cheat),
+                Err(e) => {
+                    println!("Skipping cheat: {}", e);
+                    None
+                },
+            })
+            .collect();
+
+        CheatList {
+            path,
+            cheats,
+            open: false,
+            selected: 0,
+            searching: false,
+            search: MemorySearch::new(),
+            search_selected: 0,
+            message: String::new(),
+        }
+    }
+
+    /// Add a cheat and store the list.
+    pub fn add(&mut self, cheat: Cheat) -> io::Result<()> {
+        self.cheats.push(cheat);
+
+        let contents: String = self.cheats.iter().map(|cheat| format!("{}\n", cheat)).collect();
+        fs::write(&self.path, contents)
+    }
+
+    /// Write the frozen bytes back, called after every instruction.
+    pub fn apply(&self, chip8: &mut Chip8) {
+        for cheat in self.cheats.iter().filter(|cheat| cheat.enabled) {
+            chip8.memory[cheat.address as usize] = cheat.value;
+        }
+    }
+
+    /// Toggle a freeze, or write the byte of a poke.
+    pub fn trigger(&mut self, idx: usize, chip8: &mut Chip8) {
+        if let Some(cheat) = self.cheats.get_mut(idx) {
+            match cheat.kind {
+                CheatKind::Freeze => cheat.enabled = !cheat.enabled,
+                CheatKind::Poke => chip8.memory[cheat.address as usize] = cheat.value,
+            }
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn handle_input(&mut self, window: &Window, chip8: &mut Chip8, debugger: &mut Debugger) {
+        if window.is_key_pressed(Key::F12, KeyRepeat::No) {
+            self.open = !self.open;
+            self.message.clear();
+        }
+
+        if !self.open {
+            return;
+        }
+
+        if window.is_key_pressed(Key::Tab, KeyRepeat::No) {
+            self.searching = !self.searching;
+            self.message.clear();
+        }
+
+        let (selected, count) = if self.searching {
+            (&mut self.search_selected, self.search.candidates().len())
+        } else {
+            (&mut self.selected, self.cheats.len())
+        };
+        if window.is_key_pressed(Key::Up, KeyRepeat::Yes) {
+            *selected = selected.saturating_sub(1);
+        }
+        if window.is_key_pressed(Key::Down, KeyRepeat::Yes) {
+            *selected = (*selected + 1).min(count.saturating_sub(1));
+        }
+
+        if self.searching {
+            self.handle_search_input(window, chip8, debugger);
+        } else if window.is_key_pressed(Key::Enter, KeyRepeat::No) {
+            self.trigger(self.selected, chip8);
+        }
+    }
+
+    fn handle_search_input(&mut self, window: &Window, chip8: &Chip8, debugger: &mut Debugger) {
+        let filters = [
+            (Key::C, Filter::Changed),
+            (Key::U, Filter::Unchanged),
+            (Key::I, Filter::Increased),
+            (Key::D, Filter::Decreased),
+        ];
+
+        if window.is_key_pressed(Key::N, KeyRepeat::No) {
+            self.search.start(&chip8.memory);
+            self.search_selected = 0;
+        }
+
+        for &(key, filter) in filters.iter() {
+            if self.search.is_started() && window.is_key_pressed(key, KeyRepeat::No) {
+                self.search.filter(&chip8.memory, filter);
+                self.search_selected = 0;
+            }
+        }
+
+        let address = match self.search.candidates().get(self.search_selected) {
+            Some(&address) => address,
+            None => return,
+        };
+        let value = chip8.memory[address as usize];
+
+        if window.is_key_pressed(Key::F, KeyRepeat::No) {
+            let cheat = Cheat {
+                kind: CheatKind::Freeze,
+                address,
+                value,
+                name: String::new(),
+                enabled: true,
+            };
+
+            self.message = match self.add(cheat) {
+                Ok(()) => format!("FROZE {:03X}", address),
+                Err(e) => {
+                    println!("Could not store cheat: {}", e);
+                    String::from("SAVE FAILED")
+                },
+            };
+        }
+
+        if window.is_key_pressed(Key::W, KeyRepeat::No) {
+            let watchpoint: Result<Breakpoint, String> =
+                format!("[{:#05X}] != {:#04X}", address, value).parse();
+
+            if let Ok(watchpoint) = watchpoint {
+                println!("Watching {}", watchpoint);
+                debugger.add_breakpoint(watchpoint);
+                self.message = format!("WATCH {:03X}", address);
+            }
+        }
+    }
+
+    pub fn render(&self, buffer: &mut Buffer, chip8: &Chip8) {
+        buffer.clear();
+
+        if self.searching {
+            self.render_search(buffer, chip8);
+            return;
+        }
+
+        buffer.draw_text("CHEATS", Point::new(0, 0), TITLE_COLOR);
+
+        if self.cheats.is_empty() {
+            buffer.draw_text("NONE", Point::new(0, LINE_HEIGHT + 1), DISABLED_COLOR);
+        }
+
+        for (idx, cheat) in self.cheats.iter().enumerate() {
+            let marker = match (cheat.kind, idx == self.selected) {
+                (_, true) => '>',
+                (CheatKind::Poke, false) => '!',
+                (CheatKind::Freeze, false) => ' ',
+            };
+            let color = if cheat.enabled { ENABLED_COLOR } else { DISABLED_COLOR };
+
+            buffer.draw_text(
+                &format!("{}{}", marker, cheat.label()),
+                Point::new(0, (idx + 1) * LINE_HEIGHT + 1),
+                color);
+        }
+    }
+
+    fn render_search(&self, buffer: &mut Buffer, chip8: &Chip8) {
+        buffer.draw_text("SEARCH N C U I D", Point::new(0, 0), TITLE_COLOR);
+
+        let status = if !self.search.is_started() {
+            String::from("N TO START")
+        } else if !self.message.is_empty() {
+            self.message.clone()
+        } else {
+            format!("{} LEFT", self.search.candidates().len())
+        };
+        buffer.draw_text(&status, Point::new(0, LINE_HEIGHT + 1), DISABLED_COLOR);
+
+        // Scroll so the selection stays visible
+        let rows = (buffer.height() / LINE_HEIGHT).saturating_sub(3);
+        let first = self.search_selected.saturating_sub(rows.saturating_sub(1));
+        let visible = self.search.candidates().iter().enumerate().skip(first).take(rows);
+
+        for (row, (idx, &address)) in visible.enumerate() {
+            let marker = if idx == self.search_selected { '>' } else { ' ' };
+            let color = if idx == self.search_selected { TITLE_COLOR } else { ENABLED_COLOR };
+
+            buffer.draw_text(
+                &format!("{}{:03X}={:02X}", marker, address, chip8.memory[address as usize]),
+                Point::new(0, (row + 2) * LINE_HEIGHT + 1),
+                color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cheat() {
+        let cheat = Cheat::parse("freeze 2F0 01 Slow ball").unwrap();
+        assert_eq!((cheat.kind, cheat.address, cheat.value), (CheatKind::Freeze, 0x2F0, 0x01));
+        assert_eq!(cheat.name, "Slow ball");
+        assert!(cheat.enabled);
+        assert_eq!(Cheat::parse(&cheat.to_string()), Ok(cheat));
+
+        assert!(!Cheat::parse("poke 300 FF").unwrap().enabled);
+        assert!(Cheat::parse("poke 1000 FF").is_err());
+        assert!(Cheat::parse("freeze 300").is_err());
+        assert!(Cheat::parse("peek 300 FF").is_err());
+    }
+
+    #[test]
+    fn test_freeze_and_poke() {
+        let mut list = CheatList::load(PathBuf::new(), "missing");
+        list.cheats = vec!(
+            Cheat::parse("freeze 300 05").unwrap(),
+            Cheat::parse("poke 301 07").unwrap(),
+        );
+        let mut chip8 = Chip8::new();
+
+        list.apply(&mut chip8);
+        assert_eq!(chip8.memory[0x300..0x302], [0x05, 0x00]);
+
+        list.trigger(1, &mut chip8);
+        list.trigger(0, &mut chip8);
+        chip8.memory[0x300] = 0x01;
+        list.apply(&mut chip8);
+        assert_eq!(chip8.memory[0x300..0x302], [0x01, 0x07]);
+    }
+}
@@ -0,0 +1,187 @@
+//! The CHIP-8X extensions: the VP-590 colour board, a second hex keypad and an I/O port.
+//!
+//! The colour board gives the whole display one background colour, which 02A0 cycles
+//! through, and a foreground colour per zone of 8 pixels wide. BxyN colours zones either
+//! 4 rows high (N = 0, low resolution) or a single row high (high resolution). The second
+//! keypad is read by ExF2 and ExF5, and FxF8 and FxFB write and read the I/O port.
+
+#[cfg(feature = "std")]
+use minifb::Key;
+
+use alloc::{vec, vec::Vec};
+
+use crate::{WIDTH, HEIGHT};
+
+/// CHIP-8X programs are loaded after the larger interpreter.
+pub const PROGRAM_START: u16 = 0x300;
+
+/// Width of a colour zone in pixels.
+pub const ZONE_WIDTH: usize = 8;
+const COLUMNS: usize = WIDTH / ZONE_WIDTH;
+/// Height of a zone set in low resolution.
+const LOW_RES_ZONE_HEIGHT: usize = 4;
+
+/// The eight foreground colours of the VP-590, indexed by the colour number of BxyN.
+pub const COLORS: [u32; 8] = [
+    0x000000, 0xFF0000, 0x0000FF, 0xFF00FF, 0x00FF00, 0xFFFF00, 0x00FFFF, 0xFFFFFF,
+];
+
+/// The background colours in the order 02A0 cycles through them.
+pub const BACKGROUNDS: [u32; 4] = [0x000080, 0x000000, 0x008000, 0x800000];
+
+/// Foreground colour of every zone after a reset, red.
+const DEFAULT_COLOR: u8 = 1;
+
+/// Keys of the second keypad, indexed by key value. The numeric keypad is used, since it
+/// is in the same place on every keyboard layout.
+#[cfg(feature = "std")]
+pub const SECOND_KEYPAD: [Key; 16] = [
+    Key::NumPad0, Key::NumPad1, Key::NumPad2, Key::NumPad3,
+    Key::NumPad4, Key::NumPad5, Key::NumPad6, Key::NumPad7,
+    Key::NumPad8, Key::NumPad9, Key::NumPadSlash, Key::NumPadAsterisk,
+    Key::NumPadMinus, Key::NumPadPlus, Key::NumPadEnter, Key::NumPadDot,
+];
+
+/// The colours the display is shown in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorBoard {
+    background: usize,
+    // The foreground colour of every zone, by pixel row and zone column
+    zones: [[u8; COLUMNS]; HEIGHT],
+}
+
+impl ColorBoard {
+    pub fn new() -> ColorBoard {
+        ColorBoard {
+            background: 0,
+            zones: [[DEFAULT_COLOR; COLUMNS]; HEIGHT],
+        }
+    }
+
+    pub fn cycle_background(&mut self) {
+        self.background = (self.background + 1) % BACKGROUNDS.len();
+    }
+
+    /// Colour zones as BxyN does. The low nibble of `columns` is the first zone column and
+    /// the high nibble the number of columns after it. With `n` = 0 the rows are given the
+    /// same way in zones of 4 pixel rows, otherwise `rows` is the first pixel row and `n`
+    /// the number of rows.
+    pub fn set_zones(&mut self, columns: u8, rows: u8, n: u8, color: u8) {
+        let (first_row, row_count) = match n {
+            0 => ((rows & 0xF) as usize * LOW_RES_ZONE_HEIGHT,
+                ((rows >> 4) as usize + 1) * LOW_RES_ZONE_HEIGHT),
+            n => (rows as usize, n as usize),
+        };
+        let first_column = (columns & 0xF) as usize;
+        let column_count = (columns >> 4) as usize + 1;
+
+        for row in self.zones.iter_mut().skip(first_row).take(row_count) {
+            for zone in row.iter_mut().skip(first_column).take(column_count) {
+                *zone = color & 0x7;
+            }
+        }
+    }
+
+    /// The colour of the pixel at (x, y).
+    pub fn color(&self, x: usize, y: usize, lit: bool) -> u32 {
+        if lit {
+            COLORS[self.zones[y % HEIGHT][x % WIDTH / ZONE_WIDTH] as usize]
+        } else {
+            BACKGROUNDS[self.background]
+        }
+    }
+
+    /// The background and zones as bytes, for save states.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec!(self.background as u8);
+        bytes.extend(self.zones.iter().flat_map(|row| row.iter()));
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<ColorBoard> {
+        if bytes.len() != 1 + COLUMNS * HEIGHT {
+            return None;
+        }
+
+        let mut colors = ColorBoard::new();
+        colors.background = bytes[0] as usize % BACKGROUNDS.len();
+        for (row, zones) in colors.zones.iter_mut().zip(bytes[1..].chunks(COLUMNS)) {
+            for (zone, &color) in row.iter_mut().zip(zones) {
+                *zone = color & 0x7;
+            }
+        }
+
+        Some(colors)
+    }
+}
+
+/// The hardware CHIP-8X adds to the machine.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chip8X {
+    pub colors: ColorBoard,
+    keys: [bool; 16],
+    /// The last byte written to the I/O port by FxF8.
+    pub output: u8,
+    /// The byte FxFB reads from the I/O port. Nothing is connected to it yet.
+    pub input: u8,
+}
+
+impl Chip8X {
+    pub fn new() -> Chip8X {
+        Chip8X {
+            colors: ColorBoard::new(),
+            keys: [false; 16],
+            output: 0,
+            input: 0,
+        }
+    }
+
+    /// Set which keys of the second keypad are held down.
+    pub fn set_keys(&mut self, keys: [bool; 16]) {
+        self.keys = keys;
+    }
+
+    pub fn is_key_down(&self, key: usize) -> bool {
+        self.keys[key & 0xF]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_low_res_zones() {
+        let mut colors = ColorBoard::new();
+        // Zones 2 and 3 across, the second row of 4 pixels down
+        colors.set_zones(0x12, 0x01, 0, 4);
+
+        assert_eq!(colors.color(16, 4, true), COLORS[4]);
+        assert_eq!(colors.color(31, 7, true), COLORS[4]);
+        assert_eq!(colors.color(32, 4, true), COLORS[DEFAULT_COLOR as usize]);
+        assert_eq!(colors.color(16, 8, true), COLORS[DEFAULT_COLOR as usize]);
+        assert_eq!(colors.color(16, 4, false), BACKGROUNDS[0]);
+    }
+
+    #[test]
+    fn test_high_res_zones() {
+        let mut colors = ColorBoard::new();
+        colors.set_zones(0x00, 0x05, 2, 7);
+
+        assert_eq!(colors.color(0, 4, true), COLORS[DEFAULT_COLOR as usize]);
+        assert_eq!(colors.color(0, 5, true), COLORS[7]);
+        assert_eq!(colors.color(7, 6, true), COLORS[7]);
+        assert_eq!(colors.color(0, 7, true), COLORS[DEFAULT_COLOR as usize]);
+    }
+
+    #[test]
+    fn test_color_board_bytes() {
+        let mut colors = ColorBoard::new();
+        colors.cycle_background();
+        colors.set_zones(0x70, 0x70, 0, 5);
+
+        assert_eq!(ColorBoard::from_bytes(&colors.to_bytes()), Some(colors));
+        assert_eq!(ColorBoard::from_bytes(&[0; 3]), None);
+    }
+}
@@ -0,0 +1,102 @@
+//! The command line: the subcommands, and otherwise the emulator with its window.
+
+use std::{fmt, process};
+
+use crate::{batch, bench_rom, capture, compare, compat, decode, detect, differential, embed,
+    frontend, golden, gym, memory_map, savestate, soak, sprite_editor, test_runner,
+    trace_diff};
+use crate::config::Config;
+
+/// The result of a subcommand, or else print the error and exit with status 1.
+fn or_exit<T, E: fmt::Display>(result: Result<T, E>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1)
+    })
+}
+
+/// Run the subcommand the arguments name, or else the emulator with its window.
+pub fn run(args: Vec<String>) {
+    match args.first().map(String::as_str) {
+        Some("embed") => {
+            or_exit(embed::run(&args[1..]));
+            return;
+        },
+        Some("run") => {
+            let code = or_exit(batch::run_command(&args[1..]));
+            process::exit(code);
+        },
+        Some("soak") => {
+            let code = or_exit(soak::run_command(&args[1..]));
+            process::exit(code);
+        },
+        Some("map") => {
+            or_exit(memory_map::run(&args[1..]));
+            return;
+        },
+        Some("test") => {
+            or_exit(test_runner::run_command(&args[1..]));
+            return;
+        },
+        Some("state") => {
+            or_exit(savestate::run_command(&args[1..]));
+            return;
+        },
+        Some("record-golden") => {
+            or_exit(golden::record_command(&args[1..]));
+            return;
+        },
+        Some("verify-golden") => {
+            or_exit(golden::verify_command(&args[1..]));
+            return;
+        },
+        Some("detect-quirks") => {
+            or_exit(detect::run_command(&args[1..]));
+            return;
+        },
+        Some("caps") => {
+            or_exit(decode::run_command(&args[1..]));
+            return;
+        },
+        Some("compare") => {
+            or_exit(compare::run_command(&args[1..]));
+            return;
+        },
+        Some("compat-scan") => {
+            or_exit(compat::run_command(&args[1..]));
+            return;
+        },
+        Some("gym") => {
+            or_exit(gym::run_command(&args[1..]));
+            return;
+        },
+        Some("capture") => {
+            or_exit(capture::run_command(&args[1..]));
+            return;
+        },
+        Some("trace-diff") => {
+            or_exit(trace_diff::run_command(&args[1..]));
+            return;
+        },
+        Some("gen-bench") => {
+            or_exit(bench_rom::run_command(&args[1..]));
+            return;
+        },
+        Some("sprite-edit") => {
+            or_exit(sprite_editor::run_command(&args[1..]));
+            return;
+        },
+        Some("diff-test") => {
+            or_exit(differential::run_command(&args[1..]));
+            return;
+        },
+        _ => {},
+    }
+
+    let config = or_exit(Config::from_args(args));
+
+    if let Err(e) = frontend::run(config) {
+        println!("{}", e);
+        process::exit(1);
+    }
+}
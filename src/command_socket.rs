@@ -0,0 +1,195 @@
+//! Remote control of a running emulator over a local socket, for scripts and editor
+//! extensions.
+//!
+//! With `--command-socket 127.0.0.1:47383` the emulator accepts connections on that
+//! address, and every line a client sends is a text command of `debugger::protocol`, such
+//! as `pause`, `step`, `screenshot shot.gif`, `loadstate level2.c8s` or `press-key 5 10`.
+//! Every command is answered with a line of JSON. Clients can stay connected and send
+//! commands as they go.
+//!
+//! Only loopback addresses can be bound, as the commands read and write files of the user.
+
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+};
+
+use crate::Chip8;
+use crate::debugger::Debugger;
+use crate::debugger::protocol::{self, Request, Response};
+
+// Longest line a client may send before it is dropped, far longer than any command
+const MAX_LINE: usize = 4096;
+
+struct Client {
+    stream: TcpStream,
+    // What was received after the last complete line
+    pending: Vec<u8>,
+}
+
+impl Client {
+    /// Read what the client sent, returning false once it disconnected.
+    fn receive(&mut self) -> bool {
+        let mut bytes = [0; 512];
+
+        loop {
+            match self.stream.read(&mut bytes) {
+                Ok(0) => return false,
+                Ok(count) => {
+                    self.pending.extend_from_slice(&bytes[..count]);
+                    if self.line_length() > MAX_LINE {
+                        return false;
+                    }
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return true,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+                Err(_) => return false,
+            }
+        }
+    }
+
+    /// The length of the line that is not complete yet.
+    fn line_length(&self) -> usize {
+        let start = self.pending.iter().rposition(|&byte| byte == b'\n').map_or(0, |idx| idx + 1);
+
+        self.pending.len() - start
+    }
+
+    /// The complete lines received, taken out of the pending bytes.
+    fn take_lines(&mut self) -> Vec<String> {
+        let end = match self.pending.iter().rposition(|&byte| byte == b'\n') {
+            Some(idx) => idx + 1,
+            None => return Vec::new(),
+        };
+
+        let lines: Vec<u8> = self.pending.drain(..end).collect();
+        String::from_utf8_lossy(&lines).lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+pub struct CommandSocket {
+    listener: TcpListener,
+    clients: Vec<Client>,
+}
+
+impl CommandSocket {
+    /// Listen on an address, which has to be a loopback one.
+    pub fn bind(address: &str) -> io::Result<CommandSocket> {
+        let addresses: Vec<SocketAddr> = address.to_socket_addrs()?.collect();
+        if let Some(address) = addresses.iter().find(|address| !address.ip().is_loopback()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!(
+                "The command socket only listens on loopback addresses, not {}", address)));
+        }
+
+        let listener = TcpListener::bind(&addresses[..])?;
+        listener.set_nonblocking(true)?;
+
+        println!("Accepting commands on {}", listener.local_addr()?);
+
+        Ok(CommandSocket { listener, clients: Vec::new() })
+    }
+
+    /// Accept new clients and carry out the commands received since the last call, without
+    /// blocking. Called between frames, so commands always see a consistent machine.
+    pub fn poll(&mut self, chip8: &mut Chip8, debugger: &mut Debugger) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                self.clients.push(Client { stream, pending: Vec::new() });
+            }
+        }
+
+        self.clients.retain_mut(|client| {
+            let connected = client.receive();
+
+            for line in client.take_lines() {
+                let response = match line.parse::<Request>() {
+                    Ok(request) => protocol::execute(request, chip8, debugger),
+                    Err(e) => Response::Error(e),
+                };
+
+                if writeln!(client.stream, "{}", response.to_json()).is_err() {
+                    return false;
+                }
+            }
+
+            connected
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+
+    #[test]
+    fn test_commands() {
+        let mut socket = CommandSocket::bind("127.0.0.1:0").unwrap();
+        let address = socket.listener.local_addr().unwrap();
+        let mut chip8 = Chip8::new();
+        let mut debugger = Debugger::new();
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(b"pause\npress-key a 2\nfly\nmem 0x").unwrap();
+        let mut responses = BufReader::new(stream.try_clone().unwrap());
+
+        let mut lines = Vec::new();
+        while lines.len() < 3 {
+            socket.poll(&mut chip8, &mut debugger);
+
+            let mut line = String::new();
+            stream.set_read_timeout(Some(std::time::Duration::from_millis(10))).unwrap();
+            if responses.read_line(&mut line).is_ok() && !line.is_empty() {
+                lines.push(line.trim_end().to_string());
+            }
+        }
+
+        assert_eq!(lines, vec!("{\"paused\":true}", "{\"key\":10,\"frames\":2}",
+            "{\"error\":\"Unknown command 'fly'\"}"));
+        assert!(debugger.paused);
+        assert!(debugger.held_keys()[0xA]);
+
+        // The rest of a line is kept until it is complete
+        stream.write_all(b"200 2\n").unwrap();
+        let mut line = String::new();
+        while line.is_empty() {
+            socket.poll(&mut chip8, &mut debugger);
+            let _ = responses.read_line(&mut line);
+        }
+        assert_eq!(line.trim_end(), "{\"start\":512,\"bytes\":[0, 0]}");
+    }
+
+    #[test]
+    fn test_only_loopback() {
+        for address in &["0.0.0.0:0", "[::]:0", "192.0.2.1:47383"] {
+            let error = CommandSocket::bind(address).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput, "{}", address);
+        }
+
+        assert!(CommandSocket::bind("localhost:0").is_ok());
+    }
+
+    #[test]
+    fn test_drops_endless_line() {
+        let mut socket = CommandSocket::bind("127.0.0.1:0").unwrap();
+        let address = socket.listener.local_addr().unwrap();
+        let mut chip8 = Chip8::new();
+        let mut debugger = Debugger::new();
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(&[b'a'; MAX_LINE]).unwrap();
+        // Up to the limit the client stays
+        while socket.clients.iter().all(|client| client.line_length() < MAX_LINE) {
+            socket.poll(&mut chip8, &mut debugger);
+        }
+
+        stream.write_all(b"a").unwrap();
+        while !socket.clients.is_empty() {
+            socket.poll(&mut chip8, &mut debugger);
+        }
+    }
+}
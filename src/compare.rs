@@ -0,0 +1,219 @@
+//! Comparing the runs of two ROMs frame by frame, to check that rebuilding a ROM with
+//! another assembler or applying a patch did not change what it does.
+//!
+//! ```text
+//! chip8 compare game.ch8 patched.ch8 --frames 600 --input play.replay --video diff.pgm
+//! ```
+//!
+//! Both ROMs run in deterministic mode with the same seed and the keys of the same replay.
+//! The report lists the first frame where the displays differ. The video is a stream of
+//! PGM frames, either the two displays side by side or only the pixels that differ, and
+//! can be turned into a video with `ffmpeg -f image2pipe -i diff.pgm diff.mp4`.
+
+use std::{fmt, fs, str::FromStr};
+
+use crate::{WIDTH, HEIGHT};
+use crate::archive::read_rom;
+use crate::display::{Display, LIT};
+use crate::emulator::Emulator;
+use crate::golden::run_frame;
+use crate::replay::Replay;
+use crate::screen::{Buffer, Point};
+
+const DEFAULT_FRAMES: u64 = 600;
+const USAGE: &str = "Usage: chip8 compare a.ch8 b.ch8 [--frames N] [--seed N] \
+    [--input play.replay] [--video out.pgm] [--mode side|xor]";
+
+/// How the frames of the video show the two displays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiffMode {
+    /// Both displays next to each other, with a column between them.
+    SideBySide,
+    /// Only the pixels that are lit on one display but not on the other.
+    Xor,
+}
+
+impl FromStr for DiffMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DiffMode, String> {
+        match s {
+            "side" => Ok(DiffMode::SideBySide),
+            "xor" => Ok(DiffMode::Xor),
+            _ => Err(format!("Unknown diff mode '{}', expected side or xor", s)),
+        }
+    }
+}
+
+/// A single frame of the video.
+pub fn diff_frame(a: &Display, b: &Display, mode: DiffMode) -> Buffer {
+    match mode {
+        DiffMode::SideBySide => {
+            let mut buffer = Buffer::new(2 * WIDTH + 1, HEIGHT, None);
+            buffer.blit(&a.to_buffer(), Point::new(0, 0));
+            buffer.blit(&b.to_buffer(), Point::new(WIDTH + 1, 0));
+            for y in (0..HEIGHT).step_by(2) {
+                buffer.set_pixel(WIDTH, y, LIT);
+            }
+
+            buffer
+        },
+        DiffMode::Xor => {
+            let mut rows = *a.rows();
+            for (row, other) in rows.iter_mut().zip(b.rows()) {
+                *row ^= other;
+            }
+
+            Display::from_rows(rows).to_buffer()
+        },
+    }
+}
+
+/// How the runs of the two ROMs compare.
+#[derive(Debug, PartialEq)]
+pub struct Comparison {
+    pub frames: u64,
+    pub differing_frames: u64,
+    /// The first frame on which the displays differ, and in how many pixels.
+    pub first_difference: Option<(u64, u32)>,
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.first_difference {
+            Some((frame, pixels)) => write!(f,
+                "The displays differ on {} of {} frames, first on frame {} in {} pixels",
+                self.differing_frames, self.frames, frame, pixels),
+            None => write!(f, "The displays are the same on all {} frames", self.frames),
+        }
+    }
+}
+
+/// Run both ROMs for a number of frames, calling `on_frame` with both displays after every
+/// frame.
+pub fn compare<F>(a: &[u8], b: &[u8], seed: u64, frames: u64, input: Option<&Replay>,
+    mut on_frame: F) -> Result<Comparison, String>
+    where F: FnMut(&Display, &Display)
+{
+    let build = |rom| Emulator::builder().seed(seed).rom_bytes(rom).build();
+    let (a, b) = (build(a)?, build(b)?);
+    let cycles_per_frame = a.cycles_per_frame();
+    let (mut a, mut b) = (a.chip8, b.chip8);
+
+    let mut comparison = Comparison { frames, differing_frames: 0, first_difference: None };
+    for frame in 0..frames {
+        let keys = input.map_or([false; 16], |input| input.keys_at(frame));
+        for chip8 in [&mut a, &mut b].iter_mut() {
+            chip8.set_keys(keys);
+            run_frame(chip8, cycles_per_frame);
+        }
+
+        let pixels: u32 = a.display.rows().iter().zip(b.display.rows())
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum();
+        if pixels > 0 {
+            comparison.differing_frames += 1;
+            comparison.first_difference.get_or_insert((frame, pixels));
+        }
+
+        on_frame(&a.display, &b.display);
+    }
+
+    Ok(comparison)
+}
+
+/// The `compare` subcommand, which fails when the displays differ.
+pub fn run_command(args: &[String]) -> Result<(), String> {
+    let mut roms = Vec::new();
+    let mut frames = DEFAULT_FRAMES;
+    let mut seed = None;
+    let mut input = None;
+    let mut video = None;
+    let mut mode = DiffMode::SideBySide;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            roms.push(arg);
+            continue;
+        }
+
+        let value = args.next().ok_or(format!("{} requires a value", arg))?;
+        let number = || value.parse().map_err(|_| format!("Invalid number '{}'", value));
+
+        match arg.as_str() {
+            "--frames" => frames = number()?,
+            "--seed" => seed = Some(number()?),
+            "--input" => {
+                let contents = fs::read_to_string(value)
+                    .map_err(|e| format!("Could not read {}: {}", value, e))?;
+                input = Some(Replay::parse(&contents)?);
+            },
+            "--video" => video = Some(value),
+            "--mode" => mode = value.parse()?,
+            _ => return Err(format!("Unknown argument '{}'\n{}", arg, USAGE)),
+        }
+    }
+
+    let (a, b) = match roms.as_slice() {
+        [a, b] => (a, b),
+        _ => return Err(String::from(USAGE)),
+    };
+    let read = |path: &str| read_rom(path).map_err(|e| format!("Could not read {}: {}", path, e));
+    let (rom_a, rom_b) = (read(a)?, read(b)?);
+    let seed = seed.or(input.as_ref().map(|input| input.seed)).unwrap_or(0);
+
+    let mut pgm = Vec::new();
+    let comparison = compare(&rom_a, &rom_b, seed, frames, input.as_ref(), |a, b| {
+        if video.is_some() {
+            pgm.extend(diff_frame(a, b, mode).to_pgm());
+        }
+    })?;
+
+    if let Some(path) = video {
+        fs::write(path, pgm).map_err(|e| format!("Could not write {}: {}", path, e))?;
+    }
+
+    if comparison.first_difference.is_some() {
+        return Err(comparison.to_string());
+    }
+
+    println!("{}", comparison);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        // LD I, 0x200; DRW V0, V0, 1; JP 0x204
+        let a = [0xA2, 0x00, 0xD0, 0x01, 0x12, 0x04];
+        let same = compare(&a, &a, 0, 5, None, |_, _| {}).unwrap();
+        assert_eq!(same.first_difference, None);
+
+        // The sprite is read from 0x201 instead, 0x01 rather than 0xA2
+        let b = [0xA2, 0x01, 0xD0, 0x01, 0x12, 0x04];
+        let mut shown = 0;
+        let different = compare(&a, &b, 0, 5, None, |_, _| shown += 1).unwrap();
+        assert_eq!(different.first_difference, Some((0, 4)));
+        assert_eq!((different.differing_frames, shown), (5, 5));
+    }
+
+    #[test]
+    fn test_diff_frame() {
+        let mut a = Display::new();
+        a.draw_sprite(0, 0, &[0xC0]);
+        let mut b = Display::new();
+        b.draw_sprite(0, 0, &[0x80]);
+
+        let xor = diff_frame(&a, &b, DiffMode::Xor);
+        assert_eq!(&xor.pixels()[..3], &[0, LIT, 0]);
+
+        let side = diff_frame(&a, &b, DiffMode::SideBySide);
+        assert_eq!(side.width(), 2 * WIDTH + 1);
+        assert_eq!(side.pixels()[WIDTH + 1], LIT);
+        assert_eq!(side.pixels()[WIDTH + 2], 0);
+    }
+}
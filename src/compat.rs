@@ -0,0 +1,296 @@
+//! Compatibility reports for a collection of ROMs.
+//!
+//! `chip8 compat-scan dir/` runs every ROM in a directory headlessly under the default
+//! profile and sorts it into one of four classes: it runs fine, it runs better under the
+//! quirks of another variant, it crashes, or it reaches opcodes of an extension the
+//! interpreter does not support. The report is a markdown table, or JSON with `--json`.
+
+use std::{fmt, fs, thread, panic::{self, AssertUnwindSafe}, path::Path};
+
+use crate::detect::{suggest, Outcome};
+use crate::emulator::Emulator;
+use crate::gallery::list_roms;
+use crate::golden::run_frame;
+use crate::variant::Variant;
+
+const DEFAULT_FRAMES: u64 = 300;
+const USAGE: &str = "Usage: chip8 compat-scan dir/ [--frames N] [--json] [-o report]";
+
+/// How a ROM fares under the default profile.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Compatibility {
+    RunsFine,
+    /// Runs, but fares better under the quirks of the variant.
+    NeedsQuirks(Variant),
+    Crashes { frame: u64, reason: String },
+    /// Reached an opcode the interpreter cannot execute.
+    UnsupportedExtension { frame: u64, opcode: u16 },
+}
+
+impl Compatibility {
+    /// Run the first `frames` frames of a ROM and classify it.
+    pub fn check(rom: &[u8], frames: u64) -> Result<Compatibility, String> {
+        let emulator = Emulator::builder().seed(0).rom_bytes(rom).build()?;
+        let cycles_per_frame = emulator.cycles_per_frame();
+        let mut chip8 = emulator.chip8;
+
+        for frame in 0..frames {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                run_frame(&mut chip8, cycles_per_frame)
+            }));
+
+            if let Err(payload) = result {
+                let reason = payload.downcast_ref::<String>().map(String::as_str)
+                    .or_else(|| payload.downcast_ref::<&str>().copied())
+                    .unwrap_or("unknown error");
+
+                return Ok(Compatibility::Crashes { frame, reason: reason.to_string() });
+            }
+            // A fault usually sends the ROM into data, so faults go before unknown opcodes
+            if let Some(error) = chip8.bounds_fault {
+                return Ok(Compatibility::Crashes { frame, reason: error.to_string() });
+            }
+            if let Some(pc) = chip8.stack_fault {
+                let reason = format!("Stack fault at {:#05X}", pc);
+                return Ok(Compatibility::Crashes { frame, reason });
+            }
+            if let Some(address) = chip8.unknown_opcode {
+                let address = address as usize;
+                let opcode = (chip8.memory[address] as u16) << 8
+                    | chip8.memory.get(address + 1).copied().unwrap_or(0) as u16;
+
+                return Ok(Compatibility::UnsupportedExtension { frame, opcode });
+            }
+        }
+
+        // Only a ROM that runs is worth trying under the quirks of the other variants
+        let outcomes = Variant::ALL.iter()
+            .map(|&variant| Outcome::run(rom, variant, frames))
+            .collect::<Result<Vec<Outcome>, String>>()?;
+        let baseline = &outcomes[0];
+
+        let better = suggest(&outcomes)
+            .and_then(|variant| outcomes.iter().find(|outcome| outcome.variant == variant))
+            .filter(|outcome| outcome.diverges_from(baseline).is_some());
+
+        Ok(match better {
+            Some(outcome) => Compatibility::NeedsQuirks(outcome.variant),
+            None => Compatibility::RunsFine,
+        })
+    }
+
+    /// The class, as the report lists it.
+    pub fn class(&self) -> &'static str {
+        match self {
+            Compatibility::RunsFine => "runs fine",
+            Compatibility::NeedsQuirks(_) => "needs quirks",
+            Compatibility::Crashes { .. } => "crashes",
+            Compatibility::UnsupportedExtension { .. } => "uses unsupported extensions",
+        }
+    }
+
+    /// What the class is based on, empty for a ROM that runs fine.
+    pub fn notes(&self) -> String {
+        match self {
+            Compatibility::RunsFine => String::new(),
+            Compatibility::NeedsQuirks(variant) => format!("runs best as {}", variant.name()),
+            Compatibility::Crashes { frame, reason } => format!("frame {}: {}", frame, reason),
+            Compatibility::UnsupportedExtension { frame, opcode } => match extension(*opcode) {
+                Some(name) => format!("frame {}: {} opcode {:04X}", frame, name, opcode),
+                None => format!("frame {}: opcode {:04X}", frame, opcode),
+            },
+        }
+    }
+}
+
+/// The extension an opcode the interpreter does not know belongs to, if it is a known one.
+fn extension(opcode: u16) -> Option<&'static str> {
+    match opcode {
+        0x00C0..=0x00CF | 0x00FB..=0x00FF => Some("SCHIP"),
+        _ if opcode & 0xF00F == 0xD000 => Some("SCHIP"),
+        _ if opcode & 0xF0FF == 0xF030 || opcode & 0xF0FF == 0xF075
+            || opcode & 0xF0FF == 0xF085 => Some("SCHIP"),
+        0x00D0..=0x00DF | 0xF000 | 0xF002 => Some("XO-CHIP"),
+        _ if opcode & 0xF00E == 0x5002 || opcode & 0xF0FF == 0xF001 => Some("XO-CHIP"),
+        _ => None,
+    }
+}
+
+/// The classification of every ROM in a collection.
+pub struct Report {
+    pub entries: Vec<(String, Compatibility)>,
+}
+
+impl Report {
+    /// Check every ROM in the directory. Each ROM runs on its own thread.
+    pub fn scan(dir: &Path, frames: u64) -> Result<Report, String> {
+        let handles: Vec<_> = list_roms(dir)?.into_iter().map(|path| {
+            let rom = fs::read(&path);
+
+            let handle = thread::spawn(move || {
+                let rom = rom.map_err(|e| e.to_string())?;
+                Compatibility::check(&rom, frames)
+            });
+
+            (path, handle)
+        }).collect();
+
+        let entries = handles.into_iter().map(|(path, handle)| {
+            let name = path.file_name()
+                .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+
+            let compatibility = match handle.join() {
+                Ok(Ok(compatibility)) => compatibility,
+                Ok(Err(e)) => Compatibility::Crashes { frame: 0, reason: e },
+                Err(_) => Compatibility::Crashes {
+                    frame: 0,
+                    reason: String::from("the emulator crashed"),
+                },
+            };
+
+            (name, compatibility)
+        }).collect();
+
+        Ok(Report { entries })
+    }
+
+    /// The number of ROMs in the class.
+    fn count(&self, class: &str) -> usize {
+        self.entries.iter().filter(|(_, compatibility)| compatibility.class() == class).count()
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut report = String::from("# Compatibility report\n\n");
+        report.push_str("| ROM | Status | Notes |\n|-----|--------|-------|\n");
+
+        for (name, compatibility) in &self.entries {
+            report.push_str(&format!("| {} | {} | {} |\n",
+                name.replace('|', "\\|"), compatibility.class(), compatibility.notes()));
+        }
+
+        report.push_str(&format!("\n{}\n", self));
+        report
+    }
+
+    pub fn to_json(&self) -> String {
+        let entries: Vec<String> = self.entries.iter().map(|(name, compatibility)| {
+            format!("{{\"rom\":{},\"status\":{},\"notes\":{}}}", json_string(name),
+                json_string(compatibility.class()), json_string(&compatibility.notes()))
+        }).collect();
+
+        format!("{{\"roms\":[{}]}}\n", entries.join(","))
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ROMs: {} run fine, {} need quirks, {} crash, {} use unsupported extensions",
+            self.entries.len(), self.count("runs fine"), self.count("needs quirks"),
+            self.count("crashes"), self.count("uses unsupported extensions"))
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+/// The `compat-scan` subcommand.
+pub fn run_command(args: &[String]) -> Result<(), String> {
+    let mut dir = None;
+    let mut frames = DEFAULT_FRAMES;
+    let mut json = false;
+    let mut output = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => {
+                let value = args.next().ok_or("--frames requires a number")?;
+                frames = value.parse().map_err(|_| format!("Invalid number '{}'", value))?;
+            },
+            "--json" => json = true,
+            "-o" => output = Some(args.next().ok_or("-o requires a path")?),
+            _ if !arg.starts_with("--") && dir.is_none() => dir = Some(arg),
+            _ => return Err(format!("Unknown argument '{}'\n{}", arg, USAGE)),
+        }
+    }
+
+    let dir = dir.ok_or(USAGE)?;
+
+    // Crashes are part of the report, not backtraces
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let report = Report::scan(Path::new(dir), frames);
+    panic::set_hook(hook);
+    let report = report?;
+
+    let contents = if json { report.to_json() } else { report.to_markdown() };
+    match output {
+        Some(path) => {
+            fs::write(path, contents).map_err(|e| format!("Could not write {}: {}", path, e))?;
+            println!("{}", report);
+        },
+        None => print!("{}", contents),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn test_check() {
+        // CLS; LD V0, K
+        assert_eq!(Compatibility::check(&[0x00, 0xE0, 0xF0, 0x0A], 2), Ok(Compatibility::RunsFine));
+        // LD V0, 1; SCHIP HIGH
+        assert_eq!(Compatibility::check(&[0x60, 0x01, 0x00, 0xFF], 2),
+            Ok(Compatibility::UnsupportedExtension { frame: 0, opcode: 0x00FF }));
+        // RET with an empty stack
+        let crashed = Compatibility::check(&[0x00, 0xEE], 2).unwrap();
+        assert_eq!(crashed.class(), "crashes");
+    }
+
+    #[test]
+    fn test_needs_quirks() {
+        // The vf_reset ROM of detect-quirks
+        let rom = [0xA2, 0x10, 0x6F, 0x01, 0x80, 0x11, 0x3F, 0x00, 0xA2, 0x12,
+            0xD0, 0x01, 0xF0, 0x0A, 0x00, 0x00, 0xFF, 0x00, 0x00];
+
+        assert_eq!(Compatibility::check(&rom, 2),
+            Ok(Compatibility::NeedsQuirks(Variant::CosmacVip)));
+    }
+
+    #[test]
+    fn test_report() {
+        let dir = env::temp_dir()
+            .join(format!("chip8-compat-{}-test_report", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("fine.ch8"), [0xF0, 0x0A]).unwrap();
+        fs::write(dir.join("hires \"x\".ch8"), [0x00, 0xFF]).unwrap();
+
+        let report = Report::scan(&dir, 2).unwrap();
+        assert_eq!(report.to_string(),
+            "2 ROMs: 1 run fine, 0 need quirks, 0 crash, 1 use unsupported extensions");
+        assert!(report.to_markdown().contains("| fine.ch8 | runs fine |  |\n"));
+        assert!(report.to_json().starts_with(
+            "{\"roms\":[{\"rom\":\"fine.ch8\",\"status\":\"runs fine\",\"notes\":\"\"},\
+            {\"rom\":\"hires \\\"x\\\".ch8\",\"status\":\"uses unsupported extensions\""));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,582 @@
+use std::fs;
+
+use crate::accessibility::Accessibility;
+use crate::debugger::{Breakpoint, RunBudget};
+use crate::debugger::expression::Watch;
+use crate::paths;
+use crate::audio::Waveform;
+use crate::filters::Filter;
+use crate::instance;
+use crate::layout::Layout;
+use crate::locale::Language;
+use crate::logging::LogFilter;
+use crate::quirks::Quirks;
+use crate::random::RngKind;
+use crate::stack;
+use crate::strictness::Strictness;
+use crate::stream::StreamFormat;
+use crate::timing::FrameSkip;
+use crate::trace::TraceFormat;
+use crate::variant::Variant;
+
+/// Emulator settings, read from a config file and overridden by command line arguments.
+///
+/// The config file contains one `key = value` pair per line, for example:
+///
+/// ```text
+/// rom = "roms/test_opcode.ch8"
+/// cpu_hz = 500
+/// timer_hz = 50
+/// quirks = key-release, vf-reset, display-wait
+/// protect_memory = true
+/// ```
+pub struct Config {
+    /// The ROM to run, the built-in demo when empty, see `demo`.
+    pub rom: String,
+    /// IPS patch applied to the ROM when it is loaded, see `ips`.
+    pub patch: Option<String>,
+    /// Strip foreign headers and cut off what does not fit in memory when loading the ROM,
+    /// see `recovery`.
+    pub recover: bool,
+    /// Pick the ROM from previews of the ROMs in this directory, see `gallery`.
+    pub gallery: Option<String>,
+    /// Where the ROMs are, previewed in the gallery when no ROM is given.
+    pub rom_dir: Option<String>,
+    pub cpu_hz: u32,
+    pub timer_hz: u32,
+    /// Charge every instruction its COSMAC VIP cost instead of running `cpu_hz`, see
+    /// `vip_timing`.
+    pub authentic_timing: bool,
+    /// Adjust the CPU speed to how the ROM waits for the delay timer, see `calibration`.
+    pub auto_speed: bool,
+    pub variant: Variant,
+    pub waveform: Waveform,
+    /// Milliseconds of sound the audio device buffers, its own default when not set. Less
+    /// makes the buzzer more responsive, but may crackle.
+    pub audio_latency: Option<u32>,
+    /// Upscaling filter for the game display, cycled with F4 at runtime.
+    pub filter: Filter,
+    /// Frames not drawn to the window, see `timing::FrameSkipper`.
+    pub frameskip: FrameSkip,
+    /// Inverted colours, a larger window and frame blending, see `accessibility`.
+    pub accessibility: Accessibility,
+    /// Overrides the keyboard layout guessed from the environment, see `layout`.
+    pub keyboard_layout: Option<Layout>,
+    /// The language of the menus and messages, see `locale`.
+    pub language: Language,
+    pub quirks: Quirks,
+    pub breakpoints: Vec<Breakpoint>,
+    /// Expressions shown with their values in the watches panel, see `debugger::expression`.
+    pub watches: Vec<Watch>,
+    /// Break once the ROM ran this long, for reproducing a bug at a known frame.
+    pub run_for: Option<RunBudget>,
+    /// Symbol file from the assembler, see `symbols`.
+    pub symbols: Option<String>,
+    pub protect_memory: bool,
+    /// Warn or break on odd addresses and accesses past the end of memory, and in strict
+    /// mode on ambiguous behaviour, see `strictness`.
+    pub strictness: Strictness,
+    /// Only show completed frames, presented at the display interrupts, see `display`.
+    pub double_buffer: bool,
+    /// Return addresses the stack can hold, see `stack`.
+    pub stack_depth: usize,
+    /// Show the debug panels in a separate window.
+    pub debug_window: bool,
+    /// Freeze the machine while the window does not have focus.
+    pub pause_on_focus_loss: bool,
+    /// Run the machine on a thread of its own, without the debugger and the overlays, see
+    /// `core_thread`.
+    pub threaded: bool,
+    /// Open the screen for testing the keys, timers, sound and display, see `setup_test`.
+    pub diag: bool,
+    /// Store the ROM and its state on exit, to resume from when launched without arguments.
+    pub save_session: bool,
+    /// Launched without arguments while sessions are saved, so the last one is offered.
+    pub resume: bool,
+    /// Launched without arguments or a config file, so the settings are asked for first,
+    /// see `first_run`.
+    pub first_run: bool,
+    /// Show a checksum of the machine state on the game display, to find where two runs
+    /// diverge.
+    pub show_checksum: bool,
+    /// Unlock the achievements of ROMs and show them on the game display, see
+    /// `achievements`.
+    pub achievements: bool,
+    /// Speaks the announcements of game state, see `announce`.
+    pub speech_command: Option<String>,
+    /// Reload the ROM whenever the file changes.
+    pub watch: bool,
+    /// Open ROMs in the emulator that is already running, see `instance`.
+    pub single_instance: bool,
+    pub break_on_unknown: bool,
+    /// Run a fixed number of cycles per frame with a seeded RNG, see `replay`.
+    pub deterministic: bool,
+    pub seed: u64,
+    /// The generator RND draws from, see `random`.
+    pub rng: RngKind,
+    pub record: Option<String>,
+    pub replay: Option<String>,
+    pub trace_format: Option<TraceFormat>,
+    pub trace_file: Option<String>,
+    /// Which diagnostic messages of the core are printed, see `logging`. Without it the
+    /// filter in `RUST_LOG` is used.
+    pub log: Option<LogFilter>,
+    /// Write the display to stdout in this format, see `stream`.
+    pub stream_fb: Option<StreamFormat>,
+    /// Show the game display without overlays in a second window, for capturing it.
+    pub clean_feed: bool,
+    /// Keep a trace and the keys pressed, for a report saved with Ctrl+B, see `bug_report`.
+    pub bug_report: bool,
+    #[cfg(feature = "http")]
+    pub http_address: Option<String>,
+    /// Take text commands from scripts and editors on this address, see `command_socket`.
+    pub command_socket: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            rom: String::new(),
+            patch: None,
+            recover: false,
+            gallery: None,
+            rom_dir: None,
+            cpu_hz: 500,
+            timer_hz: 60,
+            authentic_timing: false,
+            auto_speed: false,
+            variant: Variant::Chip8,
+            waveform: Waveform::Square,
+            audio_latency: None,
+            filter: Filter::Nearest,
+            frameskip: FrameSkip::Fixed(0),
+            accessibility: Accessibility::default(),
+            keyboard_layout: None,
+            language: Language::English,
+            quirks: Quirks::default(),
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+            run_for: None,
+            symbols: None,
+            protect_memory: false,
+            strictness: Strictness::Off,
+            double_buffer: false,
+            stack_depth: stack::DEFAULT_DEPTH,
+            debug_window: false,
+            pause_on_focus_loss: false,
+            threaded: false,
+            diag: false,
+            save_session: false,
+            resume: false,
+            first_run: false,
+            show_checksum: false,
+            achievements: false,
+            speech_command: None,
+            watch: false,
+            single_instance: false,
+            break_on_unknown: false,
+            deterministic: false,
+            seed: 0,
+            rng: RngKind::Modern,
+            record: None,
+            replay: None,
+            trace_format: None,
+            trace_file: None,
+            log: None,
+            stream_fb: None,
+            clean_feed: false,
+            bug_report: false,
+            #[cfg(feature = "http")]
+            http_address: None,
+            command_socket: None,
+        }
+    }
+}
+
+impl Config {
+    /// Build the configuration from the command line, loading the config file first.
+    ///
+    /// The config file is given by `--config`, otherwise it is looked up with
+    /// `paths::config_file` and only read when it exists.
+    pub fn from_args(args: Vec<String>) -> Result<Config, String> {
+        let mut config = Config::default();
+        let no_arguments = args.is_empty();
+        let mut no_config_file = false;
+
+        match args.iter().position(|arg| arg == "--config") {
+            Some(idx) => {
+                let path = args.get(idx + 1).ok_or("--config requires a path")?;
+                config.load_file(path)?;
+            },
+            None => {
+                let path = paths::config_file();
+
+                if path.exists() {
+                    config.load_file(&path.to_string_lossy())?;
+                } else {
+                    no_config_file = true;
+                }
+            },
+        }
+
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            // Including a ROM opened from the file manager
+            if !arg.starts_with("--") {
+                config.rom = instance::rom_argument(&arg);
+                continue;
+            }
+
+            if arg == "--protect-memory" {
+                config.protect_memory = true;
+                continue;
+            }
+
+            if arg == "--debug-window" {
+                config.debug_window = true;
+                continue;
+            }
+
+            if arg == "--bug-report" {
+                config.bug_report = true;
+                continue;
+            }
+
+            if arg == "--clean-feed" {
+                config.clean_feed = true;
+                continue;
+            }
+
+            if arg == "--double-buffer" {
+                config.double_buffer = true;
+                continue;
+            }
+
+            if arg == "--pause-on-focus-loss" {
+                config.pause_on_focus_loss = true;
+                continue;
+            }
+
+            if arg == "--threaded" {
+                config.threaded = true;
+                continue;
+            }
+
+            if arg == "--diag" {
+                config.diag = true;
+                continue;
+            }
+
+            if arg == "--save-session" {
+                config.save_session = true;
+                continue;
+            }
+
+            if arg == "--show-checksum" {
+                config.show_checksum = true;
+                continue;
+            }
+
+            if arg == "--achievements" {
+                config.achievements = true;
+                continue;
+            }
+
+            if arg == "--recover" {
+                config.recover = true;
+                continue;
+            }
+
+            if arg == "--watch" {
+                config.watch = true;
+                continue;
+            }
+
+            if arg == "--invert-colors" {
+                config.accessibility.invert_colors = true;
+                continue;
+            }
+
+            if arg == "--large-scale" {
+                config.accessibility.large_scale = true;
+                continue;
+            }
+
+            if arg == "--blend-frames" {
+                config.accessibility.blend_frames = true;
+                continue;
+            }
+
+            if arg == "--reduce-flicker" {
+                config.accessibility.reduce_flicker = true;
+                continue;
+            }
+
+            if arg == "--single-instance" {
+                config.single_instance = true;
+                continue;
+            }
+
+            if arg == "--break-on-unknown" {
+                config.break_on_unknown = true;
+                continue;
+            }
+
+            if arg == "--authentic-timing" {
+                config.authentic_timing = true;
+                continue;
+            }
+
+            if arg == "--auto-speed" {
+                config.auto_speed = true;
+                continue;
+            }
+
+            if arg == "--strict" {
+                config.strictness = Strictness::Strict;
+                continue;
+            }
+
+            if arg == "--deterministic" {
+                config.deterministic = true;
+                continue;
+            }
+
+            let value = args.next().ok_or(format!("{} requires a value", arg))?;
+
+            match arg.as_str() {
+                "--config" => {},
+                "--break" => config.set("break", &value)?,
+                "--watch-expr" => config.set("watch_expr", &value)?,
+                "--run-for" => config.set("run_for", &value)?,
+                "--gallery" => config.set("gallery", &value)?,
+                "--rom-dir" => config.set("rom_dir", &value)?,
+                "--patch" => config.set("patch", &value)?,
+                "--symbols" => config.set("symbols", &value)?,
+                "--quirk" => config.set("quirks", &value)?,
+                "--cpu-hz" => config.set("cpu_hz", &value)?,
+                "--timer-hz" => config.set("timer_hz", &value)?,
+                "--stack-depth" => config.set("stack_depth", &value)?,
+                "--strictness" => config.set("strictness", &value)?,
+                "--variant" => config.set("variant", &value)?,
+                "--waveform" => config.set("waveform", &value)?,
+                "--audio-latency" => config.set("audio_latency", &value)?,
+                "--filter" => config.set("filter", &value)?,
+                "--frameskip" => config.set("frameskip", &value)?,
+                "--keyboard-layout" => config.set("keyboard_layout", &value)?,
+                "--lang" => config.set("language", &value)?,
+                "--speech-command" => config.set("speech_command", &value)?,
+                "--trace-format" => config.set("trace_format", &value)?,
+                "--trace-file" => config.set("trace_file", &value)?,
+                "--log" => config.set("log", &value)?,
+                "--stream-fb" => config.set("stream_fb", &value)?,
+                "--seed" => config.set("seed", &value)?,
+                "--rng" => config.set("rng", &value)?,
+                "--record" => config.set("record", &value)?,
+                "--replay" => config.set("replay", &value)?,
+                #[cfg(feature = "http")]
+                "--http" => config.set("http", &value)?,
+                "--command-socket" => config.set("command_socket", &value)?,
+                _ => return Err(format!("Unknown argument '{}'", arg)),
+            }
+        }
+
+        config.resume = no_arguments && config.save_session;
+        config.first_run = no_arguments && no_config_file;
+        if config.rom.is_empty() && config.gallery.is_none() && !config.resume {
+            config.gallery = config.rom_dir.clone();
+        }
+
+        Ok(config)
+    }
+
+    /// The options given that the threaded core does not support, as they are written on the
+    /// command line.
+    pub fn threaded_conflicts(&self) -> Vec<&'static str> {
+        #[cfg(feature = "http")]
+        let http = self.http_address.is_some();
+        #[cfg(not(feature = "http"))]
+        let http = false;
+
+        let options = [
+            ("--trace-format", self.trace_format.is_some()),
+            ("--record", self.record.is_some()),
+            ("--replay", self.replay.is_some()),
+            ("--deterministic", self.deterministic),
+            ("--break", !self.breakpoints.is_empty()),
+            ("--run-for", self.run_for.is_some()),
+            ("--http", http),
+            ("--command-socket", self.command_socket.is_some()),
+        ];
+
+        options.iter().filter(|(_, given)| *given).map(|(option, _)| *option).collect()
+    }
+
+    pub fn load_file(&mut self, path: &str) -> Result<(), String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read config file {}: {}", path, e))?;
+
+        self.parse(&contents)
+    }
+
+    fn parse(&mut self, contents: &str) -> Result<(), String> {
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.splitn(2, '#').next().unwrap().trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap().trim();
+            let value = parts.next()
+                .ok_or(format!("Line {} of the config file has no value", number + 1))?
+                .trim()
+                .trim_matches('"');
+
+            self.set(key, value)?;
+        }
+
+        Ok(())
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "rom" => self.rom = value.to_string(),
+            "gallery" => self.gallery = Some(value.to_string()),
+            "rom_dir" => self.rom_dir = Some(value.to_string()),
+            "patch" => self.patch = Some(value.to_string()),
+            "cpu_hz" => self.cpu_hz = parse_hz(key, value)?,
+            "timer_hz" => self.timer_hz = parse_hz(key, value)?,
+            "variant" => self.variant = value.parse()?,
+            "waveform" => self.waveform = value.parse()?,
+            "audio_latency" => self.audio_latency = match value.parse() {
+                Ok(ms) if ms > 0 => Some(ms),
+                _ => return Err(
+                    format!("audio_latency must be a positive number, not '{}'", value)),
+            },
+            "filter" => self.filter = value.parse()?,
+            "frameskip" => self.frameskip = value.parse()?,
+            "invert_colors" => self.accessibility.invert_colors = parse_bool(key, value)?,
+            "large_scale" => self.accessibility.large_scale = parse_bool(key, value)?,
+            "blend_frames" => self.accessibility.blend_frames = parse_bool(key, value)?,
+            "reduce_flicker" => self.accessibility.reduce_flicker = parse_bool(key, value)?,
+            "keyboard_layout" => self.keyboard_layout = Some(value.parse()?),
+            "language" => self.language = value.parse()?,
+            "quirks" => {
+                for quirk in value.split(',').map(str::trim).filter(|q| !q.is_empty()) {
+                    self.quirks.enable(quirk)?;
+                }
+            },
+            "break" => self.breakpoints.push(value.parse()?),
+            "watch_expr" => self.watches.push(value.parse()?),
+            "run_for" => self.run_for = Some(value.parse()?),
+            "symbols" => self.symbols = Some(value.to_string()),
+            "recover" => self.recover = parse_bool(key, value)?,
+            "protect_memory" => self.protect_memory = parse_bool(key, value)?,
+            "strictness" => self.strictness = value.parse()?,
+            "double_buffer" => self.double_buffer = parse_bool(key, value)?,
+            "stack_depth" => self.stack_depth = match value.parse() {
+                Ok(depth) if depth > 0 => depth,
+                _ => return Err(format!("stack_depth must be a positive number, not '{}'", value)),
+            },
+            "debug_window" => self.debug_window = parse_bool(key, value)?,
+            "pause_on_focus_loss" => self.pause_on_focus_loss = parse_bool(key, value)?,
+            "threaded" => self.threaded = parse_bool(key, value)?,
+            "show_checksum" => self.show_checksum = parse_bool(key, value)?,
+            "achievements" => self.achievements = parse_bool(key, value)?,
+            "save_session" => self.save_session = parse_bool(key, value)?,
+            "speech_command" => self.speech_command = Some(value.to_string()),
+            "watch" => self.watch = parse_bool(key, value)?,
+            "single_instance" => self.single_instance = parse_bool(key, value)?,
+            "authentic_timing" => self.authentic_timing = parse_bool(key, value)?,
+            "auto_speed" => self.auto_speed = parse_bool(key, value)?,
+            "break_on_unknown" => self.break_on_unknown = parse_bool(key, value)?,
+            "deterministic" => self.deterministic = parse_bool(key, value)?,
+            "seed" => self.seed = value.parse()
+                .map_err(|_| format!("seed must be a number, not '{}'", value))?,
+            "rng" => self.rng = value.parse()?,
+            // Recording and replaying input only makes sense in deterministic mode
+            "record" => {
+                self.record = Some(value.to_string());
+                self.deterministic = true;
+            },
+            "replay" => {
+                self.replay = Some(value.to_string());
+                self.deterministic = true;
+            },
+            "trace_format" => self.trace_format = Some(value.parse()?),
+            "trace_file" => self.trace_file = Some(value.to_string()),
+            "log" => self.log = Some(value.parse()?),
+            "stream_fb" => self.stream_fb = Some(value.parse()?),
+            "clean_feed" => self.clean_feed = parse_bool(key, value)?,
+            "bug_report" => self.bug_report = parse_bool(key, value)?,
+            #[cfg(feature = "http")]
+            "http" => self.http_address = Some(value.to_string()),
+            "command_socket" => self.command_socket = Some(value.to_string()),
+            _ => return Err(format!("Unknown setting '{}'", key)),
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_hz(key: &str, value: &str) -> Result<u32, String> {
+    match value.parse() {
+        Ok(hz) if hz > 0 => Ok(hz),
+        _ => Err(format!("{} must be a positive number, not '{}'", key, value)),
+    }
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!("{} must be true or false, not '{}'", key, value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let mut config = Config::default();
+        config.parse("# PAL timing\ntimer_hz = 50\nrom = \"pong.ch8\"\n").unwrap();
+
+        assert_eq!(config.timer_hz, 50);
+        assert_eq!(config.cpu_hz, 500);
+        assert_eq!(config.rom, "pong.ch8");
+    }
+
+    #[test]
+    fn test_arguments_override() {
+        let args = vec!("--cpu-hz", "1000", "game.ch8")
+            .into_iter().map(String::from).collect();
+        let config = Config::from_args(args).unwrap();
+
+        assert_eq!(config.cpu_hz, 1000);
+        assert_eq!(config.rom, "game.ch8");
+    }
+
+    #[test]
+    fn test_threaded_conflicts() {
+        let args = vec!("--threaded", "--deterministic", "--run-for", "60", "game.ch8")
+            .into_iter().map(String::from).collect();
+        let config = Config::from_args(args).unwrap();
+
+        assert_eq!(config.threaded_conflicts(), vec!("--deterministic", "--run-for"));
+        assert!(Config::default().threaded_conflicts().is_empty());
+    }
+
+    #[test]
+    fn test_audio_latency() {
+        let mut config = Config::default();
+
+        config.set("audio_latency", "20").unwrap();
+        assert_eq!(config.audio_latency, Some(20));
+        assert!(config.set("audio_latency", "0").is_err());
+    }
+}
@@ -0,0 +1,176 @@
+//! The CHIP-8 binary container format (`.c8b`), which bundles one or more ROMs with
+//! metadata about how they should be run.
+//!
+//! The file starts with the magic bytes `CBF`, a version byte and the big-endian offset of
+//! the bytecode table. The properties table follows the header up to the bytecode table,
+//! as records of a type byte and a big-endian offset to the property data. The bytecode
+//! table starts with its number of entries, each a platform byte followed by the
+//! big-endian offset and length of the ROM for that platform.
+
+use std::io;
+
+use crate::archive::read_rom;
+use crate::config::Config;
+
+const MAGIC: &[u8] = b"CBF";
+const HEADER_LENGTH: usize = 6;
+
+// Property types
+const NAME: u8 = 0x00;
+const AUTHOR: u8 = 0x02;
+const TICK_RATE: u8 = 0x07;
+const COLORS: u8 = 0x08;
+const KEYMAP: u8 = 0x0A;
+
+// Platforms
+const COSMAC_VIP: u8 = 0x00;
+
+/// Settings embedded in a container.
+#[derive(Debug, Default, PartialEq)]
+pub struct Metadata {
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub platform: Option<u8>,
+    /// Instructions executed per 60 Hz frame.
+    pub tick_rate: Option<u16>,
+    /// Properties that were present but cannot be applied by this emulator.
+    pub ignored: Vec<&'static str>,
+}
+
+impl Metadata {
+    /// Apply the embedded settings on top of the configuration.
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(name) = &self.name {
+            match &self.author {
+                Some(author) => println!("Loading {} by {}", name, author),
+                None => println!("Loading {}", name),
+            }
+        }
+
+        // The original interpreter reset VF after logic instructions and waited for the
+        // key to be released in Fx0A
+        if self.platform == Some(COSMAC_VIP) {
+            config.quirks.vf_reset = true;
+            config.quirks.key_release = true;
+        }
+
+        if let Some(tick_rate) = self.tick_rate.filter(|&rate| rate > 0) {
+            config.cpu_hz = tick_rate as u32 * 60;
+        }
+
+        for property in &self.ignored {
+            println!("Ignoring the {} embedded in the ROM file", property);
+        }
+    }
+}
+
+/// Read a ROM file, unpacking it when it is stored in a container.
+///
+/// Plain ROMs are returned as they are, with empty metadata.
+pub fn load(path: &str) -> io::Result<(Vec<u8>, Metadata)> {
+    let bytes = read_rom(path)?;
+
+    if !bytes.starts_with(MAGIC) {
+        return Ok((bytes, Metadata::default()));
+    }
+
+    parse(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Split a container into the ROM and its metadata. A CHIP-8 ROM is preferred when the
+/// container holds ROMs for several platforms.
+pub fn parse(bytes: &[u8]) -> Result<(Vec<u8>, Metadata), String> {
+    let truncated = || String::from("Container is truncated");
+    let u16_at = |offset: usize| -> Result<u16, String> {
+        bytes.get(offset..offset + 2)
+            .map(|b| (b[0] as u16) << 8 | b[1] as u16)
+            .ok_or_else(truncated)
+    };
+
+    if bytes.get(3) != Some(&0) {
+        return Err(String::from("Unsupported container version"));
+    }
+
+    let table = u16_at(4)? as usize;
+    let mut metadata = Metadata::default();
+
+    for record in bytes.get(HEADER_LENGTH..table).ok_or_else(truncated)?.chunks(3) {
+        if record.len() < 3 {
+            return Err(truncated());
+        }
+
+        let offset = ((record[1] as usize) << 8) | record[2] as usize;
+
+        match record[0] {
+            NAME => metadata.name = Some(string_at(bytes, offset)?),
+            AUTHOR => metadata.author = Some(string_at(bytes, offset)?),
+            TICK_RATE => metadata.tick_rate = Some(u16_at(offset)?),
+            COLORS => metadata.ignored.push("colors"),
+            KEYMAP => metadata.ignored.push("keymap"),
+            _ => {},
+        }
+    }
+
+    let count = *bytes.get(table).ok_or_else(truncated)? as usize;
+    let mut entries = Vec::new();
+
+    for idx in 0..count {
+        let entry = table + 1 + idx * 5;
+        let platform = *bytes.get(entry).ok_or_else(truncated)?;
+
+        entries.push((platform, u16_at(entry + 1)? as usize, u16_at(entry + 3)? as usize));
+    }
+
+    let (platform, offset, length) = entries.iter()
+        .find(|&&(platform, _, _)| platform == COSMAC_VIP)
+        .or_else(|| entries.first())
+        .cloned()
+        .ok_or("Container holds no ROM")?;
+
+    let rom = bytes.get(offset..offset + length).ok_or_else(truncated)?.to_vec();
+    metadata.platform = Some(platform);
+
+    Ok((rom, metadata))
+}
+
+fn string_at(bytes: &[u8], offset: usize) -> Result<String, String> {
+    let data = bytes.get(offset..).ok_or("Property is out of bounds")?;
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+
+    Ok(String::from_utf8_lossy(&data[..end]).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let mut bytes = b"CBF\x00\x00\x0F".to_vec();
+        // Properties: name at 0x15, tick rate at 0x1A, keymap
+        bytes.extend(&[NAME, 0x00, 0x15, TICK_RATE, 0x00, 0x1A, KEYMAP, 0x00, 0x00]);
+        // Bytecode table: one CHIP-8 ROM of two bytes at 0x1C
+        bytes.extend(&[1, COSMAC_VIP, 0x00, 0x1C, 0x00, 0x02]);
+        bytes.extend(b"Pong\x00");
+        bytes.extend(&[0x00, 0x0F]);
+        bytes.extend(&[0x12, 0x00]);
+
+        let (rom, metadata) = parse(&bytes).unwrap();
+
+        assert_eq!(rom, vec!(0x12, 0x00));
+        assert_eq!(metadata.name, Some(String::from("Pong")));
+        assert_eq!(metadata.tick_rate, Some(15));
+        assert_eq!(metadata.ignored, vec!("keymap"));
+
+        let mut config = Config::default();
+        metadata.apply(&mut config);
+
+        assert_eq!(config.cpu_hz, 900);
+        assert!(config.quirks.vf_reset);
+    }
+
+    #[test]
+    fn test_truncated() {
+        assert!(parse(b"CBF\x00\x00\x20").is_err());
+    }
+}
@@ -0,0 +1,198 @@
+//! Running the machine on a thread of its own, apart from the window.
+//!
+//! The window thread sends the keys and control commands over a channel and receives a
+//! snapshot of the display whenever it changes, so a burst of cycles at a high CPU speed
+//! does not hold up the window events, and a slow window does not hold up the machine.
+//! The debugger and the overlays work on the machine directly, so they are not available
+//! while it runs on its own thread.
+
+use std::{thread, sync::mpsc::{self, Receiver, Sender, TryRecvError}};
+use std::time::{Duration, Instant};
+
+use crate::{Chip8, HEIGHT};
+use crate::events::Event;
+use crate::locale::{fill, Text};
+use crate::timing::Ticker;
+
+/// How long the machine thread sleeps between batches of cycles.
+const BATCH_TIME: Duration = Duration::from_millis(2);
+/// Longest stretch of time a batch catches up on, when the thread was not scheduled.
+const MAX_CATCH_UP: Duration = Duration::from_millis(250);
+
+/// What the window thread asks of the machine.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Keys([bool; 16]),
+    Pause,
+    Resume,
+}
+
+/// The display as it was when it last changed, whether the buzzer sounds and whether the
+/// machine waits for a key.
+pub struct Frame {
+    pub rows: [u64; HEIGHT],
+    pub beeping: bool,
+    pub waiting_for_key: bool,
+}
+
+/// A machine running on its own thread. It stops once the handle is stopped or dropped.
+pub struct CoreThread {
+    commands: Sender<Command>,
+    frames: Receiver<Frame>,
+    handle: thread::JoinHandle<Chip8>,
+}
+
+impl CoreThread {
+    pub fn spawn(chip8: Chip8, cpu_hz: u32) -> CoreThread {
+        let (commands, command_receiver) = mpsc::channel();
+        let (frame_sender, frames) = mpsc::channel();
+        let handle = thread::spawn(move || run(chip8, cpu_hz, command_receiver, frame_sender));
+
+        CoreThread { commands, frames, handle }
+    }
+
+    pub fn send(&self, command: Command) {
+        // The thread only stops when asked to, or when it panicked, which `stop` reports
+        let _ = self.commands.send(command);
+    }
+
+    /// The most recent of the frames sent since the previous call, if any.
+    pub fn latest_frame(&self) -> Option<Frame> {
+        self.frames.try_iter().last()
+    }
+
+    /// Stop the thread, handing back the machine in the state it was left in.
+    pub fn stop(self) -> Result<Chip8, String> {
+        drop(self.commands);
+        self.handle.join().map_err(|_| String::from("The emulator thread panicked"))
+    }
+}
+
+fn run(mut chip8: Chip8, cpu_hz: u32, commands: Receiver<Command>, frames: Sender<Frame>)
+    -> Chip8
+{
+    let mut cpu_ticker = Ticker::new(cpu_hz);
+    let mut last = Instant::now();
+    let mut paused = false;
+    let mut beeping = false;
+    let mut waiting_for_key = false;
+    let events = chip8.events.subscribe();
+
+    loop {
+        loop {
+            match commands.try_recv() {
+                Ok(Command::Keys(keys)) => chip8.set_keys(keys),
+                Ok(Command::Pause) => paused = true,
+                Ok(Command::Resume) => paused = false,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return chip8,
+            }
+        }
+
+        let now = Instant::now();
+        let cycles = cpu_ticker.advance((now - last).min(MAX_CATCH_UP));
+        let timer_ticks = chip8.timers.due();
+        last = now;
+
+        // Time spent paused is not caught up afterwards, and the timers freeze along
+        if !paused {
+            for _ in 0..cycles {
+                chip8.cycle();
+
+                // The faults are logged by the machine, which goes on past unknown opcodes
+                if chip8.protection_fault.take().is_some() {
+                    paused = true;
+                    break;
+                }
+
+                if let Some(address) = chip8.stack_fault.take() {
+                    let fault = fill(Text::StackFault, &[&format!("{:#05X}", address)]);
+                    println!("{}", fill(Text::Pausing, &[&fault]));
+                    paused = true;
+                    break;
+                }
+
+                if let Some(error) = chip8.bounds_fault.take() {
+                    println!("{}", fill(Text::Pausing, &[&error]));
+                    paused = true;
+                    break;
+                }
+
+                if let Some(address) = chip8.strictness_fault.take() {
+                    let fault = fill(Text::StrictnessFault, &[&format!("{:#05X}", address)]);
+                    println!("{}", fill(Text::Pausing, &[&fault]));
+                    paused = true;
+                    break;
+                }
+
+                chip8.unknown_opcode.take();
+            }
+
+            for _ in 0..timer_ticks {
+                chip8.update_timers();
+            }
+        }
+
+        let mut changed = false;
+        for event in events.try_iter() {
+            match event {
+                Event::DisplayUpdated => changed = true,
+                Event::SoundStateChanged(sound) => {
+                    beeping = sound;
+                    changed = true;
+                },
+                _ => {},
+            }
+        }
+        if chip8.is_waiting_for_key() != waiting_for_key {
+            waiting_for_key = !waiting_for_key;
+            changed = true;
+        }
+
+        if changed {
+            let frame = Frame { rows: *chip8.display.front_rows(), beeping, waiting_for_key };
+            if frames.send(frame).is_err() {
+                return chip8;
+            }
+        }
+
+        thread::sleep(BATCH_TIME);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Wait for the thread to send a frame that passes the check
+    fn wait_for(core: &CoreThread, check: impl Fn(&Frame) -> bool) -> bool {
+        for _ in 0..500 {
+            if core.latest_frame().map_or(false, |frame| check(&frame)) {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(2));
+        }
+
+        false
+    }
+
+    #[test]
+    fn test_runs_on_its_own_thread() {
+        let mut chip8 = Chip8::new();
+        // LD I, 0x200; DRW V0, V0, 1; LD V1, K; LD ST, V1; JP 0x208
+        chip8.load_bytes(&[0xA2, 0x00, 0xD0, 0x01, 0xF1, 0x0A, 0xF1, 0x18, 0x12, 0x08]);
+
+        let core = CoreThread::spawn(chip8, 1000);
+        assert!(wait_for(&core, |frame| {
+            frame.rows[0] != 0 && !frame.beeping && frame.waiting_for_key
+        }));
+
+        let mut keys = [false; 16];
+        keys[9] = true;
+        core.send(Command::Keys(keys));
+        assert!(wait_for(&core, |frame| frame.beeping && !frame.waiting_for_key));
+
+        let chip8 = core.stop().unwrap();
+        assert_eq!(chip8.registers[1], 9);
+    }
+}
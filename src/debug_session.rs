@@ -0,0 +1,194 @@
+//! Debugger sessions: the breakpoints and watchpoints of a ROM, the panel that was shown and
+//! where the memory views were scrolled to, kept in its metadata directory so debugging
+//! picks up where it left off the next time the ROM is opened.
+//!
+//! A session is written as one setting per line:
+//!
+//! ```text
+//! break = Dxyn if V0 == 0x3F
+//! break = [0x2F0] != 3
+//! panel = sprites
+//! position = sprites 0x2A0
+//! ```
+//!
+//! Breakpoints keep their labels, which are resolved against the symbol file of the run
+//! that loads them.
+
+use std::{fmt, fs, io, path::{Path, PathBuf}};
+
+use crate::debugger::{parse_number, Breakpoint, Debugger};
+use crate::panels::Panels;
+use crate::symbols::SymbolTable;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct DebugSession {
+    pub breakpoints: Vec<Breakpoint>,
+    /// The name of the panel that was shown.
+    pub panel: Option<String>,
+    /// The addresses of the memory views, by panel name.
+    pub positions: Vec<(String, usize)>,
+}
+
+impl DebugSession {
+    pub fn parse(contents: &str) -> Result<DebugSession, String> {
+        let mut session = DebugSession::default();
+
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let idx = line.find('=').ok_or(format!("Expected 'key = value' in '{}'", line))?;
+            let value = line[idx + 1..].trim();
+
+            match line[..idx].trim() {
+                "break" => session.breakpoints.push(value.parse()?),
+                "panel" => session.panel = Some(value.to_string()),
+                "position" => {
+                    let mut words = value.split_whitespace();
+                    match (words.next(), words.next().map(parse_number), words.next()) {
+                        (Some(name), Some(Ok(address)), None) => {
+                            session.positions.push((name.to_string(), address as usize));
+                        },
+                        _ => return Err(format!("Expected 'panel address' in '{}'", line)),
+                    }
+                },
+                key => return Err(format!("Unknown setting '{}'", key)),
+            }
+        }
+
+        Ok(session)
+    }
+
+    fn path(dir: &Path, rom_name: &str) -> PathBuf {
+        dir.join(format!("{}.debug", rom_name))
+    }
+
+    /// The session stored for a ROM, an empty one if there is none or it does not parse.
+    pub fn load(dir: &Path, rom_name: &str) -> DebugSession {
+        let contents = match fs::read_to_string(DebugSession::path(dir, rom_name)) {
+            Ok(contents) => contents,
+            Err(_) => return DebugSession::default(),
+        };
+
+        DebugSession::parse(&contents).unwrap_or_else(|e| {
+            println!("Ignoring the debugger session of {}: {}", rom_name, e);
+            DebugSession::default()
+        })
+    }
+
+    pub fn store(&self, dir: &Path, rom_name: &str) -> io::Result<()> {
+        fs::write(DebugSession::path(dir, rom_name), self.to_string())
+    }
+
+    /// The session of the debugger and the panels as they are.
+    pub fn capture(debugger: &Debugger, panels: &Panels) -> DebugSession {
+        DebugSession {
+            breakpoints: debugger.breakpoints().to_vec(),
+            panel: Some(panels.active().to_string()),
+            positions: panels.positions().into_iter()
+                .map(|(name, address)| (name.to_string(), address))
+                .collect(),
+        }
+    }
+
+    /// Add the breakpoints of the session the debugger does not have yet, and show the
+    /// panels as they were. Breakpoints with labels the symbol file lacks are skipped.
+    pub fn restore(self, debugger: &mut Debugger, panels: &mut Panels, symbols: &SymbolTable) {
+        for mut breakpoint in self.breakpoints {
+            let source = breakpoint.to_string();
+            if debugger.breakpoints().iter().any(|b| b.to_string() == source) {
+                continue;
+            }
+
+            match breakpoint.resolve(symbols) {
+                Ok(()) => debugger.add_breakpoint(breakpoint),
+                Err(e) => println!("Skipping breakpoint '{}': {}", source, e),
+            }
+        }
+
+        if let Some(panel) = &self.panel {
+            panels.show(panel);
+        }
+        for (name, address) in &self.positions {
+            panels.set_position(name, *address);
+        }
+    }
+}
+
+impl fmt::Display for DebugSession {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for breakpoint in &self.breakpoints {
+            writeln!(f, "break = {}", breakpoint)?;
+        }
+        if let Some(panel) = &self.panel {
+            writeln!(f, "panel = {}", panel)?;
+        }
+        for (name, address) in &self.positions {
+            writeln!(f, "position = {} {:#05X}", name, address)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+    use crate::layout::Layout;
+
+    #[test]
+    fn test_parse() {
+        let contents = "# Pong\nbreak = Dxyn if V0 == 0x3F\nbreak = @draw\n\
+            panel = sprites\nposition = sprites 0x2A0\n";
+        let session = DebugSession::parse(contents).unwrap();
+
+        assert_eq!(session.breakpoints, vec!("Dxyn if V0 == 0x3F".parse().unwrap(),
+            "@draw".parse().unwrap()));
+        assert_eq!(session.panel.as_deref(), Some("sprites"));
+        assert_eq!(session.positions, vec!((String::from("sprites"), 0x2A0)));
+        assert_eq!(DebugSession::parse(&session.to_string()), Ok(session));
+
+        assert!(DebugSession::parse("break Dxyn").is_err());
+        assert!(DebugSession::parse("position = sprites").is_err());
+        assert!(DebugSession::parse("layout = wide").is_err());
+    }
+
+    #[test]
+    fn test_capture_and_restore() {
+        let mut chip8 = Chip8::new();
+        let keymap = Layout::Qwerty.keymap();
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint("[0x2F0] != 3".parse().unwrap());
+        let mut panels = Panels::new(SymbolTable::default(), keymap, Vec::new(), &mut chip8);
+        panels.show("sprites");
+        panels.set_position("sprites", 0x2A0);
+
+        let session = DebugSession::parse(&DebugSession::capture(&debugger, &panels).to_string())
+            .unwrap();
+
+        let mut restored = Debugger::new();
+        restored.add_breakpoint("[0x2F0] != 3".parse().unwrap());
+        let mut restored_panels = Panels::new(SymbolTable::default(), keymap, Vec::new(),
+            &mut chip8);
+        session.restore(&mut restored, &mut restored_panels, &SymbolTable::default());
+
+        // Breakpoints given on the command line as well are not added twice
+        assert_eq!(restored.breakpoints(), debugger.breakpoints());
+        assert_eq!(restored_panels.active(), "sprites");
+        assert_eq!(restored_panels.positions(), vec!(("sprites", 0x2A0)));
+    }
+
+    #[test]
+    fn test_skips_unknown_labels() {
+        let mut chip8 = Chip8::new();
+        let mut debugger = Debugger::new();
+        let mut panels = Panels::new(SymbolTable::default(), Layout::Qwerty.keymap(), Vec::new(),
+            &mut chip8);
+
+        let session = DebugSession::parse("break = @draw\nbreak = Dxyn").unwrap();
+        session.restore(&mut debugger, &mut panels, &SymbolTable::default());
+        assert_eq!(debugger.breakpoints(), &["Dxyn".parse().unwrap()]);
+    }
+}
@@ -0,0 +1,355 @@
+//! Expressions over the machine state, such as `V3 + V4`, `mem[I]` or
+//! `mem[0x300] * 10 + mem[0x301]`, for watches and the conditions of breakpoints.
+//!
+//! An expression combines values with `+`, `-`, `*`, `/`, `%`, `&`, `|` and `^`, which
+//! bind as in C, and parentheses. The values are numbers, the registers `V0` to `VF`,
+//! `I`, `PC`, `DT`, `ST`, labels from the symbol file and bytes of memory, written as
+//! `mem[address]` or `[address]`. Arithmetic is on 16 bits and wraps around.
+
+use std::{fmt, str::FromStr};
+
+use crate::{Chip8, MEMORY};
+use crate::symbols::SymbolTable;
+use super::parse_number;
+
+/// A value that can be read from the machine.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Operand {
+    Register(usize),
+    I,
+    Pc,
+    DelayTimer,
+    SoundTimer,
+    Literal(u16),
+    /// A label from the symbol file, replaced by its address by `Expression::resolve`.
+    Label(String),
+}
+
+impl Operand {
+    fn evaluate(&self, chip8: &Chip8) -> u16 {
+        match *self {
+            Operand::Register(v_x) => chip8.registers[v_x] as u16,
+            Operand::I => chip8.i,
+            Operand::Pc => chip8.pc,
+            Operand::DelayTimer => chip8.timers.delay as u16,
+            Operand::SoundTimer => chip8.timers.sound as u16,
+            Operand::Literal(value) => value,
+            Operand::Label(ref label) => unreachable!("Label '{}' was not resolved", label),
+        }
+    }
+
+    fn parse(word: &str) -> Result<Operand, String> {
+        let upper = word.to_uppercase();
+
+        let operand = match upper.as_str() {
+            "I" => Operand::I,
+            "PC" => Operand::Pc,
+            "DT" => Operand::DelayTimer,
+            "ST" => Operand::SoundTimer,
+            _ if upper.starts_with('V') && upper.len() == 2 => {
+                let v_x = u8::from_str_radix(&upper[1..], 16)
+                    .map_err(|_| format!("Invalid register '{}'", word))?;
+
+                Operand::Register(v_x as usize)
+            },
+            _ if word.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') => {
+                Operand::Label(word.to_string())
+            },
+            _ => Operand::Literal(parse_number(word)?),
+        };
+
+        Ok(operand)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    And,
+    Or,
+    Xor,
+}
+
+impl Operator {
+    fn from_char(c: char) -> Option<Operator> {
+        match c {
+            '+' => Some(Operator::Add),
+            '-' => Some(Operator::Subtract),
+            '*' => Some(Operator::Multiply),
+            '/' => Some(Operator::Divide),
+            '%' => Some(Operator::Remainder),
+            '&' => Some(Operator::And),
+            '|' => Some(Operator::Or),
+            '^' => Some(Operator::Xor),
+            _ => None,
+        }
+    }
+
+    /// How tightly the operator binds, higher first.
+    fn precedence(self) -> u8 {
+        match self {
+            Operator::Or => 0,
+            Operator::Xor => 1,
+            Operator::And => 2,
+            Operator::Add | Operator::Subtract => 3,
+            Operator::Multiply | Operator::Divide | Operator::Remainder => 4,
+        }
+    }
+
+    /// The result, if there is one: dividing by zero has none.
+    fn apply(self, left: u16, right: u16) -> Option<u16> {
+        match self {
+            Operator::Add => Some(left.wrapping_add(right)),
+            Operator::Subtract => Some(left.wrapping_sub(right)),
+            Operator::Multiply => Some(left.wrapping_mul(right)),
+            Operator::Divide => left.checked_div(right),
+            Operator::Remainder => left.checked_rem(right),
+            Operator::And => Some(left & right),
+            Operator::Or => Some(left | right),
+            Operator::Xor => Some(left ^ right),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expression {
+    Value(Operand),
+    /// The byte at the address the expression evaluates to.
+    Memory(Box<Expression>),
+    Binary(Box<Expression>, Operator, Box<Expression>),
+}
+
+impl Expression {
+    /// The value of the expression, or `None` when it divides by zero.
+    pub fn evaluate(&self, chip8: &Chip8) -> Option<u16> {
+        match self {
+            Expression::Value(operand) => Some(operand.evaluate(chip8)),
+            Expression::Memory(address) => {
+                let address = address.evaluate(chip8)? as usize;
+                Some(chip8.memory[address % MEMORY] as u16)
+            },
+            Expression::Binary(left, operator, right) => {
+                operator.apply(left.evaluate(chip8)?, right.evaluate(chip8)?)
+            },
+        }
+    }
+
+    /// Replace the labels by their addresses.
+    pub fn resolve(&mut self, symbols: &SymbolTable) -> Result<(), String> {
+        match self {
+            Expression::Value(operand) => {
+                if let Operand::Label(label) = operand {
+                    let address = symbols.address_of(label)
+                        .ok_or_else(|| format!("Unknown label '{}'", label))?;
+                    *operand = Operand::Literal(address);
+                }
+            },
+            Expression::Memory(address) => address.resolve(symbols)?,
+            Expression::Binary(left, _, right) => {
+                left.resolve(symbols)?;
+                right.resolve(symbols)?;
+            },
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Word(String),
+    Operator(Operator),
+    Open(char),
+    Close(char),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphanumeric() || **c == '_') {
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+        } else {
+            chars.next();
+            tokens.push(match c {
+                '(' | '[' => Token::Open(c),
+                ')' | ']' => Token::Close(c),
+                _ => Token::Operator(Operator::from_char(c)
+                    .ok_or_else(|| format!("Unexpected '{}' in expression", c))?),
+            });
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    /// An expression of operators that bind at least as tightly as `precedence`.
+    fn expression(&mut self, precedence: u8) -> Result<Expression, String> {
+        let mut left = self.primary()?;
+
+        while let Some(&Token::Operator(operator)) = self.peek() {
+            if operator.precedence() < precedence {
+                break;
+            }
+
+            self.next();
+            let right = self.expression(operator.precedence() + 1)?;
+            left = Expression::Binary(Box::new(left), operator, Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    fn primary(&mut self) -> Result<Expression, String> {
+        match self.next() {
+            Some(Token::Word(ref word)) if word.eq_ignore_ascii_case("mem") => {
+                match self.next() {
+                    Some(Token::Open('[')) => self.enclosed(']').map(Box::new)
+                        .map(Expression::Memory),
+                    _ => Err(String::from("Expected '[' after mem")),
+                }
+            },
+            Some(Token::Word(word)) => Ok(Expression::Value(Operand::parse(&word)?)),
+            Some(Token::Open('[')) => self.enclosed(']').map(Box::new).map(Expression::Memory),
+            Some(Token::Open(_)) => self.enclosed(')'),
+            Some(token) => Err(format!("Unexpected {:?} in expression", token)),
+            None => Err(String::from("Expression ends too early")),
+        }
+    }
+
+    /// The expression up to the closing bracket.
+    fn enclosed(&mut self, close: char) -> Result<Expression, String> {
+        let expression = self.expression(0)?;
+
+        match self.next() {
+            Some(Token::Close(c)) if c == close => Ok(expression),
+            _ => Err(format!("Expected '{}'", close)),
+        }
+    }
+}
+
+impl FromStr for Expression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Expression, String> {
+        let mut parser = Parser { tokens: tokenize(s)?, position: 0 };
+        let expression = parser.expression(0)?;
+
+        match parser.peek() {
+            None => Ok(expression),
+            Some(token) => Err(format!("Unexpected {:?} in expression '{}'", token, s.trim())),
+        }
+    }
+}
+
+/// An expression shown with its value in the watch panel, see `panels`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Watch {
+    expression: Expression,
+    source: String,
+}
+
+impl Watch {
+    pub fn evaluate(&self, chip8: &Chip8) -> Option<u16> {
+        self.expression.evaluate(chip8)
+    }
+
+    pub fn resolve(&mut self, symbols: &SymbolTable) -> Result<(), String> {
+        self.expression.resolve(symbols)
+    }
+}
+
+impl FromStr for Watch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Watch, String> {
+        Ok(Watch { expression: s.parse()?, source: s.trim().to_string() })
+    }
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(s: &str, chip8: &Chip8) -> Option<u16> {
+        s.parse::<Expression>().unwrap().evaluate(chip8)
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let mut chip8 = Chip8::new();
+        chip8.registers[3] = 7;
+        chip8.registers[4] = 5;
+        chip8.i = 0x300;
+        chip8.memory[0x300] = 4;
+        chip8.memory[0x301] = 2;
+
+        assert_eq!(evaluate("V3 + V4", &chip8), Some(12));
+        assert_eq!(evaluate("mem[I]", &chip8), Some(4));
+        assert_eq!(evaluate("mem[0x300] * 10 + mem[0x301]", &chip8), Some(42));
+        assert_eq!(evaluate("[I + 1]", &chip8), Some(2));
+        assert_eq!(evaluate("(V3 + V4) * 2 - 1", &chip8), Some(23));
+        assert_eq!(evaluate("V3 - V4 - 1", &chip8), Some(1));
+        assert_eq!(evaluate("V3 & 3 | 8", &chip8), Some(11));
+        assert_eq!(evaluate("V4 - V3", &chip8), Some(0xFFFE));
+        assert_eq!(evaluate("V3 / (V4 - 5)", &chip8), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!("V3 +".parse::<Expression>().is_err());
+        assert!("mem[I".parse::<Expression>().is_err());
+        assert!("(V3))".parse::<Expression>().is_err());
+        assert!("V3 $ 2".parse::<Expression>().is_err());
+        assert!("VG".parse::<Expression>().is_err());
+    }
+
+    #[test]
+    fn test_resolve_labels() {
+        let symbols = SymbolTable::parse("label score 0x2F0").unwrap();
+        let mut chip8 = Chip8::new();
+        chip8.memory[0x2F1] = 9;
+
+        let mut watch: Watch = " mem[score + 1] ".parse().unwrap();
+        watch.resolve(&symbols).unwrap();
+        assert_eq!(watch.evaluate(&chip8), Some(9));
+        assert_eq!(watch.to_string(), "mem[score + 1]");
+
+        let mut unknown: Watch = "mem[lives]".parse().unwrap();
+        assert!(unknown.resolve(&symbols).is_err());
+    }
+}
@@ -0,0 +1,531 @@
+//! Breakpoints, single stepping and undoing steps.
+
+// Only the HTTP server takes requests so far
+#[cfg_attr(not(feature = "http"), allow(dead_code))]
+pub mod protocol;
+pub mod expression;
+
+use std::{fmt, collections::VecDeque, str::FromStr};
+
+use crate::Chip8;
+use crate::savestate::SaveState;
+use crate::symbols::SymbolTable;
+use self::expression::Expression;
+
+/// Number of single steps that can be undone.
+const UNDO_DEPTH: usize = 256;
+
+/// An opcode pattern such as `Dxyn` or `Fx0A`.
+///
+/// Hexadecimal digits must match exactly, while the placeholders `x`, `y`, `n` and `k`
+/// match any nibble.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct OpcodePattern {
+    mask: u16,
+    value: u16,
+}
+
+impl OpcodePattern {
+    pub fn matches(&self, opcode: u16) -> bool {
+        opcode & self.mask == self.value
+    }
+}
+
+impl FromStr for OpcodePattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.chars().count() != 4 {
+            return Err(format!("Opcode pattern '{}' must be four characters long", s));
+        }
+
+        let mut mask = 0;
+        let mut value = 0;
+
+        for c in s.chars() {
+            mask <<= 4;
+            value <<= 4;
+
+            match c {
+                'x' | 'y' | 'n' | 'k' => {},
+                _ => match c.to_digit(16) {
+                    Some(digit) => {
+                        mask |= 0xF;
+                        value |= digit as u16;
+                    },
+                    None => return Err(format!("Invalid character '{}' in opcode pattern", c)),
+                },
+            }
+        }
+
+        Ok(OpcodePattern { mask, value })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+/// A comparison between two expressions, for example `V0 == 0x3F` or `V1 + V2 > 10`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Condition {
+    left: Expression,
+    comparison: Comparison,
+    right: Expression,
+}
+
+impl Condition {
+    /// Whether the condition holds. It does not when either side divides by zero.
+    pub fn evaluate(&self, chip8: &Chip8) -> bool {
+        let (left, right) = match (self.left.evaluate(chip8), self.right.evaluate(chip8)) {
+            (Some(left), Some(right)) => (left, right),
+            _ => return false,
+        };
+
+        match self.comparison {
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+            Comparison::Less => left < right,
+            Comparison::LessEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterEqual => left >= right,
+        }
+    }
+}
+
+impl Condition {
+    /// Replace the labels by their addresses.
+    pub fn resolve(&mut self, symbols: &SymbolTable) -> Result<(), String> {
+        self.left.resolve(symbols)?;
+        self.right.resolve(symbols)
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let idx = s.find(|c| "=!<>".contains(c))
+            .ok_or_else(|| format!("Condition '{}' must have the form 'a == b'", s))?;
+
+        let (comparison, length) = match &s[idx..] {
+            rest if rest.starts_with("==") => (Comparison::Equal, 2),
+            rest if rest.starts_with("!=") => (Comparison::NotEqual, 2),
+            rest if rest.starts_with("<=") => (Comparison::LessEqual, 2),
+            rest if rest.starts_with(">=") => (Comparison::GreaterEqual, 2),
+            rest if rest.starts_with('<') => (Comparison::Less, 1),
+            rest if rest.starts_with('>') => (Comparison::Greater, 1),
+            rest => return Err(format!("Unknown comparison '{}'",
+                rest.split_whitespace().next().unwrap_or(rest))),
+        };
+
+        Ok(Condition {
+            left: s[..idx].parse()?,
+            comparison,
+            right: s[idx + length..].parse()?,
+        })
+    }
+}
+
+/// Named groups of opcode patterns that can be used in place of a single pattern.
+///
+/// `mode-switch` covers the SCHIP resolution switches (00FE, 00FF) and the XO-CHIP plane
+/// selection (Fn01), to find where a ROM changes how it draws.
+fn pattern_group(name: &str) -> Option<&'static [&'static str]> {
+    match name {
+        "mode-switch" => Some(&["00FE", "00FF", "Fx01"]),
+        _ => None,
+    }
+}
+
+fn parse_patterns(s: &str) -> Result<Vec<OpcodePattern>, String> {
+    match pattern_group(s) {
+        Some(group) => group.iter().map(|pattern| pattern.parse()).collect(),
+        None => Ok(vec!(s.parse()?)),
+    }
+}
+
+/// A breakpoint that triggers on an opcode pattern, a condition, or both.
+///
+/// Breakpoints are written as `Dxyn`, `V0 == 0x3F` or `Dxyn if V0 == 0x3F`. The pattern
+/// may also be a named group, see `pattern_group`. Conditions can use labels from the symbol
+/// file, and `@label` is short for `PC == label`. A condition on memory, such as
+/// `[0x2F0] != 3`, acts as a watchpoint. Both sides of a condition are expressions, see
+/// `expression`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Breakpoint {
+    // Any of these patterns must match, or none are given
+    patterns: Vec<OpcodePattern>,
+    condition: Option<Condition>,
+    source: String,
+}
+
+impl Breakpoint {
+    pub fn matches(&self, chip8: &Chip8, opcode: u16) -> bool {
+        let pattern = self.patterns.is_empty() || self.patterns.iter().any(|p| p.matches(opcode));
+        let condition = self.condition.as_ref().map_or(true, |c| c.evaluate(chip8));
+
+        pattern && condition
+    }
+
+    /// Replace the labels in the condition by their addresses.
+    pub fn resolve(&mut self, symbols: &SymbolTable) -> Result<(), String> {
+        if let Some(condition) = self.condition.as_mut() {
+            condition.resolve(symbols)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Breakpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if s.starts_with('@') {
+            let condition = format!("PC == {}", &s[1..]).parse()?;
+
+            return Ok(Breakpoint {
+                patterns: Vec::new(),
+                condition: Some(condition),
+                source: s.to_string(),
+            });
+        }
+
+        let (patterns, condition) = match s.find(" if ") {
+            Some(idx) => (parse_patterns(&s[..idx])?, Some(s[idx + 4..].parse()?)),
+            None if s.contains(' ') => (Vec::new(), Some(s.parse()?)),
+            None => (parse_patterns(s)?, None),
+        };
+
+        Ok(Breakpoint {
+            patterns,
+            condition,
+            source: s.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// How long to run before breaking again, such as `500 cycles` or `412 frames`. A bare
+/// number counts cycles, which are the instructions executed.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RunBudget {
+    Cycles(u64),
+    Frames(u64),
+}
+
+impl FromStr for RunBudget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let count = |word: &str| word.parse().map_err(|_| format!("Invalid count '{}'", word));
+
+        match words.as_slice() {
+            [n] | [n, "cycles"] | [n, "cycle"] => Ok(RunBudget::Cycles(count(n)?)),
+            [n, "frames"] | [n, "frame"] => Ok(RunBudget::Frames(count(n)?)),
+            _ => Err(format!("Expected a number of cycles or frames, not '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for RunBudget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunBudget::Cycles(n) => write!(f, "{} cycles", n),
+            RunBudget::Frames(n) => write!(f, "{} frames", n),
+        }
+    }
+}
+
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    pub paused: bool,
+
+    // Set after resuming, so the breakpoint that paused execution does not trigger again
+    skip_check: bool,
+    step_requested: bool,
+    // Snapshots taken before every single step, the most recent last
+    undo: VecDeque<SaveState>,
+    // What is left of the budget of the current run, if it has one
+    budget: Option<RunBudget>,
+    // Frames left that the keys pressed through `protocol` are held down
+    held_keys: [u32; 16],
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger {
+            breakpoints: Vec::new(),
+            paused: false,
+            skip_check: false,
+            step_requested: false,
+            undo: VecDeque::new(),
+            budget: None,
+            held_keys: [0; 16],
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Check the decoded opcode against all breakpoints, pausing execution on a hit.
+    pub fn should_break(&mut self, chip8: &Chip8, opcode: u16) -> bool {
+        if self.skip_check {
+            self.skip_check = false;
+            return false;
+        }
+
+        match self.breakpoints.iter().find(|b| b.matches(chip8, opcode)) {
+            Some(breakpoint) => {
+                println!("Breakpoint '{}' hit at {:#X?} ({:#X?})", breakpoint, chip8.pc, opcode);
+                self.paused = true;
+
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Resume execution. Steps can no longer be undone once the machine has run freely.
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.skip_check = true;
+        self.undo.clear();
+        self.budget = None;
+    }
+
+    /// Resume execution, breaking again once the budget is spent.
+    pub fn run_for(&mut self, budget: RunBudget) {
+        self.resume();
+        self.budget = Some(budget);
+    }
+
+    /// Count an instruction that is about to execute against a budget of cycles. Returns
+    /// true and pauses when the budget is spent, so the instruction does not execute.
+    pub fn spend_cycle(&mut self, chip8: &Chip8) -> bool {
+        match self.budget {
+            Some(RunBudget::Cycles(0)) => {
+                println!("Ran the requested cycles, pausing at {:#X?}", chip8.pc);
+                self.budget = None;
+                self.paused = true;
+
+                true
+            },
+            Some(RunBudget::Cycles(n)) => {
+                self.budget = Some(RunBudget::Cycles(n - 1));
+                false
+            },
+            _ => false,
+        }
+    }
+
+    /// Count a finished frame against a budget of frames, pausing when it is spent.
+    pub fn spend_frame(&mut self, chip8: &Chip8) {
+        if let Some(RunBudget::Frames(n)) = self.budget {
+            if n <= 1 {
+                println!("Ran the requested frames, pausing at {:#X?}", chip8.pc);
+                self.budget = None;
+                self.paused = true;
+            } else {
+                self.budget = Some(RunBudget::Frames(n - 1));
+            }
+        }
+    }
+
+    /// Hold down a key for a number of frames, on top of the keys pressed on the host.
+    pub fn press_key(&mut self, key: usize, frames: u32) {
+        self.held_keys[key] = frames;
+    }
+
+    /// The keys held down in a frame, counting the frame off.
+    pub fn held_keys(&mut self) -> [bool; 16] {
+        let mut keys = [false; 16];
+        for (down, held) in keys.iter_mut().zip(self.held_keys.iter_mut()) {
+            if *held > 0 {
+                *down = true;
+                *held -= 1;
+            }
+        }
+
+        keys
+    }
+
+    /// Ask for a single instruction to be executed while paused.
+    pub fn request_step(&mut self) {
+        self.step_requested = true;
+    }
+
+    /// Whether a single step was requested, clearing the request.
+    pub fn take_step(&mut self) -> bool {
+        let step = self.paused && self.step_requested;
+        self.step_requested = false;
+
+        step
+    }
+
+    /// Snapshot the machine before a single step, so `step_back` can undo it.
+    pub fn record_step(&mut self, chip8: &Chip8) {
+        if self.undo.len() == UNDO_DEPTH {
+            self.undo.pop_front();
+        }
+
+        self.undo.push_back(SaveState::capture(chip8));
+    }
+
+    /// Undo the most recent single step, restoring the registers, memory and display.
+    /// Returns false when there is no step to undo.
+    pub fn step_back(&mut self, chip8: &mut Chip8) -> bool {
+        match self.undo.pop_back() {
+            Some(state) => {
+                state.restore(chip8);
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+pub fn parse_number(s: &str) -> Result<u16, String> {
+    let result = if s.starts_with("0x") || s.starts_with("0X") {
+        u16::from_str_radix(&s[2..], 16)
+    } else {
+        s.parse()
+    };
+
+    result.map_err(|_| format!("Invalid number '{}'", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opcode_pattern() {
+        let pattern: OpcodePattern = "Fx0A".parse().unwrap();
+
+        assert!(pattern.matches(0xF30A));
+        assert!(!pattern.matches(0xF307));
+    }
+
+    #[test]
+    fn test_breakpoint_condition() {
+        let breakpoint: Breakpoint = "Dxyn if V0 == 0x3F".parse().unwrap();
+        let mut chip8 = Chip8::new();
+
+        assert!(!breakpoint.matches(&chip8, 0xD125));
+
+        chip8.registers[0] = 0x3F;
+
+        assert!(breakpoint.matches(&chip8, 0xD125));
+        assert!(!breakpoint.matches(&chip8, 0x6125));
+    }
+
+    #[test]
+    fn test_memory_watchpoint() {
+        let breakpoint: Breakpoint = "[0x2F0] != 3".parse().unwrap();
+        let mut chip8 = Chip8::new();
+        chip8.memory[0x2F0] = 3;
+
+        assert!(!breakpoint.matches(&chip8, 0x6000));
+        chip8.memory[0x2F0] = 2;
+        assert!(breakpoint.matches(&chip8, 0x6000));
+    }
+
+    #[test]
+    fn test_label_breakpoint() {
+        let symbols = SymbolTable::parse("label draw 0x20A").unwrap();
+        let mut chip8 = Chip8::new();
+
+        let mut breakpoint: Breakpoint = "@draw".parse().unwrap();
+        breakpoint.resolve(&symbols).unwrap();
+
+        assert!(!breakpoint.matches(&chip8, 0x00E0));
+        chip8.pc = 0x20A;
+        assert!(breakpoint.matches(&chip8, 0x00E0));
+
+        let mut unknown: Breakpoint = "I == sprites".parse().unwrap();
+        assert!(unknown.resolve(&symbols).is_err());
+    }
+
+    #[test]
+    fn test_mode_switch_breakpoint() {
+        let breakpoint: Breakpoint = "mode-switch".parse().unwrap();
+        let chip8 = Chip8::new();
+
+        assert!(breakpoint.matches(&chip8, 0x00FE));
+        assert!(breakpoint.matches(&chip8, 0x00FF));
+        assert!(breakpoint.matches(&chip8, 0xF201));
+        assert!(!breakpoint.matches(&chip8, 0x00E0));
+        assert!(!breakpoint.matches(&chip8, 0xF20A));
+    }
+
+    #[test]
+    fn test_step_back() {
+        // LD V0, 0x05; LD I, 0x206; DRW V0, V0, 1
+        let mut chip8 = Chip8::new();
+        chip8.load_bytes(&[0x60, 0x05, 0xA2, 0x06, 0xD0, 0x01, 0xFF]);
+        let mut debugger = Debugger::new();
+        debugger.paused = true;
+
+        for _ in 0..3 {
+            debugger.record_step(&chip8);
+            chip8.cycle();
+        }
+        assert!(chip8.display.lit_count() > 0);
+
+        assert!(debugger.step_back(&mut chip8));
+        assert_eq!(chip8.pc, 0x204);
+        assert_eq!(chip8.display.lit_count(), 0);
+
+        assert!(debugger.step_back(&mut chip8));
+        assert!(debugger.step_back(&mut chip8));
+        assert_eq!(chip8.registers[0], 0);
+        assert!(!debugger.step_back(&mut chip8));
+    }
+
+    #[test]
+    fn test_run_budget() {
+        assert_eq!("500".parse(), Ok(RunBudget::Cycles(500)));
+        assert_eq!("412 frames".parse(), Ok(RunBudget::Frames(412)));
+        assert!("412 seconds".parse::<RunBudget>().is_err());
+
+        let chip8 = Chip8::new();
+        let mut debugger = Debugger::new();
+
+        debugger.run_for(RunBudget::Cycles(2));
+        assert!(!debugger.spend_cycle(&chip8));
+        assert!(!debugger.spend_cycle(&chip8));
+        assert!(debugger.spend_cycle(&chip8));
+        assert!(debugger.paused);
+
+        debugger.run_for(RunBudget::Frames(2));
+        debugger.spend_frame(&chip8);
+        assert!(!debugger.paused && !debugger.spend_cycle(&chip8));
+        debugger.spend_frame(&chip8);
+        assert!(debugger.paused);
+    }
+}
@@ -0,0 +1,398 @@
+//! The commands a debugger front end can send to the emulator, and what it gets back.
+//!
+//! Front ends such as the HTTP server translate their own input into a `Request` and
+//! present the `Response`, so every front end offers the same commands and a new one only
+//! has to deal with its own transport. Requests can also be typed as text commands, such
+//! as `dump 0x200 0x400 sprites.bin`.
+
+use std::{fs, str::FromStr};
+
+use crate::{Chip8, HEIGHT, MEMORY, WIDTH};
+use crate::gif::GifEncoder;
+use crate::savestate::SaveState;
+use crate::screen::Buffer;
+use super::{parse_number, Breakpoint, Debugger, RunBudget};
+
+// How long `press-key` holds a key without a number of frames, long enough for ROMs that
+// only check the keys now and then
+const DEFAULT_PRESS_FRAMES: u32 = 6;
+const WHITE: u32 = 0xFFFFFF;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    /// Stop execution.
+    Pause,
+    /// Resume execution after a pause or a breakpoint.
+    Continue,
+    /// Execute a single instruction while paused.
+    Step,
+    /// Resume execution for a number of cycles or frames, then break.
+    Run(RunBudget),
+    /// Undo the most recent single step.
+    StepBack,
+    /// Restart the ROM.
+    Reset,
+    /// Read `length` bytes of memory from `start` on.
+    ReadMem { start: usize, length: usize },
+    SetBreakpoint(Breakpoint),
+    /// Read the registers, timers and stack.
+    Registers,
+    /// Write memory from `start` up to `end` to a file.
+    DumpMem { start: usize, end: usize, path: String },
+    /// Write the contents of a file into memory from `start` on, bypassing protection.
+    LoadMem { path: String, start: usize },
+    /// Write the display to a GIF file.
+    Screenshot { path: String },
+    /// Restore the machine from a save state file.
+    LoadState { path: String },
+    /// Hold down a key of the keypad for a number of frames.
+    PressKey { key: usize, frames: u32 },
+}
+
+impl Request {
+    /// The file the request reads or writes, if it touches one.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Request::DumpMem { path, .. } | Request::LoadMem { path, .. }
+                | Request::Screenshot { path } | Request::LoadState { path } => Some(path),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Request {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Request, String> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let address = |word: &str| parse_number(word).map(|address| address as usize);
+
+        let request = match words.as_slice() {
+            ["pause"] => Request::Pause,
+            ["continue"] => Request::Continue,
+            ["step"] => Request::Step,
+            ["step-back"] => Request::StepBack,
+            ["run", ..] => Request::Run(words[1..].join(" ").parse()?),
+            ["reset"] => Request::Reset,
+            ["registers"] => Request::Registers,
+            ["mem", start, length] =>
+                Request::ReadMem { start: address(start)?, length: address(length)? },
+            ["break", ..] => Request::SetBreakpoint(words[1..].join(" ").parse()?),
+            ["dump", start, end, path] => {
+                let (start, end) = (address(start)?, address(end)?);
+                if start >= end || end > MEMORY {
+                    return Err(format!("Invalid range {:#X}-{:#X}", start, end));
+                }
+
+                Request::DumpMem { start, end, path: path.to_string() }
+            },
+            ["load", path, start] =>
+                Request::LoadMem { path: path.to_string(), start: address(start)? },
+            ["screenshot", path] => Request::Screenshot { path: path.to_string() },
+            ["loadstate", path] => Request::LoadState { path: path.to_string() },
+            ["press-key", key] | ["press-key", key, _] => {
+                let key = match usize::from_str_radix(key, 16) {
+                    Ok(key) if key < 16 => key,
+                    _ => return Err(format!("Invalid key '{}', expected 0-F", key)),
+                };
+                let frames = match words.get(2) {
+                    Some(frames) => frames.parse()
+                        .map_err(|_| format!("Invalid number of frames '{}'", frames))?,
+                    None => DEFAULT_PRESS_FRAMES,
+                };
+
+                Request::PressKey { key, frames }
+            },
+            _ => return Err(format!("Unknown command '{}'", s.trim())),
+        };
+
+        Ok(request)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    /// Whether execution is paused now.
+    Paused(bool),
+    /// Memory from `start` on, cut off at the end of memory.
+    Memory { start: usize, bytes: Vec<u8> },
+    /// The breakpoint that was added.
+    BreakpointSet(String),
+    Registers(CpuState),
+    /// `length` bytes of memory were written to the file at `path`.
+    Dumped { path: String, length: usize },
+    /// `length` bytes were loaded into memory from `start` on.
+    Loaded { start: usize, length: usize },
+    /// The key is held down for this many frames.
+    KeyHeld { key: usize, frames: u32 },
+    Error(String),
+}
+
+/// The registers, timers and stack of the machine.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpuState {
+    pub pc: u16,
+    pub i: u16,
+    pub v: [u8; 16],
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    /// Return addresses, the oldest first.
+    pub stack: Vec<u16>,
+}
+
+impl CpuState {
+    pub fn capture(chip8: &Chip8) -> CpuState {
+        CpuState {
+            pc: chip8.pc,
+            i: chip8.i,
+            v: chip8.registers,
+            delay_timer: chip8.timers.delay,
+            sound_timer: chip8.timers.sound,
+            stack: chip8.stack.entries().to_vec(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"pc\":{},\"i\":{},\"sp\":{},\"delay_timer\":{},\"sound_timer\":{},\"v\":{:?},\"stack\":{:?}}}",
+            self.pc, self.i, self.stack.len(), self.delay_timer, self.sound_timer,
+            self.v, self.stack)
+    }
+}
+
+impl Response {
+    pub fn to_json(&self) -> String {
+        match self {
+            Response::Paused(paused) => format!("{{\"paused\":{}}}", paused),
+            Response::Memory { start, bytes } =>
+                format!("{{\"start\":{},\"bytes\":{:?}}}", start, bytes),
+            Response::BreakpointSet(breakpoint) =>
+                format!("{{\"breakpoint\":{:?}}}", breakpoint),
+            Response::Registers(state) => state.to_json(),
+            Response::Dumped { path, length } =>
+                format!("{{\"path\":{:?},\"length\":{}}}", path, length),
+            Response::Loaded { start, length } =>
+                format!("{{\"start\":{},\"length\":{}}}", start, length),
+            Response::KeyHeld { key, frames } =>
+                format!("{{\"key\":{},\"frames\":{}}}", key, frames),
+            Response::Error(e) => format!("{{\"error\":{:?}}}", e),
+        }
+    }
+}
+
+/// Carry out a request between two cycles of the main loop.
+pub fn execute(request: Request, chip8: &mut Chip8, debugger: &mut Debugger) -> Response {
+    match request {
+        Request::Pause => {
+            debugger.paused = true;
+            Response::Paused(true)
+        },
+        Request::Continue => {
+            debugger.resume();
+            Response::Paused(false)
+        },
+        Request::Run(budget) => {
+            debugger.run_for(budget);
+            Response::Paused(false)
+        },
+        // The step itself is taken by the main loop
+        Request::Step => {
+            debugger.request_step();
+            Response::Registers(CpuState::capture(chip8))
+        },
+        Request::StepBack => {
+            debugger.step_back(chip8);
+            Response::Registers(CpuState::capture(chip8))
+        },
+        Request::Reset => {
+            chip8.reset();
+            Response::Registers(CpuState::capture(chip8))
+        },
+        Request::ReadMem { start, length } => {
+            let start = start.min(MEMORY);
+            let end = start.saturating_add(length).min(MEMORY);
+
+            Response::Memory { start, bytes: chip8.memory[start..end].to_vec() }
+        },
+        Request::SetBreakpoint(breakpoint) => {
+            let source = breakpoint.to_string();
+            debugger.add_breakpoint(breakpoint);
+
+            Response::BreakpointSet(source)
+        },
+        Request::Registers => Response::Registers(CpuState::capture(chip8)),
+        Request::DumpMem { start, end, path } => match fs::write(&path, &chip8.memory[start..end]) {
+            Ok(()) => Response::Dumped { path, length: end - start },
+            Err(e) => Response::Error(format!("Could not write {}: {}", path, e)),
+        },
+        Request::LoadMem { path, start } => {
+            let bytes = match fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(e) => return Response::Error(format!("Could not read {}: {}", path, e)),
+            };
+            if start + bytes.len() > MEMORY {
+                return Response::Error(format!("{} does not fit in memory at {:#X}", path, start));
+            }
+
+            chip8.memory[start..start + bytes.len()].copy_from_slice(&bytes);
+            Response::Loaded { start, length: bytes.len() }
+        },
+        Request::Screenshot { path } => {
+            let pixels = (0..WIDTH * HEIGHT)
+                .map(|idx| if chip8.display.is_lit(idx % WIDTH, idx / WIDTH) { WHITE } else { 0 })
+                .collect();
+            let gif = GifEncoder::new(WIDTH, HEIGHT, &[0, WHITE])
+                .and_then(|mut encoder| {
+                    encoder.add_frame(&Buffer::new(WIDTH, HEIGHT, Some(pixels)), 0)?;
+                    Ok(encoder.finish())
+                });
+
+            match gif.and_then(|gif| fs::write(&path, &gif)
+                    .map(|()| gif.len())
+                    .map_err(|e| format!("Could not write {}: {}", path, e))) {
+                Ok(length) => Response::Dumped { path, length },
+                Err(e) => Response::Error(e),
+            }
+        },
+        Request::LoadState { path } => {
+            let state = fs::read(&path).and_then(|bytes| SaveState::from_bytes(&bytes));
+
+            match state {
+                Ok(state) => {
+                    state.restore(chip8);
+                    Response::Registers(CpuState::capture(chip8))
+                },
+                Err(e) => Response::Error(format!("Could not load state {}: {}", path, e)),
+            }
+        },
+        Request::PressKey { key, frames } => {
+            debugger.press_key(key, frames);
+            Response::KeyHeld { key, frames }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_mem_is_cut_off() {
+        let mut chip8 = Chip8::new();
+        let mut debugger = Debugger::new();
+        chip8.memory[0xFFF] = 7;
+
+        let response = execute(Request::ReadMem { start: 0xFFF, length: 4 }, &mut chip8,
+            &mut debugger);
+
+        assert_eq!(response, Response::Memory { start: 0xFFF, bytes: vec!(7) });
+        assert_eq!(response.to_json(), "{\"start\":4095,\"bytes\":[7]}");
+    }
+
+    #[test]
+    fn test_pause_and_set_breakpoint() {
+        let mut chip8 = Chip8::new();
+        let mut debugger = Debugger::new();
+
+        assert_eq!(execute(Request::Pause, &mut chip8, &mut debugger), Response::Paused(true));
+        assert!(debugger.paused);
+
+        let breakpoint = "Dxyn".parse().unwrap();
+        let response = execute(Request::SetBreakpoint(breakpoint), &mut chip8, &mut debugger);
+        assert_eq!(response.to_json(), "{\"breakpoint\":\"Dxyn\"}");
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!("step".parse(), Ok(Request::Step));
+        assert_eq!("run 412 frames".parse(), Ok(Request::Run(RunBudget::Frames(412))));
+        assert_eq!("dump 0x200 0x400 sprites.bin".parse(), Ok(Request::DumpMem {
+            start: 0x200,
+            end: 0x400,
+            path: String::from("sprites.bin"),
+        }));
+        assert_eq!("load test.bin 0x300".parse(), Ok(Request::LoadMem {
+            path: String::from("test.bin"),
+            start: 0x300,
+        }));
+        assert!("dump 0x400 0x200 sprites.bin".parse::<Request>().is_err());
+        assert!("dump 0x200 0x1001 sprites.bin".parse::<Request>().is_err());
+        assert!("fly".parse::<Request>().is_err());
+
+        assert_eq!("press-key F".parse(), Ok(Request::PressKey { key: 0xF, frames: 6 }));
+        assert_eq!("press-key 5 30".parse(), Ok(Request::PressKey { key: 5, frames: 30 }));
+        assert!("press-key 10".parse::<Request>().is_err());
+        assert_eq!("loadstate level2.c8s".parse(),
+            Ok(Request::LoadState { path: String::from("level2.c8s") }));
+    }
+
+    #[test]
+    fn test_press_key() {
+        let mut chip8 = Chip8::new();
+        let mut debugger = Debugger::new();
+
+        let response = execute(Request::PressKey { key: 5, frames: 2 }, &mut chip8, &mut debugger);
+        assert_eq!(response.to_json(), "{\"key\":5,\"frames\":2}");
+
+        // Only key 5 is down, for the two frames asked for
+        let mut held = [false; 16];
+        held[5] = true;
+        assert_eq!(debugger.held_keys(), held);
+        assert_eq!(debugger.held_keys(), held);
+        assert_eq!(debugger.held_keys(), [false; 16]);
+    }
+
+    #[test]
+    fn test_screenshot_and_load_state() {
+        let mut chip8 = Chip8::new();
+        let mut debugger = Debugger::new();
+        let dir = std::env::temp_dir();
+        let (gif, state) = (dir.join("protocol_test_shot.gif"), dir.join("protocol_test.c8s"));
+        let (gif, state) =
+            (gif.to_string_lossy().into_owned(), state.to_string_lossy().into_owned());
+
+        chip8.display.draw_sprite(0, 0, &[0xFF]);
+        match execute(Request::Screenshot { path: gif.clone() }, &mut chip8, &mut debugger) {
+            Response::Dumped { length, .. } => assert!(length > 0),
+            response => panic!("{:?}", response),
+        }
+        assert!(fs::read(&gif).unwrap().starts_with(b"GIF89a"));
+
+        chip8.registers[3] = 42;
+        fs::write(&state, SaveState::capture(&chip8).to_bytes()).unwrap();
+        chip8.registers[3] = 0;
+        match execute(Request::LoadState { path: state.clone() }, &mut chip8, &mut debugger) {
+            Response::Registers(registers) => assert_eq!(registers.v[3], 42),
+            response => panic!("{:?}", response),
+        }
+        assert!(matches!(execute(Request::LoadState { path: gif.clone() }, &mut chip8,
+            &mut debugger), Response::Error(_)));
+
+        fs::remove_file(&gif).unwrap();
+        fs::remove_file(&state).unwrap();
+    }
+
+    #[test]
+    fn test_dump_and_load() {
+        let mut chip8 = Chip8::new();
+        let mut debugger = Debugger::new();
+        let path = std::env::temp_dir().join("protocol_test_dump.bin");
+        let path = path.to_string_lossy().into_owned();
+        chip8.memory[0x200..0x204].copy_from_slice(&[1, 2, 3, 4]);
+
+        let dump = format!("dump 0x200 0x204 {}", path).parse().unwrap();
+        assert_eq!(execute(dump, &mut chip8, &mut debugger),
+            Response::Dumped { path: path.clone(), length: 4 });
+
+        let load = format!("load {} 0xFFC", path).parse().unwrap();
+        assert_eq!(execute(load, &mut chip8, &mut debugger),
+            Response::Loaded { start: 0xFFC, length: 4 });
+        assert_eq!(chip8.memory[0xFFC..], [1, 2, 3, 4]);
+
+        let load = format!("load {} 0xFFD", path).parse().unwrap();
+        assert_eq!(execute(load, &mut chip8, &mut debugger),
+            Response::Error(format!("{} does not fit in memory at 0xFFD", path)));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
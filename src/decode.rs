@@ -0,0 +1,231 @@
+//! The table the interpreter decodes opcodes with.
+//!
+//! `Chip8::cycle` executes the first instruction whose pattern matches the opcode, so the
+//! `caps` subcommand can print exactly the instructions a variant supports from the same
+//! table.
+
+use alloc::{format, string::String, vec::Vec};
+
+use crate::{ops, Chip8, MEMORY};
+use crate::disassembler::disassemble;
+use crate::variant::Variant;
+
+const USAGE: &str = "Usage: chip8 caps [--variant chip8|vip|schip|xochip|chip8x]";
+
+/// An instruction the interpreter can execute.
+pub struct Instruction {
+    /// The opcode with its operands as letters, e.g. `8xy4`.
+    pub pattern: &'static str,
+    pub mask: u16,
+    pub value: u16,
+    pub mnemonic: &'static str,
+    /// Only decoded when emulating CHIP-8X.
+    pub chip8x: bool,
+    /// The quirk the behaviour of the instruction depends on, by its command line name.
+    pub quirk: Option<&'static str>,
+    pub execute: fn(&mut Chip8, u16),
+}
+
+impl Instruction {
+    fn matches(&self, opcode: u16, chip8x: bool) -> bool {
+        opcode & self.mask == self.value && (chip8x || !self.chip8x)
+    }
+
+    /// Whether every opcode this instruction matches is taken by another one.
+    fn is_shadowed_by(&self, other: &Instruction) -> bool {
+        self.mask & other.mask == other.mask && self.value & other.mask == other.value
+    }
+}
+
+const fn op(pattern: &'static str, mask: u16, value: u16, mnemonic: &'static str,
+        execute: fn(&mut Chip8, u16)) -> Instruction {
+    Instruction { pattern, mask, value, mnemonic, chip8x: false, quirk: None, execute }
+}
+
+const fn chip8x_op(pattern: &'static str, mask: u16, value: u16, mnemonic: &'static str,
+        execute: fn(&mut Chip8, u16)) -> Instruction {
+    Instruction { pattern, mask, value, mnemonic, chip8x: true, quirk: None, execute }
+}
+
+const fn quirky_op(pattern: &'static str, mask: u16, value: u16, mnemonic: &'static str,
+        quirk: &'static str, execute: fn(&mut Chip8, u16)) -> Instruction {
+    Instruction { pattern, mask, value, mnemonic, chip8x: false, quirk: Some(quirk), execute }
+}
+
+/// Every instruction in the order they are tried, so the CHIP-8X ones come before the ones
+/// they take the place of.
+pub const INSTRUCTIONS: &[Instruction] = &[
+    chip8x_op("02A0", 0xFFFF, 0x02A0, "BGCYCLE", ops::cycle_background),
+    op("00E0", 0xF0FF, 0x00E0, "CLS", ops::cls_clear_display),
+    op("00EE", 0xF0FF, 0x00EE, "RET", ops::ret_return_from_subroutine),
+    op("00Cn", 0xFFF0, 0x00C0, "SCD n", ops::scd_scroll_down),
+    op("00Dn", 0xFFF0, 0x00D0, "SCU n", ops::scu_scroll_up),
+    op("00FB", 0xFFFF, 0x00FB, "SCR", ops::scr_scroll_right),
+    op("00FC", 0xFFFF, 0x00FC, "SCL", ops::scl_scroll_left),
+    op("1nnn", 0xF000, 0x1000, "JP nnn", ops::jp_jump_to_address),
+    op("2nnn", 0xF000, 0x2000, "CALL nnn", ops::call_subroutine),
+    op("3xkk", 0xF000, 0x3000, "SE Vx, kk", ops::se_register_byte),
+    op("4xkk", 0xF000, 0x4000, "SNE Vx, kk", ops::sne_skip_not_equal),
+    chip8x_op("5xy1", 0xF00F, 0x5001, "ADD Vx, Vy (nibbles)", ops::add_nibbles),
+    op("5xy0", 0xF000, 0x5000, "SE Vx, Vy", ops::se_registers),
+    op("6xkk", 0xF000, 0x6000, "LD Vx, kk", ops::ld_register_byte),
+    op("7xkk", 0xF000, 0x7000, "ADD Vx, kk", ops::add_register_byte),
+    op("8xy0", 0xF00F, 0x8000, "LD Vx, Vy", ops::ld_registers),
+    quirky_op("8xy1", 0xF00F, 0x8001, "OR Vx, Vy", "vf-reset", ops::or_registers),
+    quirky_op("8xy2", 0xF00F, 0x8002, "AND Vx, Vy", "vf-reset", ops::and_registers),
+    quirky_op("8xy3", 0xF00F, 0x8003, "XOR Vx, Vy", "vf-reset", ops::xor_registers),
+    op("8xy4", 0xF00F, 0x8004, "ADD Vx, Vy", ops::add_registers),
+    op("8xy5", 0xF00F, 0x8005, "SUB Vx, Vy", ops::sub_registers),
+    op("8xy6", 0xF00F, 0x8006, "SHR Vx, Vy", ops::shr_registers),
+    op("8xy7", 0xF00F, 0x8007, "SUBN Vx, Vy", ops::subn_registers),
+    op("8xyE", 0xF00F, 0x800E, "SHL Vx, Vy", ops::shl_registers),
+    op("9xy0", 0xF000, 0x9000, "SNE Vx, Vy", ops::sne_registers),
+    op("Annn", 0xF000, 0xA000, "LD I, nnn", ops::ld_i_byte),
+    chip8x_op("Bxyn", 0xF000, 0xB000, "COL Vx, Vy, n", ops::set_zone_color),
+    op("Bnnn", 0xF000, 0xB000, "JP V0, nnn", ops::jp_bnnn),
+    op("Cxkk", 0xF000, 0xC000, "RND Vx, kk", ops::rnd),
+    quirky_op("Dxyn", 0xF000, 0xD000, "DRW Vx, Vy, n", "display-wait", ops::drw_draw_sprite),
+    op("Ex9E", 0xF0FF, 0xE09E, "SKP Vx", ops::skp_skip_pressed),
+    op("ExA1", 0xF0FF, 0xE0A1, "SKNP Vx", ops::sknp_skip_not_pressed),
+    chip8x_op("ExF2", 0xF0FF, 0xE0F2, "SKP2 Vx", ops::skp_second_keypad),
+    chip8x_op("ExF5", 0xF0FF, 0xE0F5, "SKNP2 Vx", ops::sknp_second_keypad),
+    op("F002", 0xFFFF, 0xF002, "AUDIO", ops::ld_audio_pattern),
+    op("Fx07", 0xF0FF, 0xF007, "LD Vx, DT", ops::ld_get_delay_timer),
+    quirky_op("Fx0A", 0xF0FF, 0xF00A, "LD Vx, K", "key-release", ops::ld_wait_for_key),
+    op("Fx15", 0xF0FF, 0xF015, "LD DT, Vx", ops::ld_set_delay_timer),
+    op("Fx18", 0xF0FF, 0xF018, "LD ST, Vx", ops::ld_set_sound_timer),
+    op("Fx1E", 0xF0FF, 0xF01E, "ADD I, Vx", ops::add_to_i),
+    op("Fx29", 0xF0FF, 0xF029, "LD F, Vx", ops::ld_i_to_sprite),
+    op("Fx33", 0xF0FF, 0xF033, "LD B, Vx", ops::ld_bcd),
+    op("Fx3A", 0xF0FF, 0xF03A, "PITCH Vx", ops::ld_pitch),
+    op("Fx55", 0xF0FF, 0xF055, "LD [I], Vx", ops::ld_store_registers),
+    op("Fx65", 0xF0FF, 0xF065, "LD Vx, [I]", ops::ld_read_registers),
+    chip8x_op("FxF8", 0xF0FF, 0xF0F8, "OUT Vx", ops::out_port),
+    chip8x_op("FxFB", 0xF0FF, 0xF0FB, "IN Vx", ops::in_port),
+];
+
+/// The instruction the interpreter executes for an opcode, if any.
+pub fn decode(opcode: u16, chip8x: bool) -> Option<&'static Instruction> {
+    INSTRUCTIONS.iter().find(|instruction| instruction.matches(opcode, chip8x))
+}
+
+/// An opcode read from memory, with the instruction the interpreter executes for it.
+#[derive(Clone, Copy)]
+pub struct Decoded {
+    pub opcode: u16,
+    /// None when the interpreter cannot execute the opcode, e.g. because it is data.
+    pub instruction: Option<&'static Instruction>,
+}
+
+impl Decoded {
+    pub fn disassemble(&self) -> String {
+        disassemble(self.opcode)
+    }
+}
+
+/// The opcodes in memory from `start` on, two bytes apart, with their addresses.
+pub fn instructions(memory: &[u8], start: u16, chip8x: bool)
+        -> impl Iterator<Item = (u16, Decoded)> + '_ {
+    (start as usize..memory.len().min(MEMORY).saturating_sub(1)).step_by(2).map(move |address| {
+        let opcode = (memory[address] as u16) << 8 | memory[address + 1] as u16;
+        let instruction = decode(opcode, chip8x);
+
+        (address as u16, Decoded { opcode, instruction })
+    })
+}
+
+/// The instructions a variant supports as a table, with the state of the quirks they
+/// depend on.
+pub fn capabilities(variant: Variant) -> String {
+    let chip8x = variant == Variant::Chip8X;
+    let quirks = variant.quirks();
+    let mut report = format!("{} instructions\n", variant.name());
+
+    let supported: Vec<&Instruction> = INSTRUCTIONS.iter()
+        .filter(|instruction| chip8x || !instruction.chip8x)
+        .collect();
+
+    for (idx, instruction) in supported.iter().enumerate() {
+        if supported[..idx].iter().any(|earlier| instruction.is_shadowed_by(earlier)) {
+            continue;
+        }
+
+        let mut line = format!("{}  {:<22}", instruction.pattern, instruction.mnemonic);
+        if let Some(quirk) = instruction.quirk {
+            let state = if quirks.is_enabled(quirk) { "on" } else { "off" };
+            line.push_str(&format!("quirk {}: {}", quirk, state));
+        }
+
+        report.push_str(line.trim_end());
+        report.push('\n');
+    }
+
+    report
+}
+
+/// Print the instructions of a variant, `chip8 caps --variant xochip`.
+#[cfg(feature = "std")]
+pub fn run_command(args: &[String]) -> Result<(), String> {
+    let variant = match args {
+        [] => Variant::Chip8,
+        [flag, name] if flag == "--variant" => name.parse()?,
+        _ => return Err(USAGE.to_string()),
+    };
+
+    print!("{}", capabilities(variant));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(opcode: u16, chip8x: bool) -> Option<&'static str> {
+        decode(opcode, chip8x).map(|instruction| instruction.pattern)
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(pattern(0x00E0, false), Some("00E0"));
+        assert_eq!(pattern(0xD125, false), Some("Dxyn"));
+        assert_eq!(pattern(0x8AB6, false), Some("8xy6"));
+        assert_eq!(pattern(0x8AB8, false), None);
+        assert_eq!(pattern(0xF255, false), Some("Fx55"));
+        assert_eq!(pattern(0xF265, false), Some("Fx65"));
+        assert_eq!(pattern(0xF2FF, false), None);
+    }
+
+    #[test]
+    fn test_decode_chip8x() {
+        assert_eq!(pattern(0x5121, false), Some("5xy0"));
+        assert_eq!(pattern(0x5121, true), Some("5xy1"));
+        assert_eq!(pattern(0xB123, false), Some("Bnnn"));
+        assert_eq!(pattern(0xB123, true), Some("Bxyn"));
+        assert_eq!(pattern(0x02A0, false), None);
+    }
+
+    #[test]
+    fn test_instructions() {
+        let memory = [0x00, 0xE0, 0xF2, 0xFF, 0x12];
+        let decoded: Vec<(u16, u16, Option<&str>)> = instructions(&memory, 0, false)
+            .map(|(address, decoded)| {
+                (address, decoded.opcode, decoded.instruction.map(|i| i.pattern))
+            })
+            .collect();
+
+        assert_eq!(decoded, vec!((0, 0x00E0, Some("00E0")), (2, 0xF2FF, None)));
+        assert_eq!(instructions(&memory, 3, false).count(), 1);
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let chip8 = capabilities(Variant::Chip8);
+        assert!(chip8.contains("8xy1  OR Vx, Vy             quirk vf-reset: off\n"));
+        assert!(!chip8.contains("FxF8"));
+
+        let chip8x = capabilities(Variant::Chip8X);
+        assert!(chip8x.contains("8xy1  OR Vx, Vy             quirk vf-reset: on\n"));
+        assert!(chip8x.contains("FxF8  OUT Vx\n"));
+        assert!(!chip8x.contains("Bnnn"));
+    }
+}
@@ -0,0 +1,130 @@
+//! The ROM run when no ROM is given.
+//!
+//! It draws a logo and then an outline for each of the 16 keys. Every key pressed fills in
+//! its square, or clears it again, and beeps, so it shows at a glance that the display,
+//! the keypad and the buzzer work. It also pauses on the delay timer before drawing the
+//! keys.
+//!
+//! The loops jump to a `LD Vx, Vx`, which does nothing, so they behave the same whether a
+//! jump lands on its target or on the instruction after it.
+
+/// The name the demo goes by in messages, where a ROM would show its path.
+pub const NAME: &str = "the built-in demo";
+
+pub static ROM: [u8; 148] = [
+    // The logo, a letter at a time
+    0x00, 0xE0, // 200 CLS
+    0x60, 0x11, // 202 LD V0, 17
+    0x61, 0x02, // 204 LD V1, 2
+    0xA2, 0x70, // 206 LD I, logo_C
+    0xD0, 0x15, // 208 DRW V0, V1, 5
+    0x70, 0x05, // 20A ADD V0, 5
+    0xA2, 0x75, // 20C LD I, logo_H
+    0xD0, 0x15, // 20E DRW V0, V1, 5
+    0x70, 0x05, // 210 ADD V0, 5
+    0xA2, 0x7A, // 212 LD I, logo_I
+    0xD0, 0x15, // 214 DRW V0, V1, 5
+    0x70, 0x05, // 216 ADD V0, 5
+    0xA2, 0x7F, // 218 LD I, logo_P
+    0xD0, 0x15, // 21A DRW V0, V1, 5
+    0x70, 0x05, // 21C ADD V0, 5
+    0xA2, 0x84, // 21E LD I, logo_-
+    0xD0, 0x15, // 220 DRW V0, V1, 5
+    0x70, 0x05, // 222 ADD V0, 5
+    0xA2, 0x89, // 224 LD I, logo_8
+    0xD0, 0x15, // 226 DRW V0, V1, 5
+
+    // Show it for half a second
+    0x65, 0x1E, // 228 LD V5, 30
+    0xF5, 0x15, // 22A LD DT, V5
+    0x85, 0x50, // 22C pause: LD V5, V5
+    0xF5, 0x07, // 22E LD V5, DT
+    0x35, 0x00, // 230 SE V5, 0
+    0x12, 0x2C, // 232 JP pause
+
+    // An outline for every key, in four rows of four from 0 to F
+    0x62, 0x00, // 234 LD V2, 0
+    0xA2, 0x8E, // 236 LD I, outline
+    0x82, 0x20, // 238 grid: LD V2, V2
+    0x22, 0x54, // 23A CALL position
+    0xD3, 0x43, // 23C DRW V3, V4, 3
+    0x72, 0x01, // 23E ADD V2, 1
+    0x32, 0x10, // 240 SE V2, 16
+    0x12, 0x38, // 242 JP grid
+
+    // Fill in or clear the square of every key pressed, with a beep
+    0xA2, 0x91, // 244 LD I, fill
+    0x82, 0x20, // 246 loop: LD V2, V2
+    0xF2, 0x0A, // 248 LD V2, K
+    0x22, 0x54, // 24A CALL position
+    0xD3, 0x43, // 24C DRW V3, V4, 3
+    0x65, 0x04, // 24E LD V5, 4
+    0xF5, 0x18, // 250 LD ST, V5
+    0x12, 0x46, // 252 JP loop
+
+    // position: the square of the key in V2 at (V3, V4)
+    0x82, 0x20, // 254 position: LD V2, V2
+    0x83, 0x20, // 256 LD V3, V2
+    0x66, 0x03, // 258 LD V6, 3
+    0x83, 0x62, // 25A AND V3, V6
+    0x83, 0x34, // 25C ADD V3, V3
+    0x84, 0x30, // 25E LD V4, V3
+    0x83, 0x34, // 260 ADD V3, V3
+    0x83, 0x44, // 262 ADD V3, V4
+    0x73, 0x14, // 264 ADD V3, 20
+    0x84, 0x20, // 266 LD V4, V2
+    0x66, 0x0C, // 268 LD V6, 12
+    0x84, 0x62, // 26A AND V4, V6
+    0x74, 0x0C, // 26C ADD V4, 12
+    0x00, 0xEE, // 26E RET
+
+    // Sprites
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // 270 C
+    0x90, 0x90, 0xF0, 0x90, 0x90, // 275 H
+    0xE0, 0x40, 0x40, 0x40, 0xE0, // 27A I
+    0xF0, 0x90, 0xF0, 0x80, 0x80, // 27F P
+    0x00, 0x00, 0xF0, 0x00, 0x00, // 284 -
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 289 8
+    0xF8, 0x88, 0xF8, // 28E outline
+    0x00, 0x70, 0x00, // 291 fill
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+    use crate::golden::run_frame;
+
+    // Whether the square of a key is filled in
+    fn is_filled(chip8: &Chip8, key: usize) -> bool {
+        chip8.display.is_lit(22 + key % 4 * 6, 13 + key / 4 * 4)
+    }
+
+    #[test]
+    fn test_demo() {
+        let mut chip8 = Chip8::new();
+        chip8.load_bytes(&ROM);
+
+        // Past the half second pause, drawing a sprite a frame
+        for _ in 0..60 {
+            run_frame(&mut chip8, 20);
+        }
+
+        // The C of the logo, and the outline of key F
+        assert!(chip8.display.is_lit(17, 2) && !chip8.display.is_lit(18, 3));
+        assert!(chip8.display.is_lit(38, 24) && chip8.display.is_lit(42, 26));
+        assert!((0..16).all(|key| !is_filled(&chip8, key)));
+
+        let mut keys = [false; 16];
+        keys[6] = true;
+        chip8.set_keys(keys);
+        run_frame(&mut chip8, 20);
+        chip8.set_keys([false; 16]);
+        run_frame(&mut chip8, 20);
+
+        assert!(is_filled(&chip8, 6));
+        assert_eq!((0..16).filter(|&key| is_filled(&chip8, key)).count(), 1);
+        assert!(chip8.timers.sound > 0);
+        assert!(chip8.unknown_opcode.is_none());
+    }
+}
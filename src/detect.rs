@@ -0,0 +1,169 @@
+//! Guessing the platform a ROM was written for.
+//!
+//! A ROM that shows garbage or crashes often expects the quirks of another platform. The
+//! `detect-quirks` subcommand runs the first frames of the ROM under the quirks of every
+//! variant, reports where the runs diverge, and suggests the variant under which the ROM
+//! fares best: without crashing or reaching unknown opcodes, and showing the most activity.
+
+use std::{collections::BTreeSet, panic::{self, AssertUnwindSafe}};
+
+use crate::archive::read_rom;
+use crate::emulator::Emulator;
+use crate::golden::run_frame;
+use crate::replay::frame_hash;
+use crate::variant::Variant;
+
+const DEFAULT_FRAMES: u64 = 300;
+const USAGE: &str = "Usage: chip8 detect-quirks rom.ch8 [--frames N]";
+
+/// How a ROM ran under the quirks of one variant.
+#[derive(Debug, PartialEq)]
+pub struct Outcome {
+    pub variant: Variant,
+    /// The display hash after every frame, up to a crash.
+    pub frames: Vec<u64>,
+    /// The frame at which the emulator panicked or reached an unknown opcode.
+    pub failed_at: Option<u64>,
+    /// The number of frames in which anything was drawn.
+    pub drawn_frames: usize,
+}
+
+impl Outcome {
+    pub fn run(rom: &[u8], variant: Variant, frames: u64) -> Result<Outcome, String> {
+        let emulator = Emulator::builder().variant(variant).seed(0).rom_bytes(rom).build()?;
+        let cycles_per_frame = emulator.cycles_per_frame();
+        let mut chip8 = emulator.chip8;
+        let mut outcome = Outcome { variant, frames: Vec::new(), failed_at: None, drawn_frames: 0 };
+
+        for frame in 0..frames {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                run_frame(&mut chip8, cycles_per_frame)
+            }));
+
+            if result.is_err() || chip8.unknown_opcode.is_some() {
+                outcome.failed_at = Some(frame);
+                break;
+            }
+
+            outcome.frames.push(frame_hash(&chip8.display));
+            if chip8.display.lit_count() > 0 {
+                outcome.drawn_frames += 1;
+            }
+        }
+
+        Ok(outcome)
+    }
+
+    /// The first frame at which this run shows something different from another.
+    pub fn diverges_from(&self, other: &Outcome) -> Option<usize> {
+        let first = self.frames.iter().zip(&other.frames).position(|(a, b)| a != b);
+
+        first.or_else(|| {
+            if self.frames.len() != other.frames.len() {
+                Some(self.frames.len().min(other.frames.len()))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Rank of the run for the suggestion: completed runs first, then the number of
+    /// different frames and of frames with anything on them, since a stuck or blank
+    /// display is the usual failure.
+    fn score(&self) -> (bool, usize, usize) {
+        let distinct: BTreeSet<&u64> = self.frames.iter().collect();
+
+        (self.failed_at.is_none(), distinct.len(), self.drawn_frames)
+    }
+}
+
+/// The variant whose quirks suit the ROM best, the first one in `Variant::ALL` on a tie.
+pub fn suggest(outcomes: &[Outcome]) -> Option<Variant> {
+    let best = outcomes.iter().map(Outcome::score).max()?;
+
+    outcomes.iter().find(|outcome| outcome.score() == best).map(|outcome| outcome.variant)
+}
+
+/// The `detect-quirks` subcommand.
+pub fn run_command(args: &[String]) -> Result<(), String> {
+    let mut path = None;
+    let mut frames = DEFAULT_FRAMES;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => {
+                let value = args.next().ok_or("--frames requires a number")?;
+                frames = value.parse().map_err(|_| format!("Invalid number '{}'", value))?;
+            },
+            _ => path = Some(arg),
+        }
+    }
+
+    let path = path.ok_or(USAGE)?;
+    let rom = read_rom(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+    // Crashes are reported per variant, not as a backtrace for each
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let outcomes: Result<Vec<Outcome>, String> = Variant::ALL.iter()
+        .map(|&variant| Outcome::run(&rom, variant, frames))
+        .collect();
+    panic::set_hook(hook);
+    let outcomes = outcomes?;
+
+    let baseline = &outcomes[0];
+    for outcome in &outcomes {
+        let status = match outcome.failed_at {
+            Some(frame) => format!("fails at frame {}", frame),
+            None => String::from("runs"),
+        };
+        let divergence = match outcome.diverges_from(baseline) {
+            Some(frame) => format!("diverges from {} at frame {}", baseline.variant.name(), frame),
+            None => format!("same as {}", baseline.variant.name()),
+        };
+
+        println!("{:<12} {}, {}", outcome.variant.name(), status, divergence);
+    }
+
+    if outcomes.iter().all(|outcome| outcome.diverges_from(baseline).is_none()) {
+        println!("The quirks make no difference in the {} frames run", baseline.frames.len());
+    } else if let Some(variant) = suggest(&outcomes) {
+        println!("Most likely written for {}", variant.name());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_vf_reset() {
+        // LD I, 0x210; LD VF, 0x01; OR V0, V1; SE VF, 0x00; LD I, 0x212 (an empty sprite)
+        // DRW V0, V0, 1; LD V0, K; sprite
+        let rom = [0xA2, 0x10, 0x6F, 0x01, 0x80, 0x11, 0x3F, 0x00, 0xA2, 0x12,
+            0xD0, 0x01, 0xF0, 0x0A, 0x00, 0x00, 0xFF, 0x00, 0x00];
+
+        let chip8 = Outcome::run(&rom, Variant::Chip8, 2).unwrap();
+        let vip = Outcome::run(&rom, Variant::CosmacVip, 2).unwrap();
+
+        assert_eq!(vip.diverges_from(&chip8), Some(0));
+        assert_eq!(suggest(&[chip8, vip]), Some(Variant::CosmacVip));
+    }
+
+    #[test]
+    fn test_failure_loses() {
+        let failed = Outcome::run(&[0xFF, 0xFF], Variant::Chip8, 2).unwrap();
+        let healthy = Outcome {
+            variant: Variant::CosmacVip,
+            frames: vec!(1, 1),
+            failed_at: None,
+            drawn_frames: 0,
+        };
+
+        assert_eq!(failed.failed_at, Some(0));
+        assert_eq!(suggest(&[failed, healthy]), Some(Variant::CosmacVip));
+    }
+}
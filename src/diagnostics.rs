@@ -0,0 +1,65 @@
+//! Explanations for opcodes the interpreter cannot execute.
+//!
+//! Most unknown opcodes in a working ROM are instructions of a CHIP-8 extension, so the
+//! diagnostic names the variant the ROM was probably written for.
+
+use alloc::{format, string::String};
+
+use crate::disassembler::disassemble;
+
+/// The CHIP-8 variant an opcode belongs to, if it is not part of the original instruction set.
+pub fn variant(opcode: u16) -> Option<&'static str> {
+    let n = opcode & 0x000F;
+    let kk = opcode & 0x00FF;
+
+    match opcode >> 12 {
+        0x0 if opcode & 0xFFF0 == 0x00C0 => Some("SCHIP"),
+        0x0 if opcode & 0xFFF0 == 0x00D0 => Some("XO-CHIP"),
+        0x0 if (0x00FB..=0x00FF).contains(&opcode) => Some("SCHIP"),
+        0x0 if opcode != 0x00E0 && opcode != 0x00EE => Some("COSMAC VIP machine code"),
+        0x5 if n == 0x2 || n == 0x3 => Some("XO-CHIP"),
+        0xF if opcode == 0xF000 || opcode == 0xF002 => Some("XO-CHIP"),
+        0xF if kk == 0x01 || kk == 0x3A => Some("XO-CHIP"),
+        0xF if kk == 0x30 || kk == 0x75 || kk == 0x85 => Some("SCHIP"),
+        _ => None,
+    }
+}
+
+/// Describe why the opcode at `pc` could not be executed.
+pub fn diagnose(pc: u16, opcode: u16) -> String {
+    let location = format!("{:#05X}: {:#06X}", pc, opcode);
+
+    match variant(opcode) {
+        Some("COSMAC VIP machine code") =>
+            format!("{} calls a machine code routine (SYS), which cannot be emulated", location),
+        Some(variant) =>
+            format!("{} is a {} instruction, but this is a CHIP-8 interpreter", location, variant),
+        None if disassemble(opcode).starts_with("DW") =>
+            format!("{} is not an instruction of any known CHIP-8 variant", location),
+        None =>
+            format!("{} ({}) is not implemented yet", location, disassemble(opcode)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variant() {
+        assert_eq!(variant(0x00FF), Some("SCHIP"));
+        assert_eq!(variant(0x00D4), Some("XO-CHIP"));
+        assert_eq!(variant(0xF375), Some("SCHIP"));
+        assert_eq!(variant(0x0123), Some("COSMAC VIP machine code"));
+        assert_eq!(variant(0x00E0), None);
+        assert_eq!(variant(0xD125), None);
+    }
+
+    #[test]
+    fn test_diagnose() {
+        assert_eq!(diagnose(0x204, 0x00FE),
+            "0x204: 0x00FE is a SCHIP instruction, but this is a CHIP-8 interpreter");
+        assert_eq!(diagnose(0x200, 0x5121),
+            "0x200: 0x5121 is not an instruction of any known CHIP-8 variant");
+    }
+}
@@ -0,0 +1,247 @@
+use std::{any::Any, fmt, fs, panic::{self, AssertUnwindSafe}};
+
+use crate::{Chip8, State};
+use crate::archive::read_rom;
+use crate::disassembler::disassemble;
+use crate::reference::Reference;
+
+const DEFAULT_CYCLES: u64 = 10_000;
+// Number of instructions per timer tick, matching the default 500 Hz CPU and 60 Hz timers
+const CYCLES_PER_TIMER_TICK: u64 = 8;
+
+/// Key presses and releases at fixed cycles, applied identically to both interpreters.
+///
+/// Each line of an input script reads `<cycle> press <key>` or `<cycle> release <key>`,
+/// where the key is a hexadecimal digit.
+#[derive(Debug, Default, PartialEq)]
+pub struct InputScript {
+    events: Vec<(u64, usize, bool)>,
+}
+
+impl InputScript {
+    pub fn parse(script: &str) -> Result<InputScript, String> {
+        let mut events = Vec::new();
+
+        for line in script.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+
+            let (cycle, action, key) = match tokens.as_slice() {
+                [cycle, action, key] => (cycle, action, key),
+                _ => return Err(format!("Invalid input script line '{}'", line)),
+            };
+
+            let cycle = cycle.parse().map_err(|_| format!("Invalid cycle '{}'", cycle))?;
+            let key = usize::from_str_radix(key, 16)
+                .ok()
+                .filter(|&key| key < 16)
+                .ok_or(format!("Invalid key '{}'", key))?;
+            let down = match *action {
+                "press" => true,
+                "release" => false,
+                _ => return Err(format!("Unknown action '{}'", action)),
+            };
+
+            events.push((cycle, key, down));
+        }
+
+        events.sort_by_key(|&(cycle, _, _)| cycle);
+
+        Ok(InputScript { events })
+    }
+
+    fn apply(&self, cycle: u64, keys: &mut [bool; 16]) {
+        for &(_, key, down) in self.events.iter().filter(|&&(c, _, _)| c == cycle) {
+            keys[key] = down;
+        }
+    }
+}
+
+/// The first point where the emulator and the reference interpreter disagree.
+#[derive(Debug, PartialEq)]
+pub struct Divergence {
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    pub differences: Vec<String>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Divergence at cycle {} after {:#05X}: {} ({:#06X})",
+            self.cycle, self.pc, disassemble(self.opcode), self.opcode)?;
+
+        for difference in &self.differences {
+            writeln!(f, "  {}", difference)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// List the registers that differ between the emulator and the reference.
+pub fn compare(chip8: &Chip8, reference: &Reference) -> Vec<String> {
+    let mut differences = Vec::new();
+
+    let mut check = |name: String, emulator: u16, expected: u16| {
+        if emulator != expected {
+            differences.push(format!("{}: emulator {:#X}, reference {:#X}", name, emulator, expected));
+        }
+    };
+
+    check(String::from("PC"), chip8.pc, reference.pc);
+    check(String::from("I"), chip8.i, reference.i);
+    check(String::from("SP"), chip8.stack.len() as u16, reference.stack.len() as u16);
+    check(String::from("DT"), chip8.timers.delay as u16, reference.delay_timer as u16);
+    check(String::from("ST"), chip8.timers.sound as u16, reference.sound_timer as u16);
+
+    for (idx, (emulator, expected)) in chip8.registers.iter().zip(&reference.v).enumerate() {
+        check(format!("V{:X}", idx), *emulator as u16, *expected as u16);
+    }
+
+    differences
+}
+
+/// The message of a panic, as far as it is text.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(message), _) => message,
+        (None, Some(message)) => message,
+        (None, None) => "no message",
+    }
+}
+
+/// Run a ROM on the emulator and the reference interpreter in lock step, returning the
+/// first divergence within the given number of cycles.
+pub fn run(rom: &[u8], script: &InputScript, cycles: u64) -> Result<Option<Divergence>, String> {
+    let mut reference = Reference::new(rom)?;
+    let mut chip8 = Chip8::new();
+    chip8.load_bytes(rom);
+    let mut keys = [false; 16];
+
+    for cycle in 0..cycles {
+        script.apply(cycle, &mut keys);
+        chip8.set_keys(keys);
+        reference.keys = keys;
+
+        let pc = chip8.pc;
+        let opcode = chip8.fetch();
+        let executes = chip8.state == State::Running;
+
+        // A panicking opcode is reported as a divergence instead of aborting the run
+        let result = panic::catch_unwind(AssertUnwindSafe(|| chip8.cycle()));
+        if let Err(payload) = result {
+            return Ok(Some(Divergence {
+                cycle,
+                pc,
+                opcode,
+                differences: vec!(format!("emulator panicked: {}", panic_message(&*payload))),
+            }));
+        }
+
+        reference.step();
+
+        // Random numbers cannot be compared, so the reference takes the emulator's value
+        if executes && opcode & 0xF000 == 0xC000 {
+            let x = ((opcode >> 8) & 0xF) as usize;
+            reference.v[x] = chip8.registers[x];
+        }
+
+        if cycle % CYCLES_PER_TIMER_TICK == CYCLES_PER_TIMER_TICK - 1 {
+            chip8.update_timers();
+            reference.update_timers();
+        }
+
+        let differences = compare(&chip8, &reference);
+        if !differences.is_empty() {
+            return Ok(Some(Divergence { cycle, pc, opcode, differences }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// The `diff-test` subcommand: `chip8 diff-test rom.ch8 [--cycles N] [--input script.txt]`.
+pub fn run_command(args: &[String]) -> Result<(), String> {
+    let mut path = None;
+    let mut cycles = DEFAULT_CYCLES;
+    let mut script = InputScript::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cycles" => {
+                let value = args.next().ok_or("--cycles requires a number")?;
+                cycles = value.parse().map_err(|_| format!("Invalid cycle count '{}'", value))?;
+            },
+            "--input" => {
+                let input = args.next().ok_or("--input requires a path")?;
+                let contents = fs::read_to_string(input)
+                    .map_err(|e| format!("Could not read {}: {}", input, e))?;
+                script = InputScript::parse(&contents)?;
+            },
+            _ => path = Some(arg),
+        }
+    }
+
+    let path = path.ok_or("Usage: chip8 diff-test rom.ch8 [--cycles N] [--input script.txt]")?;
+    let rom = read_rom(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+    match run(&rom, &script, cycles)? {
+        Some(divergence) => Err(divergence.to_string()),
+        None => {
+            println!("No divergence in {} cycles", cycles);
+            Ok(())
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MEMORY, PROGRAM_START};
+
+    #[test]
+    fn test_input_script() {
+        let script = InputScript::parse("10 press A\n# comment\n5 release 3\n").unwrap();
+        let mut keys = [false; 16];
+
+        script.apply(10, &mut keys);
+
+        assert!(keys[0xA]);
+        assert_eq!(script.events[0], (5, 3, false));
+    }
+
+    #[test]
+    fn test_run_without_divergence() {
+        // LD V0, 0x05; ADD V0, 0x03; LD I, 0x300
+        let rom = [0x60, 0x05, 0x70, 0x03, 0xA3, 0x00];
+
+        assert_eq!(run(&rom, &InputScript::default(), 3), Ok(None));
+    }
+
+    #[test]
+    fn test_run_with_jumps() {
+        // LD V0, 0x81; CALL shift; JP end; shift: SHL V0, V0; SHR V0, V0; RET;
+        // end: JP end
+        let rom = [0x60, 0x81, 0x22, 0x08, 0x12, 0x0E, 0x00, 0x00, 0x80, 0x0E, 0x80, 0x06,
+            0x00, 0xEE, 0x12, 0x0E];
+
+        assert_eq!(run(&rom, &InputScript::default(), 20), Ok(None));
+    }
+
+    #[test]
+    fn test_rom_too_large() {
+        let rom = vec!(0; MEMORY - PROGRAM_START + 1);
+
+        assert!(run(&rom, &InputScript::default(), 1).is_err());
+    }
+
+    #[test]
+    fn test_compare() {
+        let chip8 = Chip8::new();
+        let mut reference = Reference::new(&[]).unwrap();
+        reference.v[3] = 0x10;
+
+        assert_eq!(compare(&chip8, &reference), vec!("V3: emulator 0x0, reference 0x10"));
+    }
+}
@@ -0,0 +1,89 @@
+use alloc::{format, string::String};
+
+/// Translate an opcode into its assembly mnemonic, e.g. `0xD125` becomes `DRW V1, V2, 5`.
+///
+/// The mnemonics follow the notation of Cowgod's Chip-8 technical reference, the same one
+/// used in the documentation of `ops.rs`.
+pub fn disassemble(opcode: u16) -> String {
+    let x = (opcode & 0x0F00) >> 8;
+    let y = (opcode & 0x00F0) >> 4;
+    let n = opcode & 0x000F;
+    let kk = opcode & 0x00FF;
+    let nnn = opcode & 0x0FFF;
+
+    match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => String::from("CLS"),
+            0x00EE => String::from("RET"),
+            0x00FB => String::from("SCR"),
+            0x00FC => String::from("SCL"),
+            0x00C0..=0x00CF => format!("SCD {}", n),
+            0x00D0..=0x00DF => format!("SCU {}", n),
+            _ => format!("SYS {:#05X}", nnn),
+        },
+        0x1000 => format!("JP {:#05X}", nnn),
+        0x2000 => format!("CALL {:#05X}", nnn),
+        0x3000 => format!("SE V{:X}, {:#04X}", x, kk),
+        0x4000 => format!("SNE V{:X}, {:#04X}", x, kk),
+        0x5000 if n == 0 => format!("SE V{:X}, V{:X}", x, y),
+        0x6000 => format!("LD V{:X}, {:#04X}", x, kk),
+        0x7000 => format!("ADD V{:X}, {:#04X}", x, kk),
+        0x8000 => match n {
+            0x0 => format!("LD V{:X}, V{:X}", x, y),
+            0x1 => format!("OR V{:X}, V{:X}", x, y),
+            0x2 => format!("AND V{:X}, V{:X}", x, y),
+            0x3 => format!("XOR V{:X}, V{:X}", x, y),
+            0x4 => format!("ADD V{:X}, V{:X}", x, y),
+            0x5 => format!("SUB V{:X}, V{:X}", x, y),
+            0x6 => format!("SHR V{:X}, V{:X}", x, y),
+            0x7 => format!("SUBN V{:X}, V{:X}", x, y),
+            0xE => format!("SHL V{:X}, V{:X}", x, y),
+            _ => unknown(opcode),
+        },
+        0x9000 if n == 0 => format!("SNE V{:X}, V{:X}", x, y),
+        0xA000 => format!("LD I, {:#05X}", nnn),
+        0xB000 => format!("JP V0, {:#05X}", nnn),
+        0xC000 => format!("RND V{:X}, {:#04X}", x, kk),
+        0xD000 => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        0xE000 => match kk {
+            0x9E => format!("SKP V{:X}", x),
+            0xA1 => format!("SKNP V{:X}", x),
+            _ => unknown(opcode),
+        },
+        0xF000 => match kk {
+            0x02 if opcode == 0xF002 => String::from("AUDIO"),
+            0x07 => format!("LD V{:X}, DT", x),
+            0x0A => format!("LD V{:X}, K", x),
+            0x15 => format!("LD DT, V{:X}", x),
+            0x18 => format!("LD ST, V{:X}", x),
+            0x1E => format!("ADD I, V{:X}", x),
+            0x29 => format!("LD F, V{:X}", x),
+            0x33 => format!("LD B, V{:X}", x),
+            0x3A => format!("PITCH V{:X}", x),
+            0x55 => format!("LD [I], V{:X}", x),
+            0x65 => format!("LD V{:X}, [I]", x),
+            _ => unknown(opcode),
+        },
+        _ => unknown(opcode),
+    }
+}
+
+fn unknown(opcode: u16) -> String {
+    format!("DW {:#06X}", opcode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disassemble() {
+        assert_eq!(disassemble(0x00E0), "CLS");
+        assert_eq!(disassemble(0x00C3), "SCD 3");
+        assert_eq!(disassemble(0x00FB), "SCR");
+        assert_eq!(disassemble(0xD125), "DRW V1, V2, 5");
+        assert_eq!(disassemble(0xA2F0), "LD I, 0x2F0");
+        assert_eq!(disassemble(0xF30A), "LD V3, K");
+        assert_eq!(disassemble(0x5121), "DW 0x5121");
+    }
+}
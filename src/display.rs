@@ -0,0 +1,467 @@
+//! The CHIP-8 display as a bitboard: one `u64` per row, with the leftmost pixel in the
+//! most significant bit.
+//!
+//! Drawing a sprite row is a rotate and an XOR, and a collision is an AND, so DRW never
+//! touches individual pixels. The display is only converted to colours when rendered.
+//!
+//! Double buffered, the display shows the rows as they were at the last display interrupt
+//! rather than as they are, so a render between two interrupts never catches a sprite
+//! half drawn, or erased to be drawn again elsewhere. The instructions keep drawing on the
+//! rows as they are, the back buffer, and `present` copies them to the front at every
+//! interrupt.
+
+use crate::{WIDTH, HEIGHT};
+#[cfg(feature = "std")]
+use crate::chip8x::ColorBoard;
+#[cfg(feature = "std")]
+use crate::screen::Buffer;
+use crate::sprite_rules::Edge;
+
+/// Colour of a lit pixel when the display is converted to a buffer.
+pub const LIT: u32 = 255;
+
+/// Every row of the display marked as changed.
+const ALL_ROWS: u64 = u64::MAX >> (64 - HEIGHT);
+
+#[derive(Clone, PartialEq)]
+pub struct Display {
+    rows: [u64; HEIGHT],
+    // Bit n is set when row n changed since the display was last rendered
+    dirty_rows: u64,
+    // Rows that are still shown as they were before a draw the beam missed, until the next
+    // render, and their contents
+    held_rows: u64,
+    held: [u64; HEIGHT],
+    // The rows that were presented, when double buffered
+    presented: Option<[u64; HEIGHT]>,
+}
+
+impl Display {
+    pub fn new() -> Display {
+        Display::from_rows([0; HEIGHT])
+    }
+
+    pub fn from_rows(rows: [u64; HEIGHT]) -> Display {
+        Display { rows, dirty_rows: ALL_ROWS, held_rows: 0, held: [0; HEIGHT], presented: None }
+    }
+
+    pub fn rows(&self) -> &[u64; HEIGHT] {
+        &self.rows
+    }
+
+    /// Replace the rows, showing them right away also when double buffered.
+    pub fn set_rows(&mut self, rows: [u64; HEIGHT]) {
+        self.rows = rows;
+        self.dirty_rows = ALL_ROWS;
+        if let Some(presented) = self.presented.as_mut() {
+            *presented = rows;
+        }
+    }
+
+    pub fn is_double_buffered(&self) -> bool {
+        self.presented.is_some()
+    }
+
+    /// Show only the rows presented at the display interrupts, or the rows as they are.
+    pub fn set_double_buffered(&mut self, double_buffered: bool) {
+        self.presented = if double_buffered { Some(self.rows) } else { None };
+        self.dirty_rows = ALL_ROWS;
+    }
+
+    /// The rows the display shows: those presented last when double buffered, or else the
+    /// rows as they are.
+    pub fn front_rows(&self) -> &[u64; HEIGHT] {
+        self.presented.as_ref().unwrap_or(&self.rows)
+    }
+
+    /// Show the rows as they are now, at a display interrupt. Does nothing unless double
+    /// buffered. Returns whether that changed what the display shows.
+    pub fn present(&mut self) -> bool {
+        let presented = match self.presented.as_mut() {
+            Some(presented) => presented,
+            None => return false,
+        };
+
+        let rows = &self.rows;
+        let changed = (0..HEIGHT).filter(|&y| presented[y] != rows[y])
+            .fold(0, |changed, y| changed | 1 << y);
+        self.dirty_rows |= changed;
+        *presented = self.rows;
+
+        changed != 0
+    }
+
+    pub fn clear(&mut self) {
+        self.rows = [0; HEIGHT];
+        self.dirty_rows = ALL_ROWS;
+    }
+
+    /// Move every pixel `n` columns to the left, clearing the columns shifted in on the right.
+    pub fn scroll_left(&mut self, n: usize) {
+        for row in self.rows.iter_mut() {
+            *row = row.checked_shl(n as u32).unwrap_or(0);
+        }
+        self.dirty_rows = ALL_ROWS;
+    }
+
+    /// Move every pixel `n` columns to the right, clearing the columns shifted in on the left.
+    pub fn scroll_right(&mut self, n: usize) {
+        for row in self.rows.iter_mut() {
+            *row = row.checked_shr(n as u32).unwrap_or(0);
+        }
+        self.dirty_rows = ALL_ROWS;
+    }
+
+    /// Move every pixel `n` rows up, clearing the rows shifted in at the bottom.
+    pub fn scroll_up(&mut self, n: usize) {
+        shift_left(&mut self.rows, n);
+        self.dirty_rows = ALL_ROWS;
+    }
+
+    /// Move every pixel `n` rows down, clearing the rows shifted in at the top.
+    pub fn scroll_down(&mut self, n: usize) {
+        shift_right(&mut self.rows, n);
+        self.dirty_rows = ALL_ROWS;
+    }
+
+    /// XOR a sprite onto the display with its top left corner at (x, y), wrapping around
+    /// the edges. Returns whether any lit pixel was erased.
+    #[cfg(test)]
+    pub fn draw_sprite(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        self.draw(x, y, sprite, 1, Edge::Wrap)
+    }
+
+    /// XOR a sprite `width` bytes wide onto the display with its top left corner at (x, y),
+    /// taken modulo the display size. The pixels past the edges wrap around or are clipped.
+    /// Returns whether any lit pixel was erased.
+    pub fn draw(&mut self, x: usize, y: usize, sprite: &[u8], width: usize, edge: Edge) -> bool {
+        let (x, y) = (x % WIDTH, y % HEIGHT);
+        let mut collision = false;
+
+        for (row, bytes) in sprite.chunks(width).enumerate() {
+            if edge == Edge::Clip && y + row >= HEIGHT {
+                break;
+            }
+
+            let bits = bytes.iter().fold(0u64, |bits, &byte| bits << 8 | byte as u64);
+            let line = bits << (WIDTH - 8 * bytes.len());
+            let line = match edge {
+                Edge::Wrap => line.rotate_right(x as u32),
+                Edge::Clip => line >> x,
+            };
+            let target = &mut self.rows[(y + row) % HEIGHT];
+
+            collision |= *target & line != 0;
+            *target ^= line;
+            self.dirty_rows |= 1 << ((y + row) % HEIGHT);
+        }
+
+        collision
+    }
+
+    /// Mark every row as changed, so the next render draws the whole display.
+    pub fn mark_dirty(&mut self) {
+        self.dirty_rows = ALL_ROWS;
+    }
+
+    /// The rows that changed since the previous call, as a bit per row.
+    pub fn take_dirty_rows(&mut self) -> u64 {
+        core::mem::replace(&mut self.dirty_rows, 0)
+    }
+
+    /// Keep showing the rows in the mask as they are in `previous` until the next render,
+    /// for rows drawn after the beam passed them.
+    pub fn hold_rows(&mut self, rows: u64, previous: &[u64; HEIGHT]) {
+        let newly_held = rows & !self.held_rows;
+        for y in (0..HEIGHT).filter(|y| newly_held >> y & 1 == 1) {
+            self.held[y] = previous[y];
+        }
+
+        self.held_rows |= rows;
+    }
+
+    /// The rows as they are shown this frame and which of them changed since the previous
+    /// call. Held rows are shown as they were, and as they are now in the next frame.
+    pub fn take_shown_rows(&mut self) -> ([u64; HEIGHT], u64) {
+        let mut shown = *self.front_rows();
+        for y in (0..HEIGHT).filter(|y| self.held_rows >> y & 1 == 1) {
+            shown[y] = self.held[y];
+        }
+
+        let dirty_rows = self.take_dirty_rows();
+        self.dirty_rows = core::mem::replace(&mut self.held_rows, 0);
+
+        (shown, dirty_rows)
+    }
+
+    pub fn is_lit(&self, x: usize, y: usize) -> bool {
+        self.rows[y % HEIGHT] >> (WIDTH - 1 - x % WIDTH) & 1 == 1
+    }
+
+    /// The number of lit pixels.
+    pub fn lit_count(&self) -> usize {
+        self.rows.iter().map(|row| row.count_ones() as usize).sum()
+    }
+
+    /// Convert to a buffer for rendering, where every lit pixel has the colour `LIT`.
+    #[cfg(feature = "std")]
+    pub fn to_buffer(&self) -> Buffer {
+        let pixels = (0..HEIGHT)
+            .flat_map(|y| (0..WIDTH).map(move |x| (x, y)))
+            .map(|(x, y)| if self.is_lit(x, y) { LIT } else { 0 })
+            .collect();
+
+        Buffer::new(WIDTH, HEIGHT, Some(pixels))
+    }
+
+    /// Draw the rows that changed since the previous render into a buffer of the display's
+    /// size, leaving the others as they are. Lit pixels get the colour `LIT`, unless the
+    /// CHIP-8X colour board gives the colours.
+    #[cfg(feature = "std")]
+    pub fn render_changes(&mut self, buffer: &mut Buffer, colors: Option<&ColorBoard>) {
+        let (rows, dirty_rows) = self.take_shown_rows();
+
+        for y in (0..HEIGHT).filter(|y| dirty_rows >> y & 1 == 1) {
+            for x in 0..WIDTH {
+                let lit = rows[y] >> (WIDTH - 1 - x) & 1 == 1;
+                let color = match colors {
+                    Some(colors) => colors.color(x, y, lit),
+                    None => if lit { LIT } else { 0 },
+                };
+
+                buffer.set_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+/// Move the cells of a line `n` places towards its start, clearing the cells shifted in at
+/// its end. The display scrolls its rows with it, and `screen::Buffer` its pixels.
+pub fn shift_left<T: Copy + Default>(cells: &mut [T], n: usize) {
+    let n = n.min(cells.len());
+    let len = cells.len();
+
+    cells.rotate_left(n);
+    cells[len - n..].iter_mut().for_each(|cell| *cell = T::default());
+}
+
+/// Move the cells of a line `n` places towards its end, clearing the cells shifted in at
+/// its start.
+pub fn shift_right<T: Copy + Default>(cells: &mut [T], n: usize) {
+    let n = n.min(cells.len());
+
+    cells.rotate_right(n);
+    cells[..n].iter_mut().for_each(|cell| *cell = T::default());
+}
+
+impl Default for Display {
+    fn default() -> Display {
+        Display::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use crate::replay::frame_hash;
+    use crate::screen::Rect;
+
+    #[test]
+    fn test_draw_sprite_xors() {
+        let mut display = Display::new();
+
+        assert!(!display.draw_sprite(2, 1, &[0b1100_0000]));
+        assert!(display.is_lit(2, 1) && display.is_lit(3, 1));
+
+        // Drawing over a lit pixel erases it and reports a collision
+        assert!(display.draw_sprite(3, 1, &[0b1000_0000]));
+        assert!(display.is_lit(2, 1) && !display.is_lit(3, 1));
+        assert_eq!(display.lit_count(), 1);
+    }
+
+    #[test]
+    fn test_draw_sprite_wraps() {
+        let mut display = Display::new();
+        display.draw_sprite(WIDTH - 4, HEIGHT - 1, &[0xFF, 0x81]);
+
+        assert!(display.is_lit(WIDTH - 1, HEIGHT - 1));
+        assert!(display.is_lit(3, HEIGHT - 1));
+        assert!(!display.is_lit(4, HEIGHT - 1));
+        assert!(display.is_lit(WIDTH - 4, 0) && display.is_lit(3, 0));
+        assert_eq!(display.lit_count(), 10);
+    }
+
+    #[test]
+    fn test_draw_clipped() {
+        let mut display = Display::new();
+        display.draw(WIDTH - 4, HEIGHT - 1, &[0xFF, 0x81], 1, Edge::Clip);
+
+        assert!(display.is_lit(WIDTH - 1, HEIGHT - 1));
+        assert_eq!(display.lit_count(), 4);
+
+        // Only the pixels past the edge are clipped, the position still wraps around
+        display.clear();
+        display.draw(WIDTH + 2, HEIGHT + 3, &[0xFF, 0xFF, 0x80, 0x01], 2, Edge::Clip);
+        assert!(display.is_lit(2, 3) && display.is_lit(17, 4));
+        assert_eq!(display.lit_count(), 18);
+    }
+
+    #[test]
+    fn test_render_changes() {
+        let mut display = Display::new();
+        let mut buffer = Buffer::new(WIDTH, HEIGHT, None);
+        display.render_changes(&mut buffer, None);
+        buffer.take_damage();
+
+        // The sprite wraps around to the top, so only the first and last rows are drawn
+        display.draw_sprite(7, HEIGHT - 1, &[0x80, 0x80]);
+        assert_eq!(display.dirty_rows, 1 | 1 << (HEIGHT - 1));
+        display.render_changes(&mut buffer, None);
+
+        assert_eq!(display.dirty_rows, 0);
+        assert_eq!(buffer.pixels()[7], LIT);
+        assert_eq!(buffer.take_damage(), Some(Rect::new(0, 0, WIDTH, HEIGHT)));
+
+        display.draw_sprite(7, 4, &[0x80]);
+        display.render_changes(&mut buffer, None);
+        assert_eq!(buffer.take_damage(), Some(Rect::new(0, 4, WIDTH, 1)));
+    }
+
+    #[test]
+    fn test_to_buffer() {
+        let mut display = Display::new();
+        display.draw_sprite(5, 9, &[0x80]);
+
+        let buffer = display.to_buffer();
+        assert_eq!(buffer.pixels()[5 + 9 * WIDTH], LIT);
+        assert_eq!(buffer.pixels().iter().filter(|&&pixel| pixel != 0).count(), 1);
+    }
+
+    #[test]
+    fn test_held_rows_show_next_frame() {
+        let mut display = Display::new();
+        display.take_dirty_rows();
+
+        let previous = *display.rows();
+        display.draw_sprite(0, 3, &[0x80, 0x80]);
+        display.hold_rows(1 << 4, &previous);
+
+        let (shown, dirty_rows) = display.take_shown_rows();
+        assert_eq!((shown[3], shown[4]), (1 << (WIDTH - 1), 0));
+        assert_eq!(dirty_rows, 0b11 << 3);
+
+        let (shown, dirty_rows) = display.take_shown_rows();
+        assert_eq!(shown[4], 1 << (WIDTH - 1));
+        assert_eq!(dirty_rows, 1 << 4);
+    }
+
+    #[test]
+    fn test_double_buffer() {
+        let mut display = Display::new();
+        display.set_double_buffered(true);
+        display.take_shown_rows();
+
+        // Erased and drawn again elsewhere, as a moving sprite is
+        display.draw_sprite(0, 2, &[0x80]);
+        assert!(display.present());
+        display.take_shown_rows();
+        display.draw_sprite(0, 2, &[0x80]);
+        let (shown, _) = display.take_shown_rows();
+        assert_eq!(shown[2], 1 << (WIDTH - 1));
+
+        display.draw_sprite(1, 2, &[0x80]);
+        assert!(display.present());
+        assert!(!display.present());
+        let (shown, dirty_rows) = display.take_shown_rows();
+        assert_eq!(shown[2], 1 << (WIDTH - 2));
+        assert_eq!(dirty_rows, 1 << 2);
+
+        display.set_rows([0; HEIGHT]);
+        assert_eq!(display.take_shown_rows().0[2], 0);
+        assert!(display.is_double_buffered());
+    }
+
+    #[test]
+    fn test_scroll_past_edge_clears() {
+        let mut display = Display::from_rows([u64::MAX; HEIGHT]);
+        display.scroll_left(WIDTH);
+        assert_eq!(display.lit_count(), 0);
+
+        let mut display = Display::from_rows([u64::MAX; HEIGHT]);
+        display.scroll_down(HEIGHT + 1);
+        assert_eq!(display.lit_count(), 0);
+    }
+
+    type Scroll = fn(&mut Display, usize);
+
+    proptest! {
+        #[test]
+        fn prop_scroll_moves_every_pixel(rows in prop::array::uniform32(any::<u64>()),
+                n in 0..80usize) {
+            let display = Display::from_rows(rows);
+            // The pixel at (x, y) before the scroll, unlit past the edges
+            let lit = |x: isize, y: isize| {
+                x >= 0 && x < WIDTH as isize && y >= 0 && y < HEIGHT as isize
+                    && display.is_lit(x as usize, y as usize)
+            };
+            let n = n as isize;
+
+            let scrolls: [(Scroll, isize, isize); 4] = [
+                (Display::scroll_left, n, 0),
+                (Display::scroll_right, -n, 0),
+                (Display::scroll_up, 0, n),
+                (Display::scroll_down, 0, -n),
+            ];
+            for &(scroll, dx, dy) in &scrolls {
+                let mut scrolled = display.clone();
+                scroll(&mut scrolled, n as usize);
+
+                for y in 0..HEIGHT as isize {
+                    for x in 0..WIDTH as isize {
+                        prop_assert_eq!(scrolled.is_lit(x as usize, y as usize),
+                            lit(x + dx, y + dy));
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn prop_double_draw_restores(rows in prop::array::uniform32(any::<u64>()),
+                x in 0..256usize, y in 0..256usize,
+                sprite in prop::collection::vec(any::<u8>(), 1..16)) {
+            let mut display = Display::from_rows(rows);
+            let hash = frame_hash(&display);
+
+            display.draw_sprite(x, y, &sprite);
+            display.draw_sprite(x, y, &sprite);
+
+            prop_assert_eq!(display.rows(), &rows);
+            prop_assert_eq!(frame_hash(&display), hash);
+        }
+
+        #[test]
+        fn prop_draw_wraps(x in 0..256usize, y in 0..256usize,
+                sprite in prop::collection::vec(any::<u8>(), 1..16)) {
+            let mut display = Display::new();
+            prop_assert!(!display.draw_sprite(x, y, &sprite));
+
+            let mut wrapped = Display::new();
+            wrapped.draw_sprite(x % WIDTH, y % HEIGHT, &sprite);
+            prop_assert_eq!(display.rows(), wrapped.rows());
+
+            for (row, &byte) in sprite.iter().enumerate() {
+                for column in 0..8 {
+                    prop_assert_eq!(display.is_lit(x + column, y + row),
+                        byte >> (7 - column) & 1 == 1);
+                }
+            }
+            let bits: u32 = sprite.iter().map(|byte| byte.count_ones()).sum();
+            prop_assert_eq!(display.lit_count(), bits as usize);
+
+            // Drawing it again erases every pixel of the sprite
+            prop_assert_eq!(display.draw_sprite(x, y, &sprite), bits > 0);
+            prop_assert_eq!(display.lit_count(), 0);
+        }
+    }
+}
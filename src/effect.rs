@@ -0,0 +1,50 @@
+//! What a single instruction did that a front end has to react to.
+//!
+//! `Chip8::step` returns an `Effect` for every instruction, so an embedder such as a
+//! terminal front end or a microcontroller can run the machine in its own loop without
+//! registering callbacks.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Effect {
+    /// Nothing to show or play.
+    None,
+    /// The display was cleared.
+    ClearScreen,
+    /// A sprite was drawn.
+    Draw,
+    /// The sound timer started running, so the buzzer sounds until it runs out.
+    Beep,
+    /// Execution is halted until a key is pressed, or released with the key release quirk.
+    WaitKey,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    #[test]
+    fn test_effects() {
+        let mut chip8 = Chip8::new();
+        // CLS; LD I, 0x200; DRW V0, V0, 1; LD V0, 5; LD ST, V0; LD V1, K; LD V2, 1
+        chip8.load_bytes(&[
+            0x00, 0xE0, 0xA2, 0x00, 0xD0, 0x01, 0x60, 0x05, 0xF0, 0x18, 0xF1, 0x0A,
+            0x62, 0x01,
+        ]);
+
+        let effects: Vec<Effect> = (0..6).map(|_| chip8.step()).collect();
+        assert_eq!(effects, vec!(
+            Effect::ClearScreen, Effect::None, Effect::Draw, Effect::None, Effect::Beep,
+            Effect::WaitKey,
+        ));
+        assert_eq!(chip8.step(), Effect::WaitKey);
+
+        let mut keys = [false; 16];
+        keys[7] = true;
+        chip8.set_keys(keys);
+        chip8.step();
+        assert_eq!(chip8.registers[1], 7);
+        assert_eq!(chip8.step(), Effect::None);
+        assert_eq!(chip8.registers[2], 1);
+    }
+}
@@ -0,0 +1,76 @@
+use std::fs;
+
+use crate::archive::read_rom;
+
+const BYTES_PER_LINE: usize = 12;
+
+/// The `embed` subcommand: print a ROM as source code, so it can be compiled into a binary.
+///
+/// `chip8 embed rom.ch8 [--c] [-o output]` writes a Rust `static` (or a C array with `--c`)
+/// containing the ROM bytes, ready to be passed to `EmulatorBuilder::rom_bytes`.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut path = None;
+    let mut output = None;
+    let mut c_array = false;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--c" => c_array = true,
+            "-o" => output = Some(args.next().ok_or("-o requires a path")?),
+            _ => path = Some(arg),
+        }
+    }
+
+    let path = path.ok_or("Usage: chip8 embed rom.ch8 [--c] [-o output]")?;
+    let rom = read_rom(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+    let source = if c_array {
+        to_c_source(path, &rom)
+    } else {
+        to_rust_source(path, &rom)
+    };
+
+    match output {
+        Some(output) => fs::write(output, source)
+            .map_err(|e| format!("Could not write {}: {}", output, e)),
+        None => {
+            print!("{}", source);
+            Ok(())
+        },
+    }
+}
+
+fn to_rust_source(path: &str, rom: &[u8]) -> String {
+    format!("// Generated by `chip8 embed {}`\npub static ROM: [u8; {}] = [\n{}];\n",
+        path, rom.len(), byte_lines(rom))
+}
+
+fn to_c_source(path: &str, rom: &[u8]) -> String {
+    format!(
+        "/* Generated by `chip8 embed {} --c` */\nconst unsigned char rom[{}] = {{\n{}}};\n",
+        path, rom.len(), byte_lines(rom))
+}
+
+fn byte_lines(rom: &[u8]) -> String {
+    rom.chunks(BYTES_PER_LINE)
+        .map(|chunk| {
+            let bytes: Vec<String> = chunk.iter().map(|b| format!("{:#04X}", b)).collect();
+            format!("    {},\n", bytes.join(", "))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_rust_source() {
+        let result = to_rust_source("pong.ch8", &[0x6A, 0x02, 0x6B]);
+        let expected = "// Generated by `chip8 embed pong.ch8`\n\
+            pub static ROM: [u8; 3] = [\n    0x6A, 0x02, 0x6B,\n];\n";
+
+        assert_eq!(result, expected);
+    }
+}
@@ -0,0 +1,340 @@
+//! Setting up a machine in one go, instead of adjusting a `Chip8` field by field.
+//!
+//! ```text
+//! let emulator = Emulator::builder()
+//!     .variant(Variant::SChip)
+//!     .cpu_hz(700)
+//!     .seed(42)
+//!     .rom_bytes(&rom)
+//!     .build()?;
+//! ```
+
+use crate::Chip8;
+use crate::chip8x::Chip8X;
+use crate::container;
+use crate::quirks::Quirks;
+use crate::random::RngKind;
+use crate::stack::{self, Stack};
+use crate::strictness::Strictness;
+use crate::timers::SystemClock;
+use crate::variant::Variant;
+
+enum Rom {
+    Bytes(Vec<u8>),
+    File(String),
+}
+
+/// A machine together with the settings it runs at. Emulators share no state and are `Send`,
+/// so a host can run several of them side by side, each on its own thread.
+pub struct Emulator {
+    pub chip8: Chip8,
+    pub variant: Variant,
+    pub cpu_hz: u32,
+    pub timer_hz: u32,
+}
+
+impl Emulator {
+    pub fn builder() -> EmulatorBuilder {
+        EmulatorBuilder::new()
+    }
+
+    /// Instructions executed per timer tick.
+    pub fn cycles_per_frame(&self) -> u32 {
+        (self.cpu_hz / self.timer_hz).max(1)
+    }
+}
+
+pub struct EmulatorBuilder {
+    variant: Variant,
+    quirks: Quirks,
+    cpu_hz: u32,
+    timer_hz: u32,
+    seed: Option<u64>,
+    rng: RngKind,
+    protect_memory: bool,
+    strictness: Strictness,
+    double_buffer: bool,
+    stack_depth: usize,
+    rom: Option<Rom>,
+}
+
+impl EmulatorBuilder {
+    pub fn new() -> EmulatorBuilder {
+        EmulatorBuilder {
+            variant: Variant::Chip8,
+            quirks: Quirks::default(),
+            cpu_hz: 500,
+            timer_hz: 60,
+            seed: None,
+            rng: RngKind::Modern,
+            protect_memory: false,
+            strictness: Strictness::Off,
+            double_buffer: false,
+            stack_depth: stack::DEFAULT_DEPTH,
+            rom: None,
+        }
+    }
+
+    /// The variant to emulate, which also enables the quirks its ROMs expect.
+    pub fn variant(mut self, variant: Variant) -> EmulatorBuilder {
+        self.variant = variant;
+        self
+    }
+
+    /// Quirks to enable on top of those of the variant.
+    pub fn quirks(mut self, quirks: Quirks) -> EmulatorBuilder {
+        self.quirks = quirks;
+        self
+    }
+
+    pub fn cpu_hz(mut self, hz: u32) -> EmulatorBuilder {
+        self.cpu_hz = hz;
+        self
+    }
+
+    pub fn timer_hz(mut self, hz: u32) -> EmulatorBuilder {
+        self.timer_hz = hz;
+        self
+    }
+
+    /// Seed the random number generator, making RND reproducible.
+    pub fn seed(mut self, seed: u64) -> EmulatorBuilder {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// The generator RND draws from, see `random`.
+    pub fn rng(mut self, rng: RngKind) -> EmulatorBuilder {
+        self.rng = rng;
+        self
+    }
+
+    pub fn protect_memory(mut self, protect: bool) -> EmulatorBuilder {
+        self.protect_memory = protect;
+        self
+    }
+
+    /// What to do about odd addresses and accesses past the end of memory, see
+    /// `strictness`.
+    pub fn strictness(mut self, strictness: Strictness) -> EmulatorBuilder {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Only show the display as it was at the last display interrupt, see `display`.
+    pub fn double_buffer(mut self, double_buffer: bool) -> EmulatorBuilder {
+        self.double_buffer = double_buffer;
+        self
+    }
+
+    /// The number of return addresses the stack can hold.
+    pub fn stack_depth(mut self, depth: usize) -> EmulatorBuilder {
+        self.stack_depth = depth;
+        self
+    }
+
+    pub fn rom_bytes(mut self, rom: &[u8]) -> EmulatorBuilder {
+        self.rom = Some(Rom::Bytes(rom.to_vec()));
+        self
+    }
+
+    /// Load the ROM from a file when building. The settings of a container are not
+    /// applied, see `container::load`.
+    pub fn rom_file(mut self, path: &str) -> EmulatorBuilder {
+        self.rom = Some(Rom::File(path.to_string()));
+        self
+    }
+
+    pub fn build(self) -> Result<Emulator, String> {
+        if self.cpu_hz == 0 || self.timer_hz == 0 {
+            return Err(String::from("Frequencies must be positive"));
+        }
+
+        let mut chip8 = Chip8::new();
+        chip8.quirks = self.variant.quirks().union(self.quirks);
+        chip8.sprite_rules = self.variant.sprite_rules();
+        chip8.protect_memory = self.protect_memory;
+        chip8.strictness = self.strictness;
+        chip8.display.set_double_buffered(self.double_buffer);
+        chip8.stack = Stack::new(self.stack_depth);
+        chip8.timers.set_clock(self.timer_hz, Box::new(SystemClock::new()));
+
+        chip8.program_start = self.variant.program_start();
+        if self.variant == Variant::Chip8X {
+            chip8.chip8x = Some(Chip8X::new());
+        }
+
+        chip8.set_rng_kind(self.rng);
+        if let Some(seed) = self.seed {
+            chip8.set_seed(seed);
+        }
+
+        match self.rom {
+            Some(Rom::Bytes(rom)) => chip8.load_bytes(&rom),
+            Some(Rom::File(path)) => {
+                let (rom, _) = container::load(&path)
+                    .map_err(|e| format!("Could not open {}: {}", path, e))?;
+                chip8.load_bytes(&rom);
+            },
+            None => {},
+        }
+
+        Ok(Emulator {
+            chip8,
+            variant: self.variant,
+            cpu_hz: self.cpu_hz,
+            timer_hz: self.timer_hz,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::chip8x;
+    use crate::events::Event;
+    use crate::timers::ManualClock;
+
+    #[test]
+    fn test_build() {
+        let emulator = Emulator::builder()
+            .variant(Variant::CosmacVip)
+            .cpu_hz(720)
+            .seed(42)
+            .rom_bytes(&[0x60, 0x2A])
+            .build()
+            .unwrap();
+
+        assert!(emulator.chip8.quirks.vf_reset);
+        assert_eq!(emulator.chip8.sprite_rules, Variant::CosmacVip.sprite_rules());
+        assert_eq!(emulator.chip8.seed, Some(42));
+        assert_eq!(emulator.chip8.memory[0x200], 0x60);
+        assert_eq!(emulator.cycles_per_frame(), 12);
+    }
+
+    #[test]
+    fn test_embedded_rom() {
+        static ROM: [u8; 4] = [0x60, 0x2A, 0x12, 0x02];
+        let mut chip8 = Chip8::with_embedded_rom(&ROM);
+
+        chip8.cycle();
+        assert_eq!(chip8.registers[0], 0x2A);
+        assert_eq!(chip8.rom, ROM);
+    }
+
+    #[test]
+    fn test_instances_on_threads() {
+        let handles: Vec<_> = (0..2).map(|_| {
+            let mut emulator = Emulator::builder()
+                .seed(7)
+                .rom_bytes(&[0xC0, 0xFF, 0x12, 0x00])
+                .build()
+                .unwrap();
+
+            std::thread::spawn(move || {
+                for _ in 0..100 {
+                    emulator.chip8.cycle();
+                }
+                emulator.chip8.registers[0]
+            })
+        }).collect();
+
+        let results: Vec<u8> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(results[0], results[1]);
+    }
+
+    #[test]
+    fn test_vip_rng_starts_over() {
+        // RND V0, 0xFF; RND V1, 0xFF
+        let mut chip8 = Emulator::builder()
+            .rng(RngKind::Vip)
+            .rom_bytes(&[0xC0, 0xFF, 0xC1, 0xFF])
+            .build()
+            .unwrap()
+            .chip8;
+
+        let run = |chip8: &mut Chip8| {
+            chip8.cycle();
+            chip8.cycle();
+            (chip8.registers[0], chip8.registers[1])
+        };
+        let numbers = run(&mut chip8);
+        assert_ne!(numbers.0, numbers.1);
+
+        // Without a seed the VIP generator still repeats itself after a reset
+        chip8.reset();
+        assert_eq!(run(&mut chip8), numbers);
+    }
+
+    #[test]
+    fn test_double_buffer_presents_on_timer_ticks() {
+        // LD I, 0x200; DRW V0, V0, 1
+        let mut chip8 = Emulator::builder()
+            .double_buffer(true)
+            .rom_bytes(&[0xA2, 0x00, 0xD0, 0x01])
+            .build()
+            .unwrap()
+            .chip8;
+        let events = chip8.events.subscribe();
+
+        chip8.cycle();
+        chip8.cycle();
+        assert_eq!(chip8.display.front_rows()[0], 0);
+        assert_eq!(events.try_recv().ok(), None);
+
+        chip8.update_timers();
+        assert_eq!(chip8.display.front_rows(), chip8.display.rows());
+        assert_eq!(events.try_recv().ok(), Some(Event::DisplayUpdated));
+
+        // Double buffering survives a reset
+        chip8.reset();
+        assert!(chip8.display.is_double_buffered());
+    }
+
+    #[test]
+    fn test_chip8x() {
+        // LD V0, 0x11; LD V1, 0x01; LD V2, 4; B012 (zones 1-2 of rows 1-2 green)
+        let mut chip8 = Emulator::builder()
+            .variant(Variant::Chip8X)
+            .rom_bytes(&[0x60, 0x11, 0x61, 0x01, 0x62, 0x04, 0xB0, 0x22])
+            .build()
+            .unwrap()
+            .chip8;
+
+        assert_eq!(chip8.pc, 0x300);
+        for _ in 0..4 {
+            chip8.cycle();
+        }
+
+        let colors = &chip8.chip8x.as_ref().unwrap().colors;
+        assert_eq!(colors.color(8, 1, true), chip8x::COLORS[4]);
+        assert_eq!(colors.color(23, 2, true), chip8x::COLORS[4]);
+        assert_eq!(colors.color(8, 3, true), chip8x::COLORS[1]);
+        assert_eq!(chip8.pc, 0x308);
+    }
+
+    #[test]
+    fn test_timers_by_clock() {
+        let clock = ManualClock::new();
+        let mut chip8 = Emulator::builder().build().unwrap().chip8;
+        chip8.timers.set_clock(60, Box::new(clock.clone()));
+        chip8.timers.delay = 10;
+
+        clock.advance(Duration::from_millis(100));
+        assert_eq!(chip8.run_timers(), 6);
+        assert_eq!(chip8.timers.delay, 4);
+
+        // A reset clears the timers, but they keep counting by the same clock
+        chip8.reset();
+        chip8.timers.delay = 10;
+        clock.advance(Duration::from_millis(50));
+        assert_eq!(chip8.run_timers(), 3);
+        assert_eq!(chip8.timers.delay, 7);
+    }
+
+    #[test]
+    fn test_zero_frequency() {
+        assert!(Emulator::builder().timer_hz(0).build().is_err());
+    }
+}
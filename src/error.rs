@@ -0,0 +1,38 @@
+//! Errors caused by a misbehaving ROM.
+//!
+//! Memory is only reached through checked accessors, so a ROM that reads, writes or jumps
+//! past the end of memory faults instead of crashing the emulator. The main loop pauses
+//! on a fault, the other front ends stop.
+
+use core::fmt;
+
+use alloc::format;
+
+use crate::locale::{fill, Text};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Chip8Error {
+    /// The program counter points past the last opcode in memory.
+    FetchOutOfBounds { pc: u16 },
+    /// The instruction at `pc` read from past the end of memory.
+    ReadOutOfBounds { pc: u16, address: usize },
+    /// The instruction at `pc` wrote to past the end of memory.
+    WriteOutOfBounds { pc: u16, address: usize },
+    /// The ROM does not fit in the memory after the program start, and was cut off.
+    RomTooLarge { size: usize, available: usize },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chip8Error::FetchOutOfBounds { pc } =>
+                write!(f, "{}", fill(Text::FetchOutOfBounds, &[&format!("{:#05X}", pc)])),
+            Chip8Error::ReadOutOfBounds { pc, address } => write!(f, "{}", fill(
+                Text::ReadOutOfBounds, &[&format!("{:#X}", address), &format!("{:#05X}", pc)])),
+            Chip8Error::WriteOutOfBounds { pc, address } => write!(f, "{}", fill(
+                Text::WriteOutOfBounds, &[&format!("{:#X}", address), &format!("{:#05X}", pc)])),
+            Chip8Error::RomTooLarge { size, available } =>
+                write!(f, "{}", fill(Text::RomTooLarge, &[size, available])),
+        }
+    }
+}
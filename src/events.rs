@@ -0,0 +1,121 @@
+//! What happened in the machine, published for the front ends to react to.
+//!
+//! Every front end subscribes to the bus of the machine and drains its events when it
+//! suits it, instead of checking flags on the machine after every batch of cycles. The
+//! subscriptions are channels, so a front end on another thread than the machine, such as
+//! the window of a `CoreThread`, receives the events all the same. A subscription ends when
+//! its receiver is dropped. Without std there are no channels, and nothing is published.
+
+#[cfg(feature = "std")]
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// What the display shows changed, through an instruction or, when it is double
+    /// buffered, through presenting it.
+    DisplayUpdated,
+    /// The buzzer started, with `true`, or stopped sounding.
+    SoundStateChanged(bool),
+    /// Fx0A halted execution until a key is pressed, which goes into the register.
+    KeyWaited(usize),
+    /// The debugger stopped at a breakpoint at this address.
+    Breakpoint(u16),
+    /// The machine ran into a fault or an instruction it cannot execute.
+    Error(String),
+}
+
+#[derive(Default)]
+pub struct EventBus {
+    #[cfg(feature = "std")]
+    subscribers: Vec<Sender<Event>>,
+}
+
+#[cfg(feature = "std")]
+impl EventBus {
+    /// Receive every event published from now on.
+    pub fn subscribe(&mut self) -> Receiver<Event> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+
+        receiver
+    }
+
+    /// Whether anyone listens, so the machine can skip working out events nobody receives.
+    pub fn has_subscribers(&self) -> bool {
+        !self.subscribers.is_empty()
+    }
+
+    /// Send an event to every subscriber, forgetting those that went away.
+    pub fn publish(&mut self, event: Event) {
+        self.subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl EventBus {
+    pub fn has_subscribers(&self) -> bool {
+        false
+    }
+
+    pub fn publish(&mut self, _event: Event) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    #[test]
+    fn test_machine_events() {
+        let mut chip8 = Chip8::new();
+        // LD I, 0x200; DRW V0, V0, 1; LD V0, 5; LD ST, V0; LD V1, K; SYS 0x123
+        chip8.load_bytes(&[
+            0xA2, 0x00, 0xD0, 0x01, 0x60, 0x05, 0xF0, 0x18, 0xF1, 0x0A, 0x01, 0x23,
+        ]);
+        let events = chip8.events.subscribe();
+
+        for _ in 0..5 {
+            chip8.cycle();
+        }
+        // The buzzer starts at the first tick and sounds for five more
+        for _ in 0..6 {
+            chip8.update_timers();
+        }
+
+        let mut keys = [false; 16];
+        keys[2] = true;
+        chip8.set_keys(keys);
+        chip8.cycle();
+        chip8.cycle();
+
+        let events: Vec<Event> = events.try_iter().collect();
+        assert_eq!(&events[..4], &[
+            Event::DisplayUpdated,
+            Event::KeyWaited(1),
+            Event::SoundStateChanged(true),
+            Event::SoundStateChanged(false),
+        ]);
+        assert!(matches!(&events[4], Event::Error(message) if message.contains("0x20A")),
+            "{:?}", events);
+        assert_eq!(events.len(), 5);
+    }
+
+    #[test]
+    fn test_dropped_subscribers() {
+        let mut bus = EventBus::default();
+        let kept = bus.subscribe();
+        drop(bus.subscribe());
+
+        bus.publish(Event::Breakpoint(0x200));
+        assert_eq!(bus.subscribers.len(), 1);
+        assert_eq!(kept.try_recv(), Ok(Event::Breakpoint(0x200)));
+
+        drop(kept);
+        bus.publish(Event::DisplayUpdated);
+        assert!(!bus.has_subscribers());
+    }
+}
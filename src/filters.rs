@@ -0,0 +1,146 @@
+//! Upscaling filters applied when the game display is copied to the window.
+//!
+//! Every filter doubles the resolution of the display, and the window is scaled up half as
+//! much in return, so switching filters does not change the size of the window.
+
+use std::str::FromStr;
+
+use crate::screen::{Buffer, Rect};
+
+/// Factor by which every filter scales the display.
+pub const FACTOR: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Nearest,
+    /// The Scale2x pixel art algorithm, which rounds off diagonal edges.
+    Scale2x,
+    /// Darkens every other row, like the scanlines of a CRT.
+    Scanlines,
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Filter, String> {
+        match s {
+            "nearest" => Ok(Filter::Nearest),
+            "scale2x" => Ok(Filter::Scale2x),
+            "scanlines" => Ok(Filter::Scanlines),
+            _ => Err(format!("Unknown filter '{}', expected nearest, scale2x or scanlines", s)),
+        }
+    }
+}
+
+impl Filter {
+    /// The filter after this one, for cycling through them with a hotkey.
+    pub fn next(self) -> Filter {
+        match self {
+            Filter::Nearest => Filter::Scale2x,
+            Filter::Scale2x => Filter::Scanlines,
+            Filter::Scanlines => Filter::Nearest,
+        }
+    }
+
+    /// Scale a region of the buffer up by `FACTOR`. The pixels around the region are taken
+    /// into account, so filtering a region gives the same result as filtering all of it.
+    pub fn apply_region(self, buffer: &Buffer, region: Rect) -> Buffer {
+        let (width, height) = (buffer.width(), buffer.height());
+        let pixels = buffer.pixels();
+        // Neighbouring pixels are clamped to the edges of the buffer
+        let at = |x: isize, y: isize| {
+            let x = x.max(0).min(width as isize - 1) as usize;
+            let y = y.max(0).min(height as isize - 1) as usize;
+
+            pixels[x + y * width]
+        };
+
+        let mut scaled = Buffer::new(region.width * FACTOR, region.height * FACTOR, None);
+
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                let (xi, yi) = (x as isize, y as isize);
+                let p = at(xi, yi);
+
+                let block = match self {
+                    Filter::Nearest => [p; 4],
+                    Filter::Scale2x => {
+                        let (a, b) = (at(xi, yi - 1), at(xi + 1, yi));
+                        let (c, d) = (at(xi - 1, yi), at(xi, yi + 1));
+
+                        [
+                            if c == a && c != d && a != b { a } else { p },
+                            if a == b && a != c && b != d { b } else { p },
+                            if d == c && d != b && c != a { c } else { p },
+                            if b == d && b != a && d != c { d } else { p },
+                        ]
+                    },
+                    Filter::Scanlines => [p, p, dim(p), dim(p)],
+                };
+
+                let (left, top) = ((x - region.x) * FACTOR, (y - region.y) * FACTOR);
+                scaled.set_pixel(left, top, block[0]);
+                scaled.set_pixel(left + 1, top, block[1]);
+                scaled.set_pixel(left, top + 1, block[2]);
+                scaled.set_pixel(left + 1, top + 1, block[3]);
+            }
+        }
+
+        scaled
+    }
+}
+
+/// Halve the brightness of every colour channel.
+pub fn dim(color: u32) -> u32 {
+    color >> 1 & 0x7F7F7F
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(filter: Filter, buffer: &Buffer) -> Buffer {
+        filter.apply_region(buffer, Rect::new(0, 0, buffer.width(), buffer.height()))
+    }
+
+    #[test]
+    fn test_nearest() {
+        let buffer = Buffer::new(2, 1, Some(vec!(1, 2)));
+
+        assert_eq!(apply(Filter::Nearest, &buffer).pixels(), &[1, 1, 2, 2, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn test_scale2x_rounds_diagonals() {
+        let buffer = Buffer::new(2, 2, Some(vec!(9, 0, 0, 9)));
+        let scaled = apply(Filter::Scale2x, &buffer);
+
+        // The two diagonal pixels are joined into a continuous line
+        assert_eq!(scaled.pixels(), &[
+            9, 9, 0, 0,
+            9, 0, 9, 0,
+            0, 9, 0, 9,
+            0, 0, 9, 9,
+        ]);
+    }
+
+    #[test]
+    fn test_apply_region() {
+        let buffer = Buffer::new(3, 3, Some(vec!(9, 0, 0, 0, 9, 0, 0, 0, 9)));
+        let full = apply(Filter::Scale2x, &buffer);
+        let region = Filter::Scale2x.apply_region(&buffer, Rect::new(1, 1, 2, 1));
+
+        assert_eq!(region.pixels(), &[
+            &full.pixels()[2 + 2 * 6..6 + 2 * 6],
+            &full.pixels()[2 + 3 * 6..6 + 3 * 6],
+        ].concat()[..]);
+    }
+
+    #[test]
+    fn test_scanlines() {
+        let buffer = Buffer::new(1, 1, Some(vec!(0xFFFFFF)));
+
+        assert_eq!(apply(Filter::Scanlines, &buffer).pixels(),
+            &[0xFFFFFF, 0xFFFFFF, 0x7F7F7F, 0x7F7F7F]);
+    }
+}
@@ -0,0 +1,314 @@
+//! Setting up on the first run. Launched without arguments and without a config file, the
+//! emulator asks in its window where the ROMs are, which keyboard layout the keypad should
+//! follow and how large the window should be, and writes the answers to a new config file.
+//!
+//! From then on, launching without arguments opens the gallery of the ROM directory.
+
+use std::{cell::RefCell, fs, path::Path, rc::Rc};
+
+use minifb::{Key, KeyRepeat, Scale, Window, WindowOptions};
+
+use crate::config::Config;
+use crate::layout::{host_key_label, Layout, KEYPAD};
+use crate::paths;
+use crate::screen::{Buffer, Point, TextLatch};
+use crate::text::{text_width, CHAR_WIDTH, LINE_HEIGHT};
+use crate::text_input;
+
+const WIDTH: usize = 160;
+const HEIGHT: usize = 80;
+const TITLE_COLOR: u32 = 0xFFFFFF;
+const TEXT_COLOR: u32 = 0xA0A0A0;
+const SELECTED_COLOR: u32 = 0x80FF80;
+const ERROR_COLOR: u32 = 0xFF4040;
+// Where ROMs are usually kept, next to the emulator
+const DEFAULT_ROM_DIR: &str = "roms";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step {
+    RomDir,
+    Layout,
+    Scale,
+}
+
+/// The answers to the questions of the first run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setup {
+    pub rom_dir: String,
+    pub layout: Layout,
+    pub large_scale: bool,
+}
+
+impl Setup {
+    /// The contents of the config file with the answers.
+    pub fn config_file(&self) -> String {
+        format!("# Written on the first run\nrom_dir = \"{}\"\nkeyboard_layout = {}\n\
+            large_scale = {}\n", self.rom_dir, self.layout.name(), self.large_scale)
+    }
+
+    /// Apply the answers to the configuration of this run.
+    pub fn apply(&self, config: &mut Config) {
+        config.rom_dir = Some(self.rom_dir.clone());
+        config.gallery = Some(self.rom_dir.clone());
+        config.keyboard_layout = Some(self.layout);
+        config.accessibility.large_scale = self.large_scale;
+    }
+}
+
+/// The questions, asked one after the other. Enter answers one, Escape goes back to the
+/// one before.
+pub struct FirstRun {
+    step: Step,
+    rom_dir: String,
+    layout: usize,
+    large_scale: bool,
+    error: Option<String>,
+}
+
+impl FirstRun {
+    pub fn new(rom_dir: &str, layout: Layout) -> FirstRun {
+        FirstRun {
+            step: Step::RomDir,
+            rom_dir: rom_dir.to_string(),
+            layout: Layout::ALL.iter().position(|&l| l == layout).unwrap_or(0),
+            large_scale: false,
+            error: None,
+        }
+    }
+
+    /// Answer the current question, returning the answers after the last one. The ROM
+    /// directory has to exist.
+    fn confirm(&mut self) -> Option<Setup> {
+        self.error = None;
+
+        match self.step {
+            Step::RomDir if !Path::new(self.rom_dir.trim()).is_dir() => {
+                self.error = Some(String::from("NO SUCH DIRECTORY"));
+            },
+            Step::RomDir => self.step = Step::Layout,
+            Step::Layout => self.step = Step::Scale,
+            Step::Scale => return Some(Setup {
+                rom_dir: self.rom_dir.trim().to_string(),
+                layout: Layout::ALL[self.layout],
+                large_scale: self.large_scale,
+            }),
+        }
+
+        None
+    }
+
+    /// Go back to the previous question, returning false on the first.
+    fn back(&mut self) -> bool {
+        self.error = None;
+
+        match self.step {
+            Step::RomDir => return false,
+            Step::Layout => self.step = Step::RomDir,
+            Step::Scale => self.step = Step::Layout,
+        }
+
+        true
+    }
+
+    /// Move the choice of the current question up or down.
+    fn choose(&mut self, down: bool) {
+        match self.step {
+            Step::RomDir => {},
+            Step::Layout => {
+                let count = Layout::ALL.len();
+                self.layout = if down { (self.layout + 1) % count } else {
+                    (self.layout + count - 1) % count
+                };
+            },
+            Step::Scale => self.large_scale = !self.large_scale,
+        }
+    }
+
+    /// Handle the keys and the text typed this frame. Returns the answers once all
+    /// questions are answered, and `Err` when the first run was cancelled.
+    pub fn handle_input(&mut self, window: &Window, typed: &str) -> Result<Option<Setup>, ()> {
+        if window.is_key_pressed(Key::Escape, KeyRepeat::No) && !self.back() {
+            return Err(());
+        }
+
+        if self.step == Step::RomDir {
+            if text_input::edit_line(&mut self.rom_dir, window, typed) {
+                return Ok(self.confirm());
+            }
+
+            return Ok(None);
+        }
+
+        if window.is_key_pressed(Key::Up, KeyRepeat::Yes) {
+            self.choose(false);
+        }
+        if window.is_key_pressed(Key::Down, KeyRepeat::Yes) {
+            self.choose(true);
+        }
+        if window.is_key_pressed(Key::Enter, KeyRepeat::No) {
+            return Ok(self.confirm());
+        }
+
+        Ok(None)
+    }
+
+    pub fn render(&self, buffer: &mut Buffer) {
+        buffer.clear();
+        buffer.draw_text("FIRST RUN SETUP", Point::new(1, 1), TITLE_COLOR);
+        let line = |idx: usize| Point::new(1, 1 + idx * LINE_HEIGHT);
+
+        match self.step {
+            Step::RomDir => {
+                buffer.draw_text("WHERE ARE YOUR ROMS?", line(2), TEXT_COLOR);
+
+                // The end of the path, when all of it does not fit
+                let input = format!("{}_", self.rom_dir.to_uppercase());
+                let fits = (buffer.width() - 2) / CHAR_WIDTH;
+                let skip = input.chars().count().saturating_sub(fits);
+                let input: String = input.chars().skip(skip).collect();
+                buffer.draw_text(&input, line(3), SELECTED_COLOR);
+
+                if let Some(error) = &self.error {
+                    buffer.draw_text(error, line(5), ERROR_COLOR);
+                }
+            },
+            Step::Layout => {
+                buffer.draw_text("KEYBOARD LAYOUT?", line(2), TEXT_COLOR);
+
+                for (idx, layout) in Layout::ALL.iter().enumerate() {
+                    let color = if idx == self.layout { SELECTED_COLOR } else { TEXT_COLOR };
+                    buffer.draw_text(&layout.name().to_uppercase(), line(idx + 3), color);
+                }
+
+                // The keys of the keypad with the chosen layout
+                let keymap = Layout::ALL[self.layout].keymap();
+                let left = buffer.width() - text_width("W W W W") - 2;
+                for (row, keys) in KEYPAD.iter().enumerate() {
+                    let labels: Vec<String> =
+                        keys.iter().map(|&key| host_key_label(keymap[key])).collect();
+                    let position = Point::new(left, 1 + (row + 3) * LINE_HEIGHT);
+                    buffer.draw_text(&labels.join(" "), position, TEXT_COLOR);
+                }
+            },
+            Step::Scale => {
+                buffer.draw_text("WINDOW SIZE?", line(2), TEXT_COLOR);
+
+                for (idx, &(label, large)) in [("NORMAL", false), ("LARGE", true)]
+                        .iter().enumerate() {
+                    let color = if large == self.large_scale { SELECTED_COLOR } else { TEXT_COLOR };
+                    buffer.draw_text(label, line(idx + 3), color);
+                }
+            },
+        }
+
+        let help = if self.step == Step::RomDir { "ENTER OK  ESC QUIT" } else {
+            "UP/DOWN  ENTER OK  ESC BACK"
+        };
+        buffer.draw_text(help, Point::new(1, buffer.height() - LINE_HEIGHT), TEXT_COLOR);
+    }
+}
+
+/// Ask the questions of the first run in a window, then write the config file and apply
+/// the answers to the configuration. Returns false when the window was closed first.
+pub fn run(config: &mut Config) -> Result<bool, String> {
+    let rom_dir = if Path::new(DEFAULT_ROM_DIR).is_dir() { DEFAULT_ROM_DIR } else { "." };
+    let mut first_run = FirstRun::new(rom_dir, Layout::detect());
+
+    let mut buffer = Buffer::new(WIDTH, HEIGHT, None);
+    let mut window = Window::new(
+        "CHIP-8 - first run",
+        WIDTH, HEIGHT,
+        WindowOptions {
+            resize: false,
+            scale: Scale::X4,
+            ..WindowOptions::default()
+        })
+        .map_err(|e| e.to_string())?;
+    let text = Rc::new(RefCell::new(String::new()));
+    window.set_input_callback(Box::new(TextLatch { text: text.clone() }));
+
+    let setup = loop {
+        if !window.is_open() {
+            return Ok(false);
+        }
+
+        let typed = text.replace(String::new());
+        match first_run.handle_input(&window, &typed) {
+            Ok(Some(setup)) => break setup,
+            Ok(None) => {},
+            Err(()) => return Ok(false),
+        }
+
+        first_run.render(&mut buffer);
+        window.update_with_buffer(buffer.pixels()).map_err(|e| e.to_string())?;
+    };
+
+    let path = paths::config_file();
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Could not create {}: {}", dir.display(), e))?;
+    }
+    fs::write(&path, setup.config_file())
+        .map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+    println!("Wrote the settings to {}", path.display());
+
+    setup.apply(config);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_questions() {
+        let mut first_run = FirstRun::new("no such directory", Layout::Azerty);
+        assert_eq!(Layout::ALL[first_run.layout], Layout::Azerty);
+
+        assert_eq!(first_run.confirm(), None);
+        assert_eq!(first_run.step, Step::RomDir);
+        assert!(first_run.error.is_some());
+
+        first_run.rom_dir = std::env::temp_dir().to_string_lossy().into_owned();
+        assert_eq!(first_run.confirm(), None);
+        assert_eq!(first_run.step, Step::Layout);
+
+        first_run.choose(false);
+        first_run.choose(false);
+        first_run.choose(true);
+        assert_eq!(Layout::ALL[first_run.layout], Layout::Qwerty);
+
+        assert_eq!(first_run.confirm(), None);
+        first_run.choose(true);
+        assert!(first_run.back());
+        assert!(first_run.back());
+        assert!(!first_run.back());
+        assert_eq!(first_run.step, Step::RomDir);
+
+        first_run.confirm();
+        first_run.confirm();
+        let setup = first_run.confirm().unwrap();
+        assert_eq!(setup.layout, Layout::Qwerty);
+        assert!(setup.large_scale);
+    }
+
+    #[test]
+    fn test_config_file() {
+        let setup = Setup {
+            rom_dir: String::from("/home/me/roms"),
+            layout: Layout::Dvorak,
+            large_scale: true,
+        };
+        let path = std::env::temp_dir()
+            .join(format!("chip8-first-run-{}.toml", std::process::id()));
+        fs::write(&path, setup.config_file()).unwrap();
+
+        let mut config = Config::default();
+        config.load_file(&path.to_string_lossy()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.rom_dir.as_deref(), Some("/home/me/roms"));
+        assert_eq!(config.keyboard_layout, Some(Layout::Dvorak));
+        assert!(config.accessibility.large_scale);
+    }
+}
@@ -0,0 +1,1040 @@
+//! The windowed front end: the game and debug displays, with the overlays and the loop
+//! that runs the machine in real time.
+//!
+//! `run` returns once the window is closed or the run ends by itself, with the reason, so
+//! other front ends and tests can start a run and see how it ended.
+
+use std::{fs, time, panic::{self, AssertUnwindSafe}, path::{Path, PathBuf}, sync::{Arc, Mutex}};
+
+use minifb::{Key, KeyRepeat};
+
+use crate::{archive, audio, demo, first_run, gallery, instance, paths, replay, usage};
+#[cfg(not(feature = "embedded-rom"))]
+use crate::{container, ips, recovery, rom_area};
+use crate::{Chip8, HEIGHT, WIDTH, WARNING_COLOR, State, reload_rom, storage_name};
+#[cfg(feature = "http")]
+use crate::http;
+use crate::accessibility::{FlickerFilter, FrameBlender};
+use crate::achievements::Achievements;
+use crate::announce::Announcer;
+use crate::bookmark::{Request, ScreenBookmark};
+use crate::bug_report::BugReporter;
+use crate::calibration::SpeedCalibrator;
+use crate::cheats::CheatList;
+use crate::command_socket::CommandSocket;
+use crate::config::Config;
+use crate::core_thread::{Command, CoreThread};
+use crate::debug_session::DebugSession;
+use crate::debugger::Debugger;
+use crate::disassembler::disassemble;
+use crate::display::Display;
+use crate::emulator::Emulator;
+use crate::events::Event;
+use crate::grid::PixelGrid;
+use crate::help::HelpOverlay;
+use crate::instance::Instance;
+use crate::keypad::{KeyInput, Source};
+use crate::layout::Layout;
+use crate::locale::{self, fill, tr, Text};
+use crate::logging::LogFilter;
+use crate::macros::Macros;
+use crate::menu::{Action, PauseMenu, Settings};
+use crate::palette::Palette;
+use crate::panels::Panels;
+use crate::patch::PatchPrompt;
+use crate::paths::DataKind;
+use crate::prompt::{Choice, NopList, OpcodePrompt};
+use crate::replay::{Recorder, Replay};
+use crate::rotation::Rotation;
+use crate::savestate::{SaveState, SlotStore};
+use crate::session::{ResumePrompt, Session};
+use crate::setup_test::SetupTest;
+use crate::screen::{Point, Screen};
+use crate::shutdown::{self, ShutdownReason};
+use crate::slots::SlotPicker;
+use crate::stream::FrameStream;
+use crate::symbols::SymbolTable;
+use crate::text::{text_width, LINE_HEIGHT};
+use crate::timing::{self, FrameBudget, FrameSkipper, IdlePacer, Ticker, TimerResolution};
+use crate::trace::TraceWriter;
+use crate::turbo::SmartTurbo;
+use crate::vip_timing::{self, VipClock};
+use crate::watch::RomWatcher;
+
+/// How a run of the windowed front end ended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExitReason {
+    /// The run came to its end by itself: the replay was played out, or the reader of the
+    /// frame stream went away.
+    Halted,
+    /// The window was closed, or the user quit from the menu or the unknown opcode prompt.
+    UserQuit,
+    /// Ctrl+C was pressed in the terminal, or the process was asked to terminate.
+    Interrupted,
+    /// The ROM was handed to an emulator that was already running.
+    Forwarded,
+}
+
+/// Run the ROM of the configuration in a window until it is closed.
+pub fn run(mut config: Config) -> Result<ExitReason, String> {
+    locale::set_language(config.language);
+
+    // The threaded core only shows the display, the rest would silently do nothing
+    let conflicts = config.threaded_conflicts();
+    if config.threaded && !conflicts.is_empty() {
+        return Err(format!("--threaded cannot be combined with {}", conflicts.join(", ")));
+    }
+
+    // A ROM double-clicked while the emulator runs is opened there instead
+    let instance_listener = if config.single_instance {
+        match instance::claim(&config.rom) {
+            Instance::Forwarded => {
+                println!("Opened {} in the running emulator", config.rom);
+                return Ok(ExitReason::Forwarded);
+            },
+            Instance::Running(listener) => listener,
+        }
+    } else {
+        None
+    };
+
+    // The frames are paced by sleeping, which needs a fine system timer on Windows
+    let _timer_resolution = TimerResolution::request();
+
+    // Launched for the first time, the settings are asked for and written to a new config
+    // file, which also sets the gallery to the ROM directory
+    if config.first_run && !first_run::run(&mut config)? {
+        return Ok(ExitReason::UserQuit);
+    }
+
+    if let Some(dir) = &config.gallery {
+        match gallery::pick(dir)? {
+            Some(rom) => config.rom = rom,
+            None => return Ok(ExitReason::UserQuit),
+        }
+    }
+
+    // Launched without arguments, the ROM of the last session is opened again
+    let session_dir = if config.save_session {
+        paths::data_dir(DataKind::Session)
+            .map_err(|e| println!("Could not create session directory: {}", e))
+            .ok()
+    } else {
+        None
+    };
+    let session = match &session_dir {
+        Some(dir) if config.resume => Session::load(dir),
+        _ => None,
+    };
+    if let Some(session) = &session {
+        config.rom = session.rom.clone();
+    }
+
+    // ROMs stored in a container bring their own settings
+    #[cfg(not(feature = "embedded-rom"))]
+    let rom = if config.rom.is_empty() {
+        demo::ROM.to_vec()
+    } else {
+        let (mut rom, metadata) = container::load(&config.rom)
+            .map_err(|e| format!("Could not open {}: {}", config.rom, e))?;
+        metadata.apply(&mut config);
+        if config.recover {
+            rom = recovery::recover(&rom, rom_area(config.variant));
+        }
+        match &config.patch {
+            Some(patch) => ips::apply_file(&rom, patch, rom_area(config.variant))?,
+            None => rom,
+        }
+    };
+
+    // In deterministic mode every frame runs the same number of cycles and a single timer
+    // tick, RND is seeded, and the input comes from or is recorded to a replay file
+    let replay = match &config.replay {
+        Some(path) => {
+            let contents = fs::read_to_string(path)
+                .map_err(|e| format!("Could not read {}: {}", path, e))?;
+            Some(Replay::parse(&contents)?)
+        },
+        None => None,
+    };
+    let seed = replay.as_ref().map_or(config.seed, |replay| replay.seed);
+
+    let emulator = Emulator::builder()
+        .variant(config.variant)
+        .quirks(config.quirks)
+        .rng(config.rng)
+        .protect_memory(config.protect_memory)
+        .strictness(config.strictness)
+        .double_buffer(config.double_buffer)
+        .stack_depth(config.stack_depth)
+        .cpu_hz(config.cpu_hz)
+        .timer_hz(config.timer_hz);
+    let emulator = if config.deterministic { emulator.seed(seed) } else { emulator };
+
+    // Build with `CHIP8_ROM=/path/to/rom.ch8 cargo build --features embedded-rom` to
+    // produce a standalone binary that always runs that ROM
+    #[cfg(feature = "embedded-rom")]
+    let emulator = emulator.rom_bytes(include_bytes!(env!("CHIP8_ROM")));
+    #[cfg(not(feature = "embedded-rom"))]
+    let emulator = emulator.rom_bytes(&rom);
+
+    let emulator = emulator.build()?;
+    let name = if config.rom.is_empty() { demo::NAME } else { &config.rom };
+    println!("Running {} as {}", name, emulator.variant.name());
+    let cycles_per_frame = emulator.cycles_per_frame();
+    let mut chip8 = emulator.chip8;
+
+    // The buzzer and the log are printed, unless stdout carries the display
+    if config.stream_fb.is_none() {
+        audio::attach(Arc::new(Mutex::new(audio::PrintSink)), &mut chip8);
+        let filter = config.log.clone().or_else(LogFilter::from_env).unwrap_or_default();
+        chip8.on_log(move |target, level, message| {
+            if filter.enabled(target, level) {
+                println!("[{}] {}", target, message);
+            }
+        });
+    } else {
+        audio::attach(Arc::new(Mutex::new(audio::NullSink)), &mut chip8);
+    }
+
+    // The output is kept alive for as long as the machine plays on it. Without one, the
+    // buzzer is shown on the game display instead.
+    #[cfg(feature = "audio")]
+    let audio_output = match audio::AudioOutput::new(config.waveform, config.audio_latency) {
+        Ok(audio) => {
+            audio::attach(audio.synth.clone(), &mut chip8);
+            Some(audio)
+        },
+        Err(e) => {
+            println!("Could not open audio output, showing the buzzer instead: {}", e);
+            None
+        },
+    };
+    #[cfg(feature = "audio")]
+    let visual_buzzer = audio_output.is_none();
+    #[cfg(not(feature = "audio"))]
+    let visual_buzzer = {
+        if config.audio_latency.is_some() {
+            println!("Built without audio output, ignoring the audio latency");
+        }
+        true
+    };
+
+    // Labels in breakpoints and watches refer to the symbol file
+    let symbols = match &config.symbols {
+        Some(path) => SymbolTable::load(path)?,
+        None => SymbolTable::default(),
+    };
+
+    let mut debugger = Debugger::new();
+    for mut breakpoint in config.breakpoints.drain(..) {
+        breakpoint.resolve(&symbols)?;
+        debugger.add_breakpoint(breakpoint);
+    }
+    if let Some(budget) = config.run_for {
+        debugger.run_for(budget);
+    }
+
+    let keymap = config.keyboard_layout.unwrap_or_else(Layout::detect).keymap();
+    let mut screen = Screen::new(
+        WIDTH, HEIGHT, 96, 64, config.debug_window, config.filter, keymap, config.accessibility);
+    let mut watches = Vec::new();
+    for mut watch in config.watches.drain(..) {
+        watch.resolve(&symbols)?;
+        watches.push(watch);
+    }
+    let mut panels = Panels::new(symbols.clone(), keymap, watches, &mut chip8);
+
+    if config.threaded {
+        if config.clean_feed {
+            println!("The clean feed is not shown when the core runs on its own thread");
+        }
+        return run_threaded(chip8, screen, &config, session_dir.as_deref(), visual_buzzer);
+    }
+    if config.clean_feed {
+        screen.open_clean_feed();
+    }
+
+    // Save states are kept per ROM, named after the ROM file (or archive entry)
+    let rom_name = storage_name(&config.rom);
+    let states_dir = paths::data_dir(DataKind::SaveStates).unwrap_or_else(|e| {
+        println!("Could not create save state directory: {}", e);
+        PathBuf::from(".")
+    });
+    let mut slot_picker = SlotPicker::new(SlotStore::new(states_dir.clone(), &rom_name));
+    let mut help = HelpOverlay::new();
+    let mut pixel_grid = PixelGrid::new(&mut chip8);
+    let mut setup_test = SetupTest::new(keymap);
+    if config.diag {
+        setup_test.open(&mut chip8);
+    }
+    let mut patch_prompt = PatchPrompt::new();
+
+    // Unknown opcodes pause the machine and ask what to do, unless the ROM's metadata says
+    // to treat them as NOPs
+    let metadata_dir = paths::data_dir(DataKind::Metadata).unwrap_or_else(|e| {
+        println!("Could not create metadata directory: {}", e);
+        PathBuf::from(".")
+    });
+    let mut nops = NopList::load(metadata_dir.clone(), &rom_name);
+    DebugSession::load(&metadata_dir, &rom_name).restore(&mut debugger, &mut panels, &symbols);
+    let mut opcode_prompt = OpcodePrompt::new();
+    let mut resume_prompt = ResumePrompt::new(session.map(|session| session.state));
+    let mut cheats = CheatList::load(metadata_dir.clone(), &rom_name);
+    let mut macros = Macros::load(metadata_dir.clone(), &rom_name);
+    screen.set_rotation(Rotation::load(&metadata_dir, &rom_name));
+    screen.set_palette(Palette::load(&metadata_dir, &rom_name));
+    let mut announcer = Announcer::load(metadata_dir.clone(), &rom_name,
+        config.speech_command.clone());
+    let mut achievements = Achievements::new();
+    let unlocks_dir = paths::data_dir(DataKind::Achievements).unwrap_or_else(|e| {
+        println!("Could not create achievements directory: {}", e);
+        PathBuf::from(".")
+    });
+    if config.achievements {
+        achievements.load(&metadata_dir, &unlocks_dir, &rom_name);
+        achievements.attach(&mut chip8);
+    }
+    let mut menu = PauseMenu::new();
+    let mut pause_on_focus_loss = config.pause_on_focus_loss;
+
+    #[cfg(feature = "http")]
+    let mut state_server = match &config.http_address {
+        Some(address) => Some(http::StateServer::new(address)
+            .map_err(|e| format!("Could not start HTTP server: {}", e))?),
+        None => None,
+    };
+    let mut command_socket = match &config.command_socket {
+        Some(address) => Some(CommandSocket::bind(address)
+            .map_err(|e| format!("Could not open the command socket: {}", e))?),
+        None => None,
+    };
+
+    // Trace to a file, or to stdout when only a format is given
+    let trace_file = config.trace_file.as_ref().map(String::as_str);
+    if let Some(format) = config.trace_format {
+        TraceWriter::new(format, trace_file)
+            .map_err(|e| format!("Could not open trace file: {}", e))?
+            .attach(&mut chip8);
+    }
+    let mut frame_stream = config.stream_fb.map(FrameStream::new);
+
+    // Reload the ROM whenever the assembler rewrites it
+    let rom_watcher = if config.watch {
+        let file = Path::new(archive::split_entry(&config.rom).0);
+        match RomWatcher::new(file) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                println!("Could not watch the ROM: {}", e);
+                None
+            },
+        }
+    } else {
+        None
+    };
+
+    let replay_end = replay.as_ref().and_then(|replay| replay.end);
+    // Bug reports come with the keys pressed
+    let mut recorder = if config.record.is_some() || config.bug_report {
+        Some(Recorder::new(seed))
+    } else {
+        None
+    };
+    let bug_reporter = if config.bug_report { Some(BugReporter::new(&mut chip8)) } else { None };
+    let mut frame: u64 = 0;
+
+    // Calibrating goes by the real timers, so not in deterministic mode or authentic timing
+    let calibrate = config.auto_speed && !config.deterministic && !config.authentic_timing;
+    let mut calibrator = if calibrate {
+        Some(SpeedCalibrator::load(metadata_dir.clone(), &rom_name, config.cpu_hz))
+    } else {
+        None
+    };
+    let cpu_hz = calibrator.as_ref().map_or(config.cpu_hz, SpeedCalibrator::cpu_hz);
+    let mut cpu_ticker = Ticker::new(cpu_hz);
+    let mut vip_clock = if config.authentic_timing { Some(VipClock::new()) } else { None };
+    let mut turbo = SmartTurbo::new(cpu_hz, config.timer_hz);
+    // The turbo runs instructions and timers at their own pace, which deterministic mode
+    // and authentic timing fix per frame
+    let turbo_allowed = !config.deterministic && vip_clock.is_none();
+    let mut bookmark = ScreenBookmark::load(&metadata_dir, &rom_name);
+    if let Some(hash) = bookmark.hash().filter(|_| turbo_allowed) {
+        turbo.seek(hash);
+    }
+    let mut idle_pacer = IdlePacer::new();
+    let mut frame_budget = FrameBudget::new();
+    let mut last_frame = time::Instant::now();
+    let mut planned = time::Duration::from_secs(0);
+    let mut frame_blender = if config.accessibility.blend_frames {
+        Some(FrameBlender::new())
+    } else {
+        None
+    };
+    let mut flicker_filter = if config.accessibility.reduce_flicker {
+        Some(FlickerFilter::new())
+    } else {
+        None
+    };
+    let mut skip_panels = false;
+    let mut drawn_warning = false;
+    let mut frame_skipper = FrameSkipper::new(config.frameskip);
+    let mut show_frame = true;
+    let events = chip8.events.subscribe();
+    shutdown::catch_interrupts();
+
+    // A panic still runs the shutdown hooks and autosaves, before it carries on
+    let result = panic::catch_unwind(AssertUnwindSafe(|| loop {
+        if !screen.window.is_open() {
+            break ExitReason::UserQuit;
+        }
+
+        if shutdown::interrupted() {
+            break ExitReason::Interrupted;
+        }
+
+        // Present the previous frame and poll input before running the CPU, so keys pressed
+        // while the loop was sleeping are seen by this batch of cycles
+        if show_frame {
+            screen.update();
+        } else {
+            screen.poll();
+        }
+
+        if replay_end == Some(frame) {
+            break ExitReason::Halted;
+        }
+
+        if rom_watcher.as_ref().map_or(false, RomWatcher::changed) {
+            match reload_rom(&mut chip8, &config) {
+                Ok(()) => println!("Reloaded {}", config.rom),
+                Err(e) => println!("Could not reload {}: {}", config.rom, e),
+            }
+        }
+
+        // Keys typed while an overlay is open go to the overlay instead
+        let overlay_open = slot_picker.is_open() || help.is_open() || opcode_prompt.is_open()
+            || resume_prompt.is_open()
+            || patch_prompt.is_open() || cheats.is_open() || menu.is_open()
+            || setup_test.is_open();
+        let mut input = KeyInput::default();
+        match &replay {
+            Some(replay) => input.set(Source::Replay, replay.keys_at(frame)),
+            // The number keys in the macro hotkeys do not go to the game
+            None if Macros::is_chord_held(&screen.window) => (),
+            None => input.set(Source::Keyboard, screen.keypad()),
+        }
+        if !overlay_open {
+            macros.handle_input(&screen.window, frame);
+        }
+        macros.record(frame, input.base());
+        if let Some(keys) = macros.played(frame) {
+            input.set(Source::Macro, keys);
+        }
+        input.set(Source::Debugger, debugger.held_keys());
+        let keys = input.merge();
+        chip8.set_keys(if overlay_open { [false; 16] } else { keys });
+        if let Some(chip8x) = chip8.chip8x.as_mut() {
+            chip8x.set_keys(if overlay_open { [false; 16] } else { screen.second_keypad() });
+        }
+
+        let now = time::Instant::now();
+        let frame_time = now - last_frame;
+        let elapsed = frame_budget.budget(frame_time, planned);
+        last_frame = now;
+
+        if screen.window.is_key_pressed(Key::F4, KeyRepeat::No) {
+            println!("Upscaling filter: {:?}", screen.cycle_filter());
+        }
+        pixel_grid.handle_input(&screen.window);
+        if let Some(reporter) = bug_reporter.as_ref() {
+            if BugReporter::is_requested(&screen.window) {
+                let replay = recorder.as_ref().map_or(String::new(), |r| r.finish(frame));
+                match reporter.save(&chip8, &config, &replay, frame, &rom_name) {
+                    Ok(path) => println!("Saved a bug report to {}", path.display()),
+                    Err(e) => println!("Could not save a bug report: {}", e),
+                }
+            }
+        }
+        if turbo_allowed {
+            turbo.handle_input(&screen.window);
+        }
+        match ScreenBookmark::handle_input(&screen.window) {
+            Some(Request::Set) => match bookmark.set(&chip8.display) {
+                Ok(()) => println!("Bookmarked the screen, the next run fast forwards to it"),
+                Err(e) => println!("Could not store the bookmark: {}", e),
+            },
+            Some(Request::Clear) => match bookmark.clear() {
+                Ok(()) => println!("Removed the bookmarked screen"),
+                Err(e) => println!("Could not remove the bookmark: {}", e),
+            },
+            None => (),
+        }
+
+        // F5 resumes after a breakpoint, F10 executes a single instruction while paused and
+        // F11 undoes the last one
+        if debugger.paused && screen.window.is_key_pressed(Key::F5, KeyRepeat::No) {
+            debugger.resume();
+        }
+
+        if debugger.paused && screen.window.is_key_pressed(Key::F11, KeyRepeat::Yes) {
+            if !debugger.step_back(&mut chip8) {
+                println!("No step to undo");
+            }
+        }
+
+        #[cfg(feature = "http")]
+        {
+            if let Some(server) = state_server.as_mut() {
+                server.poll(&mut chip8, &mut debugger);
+            }
+        }
+        if let Some(socket) = command_socket.as_mut() {
+            socket.poll(&mut chip8, &mut debugger);
+        }
+
+        let step = debugger.take_step()
+            || debugger.paused && screen.window.is_key_pressed(Key::F10, KeyRepeat::Yes);
+
+        let (cycles, timer_ticks) = if config.deterministic {
+            (cycles_per_frame, 1)
+        } else {
+            (cpu_ticker.advance(elapsed), chip8.timers.due())
+        };
+        // With authentic timing the instructions run until the frame's VIP cycles are spent
+        let cycles = if vip_clock.is_some() { u32::MAX } else { cycles };
+        // The smart turbo runs as many instructions as fit in the frame, ticking the timers
+        // as it goes
+        let turbo_frame = turbo.is_active();
+        let (cycles, timer_ticks) = if turbo_frame {
+            turbo.begin_frame();
+            (u32::MAX, 0)
+        } else {
+            (cycles, timer_ticks)
+        };
+
+        // The machine is frozen while an overlay is open, or optionally while the window is
+        // in the background. Time spent frozen is not caught up afterwards.
+        let unfocused = pause_on_focus_loss && !screen.is_focused();
+        let frozen = overlay_open || unfocused;
+
+        let mut executed = 0;
+        if step && !frozen {
+            debugger.record_step(&chip8);
+            chip8.cycle();
+            cheats.apply(&mut chip8);
+            // Every step is shown, the timers that present the display being frozen
+            chip8.present_display();
+            executed += 1;
+        } else if !debugger.paused && !frozen {
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.record(frame, chip8.keypad.state());
+            }
+            frame += 1;
+
+            if let Some(clock) = vip_clock.as_mut() {
+                for _ in 0..timer_ticks {
+                    clock.tick();
+                }
+            }
+
+            for _ in 0..cycles {
+                let pc = chip8.pc;
+                let opcode = chip8.fetch();
+                let waiting = chip8.state != State::Running;
+
+                if turbo_frame && turbo.ends_frame(opcode, waiting) {
+                    break;
+                }
+
+                if !vip_clock.as_ref().map_or(true, VipClock::has_credit) {
+                    break;
+                }
+
+                if !waiting && debugger.should_break(&chip8, opcode) {
+                    chip8.events.publish(Event::Breakpoint(pc));
+                    break;
+                }
+
+                if !waiting && debugger.spend_cycle(&chip8) {
+                    break;
+                }
+
+                // With the display wait quirk DRW waits for the next display interrupt, and
+                // then races the beam
+                let mut beam_race = None;
+                if let Some(clock) = vip_clock.as_mut() {
+                    if chip8.quirks.display_wait && opcode & 0xF000 == 0xD000 && !waiting {
+                        if !clock.at_interrupt() {
+                            clock.wait_for_interrupt();
+                            break;
+                        }
+
+                        let y = chip8.registers[(opcode as usize & 0x00F0) >> 4] as usize;
+                        let late = vip_timing::late_rows(y % HEIGHT, opcode as usize & 0xF);
+                        beam_race = Some((late, *chip8.display.rows()));
+                    }
+                }
+
+                chip8.cycle();
+                cheats.apply(&mut chip8);
+                executed += 1;
+
+                // Reaching the bookmarked screen pauses the machine
+                if turbo_frame && turbo.reached(opcode, &chip8.display) {
+                    debugger.paused = true;
+                    break;
+                }
+
+                if turbo_frame && turbo.spend_cycle() {
+                    chip8.update_timers();
+                }
+
+                if let Some(calibrator) = calibrator.as_mut().filter(|_| !waiting) {
+                    calibrator.observe(pc, opcode);
+                }
+
+                if let Some((late, previous)) = beam_race {
+                    chip8.display.hold_rows(late, &previous);
+                }
+
+                if let Some(clock) = vip_clock.as_mut() {
+                    clock.spend(opcode);
+                }
+
+                if chip8.protection_fault.take().is_some() {
+                    debugger.paused = true;
+                    break;
+                }
+
+                if let Some(address) = chip8.stack_fault.take() {
+                    let fault = fill(Text::StackFault, &[&format!("{:#05X}", address)]);
+                    println!("{}", fill(Text::Pausing, &[&fault]));
+                    debugger.paused = true;
+                    break;
+                }
+
+                if let Some(error) = chip8.bounds_fault.take() {
+                    println!("{}", fill(Text::Pausing, &[&error]));
+                    debugger.paused = true;
+                    break;
+                }
+
+                if let Some(address) = chip8.strictness_fault.take() {
+                    let fault = fill(Text::StrictnessFault, &[&format!("{:#05X}", address)]);
+                    println!("{}", fill(Text::Pausing, &[&fault]));
+                    debugger.paused = true;
+                    break;
+                }
+
+                if chip8.unknown_opcode.take().is_some() {
+                    if config.break_on_unknown {
+                        debugger.paused = true;
+                        break;
+                    }
+
+                    if !nops.contains(chip8.opcode) {
+                        opcode_prompt.open(chip8.opcode);
+                        break;
+                    }
+                }
+            }
+
+            announcer.announce(&chip8.memory);
+            achievements.update();
+            debugger.spend_frame(&chip8);
+
+            if let Some(hz) = calibrator.as_mut().and_then(SpeedCalibrator::end_frame) {
+                println!("Calibrated the CPU speed to {} Hz", hz);
+                cpu_ticker = Ticker::new(hz);
+            }
+
+            // The timers freeze together with the CPU while the debugger is paused
+            for _ in 0..timer_ticks {
+                chip8.update_timers();
+            }
+        }
+        panels.record_frame(executed, frame_time);
+
+        // While paused the title shows the next instruction, also without the debug panel
+        screen.set_status(if debugger.paused {
+            Some(format!("{:03X} {}", chip8.pc, disassemble(chip8.fetch())))
+        } else {
+            None
+        });
+
+        if let Some(stream) = frame_stream.as_mut() {
+            // Stop streaming once the reader has gone away, e.g. when ffmpeg exits
+            if stream.push(&chip8.display, elapsed).is_err() {
+                break ExitReason::Halted;
+            }
+        }
+
+        // Faults and breakpoints are reported where they pause the machine
+        let drawn = events.try_iter().fold(false, |drawn, event| {
+            drawn || event == Event::DisplayUpdated
+        });
+
+        // Only the rows that changed are drawn, unless a warning has to be drawn over
+        if drawn_warning {
+            chip8.display.mark_dirty();
+        }
+        let colors = chip8.chip8x.as_ref().map(|chip8x| &chip8x.colors);
+        match (flicker_filter.as_mut(), frame_blender.as_mut()) {
+            (Some(filter), _) => filter.render(&mut chip8.display, &mut screen.game_buffer, colors),
+            (None, Some(blender)) =>
+                blender.render(&mut chip8.display, &mut screen.game_buffer, colors),
+            (None, None) => chip8.display.render_changes(&mut screen.game_buffer, colors),
+        }
+        screen.capture_clean_feed();
+
+        // Warn on the game display while the host cannot keep up, and redraw the debug
+        // panels at half rate to save time
+        let behind = frame_budget.is_behind();
+        if behind {
+            screen.game_buffer.draw_text(tr(Text::Slow), Point::new(1, 1), WARNING_COLOR);
+        }
+        if macros.is_recording() {
+            let bottom = screen.game_buffer.height() - LINE_HEIGHT;
+            screen.game_buffer.draw_text(tr(Text::Recording), Point::new(1, bottom),
+                WARNING_COLOR);
+        }
+        // The checksum goes in the top right, for comparing runs frame by frame
+        if config.show_checksum {
+            let checksum = format!("{:08X}", replay::state_checksum(&chip8));
+            let left = screen.game_buffer.width() - text_width(&checksum) - 1;
+            screen.game_buffer.draw_text(&checksum, Point::new(left, 1), WARNING_COLOR);
+        }
+        let beeping = visual_buzzer && chip8.timers.sounding();
+        if beeping {
+            let bottom = screen.game_buffer.height() - LINE_HEIGHT;
+            let left = screen.game_buffer.width() - text_width(tr(Text::Beep)) - 1;
+            screen.game_buffer.draw_text(tr(Text::Beep), Point::new(left, bottom), WARNING_COLOR);
+        }
+        // Without a hint a ROM waiting in Fx0A looks frozen
+        let waiting = chip8.is_waiting_for_key();
+        if waiting {
+            draw_key_hint(&mut screen);
+        }
+        pixel_grid.render_label(&mut screen.game_buffer);
+        screen.set_grid(pixel_grid.overlay());
+        achievements.render(&mut screen.game_buffer);
+        opcode_prompt.render(&mut screen.game_buffer);
+        resume_prompt.render(&mut screen.game_buffer);
+        drawn_warning = behind || macros.is_recording() || opcode_prompt.is_open()
+            || resume_prompt.is_open() || config.show_checksum || beeping || waiting
+            || pixel_grid.is_shown() || achievements.is_showing();
+        skip_panels = behind && !skip_panels;
+        show_frame = frame_skipper.show(behind);
+
+        resume_prompt.handle_input(&screen.window, &mut chip8);
+        match opcode_prompt.handle_input(&screen.window) {
+            Some((Choice::Nop, opcode)) => {
+                if let Err(e) = nops.add(opcode) {
+                    println!("Could not remember {:04X} as a NOP: {}", opcode, e);
+                }
+            },
+            Some((Choice::Abort, _)) => break ExitReason::UserQuit,
+            Some((Choice::Skip, _)) | None => {},
+        }
+
+        // A ROM to switch to, picked from the menu or handed over by another instance
+        let mut open_rom = instance_listener.as_ref().and_then(|listener| listener.poll());
+
+        // Escape closes the setup test instead of opening the menu
+        let mouse = screen.debug_mouse();
+        let action = if setup_test.is_open() {
+            None
+        } else {
+            menu.handle_input(&screen.window, mouse, screen.debug_buffer.width())
+        };
+        match action {
+            Some(Action::Reset) => chip8.reset(),
+            Some(Action::LoadRom) => {
+                // Pick from the ROMs next to the current one
+                let dir = Path::new(archive::split_entry(&config.rom).0).parent()
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .map_or(String::from("."), |dir| dir.to_string_lossy().into_owned());
+
+                match gallery::pick(&dir) {
+                    Ok(path) => open_rom = path,
+                    Err(e) => println!("Could not open the gallery: {}", e),
+                }
+            },
+            Some(Action::States) => slot_picker.show(),
+            Some(Action::SetupTest) => setup_test.open(&mut chip8),
+            Some(Action::CycleFilter) => {
+                println!("Upscaling filter: {:?}", screen.cycle_filter());
+            },
+            Some(Action::CycleRotation) => {
+                let rotation = screen.rotation().next();
+                screen.set_rotation(rotation);
+                println!("Display rotation: {}", rotation);
+
+                if let Err(e) = rotation.store(&metadata_dir, &storage_name(&config.rom)) {
+                    println!("Could not store the rotation: {}", e);
+                }
+            },
+            Some(Action::CyclePalette) => {
+                let palette = screen.palette().next();
+                screen.set_palette(palette);
+                println!("Display colours: {} ({})", palette.name(), palette);
+
+                if let Err(e) = palette.store(&metadata_dir, &storage_name(&config.rom)) {
+                    println!("Could not store the colours: {}", e);
+                }
+            },
+            Some(Action::TogglePauseOnFocusLoss) => pause_on_focus_loss = !pause_on_focus_loss,
+            Some(Action::Quit) => break ExitReason::UserQuit,
+            None => {},
+        }
+
+        if let Some(path) = open_rom {
+            setup_test.close(&mut chip8);
+
+            match chip8.load_rom(&path, config.recover) {
+                Ok(()) => {
+                    println!("Running {}", path);
+                    store_debug_session(&debugger, &panels, &metadata_dir, &config.rom);
+                    let name = storage_name(&path);
+                    debugger.clear_breakpoints();
+                    DebugSession::load(&metadata_dir, &name)
+                        .restore(&mut debugger, &mut panels, &symbols);
+                    slot_picker = SlotPicker::new(SlotStore::new(states_dir.clone(), &name));
+                    nops = NopList::load(metadata_dir.clone(), &name);
+                    cheats = CheatList::load(metadata_dir.clone(), &name);
+                    macros = Macros::load(metadata_dir.clone(), &name);
+                    screen.set_rotation(Rotation::load(&metadata_dir, &name));
+                    screen.set_palette(Palette::load(&metadata_dir, &name));
+                    announcer = Announcer::load(metadata_dir.clone(), &name,
+                        config.speech_command.clone());
+                    if config.achievements {
+                        achievements.load(&metadata_dir, &unlocks_dir, &name);
+                    }
+                    if calibrate {
+                        let calibrated = SpeedCalibrator::load(metadata_dir.clone(), &name,
+                            config.cpu_hz);
+                        cpu_ticker = Ticker::new(calibrated.cpu_hz());
+                        calibrator = Some(calibrated);
+                    }
+                    config.rom = path;
+                },
+                Err(e) => println!("Could not load {}: {}", path, e),
+            }
+        }
+
+        let typed = screen.take_text();
+        setup_test.handle_input(&screen.window, keys, timer_ticks, &mut chip8);
+        let menus_open = menu.is_open() || setup_test.is_open();
+        if !menus_open {
+            help.handle_input(screen.debug_input(), &typed);
+        }
+        if !help.is_open() && !menus_open {
+            slot_picker.handle_input(&screen.window, &mut chip8);
+        }
+        if !help.is_open() && !menus_open && !slot_picker.is_open() {
+            patch_prompt.handle_input(screen.debug_input(), &typed, &mut chip8, &mut debugger);
+        }
+        if !help.is_open() && !menus_open && !slot_picker.is_open() && !patch_prompt.is_open() {
+            cheats.handle_input(&screen.window, &mut chip8, &mut debugger);
+        }
+
+        if setup_test.is_open() {
+            setup_test.render(&mut screen.debug_buffer);
+        } else if menu.is_open() {
+            let settings = Settings {
+                filter: screen.filter(),
+                rotation: screen.rotation(),
+                palette: screen.palette(),
+                pause_on_focus_loss,
+            };
+            menu.render(&mut screen.debug_buffer, &settings);
+        } else if help.is_open() {
+            help.render(&mut screen.debug_buffer);
+        } else if slot_picker.is_open() {
+            slot_picker.render(&mut screen.debug_buffer);
+        } else if patch_prompt.is_open() {
+            patch_prompt.render(&mut screen.debug_buffer);
+        } else if cheats.is_open() {
+            cheats.render(&mut screen.debug_buffer, &chip8);
+        } else {
+            panels.handle_input(screen.debug_input());
+
+            if !skip_panels {
+                panels.render(&chip8, &mut screen.debug_buffer);
+            }
+        }
+
+        let input = screen.window.get_keys().map_or(false, |keys| !keys.is_empty());
+        let quiet = debugger.paused || unfocused || chip8.state != State::Running || !drawn;
+
+        let wait_time = if config.deterministic {
+            time::Duration::from_secs(1) / config.timer_hz
+        } else {
+            idle_pacer.frame_time(quiet && !input)
+        };
+        timing::sleep(wait_time);
+        planned = wait_time;
+    }));
+
+    let reason = match result {
+        Ok(reason) => reason,
+        Err(panic) => {
+            shut_down(&mut chip8, &config.rom, ShutdownReason::Crash);
+            panic::resume_unwind(panic);
+        },
+    };
+    shut_down(&mut chip8, &config.rom, reason.into());
+    store_debug_session(&debugger, &panels, &metadata_dir, &config.rom);
+
+    for line in usage::report(&chip8) {
+        println!("{}", line);
+    }
+
+    if config.deterministic {
+        println!("State hash after {} frames: {:016X}", frame, replay::state_hash(&chip8));
+    }
+
+    if let Some(dir) = &session_dir {
+        if let Err(e) = Session::save(dir, &config.rom, &chip8) {
+            println!("Could not save the session: {}", e);
+        }
+    }
+
+    if let (Some(recorder), Some(path)) = (recorder, &config.record) {
+        if let Err(e) = fs::write(path, recorder.finish(frame)) {
+            println!("Could not write replay to {}: {}", path, e);
+        }
+    }
+
+    Ok(reason)
+}
+
+impl From<ExitReason> for ShutdownReason {
+    fn from(reason: ExitReason) -> ShutdownReason {
+        match reason {
+            ExitReason::Interrupted => ShutdownReason::Interrupted,
+            _ => ShutdownReason::Exit,
+        }
+    }
+}
+
+/// Keep the breakpoints and panels of the ROM for the next time it is debugged.
+fn store_debug_session(debugger: &Debugger, panels: &Panels, dir: &Path, rom: &str) {
+    if let Err(e) = DebugSession::capture(debugger, panels).store(dir, &storage_name(rom)) {
+        println!("Could not save the debugger session: {}", e);
+    }
+}
+
+/// Ask for a key in the middle of the game display, while the ROM waits for one.
+fn draw_key_hint(screen: &mut Screen) {
+    let buffer = &mut screen.game_buffer;
+    let left = buffer.width().saturating_sub(text_width(tr(Text::PressKey))) / 2;
+    let top = (buffer.height() - LINE_HEIGHT) / 2;
+
+    buffer.draw_text(tr(Text::PressKey), Point::new(left, top), WARNING_COLOR);
+}
+
+/// Run the shutdown hooks of the machine and autosave the state the run ended in, next to
+/// the save state slots of the ROM.
+fn shut_down(chip8: &mut Chip8, rom: &str, reason: ShutdownReason) {
+    chip8.shutdown(reason);
+
+    let state = SaveState::capture(chip8);
+    let saved = paths::data_dir(DataKind::SaveStates)
+        .and_then(|dir| SlotStore::new(dir, &storage_name(rom)).autosave(reason, &state));
+
+    match saved {
+        Ok(path) if reason != ShutdownReason::Exit => {
+            println!("Saved the state at the {} to {}", reason, path.display());
+        },
+        Ok(_) => {},
+        Err(e) => println!("Could not autosave the state: {}", e),
+    }
+}
+
+/// Run the machine on a thread of its own, with the window only showing the display and
+/// passing on the keys.
+fn run_threaded(chip8: Chip8, mut screen: Screen, config: &Config, session_dir: Option<&Path>,
+    visual_buzzer: bool) -> Result<ExitReason, String>
+{
+    let core = CoreThread::spawn(chip8, config.cpu_hz);
+    let mut display = Display::new();
+    let mut beeping = false;
+    let mut waiting = false;
+    let mut drawn_warning = false;
+    let mut paused = false;
+    let mut idle_pacer = IdlePacer::new();
+    shutdown::catch_interrupts();
+
+    let reason = loop {
+        if !screen.window.is_open() {
+            break ExitReason::UserQuit;
+        }
+
+        if shutdown::interrupted() {
+            break ExitReason::Interrupted;
+        }
+
+        screen.update();
+        core.send(Command::Keys(screen.keypad()));
+
+        let unfocused = config.pause_on_focus_loss && !screen.is_focused();
+        if unfocused != paused {
+            paused = unfocused;
+            core.send(if paused { Command::Pause } else { Command::Resume });
+        }
+
+        let frame = core.latest_frame();
+        if let Some(frame) = &frame {
+            display = Display::from_rows(frame.rows);
+            beeping = visual_buzzer && frame.beeping;
+            waiting = frame.waiting_for_key;
+        }
+
+        if drawn_warning {
+            display.mark_dirty();
+        }
+        display.render_changes(&mut screen.game_buffer, None);
+
+        if beeping {
+            let bottom = screen.game_buffer.height() - LINE_HEIGHT;
+            let left = screen.game_buffer.width() - text_width(tr(Text::Beep)) - 1;
+            screen.game_buffer.draw_text(tr(Text::Beep), Point::new(left, bottom), WARNING_COLOR);
+        }
+        if waiting {
+            draw_key_hint(&mut screen);
+        }
+        drawn_warning = beeping || waiting;
+
+        let input = screen.window.get_keys().map_or(false, |keys| !keys.is_empty());
+        timing::sleep(idle_pacer.frame_time(frame.is_none() && !input));
+    };
+
+    // The machine is lost when its thread panicked, and cannot be autosaved then
+    let mut chip8 = core.stop()?;
+    shut_down(&mut chip8, &config.rom, reason.into());
+    for line in usage::report(&chip8) {
+        println!("{}", line);
+    }
+
+    if let Some(dir) = session_dir {
+        if let Err(e) = Session::save(dir, &config.rom, &chip8) {
+            println!("Could not save the session: {}", e);
+        }
+    }
+
+    Ok(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_rom_is_an_error() {
+        let config = Config::from_args(vec!(String::from("missing.ch8"))).unwrap();
+
+        let error = run(config).unwrap_err();
+        assert!(error.starts_with("Could not open missing.ch8"), "{}", error);
+    }
+}
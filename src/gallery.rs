@@ -0,0 +1,248 @@
+use std::{fs, thread, path::{Path, PathBuf}};
+
+use minifb::{Key, KeyRepeat, Scale, Window, WindowOptions};
+
+use crate::emulator::Emulator;
+use crate::screen::{Buffer, Point};
+use crate::text::{CHAR_WIDTH, LINE_HEIGHT};
+use crate::{WIDTH, HEIGHT};
+
+/// How long every ROM runs before its screenshot is taken, a few seconds at 60 Hz.
+pub const PREVIEW_FRAMES: u32 = 300;
+
+const ROM_EXTENSIONS: [&str; 3] = ["ch8", "c8", "sc8"];
+
+const SELECTED_COLOR: u32 = 0xFFFFFF;
+const LABEL_COLOR: u32 = 0x808080;
+const COLUMNS: usize = 4;
+const ROWS: usize = 3;
+const CELL_WIDTH: usize = WIDTH + 2;
+const CELL_HEIGHT: usize = LINE_HEIGHT + HEIGHT + 2;
+
+/// A ROM in the gallery, with the screenshot taken while running it headlessly.
+pub struct Preview {
+    pub path: PathBuf,
+    pub name: String,
+    pub screenshot: Buffer,
+}
+
+/// The ROM files in a directory, sorted by name.
+pub fn list_roms(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("Could not read {}: {}", dir.display(), e))?;
+
+    let mut roms: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|extension| extension.to_str())
+                .map_or(false, |extension| ROM_EXTENSIONS.contains(&extension))
+        })
+        .collect();
+    roms.sort();
+
+    Ok(roms)
+}
+
+/// Run a ROM for `frames` frames and return the display of the frame with the most lit
+/// pixels, which is usually more telling than the last frame of an attract loop.
+pub fn capture(rom: &[u8], frames: u32) -> Result<Buffer, String> {
+    let emulator = Emulator::builder().seed(0).rom_bytes(rom).build()?;
+    let cycles_per_frame = emulator.cycles_per_frame();
+    let mut chip8 = emulator.chip8;
+
+    let mut best = chip8.display.to_buffer();
+    let mut best_lit = 0;
+
+    for _ in 0..frames {
+        for _ in 0..cycles_per_frame {
+            chip8.cycle();
+        }
+        chip8.update_timers();
+
+        let lit = chip8.display.lit_count();
+        if lit > best_lit {
+            best = chip8.display.to_buffer();
+            best_lit = lit;
+        }
+    }
+
+    Ok(best)
+}
+
+/// Capture a preview of every ROM in the directory. Each ROM runs on its own thread, and
+/// a ROM that crashes the emulator gets an empty screenshot.
+pub fn previews(dir: &Path) -> Result<Vec<Preview>, String> {
+    let handles: Vec<_> = list_roms(dir)?.into_iter().map(|path| {
+        let rom = fs::read(&path);
+
+        let handle = thread::spawn(move || {
+            let rom = rom.map_err(|e| e.to_string())?;
+            capture(&rom, PREVIEW_FRAMES)
+        });
+
+        (path, handle)
+    }).collect();
+
+    let previews = handles.into_iter().map(|(path, handle)| {
+        let name = path.file_stem()
+            .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+
+        let screenshot = match handle.join() {
+            Ok(Ok(screenshot)) => screenshot,
+            Ok(Err(e)) => {
+                println!("Could not preview {}: {}", path.display(), e);
+                Buffer::new(WIDTH, HEIGHT, None)
+            },
+            Err(_) => {
+                println!("Could not preview {}: the emulator crashed", path.display());
+                Buffer::new(WIDTH, HEIGHT, None)
+            },
+        };
+
+        Preview { path, name, screenshot }
+    }).collect();
+
+    Ok(previews)
+}
+
+/// A grid of ROM previews to pick from.
+///
+/// The arrow keys move the selection, Enter runs the selected ROM and Escape quits.
+pub struct Gallery {
+    previews: Vec<Preview>,
+    selected: usize,
+}
+
+impl Gallery {
+    pub fn new(previews: Vec<Preview>) -> Gallery {
+        Gallery {
+            previews,
+            selected: 0,
+        }
+    }
+
+    /// Move the selection by a number of cells, staying within the gallery.
+    pub fn move_selection(&mut self, offset: isize) {
+        let last = self.previews.len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + offset).max(0).min(last) as usize;
+    }
+
+    pub fn selected(&self) -> Option<&Preview> {
+        self.previews.get(self.selected)
+    }
+
+    pub fn handle_input(&mut self, window: &Window) {
+        let moves = [
+            (Key::Left, -1),
+            (Key::Right, 1),
+            (Key::Up, -(COLUMNS as isize)),
+            (Key::Down, COLUMNS as isize),
+        ];
+
+        for &(key, offset) in moves.iter() {
+            if window.is_key_pressed(key, KeyRepeat::Yes) {
+                self.move_selection(offset);
+            }
+        }
+    }
+
+    pub fn render(&self, buffer: &mut Buffer) {
+        buffer.clear();
+
+        let title = match self.selected() {
+            Some(preview) => preview.name.clone(),
+            None => String::from("NO ROMS FOUND"),
+        };
+        buffer.draw_text(&title, Point::new(0, 0), SELECTED_COLOR);
+
+        // Scroll so the row of the selection is always visible
+        let first_row = (self.selected / COLUMNS).saturating_sub(ROWS - 1);
+        let visible = self.previews.iter().enumerate()
+            .skip(first_row * COLUMNS)
+            .take(ROWS * COLUMNS);
+
+        for (idx, preview) in visible {
+            let left = (idx % COLUMNS) * CELL_WIDTH;
+            let top = LINE_HEIGHT + 1 + (idx / COLUMNS - first_row) * CELL_HEIGHT;
+            let color = if idx == self.selected { SELECTED_COLOR } else { LABEL_COLOR };
+
+            let label: String = preview.name.chars().take(WIDTH / CHAR_WIDTH).collect();
+            buffer.draw_text(&label, Point::new(left, top), color);
+
+            let pixels = preview.screenshot.pixels().iter().enumerate();
+            for (pixel, _) in pixels.filter(|(_, &value)| value != 0) {
+                buffer.set_pixel(
+                    left + pixel % WIDTH,
+                    top + LINE_HEIGHT + pixel / WIDTH,
+                    color);
+            }
+        }
+    }
+}
+
+/// The `--gallery` mode: preview the ROMs in a directory and let the user pick one,
+/// returning its path or `None` when the gallery was closed.
+pub fn pick(dir: &str) -> Result<Option<String>, String> {
+    println!("Previewing the ROMs in {}", dir);
+    let mut gallery = Gallery::new(previews(Path::new(dir))?);
+
+    let (width, height) = (COLUMNS * CELL_WIDTH, LINE_HEIGHT + 1 + ROWS * CELL_HEIGHT);
+    let mut buffer = Buffer::new(width, height, None);
+    let mut window = Window::new(
+        "CHIP-8 gallery - ENTER to run, ESC to exit",
+        width, height,
+        WindowOptions {
+            resize: false,
+            scale: Scale::X2,
+            ..WindowOptions::default()
+        })
+        .map_err(|e| e.to_string())?;
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        gallery.handle_input(&window);
+
+        if window.is_key_pressed(Key::Enter, KeyRepeat::No) {
+            if let Some(preview) = gallery.selected() {
+                return Ok(Some(preview.path.to_string_lossy().into_owned()));
+            }
+        }
+
+        gallery.render(&mut buffer);
+        window.update_with_buffer(buffer.pixels()).map_err(|e| e.to_string())?;
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_keeps_busiest_frame() {
+        // LD I, 0x208; DRW V0, V0, 5; JP 0x204; sprite of a 0
+        let rom = [0xA2, 0x08, 0xD0, 0x05, 0x12, 0x04, 0x00, 0x00,
+            0xF0, 0x90, 0x90, 0x90, 0xF0];
+        let screenshot = capture(&rom, 2).unwrap();
+
+        let lit = screenshot.pixels().iter().filter(|&&pixel| pixel != 0).count();
+        assert_eq!(lit, 14);
+    }
+
+    #[test]
+    fn test_move_selection() {
+        let preview = |name: &str| Preview {
+            path: PathBuf::from(name),
+            name: String::from(name),
+            screenshot: Buffer::new(WIDTH, HEIGHT, None),
+        };
+        let mut gallery = Gallery::new(vec!(preview("a"), preview("b"), preview("c")));
+
+        gallery.move_selection(COLUMNS as isize);
+        assert_eq!(gallery.selected().unwrap().name, "c");
+
+        gallery.move_selection(-5);
+        assert_eq!(gallery.selected().unwrap().name, "a");
+    }
+}
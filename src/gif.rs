@@ -0,0 +1,270 @@
+//! Encoding animated GIFs of the display, without an image library.
+//!
+//! The frames share one colour table of up to 256 colours, which covers everything the
+//! emulator draws. Every frame is stored in full and compressed with the variable-length
+//! LZW coding the format prescribes. The animation loops forever.
+
+use std::collections::HashMap;
+
+use crate::screen::Buffer;
+
+const MAX_CODES: u16 = 4096;
+
+/// Builds an animated GIF, one frame at a time.
+pub struct GifEncoder {
+    out: Vec<u8>,
+    palette: Vec<u32>,
+    width: usize,
+    height: usize,
+}
+
+impl GifEncoder {
+    /// Start an animation of frames of the given size, drawn in the colours of the palette.
+    pub fn new(width: usize, height: usize, palette: &[u32]) -> Result<GifEncoder, String> {
+        if palette.is_empty() || palette.len() > 256 {
+            return Err(format!("A GIF has 1 to 256 colours, not {}", palette.len()));
+        }
+        if width > 0xFFFF || height > 0xFFFF {
+            return Err(format!("A GIF is at most 65535 pixels wide and high, not {}x{}",
+                width, height));
+        }
+
+        let mut out = Vec::new();
+        out.extend_from_slice(b"GIF89a");
+        out.extend_from_slice(&(width as u16).to_le_bytes());
+        out.extend_from_slice(&(height as u16).to_le_bytes());
+
+        // A global colour table of 2^(bits + 1) entries, the unused ones black
+        let bits = color_bits(palette.len());
+        out.extend_from_slice(&[0xF0 | (bits - 1), 0, 0]);
+        for idx in 0..1 << bits {
+            let color = palette.get(idx).copied().unwrap_or(0);
+            out.extend_from_slice(&[(color >> 16) as u8, (color >> 8) as u8, color as u8]);
+        }
+
+        // Loop forever
+        out.extend_from_slice(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00");
+
+        Ok(GifEncoder { out, palette: palette.to_vec(), width, height })
+    }
+
+    /// Add a frame, shown for `delay` hundredths of a second. Every pixel of the buffer has
+    /// to be one of the colours of the palette.
+    pub fn add_frame(&mut self, buffer: &Buffer, delay: u16) -> Result<(), String> {
+        if (buffer.width(), buffer.height()) != (self.width, self.height) {
+            return Err(format!("Frame of {}x{} in an animation of {}x{}",
+                buffer.width(), buffer.height(), self.width, self.height));
+        }
+
+        let indices = buffer.pixels().iter()
+            .map(|&pixel| {
+                self.palette.iter().position(|&color| color == pixel).map(|idx| idx as u8)
+                    .ok_or(format!("Colour {:06X} is not in the palette", pixel))
+            })
+            .collect::<Result<Vec<u8>, String>>()?;
+
+        // Graphic control extension with the delay
+        self.out.extend_from_slice(&[0x21, 0xF9, 0x04, 0x00]);
+        self.out.extend_from_slice(&delay.to_le_bytes());
+        self.out.extend_from_slice(&[0x00, 0x00]);
+
+        // Image descriptor covering the whole animation, without a colour table of its own
+        self.out.extend_from_slice(&[0x2C, 0, 0, 0, 0]);
+        self.out.extend_from_slice(&(self.width as u16).to_le_bytes());
+        self.out.extend_from_slice(&(self.height as u16).to_le_bytes());
+        self.out.push(0x00);
+
+        let min_code_size = color_bits(self.palette.len()).max(2);
+        self.out.push(min_code_size);
+        for block in lzw_encode(&indices, min_code_size).chunks(255) {
+            self.out.push(block.len() as u8);
+            self.out.extend_from_slice(block);
+        }
+        self.out.push(0x00);
+
+        Ok(())
+    }
+
+    /// The encoded animation.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.out.push(0x3B);
+        self.out
+    }
+}
+
+/// The bits needed for the index of a colour, at least one.
+fn color_bits(colors: usize) -> u8 {
+    let mut bits = 1;
+    while 1 << bits < colors {
+        bits += 1;
+    }
+
+    bits
+}
+
+/// Packs codes of varying width into bytes, least significant bit first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u32,
+    count: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, width: u8) {
+        self.bits |= (code as u32) << self.count;
+        self.count += width;
+
+        while self.count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.bits as u8);
+        }
+
+        self.bytes
+    }
+}
+
+/// Compress colour indices with GIF's LZW coding. The codes start one bit wider than the
+/// indices and grow up to 12 bits, after which the table is cleared.
+fn lzw_encode(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+
+    let mut writer = BitWriter { bytes: Vec::new(), bits: 0, count: 0 };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = min_code_size + 1;
+    let mut next = end + 1;
+
+    writer.write(clear, code_size);
+
+    let mut indices = indices.iter();
+    let mut prefix = match indices.next() {
+        Some(&index) => index as u16,
+        None => {
+            writer.write(end, code_size);
+            return writer.finish();
+        },
+    };
+
+    for &index in indices {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+
+        writer.write(prefix, code_size);
+
+        if next < MAX_CODES {
+            if next == 1 << code_size {
+                code_size += 1;
+            }
+            table.insert((prefix, index), next);
+            next += 1;
+        } else {
+            writer.write(clear, code_size);
+            table.clear();
+            code_size = min_code_size + 1;
+            next = end + 1;
+        }
+
+        prefix = index as u16;
+    }
+
+    writer.write(prefix, code_size);
+    writer.write(end, code_size);
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Decode the way a viewer does, to check the encoder against
+    fn lzw_decode(data: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear = 1u16 << min_code_size;
+        let end = clear + 1;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut code_size = min_code_size + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        let mut output = Vec::new();
+        let (mut bits, mut count, mut bytes) = (0u32, 0u8, data.iter());
+
+        loop {
+            while count < code_size {
+                bits |= (*bytes.next().unwrap() as u32) << count;
+                count += 8;
+            }
+            let code = (bits & ((1 << code_size) - 1)) as u16;
+            bits >>= code_size;
+            count -= code_size;
+
+            if code == clear {
+                table = (0..clear).map(|index| vec!(index as u8)).collect();
+                table.extend(vec!(Vec::new(), Vec::new()));
+                code_size = min_code_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == end {
+                return output;
+            }
+
+            let entry = match (table.get(code as usize), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => {
+                    let mut entry = previous.clone();
+                    entry.push(previous[0]);
+                    entry
+                },
+                (None, None) => panic!("Code {} before any other", code),
+            };
+            if let Some(mut previous) = previous {
+                previous.push(entry[0]);
+                table.push(previous);
+                if table.len() == 1 << code_size && code_size < 12 {
+                    code_size += 1;
+                }
+            }
+
+            output.extend_from_slice(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn test_lzw_round_trip() {
+        let inputs = [
+            vec!(0, 0, 0, 0, 1, 1, 0, 1),
+            // Long enough to fill the table and clear it
+            (0..40_000u32).map(|idx| (idx * idx / 7 % 4) as u8).collect(),
+            vec!(3),
+        ];
+
+        for input in inputs.iter() {
+            assert_eq!(&lzw_decode(&lzw_encode(input, 2), 2), input);
+        }
+    }
+
+    #[test]
+    fn test_encoder() {
+        let mut encoder = GifEncoder::new(2, 1, &[0x000000, 0xFFFFFF, 0x808080]).unwrap();
+        encoder.add_frame(&Buffer::new(2, 1, Some(vec!(0xFFFFFF, 0))), 7).unwrap();
+        assert!(encoder.add_frame(&Buffer::new(2, 1, Some(vec!(0x123456, 0))), 7).is_err());
+        assert!(encoder.add_frame(&Buffer::new(1, 1, None), 7).is_err());
+        let gif = encoder.finish();
+
+        assert_eq!(&gif[..13], b"GIF89a\x02\x00\x01\x00\xF1\x00\x00");
+        // Four colours in the table, the unused one black
+        assert_eq!(&gif[13..25], &[0, 0, 0, 255, 255, 255, 128, 128, 128, 0, 0, 0]);
+        // The delay of the frame
+        let control = gif.windows(3).position(|bytes| bytes == [0x21, 0xF9, 0x04]).unwrap();
+        assert_eq!(&gif[control + 4..control + 6], &[7, 0]);
+        assert_eq!(gif.last(), Some(&0x3B));
+    }
+}
@@ -0,0 +1,191 @@
+//! Golden traces: the hash of the display after every frame of a deterministic run,
+//! together with the final registers. Comparing a run against a golden trace shows whether
+//! a change to the emulator altered what a ROM does, and from which frame on.
+//!
+//! ```text
+//! seed 0
+//! 0 84A1C3E2F0B4D617
+//! 1 84A1C3E2F0B4D617
+//! registers {"pc":516,...}
+//! ```
+
+use std::{fmt, fs};
+
+use crate::Chip8;
+use crate::archive::read_rom;
+use crate::emulator::Emulator;
+use crate::replay::frame_hash;
+use crate::trace::registers_json;
+
+const DEFAULT_FRAMES: u64 = 600;
+const RECORD_USAGE: &str = "Usage: chip8 record-golden rom.ch8 [--frames N] [--seed N] [-o out]";
+const VERIFY_USAGE: &str = "Usage: chip8 verify-golden rom.ch8 golden.txt";
+
+#[derive(Debug, PartialEq)]
+pub struct Golden {
+    pub seed: u64,
+    pub frames: Vec<u64>,
+    pub registers: String,
+}
+
+impl Golden {
+    /// Run a ROM in deterministic mode, without input, recording every frame.
+    pub fn record(rom: &[u8], seed: u64, frames: u64) -> Result<Golden, String> {
+        let emulator = Emulator::builder().seed(seed).rom_bytes(rom).build()?;
+        let cycles_per_frame = emulator.cycles_per_frame();
+        let mut chip8 = emulator.chip8;
+
+        let frames = (0..frames).map(|_| {
+            run_frame(&mut chip8, cycles_per_frame);
+            frame_hash(&chip8.display)
+        }).collect();
+
+        Ok(Golden { seed, frames, registers: registers_json(&chip8) })
+    }
+
+    pub fn parse(contents: &str) -> Result<Golden, String> {
+        let mut golden = Golden { seed: 0, frames: Vec::new(), registers: String::new() };
+
+        for line in contents.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let (key, value) = match line.find(' ') {
+                Some(idx) => (&line[..idx], &line[idx + 1..]),
+                None => return Err(format!("Invalid golden trace line '{}'", line)),
+            };
+
+            match key {
+                "seed" => golden.seed = value.parse()
+                    .map_err(|_| format!("Invalid seed '{}'", value))?,
+                "registers" => golden.registers = value.to_string(),
+                _ => {
+                    let hash = u64::from_str_radix(value, 16)
+                        .map_err(|_| format!("Invalid frame hash '{}'", value))?;
+                    golden.frames.push(hash);
+                },
+            }
+        }
+
+        Ok(golden)
+    }
+
+    /// Compare a new run against this golden trace, describing the first difference.
+    pub fn compare(&self, run: &Golden) -> Result<(), String> {
+        let mismatch = self.frames.iter().zip(&run.frames).position(|(a, b)| a != b);
+
+        if let Some(frame) = mismatch {
+            return Err(format!("Display differs from frame {} on", frame));
+        }
+
+        if self.registers != run.registers {
+            return Err(format!("Registers differ after {} frames: expected {}, got {}",
+                self.frames.len(), self.registers, run.registers));
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Golden {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "seed {}", self.seed)?;
+
+        for (frame, hash) in self.frames.iter().enumerate() {
+            writeln!(f, "{} {:016X}", frame, hash)?;
+        }
+
+        writeln!(f, "registers {}", self.registers)
+    }
+}
+
+/// Run the cycles of one frame followed by a single timer tick, as in deterministic mode.
+pub fn run_frame(chip8: &mut Chip8, cycles: u32) {
+    for _ in 0..cycles {
+        chip8.cycle();
+    }
+
+    chip8.update_timers();
+}
+
+/// The `record-golden` subcommand.
+pub fn record_command(args: &[String]) -> Result<(), String> {
+    let mut path = None;
+    let mut output = None;
+    let mut frames = DEFAULT_FRAMES;
+    let mut seed = 0;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" | "--seed" => {
+                let value = args.next().ok_or(format!("{} requires a number", arg))?;
+                let number = value.parse().map_err(|_| format!("Invalid number '{}'", value))?;
+
+                if arg == "--frames" { frames = number } else { seed = number }
+            },
+            "-o" => output = Some(args.next().ok_or("-o requires a path")?),
+            _ => path = Some(arg),
+        }
+    }
+
+    let path = path.ok_or(RECORD_USAGE)?;
+    let rom = read_rom(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    let golden = Golden::record(&rom, seed, frames)?;
+
+    match output {
+        Some(output) => fs::write(output, golden.to_string())
+            .map_err(|e| format!("Could not write {}: {}", output, e)),
+        None => {
+            print!("{}", golden.to_string());
+            Ok(())
+        },
+    }
+}
+
+/// The `verify-golden` subcommand.
+pub fn verify_command(args: &[String]) -> Result<(), String> {
+    let (path, golden_path) = match args {
+        [path, golden_path] => (path, golden_path),
+        _ => return Err(String::from(VERIFY_USAGE)),
+    };
+
+    let rom = read_rom(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    let contents = fs::read_to_string(golden_path)
+        .map_err(|e| format!("Could not read {}: {}", golden_path, e))?;
+    let golden = Golden::parse(&contents)?;
+
+    let run = Golden::record(&rom, golden.seed, golden.frames.len() as u64)?;
+    golden.compare(&run)?;
+
+    println!("{} frames match {}", golden.frames.len(), golden_path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // LD V0, 0x05; LD I, 0x20A; DRW V0, V0, 1; loop: ADD V1, 0x01; JP loop; sprite
+    const ROM: [u8; 11] = [0x60, 0x05, 0xA2, 0x0A, 0xD0, 0x01, 0x71, 0x01, 0x12, 0x06, 0xFF];
+
+    #[test]
+    fn test_golden_round_trip() {
+        let golden = Golden::record(&ROM, 0, 3).unwrap();
+        let parsed = Golden::parse(&golden.to_string()).unwrap();
+
+        assert_eq!(parsed, golden);
+        assert_eq!(golden.compare(&Golden::record(&ROM, 0, 3).unwrap()), Ok(()));
+    }
+
+    #[test]
+    fn test_golden_mismatch() {
+        let golden = Golden::record(&ROM, 0, 3).unwrap();
+
+        let mut run = Golden::record(&ROM, 0, 3).unwrap();
+        run.frames[1] ^= 1;
+        assert_eq!(golden.compare(&run), Err(String::from("Display differs from frame 1 on")));
+
+        let mut changed = ROM;
+        changed[7] = 0x02;
+        let run = Golden::record(&changed, 0, 3).unwrap();
+        assert!(golden.compare(&run).unwrap_err().starts_with("Registers differ"));
+    }
+}
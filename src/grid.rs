@@ -0,0 +1,186 @@
+//! An overlay for ROM developers positioning sprites: lines between the pixels of the game
+//! display, with the sprite of the most recent DRW outlined and its coordinates shown.
+//!
+//! Ctrl+G toggles the overlay. The lines are drawn over the scaled up display, every eighth
+//! one brighter to count pixels by. They are not drawn over a rotated display, where they
+//! would not line up.
+
+use std::sync::{Arc, Mutex};
+
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::{Chip8, HEIGHT, WIDTH};
+use crate::filters::FACTOR;
+use crate::screen::{Buffer, Point, Rect};
+use crate::text::LINE_HEIGHT;
+
+const LINE_COLOR: u32 = 0x406080;
+const OUTLINE_COLOR: u32 = 0xFF6040;
+const LABEL_COLOR: u32 = 0xFF6040;
+// Pixels between the brighter lines
+const MAJOR: usize = 8;
+
+/// Where a sprite was drawn, in pixels of the display.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprite {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// What the screen draws over the scaled up game display.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridOverlay {
+    pub outline: Option<Sprite>,
+}
+
+impl GridOverlay {
+    /// Draw the overlay over the part of the scaled up display in `area`, which was just
+    /// drawn. The lines are blended in, so pixels must not be drawn over twice.
+    pub fn draw(&self, buffer: &mut Buffer, area: Rect) {
+        for y in area.y..(area.y + area.height).min(HEIGHT * FACTOR) {
+            for x in area.x..(area.x + area.width).min(WIDTH * FACTOR) {
+                let color = match (x % FACTOR == 0, y % FACTOR == 0) {
+                    (false, false) => continue,
+                    _ if x % (MAJOR * FACTOR) == 0 || y % (MAJOR * FACTOR) == 0 => 2,
+                    _ => 1,
+                };
+
+                let pixel = buffer.pixels()[y * buffer.width() + x];
+                buffer.set_pixel(x, y, blend(pixel, LINE_COLOR, color));
+            }
+        }
+
+        if let Some(sprite) = self.outline {
+            let left = sprite.x * FACTOR;
+            let top = sprite.y * FACTOR;
+            let right = ((sprite.x + sprite.width) * FACTOR).min(WIDTH * FACTOR) - 1;
+            let bottom = ((sprite.y + sprite.height) * FACTOR).min(HEIGHT * FACTOR) - 1;
+
+            for x in left..=right {
+                buffer.set_pixel(x, top, OUTLINE_COLOR);
+                buffer.set_pixel(x, bottom, OUTLINE_COLOR);
+            }
+            for y in top..=bottom {
+                buffer.set_pixel(left, y, OUTLINE_COLOR);
+                buffer.set_pixel(right, y, OUTLINE_COLOR);
+            }
+        }
+    }
+}
+
+/// The colour a quarter or, with a `weight` of 2, half of the way to the line colour.
+fn blend(pixel: u32, line: u32, weight: u32) -> u32 {
+    (0..3).fold(0, |blended, channel| {
+        let shift = channel * 8;
+        let (from, to) = (pixel >> shift & 0xFF, line >> shift & 0xFF);
+
+        blended | ((from * (4 - weight) + to * weight) / 4) << shift
+    })
+}
+
+/// Follows the sprites a ROM draws and toggles the overlay.
+pub struct PixelGrid {
+    last_draw: Arc<Mutex<Option<Sprite>>>,
+    shown: bool,
+}
+
+impl PixelGrid {
+    pub fn new(chip8: &mut Chip8) -> PixelGrid {
+        let last_draw = Arc::new(Mutex::new(None));
+
+        let recorder = last_draw.clone();
+        chip8.on_pre_cycle(move |chip8| {
+            if let Some(sprite) = sprite_at_pc(chip8) {
+                *recorder.lock().unwrap() = Some(sprite);
+            }
+        });
+
+        PixelGrid { last_draw, shown: false }
+    }
+
+    /// Toggle the overlay with Ctrl+G.
+    pub fn handle_input(&mut self, window: &Window) {
+        let ctrl = window.is_key_down(Key::LeftCtrl) || window.is_key_down(Key::RightCtrl);
+        if ctrl && window.is_key_pressed(Key::G, KeyRepeat::No) {
+            self.shown = !self.shown;
+        }
+    }
+
+    pub fn is_shown(&self) -> bool {
+        self.shown
+    }
+
+    /// The overlay for the screen to draw, while it is shown.
+    pub fn overlay(&self) -> Option<GridOverlay> {
+        if self.shown {
+            Some(GridOverlay { outline: *self.last_draw.lock().unwrap() })
+        } else {
+            None
+        }
+    }
+
+    /// Show the coordinates of the most recent sprite on the game display.
+    pub fn render_label(&self, buffer: &mut Buffer) {
+        if let (true, Some(sprite)) = (self.shown, *self.last_draw.lock().unwrap()) {
+            let label = format!("DRW {},{}", sprite.x, sprite.y);
+            buffer.draw_text(&label, Point::new(1, 1 + LINE_HEIGHT), LABEL_COLOR);
+        }
+    }
+}
+
+/// The sprite the instruction about to execute draws, if it is a DRW.
+fn sprite_at_pc(chip8: &Chip8) -> Option<Sprite> {
+    let opcode = chip8.try_fetch().ok().filter(|opcode| opcode & 0xF000 == 0xD000)?;
+    let (width, rows) = chip8.sprite_rules.shape((opcode & 0xF) as u8);
+
+    Some(Sprite {
+        x: chip8.registers[(opcode >> 8 & 0xF) as usize] as usize % WIDTH,
+        y: chip8.registers[(opcode >> 4 & 0xF) as usize] as usize % HEIGHT,
+        width: 8 * width,
+        height: rows,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_draw() {
+        let mut chip8 = Chip8::new();
+        // LD V0, 70; LD V1, 20; DRW V0, V1, 5
+        chip8.load_bytes(&[0x60, 0x46, 0x61, 0x14, 0xD0, 0x15]);
+        let grid = PixelGrid::new(&mut chip8);
+        assert_eq!(grid.overlay(), None);
+
+        for _ in 0..3 {
+            chip8.cycle();
+        }
+        assert_eq!(*grid.last_draw.lock().unwrap(),
+            Some(Sprite { x: 6, y: 20, width: 8, height: 5 }));
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut buffer = Buffer::new(WIDTH * FACTOR, HEIGHT * FACTOR, None);
+        let overlay = GridOverlay { outline: Some(Sprite { x: 60, y: 0, width: 8, height: 1 }) };
+        overlay.draw(&mut buffer, Rect::new(0, 0, WIDTH * FACTOR, HEIGHT * FACTOR));
+
+        let pixel = |x: usize, y: usize| buffer.pixels()[y * WIDTH * FACTOR + x];
+        assert_eq!(pixel(1, 1), 0);
+        assert_eq!(pixel(2, 1), blend(0, LINE_COLOR, 1));
+        assert_eq!(pixel(0, 1), blend(0, LINE_COLOR, 2));
+        // The outline is cut off at the edge instead of wrapping around
+        assert_eq!(pixel(120, 0), OUTLINE_COLOR);
+        assert_eq!(pixel(127, 1), OUTLINE_COLOR);
+        assert_eq!(pixel(0, 0), blend(0, LINE_COLOR, 2));
+    }
+
+    #[test]
+    fn test_blend() {
+        assert_eq!(blend(0xFFFFFF, 0x000000, 2), 0x7F7F7F);
+        assert_eq!(blend(0x000000, 0x408000, 1), 0x102000);
+    }
+}
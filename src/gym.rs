@@ -0,0 +1,252 @@
+//! An environment for agents that learn to play CHIP-8 games, after the gym API.
+//!
+//! `reset` starts the ROM over and `step` holds the keys of an action for one frame. After
+//! either the agent observes the display and the bytes at the watched addresses, such as
+//! the score or the lives left, from which it derives its reward. An episode is done when
+//! the ROM exits, faults, reaches an unknown opcode or runs out of frames.
+//!
+//! `chip8 gym rom.ch8 --watch 2F0,2F1` serves the environment over stdin and stdout, for
+//! agents written in other languages. Every `reset` or `step` line, the latter followed by
+//! the keys to hold as hex digits, is answered with an observation as a line of JSON:
+//!
+//! ```text
+//! step 4 6
+//! {"display":["0000000000000000",...],"memory":[0,3],"done":false}
+//! ```
+
+use std::{io::{self, BufRead, Write}, panic::{self, AssertUnwindSafe}};
+
+use crate::{Chip8, HEIGHT, MEMORY};
+use crate::archive::read_rom;
+use crate::batch::EXIT_OPCODE;
+use crate::emulator::Emulator;
+use crate::golden::run_frame;
+
+const USAGE: &str = "Usage: chip8 gym rom.ch8 [--watch ADDR,ADDR..] [--max-frames N] \
+    [--seed N]";
+
+/// What the agent gets to see after a reset or a step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub display: [u64; HEIGHT],
+    /// The bytes at the watched addresses, in the order they were given.
+    pub memory: Vec<u8>,
+}
+
+impl Observation {
+    pub fn to_json(&self, done: bool) -> String {
+        let rows: Vec<String> = self.display.iter()
+            .map(|row| format!("\"{:016X}\"", row))
+            .collect();
+
+        format!("{{\"display\":[{}],\"memory\":{:?},\"done\":{}}}",
+            rows.join(","), self.memory, done)
+    }
+}
+
+/// A ROM to play episodes of, one frame per step.
+pub struct Environment {
+    rom: Vec<u8>,
+    seed: u64,
+    watched: Vec<u16>,
+    max_frames: Option<u64>,
+    chip8: Chip8,
+    cycles_per_frame: u32,
+    frame: u64,
+    done: bool,
+}
+
+impl Environment {
+    pub fn new(rom: Vec<u8>, seed: u64, watched: Vec<u16>, max_frames: Option<u64>)
+            -> Result<Environment, String> {
+        if let Some(&address) = watched.iter().find(|&&address| address as usize >= MEMORY) {
+            return Err(format!("Invalid address '{:X}'", address));
+        }
+
+        let emulator = Environment::start(&rom, seed)?;
+
+        Ok(Environment {
+            rom,
+            seed,
+            watched,
+            max_frames,
+            cycles_per_frame: emulator.cycles_per_frame(),
+            chip8: emulator.chip8,
+            frame: 0,
+            done: false,
+        })
+    }
+
+    fn start(rom: &[u8], seed: u64) -> Result<Emulator, String> {
+        Emulator::builder().seed(seed).rom_bytes(rom).build()
+    }
+
+    /// Start a new episode. The random numbers are seeded the same for every episode, so
+    /// the same actions play out the same.
+    pub fn reset(&mut self) -> Observation {
+        // The ROM was loaded once already, so starting it again cannot fail
+        self.chip8 = Environment::start(&self.rom, self.seed).expect("ROM loaded before").chip8;
+        self.frame = 0;
+        self.done = false;
+
+        self.observe()
+    }
+
+    /// Hold the keys for a frame, returning the observation after it and whether the
+    /// episode is done. Once done, the machine no longer runs until it is reset.
+    pub fn step(&mut self, keys: [bool; 16]) -> (Observation, bool) {
+        if !self.done {
+            self.chip8.set_keys(keys);
+
+            let chip8 = &mut self.chip8;
+            let cycles_per_frame = self.cycles_per_frame;
+            let ran = panic::catch_unwind(AssertUnwindSafe(|| {
+                run_frame(chip8, cycles_per_frame)
+            }));
+            self.frame += 1;
+
+            self.done = ran.is_err()
+                || self.chip8.try_fetch() == Ok(EXIT_OPCODE)
+                || self.chip8.unknown_opcode.is_some()
+                || self.chip8.bounds_fault.is_some()
+                || self.chip8.stack_fault.is_some()
+                || self.max_frames.map_or(false, |max_frames| self.frame >= max_frames);
+        }
+
+        (self.observe(), self.done)
+    }
+
+    fn observe(&self) -> Observation {
+        Observation {
+            display: *self.chip8.display.rows(),
+            memory: self.watched.iter()
+                .map(|&address| self.chip8.memory[address as usize])
+                .collect(),
+        }
+    }
+}
+
+/// The keys of a `step` line, as hex digits separated by spaces.
+fn parse_keys<'a>(digits: impl Iterator<Item = &'a str>) -> Result<[bool; 16], String> {
+    let mut keys = [false; 16];
+
+    for digit in digits {
+        let key = usize::from_str_radix(digit, 16).ok().filter(|&key| key < 16)
+            .ok_or(format!("Invalid key '{}'", digit))?;
+        keys[key] = true;
+    }
+
+    Ok(keys)
+}
+
+/// The `gym` subcommand.
+pub fn run_command(args: &[String]) -> Result<(), String> {
+    let mut path = None;
+    let mut watched = Vec::new();
+    let mut max_frames = None;
+    let mut seed = 0;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            path = Some(arg);
+            continue;
+        }
+
+        let value = args.next().ok_or(format!("{} requires a value", arg))?;
+        match arg.as_str() {
+            "--watch" => for address in value.split(',') {
+                watched.push(u16::from_str_radix(address.trim(), 16)
+                    .map_err(|_| format!("Invalid address '{}'", address))?);
+            },
+            "--max-frames" => max_frames = Some(value.parse()
+                .map_err(|_| format!("Invalid number '{}'", value))?),
+            "--seed" => seed = value.parse().map_err(|_| format!("Invalid number '{}'", value))?,
+            _ => return Err(format!("Unknown argument '{}'\n{}", arg, USAGE)),
+        }
+    }
+
+    let path = path.ok_or(USAGE)?;
+    let rom = read_rom(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    let mut environment = Environment::new(rom, seed, watched, max_frames)?;
+
+    // Crashes end the episode, they are not reported as backtraces
+    panic::set_hook(Box::new(|_| {}));
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        let mut words = line.split_whitespace();
+
+        let reply = match words.next() {
+            Some("reset") => environment.reset().to_json(false),
+            Some("step") => match parse_keys(words) {
+                Ok(keys) => {
+                    let (observation, done) = environment.step(keys);
+                    observation.to_json(done)
+                },
+                Err(e) => format!("{{\"error\":{:?}}}", e),
+            },
+            Some("quit") => break,
+            Some(command) => format!("{{\"error\":\"Unknown command '{}'\"}}", command),
+            None => continue,
+        };
+
+        writeln!(out, "{}", reply).and_then(|_| out.flush()).map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_episode() {
+        // LD I, 0x300; LD V0, K; LD B, V0; EXIT
+        let rom = vec!(0xA3, 0x00, 0xF0, 0x0A, 0xF0, 0x33, 0x00, 0xFD);
+        let mut environment = Environment::new(rom, 0, vec!(0x302), Some(100)).unwrap();
+
+        assert_eq!(environment.reset().memory, vec!(0));
+
+        let (observation, done) = environment.step([false; 16]);
+        assert_eq!((observation.memory, done), (vec!(0), false));
+
+        let mut keys = [false; 16];
+        keys[7] = true;
+        let (observation, done) = environment.step(keys);
+        assert_eq!((observation.memory, done), (vec!(7), true));
+
+        // A new episode starts over
+        assert_eq!(environment.reset().memory, vec!(0));
+        assert!(!environment.step([false; 16]).1);
+    }
+
+    #[test]
+    fn test_max_frames() {
+        let mut environment = Environment::new(vec!(0xF0, 0x0A), 0, Vec::new(), Some(2)).unwrap();
+
+        assert!(!environment.step([false; 16]).1);
+        assert!(environment.step([false; 16]).1);
+        assert!(environment.step([false; 16]).1);
+    }
+
+    #[test]
+    fn test_parse() {
+        let keys = parse_keys("4 F".split_whitespace()).unwrap();
+        assert!(keys[4] && keys[15]);
+        assert!(parse_keys(["10"].iter().copied()).is_err());
+        assert!(Environment::new(vec!(0x00, 0xE0), 0, vec!(0x1000), None).is_err());
+    }
+
+    #[test]
+    fn test_to_json() {
+        let observation = Observation { display: [0; HEIGHT], memory: vec!(3) };
+        let json = observation.to_json(true);
+
+        assert!(json.starts_with("{\"display\":[\"0000000000000000\","));
+        assert!(json.ends_with("],\"memory\":[3],\"done\":true}"));
+    }
+}
@@ -0,0 +1,201 @@
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::screen::{Buffer, Point};
+use crate::text_input;
+use crate::text::{CHAR_WIDTH, LINE_HEIGHT};
+
+const TITLE_COLOR: u32 = 0xFFFFFF;
+const TEXT_COLOR: u32 = 0xA0A0A0;
+const QUERY_COLOR: u32 = 0x80C0FF;
+
+// The instruction groups, in the order they are listed
+const OPS_SOURCE: [&str; 6] = [
+    include_str!("ops/flow.rs"),
+    include_str!("ops/alu.rs"),
+    include_str!("ops/memory.rs"),
+    include_str!("ops/display.rs"),
+    include_str!("ops/input.rs"),
+    include_str!("ops/timers.rs"),
+];
+
+/// One instruction of the opcode reference.
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    /// The opcode pattern and mnemonic, such as `8xy5 - SUB Vx, Vy`.
+    pub title: String,
+    pub description: String,
+}
+
+impl Entry {
+    fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+
+        self.title.to_lowercase().contains(&query)
+            || self.description.to_lowercase().contains(&query)
+    }
+}
+
+/// Build the opcode reference from the doc comments in the `ops` modules.
+///
+/// Every instruction is documented as `/// (pattern - mnemonic)` followed by its semantics,
+/// so the reference stays in sync with the implementation.
+pub fn opcode_reference(source: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut lines = source.lines().map(str::trim).peekable();
+
+    while let Some(line) = lines.next() {
+        if !line.starts_with("/// (") || !line.ends_with(')') {
+            continue;
+        }
+
+        let title = line["/// (".len()..line.len() - 1].to_string();
+        let mut description = Vec::new();
+
+        while let Some(doc) = lines.peek().filter(|l| l.starts_with("///")) {
+            description.push(doc.trim_start_matches('/').trim());
+            lines.next();
+        }
+
+        entries.push(Entry { title, description: description.join(" ").trim().to_string() });
+    }
+
+    entries
+}
+
+/// Split text into lines of at most `width` characters, breaking between words.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            lines.push(std::mem::replace(&mut line, String::new()));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// A help overlay with the opcode reference.
+///
+/// F1 opens and closes it. Typing searches the mnemonics and descriptions, Backspace
+/// removes a character and Up/Down page through the matching instructions.
+pub struct HelpOverlay {
+    entries: Vec<Entry>,
+    open: bool,
+    query: String,
+    selected: usize,
+}
+
+impl HelpOverlay {
+    pub fn new() -> HelpOverlay {
+        HelpOverlay {
+            entries: opcode_reference(&OPS_SOURCE.concat()),
+            open: false,
+            query: String::new(),
+            selected: 0,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    fn matches(&self) -> Vec<&Entry> {
+        self.entries.iter().filter(|entry| entry.matches(&self.query)).collect()
+    }
+
+    pub fn handle_input(&mut self, window: &Window, typed: &str) {
+        if window.is_key_pressed(Key::F1, KeyRepeat::No) {
+            self.open = !self.open;
+        }
+
+        if !self.open {
+            return;
+        }
+
+        let query_length = self.query.len();
+        text_input::edit_line(&mut self.query, window, typed);
+        if self.query.len() > query_length {
+            self.selected = 0;
+        }
+
+        if window.is_key_pressed(Key::Up, KeyRepeat::Yes) {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        if window.is_key_pressed(Key::Down, KeyRepeat::Yes) {
+            self.selected += 1;
+        }
+
+        self.selected = self.selected.min(self.matches().len().saturating_sub(1));
+    }
+
+    pub fn render(&self, buffer: &mut Buffer) {
+        buffer.clear();
+
+        let columns = buffer.width() / CHAR_WIDTH;
+        let matches = self.matches();
+
+        buffer.draw_text(&format!("?{}", self.query), Point::new(0, 0), QUERY_COLOR);
+
+        let entry = match matches.get(self.selected) {
+            Some(entry) => entry,
+            None => {
+                buffer.draw_text("NO MATCHES", Point::new(0, LINE_HEIGHT), TEXT_COLOR);
+                return;
+            },
+        };
+
+        let count = format!("{}/{}", self.selected + 1, matches.len());
+        let count_left = buffer.width().saturating_sub(count.len() * CHAR_WIDTH);
+        buffer.draw_text(&count, Point::new(count_left, 0), TEXT_COLOR);
+
+        let title = wrap(&entry.title, columns);
+        let description = wrap(&entry.description, columns);
+        let rows = buffer.height() / LINE_HEIGHT;
+
+        let lines = title.iter().map(|line| (line, TITLE_COLOR))
+            .chain(description.iter().map(|line| (line, TEXT_COLOR)))
+            .take(rows - 1);
+
+        for (idx, (line, color)) in lines.enumerate() {
+            buffer.draw_text(line, Point::new(0, (idx + 1) * LINE_HEIGHT), color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opcode_reference() {
+        let source = "/// (00E0 - CLS)\n/// Clear the display.\npub fn cls() {}\n";
+
+        assert_eq!(opcode_reference(source), vec!(Entry {
+            title: String::from("00E0 - CLS"),
+            description: String::from("Clear the display."),
+        }));
+    }
+
+    #[test]
+    fn test_reference_covers_ops() {
+        let entries = opcode_reference(&OPS_SOURCE.concat());
+
+        assert!(entries.iter().any(|entry| entry.title == "8xy5 - SUB Vx, Vy"));
+        assert!(entries.iter().all(|entry| !entry.description.is_empty()));
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("Set Vx = Vx - Vy", 8), vec!("Set Vx =", "Vx - Vy"));
+    }
+}
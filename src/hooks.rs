@@ -0,0 +1,171 @@
+use core::{fmt, mem};
+
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::Chip8;
+use crate::logging::{Level, Target};
+use crate::shutdown::ShutdownReason;
+
+/// Called with the machine before an instruction executes.
+pub type PreCycleHook = Box<dyn FnMut(&Chip8) + Send>;
+/// Called with the machine after an instruction executed, with its address and opcode.
+pub type PostCycleHook = Box<dyn FnMut(&Chip8, u16, u16) + Send>;
+/// Called with the machine once the run ends, see `shutdown`.
+pub type ShutdownHook = Box<dyn FnOnce(&Chip8, ShutdownReason) + Send>;
+/// Called with the diagnostic messages of the core, see `logging`.
+pub type LogHook = Box<dyn FnMut(Target, Level, fmt::Arguments) + Send>;
+/// Called with the XO-CHIP audio pattern and pitch when either changes.
+pub type AudioPatternHook = Box<dyn FnMut(Option<[u8; 16]>, u8) + Send>;
+
+/// Callbacks that let an embedder react to the machine, for example to start and stop its
+/// own audio backend without polling the sound timer every frame. The callbacks are `Send`
+/// so several machines can run on their own threads, each with its own hooks.
+///
+/// The cycle hooks are the single instrumentation point for tools such as the tracer, and
+/// any number of them can be registered. They only run for instructions that execute, not
+/// while the machine waits for a key.
+#[derive(Default)]
+pub struct Hooks {
+    pub sound_start: Option<Box<dyn FnMut() + Send>>,
+    pub sound_stop: Option<Box<dyn FnMut() + Send>>,
+    pub timer_tick: Option<Box<dyn FnMut(u8, u8) + Send>>,
+    pub log: Option<LogHook>,
+    pub audio_pattern: Option<AudioPatternHook>,
+    pub pre_cycle: Vec<PreCycleHook>,
+    pub post_cycle: Vec<PostCycleHook>,
+    pub shutdown: Vec<ShutdownHook>,
+}
+
+impl Hooks {
+    /// Call the sound callbacks if the buzzer starts or stops sounding.
+    pub fn sound_changed(&mut self, before: bool, after: bool) {
+        let hook = match (before, after) {
+            (false, true) => &mut self.sound_start,
+            (true, false) => &mut self.sound_stop,
+            _ => return,
+        };
+
+        if let Some(hook) = hook {
+            hook();
+        }
+    }
+
+    pub fn tick(&mut self, delay_timer: u8, sound_timer: u8) {
+        if let Some(hook) = &mut self.timer_tick {
+            hook(delay_timer, sound_timer);
+        }
+    }
+
+    pub fn audio_pattern_changed(&mut self, pattern: Option<[u8; 16]>, pitch: u8) {
+        if let Some(hook) = &mut self.audio_pattern {
+            hook(pattern, pitch);
+        }
+    }
+
+    /// Pass a diagnostic message to the log callback, messages are dropped without one.
+    pub fn log(&mut self, target: Target, level: Level, message: fmt::Arguments) {
+        if let Some(hook) = &mut self.log {
+            hook(target, level, message);
+        }
+    }
+}
+
+/// Run the pre-cycle hooks of a machine. The hooks are taken out while they run, so they
+/// can borrow the machine.
+pub fn pre_cycle(chip8: &mut Chip8) {
+    if chip8.hooks.pre_cycle.is_empty() {
+        return;
+    }
+
+    let mut hooks = mem::take(&mut chip8.hooks.pre_cycle);
+    for hook in hooks.iter_mut() {
+        hook(chip8);
+    }
+    chip8.hooks.pre_cycle = hooks;
+}
+
+/// Run the post-cycle hooks of a machine for the instruction at `pc`.
+pub fn post_cycle(chip8: &mut Chip8, pc: u16, opcode: u16) {
+    if chip8.hooks.post_cycle.is_empty() {
+        return;
+    }
+
+    let mut hooks = mem::take(&mut chip8.hooks.post_cycle);
+    for hook in hooks.iter_mut() {
+        hook(chip8, pc, opcode);
+    }
+    chip8.hooks.post_cycle = hooks;
+}
+
+/// Run the shutdown hooks of a machine, which are gone after, so they run only once.
+pub fn shutdown(chip8: &mut Chip8, reason: ShutdownReason) {
+    let hooks = mem::take(&mut chip8.hooks.shutdown);
+    for hook in hooks {
+        hook(chip8, reason);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_sound_edges() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut hooks = Hooks::default();
+
+        let start_events = events.clone();
+        hooks.sound_start = Some(Box::new(move || start_events.lock().unwrap().push("start")));
+        let stop_events = events.clone();
+        hooks.sound_stop = Some(Box::new(move || stop_events.lock().unwrap().push("stop")));
+
+        hooks.sound_changed(false, true);
+        hooks.sound_changed(true, true);
+        hooks.sound_changed(true, false);
+        hooks.sound_changed(false, false);
+
+        assert_eq!(*events.lock().unwrap(), vec!("start", "stop"));
+    }
+
+    #[test]
+    fn test_cycle_hooks() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut chip8 = Chip8::new();
+        // LD V0, 0x2A; LD V1, 0x01
+        chip8.load_bytes(&[0x60, 0x2A, 0x61, 0x01]);
+
+        let pre_events = events.clone();
+        chip8.on_pre_cycle(move |chip8| {
+            pre_events.lock().unwrap().push((chip8.pc, chip8.registers[0]));
+        });
+        let post_events = events.clone();
+        chip8.on_post_cycle(move |chip8, pc, opcode| {
+            assert_eq!(chip8.pc, pc + 2);
+            post_events.lock().unwrap().push((opcode, chip8.registers[0]));
+        });
+
+        chip8.cycle();
+        chip8.cycle();
+
+        assert_eq!(*events.lock().unwrap(),
+            vec!((0x200, 0x00), (0x602A, 0x2A), (0x202, 0x2A), (0x6101, 0x2A)));
+    }
+
+    #[test]
+    fn test_shutdown_hooks_run_once() {
+        let reasons = Arc::new(Mutex::new(Vec::new()));
+        let mut chip8 = Chip8::new();
+
+        let hook_reasons = reasons.clone();
+        chip8.on_shutdown(move |chip8, reason| {
+            hook_reasons.lock().unwrap().push((reason, chip8.registers[0]));
+        });
+        chip8.registers[0] = 3;
+
+        chip8.shutdown(ShutdownReason::Crash);
+        chip8.shutdown(ShutdownReason::Exit);
+
+        assert_eq!(*reasons.lock().unwrap(), vec!((ShutdownReason::Crash, 3)));
+    }
+}
@@ -0,0 +1,188 @@
+use std::io;
+
+use tiny_http::{Server, Request, Response, Header, Method};
+use image::{ColorType, png::PNGEncoder};
+
+use crate::{MEMORY, Chip8};
+use crate::debugger::Debugger;
+use crate::debugger::protocol::{self, Request as Command};
+use crate::disassembler::disassemble;
+
+/// A small HTTP server for inspecting and controlling a running emulator.
+///
+/// Requests are polled from the main loop, so they are answered between cycles and
+/// always see a consistent machine state.
+///
+/// * `GET /registers`
+/// * `GET /memory?start=0x200&length=64`
+/// * `GET /disassembly?start=0x200&count=16`
+/// * `GET /framebuffer.png`
+/// * `POST /pause`, `POST /resume`, `POST /step`, `POST /step-back`, `POST /reset`
+/// * `POST /breakpoint` with the breakpoint as the body, e.g. `Dxyn if V0 == 0x3F`
+/// * `POST /command` with a text command as the body, e.g. `step` or `mem 0x200 16`
+///
+/// Apart from the disassembly and the framebuffer, these are the commands of
+/// `debugger::protocol`. The commands that read or write files are refused with 403, as
+/// anyone who can reach the port could otherwise reach any file of the user.
+pub struct StateServer {
+    server: Server,
+}
+
+impl StateServer {
+    pub fn new(address: &str) -> io::Result<StateServer> {
+        let server = Server::http(address)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+
+        println!("Serving emulator state on http://{}", address);
+
+        Ok(StateServer { server })
+    }
+
+    /// Answer all pending requests without blocking.
+    pub fn poll(&mut self, chip8: &mut Chip8, debugger: &mut Debugger) {
+        while let Ok(Some(request)) = self.server.try_recv() {
+            if let Err(e) = handle(request, chip8, debugger) {
+                println!("Could not answer HTTP request: {}", e);
+            }
+        }
+    }
+}
+
+fn handle(mut request: Request, chip8: &mut Chip8, debugger: &mut Debugger) -> io::Result<()> {
+    let url = request.url().to_string();
+    let (path, query) = match url.find('?') {
+        Some(idx) => (&url[..idx], &url[idx + 1..]),
+        None => (url.as_str(), ""),
+    };
+
+    let command = match (request.method(), path) {
+        (Method::Get, "/registers") => Command::Registers,
+        (Method::Get, "/memory") => Command::ReadMem {
+            start: query_value(query, "start").unwrap_or(0x200) as usize,
+            length: query_value(query, "length").unwrap_or(64) as usize,
+        },
+        (Method::Get, "/disassembly") => {
+            let start = query_value(query, "start").unwrap_or(chip8.pc) as usize;
+            let count = query_value(query, "count").unwrap_or(16) as usize;
+
+            return respond_json(request, disassembly_json(chip8, start, count));
+        },
+        (Method::Get, "/framebuffer.png") => {
+            let frame = chip8.frame_image();
+            let mut png = Vec::new();
+
+            PNGEncoder::new(&mut png)
+                .encode(&frame, frame.width(), frame.height(), ColorType::Gray(8))?;
+
+            return request.respond(Response::from_data(png).with_header(header("image/png")));
+        },
+        (Method::Post, "/pause") => Command::Pause,
+        (Method::Post, "/resume") => Command::Continue,
+        (Method::Post, "/step") => Command::Step,
+        (Method::Post, "/step-back") => Command::StepBack,
+        (Method::Post, "/reset") => Command::Reset,
+        (Method::Post, "/breakpoint") => {
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body)?;
+
+            match body.trim().parse() {
+                Ok(breakpoint) => Command::SetBreakpoint(breakpoint),
+                Err(e) => {
+                    let response = Response::from_string(e).with_status_code(400);
+                    return request.respond(response);
+                },
+            }
+        },
+        (Method::Post, "/command") => {
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body)?;
+
+            match remote_command(&body) {
+                Ok(command) => command,
+                Err((status, e)) => {
+                    let response = Response::from_string(e).with_status_code(status);
+                    return request.respond(response);
+                },
+            }
+        },
+        _ => return request.respond(Response::from_string("Not found").with_status_code(404)),
+    };
+
+    let response = protocol::execute(command, chip8, debugger);
+    respond_json(request, response.to_json())
+}
+
+/// A text command sent over HTTP, with the status to answer when it cannot be run.
+fn remote_command(body: &str) -> Result<Command, (u16, String)> {
+    let command: Command = body.parse().map_err(|e| (400, e))?;
+
+    match command.path() {
+        Some(path) => Err((403, format!("Files cannot be accessed over HTTP: '{}'", path))),
+        None => Ok(command),
+    }
+}
+
+fn respond_json(request: Request, json: String) -> io::Result<()> {
+    request.respond(Response::from_string(json).with_header(header("application/json")))
+}
+
+fn header(content_type: &str) -> Header {
+    Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes()).unwrap()
+}
+
+fn disassembly_json(chip8: &Chip8, start: usize, count: usize) -> String {
+    let instructions: Vec<String> = (0..count)
+        .map(|idx| start + idx * 2)
+        .take_while(|address| address + 1 < MEMORY)
+        .map(|address| {
+            let opcode = (chip8.memory[address] as u16) << 8 | chip8.memory[address + 1] as u16;
+
+            format!("{{\"address\":{},\"opcode\":{},\"mnemonic\":\"{}\"}}",
+                address, opcode, disassemble(opcode))
+        })
+        .collect();
+
+    format!("[{}]", instructions.join(","))
+}
+
+/// Read a numeric query parameter, which may be written in decimal or as `0x` hexadecimal.
+fn query_value(query: &str, key: &str) -> Option<u16> {
+    query.split('&')
+        .filter_map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            Some((parts.next()?, parts.next()?))
+        })
+        .find(|(k, _)| *k == key)
+        .and_then(|(_, value)| {
+            match value.strip_prefix("0x") {
+                Some(hex) => u16::from_str_radix(hex, 16).ok(),
+                None => value.parse().ok(),
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_value() {
+        let query = "start=0x200&length=64";
+
+        assert_eq!(query_value(query, "start"), Some(0x200));
+        assert_eq!(query_value(query, "length"), Some(64));
+        assert_eq!(query_value(query, "count"), None);
+    }
+
+    #[test]
+    fn test_file_commands_are_forbidden() {
+        assert_eq!(remote_command("mem 0x200 16"),
+            Ok(Command::ReadMem { start: 0x200, length: 16 }));
+        assert_eq!(remote_command("jump").unwrap_err().0, 400);
+
+        for body in &["dump 0x200 0x400 sprites.bin", "load /etc/passwd 0x200",
+                "screenshot ~/.bashrc", "loadstate state.sav"] {
+            assert_eq!(remote_command(body).unwrap_err().0, 403, "{}", body);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Opening ROMs from the file manager.
+//!
+//! The desktop passes a double-clicked ROM as the first argument, sometimes as a `file://`
+//! URI. With `single_instance` the first emulator listens on a local port, and later ones
+//! hand their ROM to it and exit, so double-clicking another ROM does not open a second
+//! window.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::Path,
+    time::Duration,
+};
+
+use crate::archive;
+
+/// The port on the loopback interface the running instance listens on.
+const PORT: u16 = 47_382;
+/// Sent before the path, so stray connections are not taken for a ROM.
+const GREETING: &str = "CHIP8 OPEN ";
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The path of a ROM given on the command line, which may be a `file://` URI.
+pub fn rom_argument(arg: &str) -> String {
+    let path = match arg.strip_prefix("file://") {
+        // The host part is empty for local files, as in file:///home/...
+        Some(uri) => uri.trim_start_matches("localhost"),
+        None => return arg.to_string(),
+    };
+
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+
+    while idx < bytes.len() {
+        let escaped = bytes.get(idx + 1..idx + 3)
+            .filter(|_| bytes[idx] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                idx += 3;
+            },
+            None => {
+                decoded.push(bytes[idx]);
+                idx += 1;
+            },
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Receives the ROMs other instances hand over.
+pub struct InstanceListener {
+    listener: TcpListener,
+}
+
+impl InstanceListener {
+    fn bind(address: SocketAddr) -> io::Result<InstanceListener> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(InstanceListener { listener })
+    }
+
+    /// The path of a ROM another instance was asked to open, if any.
+    pub fn poll(&self) -> Option<String> {
+        let (stream, _) = self.listener.accept().ok()?;
+        stream.set_nonblocking(false).ok()?;
+        stream.set_read_timeout(Some(TIMEOUT)).ok()?;
+
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).ok()?;
+
+        line.trim_end().strip_prefix(GREETING).map(str::to_string)
+    }
+}
+
+fn forward(address: SocketAddr, rom: &str) -> io::Result<()> {
+    // The path is made absolute, since the running instance has its own working directory
+    let (file, entry) = archive::split_entry(rom);
+    let mut rom = Path::new(file).canonicalize()?.display().to_string();
+    if let Some(entry) = entry {
+        rom = format!("{}:{}", rom, entry);
+    }
+
+    let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+    writeln!(stream, "{}{}", GREETING, rom)
+}
+
+pub enum Instance {
+    /// A running instance took over the ROM.
+    Forwarded,
+    /// This is the running instance, listening for ROMs unless the port is taken.
+    Running(Option<InstanceListener>),
+}
+
+/// Hand the ROM to a running instance, or become the running instance when there is none.
+pub fn claim(rom: &str) -> Instance {
+    let address = SocketAddr::from(([127, 0, 0, 1], PORT));
+
+    if forward(address, rom).is_ok() {
+        return Instance::Forwarded;
+    }
+
+    match InstanceListener::bind(address) {
+        Ok(listener) => Instance::Running(Some(listener)),
+        Err(e) => {
+            println!("Could not listen for ROMs from other instances: {}", e);
+            Instance::Running(None)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rom_argument() {
+        assert_eq!(rom_argument("roms/pong.ch8"), "roms/pong.ch8");
+        assert_eq!(rom_argument("file:///home/abe/My%20Games/pong.ch8"),
+            "/home/abe/My Games/pong.ch8");
+        assert_eq!(rom_argument("file://localhost/tmp/100%.ch8"), "/tmp/100%.ch8");
+    }
+
+    #[test]
+    fn test_forward_to_listener() {
+        let listener = InstanceListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        let address = listener.listener.local_addr().unwrap();
+        assert_eq!(listener.poll(), None);
+
+        forward(address, "Cargo.toml").unwrap();
+
+        let expected = Path::new("Cargo.toml").canonicalize().unwrap();
+        assert_eq!(listener.poll(), Some(expected.display().to_string()));
+    }
+}
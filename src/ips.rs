@@ -0,0 +1,117 @@
+//! Applying IPS patches to ROMs, the format community bugfixes and translations are
+//! usually shared in.
+//!
+//! A patch starts with `PATCH` and ends with `EOF`. In between are records of a big-endian
+//! 3 byte offset into the ROM and a 2 byte length, followed by that many bytes to write.
+//! A length of 0 marks a run instead: a 2 byte count and the byte to repeat. The ROM may
+//! grow to fit a record, but every record has to stay inside the memory a ROM is loaded
+//! into.
+
+use std::fs;
+
+const HEADER: &[u8] = b"PATCH";
+const FOOTER: &[u8] = b"EOF";
+
+/// A change to the ROM made by a single record.
+#[derive(Debug, PartialEq)]
+struct Record {
+    offset: usize,
+    bytes: Vec<u8>,
+}
+
+/// Reads the big-endian number in the next `n` bytes.
+fn take(patch: &[u8], position: &mut usize, n: usize) -> Result<usize, String> {
+    let bytes = patch.get(*position..*position + n)
+        .ok_or_else(|| format!("Patch ends inside the record at byte {}", *position))?;
+    *position += n;
+
+    Ok(bytes.iter().fold(0, |value, &byte| value << 8 | byte as usize))
+}
+
+fn parse(patch: &[u8]) -> Result<Vec<Record>, String> {
+    if !patch.starts_with(HEADER) {
+        return Err(String::from("Not an IPS patch"));
+    }
+
+    let mut records = Vec::new();
+    let mut position = HEADER.len();
+
+    loop {
+        if patch[position..].starts_with(FOOTER) {
+            return Ok(records);
+        }
+
+        let offset = take(patch, &mut position, 3)?;
+        let bytes = match take(patch, &mut position, 2)? {
+            0 => {
+                let count = take(patch, &mut position, 2)?;
+                vec!(take(patch, &mut position, 1)? as u8; count)
+            },
+            length => {
+                let bytes = patch.get(position..position + length)
+                    .ok_or_else(|| format!("Patch ends inside the record at byte {}", position))?;
+                position += length;
+                bytes.to_vec()
+            },
+        };
+
+        records.push(Record { offset, bytes });
+    }
+}
+
+/// The ROM with the patch applied. `rom_area` is the number of bytes a ROM can take up in
+/// memory; records outside of it are rejected.
+pub fn apply(rom: &[u8], patch: &[u8], rom_area: usize) -> Result<Vec<u8>, String> {
+    let mut rom = rom.to_vec();
+
+    for record in parse(patch)? {
+        let end = record.offset + record.bytes.len();
+        if end > rom_area {
+            return Err(format!("Patch writes {:#X}-{:#X}, outside of the {:#X} bytes of ROM",
+                record.offset, end, rom_area));
+        }
+
+        if end > rom.len() {
+            rom.resize(end, 0);
+        }
+        rom[record.offset..end].copy_from_slice(&record.bytes);
+    }
+
+    Ok(rom)
+}
+
+/// Apply the patch in a file.
+pub fn apply_file(rom: &[u8], path: &str, rom_area: usize) -> Result<Vec<u8>, String> {
+    let patch = fs::read(path).map_err(|e| format!("Could not read patch {}: {}", path, e))?;
+
+    apply(rom, &patch, rom_area).map_err(|e| format!("Could not apply {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let patch = b"PATCH\x00\x00\x01\x00\x02\xAA\xBB\x00\x00\x04\x00\x00\x00\x03\xCCEOF";
+
+        let rom = apply(&[1, 2, 3, 4], patch, 0x10).unwrap();
+
+        assert_eq!(rom, vec!(1, 0xAA, 0xBB, 4, 0xCC, 0xCC, 0xCC));
+    }
+
+    #[test]
+    fn test_outside_rom_area() {
+        let patch = b"PATCH\x00\x0D\xFE\x00\x02\x01\x02EOF";
+
+        assert_eq!(apply(&[0; 4], patch, 0xE00).unwrap().len(), 0xE00);
+        assert!(apply(&[0; 4], patch, 0xD00).unwrap_err().contains("outside"));
+    }
+
+    #[test]
+    fn test_invalid_patch() {
+        assert_eq!(apply(&[], b"PATCX", 0x10), Err(String::from("Not an IPS patch")));
+        assert!(apply(&[], b"PATCH\x00\x00\x01\x00\x05\x01EOF", 0x10).is_err());
+        assert!(apply(&[], b"PATCH\x00\x00", 0x10).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+//! The hex keypad as seen by the interpreter.
+//!
+//! The frontend hands over the held keys once per frame. The keypad turns the changes into
+//! pressed and released events, so Fx0A can react to a key going down (or coming back up,
+//! like the COSMAC VIP) instead of to a key that happened to be held already.
+//!
+//! The held keys come from several sources, which `KeyInput` merges into the keys of a
+//! frame: a replay being played replaces the keyboard, and the keys of a macro and those
+//! held by the debugger are added to either.
+
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyEvent {
+    Pressed(u8),
+    Released(u8),
+}
+
+/// Where the keys of a frame come from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    /// A replay being played, which takes the place of the keyboard.
+    Replay,
+    /// The keys held on the host.
+    Keyboard,
+    /// A macro being played, see `macros`.
+    Macro,
+    /// Keys held down by the debugger, such as through the command socket.
+    Debugger,
+}
+
+/// The keys of a frame, gathered from the sources before they go to the keypad.
+#[derive(Debug, Default)]
+pub struct KeyInput {
+    replay: Option<[bool; 16]>,
+    keyboard: [bool; 16],
+    added: [bool; 16],
+}
+
+impl KeyInput {
+    pub fn set(&mut self, source: Source, keys: [bool; 16]) {
+        match source {
+            Source::Replay => self.replay = Some(keys),
+            Source::Keyboard => self.keyboard = keys,
+            Source::Macro | Source::Debugger => {
+                for (down, &key) in self.added.iter_mut().zip(&keys) {
+                    *down |= key;
+                }
+            },
+        }
+    }
+
+    /// The keys held on the host or in the replay, without the added ones.
+    pub fn base(&self) -> [bool; 16] {
+        self.replay.unwrap_or(self.keyboard)
+    }
+
+    /// The keys held down in the frame.
+    pub fn merge(&self) -> [bool; 16] {
+        let mut keys = self.base();
+        for (down, &added) in keys.iter_mut().zip(&self.added) {
+            *down |= added;
+        }
+
+        keys
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Keypad {
+    down: [bool; 16],
+    // Edges since the previous update that were not consumed yet
+    events: Vec<KeyEvent>,
+}
+
+impl Keypad {
+    /// Set which keys are held down, replacing the events of the previous frame with the
+    /// keys that went down or came up since then.
+    pub fn update(&mut self, keys: [bool; 16]) {
+        self.events.clear();
+
+        for (key, (&before, &after)) in self.down.iter().zip(&keys).enumerate() {
+            match (before, after) {
+                (false, true) => self.events.push(KeyEvent::Pressed(key as u8)),
+                (true, false) => self.events.push(KeyEvent::Released(key as u8)),
+                _ => {},
+            }
+        }
+
+        self.down = keys;
+    }
+
+    pub fn is_down(&self, key: usize) -> bool {
+        self.down[key]
+    }
+
+    /// The held keys, indexed by key value.
+    pub fn state(&self) -> [bool; 16] {
+        self.down
+    }
+
+    /// Consume the first key press of this frame, so it is only seen by one Fx0A.
+    pub fn take_pressed(&mut self) -> Option<u8> {
+        let idx = self.events.iter().position(|event| match event {
+            KeyEvent::Pressed(_) => true,
+            KeyEvent::Released(_) => false,
+        })?;
+
+        match self.events.remove(idx) {
+            KeyEvent::Pressed(key) => Some(key),
+            KeyEvent::Released(_) => None,
+        }
+    }
+
+    /// Consume the release of `key` in this frame, returning whether it was released.
+    pub fn take_released(&mut self, key: u8) -> bool {
+        match self.events.iter().position(|&event| event == KeyEvent::Released(key)) {
+            Some(idx) => {
+                self.events.remove(idx);
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edges() {
+        let mut keypad = Keypad::default();
+        let mut keys = [false; 16];
+
+        keys[3] = true;
+        keypad.update(keys);
+        assert_eq!(keypad.events, vec!(KeyEvent::Pressed(3)));
+
+        // Holding a key does not press it again
+        keypad.update(keys);
+        assert_eq!(keypad.take_pressed(), None);
+
+        keypad.update([false; 16]);
+        assert!(!keypad.take_released(5));
+        assert!(keypad.take_released(3));
+        assert!(!keypad.take_released(3));
+    }
+
+    #[test]
+    fn test_merge_sources() {
+        let mut held = [false; 16];
+        held[1] = true;
+        let mut played = [false; 16];
+        played[2] = true;
+        let mut scripted = [false; 16];
+        scripted[3] = true;
+
+        let mut input = KeyInput::default();
+        input.set(Source::Keyboard, held);
+        input.set(Source::Debugger, scripted);
+        assert_eq!(input.merge().iter().filter(|&&down| down).count(), 2);
+
+        // A replay replaces the keyboard, not the keys added on top
+        input.set(Source::Replay, played);
+        let keys = input.merge();
+        assert!(!keys[1] && keys[2] && keys[3]);
+        assert_eq!(input.base(), played);
+    }
+}
@@ -0,0 +1,189 @@
+//! Keyboard layouts, so the CHIP-8 keypad is always the same physical 4x4 block of keys.
+//!
+//! The window reports keys by the character they produce, so on an AZERTY keyboard the key
+//! in the position of the QWERTY `Q` is reported as `A`. The layout translates the physical
+//! block to those characters. It is guessed from the environment, and can be set with
+//! `keyboard_layout` when the guess is wrong.
+
+use std::{env, str::FromStr};
+
+use minifb::Key;
+
+/// CHIP-8 keys in the order they appear on the original 4x4 keypad, which is also the
+/// order of the host keys they are mapped to.
+pub const KEYPAD: [[usize; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    Qwerty,
+    Azerty,
+    Qwertz,
+    Dvorak,
+    Colemak,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Layout, String> {
+        match s {
+            "qwerty" => Ok(Layout::Qwerty),
+            "azerty" => Ok(Layout::Azerty),
+            "qwertz" => Ok(Layout::Qwertz),
+            "dvorak" => Ok(Layout::Dvorak),
+            "colemak" => Ok(Layout::Colemak),
+            _ => Err(format!(
+                "Unknown keyboard layout '{}', expected qwerty, azerty, qwertz, dvorak or colemak",
+                s)),
+        }
+    }
+}
+
+impl Layout {
+    pub const ALL: [Layout; 5] = [
+        Layout::Qwerty, Layout::Azerty, Layout::Qwertz, Layout::Dvorak, Layout::Colemak,
+    ];
+
+    /// The name of the layout in the config file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::Qwerty => "qwerty",
+            Layout::Azerty => "azerty",
+            Layout::Qwertz => "qwertz",
+            Layout::Dvorak => "dvorak",
+            Layout::Colemak => "colemak",
+        }
+    }
+
+    /// Guess the layout from the environment, falling back to QWERTY.
+    pub fn detect() -> Layout {
+        Layout::detect_from(|name| env::var(name).ok())
+    }
+
+    /// Guess the layout from the XKB layout name if set, or from the country of the locale.
+    fn detect_from<F: Fn(&str) -> Option<String>>(var: F) -> Layout {
+        if let Some(xkb) = var("XKB_DEFAULT_LAYOUT") {
+            let variant = var("XKB_DEFAULT_VARIANT").unwrap_or_default();
+            let name = format!("{} {}", xkb, variant).to_lowercase();
+
+            return if name.contains("dvorak") {
+                Layout::Dvorak
+            } else if name.contains("colemak") {
+                Layout::Colemak
+            } else {
+                Layout::for_country(xkb.get(..2).unwrap_or(""))
+            };
+        }
+
+        // A locale such as fr_FR.UTF-8, where the country decides the layout
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"].iter().filter_map(|&name| var(name)).next();
+
+        match locale.as_ref().and_then(|locale| locale.get(3..5)) {
+            Some(country) => Layout::for_country(country),
+            None => Layout::Qwerty,
+        }
+    }
+
+    fn for_country(country: &str) -> Layout {
+        match country.to_uppercase().as_str() {
+            "FR" | "BE" => Layout::Azerty,
+            "DE" | "AT" | "CH" | "CZ" | "SK" | "HU" | "SI" | "HR" => Layout::Qwertz,
+            _ => Layout::Qwerty,
+        }
+    }
+
+    /// The keys in the physical positions of QWERTY's `1234`, `QWER`, `ASDF` and `ZXCV`.
+    fn block(self) -> [[Key; 4]; 4] {
+        use Key::*;
+
+        match self {
+            Layout::Qwerty => [
+                [Key1, Key2, Key3, Key4], [Q, W, E, R], [A, S, D, F], [Z, X, C, V],
+            ],
+            Layout::Azerty => [
+                [Key1, Key2, Key3, Key4], [A, Z, E, R], [Q, S, D, F], [W, X, C, V],
+            ],
+            Layout::Qwertz => [
+                [Key1, Key2, Key3, Key4], [Q, W, E, R], [A, S, D, F], [Y, X, C, V],
+            ],
+            Layout::Dvorak => [
+                [Key1, Key2, Key3, Key4],
+                [Apostrophe, Comma, Period, P],
+                [A, O, E, U],
+                [Semicolon, Q, J, K],
+            ],
+            Layout::Colemak => [
+                [Key1, Key2, Key3, Key4], [Q, W, F, P], [A, R, S, T], [Z, X, C, V],
+            ],
+        }
+    }
+
+    /// The host key of every CHIP-8 key, indexed by key value.
+    pub fn keymap(self) -> [Key; 16] {
+        let block = self.block();
+        let mut keymap = [Key::Unknown; 16];
+
+        for (row, keys) in KEYPAD.iter().enumerate() {
+            for (column, &key) in keys.iter().enumerate() {
+                keymap[key] = block[row][column];
+            }
+        }
+
+        keymap
+    }
+}
+
+/// Short name of a host key, such as `1`, `Q` or `;`.
+pub fn host_key_label(key: Key) -> String {
+    match key {
+        Key::Apostrophe => String::from("'"),
+        Key::Comma => String::from(","),
+        Key::Period => String::from("."),
+        Key::Semicolon => String::from(";"),
+        _ => format!("{:?}", key).trim_start_matches("Key").to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qwerty_keymap() {
+        let keymap = Layout::Qwerty.keymap();
+
+        assert_eq!(keymap[0x1], Key::Key1);
+        assert_eq!(keymap[0x0], Key::X);
+        assert_eq!(keymap[0xF], Key::V);
+        assert_eq!(host_key_label(keymap[0xC]), "4");
+    }
+
+    #[test]
+    fn test_same_physical_keys() {
+        // The key left of W on QWERTY is labelled A on AZERTY and ' on Dvorak
+        assert_eq!(Layout::Azerty.keymap()[0x4], Key::A);
+        assert_eq!(Layout::Azerty.keymap()[0x7], Key::Q);
+        assert_eq!(host_key_label(Layout::Dvorak.keymap()[0x4]), "'");
+        assert_eq!(Layout::Qwertz.keymap()[0xA], Key::Y);
+    }
+
+    #[test]
+    fn test_detect() {
+        let env = |vars: &'static [(&'static str, &'static str)]| move |name: &str| {
+            vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string())
+        };
+
+        assert_eq!(Layout::detect_from(env(&[("LANG", "fr_FR.UTF-8")])), Layout::Azerty);
+        assert_eq!(Layout::detect_from(env(&[("LANG", "fr_CA.UTF-8")])), Layout::Qwerty);
+        assert_eq!(Layout::detect_from(env(&[("LANG", "de_DE.UTF-8")])), Layout::Qwertz);
+        assert_eq!(Layout::detect_from(env(&[
+            ("XKB_DEFAULT_LAYOUT", "us"), ("XKB_DEFAULT_VARIANT", "dvorak"), ("LANG", "de_DE"),
+        ])), Layout::Dvorak);
+        assert_eq!(Layout::detect_from(env(&[])), Layout::Qwerty);
+    }
+}
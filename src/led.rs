@@ -0,0 +1,128 @@
+//! Pushing the display to small hardware displays and LED matrices.
+//!
+//! `LedMatrix` draws the display onto any embedded-graphics `DrawTarget` with binary
+//! colours, such as the SSD1306 and, through a colour converting wrapper, the ST7735
+//! drivers. The displays hang off slow buses, so only the rows that changed since the
+//! previous update are sent, each as a single contiguous fill.
+
+use embedded_graphics_core::{
+    draw_target::DrawTarget,
+    geometry::{Point, Size},
+    pixelcolor::BinaryColor,
+    primitives::Rectangle,
+};
+
+use crate::{WIDTH, HEIGHT};
+use crate::display::Display;
+
+pub struct LedMatrix<D> {
+    target: D,
+    // Hardware pixels per CHIP-8 pixel, 2 fills a 128x64 SSD1306
+    scale: u32,
+    // The rows as last sent to the target, none before the first update
+    shown: Option<[u64; HEIGHT]>,
+}
+
+impl<D: DrawTarget<Color = BinaryColor>> LedMatrix<D> {
+    pub fn new(target: D, scale: u32) -> LedMatrix<D> {
+        LedMatrix { target, scale: scale.max(1), shown: None }
+    }
+
+    /// The target, for drivers that have to be flushed after an update.
+    pub fn target(&mut self) -> &mut D {
+        &mut self.target
+    }
+
+    /// Draw the rows of the display that changed since the previous update.
+    pub fn update(&mut self, display: &Display) -> Result<(), D::Error> {
+        let rows = display.rows();
+
+        for y in 0..HEIGHT {
+            if self.shown.map_or(false, |shown| shown[y] == rows[y]) {
+                continue;
+            }
+
+            let scale = self.scale as usize;
+            let row = rows[y];
+            let area = Rectangle::new(
+                Point::new(0, (y * scale) as i32),
+                Size::new((WIDTH * scale) as u32, self.scale),
+            );
+            let colors = (0..WIDTH * scale * scale).map(|i| {
+                let x = i % (WIDTH * scale) / scale;
+                BinaryColor::from(row >> (WIDTH - 1 - x) & 1 == 1)
+            });
+
+            self.target.fill_contiguous(&area, colors)?;
+        }
+
+        self.shown = Some(*rows);
+        Ok(())
+    }
+
+    /// Send the whole display on the next update, after the target was cleared.
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics_core::{geometry::OriginDimensions, Pixel};
+
+    // A monochrome panel that counts the pixels sent to it
+    struct Panel {
+        width: usize,
+        pixels: Vec<bool>,
+        written: usize,
+    }
+
+    impl Panel {
+        fn new(width: usize, height: usize) -> Panel {
+            Panel { width, pixels: vec!(false; width * height), written: 0 }
+        }
+    }
+
+    impl OriginDimensions for Panel {
+        fn size(&self) -> Size {
+            Size::new(self.width as u32, (self.pixels.len() / self.width) as u32)
+        }
+    }
+
+    impl DrawTarget for Panel {
+        type Color = BinaryColor;
+        type Error = ();
+
+        fn draw_iter<I>(&mut self, pixels: I) -> Result<(), ()>
+            where I: IntoIterator<Item = Pixel<BinaryColor>>
+        {
+            for Pixel(point, color) in pixels {
+                self.pixels[point.x as usize + point.y as usize * self.width] = color.is_on();
+                self.written += 1;
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_update_scales_changed_rows() {
+        let mut display = Display::new();
+        display.draw_sprite(3, 1, &[0x80]);
+
+        let mut matrix = LedMatrix::new(Panel::new(2 * WIDTH, 2 * HEIGHT), 2);
+        matrix.update(&display).unwrap();
+
+        let panel = matrix.target();
+        assert_eq!(panel.written, 4 * WIDTH * HEIGHT);
+        assert_eq!(panel.pixels.iter().filter(|&&lit| lit).count(), 4);
+        assert!(panel.pixels[6 + 2 * panel.width] && panel.pixels[7 + 3 * panel.width]);
+
+        // Only the row of the new sprite is sent again
+        display.draw_sprite(0, 9, &[0xFF]);
+        panel.written = 0;
+        matrix.update(&display).unwrap();
+        assert_eq!(matrix.target().written, 4 * WIDTH);
+    }
+}
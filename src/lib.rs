@@ -0,0 +1,985 @@
+//! A CHIP-8 interpreter, with the front ends and tools built around it.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+// Much of the core is there for the front ends, which are only built with std
+#![cfg_attr(not(feature = "std"), allow(dead_code))]
+
+extern crate alloc;
+extern crate rand;
+#[cfg(feature = "std")]
+extern crate minifb;
+#[cfg(feature = "std")]
+extern crate directories;
+#[cfg(feature = "image")]
+extern crate image;
+#[cfg(feature = "http")]
+extern crate tiny_http;
+#[cfg(feature = "led")]
+extern crate embedded_graphics_core;
+
+// The interpreter, which builds without std
+mod chip8x;
+mod decode;
+mod diagnostics;
+mod disassembler;
+pub mod display;
+mod effect;
+mod error;
+mod events;
+mod hooks;
+mod keypad;
+#[cfg(feature = "led")]
+pub mod led;
+mod locale;
+mod logging;
+mod ops;
+mod quirks;
+mod random;
+mod shutdown;
+mod sprite_rules;
+mod stack;
+mod strictness;
+mod timers;
+mod timing;
+mod trace;
+mod variant;
+
+// The front ends and tools built around it, which need std
+#[cfg(feature = "std")]
+mod accessibility;
+#[cfg(feature = "std")]
+mod achievements;
+#[cfg(feature = "std")]
+mod announce;
+#[cfg(feature = "std")]
+mod archive;
+#[cfg(feature = "std")]
+mod assembler;
+#[cfg(feature = "std")]
+mod audio;
+#[cfg(feature = "std")]
+mod batch;
+#[cfg(feature = "std")]
+mod bench_rom;
+#[cfg(feature = "std")]
+mod bookmark;
+#[cfg(feature = "std")]
+mod bug_report;
+#[cfg(feature = "std")]
+mod calibration;
+#[cfg(feature = "std")]
+mod capture;
+#[cfg(feature = "std")]
+mod cheats;
+#[cfg(feature = "std")]
+pub mod cli;
+#[cfg(feature = "std")]
+mod command_socket;
+#[cfg(feature = "std")]
+mod compare;
+#[cfg(feature = "std")]
+mod compat;
+#[cfg(feature = "std")]
+mod config;
+#[cfg(feature = "std")]
+mod container;
+#[cfg(feature = "std")]
+mod core_thread;
+#[cfg(feature = "std")]
+mod debug_session;
+#[cfg(feature = "std")]
+mod debugger;
+#[cfg(feature = "std")]
+mod demo;
+#[cfg(feature = "std")]
+mod detect;
+#[cfg(feature = "std")]
+mod differential;
+#[cfg(feature = "std")]
+mod embed;
+#[cfg(feature = "std")]
+mod emulator;
+#[cfg(feature = "std")]
+mod filters;
+#[cfg(feature = "std")]
+mod first_run;
+#[cfg(feature = "std")]
+mod frontend;
+#[cfg(feature = "std")]
+mod gallery;
+#[cfg(feature = "std")]
+mod gif;
+#[cfg(feature = "std")]
+mod golden;
+#[cfg(feature = "std")]
+mod grid;
+#[cfg(feature = "std")]
+mod gym;
+#[cfg(feature = "std")]
+mod help;
+#[cfg(feature = "http")]
+mod http;
+#[cfg(feature = "std")]
+mod instance;
+#[cfg(feature = "std")]
+mod ips;
+#[cfg(feature = "std")]
+mod layout;
+#[cfg(feature = "std")]
+mod macros;
+#[cfg(feature = "std")]
+mod memory_map;
+#[cfg(feature = "std")]
+mod menu;
+#[cfg(feature = "std")]
+mod palette;
+#[cfg(feature = "std")]
+mod panels;
+#[cfg(feature = "std")]
+mod patch;
+#[cfg(feature = "std")]
+mod paths;
+#[cfg(feature = "std")]
+mod prompt;
+#[cfg(feature = "std")]
+mod recovery;
+#[cfg(feature = "std")]
+mod reference;
+#[cfg(feature = "std")]
+mod replay;
+#[cfg(feature = "std")]
+mod rotation;
+#[cfg(feature = "std")]
+mod savestate;
+#[cfg(feature = "std")]
+mod screen;
+#[cfg(feature = "std")]
+mod search;
+#[cfg(feature = "std")]
+mod session;
+#[cfg(feature = "std")]
+mod setup_test;
+#[cfg(feature = "std")]
+mod slots;
+#[cfg(feature = "std")]
+mod soak;
+#[cfg(feature = "std")]
+mod sprite_editor;
+#[cfg(feature = "std")]
+mod stream;
+#[cfg(feature = "std")]
+mod symbols;
+#[cfg(feature = "std")]
+mod test_runner;
+#[cfg(feature = "std")]
+mod text;
+#[cfg(feature = "std")]
+mod text_input;
+#[cfg(feature = "std")]
+mod trace_diff;
+#[cfg(feature = "std")]
+mod turbo;
+#[cfg(feature = "std")]
+mod usage;
+#[cfg(feature = "std")]
+mod vip_timing;
+#[cfg(feature = "std")]
+mod watch;
+
+// The types of the public API, from the modules that define them
+pub use decode::{Decoded, Instruction};
+pub use effect::Effect;
+pub use logging::{Level, Target};
+pub use quirks::Quirks;
+pub use random::RngKind;
+pub use shutdown::ShutdownReason;
+pub use strictness::Strictness;
+pub use timers::Clock;
+pub use variant::Variant;
+#[cfg(feature = "std")]
+pub use audio::{attach as attach_audio, AudioSink};
+#[cfg(feature = "std")]
+pub use emulator::{Emulator, EmulatorBuilder};
+#[cfg(feature = "std")]
+pub use timers::{ManualClock, SystemClock};
+
+use core::fmt;
+#[cfg(feature = "std")]
+use std::{io, path::Path};
+
+use alloc::{boxed::Box, format, string::{String, ToString}, vec::Vec};
+
+use chip8x::Chip8X;
+#[cfg(feature = "std")]
+use config::Config;
+use display::Display;
+use events::{Event, EventBus};
+use hooks::Hooks;
+use keypad::Keypad;
+use ops::Cpu;
+use random::RandomSource;
+use sprite_rules::SpriteRules;
+use trace::TraceBuffer;
+use stack::{Stack, StackError};
+use strictness::AmbiguityCheck;
+use error::Chip8Error;
+use timers::Timers;
+
+const MEMORY: usize = 4096;
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 32;
+const VF: usize = 15;
+const PROGRAM_START: usize = 0x200;
+const TRACE_LENGTH: usize = 1024;
+const WARNING_COLOR: u32 = 0xFF4040;
+
+/// The hexadecimal digits Fx29 points I at, five rows each, at the start of memory.
+const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+type Register = u8;
+type Opcode = u16;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum State {
+    Running,
+    /// Halted by Fx0A until a key is pressed, which is then stored in Vx.
+    WaitingForKey(usize),
+    /// A key was pressed during Fx0A, but it is only stored once released.
+    WaitingForRelease(usize, u8),
+}
+
+pub struct Chip8 {
+    pc: u16,
+    // Address of the instruction being executed, which faults are reported at. The program
+    // counter has already moved past it by then.
+    executing: u16,
+    opcode: u16,
+    i: u16,
+
+    registers: [u8; 16],
+    memory: [u8; MEMORY],
+    display: Display,
+
+    timers: Timers,
+    // XO-CHIP audio pattern and its playback pitch
+    audio_pattern: Option<[u8; 16]>,
+    pitch: u8,
+
+    stack: Stack,
+
+    rng: Box<dyn RandomSource>,
+    rng_kind: RngKind,
+    // Seed of the RNG in deterministic mode, kept across resets
+    seed: Option<u64>,
+
+    keypad: Keypad,
+    state: State,
+    quirks: Quirks,
+    sprite_rules: SpriteRules,
+    // Where the ROM is loaded and execution starts, after the interpreter
+    program_start: u16,
+    // The colour board and second keypad, when emulating CHIP-8X
+    chip8x: Option<Chip8X>,
+
+    // Makes the interpreter area (0x000-0x1FF) read-only to catch stray writes
+    protect_memory: bool,
+    protection_fault: Option<u16>,
+    // Address of a CALL beyond the stack depth or a RET on an empty stack
+    stack_fault: Option<u16>,
+    // The first access past the end of memory since it was last checked
+    bounds_fault: Option<Chip8Error>,
+    // The highest address written since the last reset, see `usage`
+    highest_write: Option<u16>,
+    // Address of the first opcode that could not be executed since it was last checked
+    unknown_opcode: Option<u16>,
+    // What to do about odd addresses and accesses past the end of memory, see `strictness`
+    strictness: Strictness,
+    // The ambiguous behaviour reported in strict mode
+    ambiguity: AmbiguityCheck,
+    // Address of the first instruction that failed a check set to break since it was last
+    // checked
+    strictness_fault: Option<u16>,
+
+    trace: TraceBuffer,
+
+    rom: Vec<u8>,
+
+    hooks: Hooks,
+    events: EventBus,
+}
+
+impl Chip8 {
+    pub fn new() -> Chip8 {
+        let mut memory = [0; MEMORY];
+        memory[..FONT.len()].copy_from_slice(&FONT);
+
+        Chip8 {
+            pc: 0x200,
+            executing: 0x200,
+            opcode: 0,
+            i: 0,
+
+            registers: [0; 16],
+            memory,
+            display: Display::new(),
+
+            timers: Timers::default(),
+            audio_pattern: None,
+            pitch: 64,
+
+            stack: Stack::new(stack::DEFAULT_DEPTH),
+
+            rng: RngKind::Modern.generator(None),
+            rng_kind: RngKind::Modern,
+            seed: None,
+
+            keypad: Keypad::default(),
+            state: State::Running,
+            quirks: Quirks::default(),
+            sprite_rules: SpriteRules::default(),
+            program_start: PROGRAM_START as u16,
+            chip8x: None,
+
+            protect_memory: false,
+            protection_fault: None,
+            stack_fault: None,
+            bounds_fault: None,
+            highest_write: None,
+            unknown_opcode: None,
+            strictness: Strictness::Off,
+            ambiguity: AmbiguityCheck::default(),
+            strictness_fault: None,
+
+            trace: TraceBuffer::new(TRACE_LENGTH),
+
+            rom: Vec::new(),
+
+            hooks: Hooks::default(),
+            events: EventBus::default(),
+        }
+    }
+
+    /// Create a machine running a ROM that is compiled into the binary, for example with
+    /// `include_bytes!` or the output of `chip8 embed`, at the default settings.
+    #[cfg(feature = "std")]
+    pub fn with_embedded_rom(rom: &'static [u8]) -> Chip8 {
+        Emulator::builder().rom_bytes(rom).build()
+            .expect("The default settings are valid")
+            .chip8
+    }
+
+    /// Load a ROM file. The settings of a container are not applied, see `container::load`.
+    /// When recovering, the ROM is repaired to fit first, see `recovery`.
+    #[cfg(feature = "std")]
+    fn load_rom(&mut self, path: &str, recover: bool) -> io::Result<()> {
+        let (rom, _) = container::load(path)?;
+        if recover {
+            let available = MEMORY - self.program_start as usize;
+            self.load_bytes(&recovery::recover(&rom, available));
+        } else {
+            self.load_bytes(&rom);
+        }
+
+        Ok(())
+    }
+
+    pub fn load_bytes(&mut self, rom: &[u8]) {
+        self.rom = rom.to_vec();
+        self.reset();
+    }
+
+    /// Restore the machine to its power-on state and reload the current ROM.
+    pub fn reset(&mut self) {
+        let rom = core::mem::take(&mut self.rom);
+        let quirks = self.quirks;
+        let sprite_rules = self.sprite_rules;
+        let program_start = self.program_start;
+        let chip8x = self.chip8x.is_some();
+        let protect_memory = self.protect_memory;
+        let strictness = self.strictness;
+        let double_buffered = self.display.is_double_buffered();
+        let stack_depth = self.stack.depth();
+        let hooks = core::mem::take(&mut self.hooks);
+        let events = core::mem::take(&mut self.events);
+        // The timers keep their clock
+        let mut timers = core::mem::take(&mut self.timers);
+        let seed = self.seed;
+        let rng_kind = self.rng_kind;
+        *self = Chip8::new();
+        self.quirks = quirks;
+        self.sprite_rules = sprite_rules;
+        self.program_start = program_start;
+        if chip8x {
+            self.chip8x = Some(Chip8X::new());
+        }
+        self.protect_memory = protect_memory;
+        self.strictness = strictness;
+        self.display.set_double_buffered(double_buffered);
+        self.stack = Stack::new(stack_depth);
+        self.hooks = hooks;
+        self.events = events;
+        self.events.publish(Event::DisplayUpdated);
+        timers.clear();
+        self.timers = timers;
+
+        // The generator starts over, like the VIP's does when it is switched on
+        self.seed = seed;
+        self.set_rng_kind(rng_kind);
+        self.hooks.audio_pattern_changed(None, self.pitch);
+
+        self.pc = program_start;
+        self.executing = program_start;
+        let available = MEMORY - program_start as usize;
+        if rom.len() > available {
+            self.report_bounds_fault(Chip8Error::RomTooLarge { size: rom.len(), available });
+        }
+        for (cell, byte) in self.memory[program_start as usize..].iter_mut().zip(&rom) {
+            *cell = *byte;
+        }
+
+        self.rom = rom;
+    }
+
+    /// Make the random numbers of RND reproducible.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        self.rng = self.rng_kind.generator(self.seed);
+    }
+
+    /// Draw the random numbers of RND from another kind of generator, seeded like before.
+    fn set_rng_kind(&mut self, kind: RngKind) {
+        self.rng_kind = kind;
+        self.rng = kind.generator(self.seed);
+    }
+
+    /// The current contents of the CHIP-8 display as a grayscale image.
+    #[cfg(feature = "image")]
+    pub fn frame_image(&self) -> image::GrayImage {
+        self.display.to_buffer().to_gray_image()
+    }
+
+    /// The display, with what was drawn on it so far.
+    pub fn frame(&self) -> &Display {
+        &self.display
+    }
+
+    /// Read the opcode at the program counter without executing it, or 0000 when it is
+    /// past the end of memory.
+    fn fetch(&self) -> u16 {
+        self.try_fetch().unwrap_or(0)
+    }
+
+    fn try_fetch(&self) -> Result<u16, Chip8Error> {
+        let pc = self.pc as usize;
+
+        match (self.memory.get(pc), self.memory.get(pc + 1)) {
+            (Some(&opcode_1), Some(&opcode_2)) => Ok((opcode_1 as u16) << 8 | opcode_2 as u16),
+            _ => Err(Chip8Error::FetchOutOfBounds { pc: self.pc }),
+        }
+    }
+
+    /// The byte at an address, or an error when it is past the end of memory.
+    fn memory_at(&self, address: usize) -> Result<u8, Chip8Error> {
+        self.memory.get(address).copied()
+            .ok_or(Chip8Error::ReadOutOfBounds { pc: self.executing, address })
+    }
+
+    fn memory_at_mut(&mut self, address: usize) -> Result<&mut u8, Chip8Error> {
+        let pc = self.executing;
+
+        self.memory.get_mut(address).ok_or(Chip8Error::WriteOutOfBounds { pc, address })
+    }
+
+    /// The instructions in memory from an address on, decoded as the interpreter would
+    /// execute them.
+    pub fn instructions_at(&self, address: u16)
+            -> impl Iterator<Item = (u16, decode::Decoded)> + '_ {
+        decode::instructions(&self.memory, address, self.chip8x.is_some())
+    }
+
+    /// Set which of the 16 keys on the keypad are currently held down, called once a frame.
+    pub fn set_keys(&mut self, keys: [bool; 16]) {
+        self.keypad.update(keys);
+    }
+
+    /// Resolve a pending Fx0A, returning whether the CPU may continue executing.
+    ///
+    /// Only a key that goes down while waiting counts, a key that was already held does not.
+    fn wait_for_key(&mut self) -> bool {
+        match self.state {
+            State::Running => return true,
+            State::WaitingForKey(v_x) => {
+                if let Some(key) = self.keypad.take_pressed() {
+                    if self.quirks.key_release {
+                        self.state = State::WaitingForRelease(v_x, key);
+                    } else {
+                        self.registers[v_x] = key;
+                        self.state = State::Running;
+                    }
+                }
+            },
+            State::WaitingForRelease(v_x, key) => {
+                if self.keypad.take_released(key) {
+                    self.registers[v_x] = key;
+                    self.state = State::Running;
+                }
+            },
+        }
+
+        false
+    }
+
+    /// Whether Fx0A halted execution until a key is pressed.
+    fn is_waiting_for_key(&self) -> bool {
+        matches!(self.state, State::WaitingForKey(_))
+    }
+
+    pub fn cycle(&mut self) -> u16 {
+        // Execution is halted while waiting for a key, the timers are updated separately
+        if !self.wait_for_key() {
+            return self.opcode;
+        }
+
+        hooks::pre_cycle(self);
+        let pc = self.pc;
+
+        // Fetch opcode
+        let opcode = match self.try_fetch() {
+            Ok(opcode) => opcode,
+            Err(error) => {
+                self.report_bounds_fault(error);
+                return self.opcode;
+            },
+        };
+        self.opcode = opcode;
+        self.trace.record(self.pc, opcode);
+
+        self.hooks.log(Target::Cpu, Level::Trace,
+            format_args!("{:#X?} Opcode: {:#X?}", pc, opcode));
+
+        // The display is only compared when someone listens for changes to it, and a double
+        // buffered one only changes when it is presented
+        let shown = if self.events.has_subscribers() && !self.display.is_double_buffered() {
+            Some(*self.display.rows())
+        } else {
+            None
+        };
+
+        // The program counter moves past the instruction before it is executed, so jumps,
+        // calls and skips set it to where execution continues
+        self.executing = pc;
+        self.pc += 2;
+
+        // Decode opcode
+        match decode::decode(opcode, self.chip8x.is_some()) {
+            Some(instruction) => {
+                let warning = if self.strictness == Strictness::Off { None } else {
+                    strictness::check_index(opcode, self.i, &self.sprite_rules)
+                };
+                if let Some(warning) = warning {
+                    self.report_strictness(pc, warning);
+                }
+                if self.strictness == Strictness::Strict {
+                    if let Some(warning) = self.ambiguity.check(opcode, &self.registers) {
+                        self.report_strictness(pc, warning);
+                    }
+                }
+
+                (instruction.execute)(self, opcode)
+            },
+            None => self.report_unknown(pc, opcode),
+        }
+
+        if shown.is_some_and(|rows| rows != *self.display.rows()) {
+            self.events.publish(Event::DisplayUpdated);
+        }
+
+        // Only reported where the program counter turns odd, not for every instruction after
+        if self.strictness != Strictness::Off && !self.pc.is_multiple_of(2) && pc.is_multiple_of(2) {
+            let warning = format!("{:04X} moved the program counter to odd address {:#05X}",
+                opcode, self.pc);
+            self.report_strictness(pc, warning);
+        }
+        hooks::post_cycle(self, pc, opcode);
+
+        opcode
+    }
+
+    /// Execute a single instruction like `cycle`, returning what the front end has to do
+    /// about it.
+    pub fn step(&mut self) -> Effect {
+        let was_waiting = self.state != State::Running;
+        let sound = self.timers.sound;
+        let opcode = self.try_fetch().ok();
+
+        self.cycle();
+
+        if self.state != State::Running {
+            return Effect::WaitKey;
+        }
+        // The instruction is only executed on the next step once the key arrived
+        if was_waiting {
+            return Effect::None;
+        }
+
+        match opcode {
+            Some(0x00E0) => Effect::ClearScreen,
+            Some(opcode) if opcode & 0xF000 == 0xD000 => Effect::Draw,
+            Some(_) if sound == 0 && self.timers.sound > 0 => Effect::Beep,
+            _ => Effect::None,
+        }
+    }
+
+    /// Execute an opcode that is not in memory, as if it came right before the instruction
+    /// at the program counter, to try out instructions from the debugger.
+    pub fn execute(&mut self, opcode: u16) -> Result<(), String> {
+        let instruction = decode::decode(opcode, self.chip8x.is_some())
+            .ok_or_else(|| format!("Cannot execute {:04X}", opcode))?;
+        let shown = *self.display.front_rows();
+
+        // Instructions are executed with the PC already past them
+        self.executing = self.pc.wrapping_sub(2);
+        (instruction.execute)(self, opcode);
+
+        // The machine is paused, so what the instruction drew is shown right away
+        self.display.present();
+        if shown != *self.display.front_rows() {
+            self.events.publish(Event::DisplayUpdated);
+        }
+
+        Ok(())
+    }
+
+    fn report_stack_fault(&mut self, error: StackError) {
+        match error {
+            StackError::Overflow => self.hooks.log(Target::Cpu, Level::Warn, format_args!(
+                "Stack overflow at {:#X?}: all {} levels are in use", self.executing,
+                self.stack.depth())),
+            StackError::Underflow => self.hooks.log(Target::Cpu, Level::Warn, format_args!(
+                "Stack underflow at {:#X?}: return with an empty stack", self.executing)),
+        }
+
+        self.stack_fault.get_or_insert(self.executing);
+        let message = match error {
+            StackError::Overflow => format!("Stack overflow at {:#05X}", self.executing),
+            StackError::Underflow => format!("Stack underflow at {:#05X}", self.executing),
+        };
+        self.events.publish(Event::Error(message));
+    }
+
+    /// Log an access past the end of memory, and remember the first one.
+    fn report_bounds_fault(&mut self, error: Chip8Error) {
+        self.hooks.log(Target::Memory, Level::Warn, format_args!("{}", error));
+        self.events.publish(Event::Error(error.to_string()));
+        self.bounds_fault.get_or_insert(error);
+    }
+
+    /// Log an instruction that failed a strictness check, and remember the first one when
+    /// the machine should break on it.
+    fn report_strictness(&mut self, pc: u16, warning: String) {
+        self.hooks.log(Target::Cpu, Level::Warn, format_args!("{} at {:#05X}", warning, pc));
+        self.events.publish(Event::Error(warning));
+
+        if self.strictness.breaks() {
+            self.strictness_fault.get_or_insert(pc);
+        }
+    }
+
+    /// Log why an opcode cannot be executed, and remember the first such address.
+    fn report_unknown(&mut self, pc: u16, opcode: u16) {
+        let diagnosis = diagnostics::diagnose(pc, opcode);
+        self.hooks.log(Target::Cpu, Level::Warn, format_args!("{}", diagnosis));
+        self.events.publish(Event::Error(diagnosis));
+        self.unknown_opcode.get_or_insert(pc);
+    }
+
+    /// Count the timers down by a single tick, at the display interrupt that also presents
+    /// the display.
+    pub fn update_timers(&mut self) {
+        let sounding = self.timers.sounding();
+        self.timers.tick();
+        self.present_display();
+
+        self.sound_changed(sounding, self.timers.sounding());
+        self.hooks.tick(self.timers.delay, self.timers.sound);
+    }
+
+    /// Show the display as it is now when it is double buffered, see `display`.
+    pub fn present_display(&mut self) {
+        if self.display.present() {
+            self.events.publish(Event::DisplayUpdated);
+        }
+    }
+
+    /// Call the sound hooks and tell the subscribers when the buzzer starts or stops.
+    fn sound_changed(&mut self, before: bool, after: bool) {
+        self.hooks.sound_changed(before, after);
+
+        if before != after {
+            self.events.publish(Event::SoundStateChanged(after));
+        }
+    }
+
+    /// Set the sound timer with the buzzer following it right away, unlike a ROM loading
+    /// it, for restoring it. See `timers`.
+    pub fn restore_sound_timer(&mut self, value: u8) {
+        let sounding = self.timers.sounding();
+        self.timers.set_sound(value);
+
+        self.sound_changed(sounding, self.timers.sounding());
+    }
+
+    /// Drive the timers at `hz` by another clock, such as a `ManualClock` for virtual time.
+    pub fn set_clock<C: Clock + 'static>(&mut self, hz: u32, clock: C) {
+        self.timers.set_clock(hz, Box::new(clock));
+    }
+
+    /// Run the timer ticks that are due by the clock of the timers, returning how many.
+    pub fn run_timers(&mut self) -> u32 {
+        let due = self.timers.due();
+        for _ in 0..due {
+            self.update_timers();
+        }
+
+        due
+    }
+
+    /// Register a callback for when the sound timer starts running and the buzzer sounds.
+    pub fn on_sound_start<F: FnMut() + Send + 'static>(&mut self, callback: F) {
+        self.hooks.sound_start = Some(Box::new(callback));
+    }
+
+    /// Register a callback for when the sound timer reaches zero and the buzzer stops.
+    pub fn on_sound_stop<F: FnMut() + Send + 'static>(&mut self, callback: F) {
+        self.hooks.sound_stop = Some(Box::new(callback));
+    }
+
+    /// Register a callback for when the XO-CHIP audio pattern or pitch changes, including
+    /// back to no pattern on a reset.
+    pub fn on_audio_pattern<F>(&mut self, callback: F)
+            where F: FnMut(Option<[u8; 16]>, u8) + Send + 'static {
+        self.hooks.audio_pattern = Some(Box::new(callback));
+    }
+
+    /// Register a callback that receives the delay and sound timers after every timer tick.
+    pub fn on_timer_tick<F: FnMut(u8, u8) + Send + 'static>(&mut self, callback: F) {
+        self.hooks.timer_tick = Some(Box::new(callback));
+    }
+
+    /// Register a callback to run before every instruction, next to those already registered.
+    pub fn on_pre_cycle<F: FnMut(&Chip8) + Send + 'static>(&mut self, callback: F) {
+        self.hooks.pre_cycle.push(Box::new(callback));
+    }
+
+    /// Register a callback to run after every instruction, receiving its address and opcode.
+    pub fn on_post_cycle<F: FnMut(&Chip8, u16, u16) + Send + 'static>(&mut self, callback: F) {
+        self.hooks.post_cycle.push(Box::new(callback));
+    }
+
+    /// Register a callback to run once when the run ends, next to those already registered.
+    pub fn on_shutdown<F>(&mut self, callback: F)
+        where F: FnOnce(&Chip8, ShutdownReason) + Send + 'static
+    {
+        self.hooks.shutdown.push(Box::new(callback));
+    }
+
+    /// Run the shutdown hooks, to finish what has to be finished before the process ends.
+    pub fn shutdown(&mut self, reason: ShutdownReason) {
+        hooks::shutdown(self, reason);
+    }
+
+    /// Register a callback for the diagnostic messages of the core, which are otherwise dropped.
+    pub fn on_log<F>(&mut self, callback: F)
+        where F: FnMut(Target, Level, fmt::Arguments) + Send + 'static
+    {
+        self.hooks.log = Some(Box::new(callback));
+    }
+}
+
+impl Default for Chip8 {
+    fn default() -> Chip8 {
+        Chip8::new()
+    }
+}
+
+impl Cpu for Chip8 {
+    fn register(&self, x: usize) -> u8 {
+        self.registers[x]
+    }
+
+    fn set_register(&mut self, x: usize, value: u8) {
+        self.registers[x] = value;
+    }
+
+    fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    fn i(&self) -> u16 {
+        self.i
+    }
+
+    fn set_i(&mut self, i: u16) {
+        self.i = i;
+    }
+
+    /// Push a return address, refusing it when every level of the stack is in use.
+    fn push(&mut self, address: u16) {
+        if let Err(error) = self.stack.push(address) {
+            self.report_stack_fault(error);
+        }
+    }
+
+    /// Pop a return address. On an empty stack the program counter stays where it is.
+    fn pop(&mut self) -> u16 {
+        self.stack.pop().unwrap_or_else(|error| {
+            self.report_stack_fault(error);
+            self.pc
+        })
+    }
+
+    /// Read a byte from memory, reading 0 past the end of it.
+    fn read_memory(&mut self, address: usize) -> u8 {
+        self.memory_at(address).unwrap_or_else(|error| {
+            self.report_bounds_fault(error);
+            0
+        })
+    }
+
+    /// Write a byte to memory, refusing writes to the interpreter area when it is protected.
+    fn write_memory(&mut self, address: usize, value: u8) {
+        if self.protect_memory && address < PROGRAM_START {
+            self.hooks.log(Target::Memory, Level::Warn, format_args!(
+                "Blocked write of {:#X?} to protected address {:#X?} at {:#X?}",
+                value, address, self.executing));
+
+            self.protection_fault.get_or_insert(address as u16);
+            self.events.publish(Event::Error(format!(
+                "Write to protected address {:#05X} at {:#05X}", address, self.executing)));
+            return;
+        }
+
+        match self.memory_at_mut(address) {
+            Ok(byte) => {
+                *byte = value;
+                self.highest_write = self.highest_write.max(Some(address as u16));
+            },
+            Err(error) => self.report_bounds_fault(error),
+        }
+    }
+
+    fn display(&mut self) -> &mut Display {
+        &mut self.display
+    }
+
+    fn is_key_down(&self, key: usize) -> bool {
+        self.keypad.is_down(key)
+    }
+
+    fn set_state(&mut self, state: State) {
+        if let State::WaitingForKey(v_x) = state {
+            self.events.publish(Event::KeyWaited(v_x));
+        }
+
+        self.state = state;
+    }
+
+    fn delay_timer(&self) -> u8 {
+        self.timers.delay
+    }
+
+    fn set_delay_timer(&mut self, value: u8) {
+        self.timers.delay = value;
+    }
+
+    /// Load the sound timer, firing the sound hooks when the buzzer stops. It starts at the
+    /// next timer tick, see `timers`.
+    fn set_sound_timer(&mut self, value: u8) {
+        let sounding = self.timers.sounding();
+        self.timers.load_sound(value);
+
+        self.sound_changed(sounding, self.timers.sounding());
+    }
+
+    fn set_audio_pattern(&mut self, pattern: [u8; 16]) {
+        self.audio_pattern = Some(pattern);
+        self.hooks.audio_pattern_changed(self.audio_pattern, self.pitch);
+    }
+
+    fn set_pitch(&mut self, pitch: u8) {
+        self.pitch = pitch;
+        self.hooks.audio_pattern_changed(self.audio_pattern, self.pitch);
+    }
+
+    fn random_byte(&mut self) -> u8 {
+        self.rng.next_byte()
+    }
+
+    fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    fn sprite_rules(&self) -> SpriteRules {
+        self.sprite_rules
+    }
+
+    fn chip8x(&mut self) -> Option<&mut Chip8X> {
+        self.chip8x.as_mut()
+    }
+
+    fn log(&mut self, target: Target, message: fmt::Arguments) {
+        self.hooks.log(target, Level::Debug, message);
+    }
+}
+
+/// The number of bytes a ROM for the variant can take up in memory.
+#[cfg(feature = "std")]
+fn rom_area(variant: variant::Variant) -> usize {
+    MEMORY - variant.program_start() as usize
+}
+
+/// Load the ROM of the configuration again, with its patch.
+#[cfg(feature = "std")]
+fn reload_rom(chip8: &mut Chip8, config: &Config) -> Result<(), String> {
+    let (mut rom, _) = container::load(&config.rom).map_err(|e| e.to_string())?;
+    if config.recover {
+        rom = recovery::recover(&rom, rom_area(config.variant));
+    }
+    let rom = match &config.patch {
+        Some(patch) => ips::apply_file(&rom, patch, rom_area(config.variant))?,
+        None => rom,
+    };
+
+    chip8.load_bytes(&rom);
+    Ok(())
+}
+
+/// The name save states and metadata of a ROM are stored under: the name of the ROM file,
+/// or of the entry in an archive.
+#[cfg(feature = "std")]
+fn storage_name(rom: &str) -> String {
+    let path = Path::new(rom.rsplit(':').next().unwrap_or(rom));
+
+    path.file_stem().map_or(String::from("rom"), |stem| stem.to_string_lossy().into_owned())
+}
@@ -0,0 +1,223 @@
+//! Translations of what the emulator shows and reports to the user: the pause menu, the
+//! messages on the game display, the prompts and the faults the machine pauses on.
+//!
+//! Every text is a `Text`, with a translation per language in `Text::translations`. The
+//! language is chosen once at startup with `--lang`, English by default. Texts with
+//! values in them have a `{}` in their place, filled in by `fill`.
+//!
+//! The font only has ASCII, so translations leave out accents, and texts shown on the
+//! game display have to stay within its 16 characters.
+
+use core::{fmt, str::FromStr};
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use alloc::{format, string::{String, ToString}};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Language {
+    English,
+    Dutch,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::Dutch];
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Language, String> {
+        match s {
+            "en" => Ok(Language::English),
+            "nl" => Ok(Language::Dutch),
+            _ => Err(format!("Unknown language '{}', expected en or nl", s)),
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Language::English => write!(f, "en"),
+            Language::Dutch => write!(f, "nl"),
+        }
+    }
+}
+
+// The language of this run, as its index in `Language::ALL`
+static LANGUAGE: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_language(language: Language) {
+    let idx = Language::ALL.iter().position(|&l| l == language).unwrap_or(0);
+    LANGUAGE.store(idx as u8, Ordering::Relaxed);
+}
+
+pub fn language() -> Language {
+    Language::ALL[LANGUAGE.load(Ordering::Relaxed) as usize]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Text {
+    // The pause menu
+    Paused,
+    Settings,
+    Resume,
+    Reset,
+    LoadRom,
+    States,
+    SetupTest,
+    Quit,
+    Filter,
+    Rotate,
+    Colors,
+    FocusPause,
+    On,
+    Off,
+    Back,
+
+    // On the game display
+    Slow,
+    Recording,
+    Beep,
+    Unlocked,
+    PressKey,
+
+    // Prompts
+    ResumeSession,
+    Yes,
+    StartOver,
+    UnknownOpcode,
+    Skip,
+    Nop,
+    Abort,
+
+    // Faults
+    Pausing,
+    StackFault,
+    StrictnessFault,
+    FetchOutOfBounds,
+    ReadOutOfBounds,
+    WriteOutOfBounds,
+    RomTooLarge,
+}
+
+impl Text {
+    /// The text in every language, in the order of `Language::ALL`.
+    fn translations(self) -> [&'static str; 2] {
+        match self {
+            Text::Paused => ["PAUSED", "GEPAUZEERD"],
+            Text::Settings => ["SETTINGS", "INSTELLINGEN"],
+            Text::Resume => ["RESUME", "HERVATTEN"],
+            Text::Reset => ["RESET", "HERSTARTEN"],
+            Text::LoadRom => ["LOAD ROM", "ROM LADEN"],
+            Text::States => ["SAVE/LOAD STATE", "OPSLAAN/LADEN"],
+            Text::SetupTest => ["SETUP TEST", "TESTSCHERM"],
+            Text::Quit => ["QUIT", "AFSLUITEN"],
+            Text::Filter => ["FILTER", "FILTER"],
+            Text::Rotate => ["ROTATE", "DRAAI"],
+            Text::Colors => ["COLORS", "KLEUREN"],
+            Text::FocusPause => ["FOCUS PAUSE", "FOCUSPAUZE"],
+            Text::On => ["ON", "AAN"],
+            Text::Off => ["OFF", "UIT"],
+            Text::Back => ["BACK", "TERUG"],
+
+            Text::Slow => ["SLOW", "TRAAG"],
+            Text::Recording => ["REC", "OPN"],
+            Text::Beep => ["BEEP", "PIEP"],
+            Text::Unlocked => ["UNLOCKED", "BEHAALD"],
+            Text::PressKey => ["PRESS A KEY 0-F", "DRUK TOETS 0-F"],
+
+            Text::ResumeSession => ["RESUME?", "HERVATTEN?"],
+            Text::Yes => ["Y YES", "Y JA"],
+            Text::StartOver => ["N START OVER", "N OPNIEUW"],
+            Text::UnknownOpcode => ["OP {}?", "OP {}?"],
+            Text::Skip => ["S SKIP", "S OVERSLAAN"],
+            Text::Nop => ["N NOP", "N NOP"],
+            Text::Abort => ["A ABORT", "A AFBREKEN"],
+
+            Text::Pausing => ["{}, pausing", "{}, gepauzeerd"],
+            Text::StackFault => ["Stack fault at {}", "Stackfout op {}"],
+            Text::StrictnessFault => ["Strictness check failed at {}",
+                "Striktheidscontrole mislukt op {}"],
+            Text::FetchOutOfBounds => ["Fetch past the end of memory at {}",
+                "Instructie voorbij het einde van het geheugen op {}"],
+            Text::ReadOutOfBounds => ["Read of {} past the end of memory at {}",
+                "Lezen van {} voorbij het einde van het geheugen op {}"],
+            Text::WriteOutOfBounds => ["Write to {} past the end of memory at {}",
+                "Schrijven naar {} voorbij het einde van het geheugen op {}"],
+            Text::RomTooLarge => ["ROM of {} bytes does not fit in {} bytes of memory",
+                "ROM van {} bytes past niet in {} bytes geheugen"],
+        }
+    }
+
+    pub fn get(self, language: Language) -> &'static str {
+        let idx = Language::ALL.iter().position(|&l| l == language).unwrap_or(0);
+        self.translations()[idx]
+    }
+}
+
+/// The text in the language of this run.
+pub fn tr(text: Text) -> &'static str {
+    text.get(language())
+}
+
+/// The text in the language of this run, with its `{}` replaced by the values in order.
+pub fn fill(text: Text, values: &[&dyn fmt::Display]) -> String {
+    fill_template(tr(text), values)
+}
+
+fn fill_template(template: &str, values: &[&dyn fmt::Display]) -> String {
+    let mut parts = template.split("{}");
+    let mut filled = parts.next().unwrap_or_default().to_string();
+
+    for (idx, part) in parts.enumerate() {
+        if let Some(value) = values.get(idx) {
+            filled.push_str(&value.to_string());
+        }
+        filled.push_str(part);
+    }
+
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXTS: [Text; 34] = [Text::Paused, Text::Settings, Text::Resume, Text::Reset,
+        Text::LoadRom, Text::States, Text::SetupTest, Text::Quit, Text::Filter, Text::Rotate,
+        Text::Colors, Text::FocusPause, Text::On, Text::Off, Text::Back, Text::Slow,
+        Text::Recording, Text::Beep, Text::Unlocked, Text::PressKey, Text::ResumeSession,
+        Text::Yes, Text::StartOver, Text::UnknownOpcode, Text::Skip, Text::Nop, Text::Abort,
+        Text::Pausing, Text::StackFault, Text::StrictnessFault, Text::FetchOutOfBounds,
+        Text::ReadOutOfBounds, Text::WriteOutOfBounds, Text::RomTooLarge];
+
+    #[test]
+    fn test_translations() {
+        for &text in TEXTS.iter() {
+            let english = text.get(Language::English);
+
+            for &language in Language::ALL.iter() {
+                let translated = text.get(language);
+                assert!(!translated.is_empty() && translated.is_ascii(), "{:?}", text);
+                assert_eq!(translated.matches("{}").count(), english.matches("{}").count(),
+                    "{:?} in {}", text, language);
+            }
+        }
+
+        // Shown on the game display
+        for &text in TEXTS[..27].iter() {
+            assert!(text.get(Language::Dutch).len() <= 16, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_fill() {
+        assert_eq!(fill_template("Read of {} at {}", &[&0x1000, &"0x200"]),
+            "Read of 4096 at 0x200");
+        assert_eq!(fill_template("PAUSED", &[]), "PAUSED");
+        assert_eq!(Text::Pausing.get(Language::Dutch), "{}, gepauzeerd");
+        assert_eq!("nl".parse(), Ok(Language::Dutch));
+        assert!("fr".parse::<Language>().is_err());
+    }
+}
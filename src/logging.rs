@@ -0,0 +1,169 @@
+//! Filtering the diagnostic messages of the core by subsystem and level.
+//!
+//! Every message comes from a target such as `chip8::cpu` or `chip8::display`, at a level
+//! from `error` to `trace`. The filter is written like `RUST_LOG`: directives separated by
+//! commas, each a level for all targets, a target, or a target with a level. The directive
+//! with the longest matching target decides:
+//!
+//! ```text
+//! info,chip8::display=debug,chip8::input=off
+//! ```
+//!
+//! Faults are logged as warnings, what each instruction does at debug level and every
+//! executed opcode at trace level, so only the faults are shown by default.
+
+use core::{fmt, str::FromStr};
+#[cfg(feature = "std")]
+use std::env;
+
+use alloc::{format, string::{String, ToString}, vec::Vec};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Level, String> {
+        match s.to_lowercase().as_str() {
+            "error" => Ok(Level::Error),
+            "warn" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            _ => Err(format!("Unknown log level '{}'", s)),
+        }
+    }
+}
+
+/// The subsystem a message comes from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Cpu,
+    Display,
+    Input,
+    Memory,
+}
+
+impl Target {
+    pub fn name(self) -> &'static str {
+        match self {
+            Target::Cpu => "chip8::cpu",
+            Target::Display => "chip8::display",
+            Target::Input => "chip8::input",
+            Target::Memory => "chip8::memory",
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Which messages to show. A level of `None` turns a target off.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogFilter {
+    default: Option<Level>,
+    directives: Vec<(String, Option<Level>)>,
+}
+
+impl LogFilter {
+    /// The filter in `RUST_LOG`, if it is set and valid.
+    #[cfg(feature = "std")]
+    pub fn from_env() -> Option<LogFilter> {
+        let filter = env::var("RUST_LOG").ok()?;
+        match filter.parse() {
+            Ok(filter) => Some(filter),
+            Err(e) => {
+                println!("Ignoring RUST_LOG: {}", e);
+                None
+            },
+        }
+    }
+
+    pub fn enabled(&self, target: Target, level: Level) -> bool {
+        let name = target.name();
+        let max = self.directives.iter()
+            .filter(|(prefix, _)| name.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default, |&(_, level)| level);
+
+        max.is_some_and(|max| level <= max)
+    }
+}
+
+impl Default for LogFilter {
+    fn default() -> LogFilter {
+        LogFilter { default: Some(Level::Info), directives: Vec::new() }
+    }
+}
+
+fn parse_level(s: &str) -> Result<Option<Level>, String> {
+    if s.eq_ignore_ascii_case("off") {
+        Ok(None)
+    } else {
+        s.parse().map(Some)
+    }
+}
+
+impl FromStr for LogFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LogFilter, String> {
+        let mut filter = LogFilter::default();
+
+        for directive in s.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.find('=') {
+                Some(i) => {
+                    let level = parse_level(&directive[i + 1..])?;
+                    filter.directives.push((directive[..i].to_string(), level));
+                },
+                // A bare word is a level for all targets, or a target to show in full
+                None => match parse_level(directive) {
+                    Ok(level) => filter.default = level,
+                    Err(_) => filter.directives.push((directive.to_string(), Some(Level::Trace))),
+                },
+            }
+        }
+
+        Ok(filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_shows_faults_only() {
+        let filter = LogFilter::default();
+
+        assert!(filter.enabled(Target::Memory, Level::Warn));
+        assert!(!filter.enabled(Target::Cpu, Level::Debug));
+    }
+
+    #[test]
+    fn test_directives() {
+        let filter: LogFilter = "warn,chip8=debug,chip8::display=trace,chip8::input=off"
+            .parse().unwrap();
+
+        assert!(filter.enabled(Target::Cpu, Level::Debug));
+        assert!(!filter.enabled(Target::Cpu, Level::Trace));
+        assert!(filter.enabled(Target::Display, Level::Trace));
+        assert!(!filter.enabled(Target::Input, Level::Error));
+
+        let filter: LogFilter = "off,chip8::memory".parse().unwrap();
+        assert!(filter.enabled(Target::Memory, Level::Trace));
+        assert!(!filter.enabled(Target::Cpu, Level::Error));
+
+        assert!("chip8::cpu=loud".parse::<LogFilter>().is_err());
+    }
+}
@@ -0,0 +1,185 @@
+//! Key macros: short recorded input sequences, such as the way through a game's menu,
+//! played back with a single hotkey.
+//!
+//! Every macro of a ROM is kept in its metadata directory as a replay file, see `replay`,
+//! named after the ROM and the number it is bound to, e.g. `pong.macro1`. Only the keypad
+//! states and the length are used; the seed is ignored.
+
+use std::{fs, path::PathBuf};
+
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::replay::{Recorder, Replay};
+
+const MACRO_KEYS: [Key; 9] = [
+    Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5,
+    Key::Key6, Key::Key7, Key::Key8, Key::Key9,
+];
+
+/// The macros of a ROM, with the one being recorded or played.
+///
+/// Ctrl+Shift and a number key starts recording the macro for that number, and pressing it
+/// again stores it. Ctrl and a number key plays the macro; keys held meanwhile still count.
+/// The keys are not passed to the game while Ctrl is held, so the number keys of the
+/// keypad can be used in the hotkeys.
+pub struct Macros {
+    dir: PathBuf,
+    rom_name: String,
+    // The macro bound to number key n + 1, if there is one
+    macros: Vec<Option<Replay>>,
+    // The number being recorded and the frame the recording started at
+    recording: Option<(usize, u64, Recorder)>,
+    // The number being played and the frame it started at
+    playing: Option<(usize, u64)>,
+}
+
+impl Macros {
+    /// Read the macros of a ROM, skipping the ones that do not parse.
+    pub fn load(dir: PathBuf, rom_name: &str) -> Macros {
+        let mut macros = Macros {
+            dir,
+            rom_name: rom_name.to_string(),
+            macros: Vec::new(),
+            recording: None,
+            playing: None,
+        };
+
+        macros.macros = (1..=MACRO_KEYS.len())
+            .map(|number| {
+                let contents = fs::read_to_string(macros.path(number)).ok()?;
+
+                Replay::parse(&contents)
+                    .map_err(|e| println!("Skipping macro {}: {}", number, e))
+                    .ok()
+            })
+            .collect();
+
+        macros
+    }
+
+    fn path(&self, number: usize) -> PathBuf {
+        self.dir.join(format!("{}.macro{}", self.rom_name, number))
+    }
+
+    /// Whether the hotkeys are being held, so the keys should not go to the game.
+    pub fn is_chord_held(window: &Window) -> bool {
+        window.is_key_down(Key::LeftCtrl) || window.is_key_down(Key::RightCtrl)
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn handle_input(&mut self, window: &Window, frame: u64) {
+        if !Macros::is_chord_held(window) {
+            return;
+        }
+
+        let number = match MACRO_KEYS.iter()
+            .position(|&key| window.is_key_pressed(key, KeyRepeat::No)) {
+            Some(idx) => idx + 1,
+            None => return,
+        };
+
+        if window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift) {
+            self.toggle_recording(number, frame);
+        } else {
+            self.play(number, frame);
+        }
+    }
+
+    fn toggle_recording(&mut self, number: usize, frame: u64) {
+        let (recorded, start, recorder) = match self.recording.take() {
+            Some(recording) => recording,
+            None => {
+                println!("Recording macro {}", number);
+                self.playing = None;
+                self.recording = Some((number, frame, Recorder::new(0)));
+                return;
+            },
+        };
+
+        let contents = recorder.finish(frame - start);
+        if let Err(e) = fs::write(self.path(recorded), &contents) {
+            println!("Could not store macro {}: {}", recorded, e);
+        }
+
+        println!("Recorded macro {} of {} frames", recorded, frame - start);
+        self.macros[recorded - 1] = Replay::parse(&contents).ok();
+    }
+
+    /// Start playing a macro, unless one is being recorded.
+    pub fn play(&mut self, number: usize, frame: u64) {
+        if self.recording.is_some() {
+            return;
+        }
+
+        match self.macros.get(number - 1) {
+            Some(Some(_)) => self.playing = Some((number, frame)),
+            _ => println!("No macro {}", number),
+        }
+    }
+
+    /// Record the keys of a frame, if a macro is being recorded.
+    pub fn record(&mut self, frame: u64, keys: [bool; 16]) {
+        if let Some((_, start, recorder)) = self.recording.as_mut() {
+            recorder.record(frame - *start, keys);
+        }
+    }
+
+    /// The keys of the macro being played in a frame, if one is.
+    pub fn played(&mut self, frame: u64) -> Option<[bool; 16]> {
+        let (number, start) = self.playing?;
+        let replay = self.macros[number - 1].as_ref().unwrap();
+
+        if replay.end.map_or(false, |end| frame - start >= end) {
+            self.playing = None;
+            return None;
+        }
+
+        Some(replay.keys_at(frame - start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn macros(name: &str) -> Macros {
+        Macros::load(env::temp_dir(), name)
+    }
+
+    #[test]
+    fn test_record_and_play() {
+        let mut recording = macros("macros_test_record");
+        let mut keys = [false; 16];
+
+        recording.toggle_recording(2, 10);
+        recording.record(10, keys);
+        keys[5] = true;
+        recording.record(12, keys);
+        recording.toggle_recording(2, 14);
+
+        // The macro is read back from the metadata directory
+        let mut played = macros("macros_test_record");
+        played.play(2, 100);
+
+        assert!(!played.played(101).unwrap()[5]);
+        assert!(played.played(102).unwrap()[5]);
+        assert!(played.played(103).unwrap()[5]);
+        assert_eq!(played.played(104), None);
+        assert!(played.playing.is_none());
+
+        fs::remove_file(played.path(2)).unwrap();
+    }
+
+    #[test]
+    fn test_play_missing_macro() {
+        let mut macros = macros("macros_test_missing");
+        macros.play(9, 0);
+
+        assert!(macros.playing.is_none());
+        assert_eq!(macros.played(1), None);
+    }
+}
@@ -0,0 +1,445 @@
+use std::{fmt, fs, collections::{BTreeMap, BTreeSet}};
+
+use crate::{MEMORY, PROGRAM_START};
+use crate::archive::read_rom;
+use crate::decode::decode;
+use crate::disassembler::disassemble;
+
+const USAGE: &str = "Usage: chip8 map rom.ch8 [--dot] [-o output]";
+
+/// What a byte of the ROM is used for, as far as static analysis can tell.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Region {
+    /// Reachable instructions.
+    Code,
+    /// Drawn with DRW after pointing I at it.
+    Sprite,
+    /// Pointed at by I, but not drawn.
+    Data,
+    /// Neither reached nor referenced.
+    Unknown,
+}
+
+impl Region {
+    fn name(self) -> &'static str {
+        match self {
+            Region::Code => "code",
+            Region::Sprite => "sprite",
+            Region::Data => "data",
+            Region::Unknown => "unknown",
+        }
+    }
+}
+
+/// Something in a ROM that is likely a mistake, or that runs differently depending on the
+/// interpreter.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub enum Warning {
+    /// A jump or call from `at` to an odd address.
+    OddJump { at: u16, target: u16 },
+    /// The instruction at `at` reads memory through I up to `end`, past the last byte.
+    ReadPastEnd { at: u16, end: usize },
+    /// The DRW at `at` draws the reachable code at `sprite` as a sprite.
+    SpriteInCode { at: u16, sprite: u16 },
+    /// Bytes from `start` to `end` that look like instructions, but are never reached.
+    UnreachableCode { start: u16, end: u16 },
+    /// `count` reachable instructions, the first at `at`, behave differently depending on
+    /// a quirk.
+    Quirk { at: u16, quirk: &'static str, count: usize },
+}
+
+impl Warning {
+    /// The address the warning is about, to sort them by.
+    pub fn address(&self) -> u16 {
+        match *self {
+            Warning::OddJump { at, .. } | Warning::ReadPastEnd { at, .. }
+                | Warning::SpriteInCode { at, .. } | Warning::Quirk { at, .. } => at,
+            Warning::UnreachableCode { start, .. } => start,
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::OddJump { at, target } =>
+                write!(f, "{:#05X}: jump to odd address {:#05X}", at, target),
+            Warning::ReadPastEnd { at, end } =>
+                write!(f, "{:#05X}: reads up to {:#X}, past the end of memory", at, end),
+            Warning::SpriteInCode { at, sprite } =>
+                write!(f, "{:#05X}: draws the code at {:#05X} as a sprite", at, sprite),
+            Warning::UnreachableCode { start, end } =>
+                write!(f, "{:#05X}: unreachable code up to {:#05X}", start, end),
+            Warning::Quirk { at, quirk, count } =>
+                write!(f, "{:#05X}: {} instruction(s) depend on the {} quirk", at, count, quirk),
+        }
+    }
+}
+
+/// The layout of a ROM, found by following every path through the code from the start.
+///
+/// Both branches of every skip are followed. `JP V0, addr` is assumed to land on `addr`,
+/// which is where jump tables usually start.
+pub struct MemoryMap {
+    regions: Vec<Region>,
+    /// The subroutines, from their entry point to the subroutines they call. The program
+    /// itself is the subroutine at `PROGRAM_START`.
+    pub subroutines: BTreeMap<u16, BTreeSet<u16>>,
+    /// The targets of jumps, to the addresses jumping there.
+    pub jump_targets: BTreeMap<u16, BTreeSet<u16>>,
+    /// What looks wrong or quirk dependent, by address.
+    pub warnings: Vec<Warning>,
+    // Found while tracing, and turned into warnings once the whole ROM is known
+    found: BTreeSet<Warning>,
+    // The DRW instructions with the sprite they draw and its height
+    draws: BTreeSet<(u16, u16, u16)>,
+    // The reachable quirk dependent instructions, by quirk
+    quirk_uses: BTreeMap<&'static str, BTreeSet<u16>>,
+}
+
+impl MemoryMap {
+    pub fn analyze(rom: &[u8]) -> MemoryMap {
+        let mut map = MemoryMap {
+            regions: vec!(Region::Unknown; rom.len()),
+            subroutines: BTreeMap::new(),
+            jump_targets: BTreeMap::new(),
+            warnings: Vec::new(),
+            found: BTreeSet::new(),
+            draws: BTreeSet::new(),
+            quirk_uses: BTreeMap::new(),
+        };
+        // Subroutines still to trace, with the address I pointed at when they were called
+        let mut entries = vec!((PROGRAM_START as u16, None));
+
+        while let Some((entry, i)) = entries.pop() {
+            if map.subroutines.contains_key(&entry) {
+                continue;
+            }
+
+            let mut calls = Vec::new();
+            map.trace_subroutine(rom, entry, i, &mut calls);
+
+            map.subroutines.insert(entry, calls.iter().map(|&(call, _)| call).collect());
+            entries.extend(calls);
+        }
+
+        map.collect_warnings(rom);
+        map
+    }
+
+    /// Follow the code of a subroutine, marking what it executes and references, and
+    /// collect the subroutines it calls.
+    fn trace_subroutine(
+            &mut self, rom: &[u8], entry: u16, i: Option<u16>,
+            calls: &mut Vec<(u16, Option<u16>)>) {
+
+        let mut visited = BTreeSet::new();
+        // Every path carries the address I was last set to, to find the sprites
+        let mut pending = vec!((entry, i));
+
+        while let Some((address, i)) = pending.pop() {
+            let opcode = match opcode_at(rom, address) {
+                Some(opcode) if visited.insert(address) => opcode,
+                _ => continue,
+            };
+            self.mark(address, 2, Region::Code);
+
+            let next = address + 2;
+            let nnn = opcode & 0x0FFF;
+            self.check(address, opcode, i);
+
+            match opcode & 0xF000 {
+                0x0000 if opcode == 0x00EE => {},
+                0x1000 => {
+                    self.jump_targets.entry(nnn).or_default().insert(address);
+                    pending.push((nnn, i));
+                },
+                0x2000 => {
+                    calls.push((nnn, i));
+                    pending.push((next, i));
+                },
+                0x3000 | 0x4000 | 0x5000 | 0x9000 => {
+                    pending.push((next, i));
+                    pending.push((next + 2, i));
+                },
+                0xA000 => {
+                    self.mark(nnn, 1, Region::Data);
+                    pending.push((next, Some(nnn)));
+                },
+                0xB000 => {
+                    self.jump_targets.entry(nnn).or_default().insert(address);
+                    pending.push((nnn, i));
+                },
+                0xD000 => {
+                    if let Some(sprite) = i {
+                        self.mark(sprite, opcode & 0x000F, Region::Sprite);
+                        self.draws.insert((address, sprite, opcode & 0x000F));
+                    }
+                    pending.push((next, i));
+                },
+                0xE000 if opcode & 0x00FF == 0x9E || opcode & 0x00FF == 0xA1 => {
+                    pending.push((next, i));
+                    pending.push((next + 2, i));
+                },
+                // ADD I changes I in a way that cannot be followed statically
+                0xF000 if opcode & 0x00FF == 0x1E => pending.push((next, None)),
+                // Anything that does not disassemble is most likely data
+                _ if disassemble(opcode).starts_with("DW") => {},
+                _ => pending.push((next, i)),
+            }
+        }
+    }
+
+    /// Take note of what looks wrong about an instruction on its own.
+    fn check(&mut self, address: u16, opcode: u16, i: Option<u16>) {
+        let nnn = opcode & 0x0FFF;
+
+        if [0x1000, 0x2000, 0xB000].contains(&(opcode & 0xF000)) && nnn % 2 == 1 {
+            self.found.insert(Warning::OddJump { at: address, target: nnn });
+        }
+
+        // The bytes read through I, where it is known
+        let length = match opcode {
+            0xF002 => 16,
+            _ if opcode & 0xF000 == 0xD000 => opcode & 0x000F,
+            _ if opcode & 0xF0FF == 0xF065 => ((opcode & 0x0F00) >> 8) + 1,
+            _ => 0,
+        };
+        if let Some(i) = i {
+            let end = i as usize + length as usize;
+            if length > 0 && end > MEMORY {
+                self.found.insert(Warning::ReadPastEnd { at: address, end: end - 1 });
+            }
+        }
+
+        if let Some(quirk) = decode(opcode, false).and_then(|instruction| instruction.quirk) {
+            self.quirk_uses.entry(quirk).or_default().insert(address);
+        }
+    }
+
+    /// Turn what was found while tracing into warnings, adding the ones that need the whole
+    /// ROM to be known.
+    fn collect_warnings(&mut self, rom: &[u8]) {
+        let mut warnings: Vec<Warning> = std::mem::take(&mut self.found).into_iter().collect();
+
+        for &(address, sprite, height) in &self.draws {
+            let draws_code = (sprite..sprite + height).any(|byte| {
+                let offset = (byte as usize).wrapping_sub(PROGRAM_START);
+                self.regions.get(offset) == Some(&Region::Code)
+            });
+
+            if draws_code {
+                warnings.push(Warning::SpriteInCode { at: address, sprite });
+            }
+        }
+
+        // Bytes nothing refers to are only reported when they read as instructions, as
+        // data reached through a computed I is never referenced either
+        for (start, end, region) in self.regions() {
+            let looks_like_code = end > start && (start..end).step_by(2).all(|address| {
+                opcode_at(rom, address).map_or(false, |opcode| {
+                    let text = disassemble(opcode);
+                    !text.starts_with("DW") && !text.starts_with("SYS")
+                })
+            });
+
+            if region == Region::Unknown && looks_like_code {
+                warnings.push(Warning::UnreachableCode { start, end });
+            }
+        }
+
+        for (&quirk, uses) in &self.quirk_uses {
+            let at = *uses.iter().next().unwrap();
+            warnings.push(Warning::Quirk { at, quirk, count: uses.len() });
+        }
+
+        warnings.sort_by_key(Warning::address);
+        self.warnings = warnings;
+    }
+
+    /// Mark `length` bytes from `address`, where code takes precedence over sprites and
+    /// sprites over other data.
+    fn mark(&mut self, address: u16, length: u16, region: Region) {
+        let rank = |region| match region {
+            Region::Code => 3,
+            Region::Sprite => 2,
+            Region::Data => 1,
+            Region::Unknown => 0,
+        };
+
+        for address in address..address + length {
+            let offset = (address as usize).wrapping_sub(PROGRAM_START);
+
+            if let Some(current) = self.regions.get_mut(offset) {
+                if rank(region) > rank(*current) {
+                    *current = region;
+                }
+            }
+        }
+    }
+
+    /// Runs of bytes with the same use, as inclusive `(start, end, region)` addresses.
+    pub fn regions(&self) -> Vec<(u16, u16, Region)> {
+        let mut runs: Vec<(u16, u16, Region)> = Vec::new();
+
+        for (offset, &region) in self.regions.iter().enumerate() {
+            let address = (PROGRAM_START + offset) as u16;
+
+            match runs.last_mut() {
+                Some(run) if run.2 == region => run.1 = address,
+                _ => runs.push((address, address, region)),
+            }
+        }
+
+        runs
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{} bytes\n\nRegions:\n", self.regions.len());
+
+        for (start, end, region) in self.regions() {
+            text += &format!("  {:#05X}-{:#05X}  {}\n", start, end, region.name());
+        }
+
+        text += "\nSubroutines:\n";
+        for (entry, calls) in &self.subroutines {
+            text += &format!("  {:#05X}  calls {}\n", entry, address_list(calls));
+        }
+
+        text += "\nJump targets:\n";
+        for (target, sources) in &self.jump_targets {
+            text += &format!("  {:#05X}  from {}\n", target, address_list(sources));
+        }
+
+        if !self.warnings.is_empty() {
+            text += "\nWarnings:\n";
+            for warning in &self.warnings {
+                text += &format!("  warning: {}\n", warning);
+            }
+        }
+
+        text
+    }
+
+    /// The call graph in the DOT language of Graphviz.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph calls {\n");
+
+        for (entry, calls) in &self.subroutines {
+            dot += &format!("    \"{:#05X}\";\n", entry);
+
+            for call in calls {
+                dot += &format!("    \"{:#05X}\" -> \"{:#05X}\";\n", entry, call);
+            }
+        }
+
+        dot + "}\n"
+    }
+}
+
+fn opcode_at(rom: &[u8], address: u16) -> Option<u16> {
+    let offset = (address as usize).checked_sub(PROGRAM_START)?;
+
+    match (rom.get(offset), rom.get(offset + 1)) {
+        (Some(&high), Some(&low)) => Some((high as u16) << 8 | low as u16),
+        _ => None,
+    }
+}
+
+fn address_list(addresses: &BTreeSet<u16>) -> String {
+    if addresses.is_empty() {
+        return String::from("-");
+    }
+
+    let addresses: Vec<String> = addresses.iter().map(|a| format!("{:#05X}", a)).collect();
+    addresses.join(", ")
+}
+
+/// The `map` subcommand: print the memory map of a ROM as text, or its call graph as DOT.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut path = None;
+    let mut output = None;
+    let mut dot = false;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dot" => dot = true,
+            "-o" => output = Some(args.next().ok_or("-o requires a path")?),
+            _ => path = Some(arg),
+        }
+    }
+
+    let path = path.ok_or(USAGE)?;
+    let rom = read_rom(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    let map = MemoryMap::analyze(&rom);
+
+    let report = if dot { map.to_dot() } else { map.to_text() };
+
+    match output {
+        Some(output) => fs::write(output, report)
+            .map_err(|e| format!("Could not write {}: {}", output, e)),
+        None => {
+            print!("{}", report);
+            Ok(())
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0x200: LD I, 0x20C; CALL 0x208; JP 0x204
+    // 0x206: unreachable
+    // 0x208: DRW V0, V0, 2; RET
+    // 0x20C: sprite
+    const ROM: [u8; 14] = [
+        0xA2, 0x0C, 0x22, 0x08, 0x12, 0x04,
+        0xFF, 0xFF,
+        0xD0, 0x02, 0x00, 0xEE,
+        0xF0, 0x90,
+    ];
+
+    #[test]
+    fn test_regions() {
+        let map = MemoryMap::analyze(&ROM);
+
+        assert_eq!(map.regions(), vec!(
+            (0x200, 0x205, Region::Code),
+            (0x206, 0x207, Region::Unknown),
+            (0x208, 0x20B, Region::Code),
+            (0x20C, 0x20D, Region::Sprite),
+        ));
+    }
+
+    #[test]
+    fn test_call_graph() {
+        let map = MemoryMap::analyze(&ROM);
+
+        assert_eq!(map.subroutines[&0x200].iter().collect::<Vec<_>>(), vec!(&0x208));
+        assert!(map.subroutines[&0x208].is_empty());
+        assert_eq!(map.jump_targets[&0x204].iter().collect::<Vec<_>>(), vec!(&0x204));
+        assert!(map.to_dot().contains("\"0x200\" -> \"0x208\";"));
+    }
+
+    #[test]
+    fn test_warnings() {
+        // 0x200: LD I, 0x200; DRW V0, V0, 2; LD I, 0xFFE; LD V2, [I]; JP 0x20D
+        // 0x20A: unreachable LD V0, 1; ADD V0, 1
+        let rom = [
+            0xA2, 0x00, 0xD0, 0x02, 0xAF, 0xFE, 0xF2, 0x65, 0x12, 0x0D,
+            0x60, 0x01, 0x70, 0x01,
+        ];
+        let map = MemoryMap::analyze(&rom);
+
+        assert_eq!(map.warnings, vec!(
+            Warning::SpriteInCode { at: 0x202, sprite: 0x200 },
+            Warning::Quirk { at: 0x202, quirk: "display-wait", count: 1 },
+            Warning::ReadPastEnd { at: 0x206, end: 0x1000 },
+            Warning::OddJump { at: 0x208, target: 0x20D },
+            Warning::UnreachableCode { start: 0x20A, end: 0x20D },
+        ));
+        assert!(map.to_text().contains("warning: 0x208: jump to odd address 0x20D"));
+    }
+}
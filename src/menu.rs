@@ -0,0 +1,244 @@
+//! The pause menu, so the common actions do not require knowing the hotkeys.
+
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::filters::Filter;
+use crate::locale::{tr, Text};
+use crate::palette::Palette;
+use crate::rotation::Rotation;
+use crate::screen::{Buffer, Point};
+use crate::text::{CHAR_WIDTH, LINE_HEIGHT};
+
+const TITLE_COLOR: u32 = 0x808080;
+const ITEM_COLOR: u32 = 0xA0A0A0;
+const SELECTED_COLOR: u32 = 0xFFFFFF;
+const SELECTED_BACKGROUND: u32 = 0x304060;
+const ITEM_HEIGHT: usize = LINE_HEIGHT + 1;
+
+/// What the main loop should do after a menu item was chosen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Reset,
+    LoadRom,
+    /// Open the save state slots.
+    States,
+    /// Open the screen for testing the keys, timers, sound and display.
+    SetupTest,
+    CycleFilter,
+    /// Turn the display a quarter further.
+    CycleRotation,
+    /// Show the display in the next preset colours.
+    CyclePalette,
+    TogglePauseOnFocusLoss,
+    Quit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Item {
+    Resume,
+    Reset,
+    LoadRom,
+    States,
+    SetupTest,
+    Settings,
+    Quit,
+    Filter,
+    Rotation,
+    Palette,
+    PauseOnFocusLoss,
+    Back,
+}
+
+const MAIN_ITEMS: [Item; 7] = [Item::Resume, Item::Reset, Item::LoadRom, Item::States,
+    Item::SetupTest, Item::Settings, Item::Quit];
+const SETTINGS_ITEMS: [Item; 5] =
+    [Item::Filter, Item::Rotation, Item::Palette, Item::PauseOnFocusLoss, Item::Back];
+
+/// The settings shown in the menu, which the main loop owns.
+pub struct Settings {
+    pub filter: Filter,
+    pub rotation: Rotation,
+    pub palette: Palette,
+    pub pause_on_focus_loss: bool,
+}
+
+/// A menu opened with Escape, which also pauses the machine.
+///
+/// Up and Down or hovering with the mouse select an item, Enter or a click chooses it, and
+/// Escape closes the menu again.
+pub struct PauseMenu {
+    open: bool,
+    in_settings: bool,
+    selected: usize,
+    // Clicks are taken when the button is released over the item it was pressed on
+    mouse_was_down: bool,
+}
+
+impl PauseMenu {
+    pub fn new() -> PauseMenu {
+        PauseMenu {
+            open: false,
+            in_settings: false,
+            selected: 0,
+            mouse_was_down: false,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    fn items(&self) -> &'static [Item] {
+        if self.in_settings { &SETTINGS_ITEMS } else { &MAIN_ITEMS }
+    }
+
+    /// The item under a position in the menu's buffer.
+    fn item_at(&self, x: usize, y: usize, width: usize) -> Option<usize> {
+        let row = y.checked_sub(LINE_HEIGHT + 1)? / ITEM_HEIGHT;
+
+        if x < width && row < self.items().len() { Some(row) } else { None }
+    }
+
+    /// Handle the keyboard, and the mouse given as a position in the menu's buffer with the
+    /// state of the left button. Returns the action of a chosen item.
+    pub fn handle_input(
+            &mut self, window: &Window, mouse: Option<(usize, usize, bool)>, width: usize)
+            -> Option<Action> {
+
+        if window.is_key_pressed(Key::Escape, KeyRepeat::No) {
+            self.open = !self.open;
+            self.in_settings = false;
+            self.selected = 0;
+        }
+
+        if !self.open {
+            return None;
+        }
+
+        let count = self.items().len();
+        if window.is_key_pressed(Key::Up, KeyRepeat::Yes) {
+            self.selected = (self.selected + count - 1) % count;
+        }
+        if window.is_key_pressed(Key::Down, KeyRepeat::Yes) {
+            self.selected = (self.selected + 1) % count;
+        }
+
+        let mut chosen = window.is_key_pressed(Key::Enter, KeyRepeat::No);
+
+        if let Some((x, y, down)) = mouse {
+            if let Some(item) = self.item_at(x, y, width) {
+                self.selected = item;
+                chosen |= self.mouse_was_down && !down;
+            }
+            self.mouse_was_down = down;
+        }
+
+        if chosen { self.choose() } else { None }
+    }
+
+    fn choose(&mut self) -> Option<Action> {
+        let action = match self.items()[self.selected] {
+            Item::Resume => None,
+            Item::Reset => Some(Action::Reset),
+            Item::LoadRom => Some(Action::LoadRom),
+            Item::States => Some(Action::States),
+            Item::SetupTest => Some(Action::SetupTest),
+            Item::Quit => Some(Action::Quit),
+            Item::Settings | Item::Back => {
+                self.in_settings = !self.in_settings;
+                self.selected = 0;
+                return None;
+            },
+            // Settings are changed in place, so the menu stays open
+            Item::Filter => return Some(Action::CycleFilter),
+            Item::Rotation => return Some(Action::CycleRotation),
+            Item::Palette => return Some(Action::CyclePalette),
+            Item::PauseOnFocusLoss => return Some(Action::TogglePauseOnFocusLoss),
+        };
+
+        self.open = false;
+        action
+    }
+
+    pub fn render(&self, buffer: &mut Buffer, settings: &Settings) {
+        buffer.clear();
+
+        let title = if self.in_settings { Text::Settings } else { Text::Paused };
+        buffer.draw_text(tr(title), Point::new(0, 0), TITLE_COLOR);
+
+        for (idx, item) in self.items().iter().enumerate() {
+            let label = match item {
+                Item::Resume => String::from(tr(Text::Resume)),
+                Item::Reset => String::from(tr(Text::Reset)),
+                Item::LoadRom => String::from(tr(Text::LoadRom)),
+                Item::States => String::from(tr(Text::States)),
+                Item::SetupTest => String::from(tr(Text::SetupTest)),
+                Item::Settings => String::from(tr(Text::Settings)),
+                Item::Quit => String::from(tr(Text::Quit)),
+                Item::Filter => format!("{} {:?}", tr(Text::Filter), settings.filter)
+                    .to_uppercase(),
+                Item::Rotation => format!("{} {}", tr(Text::Rotate), settings.rotation),
+                Item::Palette => format!("{} {}", tr(Text::Colors), settings.palette.name()),
+                Item::PauseOnFocusLoss => format!("{} {}", tr(Text::FocusPause),
+                    tr(if settings.pause_on_focus_loss { Text::On } else { Text::Off })),
+                Item::Back => String::from(tr(Text::Back)),
+            };
+
+            let top = LINE_HEIGHT + 1 + idx * ITEM_HEIGHT;
+            let selected = idx == self.selected;
+
+            if selected {
+                for y in top..top + ITEM_HEIGHT {
+                    for x in 0..(label.len() + 1) * CHAR_WIDTH {
+                        buffer.set_pixel(x, y, SELECTED_BACKGROUND);
+                    }
+                }
+            }
+
+            let color = if selected { SELECTED_COLOR } else { ITEM_COLOR };
+            buffer.draw_text(&label, Point::new(CHAR_WIDTH / 2, top + 1), color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_submenu() {
+        let mut menu = PauseMenu::new();
+        menu.open = true;
+
+        menu.selected = 4;
+        assert_eq!(menu.choose(), Some(Action::SetupTest));
+        assert!(!menu.is_open());
+
+        menu.open = true;
+        menu.selected = 5;
+        assert_eq!(menu.choose(), None);
+        assert!(menu.in_settings && menu.is_open());
+
+        assert_eq!(menu.choose(), Some(Action::CycleFilter));
+        assert!(menu.is_open());
+
+        menu.selected = 2;
+        assert_eq!(menu.choose(), Some(Action::CyclePalette));
+
+        menu.selected = 4;
+        menu.choose();
+        menu.selected = 6;
+        assert_eq!(menu.choose(), Some(Action::Quit));
+        assert!(!menu.is_open());
+    }
+
+    #[test]
+    fn test_item_at() {
+        let menu = PauseMenu::new();
+
+        assert_eq!(menu.item_at(3, 2, 96), None);
+        assert_eq!(menu.item_at(3, LINE_HEIGHT + 1, 96), Some(0));
+        assert_eq!(menu.item_at(3, LINE_HEIGHT + 1 + 6 * ITEM_HEIGHT, 96), Some(6));
+        assert_eq!(menu.item_at(3, LINE_HEIGHT + 1 + 7 * ITEM_HEIGHT, 96), None);
+    }
+}
@@ -0,0 +1,286 @@
+//! Arithmetic and logic on the V registers.
+
+use crate::VF;
+use crate::logging::Target;
+use super::{Cpu, decode_register_x, decode_registers, decode_byte};
+
+/// (6xkk - LD Vx, byte)
+/// Set Vx = kk.
+///
+/// The interpreter puts the value kk into register Vx.
+pub fn ld_register_byte<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode) as usize;
+    let kk = decode_byte(opcode);
+
+    cpu.log(Target::Cpu, format_args!("Setting register V{:X?} to {:#X?}", v_x, kk));
+
+    cpu.set_register(v_x, kk);
+}
+
+/// (7xkk - ADD Vx, byte)
+/// Set Vx = Vx + kk.
+///
+/// Adds the value kk to the value of register Vx, then stores the result in Vx.
+pub fn add_register_byte<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode) as usize;
+    let kk = decode_byte(opcode);
+
+    let value = cpu.register(v_x);
+
+    cpu.log(Target::Cpu, format_args!("Adding value {:#X?} to V{:X?} ({:#X?})", kk, v_x, value));
+
+    cpu.set_register(v_x, value.wrapping_add(kk));
+}
+
+/// (5xy1 - CHIP-8X)
+/// Set Vx = Vx + Vy, adding each nibble separately, modulo 8.
+///
+/// Used by CHIP-8X ROMs to move colour zones, where both nibbles are a coordinate.
+pub fn add_nibbles<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let (v_x, v_y) = decode_registers(opcode);
+    let x = cpu.register(v_x as usize);
+    let y = cpu.register(v_y as usize);
+
+    let high = ((x >> 4) + (y >> 4)) % 8;
+    let low = ((x & 0xF) + (y & 0xF)) % 8;
+
+    cpu.set_register(v_x as usize, high << 4 | low);
+}
+
+/// (8xy0 - LD Vx, Vy)
+/// Set Vx = Vy.
+///
+/// Stores the value of register Vy in register Vx.
+pub fn ld_registers<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let (v_x, v_y) = decode_registers(opcode);
+    let y = cpu.register(v_y as usize);
+
+    cpu.set_register(v_x as usize, y);
+}
+
+/// (8xy1 - OR Vx, Vy)
+/// Set Vx = Vx OR Vy.
+///
+/// Performs a bitwise OR on the values of Vx and Vy, then stores the result in Vx.
+/// A bitwise OR compares the corrseponding bits from two values, and if either bit is 1,
+/// then the same bit in the result is also 1. Otherwise, it is 0.
+pub fn or_registers<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let (v_x, v_y) = decode_registers(opcode);
+    let x = cpu.register(v_x as usize);
+    let y = cpu.register(v_y as usize);
+
+    cpu.set_register(v_x as usize, x | y);
+    reset_flag(cpu);
+}
+
+/// (8xy2 - AND Vx, Vy)
+/// Set Vx = Vx AND Vy.
+///
+/// Performs a bitwise AND on the values of Vx and Vy, then stores the result in Vx.
+/// A bitwise AND compares the corrseponding bits from two values, and if both bits are 1,
+/// then the same bit in the result is also 1. Otherwise, it is 0.
+pub fn and_registers<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let (v_x, v_y) = decode_registers(opcode);
+
+    let x = cpu.register(v_x as usize);
+    let y = cpu.register(v_y as usize);
+
+    cpu.set_register(v_x as usize, x & y);
+    reset_flag(cpu);
+}
+
+/// (8xy3 - XOR Vx, Vy)
+/// Set Vx = Vx XOR Vy.
+///
+/// Performs a bitwise exclusive OR on the values of Vx and Vy, then stores the result
+/// in Vx. An exclusive OR compares the corrseponding bits from two values, and if
+/// the bits are not both the same, then the corresponding bit in the result is set to 1.
+/// Otherwise, it is 0.
+pub fn xor_registers<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let (v_x, v_y) = decode_registers(opcode);
+
+    let x = cpu.register(v_x as usize);
+    let y = cpu.register(v_y as usize);
+
+    cpu.set_register(v_x as usize, x ^ y);
+    reset_flag(cpu);
+}
+
+/// With the `vf_reset` quirk the logic instructions clear VF, like on the COSMAC VIP.
+fn reset_flag<C: Cpu>(cpu: &mut C) {
+    if cpu.quirks().vf_reset {
+        cpu.set_register(VF, 0);
+    }
+}
+
+/// (8xy4 - ADD Vx, Vy)
+/// Set Vx = Vx + Vy, set VF = carry.
+///
+/// The values of Vx and Vy are added together. If the result is greater than
+/// 8 bits (i.e., > 255,) VF is set to 1, otherwise 0. Only the lowest 8 bits
+/// of the result are kept, and stored in Vx.
+pub fn add_registers<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let (v_x, v_y) = decode_registers(opcode);
+
+    let x = cpu.register(v_x as usize);
+    let y = cpu.register(v_y as usize);
+
+    let (sum, overflow) = x.overflowing_add(y);
+
+    cpu.set_register(v_x as usize, sum);
+    cpu.set_register(VF, overflow as u8);
+}
+
+/// (8xy5 - SUB Vx, Vy)
+/// Set Vx = Vx - Vy, set VF = NOT borrow.
+///
+/// If Vx >= Vy, then VF is set to 1, otherwise 0. Then Vy is subtracted from Vx, and
+/// the results stored in Vx. The subtraction wraps around when it borrows.
+pub fn sub_registers<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let (v_x, v_y) = decode_registers(opcode);
+
+    let x = cpu.register(v_x as usize);
+    let y = cpu.register(v_y as usize);
+
+    cpu.set_register(v_x as usize, x.wrapping_sub(y));
+    cpu.set_register(VF, (x >= y) as u8);
+}
+
+/// (8xy6 - SHR Vx {, Vy})
+/// Set Vx = Vx SHR 1.
+///
+/// If the least-significant bit of Vx is 1, then VF is set to 1, otherwise 0. Then Vx
+/// is divided by 2.
+pub fn shr_registers<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode) as usize;
+
+    let x = cpu.register(v_x);
+
+    cpu.set_register(v_x, x >> 1);
+    cpu.set_register(VF, x & 0b00000001);
+}
+
+/// (8xy7 - SUBN Vx, Vy)
+/// Set Vx = Vy - Vx, set VF = NOT borrow.
+///
+/// If Vy >= Vx, then VF is set to 1, otherwise 0. Then Vx is subtracted from Vy, and
+/// the results stored in Vx. The subtraction wraps around when it borrows.
+pub fn subn_registers<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let (v_x, v_y) = decode_registers(opcode);
+
+    let x = cpu.register(v_x as usize);
+    let y = cpu.register(v_y as usize);
+
+    cpu.set_register(v_x as usize, y.wrapping_sub(x));
+    cpu.set_register(VF, (y >= x) as u8);
+}
+
+/// (8xyE - SHL Vx {, Vy})
+/// Set Vx = Vx SHL 1.
+///
+/// If the most-significant bit of Vx is 1, then VF is set to 1, otherwise to 0. Then Vx
+/// is multiplied by 2.
+pub fn shl_registers<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode) as usize;
+
+    let x = cpu.register(v_x);
+
+    cpu.set_register(v_x, x << 1);
+    cpu.set_register(VF, x >> 7);
+}
+
+/// (Cxkk - RND Vx, byte)
+/// Set Vx = random byte AND kk.
+///
+/// The interpreter generates a random number from 0 to 255, which is then ANDed with
+/// the value kk. The results are stored in Vx. See instruction 8xy2 for more information
+/// on AND.
+pub fn rnd<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let x = decode_register_x(opcode);
+    let kk = decode_byte(opcode);
+
+    let random = cpu.random_byte();
+    cpu.log(Target::Cpu, format_args!("Sample {}", random));
+
+    cpu.set_register(x as usize, random & kk);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    #[test]
+    fn test_sub_all_pairs() {
+        let mut chip8 = Chip8::new();
+
+        for x in 0..=255u8 {
+            for y in 0..=255u8 {
+                chip8.registers[1] = x;
+                chip8.registers[2] = y;
+                sub_registers(&mut chip8, 0x8125);
+
+                assert_eq!(chip8.registers[1], x.wrapping_sub(y));
+                assert_eq!(chip8.registers[VF], (x >= y) as u8);
+
+                chip8.registers[1] = x;
+                subn_registers(&mut chip8, 0x8127);
+
+                assert_eq!(chip8.registers[1], y.wrapping_sub(x));
+                assert_eq!(chip8.registers[VF], (y >= x) as u8);
+            }
+        }
+    }
+
+    #[test]
+    fn test_shift_all_values() {
+        let mut chip8 = Chip8::new();
+
+        for x in 0..=255u8 {
+            chip8.registers[1] = x;
+            shl_registers(&mut chip8, 0x812E);
+
+            assert_eq!(chip8.registers[1], x << 1);
+            assert_eq!(chip8.registers[VF], x >> 7);
+
+            chip8.registers[1] = x;
+            shr_registers(&mut chip8, 0x8126);
+
+            assert_eq!(chip8.registers[1], x >> 1);
+            assert_eq!(chip8.registers[VF], x & 1);
+        }
+
+        // The flag takes the place of the result in VF
+        chip8.registers[VF] = 0x81;
+        shl_registers(&mut chip8, 0x8FFE);
+        assert_eq!(chip8.registers[VF], 1);
+    }
+
+    #[test]
+    fn test_sub_same_register() {
+        let mut chip8 = Chip8::new();
+        chip8.registers[3] = 0x42;
+
+        sub_registers(&mut chip8, 0x8335);
+        assert_eq!(chip8.registers[3], 0);
+        assert_eq!(chip8.registers[VF], 1);
+
+        chip8.registers[3] = 0x42;
+        subn_registers(&mut chip8, 0x8337);
+        assert_eq!(chip8.registers[3], 0);
+        assert_eq!(chip8.registers[VF], 1);
+    }
+
+    #[test]
+    fn test_vf_reset_quirk() {
+        let mut chip8 = Chip8::new();
+        chip8.registers[VF] = 1;
+
+        or_registers(&mut chip8, 0x8011);
+        assert_eq!(chip8.registers[VF], 1);
+
+        chip8.quirks.vf_reset = true;
+        and_registers(&mut chip8, 0x8012);
+        assert_eq!(chip8.registers[VF], 0);
+    }
+}
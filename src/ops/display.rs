@@ -0,0 +1,203 @@
+//! Drawing to the display.
+
+use alloc::{format, string::String, vec::Vec};
+
+use crate::logging::Target;
+use super::{Cpu, decode_registers};
+
+/// (00E0 - CLS)
+/// Clear the display.
+pub fn cls_clear_display<C: Cpu>(cpu: &mut C, _opcode: u16) {
+    cpu.log(Target::Display, format_args!("Clear display"));
+    cpu.display().clear();
+}
+
+/// (00Cn - SCD n)
+/// Scroll the display down n pixels, from SUPER-CHIP.
+pub fn scd_scroll_down<C: Cpu>(cpu: &mut C, opcode: u16) {
+    cpu.display().scroll_down((opcode & 0x000F) as usize);
+}
+
+/// (00Dn - SCU n)
+/// Scroll the display up n pixels, from XO-CHIP.
+pub fn scu_scroll_up<C: Cpu>(cpu: &mut C, opcode: u16) {
+    cpu.display().scroll_up((opcode & 0x000F) as usize);
+}
+
+/// (00FB - SCR)
+/// Scroll the display right 4 pixels, from SUPER-CHIP.
+pub fn scr_scroll_right<C: Cpu>(cpu: &mut C, _opcode: u16) {
+    cpu.display().scroll_right(4);
+}
+
+/// (00FC - SCL)
+/// Scroll the display left 4 pixels, from SUPER-CHIP.
+pub fn scl_scroll_left<C: Cpu>(cpu: &mut C, _opcode: u16) {
+    cpu.display().scroll_left(4);
+}
+
+/// (Dxyn - DRW Vx, Vy, n)
+/// Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
+///
+/// The interpreter reads n bytes from memory, starting at the address stored in I.
+/// These bytes are then displayed as sprites on screen at coordinates (Vx, Vy).
+/// Sprites are XORed onto the existing screen. If this causes any pixels to be erased,
+/// VF is set to 1, otherwise it is set to 0. If the sprite is positioned so part of it
+/// is outside the coordinates of the display, it wraps around to the opposite side of
+/// the screen. See instruction 8xy3 for more information on XOR, and section 2.4,
+/// Display, for more information on the Chip-8 screen and sprites.
+///
+/// What n = 0 draws and whether the sprite is clipped at the edges instead depends on the
+/// variant, see `sprite_rules`.
+pub fn drw_draw_sprite<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let (v_x, v_y) = decode_registers(opcode);
+    let n = (opcode & 0x000F) as u8;
+
+    let x = cpu.register(v_x as usize) as usize;
+    let y = cpu.register(v_y as usize) as usize;
+
+    let rules = cpu.sprite_rules();
+    // The display only has the low resolution of CHIP-8
+    let (width, rows) = rules.shape(n);
+
+    let start = cpu.i() as usize;
+    let end = start + width * rows;
+
+    let read: Vec<u8> = (start..end).map(|address| cpu.read_memory(address)).collect();
+
+    cpu.log(Target::Display, format_args!("At position ({}, {}), draw:", x, y));
+    for bytes in read.chunks(width) {
+        let row: Vec<String> = bytes.iter().map(|byte| format!("{:08b}", byte)).collect();
+        cpu.log(Target::Display, format_args!("{}", row.concat()));
+    }
+
+    let collision = cpu.display().draw(x, y, &read, width, rules.edge);
+    cpu.set_register(0xF, collision as u8);
+}
+
+/// (02A0 - CHIP-8X)
+/// Cycle the background colour through blue, black, green and red.
+pub fn cycle_background<C: Cpu>(cpu: &mut C, _opcode: u16) {
+    if let Some(chip8x) = cpu.chip8x() {
+        chip8x.colors.cycle_background();
+    }
+
+    cpu.display().mark_dirty();
+}
+
+/// (Bxyn - CHIP-8X)
+/// Set the foreground colour of the zones given by Vx and Vx+1 to Vy.
+///
+/// Vx holds the zone columns, 8 pixels wide, as the first column in the low nibble and the
+/// number of columns after it in the high nibble. With n = 0, Vx+1 holds the rows the same
+/// way in zones of 4 pixel rows. Otherwise Vx+1 is the first pixel row and n rows are set.
+pub fn set_zone_color<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let (v_x, v_y) = decode_registers(opcode);
+    let n = (opcode & 0x000F) as u8;
+
+    let columns = cpu.register(v_x as usize);
+    let rows = cpu.register((v_x as usize + 1) & 0xF);
+    let color = cpu.register(v_y as usize);
+
+    cpu.log(Target::Display,
+        format_args!("Colour zones {:#X?} x {:#X?} ({}) with {}", columns, rows, n, color));
+
+    if let Some(chip8x) = cpu.chip8x() {
+        chip8x.colors.set_zones(columns, rows, n, color);
+    }
+
+    cpu.display().mark_dirty();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Chip8, WIDTH, HEIGHT};
+    use crate::variant::Variant;
+
+    #[test]
+    fn test_draw_sprite_collision() {
+        let mut chip8 = Chip8::new();
+        chip8.set_i(0x300);
+        chip8.write_memory(0x300, 0b1010_0000);
+
+        drw_draw_sprite(&mut chip8, 0xD011);
+        assert_eq!(chip8.register(0xF), 0);
+        assert_eq!(chip8.display().lit_count(), 2);
+
+        drw_draw_sprite(&mut chip8, 0xD011);
+        assert_eq!(chip8.register(0xF), 1);
+        assert_eq!(chip8.display().lit_count(), 0);
+    }
+
+    #[test]
+    fn test_scroll() {
+        let mut chip8 = Chip8::new();
+        chip8.display().draw_sprite(0, 0, &[0x80]);
+
+        // SCD 3; SCR; SCL; SCL; SCU 1
+        scd_scroll_down(&mut chip8, 0x00C3);
+        scr_scroll_right(&mut chip8, 0x00FB);
+        assert!(chip8.display().is_lit(4, 3));
+
+        scl_scroll_left(&mut chip8, 0x00FC);
+        scu_scroll_up(&mut chip8, 0x00D1);
+        assert!(chip8.display().is_lit(0, 2));
+
+        scl_scroll_left(&mut chip8, 0x00FC);
+        assert_eq!(chip8.display().lit_count(), 0);
+    }
+
+    #[test]
+    fn test_draw_sprite_past_end_of_memory() {
+        let mut chip8 = Chip8::new();
+        chip8.set_i(0xFFF);
+        chip8.write_memory(0xFFF, 0xFF);
+
+        drw_draw_sprite(&mut chip8, 0xD012);
+        assert_eq!(chip8.display().lit_count(), 8);
+        assert!(chip8.bounds_fault.is_some());
+    }
+
+    #[test]
+    fn test_draw_zero_height() {
+        let mut chip8 = Chip8::new();
+        chip8.set_i(0x300);
+        for address in 0x300..0x320 {
+            chip8.write_memory(address, 0xFF);
+        }
+
+        for &(variant, lit) in [(Variant::Chip8, 0), (Variant::CosmacVip, 0),
+                (Variant::Chip8X, 0), (Variant::SChip, 8 * 16), (Variant::XoChip, 16 * 16)].iter() {
+            chip8.display().clear();
+            chip8.sprite_rules = variant.sprite_rules();
+
+            drw_draw_sprite(&mut chip8, 0xD000);
+            assert_eq!(chip8.display().lit_count(), lit, "{}", variant.name());
+        }
+    }
+
+    #[test]
+    fn test_draw_past_the_edges() {
+        let mut chip8 = Chip8::new();
+        chip8.set_i(0x300);
+        for address in 0x300..0x320 {
+            chip8.write_memory(address, 0xFF);
+        }
+        // The bottom right corner, so a quarter of a 16x16 sprite is on the display
+        chip8.set_register(0, WIDTH as u8 - 8);
+        chip8.set_register(1, HEIGHT as u8 - 8);
+
+        for &(variant, lit) in [(Variant::Chip8, 8 * 15), (Variant::CosmacVip, 8 * 8),
+                (Variant::Chip8X, 8 * 8), (Variant::SChip, 8 * 8),
+                (Variant::XoChip, 16 * 16)].iter() {
+            chip8.display().clear();
+            chip8.sprite_rules = variant.sprite_rules();
+
+            let opcode = if variant == Variant::XoChip { 0xD010 } else { 0xD01F };
+            drw_draw_sprite(&mut chip8, opcode);
+            assert_eq!(chip8.display().lit_count(), lit, "{}", variant.name());
+            assert!(chip8.display().is_lit(WIDTH - 1, HEIGHT - 1), "{}", variant.name());
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Jumps, subroutines and conditional skips.
+
+use crate::logging::Target;
+use super::{Cpu, decode_register_x, decode_registers, decode_byte, decode_short};
+
+/// (0nnn - SYS addr)
+/// Jump to a machine code routine at nnn.
+///
+/// This instruction is only used on the old computers on which Chip-8 was originally implemented.
+/// It is ignored by modern interpreters.
+pub fn sys_jump_to_routine<C: Cpu>(cpu: &mut C, opcode: u16) {
+}
+
+/// (00EE - RET)
+/// Return from a subroutine.
+///
+/// The interpreter sets the program counter to the address at the top of the stack, then
+/// subtracts 1 from the stack pointer.
+pub fn ret_return_from_subroutine<C: Cpu>(cpu: &mut C, _opcode: u16) {
+    let address = cpu.pop();
+    cpu.set_pc(address);
+}
+
+/// (1nnn - JP addr)
+/// Jump to location nnn.
+///
+/// The interpreter sets the program counter to nnn.
+pub fn jp_jump_to_address<C: Cpu>(cpu: &mut C, opcode: u16) {
+    cpu.set_pc(opcode & 0x0FFF);
+    cpu.log(Target::Cpu, format_args!("Jump to location {:#X?}", cpu.pc()));
+}
+
+/// (2nnn - CALL addr)
+/// Call subroutine at nnn.
+///
+/// The interpreter increments the stack pointer, then puts the current PC on the top of
+/// the stack. The PC is then set to nnn.
+pub fn call_subroutine<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let subroutine = opcode & 0x0FFF;
+
+    cpu.log(Target::Cpu, format_args!("Add pc {:#X?} to stack, run subroutine at {:#X?}",
+        cpu.pc(), subroutine));
+
+    let pc = cpu.pc();
+    cpu.push(pc);
+    cpu.set_pc(subroutine);
+}
+
+/// (3xkk - SE Vx, byte)
+/// Skip next instruction if Vx = kk.
+///
+/// The interpreter compares register Vx to kk, and if they are equal, increments the program
+/// counter by 2.
+pub fn se_register_byte<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode) as usize;
+    let kk = decode_byte(opcode);
+
+    let x = cpu.register(v_x);
+
+    if x == kk {
+        skip(cpu);
+    }
+}
+
+/// (4xkk - SNE Vx, byte)
+/// Skip next instruction if Vx != kk.
+///
+/// The interpreter compares register Vx to kk, and if they are not equal, increments
+/// the program counter by 2.
+pub fn sne_skip_not_equal<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode) as usize;
+    let kk = decode_byte(opcode);
+
+    let x = cpu.register(v_x);
+
+    if x != kk {
+        skip(cpu);
+    }
+}
+
+/// (5xy0 - SE Vx, Vy)
+/// Skip next instruction if Vx = Vy.
+///
+/// The interpreter compares register Vx to register Vy, and if they are equal,
+/// increments the program counter by 2.
+pub fn se_registers<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let (v_x, v_y) = decode_registers(opcode);
+    let x = cpu.register(v_x as usize);
+    let y = cpu.register(v_y as usize);
+
+    if x == y {
+        skip(cpu);
+    }
+}
+
+/// (9xy0 - SNE Vx, Vy)
+/// Skip next instruction if Vx != Vy.
+///
+/// The values of Vx and Vy are compared, and if they are not equal, the program counter
+/// is increased by 2.
+pub fn sne_registers<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let (v_x, v_y) = decode_registers(opcode);
+
+    if cpu.register(v_x as usize) != cpu.register(v_y as usize) {
+        skip(cpu);
+    }
+}
+
+/// (Bnnn - JP V0, addr)
+/// Jump to location nnn + V0.
+///
+/// The program counter is set to nnn plus the value of V0.
+pub fn jp_bnnn<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let nnn = decode_short(opcode);
+    let v0 = cpu.register(0) as u16;
+
+    cpu.set_pc(nnn + v0);
+
+    cpu.log(Target::Cpu, format_args!("Set Program Counter to {:#X?}", cpu.pc()));
+}
+
+/// Skip the next instruction, also used by the key instructions.
+pub(super) fn skip<C: Cpu>(cpu: &mut C) {
+    let pc = cpu.pc();
+    cpu.set_pc(pc + 2);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+    use crate::error::Chip8Error;
+
+    #[test]
+    fn test_call_and_return() {
+        let mut chip8 = Chip8::new();
+        chip8.pc = 0x204;
+
+        call_subroutine(&mut chip8, 0x2300);
+        assert_eq!(chip8.pc, 0x300);
+
+        ret_return_from_subroutine(&mut chip8, 0x00EE);
+        assert_eq!(chip8.pc, 0x204);
+        assert_eq!(chip8.stack.len(), 0);
+    }
+
+    #[test]
+    fn test_fetch_past_end_of_memory() {
+        let mut chip8 = Chip8::new();
+        chip8.pc = 0xFFF;
+
+        chip8.cycle();
+        assert_eq!(chip8.pc, 0xFFF);
+        assert_eq!(chip8.bounds_fault, Some(Chip8Error::FetchOutOfBounds { pc: 0xFFF }));
+    }
+}
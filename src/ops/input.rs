@@ -0,0 +1,122 @@
+//! Reading the keypad.
+
+use crate::State;
+use crate::logging::Target;
+use super::{Cpu, decode_register_x};
+use super::flow::skip;
+
+/// (Ex9E - SKP Vx)
+/// Skip next instruction if key with the value of Vx is pressed.
+///
+/// Checks the keyboard, and if the key corresponding to the value of Vx is currently
+/// in the down position, PC is increased by 2.
+pub fn skp_skip_pressed<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode) as usize;
+    let key = cpu.register(v_x) as usize & 0xF;
+
+    if cpu.is_key_down(key) {
+        skip(cpu);
+    }
+}
+
+/// (ExA1 - SKNP Vx)
+/// Skip next instruction if key with the value of Vx is not pressed.
+///
+/// Checks the keyboard, and if the key corresponding to the value of Vx is currently in
+/// the up position, PC is increased by 2.
+pub fn sknp_skip_not_pressed<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode) as usize;
+    let key = cpu.register(v_x) as usize & 0xF;
+
+    if !cpu.is_key_down(key) {
+        skip(cpu);
+    }
+}
+
+/// (Fx0A - LD Vx, K)
+/// Wait for a key press, store the value of the key in Vx.
+///
+/// All execution stops until a key is pressed, then the value of that key is stored in Vx.
+/// The timers keep counting down while waiting. With the `key_release` quirk the key is
+/// only stored once it is released, like on the COSMAC VIP.
+pub fn ld_wait_for_key<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode) as usize;
+
+    cpu.log(Target::Input, format_args!("Waiting for key press to store in V{:X?}", v_x));
+
+    cpu.set_state(State::WaitingForKey(v_x));
+}
+
+/// (ExF2 - CHIP-8X)
+/// Skip next instruction if key Vx on the second keypad is pressed.
+pub fn skp_second_keypad<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode) as usize;
+    let key = cpu.register(v_x) as usize;
+
+    if cpu.chip8x().is_some_and(|chip8x| chip8x.is_key_down(key)) {
+        skip(cpu);
+    }
+}
+
+/// (ExF5 - CHIP-8X)
+/// Skip next instruction if key Vx on the second keypad is not pressed.
+pub fn sknp_second_keypad<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode) as usize;
+    let key = cpu.register(v_x) as usize;
+
+    if !cpu.chip8x().is_some_and(|chip8x| chip8x.is_key_down(key)) {
+        skip(cpu);
+    }
+}
+
+/// (FxF8 - CHIP-8X)
+/// Write Vx to the I/O port.
+pub fn out_port<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode) as usize;
+    let value = cpu.register(v_x);
+
+    cpu.log(Target::Input, format_args!("Writing {:#X?} to the I/O port", value));
+
+    if let Some(chip8x) = cpu.chip8x() {
+        chip8x.output = value;
+    }
+}
+
+/// (FxFB - CHIP-8X)
+/// Read the I/O port into Vx.
+///
+/// The interpreter waits for the input line to be raised. Nothing drives the port, so the
+/// byte on it is read right away.
+pub fn in_port<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode) as usize;
+    let value = cpu.chip8x().map_or(0, |chip8x| chip8x.input);
+
+    cpu.set_register(v_x, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    #[test]
+    fn test_ld_wait_for_key_release() {
+        let mut chip8 = Chip8::new();
+        chip8.quirks.key_release = true;
+
+        ld_wait_for_key(&mut chip8, 0xF30A);
+
+        let mut keys = [false; 16];
+        keys[0xB] = true;
+        chip8.set_keys(keys);
+
+        assert!(!chip8.wait_for_key());
+        assert_eq!(chip8.state, State::WaitingForRelease(3, 0xB));
+
+        chip8.set_keys([false; 16]);
+
+        assert!(!chip8.wait_for_key());
+        assert!(chip8.wait_for_key());
+        assert_eq!(chip8.registers[3], 0xB);
+    }
+}
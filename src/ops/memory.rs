@@ -0,0 +1,161 @@
+//! The index register and transfers between registers and memory.
+
+use crate::logging::Target;
+use super::{Cpu, decode_register_x};
+
+/// (Annn - LD I, addr)
+/// Set I = nnn.
+///
+/// The value of register I is set to nnn.
+pub fn ld_i_byte<C: Cpu>(cpu: &mut C, opcode: u16) {
+    cpu.set_i(opcode & 0x0FFF);
+
+    cpu.log(Target::Memory, format_args!("Set I to {:#X?}", cpu.i()));
+}
+
+/// (Fx1E - ADD I, Vx)
+/// Set I = I + Vx.
+///
+/// The values of I and Vx are added, and the results are stored in I.
+pub fn add_to_i<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode);
+
+    let i = cpu.i().wrapping_add(cpu.register(v_x as usize) as u16);
+    cpu.set_i(i);
+}
+
+/// (Fx29 - LD F, Vx)
+/// Set I = location of sprite for digit Vx.
+///
+/// The value of I is set to the location for the hexadecimal sprite corresponding to
+/// the value of Vx. See section 2.4, Display, for more information on
+/// the Chip-8 hexadecimal font.
+pub fn ld_i_to_sprite<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode);
+
+    // The font starts at address 0, five bytes a digit
+    let digit = cpu.register(v_x as usize) & 0xF;
+    cpu.set_i(digit as u16 * 5);
+}
+
+/// (Fx33 - LD B, Vx)
+/// Store BCD representation of Vx in memory locations I, I+1, and I+2.
+///
+/// The interpreter takes the decimal value of Vx, and places the hundreds digit in
+/// memory at location in I, the tens digit at location I+1, and the ones digit at
+/// location I+2.
+pub fn ld_bcd<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode) as usize;
+    let x = cpu.register(v_x);
+    let i = cpu.i() as usize;
+
+    let hundreds = x / 100;
+    let tens = x / 10 % 10;
+    let ones = x % 10;
+
+    cpu.log(Target::Memory, format_args!("{}", x));
+    cpu.log(Target::Memory, format_args!("{}, {}, {}", hundreds, tens, ones));
+
+    cpu.write_memory(i, hundreds);
+    cpu.write_memory(i + 1, tens);
+    cpu.write_memory(i + 2, ones);
+}
+
+/// (Fx55 - LD [I], Vx)
+/// Store registers V0 through Vx in memory starting at location I.
+///
+/// The interpreter copies the values of registers V0 through Vx into memory, starting
+/// at the address in I.
+pub fn ld_store_registers<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode);
+    let i = cpu.i() as usize;
+
+    for register in 0..=v_x as usize {
+        cpu.log(Target::Memory, format_args!("{}, {}", i + register, register));
+
+        let value = cpu.register(register);
+        cpu.write_memory(i + register, value);
+    }
+}
+
+/// (Fx65 - LD Vx, [I])
+/// Read registers V0 through Vx from memory starting at location I.
+///
+/// The interpreter reads values from memory starting at location I into registers
+/// V0 through Vx.
+pub fn ld_read_registers<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode);
+    let i = cpu.i() as usize;
+
+    for register in 0..=v_x as usize {
+        let value = cpu.read_memory(i + register);
+        cpu.set_register(register, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+    use crate::error::Chip8Error;
+
+    #[test]
+    fn test_protected_memory_write() {
+        let mut chip8 = Chip8::new();
+        chip8.protect_memory = true;
+        chip8.registers[0] = 123;
+
+        chip8.i = 0x100;
+        ld_bcd(&mut chip8, 0xF033);
+
+        assert_eq!(&chip8.memory[0x100..0x103], &[0, 0, 0]);
+        assert_eq!(chip8.protection_fault, Some(0x100));
+
+        chip8.i = 0x300;
+        ld_bcd(&mut chip8, 0xF033);
+
+        assert_eq!(&chip8.memory[0x300..0x303], &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_store_and_read_registers() {
+        let mut chip8 = Chip8::new();
+        chip8.registers[..3].copy_from_slice(&[1, 2, 3]);
+
+        chip8.i = 0x300;
+        ld_store_registers(&mut chip8, 0xF255);
+        chip8.registers = [0; 16];
+        ld_read_registers(&mut chip8, 0xF165);
+
+        assert_eq!(&chip8.registers[..3], &[1, 2, 0]);
+        assert_eq!(chip8.i, 0x300);
+    }
+
+    #[test]
+    fn test_index_arithmetic() {
+        let mut chip8 = Chip8::new();
+        chip8.registers[3] = 0x1A;
+
+        chip8.i = 0x300;
+        add_to_i(&mut chip8, 0xF31E);
+        assert_eq!(chip8.i, 0x31A);
+
+        // The low nibble picks the digit, A is the eleventh
+        ld_i_to_sprite(&mut chip8, 0xF329);
+        assert_eq!(chip8.i, 50);
+        assert_eq!(&chip8.memory[50..55], &[0xF0, 0x90, 0xF0, 0x90, 0x90]);
+    }
+
+    #[test]
+    fn test_write_past_end_of_memory() {
+        let mut chip8 = Chip8::new();
+        chip8.registers[0] = 123;
+
+        chip8.i = 0xFFE;
+        ld_bcd(&mut chip8, 0xF033);
+
+        assert_eq!(&chip8.memory[0xFFE..], &[1, 2]);
+        assert_eq!(chip8.bounds_fault,
+            Some(Chip8Error::WriteOutOfBounds { pc: 0x200, address: 0x1000 }));
+    }
+}
@@ -0,0 +1,104 @@
+//! The CHIP-8 instructions, grouped by what they operate on.
+//!
+//! Every instruction is a function taking the machine and the opcode. They are written
+//! against the `Cpu` trait rather than `Chip8`, so a variant can reuse the instructions and
+//! override how selected parts of the machine behave.
+
+use core::fmt;
+
+use crate::State;
+use crate::chip8x::Chip8X;
+use crate::quirks::Quirks;
+use crate::sprite_rules::SpriteRules;
+use crate::display::Display;
+use crate::logging::Target;
+
+pub mod alu;
+pub mod display;
+pub mod flow;
+pub mod input;
+pub mod memory;
+pub mod timers;
+
+pub use self::alu::*;
+pub use self::display::*;
+pub use self::flow::*;
+pub use self::input::*;
+pub use self::memory::*;
+pub use self::timers::*;
+
+/// The machine state the instructions operate on.
+pub trait Cpu {
+    fn register(&self, x: usize) -> u8;
+    fn set_register(&mut self, x: usize, value: u8);
+
+    fn pc(&self) -> u16;
+    fn set_pc(&mut self, pc: u16);
+    fn i(&self) -> u16;
+    fn set_i(&mut self, i: u16);
+
+    /// Push an address on the stack, for CALL.
+    fn push(&mut self, address: u16);
+    /// Pop the address on top of the stack, for RET.
+    fn pop(&mut self) -> u16;
+
+    fn read_memory(&mut self, address: usize) -> u8;
+    fn write_memory(&mut self, address: usize, value: u8);
+
+    fn display(&mut self) -> &mut Display;
+
+    fn is_key_down(&self, key: usize) -> bool;
+    fn set_state(&mut self, state: State);
+
+    fn delay_timer(&self) -> u8;
+    fn set_delay_timer(&mut self, value: u8);
+    fn set_sound_timer(&mut self, value: u8);
+    fn set_audio_pattern(&mut self, pattern: [u8; 16]);
+    fn set_pitch(&mut self, pitch: u8);
+
+    fn random_byte(&mut self) -> u8;
+    fn quirks(&self) -> Quirks;
+    fn sprite_rules(&self) -> SpriteRules;
+    /// The hardware of CHIP-8X, when emulating it.
+    fn chip8x(&mut self) -> Option<&mut Chip8X>;
+
+    /// Report what an instruction did at debug level, for debugging.
+    fn log(&mut self, target: Target, message: fmt::Arguments);
+}
+
+fn decode_register_x(opcode: u16) -> u8 {
+    let v_x = (opcode & 0x0F00) >> 8;
+
+    v_x as u8
+}
+
+fn decode_register_y(opcode: u16) -> u8 {
+    let v_y = (opcode & 0x00F0) >> 4;
+
+    v_y as u8
+}
+
+fn decode_registers(opcode: u16) -> (u8, u8) {
+    (decode_register_x(opcode), decode_register_y(opcode))
+}
+
+fn decode_byte(opcode: u16) -> u8 {
+    (opcode & 0x00FF) as u8
+}
+
+fn decode_short(opcode: u16) -> u16 {
+    (opcode & 0x0FFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_short() {
+        let result = decode_short(0xABCD);
+        let expected = 0xBCD;
+
+        assert_eq!(result, expected);
+    }
+}
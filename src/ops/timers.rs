@@ -0,0 +1,63 @@
+//! The delay and sound timers, and the XO-CHIP audio they drive.
+
+use crate::MEMORY;
+use super::{Cpu, decode_register_x};
+
+/// (Fx07 - LD Vx, DT)
+/// Set Vx = delay timer value.
+///
+/// The value of DT is placed into Vx.
+pub fn ld_get_delay_timer<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode);
+
+    let delay_timer = cpu.delay_timer();
+    cpu.set_register(v_x as usize, delay_timer);
+}
+
+/// (Fx15 - LD DT, Vx)
+/// Set delay timer = Vx.
+///
+/// DT is set equal to the value of Vx.
+pub fn ld_set_delay_timer<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode);
+
+    let value = cpu.register(v_x as usize);
+    cpu.set_delay_timer(value);
+}
+
+/// (Fx18 - LD ST, Vx)
+/// Set sound timer = Vx.
+///
+/// ST is set equal to the value of Vx.
+pub fn ld_set_sound_timer<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode);
+
+    let value = cpu.register(v_x as usize);
+    cpu.set_sound_timer(value);
+}
+
+/// (F002 - AUDIO)
+/// Load the XO-CHIP audio pattern from memory locations I through I+15.
+///
+/// The 16 bytes form a 128-bit pattern that is played instead of the buzzer tone while
+/// the sound timer is running.
+pub fn ld_audio_pattern<C: Cpu>(cpu: &mut C, _opcode: u16) {
+    let mut pattern = [0; 16];
+
+    for (idx, byte) in pattern.iter_mut().enumerate() {
+        *byte = cpu.read_memory((cpu.i() as usize + idx) % MEMORY);
+    }
+
+    cpu.set_audio_pattern(pattern);
+}
+
+/// (Fx3A - PITCH Vx)
+/// Set the XO-CHIP audio pattern playback rate from Vx.
+///
+/// The pattern is played at 4000 * 2^((Vx - 64) / 48) bits per second.
+pub fn ld_pitch<C: Cpu>(cpu: &mut C, opcode: u16) {
+    let v_x = decode_register_x(opcode);
+
+    let pitch = cpu.register(v_x as usize);
+    cpu.set_pitch(pitch);
+}
@@ -0,0 +1,162 @@
+//! The colours the display is shown in, chosen per ROM.
+//!
+//! Lit pixels are drawn in the foreground colour and unlit ones in the background colour.
+//! The settings menu cycles through a few presets, and the colours of a ROM are kept in its
+//! metadata directory as two hex colours, so any others can be filled in by hand:
+//!
+//! ```text
+//! FFB000 1A1000
+//! ```
+
+use std::{fmt, fs, io, path::{Path, PathBuf}, str::FromStr};
+
+use crate::display::LIT;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub foreground: u32,
+    pub background: u32,
+}
+
+const PRESETS: [(&str, Palette); 5] = [
+    ("DEFAULT", Palette::DEFAULT),
+    ("WHITE", Palette { foreground: 0xFFFFFF, background: 0x000000 }),
+    ("AMBER", Palette { foreground: 0xFFB000, background: 0x1A1000 }),
+    ("GREEN", Palette { foreground: 0x33FF66, background: 0x001A08 }),
+    ("LCD", Palette { foreground: 0x0F380F, background: 0x9BBC0F }),
+];
+
+impl Palette {
+    /// The colours the display is drawn in without a palette.
+    pub const DEFAULT: Palette = Palette { foreground: LIT, background: 0 };
+
+    /// The name of the preset, or CUSTOM for colours filled in by hand.
+    pub fn name(self) -> &'static str {
+        PRESETS.iter()
+            .find(|(_, preset)| *preset == self)
+            .map_or("CUSTOM", |(name, _)| name)
+    }
+
+    /// The next preset, for cycling through them from the menu. Custom colours are followed
+    /// by the first preset.
+    pub fn next(self) -> Palette {
+        let position = PRESETS.iter().position(|(_, preset)| *preset == self);
+
+        match position {
+            Some(idx) => PRESETS[(idx + 1) % PRESETS.len()].1,
+            None => PRESETS[0].1,
+        }
+    }
+
+    /// The colour to show for a pixel of the game buffer. The display is drawn in shades of
+    /// `LIT`, which are mixed from the background to the foreground. Other colours, such as
+    /// those of overlays, are left as they are.
+    pub fn color(self, pixel: u32) -> u32 {
+        if self == Palette::DEFAULT || pixel & !LIT != 0 {
+            return pixel;
+        }
+
+        let shade = pixel & LIT;
+        let mix = |shift: u32| {
+            let background = (self.background >> shift & 0xFF) as i32;
+            let foreground = (self.foreground >> shift & 0xFF) as i32;
+
+            let mixed = background + (foreground - background) * shade as i32 / LIT as i32;
+
+            (mixed as u32) << shift
+        };
+
+        mix(16) | mix(8) | mix(0)
+    }
+
+    fn path(dir: &Path, rom_name: &str) -> PathBuf {
+        dir.join(format!("{}.colors", rom_name))
+    }
+
+    /// The colours stored for a ROM, the default ones if none were chosen.
+    pub fn load(dir: &Path, rom_name: &str) -> Palette {
+        fs::read_to_string(Palette::path(dir, rom_name)).ok()
+            .and_then(|contents| contents.trim().parse().ok())
+            .unwrap_or(Palette::DEFAULT)
+    }
+
+    pub fn store(self, dir: &Path, rom_name: &str) -> io::Result<()> {
+        fs::write(Palette::path(dir, rom_name), format!("{}\n", self))
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::DEFAULT
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Palette, String> {
+        let colors = s.split_whitespace()
+            .map(|color| {
+                u32::from_str_radix(color.trim_start_matches('#'), 16).ok()
+                    .filter(|&color| color <= 0xFFFFFF)
+                    .ok_or(format!("Invalid colour '{}'", color))
+            })
+            .collect::<Result<Vec<u32>, String>>()?;
+
+        match colors.as_slice() {
+            &[foreground, background] => Ok(Palette { foreground, background }),
+            _ => Err(format!("Expected a foreground and a background colour, got '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:06X} {:06X}", self.foreground, self.background)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_color() {
+        let amber = Palette { foreground: 0xFFB000, background: 0x1A1000 };
+
+        assert_eq!(amber.color(LIT), 0xFFB000);
+        assert_eq!(amber.color(0), 0x1A1000);
+        // A dimmed pixel is mixed halfway
+        assert_eq!(amber.color(LIT >> 1), 0x8C5F00);
+        assert_eq!(amber.color(0xFFFFFF), 0xFFFFFF);
+        assert_eq!(Palette::DEFAULT.color(0x7F), 0x7F);
+    }
+
+    #[test]
+    fn test_cycle() {
+        assert_eq!(Palette::DEFAULT.name(), "DEFAULT");
+        assert_eq!(Palette::DEFAULT.next().name(), "WHITE");
+        assert_eq!(PRESETS[PRESETS.len() - 1].1.next(), Palette::DEFAULT);
+
+        let custom = Palette { foreground: 0x123456, background: 0 };
+        assert_eq!(custom.name(), "CUSTOM");
+        assert_eq!(custom.next(), Palette::DEFAULT);
+    }
+
+    #[test]
+    fn test_parse_and_store() {
+        assert_eq!("#FFB000 1a1000".parse(), Ok(Palette { foreground: 0xFFB000,
+            background: 0x1A1000 }));
+        assert!("FFB000".parse::<Palette>().is_err());
+        assert!("1000000 0".parse::<Palette>().is_err());
+
+        let dir = env::temp_dir();
+        let lcd = PRESETS[4].1;
+        lcd.store(&dir, "palette_test").unwrap();
+        assert_eq!(Palette::load(&dir, "palette_test"), lcd);
+        assert_eq!(Palette::load(&dir, "palette_test_missing"), Palette::DEFAULT);
+
+        fs::remove_file(Palette::path(&dir, "palette_test")).unwrap();
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::{Arc, Mutex};
+
+use minifb::Window;
+
+use crate::Chip8;
+use crate::screen::{Buffer, Point};
+use crate::symbols::SymbolTable;
+use crate::text::LINE_HEIGHT;
+use super::Panel;
+
+const DEPTH_COLOR: u32 = 0x808080;
+const FRAME_COLOR: u32 = 0xE0E0E0;
+const CURRENT_COLOR: u32 = 0xFFFFFF;
+
+/// A subroutine call on the stack.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Call {
+    /// The address of the CALL.
+    site: u16,
+    /// The subroutine called, unknown for calls that were on the stack before they could
+    /// be seen, such as after loading a save state.
+    target: Option<u16>,
+}
+
+/// The calls on the stack, kept alongside the return addresses of the machine.
+#[derive(Debug, Default)]
+struct ShadowStack {
+    calls: Vec<Call>,
+}
+
+impl ShadowStack {
+    /// Follow the stack after an instruction executed. The return address of a call is the
+    /// instruction after the CALL.
+    fn record(&mut self, chip8: &Chip8, address: u16, opcode: u16) {
+        let entries = chip8.stack.entries();
+        let called = opcode & 0xF000 == 0x2000 && entries.last() == Some(&(address + 2));
+
+        // The calls are kept as long as they match the return addresses. The rest appeared
+        // without a CALL, such as after a reset or when a state was restored.
+        let kept = self.calls.iter().zip(entries)
+            .take_while(|(call, &entry)| call.site + 2 == entry)
+            .count();
+        self.calls.truncate(kept);
+
+        for (idx, &entry) in entries.iter().enumerate().skip(kept) {
+            let target = if called && idx == entries.len() - 1 {
+                Some(opcode & 0x0FFF)
+            } else {
+                None
+            };
+            self.calls.push(Call { site: entry.wrapping_sub(2), target });
+        }
+    }
+}
+
+/// An address with the closest label before it from the symbol file, such as `DRAW+4`.
+fn describe(symbols: &SymbolTable, address: u16) -> String {
+    match symbols.label_before(address) {
+        Some((label, 0)) => label.to_string(),
+        Some((label, offset)) => format!("{}+{}", label, offset),
+        None => format!("{:03X}", address),
+    }
+}
+
+/// The backtrace of the subroutine calls that led to the current instruction, innermost
+/// first, with the labels from the symbol file when one is loaded.
+///
+/// Every call is shown with its depth, the address of the CALL and the subroutine it
+/// called. The calls are followed by a hook, also while the panel is hidden.
+pub struct CallStackPanel {
+    stack: Arc<Mutex<ShadowStack>>,
+    symbols: SymbolTable,
+}
+
+impl CallStackPanel {
+    pub fn new(symbols: SymbolTable, chip8: &mut Chip8) -> CallStackPanel {
+        let stack = Arc::new(Mutex::new(ShadowStack::default()));
+
+        let recorder = stack.clone();
+        chip8.on_post_cycle(move |chip8, address, opcode| {
+            recorder.lock().unwrap().record(chip8, address, opcode);
+        });
+
+        CallStackPanel { stack, symbols }
+    }
+
+    /// The lines of the backtrace, the current instruction first.
+    fn lines(&self, chip8: &Chip8) -> Vec<(String, u32)> {
+        let calls = &self.stack.lock().unwrap().calls;
+        let mut lines = vec!((format!("{:>2} {:03X} {}", calls.len(), chip8.pc,
+            describe(&self.symbols, chip8.pc)), CURRENT_COLOR));
+
+        for (depth, call) in calls.iter().enumerate().rev() {
+            let target = call.target.map_or(String::from("?"), |target| {
+                describe(&self.symbols, target)
+            });
+            lines.push((format!("{:>2} {:03X} {} > {}", depth, call.site,
+                describe(&self.symbols, call.site), target), FRAME_COLOR));
+        }
+
+        lines
+    }
+}
+
+impl Panel for CallStackPanel {
+    fn name(&self) -> &'static str {
+        "calls"
+    }
+
+    fn handle_input(&mut self, _window: &Window) {}
+
+    fn render(&self, chip8: &Chip8, buffer: &mut Buffer) {
+        buffer.clear();
+
+        buffer.draw_text("CALLS", Point::new(0, 0), DEPTH_COLOR);
+        let rows = buffer.height() / LINE_HEIGHT - 1;
+        for (idx, (line, color)) in self.lines(chip8).iter().take(rows).enumerate() {
+            buffer.draw_text(line, Point::new(0, (idx + 1) * LINE_HEIGHT), *color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(chip8: &mut Chip8, cycles: usize) {
+        for _ in 0..cycles {
+            chip8.cycle();
+        }
+    }
+
+    #[test]
+    fn test_backtrace() {
+        let symbols = SymbolTable::parse("label main 0x200\nlabel draw 0x204\nlabel dot 0x20A\n")
+            .unwrap();
+        let mut chip8 = Chip8::new();
+        // CALL draw; JP main; draw: CALL dot; dot: RET
+        chip8.load_bytes(&[0x22, 0x04, 0x12, 0x00, 0x22, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0xEE]);
+        let panel = CallStackPanel::new(symbols, &mut chip8);
+
+        run(&mut chip8, 2);
+        assert_eq!(panel.stack.lock().unwrap().calls, vec!(
+            Call { site: 0x200, target: Some(0x204) },
+            Call { site: 0x204, target: Some(0x20A) },
+        ));
+        assert_eq!(panel.lines(&chip8).iter().map(|(line, _)| line.as_str()).collect::<Vec<_>>(),
+            vec!(" 2 20A dot", " 1 204 draw > dot", " 0 200 main > draw"));
+
+        run(&mut chip8, 1);
+        assert_eq!(panel.stack.lock().unwrap().calls.len(), 1);
+
+        // Return addresses that were not seen being pushed
+        chip8.reset();
+        chip8.stack.set_entries(&[0x302]);
+        run(&mut chip8, 1);
+        assert_eq!(panel.stack.lock().unwrap().calls, vec!(
+            Call { site: 0x300, target: None },
+            Call { site: 0x200, target: Some(0x204) },
+        ));
+    }
+}
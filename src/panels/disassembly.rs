@@ -0,0 +1,110 @@
+use minifb::Window;
+
+use crate::Chip8;
+use crate::screen::{Buffer, Point};
+use crate::symbols::SymbolTable;
+use crate::text::LINE_HEIGHT;
+use super::Panel;
+
+const CURRENT_COLOR: u32 = 0xFFFFFF;
+const CODE_COLOR: u32 = 0xA0A0A0;
+const LABEL_COLOR: u32 = 0x80C0FF;
+const COMMENT_COLOR: u32 = 0x60A060;
+// Opcodes the interpreter cannot execute
+const UNKNOWN_COLOR: u32 = 0xC06060;
+// Instructions shown before the one at the program counter
+const CONTEXT: u16 = 2;
+
+/// The instructions around the program counter, with the labels and comments from the
+/// symbol file when one is loaded. Opcodes the interpreter cannot execute are shown in red.
+pub struct DisassemblyPanel {
+    symbols: SymbolTable,
+}
+
+impl DisassemblyPanel {
+    pub fn new(symbols: SymbolTable) -> DisassemblyPanel {
+        DisassemblyPanel { symbols }
+    }
+
+    /// The lines to show for the instructions starting at `start`, with their colours.
+    pub fn lines(&self, chip8: &Chip8, start: u16, rows: usize) -> Vec<(String, u32)> {
+        let mut lines = Vec::new();
+
+        for (address, decoded) in chip8.instructions_at(start) {
+            if lines.len() >= rows {
+                break;
+            }
+
+            if let Some(label) = self.symbols.label_at(address) {
+                lines.push((format!("{}:", label), LABEL_COLOR));
+            }
+
+            let (marker, color) = match decoded.instruction {
+                _ if address == chip8.pc => ('>', CURRENT_COLOR),
+                Some(_) => (' ', CODE_COLOR),
+                None => (' ', UNKNOWN_COLOR),
+            };
+            lines.push((format!("{}{:03X} {}", marker, address, decoded.disassemble()), color));
+
+            if let Some(comment) = self.symbols.line_at(address).and_then(|line| line.comment()) {
+                lines.push((format!("  ;{}", comment), COMMENT_COLOR));
+            }
+        }
+
+        lines.truncate(rows);
+        lines
+    }
+}
+
+impl Panel for DisassemblyPanel {
+    fn name(&self) -> &'static str {
+        "disassembly"
+    }
+
+    fn handle_input(&mut self, _window: &Window) {}
+
+    fn render(&self, chip8: &Chip8, buffer: &mut Buffer) {
+        buffer.clear();
+
+        let start = chip8.pc.saturating_sub(2 * CONTEXT);
+        let rows = buffer.height() / LINE_HEIGHT;
+
+        for (idx, (line, color)) in self.lines(chip8, start, rows).iter().enumerate() {
+            buffer.draw_text(line, Point::new(0, idx * LINE_HEIGHT), *color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines_with_symbols() {
+        let symbols = SymbolTable::parse("label main 0x200\nline 0x200 1 CLS ; start\n").unwrap();
+        let panel = DisassemblyPanel::new(symbols);
+
+        let mut chip8 = Chip8::new();
+        chip8.load_bytes(&[0x00, 0xE0, 0x12, 0x00]);
+
+        let lines: Vec<String> = panel.lines(&chip8, 0x200, 4).into_iter()
+            .map(|(line, _)| line)
+            .collect();
+
+        assert_eq!(lines, vec!("main:", ">200 CLS", "  ;start", " 202 JP 0x200"));
+    }
+
+    #[test]
+    fn test_unknown_opcodes_are_marked() {
+        let panel = DisassemblyPanel::new(SymbolTable::default());
+
+        let mut chip8 = Chip8::new();
+        chip8.load_bytes(&[0x00, 0xE0, 0x80, 0x08]);
+
+        let colors: Vec<u32> = panel.lines(&chip8, 0x200, 2).into_iter()
+            .map(|(_, color)| color)
+            .collect();
+
+        assert_eq!(colors, vec!(CURRENT_COLOR, UNKNOWN_COLOR));
+    }
+}
@@ -0,0 +1,265 @@
+use std::{collections::VecDeque, sync::{Arc, Mutex}, time::Duration};
+
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::{Chip8, HEIGHT, WIDTH};
+use crate::screen::{Buffer, Point};
+use crate::sprite_rules::Edge;
+use crate::text::{CHAR_WIDTH, LINE_HEIGHT};
+use super::Panel;
+
+const LABEL_COLOR: u32 = 0x808080;
+const VALUE_COLOR: u32 = 0xE0E0E0;
+const PIXEL_COLOR: u32 = 0x606060;
+// Size in pixels of the regions of the display that drawing is counted in
+const REGION_SIZE: usize = 8;
+const REGION_COLUMNS: usize = WIDTH / REGION_SIZE;
+const REGIONS: usize = REGION_COLUMNS * (HEIGHT / REGION_SIZE);
+// Number of frames the rates are taken over, about a second
+const HISTORY: usize = 60;
+// Share of the drawing in a region kept from one frame to the next, as in the heatmap
+const DECAY: f32 = 0.95;
+
+/// The sprites drawn in a frame.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct FrameDraws {
+    sprites: u32,
+    rows: u32,
+    collisions: u32,
+}
+
+struct DrawStats {
+    current: FrameDraws,
+    // The draws and host time of the most recent frames, oldest first
+    frames: VecDeque<(FrameDraws, Duration)>,
+    // How much was drawn in every region of the display recently
+    regions: [f32; REGIONS],
+    // The instruction about to execute is a DRW
+    drawing: bool,
+}
+
+impl DrawStats {
+    fn new() -> DrawStats {
+        DrawStats {
+            current: FrameDraws::default(),
+            frames: VecDeque::with_capacity(HISTORY),
+            regions: [0.0; REGIONS],
+            drawing: false,
+        }
+    }
+
+    /// Count the sprite of the instruction about to execute, if it draws one. Its position
+    /// is taken before VF is overwritten by the collision.
+    fn record_sprite(&mut self, chip8: &Chip8) {
+        let opcode = match chip8.try_fetch() {
+            Ok(opcode) if opcode & 0xF000 == 0xD000 => opcode,
+            _ => return,
+        };
+
+        let rules = chip8.sprite_rules;
+        let (width, rows) = rules.shape((opcode & 0xF) as u8);
+        let x = chip8.registers[(opcode >> 8 & 0xF) as usize] as usize % WIDTH;
+        let y = chip8.registers[(opcode >> 4 & 0xF) as usize] as usize % HEIGHT;
+
+        self.current.sprites += 1;
+        self.current.rows += rows as u32;
+        self.drawing = true;
+
+        let mut touched = [false; REGIONS];
+        for row in 0..rows {
+            for column in 0..8 * width {
+                let (px, py) = (x + column, y + row);
+                if rules.edge == Edge::Clip && (px >= WIDTH || py >= HEIGHT) {
+                    continue;
+                }
+
+                let (px, py) = (px % WIDTH, py % HEIGHT);
+                touched[px / REGION_SIZE + py / REGION_SIZE * REGION_COLUMNS] = true;
+            }
+        }
+        for (region, _) in self.regions.iter_mut().zip(touched.iter()).filter(|(_, &t)| t) {
+            *region += 1.0;
+        }
+    }
+
+    /// Count the collision of the sprite just drawn.
+    fn record_collision(&mut self, chip8: &Chip8) {
+        if self.drawing && chip8.registers[0xF] == 1 {
+            self.current.collisions += 1;
+        }
+        self.drawing = false;
+    }
+
+    fn end_frame(&mut self, time: Duration) {
+        if self.frames.len() == HISTORY {
+            self.frames.pop_front();
+        }
+        self.frames.push_back((self.current, time));
+        self.current = FrameDraws::default();
+
+        self.regions.iter_mut().for_each(|region| *region *= DECAY);
+    }
+
+    /// The draws of the recent frames together, and the time they took.
+    fn totals(&self) -> (FrameDraws, Duration) {
+        self.frames.iter().fold((FrameDraws::default(), Duration::from_secs(0)),
+            |(total, total_time), (draws, time)| (FrameDraws {
+                sprites: total.sprites + draws.sprites,
+                rows: total.rows + draws.rows,
+                collisions: total.collisions + draws.collisions,
+            }, total_time + *time))
+    }
+
+    fn sprites_per_second(&self) -> f32 {
+        let (total, time) = self.totals();
+        if time == Duration::from_secs(0) {
+            0.0
+        } else {
+            total.sprites as f32 / time.as_secs_f32()
+        }
+    }
+
+    fn average_height(&self) -> f32 {
+        let (total, _) = self.totals();
+        if total.sprites == 0 { 0.0 } else { total.rows as f32 / total.sprites as f32 }
+    }
+
+    /// The share of sprites that erased a pixel, in percent.
+    fn collision_rate(&self) -> f32 {
+        let (total, _) = self.totals();
+        if total.sprites == 0 {
+            0.0
+        } else {
+            total.collisions as f32 * 100.0 / total.sprites as f32
+        }
+    }
+
+    /// The top left corner of the region drawn in most recently, if any.
+    fn hottest_region(&self) -> Option<(usize, usize)> {
+        let (region, &heat) = self.regions.iter().enumerate()
+            .fold((0, &0.0), |hottest, region| {
+                if region.1 > hottest.1 { region } else { hottest }
+            });
+
+        if heat > 0.0 {
+            Some((region % REGION_COLUMNS * REGION_SIZE, region / REGION_COLUMNS * REGION_SIZE))
+        } else {
+            None
+        }
+    }
+}
+
+/// Draw statistics for optimizing games: the sprites drawn per second, their average
+/// height, how many of them collide, and the regions of the display drawn in the most.
+///
+/// Below the numbers, the display is shown with the recent drawing per region of 8x8
+/// pixels as a heatmap over it, which H toggles. The sprites are counted by cycle hooks,
+/// also while the panel is hidden.
+pub struct DrawStatsPanel {
+    stats: Arc<Mutex<DrawStats>>,
+    show_heat: bool,
+}
+
+impl DrawStatsPanel {
+    pub fn new(chip8: &mut Chip8) -> DrawStatsPanel {
+        let stats = Arc::new(Mutex::new(DrawStats::new()));
+
+        let recorder = stats.clone();
+        chip8.on_pre_cycle(move |chip8| recorder.lock().unwrap().record_sprite(chip8));
+        let recorder = stats.clone();
+        chip8.on_post_cycle(move |chip8, _, _| recorder.lock().unwrap().record_collision(chip8));
+
+        DrawStatsPanel { stats, show_heat: true }
+    }
+}
+
+impl Panel for DrawStatsPanel {
+    fn name(&self) -> &'static str {
+        "draws"
+    }
+
+    fn handle_input(&mut self, window: &Window) {
+        if window.is_key_pressed(Key::H, KeyRepeat::No) {
+            self.show_heat = !self.show_heat;
+        }
+    }
+
+    fn record_frame(&mut self, _instructions: u32, time: Duration) {
+        self.stats.lock().unwrap().end_frame(time);
+    }
+
+    fn render(&self, chip8: &Chip8, buffer: &mut Buffer) {
+        buffer.clear();
+
+        let stats = self.stats.lock().unwrap();
+        let hottest = match stats.hottest_region() {
+            Some((x, y)) => format!("{},{}", x, y),
+            None => String::from("-"),
+        };
+        let lines = [
+            ("SPR/S", format!("{:.0}", stats.sprites_per_second())),
+            ("HEIGHT", format!("{:.1}", stats.average_height())),
+            ("HIT %", format!("{:.0}", stats.collision_rate())),
+            ("HOT", hottest),
+        ];
+        for (idx, (label, value)) in lines.iter().enumerate() {
+            buffer.draw_text(label, Point::new(0, idx * LINE_HEIGHT), LABEL_COLOR);
+            buffer.draw_text(value, Point::new(7 * CHAR_WIDTH, idx * LINE_HEIGHT), VALUE_COLOR);
+        }
+
+        let top = lines.len() * LINE_HEIGHT + 1;
+        let max = stats.regions.iter().cloned().fold(0.0, f32::max);
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let lit = if chip8.display.is_lit(x, y) { PIXEL_COLOR } else { 0 };
+                let region = x / REGION_SIZE + y / REGION_SIZE * REGION_COLUMNS;
+                let heat = if self.show_heat && max > 0.0 {
+                    ((stats.regions[region] / max).sqrt() * 160.0) as u32
+                } else {
+                    0
+                };
+
+                buffer.set_pixel(x, top + y, heat << 16 | lit);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::run_frame;
+
+    #[test]
+    fn test_record() {
+        let mut chip8 = Chip8::new();
+        // LD V0, 60; LD I, 0x210; DRW V0, V1, 3; DRW V0, V1, 3; DRW V0, V1, 3
+        chip8.load_bytes(&[0x60, 0x3C, 0xA2, 0x10, 0xD0, 0x13, 0xD0, 0x13, 0xD0, 0x13, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF]);
+        let mut panel = DrawStatsPanel::new(&mut chip8);
+
+        run_frame(&mut chip8, 5);
+        panel.record_frame(5, Duration::from_millis(500));
+
+        let stats = panel.stats.lock().unwrap();
+        assert_eq!(stats.frames[0].0, FrameDraws { sprites: 3, rows: 9, collisions: 1 });
+        assert_eq!(stats.sprites_per_second(), 6.0);
+        assert_eq!(stats.average_height(), 3.0);
+        assert_eq!(stats.collision_rate() as u32, 33);
+
+        // The sprite at x = 60 wraps around to the left edge
+        assert_eq!(stats.regions[7], 3.0 * DECAY);
+        assert_eq!(stats.regions[0], 3.0 * DECAY);
+        assert_eq!(stats.regions[1], 0.0);
+        assert_eq!(stats.hottest_region(), Some((0, 0)));
+    }
+
+    #[test]
+    fn test_empty() {
+        let stats = DrawStats::new();
+        assert_eq!(stats.sprites_per_second(), 0.0);
+        assert_eq!(stats.average_height(), 0.0);
+        assert_eq!(stats.collision_rate(), 0.0);
+        assert_eq!(stats.hottest_region(), None);
+    }
+}
@@ -0,0 +1,171 @@
+use std::{sync::{Arc, Mutex}, time::Duration};
+
+use minifb::Window;
+
+use crate::{MEMORY, Chip8};
+use crate::screen::{Buffer, Point};
+use crate::text::LINE_HEIGHT;
+use super::Panel;
+
+const LABEL_COLOR: u32 = 0x808080;
+const READ_COLOR: u32 = 0x4060FF;
+const WRITE_COLOR: u32 = 0xFF4040;
+const EXECUTE_COLOR: u32 = 0x40FF40;
+// Bytes per row of the map, which makes memory a square of 64x64 pixels
+const COLUMNS: usize = 64;
+// Share of the accesses kept from one frame to the next, so the map shows the last second
+// or so
+const DECAY: f32 = 0.95;
+
+/// How often every byte of memory was accessed recently.
+struct Heat {
+    read: Vec<f32>,
+    written: Vec<f32>,
+    executed: Vec<f32>,
+}
+
+impl Heat {
+    fn new() -> Heat {
+        Heat {
+            read: vec!(0.0; MEMORY),
+            written: vec!(0.0; MEMORY),
+            executed: vec!(0.0; MEMORY),
+        }
+    }
+
+    /// Count the accesses of the instruction about to execute.
+    fn record(&mut self, chip8: &Chip8) {
+        let pc = chip8.pc as usize;
+        let opcode = match chip8.try_fetch() {
+            Ok(opcode) => opcode,
+            Err(_) => return,
+        };
+
+        count(&mut self.executed, pc, 2);
+
+        let i = chip8.i as usize;
+        let x = (opcode >> 8 & 0xF) as usize;
+        match opcode & 0xF0FF {
+            0xF033 => count(&mut self.written, i, 3),
+            0xF055 => count(&mut self.written, i, x + 1),
+            0xF065 => count(&mut self.read, i, x + 1),
+            _ if opcode & 0xF000 == 0xD000 => count(&mut self.read, i, (opcode & 0xF) as usize),
+            _ => {},
+        }
+    }
+
+    fn decay(&mut self) {
+        for counts in [&mut self.read, &mut self.written, &mut self.executed].iter_mut() {
+            counts.iter_mut().for_each(|count| *count *= DECAY);
+        }
+    }
+}
+
+fn count(counts: &mut [f32], start: usize, length: usize) {
+    for address in start..(start + length).min(MEMORY) {
+        counts[address] += 1.0;
+    }
+}
+
+/// The brightness of a channel for a count, relative to the highest count of its kind. The
+/// square root keeps rarely touched bytes visible next to a hot loop.
+fn intensity(count: f32, max: f32) -> u32 {
+    if max > 0.0 { ((count / max).sqrt() * 255.0) as u32 } else { 0 }
+}
+
+/// Memory as a 64x64 map with a pixel per byte, coloured by how often the byte was read,
+/// written and executed recently. Code, data and hot loops stand out at a glance.
+///
+/// The accesses are counted by a pre-cycle hook, also while the panel is hidden.
+pub struct HeatmapPanel {
+    heat: Arc<Mutex<Heat>>,
+}
+
+impl HeatmapPanel {
+    pub fn new(chip8: &mut Chip8) -> HeatmapPanel {
+        let heat = Arc::new(Mutex::new(Heat::new()));
+
+        let recorder = heat.clone();
+        chip8.on_pre_cycle(move |chip8| recorder.lock().unwrap().record(chip8));
+
+        HeatmapPanel { heat }
+    }
+}
+
+impl Panel for HeatmapPanel {
+    fn name(&self) -> &'static str {
+        "heatmap"
+    }
+
+    fn handle_input(&mut self, _window: &Window) {}
+
+    fn record_frame(&mut self, _instructions: u32, _time: Duration) {
+        self.heat.lock().unwrap().decay();
+    }
+
+    fn render(&self, _chip8: &Chip8, buffer: &mut Buffer) {
+        buffer.clear();
+
+        let heat = self.heat.lock().unwrap();
+        let max = |counts: &[f32]| counts.iter().cloned().fold(0.0, f32::max);
+        let (max_read, max_written, max_executed) =
+            (max(&heat.read), max(&heat.written), max(&heat.executed));
+
+        for address in 0..MEMORY {
+            let color = intensity(heat.written[address], max_written) << 16
+                | intensity(heat.executed[address], max_executed) << 8
+                | intensity(heat.read[address], max_read);
+
+            buffer.set_pixel(address % COLUMNS, address / COLUMNS, color);
+        }
+
+        let left = COLUMNS + 2;
+        buffer.draw_text("READ", Point::new(left, 0), READ_COLOR);
+        buffer.draw_text("WRITE", Point::new(left, LINE_HEIGHT), WRITE_COLOR);
+        buffer.draw_text("EXEC", Point::new(left, 2 * LINE_HEIGHT), EXECUTE_COLOR);
+
+        let hottest = heat.executed.iter().enumerate()
+            .filter(|&(_, &count)| count > 0.0)
+            .fold(None, |hottest: Option<(usize, f32)>, (address, &count)| match hottest {
+                Some((_, most)) if most >= count => hottest,
+                _ => Some((address, count)),
+            });
+        if let Some((address, _)) = hottest {
+            buffer.draw_text("HOT", Point::new(left, 4 * LINE_HEIGHT), LABEL_COLOR);
+            buffer.draw_text(&format!("{:03X}", address), Point::new(left, 5 * LINE_HEIGHT),
+                EXECUTE_COLOR);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::run_frame;
+
+    #[test]
+    fn test_record() {
+        let mut chip8 = Chip8::new();
+        // LD I, 0x300; LD B, V0; DRW V0, V0, 2; LD V1, [I]
+        chip8.load_bytes(&[0xA3, 0x00, 0xF0, 0x33, 0xD0, 0x02, 0xF1, 0x65]);
+        let mut panel = HeatmapPanel::new(&mut chip8);
+
+        run_frame(&mut chip8, 4);
+        panel.record_frame(4, Duration::from_millis(16));
+
+        let heat = panel.heat.lock().unwrap();
+        assert_eq!(heat.executed[0x200..0x209].iter().filter(|&&count| count > 0.0).count(), 8);
+        assert!(heat.written[0x300..0x303].iter().all(|&count| count == DECAY));
+        assert_eq!(heat.written[0x303], 0.0);
+        assert_eq!(heat.read[0x300], 2.0 * DECAY);
+        assert_eq!(heat.read[0x301], 2.0 * DECAY);
+        assert_eq!(heat.read[0x302], 0.0);
+    }
+
+    #[test]
+    fn test_intensity() {
+        assert_eq!(intensity(0.0, 0.0), 0);
+        assert_eq!(intensity(4.0, 4.0), 255);
+        assert_eq!(intensity(1.0, 4.0), 127);
+    }
+}
@@ -0,0 +1,70 @@
+use minifb::Window;
+
+use crate::Chip8;
+use minifb::Key;
+
+use crate::layout::{host_key_label, KEYPAD};
+use crate::screen::{Buffer, Point};
+use crate::text::{CHAR_WIDTH, LINE_HEIGHT};
+use super::Panel;
+
+const PRESSED_COLOR: u32 = 0xFFFFFF;
+const RELEASED_COLOR: u32 = 0x606060;
+const PRESSED_BACKGROUND: u32 = 0x304060;
+// The keys while the ROM waits for one to be pressed
+const WAITING_COLOR: u32 = 0xC0A040;
+const CELL_WIDTH: usize = 4 * CHAR_WIDTH;
+const CELL_HEIGHT: usize = 2 * LINE_HEIGHT + 2;
+
+/// Shows the 4x4 keypad with the keys that are currently held down highlighted.
+///
+/// Each key lists its CHIP-8 value above the host key it is mapped to. While the ROM waits
+/// for a key the keys that are up are highlighted as well.
+pub struct KeypadPanel {
+    keymap: [Key; 16],
+}
+
+impl KeypadPanel {
+    pub fn new(keymap: [Key; 16]) -> KeypadPanel {
+        KeypadPanel { keymap }
+    }
+}
+
+impl Panel for KeypadPanel {
+    fn name(&self) -> &'static str {
+        "keypad"
+    }
+
+    fn handle_input(&mut self, _window: &Window) {}
+
+    fn render(&self, chip8: &Chip8, buffer: &mut Buffer) {
+        buffer.clear();
+        let waiting = chip8.is_waiting_for_key();
+        let released_color = if waiting { WAITING_COLOR } else { RELEASED_COLOR };
+        buffer.draw_text(if waiting { "KEYPAD: PRESS A KEY" } else { "KEYPAD" },
+            Point::new(0, 0), released_color);
+
+        for (row, keys) in KEYPAD.iter().enumerate() {
+            for (column, &key) in keys.iter().enumerate() {
+                let left = column * CELL_WIDTH;
+                let top = LINE_HEIGHT + row * CELL_HEIGHT;
+                let pressed = chip8.keypad.is_down(key);
+
+                if pressed {
+                    for y in top..top + CELL_HEIGHT - 1 {
+                        for x in left..left + CELL_WIDTH - 1 {
+                            buffer.set_pixel(x, y, PRESSED_BACKGROUND);
+                        }
+                    }
+                }
+
+                let color = if pressed { PRESSED_COLOR } else { released_color };
+                let label = Point::new(left + 1, top + 1);
+                let host = Point::new(left + 1 + CHAR_WIDTH, top + 1 + LINE_HEIGHT);
+
+                buffer.draw_text(&format!("{:X}", key), label, color);
+                buffer.draw_text(&host_key_label(self.keymap[key]), host, color);
+            }
+        }
+    }
+}
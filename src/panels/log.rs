@@ -0,0 +1,141 @@
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::Chip8;
+use crate::screen::{Buffer, Point};
+use crate::text::{CHAR_WIDTH, LINE_HEIGHT};
+use crate::trace::{EventKind, TraceBuffer};
+use super::Panel;
+
+const ENABLED_COLOR: u32 = 0xFFFFFF;
+const DISABLED_COLOR: u32 = 0x404040;
+
+/// Which kinds of events are shown in the log.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Filter {
+    pub draws: bool,
+    pub jumps: bool,
+    pub keypad: bool,
+    pub timers: bool,
+}
+
+impl Filter {
+    pub fn shows(&self, kind: EventKind) -> bool {
+        match kind {
+            EventKind::Draw => self.draws,
+            EventKind::Jump => self.jumps,
+            EventKind::Keypad => self.keypad,
+            EventKind::TimerWrite => self.timers,
+            EventKind::Other => false,
+        }
+    }
+}
+
+/// A scrollable log of recently executed draw, jump, keypad and timer instructions.
+///
+/// F6-F9 toggle draws, jumps, keypad and timer writes, PageUp/PageDown scroll through
+/// the history and End jumps back to the newest events.
+pub struct LogPanel {
+    filter: Filter,
+    // Number of lines scrolled back from the newest event
+    scroll: usize,
+}
+
+impl LogPanel {
+    pub fn new() -> LogPanel {
+        LogPanel {
+            filter: Filter { draws: true, jumps: true, keypad: true, timers: true },
+            scroll: 0,
+        }
+    }
+
+    /// The log lines that fit in `rows`, oldest first.
+    pub fn lines(&self, trace: &TraceBuffer, rows: usize) -> Vec<String> {
+        let mut lines: Vec<String> = trace.iter()
+            .rev()
+            .filter(|event| self.filter.shows(event.kind()))
+            .skip(self.scroll)
+            .take(rows)
+            .map(|event| event.describe())
+            .collect();
+
+        lines.reverse();
+        lines
+    }
+}
+
+impl Panel for LogPanel {
+    fn name(&self) -> &'static str {
+        "log"
+    }
+
+    fn handle_input(&mut self, window: &Window) {
+        if window.is_key_pressed(Key::F6, KeyRepeat::No) {
+            self.filter.draws = !self.filter.draws;
+        }
+        if window.is_key_pressed(Key::F7, KeyRepeat::No) {
+            self.filter.jumps = !self.filter.jumps;
+        }
+        if window.is_key_pressed(Key::F8, KeyRepeat::No) {
+            self.filter.keypad = !self.filter.keypad;
+        }
+        if window.is_key_pressed(Key::F9, KeyRepeat::No) {
+            self.filter.timers = !self.filter.timers;
+        }
+
+        if window.is_key_pressed(Key::PageUp, KeyRepeat::Yes) {
+            self.scroll += 1;
+        }
+        if window.is_key_pressed(Key::PageDown, KeyRepeat::Yes) {
+            self.scroll = self.scroll.saturating_sub(1);
+        }
+        if window.is_key_pressed(Key::End, KeyRepeat::No) {
+            self.scroll = 0;
+        }
+    }
+
+    fn render(&self, chip8: &Chip8, buffer: &mut Buffer) {
+        buffer.clear();
+
+        // Header showing the enabled filters
+        let toggles = [
+            ("D", self.filter.draws),
+            ("J", self.filter.jumps),
+            ("K", self.filter.keypad),
+            ("T", self.filter.timers),
+        ];
+
+        for (idx, (label, enabled)) in toggles.iter().enumerate() {
+            let color = if *enabled { ENABLED_COLOR } else { DISABLED_COLOR };
+            buffer.draw_text(label, Point::new(idx * CHAR_WIDTH * 2, 0), color);
+        }
+
+        if self.scroll > 0 {
+            let label = format!("-{}", self.scroll);
+            buffer.draw_text(&label, Point::new(CHAR_WIDTH * 8, 0), DISABLED_COLOR);
+        }
+
+        let rows = (buffer.height() / LINE_HEIGHT).saturating_sub(1);
+
+        for (idx, line) in self.lines(&chip8.trace, rows).iter().enumerate() {
+            buffer.draw_text(line, Point::new(0, (idx + 1) * LINE_HEIGHT), ENABLED_COLOR);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines_filtered() {
+        let mut trace = TraceBuffer::new(16);
+        trace.record(0x200, 0xD125);
+        trace.record(0x202, 0x6001);
+        trace.record(0x204, 0x1200);
+
+        let mut panel = LogPanel::new();
+        panel.filter.jumps = false;
+
+        assert_eq!(panel.lines(&trace, 4), vec!("200 DRW V1, V2, 5"));
+    }
+}
@@ -0,0 +1,127 @@
+//! Views rendered into the debug area next to the game display.
+
+mod calls;
+mod disassembly;
+mod draws;
+mod heatmap;
+mod keypad;
+mod log;
+mod performance;
+mod sprites;
+mod watches;
+
+use std::time::Duration;
+
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::Chip8;
+use crate::debugger::expression::Watch;
+use crate::screen::Buffer;
+use crate::symbols::SymbolTable;
+
+pub use self::calls::CallStackPanel;
+pub use self::disassembly::DisassemblyPanel;
+pub use self::draws::DrawStatsPanel;
+pub use self::heatmap::HeatmapPanel;
+pub use self::keypad::KeypadPanel;
+pub use self::log::LogPanel;
+pub use self::performance::PerformancePanel;
+pub use self::sprites::SpritePanel;
+pub use self::watches::WatchPanel;
+
+/// A view of the machine state that can be shown in the debug area.
+pub trait Panel {
+    /// The name of the panel in debugger sessions, see `debug_session`.
+    fn name(&self) -> &'static str;
+
+    /// React to keys pressed while this panel is shown.
+    fn handle_input(&mut self, window: &Window);
+
+    /// Take note of a frame the main loop ran, with the instructions executed in it and the
+    /// host time it took.
+    fn record_frame(&mut self, _instructions: u32, _time: Duration) {}
+
+    /// The address a panel that views memory is scrolled to.
+    fn position(&self) -> Option<usize> {
+        None
+    }
+
+    fn set_position(&mut self, _position: usize) {}
+
+    fn render(&self, chip8: &Chip8, buffer: &mut Buffer);
+}
+
+/// All debug panels, of which one is shown at a time. Tab switches to the next panel.
+pub struct Panels {
+    panels: Vec<Box<dyn Panel>>,
+    active: usize,
+}
+
+impl Panels {
+    /// The panels for a machine, some of which instrument it with hooks.
+    pub fn new(symbols: SymbolTable, keymap: [Key; 16], watches: Vec<Watch>, chip8: &mut Chip8)
+            -> Panels {
+        Panels {
+            panels: vec!(
+                Box::new(LogPanel::new()),
+                Box::new(SpritePanel::new()),
+                Box::new(KeypadPanel::new(keymap)),
+                Box::new(DisassemblyPanel::new(symbols.clone())),
+                Box::new(CallStackPanel::new(symbols, chip8)),
+                Box::new(WatchPanel::new(watches)),
+                Box::new(PerformancePanel::new()),
+                Box::new(DrawStatsPanel::new(chip8)),
+                Box::new(HeatmapPanel::new(chip8)),
+            ),
+            active: 0,
+        }
+    }
+
+    pub fn handle_input(&mut self, window: &Window) {
+        if window.is_key_pressed(Key::Tab, KeyRepeat::No) {
+            self.active = (self.active + 1) % self.panels.len();
+        }
+
+        self.panels[self.active].handle_input(window);
+    }
+
+    /// Let every panel, also the hidden ones, take note of a frame.
+    pub fn record_frame(&mut self, instructions: u32, time: Duration) {
+        for panel in self.panels.iter_mut() {
+            panel.record_frame(instructions, time);
+        }
+    }
+
+    pub fn render(&self, chip8: &Chip8, buffer: &mut Buffer) {
+        self.panels[self.active].render(chip8, buffer);
+    }
+
+    /// The name of the panel shown.
+    pub fn active(&self) -> &'static str {
+        self.panels[self.active].name()
+    }
+
+    /// Show the panel with the name, returning whether there is one.
+    pub fn show(&mut self, name: &str) -> bool {
+        match self.panels.iter().position(|panel| panel.name() == name) {
+            Some(idx) => {
+                self.active = idx;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// The addresses the panels that view memory are scrolled to, by panel name.
+    pub fn positions(&self) -> Vec<(&'static str, usize)> {
+        self.panels.iter()
+            .filter_map(|panel| panel.position().map(|position| (panel.name(), position)))
+            .collect()
+    }
+
+    pub fn set_position(&mut self, name: &str, position: usize) {
+        if let Some(panel) = self.panels.iter_mut().find(|panel| panel.name() == name) {
+            panel.set_position(position);
+        }
+    }
+}
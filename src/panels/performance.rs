@@ -0,0 +1,151 @@
+use std::{collections::VecDeque, time::Duration};
+
+use minifb::Window;
+
+use crate::Chip8;
+use crate::screen::{Buffer, Point};
+use crate::text::LINE_HEIGHT;
+use super::Panel;
+
+const LABEL_COLOR: u32 = 0x808080;
+const IPS_COLOR: u32 = 0x60C060;
+const FPS_COLOR: u32 = 0x6080E0;
+const FRAME_TIME_COLOR: u32 = 0xE0A040;
+const GRAPH_BACKGROUND: u32 = 0x181818;
+const GRAPH_HEIGHT: usize = 14;
+// Number of frames kept, enough to fill the width of the debug area
+const HISTORY: usize = 96;
+
+/// Rolling graphs of the instructions per second, frames per second and host frame time.
+///
+/// Every graph is scaled to its highest value in view, which is shown next to the latest
+/// one, so the effect of the speed hotkeys and stutter on the host stand out.
+pub struct PerformancePanel {
+    // Instructions run and host time taken by the most recent frames, oldest first
+    frames: VecDeque<(u32, Duration)>,
+}
+
+impl PerformancePanel {
+    pub fn new() -> PerformancePanel {
+        PerformancePanel {
+            frames: VecDeque::with_capacity(HISTORY),
+        }
+    }
+
+    fn instructions_per_second(&self) -> Vec<f32> {
+        self.frames.iter().map(|&(instructions, time)| per_second(instructions, time)).collect()
+    }
+
+    fn frames_per_second(&self) -> Vec<f32> {
+        self.frames.iter().map(|&(_, time)| per_second(1, time)).collect()
+    }
+
+    fn frame_times(&self) -> Vec<f32> {
+        self.frames.iter().map(|(_, time)| time.as_secs_f32() * 1000.0).collect()
+    }
+}
+
+fn per_second(count: u32, time: Duration) -> f32 {
+    if time == Duration::from_secs(0) { 0.0 } else { count as f32 / time.as_secs_f32() }
+}
+
+/// Draw the samples as bars from the bottom up, the newest on the right.
+fn draw_graph(buffer: &mut Buffer, samples: &[f32], top: usize, color: u32) {
+    let width = buffer.width();
+    let max = samples.iter().cloned().fold(0.0, f32::max);
+
+    for y in top..top + GRAPH_HEIGHT {
+        for x in 0..width {
+            buffer.set_pixel(x, y, GRAPH_BACKGROUND);
+        }
+    }
+
+    let visible = &samples[samples.len().saturating_sub(width)..];
+    let left = width - visible.len();
+
+    for (idx, &sample) in visible.iter().enumerate() {
+        let height = if max > 0.0 {
+            (sample / max * GRAPH_HEIGHT as f32).round() as usize
+        } else {
+            0
+        };
+
+        for y in top + GRAPH_HEIGHT - height..top + GRAPH_HEIGHT {
+            buffer.set_pixel(left + idx, y, color);
+        }
+    }
+}
+
+impl Panel for PerformancePanel {
+    fn name(&self) -> &'static str {
+        "performance"
+    }
+
+    fn handle_input(&mut self, _window: &Window) {}
+
+    fn record_frame(&mut self, instructions: u32, time: Duration) {
+        if self.frames.len() == HISTORY {
+            self.frames.pop_front();
+        }
+
+        self.frames.push_back((instructions, time));
+    }
+
+    fn render(&self, _chip8: &Chip8, buffer: &mut Buffer) {
+        buffer.clear();
+
+        let graphs = [
+            ("IPS", self.instructions_per_second(), IPS_COLOR),
+            ("FPS", self.frames_per_second(), FPS_COLOR),
+            ("MS", self.frame_times(), FRAME_TIME_COLOR),
+        ];
+
+        for (idx, (name, samples, color)) in graphs.iter().enumerate() {
+            let top = idx * (LINE_HEIGHT + GRAPH_HEIGHT + 1);
+            let latest = samples.last().cloned().unwrap_or(0.0);
+            let max = samples.iter().cloned().fold(0.0, f32::max);
+
+            let label = format!("{} {:.0} MAX {:.0}", name, latest, max);
+            buffer.draw_text(&label, Point::new(0, top), LABEL_COLOR);
+            draw_graph(buffer, samples, top + LINE_HEIGHT, *color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rates() {
+        let mut panel = PerformancePanel::new();
+        panel.record_frame(10, Duration::from_millis(20));
+        panel.record_frame(0, Duration::from_secs(0));
+
+        assert_eq!(panel.instructions_per_second(), vec!(500.0, 0.0));
+        assert_eq!(panel.frames_per_second(), vec!(50.0, 0.0));
+        assert_eq!(panel.frame_times(), vec!(20.0, 0.0));
+    }
+
+    #[test]
+    fn test_history_is_limited() {
+        let mut panel = PerformancePanel::new();
+        for instructions in 0..HISTORY as u32 + 5 {
+            panel.record_frame(instructions, Duration::from_secs(1));
+        }
+
+        assert_eq!(panel.frames.len(), HISTORY);
+        assert_eq!(panel.frames.front(), Some(&(5, Duration::from_secs(1))));
+    }
+
+    #[test]
+    fn test_graph_is_scaled_to_max() {
+        let mut buffer = Buffer::new(4, GRAPH_HEIGHT, None);
+        draw_graph(&mut buffer, &[2.0, 1.0], 0, 0xFFFFFF);
+
+        let lit = |x| (0..GRAPH_HEIGHT)
+            .filter(|&y| buffer.pixels()[x + y * 4] == 0xFFFFFF)
+            .count();
+        assert_eq!((lit(0), lit(1), lit(2), lit(3)), (0, 0, GRAPH_HEIGHT, GRAPH_HEIGHT / 2));
+    }
+}
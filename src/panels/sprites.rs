@@ -0,0 +1,120 @@
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::{MEMORY, Chip8};
+use crate::screen::{Buffer, Point};
+use crate::text::LINE_HEIGHT;
+use super::Panel;
+
+const PIXEL_COLOR: u32 = 0xFFFFFF;
+const LABEL_COLOR: u32 = 0x808080;
+const MAX_SPRITE_HEIGHT: usize = 15;
+// Spacing between the sprite columns of the memory browser
+const COLUMN_WIDTH: usize = 10;
+
+/// Shows memory interpreted as 8xN sprites.
+///
+/// On the left is the sprite at I, using the height of the upcoming (or most recent) DRW.
+/// On the right, memory from a chosen address is shown as columns of 15-row sprites to
+/// help find graphics data in a ROM. Left/Right move the address by a byte, Up/Down by
+/// a column.
+pub struct SpritePanel {
+    address: usize,
+}
+
+impl SpritePanel {
+    pub fn new() -> SpritePanel {
+        SpritePanel {
+            address: 0x200,
+        }
+    }
+}
+
+impl Panel for SpritePanel {
+    fn name(&self) -> &'static str {
+        "sprites"
+    }
+
+    fn handle_input(&mut self, window: &Window) {
+        if window.is_key_pressed(Key::Right, KeyRepeat::Yes) {
+            self.address = (self.address + 1) % MEMORY;
+        }
+        if window.is_key_pressed(Key::Left, KeyRepeat::Yes) {
+            self.address = (self.address + MEMORY - 1) % MEMORY;
+        }
+        if window.is_key_pressed(Key::Down, KeyRepeat::Yes) {
+            self.address = (self.address + MAX_SPRITE_HEIGHT) % MEMORY;
+        }
+        if window.is_key_pressed(Key::Up, KeyRepeat::Yes) {
+            self.address = (self.address + MEMORY - MAX_SPRITE_HEIGHT) % MEMORY;
+        }
+    }
+
+    fn position(&self) -> Option<usize> {
+        Some(self.address)
+    }
+
+    fn set_position(&mut self, position: usize) {
+        self.address = position % MEMORY;
+    }
+
+    fn render(&self, chip8: &Chip8, buffer: &mut Buffer) {
+        buffer.clear();
+
+        let i = chip8.i as usize;
+        let height = sprite_height(chip8);
+
+        buffer.draw_text(&format!("I{:03X}", i), Point::new(0, 0), LABEL_COLOR);
+        draw_sprite(buffer, &chip8.memory, i, height, Point::new(0, LINE_HEIGHT), 2);
+
+        let left = 2 * COLUMN_WIDTH;
+        let columns = (buffer.width() - left) / COLUMN_WIDTH;
+
+        buffer.draw_text(&format!("@{:03X}", self.address), Point::new(left, 0), LABEL_COLOR);
+
+        for column in 0..columns {
+            let address = self.address + column * MAX_SPRITE_HEIGHT;
+            let position = Point::new(left + column * COLUMN_WIDTH, LINE_HEIGHT);
+
+            draw_sprite(buffer, &chip8.memory, address, MAX_SPRITE_HEIGHT, position, 1);
+        }
+    }
+}
+
+/// Height of the sprite at I: taken from the next instruction if it is a DRW, otherwise
+/// from the most recent DRW.
+fn sprite_height(chip8: &Chip8) -> usize {
+    let next = chip8.fetch();
+    let last_draw = chip8.trace.iter()
+        .rev()
+        .find(|event| event.opcode & 0xF000 == 0xD000)
+        .map(|event| event.opcode);
+
+    let opcode = if next & 0xF000 == 0xD000 { Some(next) } else { last_draw };
+
+    match opcode.map(|opcode| (opcode & 0x000F) as usize) {
+        Some(n) if n > 0 => n,
+        _ => MAX_SPRITE_HEIGHT,
+    }
+}
+
+fn draw_sprite(
+        buffer: &mut Buffer, memory: &[u8], address: usize, height: usize,
+        position: Point, scale: usize) {
+    let (left, top) = (position.x(), position.y());
+
+    for row in 0..height {
+        let byte = memory[(address + row) % memory.len()];
+
+        for column in 0..8 {
+            if byte >> (7 - column) & 1 == 0 {
+                continue;
+            }
+
+            for dy in 0..scale {
+                for dx in 0..scale {
+                    buffer.set_pixel(left + column * scale + dx, top + row * scale + dy, PIXEL_COLOR);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use minifb::Window;
+
+use crate::Chip8;
+use crate::debugger::expression::Watch;
+use crate::screen::{Buffer, Point};
+use crate::text::LINE_HEIGHT;
+use super::Panel;
+
+const TITLE_COLOR: u32 = 0x808080;
+const SOURCE_COLOR: u32 = 0xA0A0A0;
+const VALUE_COLOR: u32 = 0xFFFFFF;
+const ERROR_COLOR: u32 = 0xE06040;
+
+/// The watch expressions given with `--watch-expr` and their values, evaluated again every
+/// time the panel is drawn, so also after every single step.
+///
+/// Every watch takes two lines, the expression and its value in decimal and hexadecimal,
+/// as expressions rarely fit next to their value in the debug area.
+pub struct WatchPanel {
+    watches: Vec<Watch>,
+}
+
+impl WatchPanel {
+    pub fn new(watches: Vec<Watch>) -> WatchPanel {
+        WatchPanel { watches }
+    }
+
+    /// The lines for the watches, with their colours.
+    fn lines(&self, chip8: &Chip8) -> Vec<(String, u32)> {
+        if self.watches.is_empty() {
+            return vec!((String::from("NONE, SEE --WATCH-EXPR"), SOURCE_COLOR));
+        }
+
+        self.watches.iter().flat_map(|watch| {
+            let value = match watch.evaluate(chip8) {
+                Some(value) => (format!(" = {} ({:04X})", value, value), VALUE_COLOR),
+                None => (String::from(" = DIVIDED BY 0"), ERROR_COLOR),
+            };
+
+            vec!((watch.to_string(), SOURCE_COLOR), value)
+        }).collect()
+    }
+}
+
+impl Panel for WatchPanel {
+    fn name(&self) -> &'static str {
+        "watches"
+    }
+
+    fn handle_input(&mut self, _window: &Window) {}
+
+    fn render(&self, chip8: &Chip8, buffer: &mut Buffer) {
+        buffer.clear();
+
+        buffer.draw_text("WATCHES", Point::new(0, 0), TITLE_COLOR);
+        let rows = buffer.height() / LINE_HEIGHT - 1;
+        for (idx, (line, color)) in self.lines(chip8).iter().take(rows).enumerate() {
+            buffer.draw_text(line, Point::new(0, (idx + 1) * LINE_HEIGHT), *color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines() {
+        let mut chip8 = Chip8::new();
+        chip8.registers[3] = 40;
+        chip8.registers[4] = 2;
+        let panel = WatchPanel::new(vec!("V3 + V4".parse().unwrap(), "V3 / VF".parse().unwrap()));
+
+        let lines: Vec<String> = panel.lines(&chip8).into_iter().map(|(line, _)| line).collect();
+        assert_eq!(lines, vec!("V3 + V4", " = 42 (002A)", "V3 / VF", " = DIVIDED BY 0"));
+    }
+}
@@ -0,0 +1,296 @@
+//! Live patching: writing bytes into memory from the debugger, to try out a fix without
+//! reassembling the ROM. The prompt also takes the `dump` and `load` commands of the
+//! debugger protocol, to save a range of memory to a file or to load one into memory, and
+//! `run`, to run a number of cycles or frames and break again. Instructions typed as
+//! mnemonics are assembled, and executed right away or written into memory.
+
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::{Chip8, MEMORY};
+use crate::assembler::{self, assemble};
+use crate::debugger::Debugger;
+use crate::debugger::protocol::{self, Request, Response};
+use crate::screen::{Buffer, Point};
+use crate::text::LINE_HEIGHT;
+use crate::text_input;
+
+const TITLE_COLOR: u32 = 0xFFFFFF;
+const TEXT_COLOR: u32 = 0xA0A0A0;
+const INPUT_COLOR: u32 = 0x80C0FF;
+
+/// Parse a patch such as `2A0 6005 1234`: a hexadecimal address followed by the bytes to
+/// write there, in hexadecimal pairs that may be grouped freely.
+pub fn parse_patch(s: &str) -> Result<(usize, Vec<u8>), String> {
+    let mut tokens = s.split_whitespace();
+
+    let address = tokens.next().ok_or("Missing address")?;
+    let digits = address.trim_start_matches("0x").trim_start_matches("0X");
+    let address = usize::from_str_radix(digits, 16)
+        .map_err(|_| format!("Invalid address '{}'", address))?;
+
+    let hex: String = tokens.collect();
+    if hex.is_empty() || hex.len() % 2 != 0 {
+        return Err(String::from("Expected hex bytes"));
+    }
+
+    let bytes = (0..hex.len()).step_by(2)
+        .map(|idx| u8::from_str_radix(&hex[idx..idx + 2], 16)
+            .map_err(|_| format!("Invalid byte '{}'", &hex[idx..idx + 2])))
+        .collect::<Result<Vec<u8>, String>>()?;
+
+    if address + bytes.len() > MEMORY {
+        return Err(format!("Patch does not fit in memory at {:#X}", address));
+    }
+
+    Ok((address, bytes))
+}
+
+/// Parse the `dump`, `load` and `run` commands, with the addresses in hexadecimal as in
+/// patches and the counts of `run` in decimal. Anything else is not a command, but may be
+/// a patch.
+fn parse_command(input: &str) -> Option<Result<Request, String>> {
+    let mut words: Vec<&str> = input.split_whitespace().collect();
+    let command = words.first().map(|word| word.to_lowercase());
+    if let Some(command) = &command {
+        words[0] = command;
+    }
+
+    let command = match words.as_slice() {
+        ["dump", start, end, path] => format!("dump 0x{} 0x{} {}", start, end, path),
+        ["load", path, start] => format!("load {} 0x{}", path, start),
+        ["run", ..] => words.join(" ").to_lowercase(),
+        ["dump", ..] => return Some(Err(String::from("Expected dump start end file"))),
+        ["load", ..] => return Some(Err(String::from("Expected load file address"))),
+        _ => return None,
+    };
+
+    Some(command.parse())
+}
+
+/// An instruction typed into the prompt.
+#[derive(Debug, PartialEq)]
+enum Assembly {
+    /// Execute the opcode right away.
+    Execute(u16),
+    /// Write the opcode at an address.
+    Write(usize, u16),
+}
+
+/// Parse an instruction such as `LD V3, 0x1F`, which is executed right away. Prefixed with
+/// a hexadecimal address and a colon, as in `2A0: LD V3, 0x1F`, it is written there instead,
+/// and with only a colon it is written at the cursor. Anything that does not start with a
+/// mnemonic is not an instruction, but may be a patch.
+fn parse_assembly(input: &str, cursor: usize) -> Option<Result<Assembly, String>> {
+    let (address, instruction) = match input.find(':') {
+        Some(idx) => (Some(input[..idx].trim()), &input[idx + 1..]),
+        None => (None, input),
+    };
+    if !assembler::is_mnemonic(instruction.split_whitespace().next()?) {
+        return None;
+    }
+
+    let address = match address {
+        None => return Some(assemble(instruction).map(Assembly::Execute)),
+        Some("") => cursor,
+        Some(address) => {
+            let digits = address.trim_start_matches("0x").trim_start_matches("0X");
+            match usize::from_str_radix(digits, 16) {
+                Ok(address) => address,
+                Err(_) => return Some(Err(format!("Invalid address '{}'", address))),
+            }
+        },
+    };
+    if address + 2 > MEMORY {
+        return Some(Err(format!("Instruction does not fit in memory at {:#X}", address)));
+    }
+
+    Some(assemble(instruction).map(|opcode| Assembly::Write(address, opcode)))
+}
+
+/// An input prompt for patching memory while the debugger is paused.
+///
+/// F2 opens and closes the prompt. Type the address and bytes, then Enter writes them.
+/// Writes bypass memory protection, so the interpreter area can be patched too. Commands
+/// such as `dump 200 400 sprites.bin` and instructions such as `: LD V3, 0x1F` are typed
+/// the same way. The cursor instructions are written at starts at the PC and moves past
+/// every instruction written, so a few lines make up a routine. Patches at an address that
+/// reads as a mnemonic, such as `ADD`, are written as `0xADD`.
+pub struct PatchPrompt {
+    open: bool,
+    input: String,
+    message: String,
+    cursor: usize,
+}
+
+impl PatchPrompt {
+    pub fn new() -> PatchPrompt {
+        PatchPrompt {
+            open: false,
+            input: String::new(),
+            message: String::new(),
+            cursor: 0,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn handle_input(&mut self, window: &Window, typed: &str, chip8: &mut Chip8,
+        debugger: &mut Debugger)
+    {
+        if window.is_key_pressed(Key::F2, KeyRepeat::No) {
+            self.open = debugger.paused && !self.open;
+            self.input.clear();
+            self.message.clear();
+            self.cursor = chip8.pc as usize;
+        }
+
+        if !self.open {
+            return;
+        }
+
+        if text_input::edit_line(&mut self.input, window, typed) {
+            self.apply(chip8, debugger);
+        }
+    }
+
+    fn apply(&mut self, chip8: &mut Chip8, debugger: &mut Debugger) {
+        if let Some(request) = parse_command(&self.input) {
+            // The machine is frozen while the prompt is open
+            if let Ok(Request::Run(_)) = request {
+                self.open = false;
+            }
+
+            let response = request.map(|request| protocol::execute(request, chip8, debugger));
+
+            self.message = match response {
+                Ok(Response::Dumped { length, .. }) => format!("DUMPED {} BYTES", length),
+                Ok(Response::Loaded { start, length }) =>
+                    format!("{} BYTES AT {:03X}", length, start),
+                Ok(Response::Error(e)) | Err(e) => e.to_uppercase(),
+                Ok(_) => String::new(),
+            };
+            self.input.clear();
+            return;
+        }
+
+        if let Some(assembly) = parse_assembly(&self.input, self.cursor) {
+            let result = assembly.and_then(|assembly| match assembly {
+                Assembly::Execute(opcode) => chip8.execute(opcode)
+                    .map(|_| format!("RAN {:04X}", opcode)),
+                Assembly::Write(address, opcode) => {
+                    chip8.memory[address..address + 2].copy_from_slice(&opcode.to_be_bytes());
+                    self.cursor = address + 2;
+                    Ok(format!("{:04X} AT {:03X}", opcode, address))
+                },
+            });
+
+            match result {
+                Ok(message) => {
+                    self.message = message;
+                    self.input.clear();
+                },
+                Err(e) => self.message = e.to_uppercase(),
+            }
+            return;
+        }
+
+        match parse_patch(&self.input) {
+            Ok((address, bytes)) => {
+                chip8.memory[address..address + bytes.len()].copy_from_slice(&bytes);
+                self.message = format!("{} BYTES AT {:03X}", bytes.len(), address);
+                self.input.clear();
+            },
+            Err(e) => self.message = e.to_uppercase(),
+        }
+    }
+
+    pub fn render(&self, buffer: &mut Buffer) {
+        buffer.clear();
+
+        let input = format!(">{}", self.input.to_uppercase());
+        buffer.draw_text("PATCH ADDR BYTES", Point::new(0, 0), TITLE_COLOR);
+        buffer.draw_text(&input, Point::new(0, LINE_HEIGHT + 1), INPUT_COLOR);
+        buffer.draw_text(&self.message, Point::new(0, 3 * LINE_HEIGHT), TEXT_COLOR);
+
+        let cursor = format!("CURSOR {:03X}", self.cursor);
+        buffer.draw_text(&cursor, Point::new(0, 5 * LINE_HEIGHT), TEXT_COLOR);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debugger::RunBudget;
+
+    #[test]
+    fn test_parse_patch() {
+        assert_eq!(parse_patch("2A0 6005 12 34"), Ok((0x2A0, vec!(0x60, 0x05, 0x12, 0x34))));
+        assert_eq!(parse_patch("0x300 00E0"), Ok((0x300, vec!(0x00, 0xE0))));
+        assert!(parse_patch("2A0 600").is_err());
+        assert!(parse_patch("2A0").is_err());
+        assert!(parse_patch("FFF 0000").is_err());
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("dump 200 400 sprites.bin"), Some(Ok(Request::DumpMem {
+            start: 0x200,
+            end: 0x400,
+            path: String::from("sprites.bin"),
+        })));
+        assert_eq!(parse_command("load cafe 300"), Some(Ok(Request::LoadMem {
+            path: String::from("cafe"),
+            start: 0x300,
+        })));
+        assert!(parse_command("load cafe").unwrap().is_err());
+        assert_eq!(parse_command("2a0 6005"), None);
+        assert_eq!(parse_command("DUMP 200 201 A.bin").unwrap().unwrap(), Request::DumpMem {
+            start: 0x200,
+            end: 0x201,
+            path: String::from("A.bin"),
+        });
+        assert_eq!(parse_command("run 412 frames"), Some(Ok(Request::Run(RunBudget::Frames(412)))));
+    }
+
+    #[test]
+    fn test_parse_assembly() {
+        assert_eq!(parse_assembly("LD V3, 0x1F", 0x200), Some(Ok(Assembly::Execute(0x631F))));
+        assert_eq!(parse_assembly("2A0: cls", 0x200), Some(Ok(Assembly::Write(0x2A0, 0x00E0))));
+        assert_eq!(parse_assembly(": RET", 0x204), Some(Ok(Assembly::Write(0x204, 0x00EE))));
+        assert!(parse_assembly("FFF: CLS", 0x200).unwrap().is_err());
+        assert!(parse_assembly("LD V3", 0x200).unwrap().is_err());
+        assert_eq!(parse_assembly("2a0 6005", 0x200), None);
+        assert_eq!(parse_assembly("", 0x200), None);
+    }
+
+    #[test]
+    fn test_execute_instruction() {
+        // LD V0, 1; LD V1, 2
+        let mut chip8 = Chip8::new();
+        chip8.load_bytes(&[0x60, 0x01, 0x61, 0x02]);
+        let mut debugger = Debugger::new();
+        debugger.paused = true;
+
+        let mut prompt = PatchPrompt::new();
+        prompt.open = true;
+        prompt.cursor = 0x202;
+
+        prompt.input = String::from("LD V3, 0x1F");
+        prompt.apply(&mut chip8, &mut debugger);
+        assert_eq!((chip8.registers[3], chip8.pc), (0x1F, 0x200));
+
+        // A skip skips the instruction at the PC
+        prompt.input = String::from("SE V3, 0x1F");
+        prompt.apply(&mut chip8, &mut debugger);
+        assert_eq!(chip8.pc, 0x202);
+
+        prompt.input = String::from(": LD V1, 7");
+        prompt.apply(&mut chip8, &mut debugger);
+        assert_eq!(prompt.cursor, 0x204);
+        chip8.cycle();
+        assert_eq!(chip8.registers[1], 7);
+        assert_eq!(prompt.message, "6107 AT 202");
+    }
+}
@@ -0,0 +1,64 @@
+use std::{fs, io, path::PathBuf};
+
+use directories::ProjectDirs;
+
+const CONFIG_FILE: &str = "chip8.toml";
+
+/// Kinds of files the emulator stores in its data directory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataKind {
+    SaveStates,
+    /// Per-ROM settings, such as the unknown opcodes to treat as NOPs.
+    Metadata,
+    /// The ROM and state to resume, see `session`.
+    Session,
+    /// The achievements unlocked per ROM, see `achievements`.
+    Achievements,
+    /// Reports saved with Ctrl+B, see `bug_report`.
+    BugReports,
+}
+
+impl DataKind {
+    fn dir_name(&self) -> &'static str {
+        match self {
+            DataKind::SaveStates => "states",
+            DataKind::Metadata => "metadata",
+            DataKind::Session => "session",
+            DataKind::Achievements => "achievements",
+            DataKind::BugReports => "bug-reports",
+        }
+    }
+}
+
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "chip8")
+}
+
+/// Location of the config file.
+///
+/// A `chip8.toml` in the working directory takes precedence, otherwise the platform's
+/// config directory is used (`~/.config/chip8` on Linux, `%APPDATA%\chip8` on Windows and
+/// `~/Library/Application Support/chip8` on macOS).
+pub fn config_file() -> PathBuf {
+    let local = PathBuf::from(CONFIG_FILE);
+
+    match project_dirs() {
+        Some(dirs) if !local.exists() => dirs.config_dir().join(CONFIG_FILE),
+        _ => local,
+    }
+}
+
+/// Directory for a kind of data file, created when it does not exist yet.
+///
+/// Falls back to the working directory when no home directory can be found.
+pub fn data_dir(kind: DataKind) -> io::Result<PathBuf> {
+    let base = match project_dirs() {
+        Some(dirs) => dirs.data_dir().to_path_buf(),
+        None => PathBuf::from("."),
+    };
+
+    let dir = base.join(kind.dir_name());
+    fs::create_dir_all(&dir)?;
+
+    Ok(dir)
+}
@@ -0,0 +1,138 @@
+//! Asking what to do when a ROM runs into an opcode the interpreter cannot execute.
+//!
+//! The machine pauses and an on-screen prompt offers to skip the instruction once, to treat
+//! that opcode as a NOP from now on, or to abort. Opcodes treated as NOPs are remembered
+//! per ROM in the metadata directory, so the prompt does not return on the next run.
+
+use std::{fs, io, path::PathBuf};
+
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::locale::{fill, tr, Text};
+use crate::screen::{Buffer, Point};
+use crate::text::LINE_HEIGHT;
+
+const TITLE_COLOR: u32 = 0xFF4040;
+const TEXT_COLOR: u32 = 0xFFFFFF;
+const BACKGROUND_COLOR: u32 = 0x202020;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Choice {
+    /// Continue after the instruction, asking again when it comes up again.
+    Skip,
+    /// Continue and ignore this opcode from now on.
+    Nop,
+    /// Stop the emulator. There is no launcher to return to yet.
+    Abort,
+}
+
+/// The opcodes a ROM treats as NOPs, stored as one hexadecimal opcode per line.
+pub struct NopList {
+    path: PathBuf,
+    opcodes: Vec<u16>,
+}
+
+impl NopList {
+    /// Read the list of a ROM, which is empty when nothing was stored yet.
+    pub fn load(dir: PathBuf, rom_name: &str) -> NopList {
+        let path = dir.join(format!("{}.nops", rom_name));
+        let opcodes = fs::read_to_string(&path).map_or(Vec::new(), |contents| parse(&contents));
+
+        NopList { path, opcodes }
+    }
+
+    pub fn contains(&self, opcode: u16) -> bool {
+        self.opcodes.contains(&opcode)
+    }
+
+    pub fn add(&mut self, opcode: u16) -> io::Result<()> {
+        if !self.contains(opcode) {
+            self.opcodes.push(opcode);
+        }
+
+        fs::write(&self.path, format(&self.opcodes))
+    }
+}
+
+fn parse(contents: &str) -> Vec<u16> {
+    contents.lines()
+        .filter_map(|line| u16::from_str_radix(line.trim(), 16).ok())
+        .collect()
+}
+
+fn format(opcodes: &[u16]) -> String {
+    opcodes.iter().map(|opcode| format!("{:04X}\n", opcode)).collect()
+}
+
+/// The on-screen prompt for an unknown opcode.
+///
+/// S skips the instruction, N treats the opcode as a NOP and A aborts.
+pub struct OpcodePrompt {
+    opcode: Option<u16>,
+}
+
+impl OpcodePrompt {
+    pub fn new() -> OpcodePrompt {
+        OpcodePrompt { opcode: None }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.opcode.is_some()
+    }
+
+    pub fn open(&mut self, opcode: u16) {
+        self.opcode = Some(opcode);
+    }
+
+    /// Return the chosen action together with the opcode, once a choice is made.
+    pub fn handle_input(&mut self, window: &Window) -> Option<(Choice, u16)> {
+        let opcode = self.opcode?;
+
+        let choice = if window.is_key_pressed(Key::S, KeyRepeat::No) {
+            Choice::Skip
+        } else if window.is_key_pressed(Key::N, KeyRepeat::No) {
+            Choice::Nop
+        } else if window.is_key_pressed(Key::A, KeyRepeat::No) {
+            Choice::Abort
+        } else {
+            return None;
+        };
+
+        self.opcode = None;
+
+        Some((choice, opcode))
+    }
+
+    pub fn render(&self, buffer: &mut Buffer) {
+        let opcode = match self.opcode {
+            Some(opcode) => opcode,
+            None => return,
+        };
+
+        for y in 0..4 * LINE_HEIGHT + 1 {
+            for x in 0..buffer.width() {
+                buffer.set_pixel(x, y, BACKGROUND_COLOR);
+            }
+        }
+
+        let title = fill(Text::UnknownOpcode, &[&format!("{:04X}", opcode)]);
+        buffer.draw_text(&title, Point::new(1, 1), TITLE_COLOR);
+        buffer.draw_text(tr(Text::Skip), Point::new(1, 1 + LINE_HEIGHT), TEXT_COLOR);
+        buffer.draw_text(tr(Text::Nop), Point::new(1, 1 + 2 * LINE_HEIGHT), TEXT_COLOR);
+        buffer.draw_text(tr(Text::Abort), Point::new(1, 1 + 3 * LINE_HEIGHT), TEXT_COLOR);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nop_list_format() {
+        let opcodes = vec!(0x00FE, 0xF275);
+
+        assert_eq!(format(&opcodes), "00FE\nF275\n");
+        assert_eq!(parse(&format(&opcodes)), opcodes);
+        assert_eq!(parse("00ff\n\nnot an opcode\n"), vec!(0x00FF));
+    }
+}
@@ -0,0 +1,48 @@
+use alloc::{format, string::String};
+
+/// Behaviours that differ between the original COSMAC VIP interpreter and modern ones.
+///
+/// Every quirk defaults to the modern behaviour.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Quirks {
+    /// Fx0A only stores the key once it is released again, like on the COSMAC VIP.
+    pub key_release: bool,
+    /// 8xy1, 8xy2 and 8xy3 reset VF to 0, like on the COSMAC VIP.
+    pub vf_reset: bool,
+    /// Dxyn waits for the display interrupt and races the beam, like on the COSMAC VIP.
+    /// Only has an effect with authentic timing, see `vip_timing`.
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    /// Enable a quirk by its command line name.
+    pub fn enable(&mut self, name: &str) -> Result<(), String> {
+        match name {
+            "key-release" => self.key_release = true,
+            "vf-reset" => self.vf_reset = true,
+            "display-wait" => self.display_wait = true,
+            _ => return Err(format!("Unknown quirk '{}'", name)),
+        }
+
+        Ok(())
+    }
+
+    /// Whether a quirk is enabled, by its command line name.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        match name {
+            "key-release" => self.key_release,
+            "vf-reset" => self.vf_reset,
+            "display-wait" => self.display_wait,
+            _ => false,
+        }
+    }
+
+    /// Combine two sets of quirks, enabling every quirk that is enabled in either.
+    pub fn union(self, other: Quirks) -> Quirks {
+        Quirks {
+            key_release: self.key_release || other.key_release,
+            vf_reset: self.vf_reset || other.vf_reset,
+            display_wait: self.display_wait || other.display_wait,
+        }
+    }
+}
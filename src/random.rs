@@ -0,0 +1,149 @@
+//! The random numbers RND (Cxkk) draws from.
+//!
+//! By default they come from a modern PRNG, seeded from the system unless a seed is given.
+//! ROMs written for the COSMAC VIP sometimes depend on its interpreter always producing
+//! the same numbers after it was switched on, for example to lay out a level, which the
+//! `vip` generator imitates with a 16-bit LFSR that starts over from the same state on
+//! every reset.
+//!
+//! Without std there is no entropy to seed from, so the modern generator always starts from
+//! the same state too, unless the host hands it a seed.
+
+use core::{fmt, str::FromStr};
+
+use alloc::{boxed::Box, format, string::String};
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+/// A source of random bytes for RND.
+pub trait RandomSource: Send {
+    fn next_byte(&mut self) -> u8;
+}
+
+/// The generators to choose from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RngKind {
+    Modern,
+    Vip,
+}
+
+impl RngKind {
+    /// A generator of this kind. Without a seed the modern generator is seeded from the
+    /// system, while the VIP one starts from its power-on state.
+    pub fn generator(self, seed: Option<u64>) -> Box<dyn RandomSource> {
+        match (self, seed) {
+            (RngKind::Modern, Some(seed)) => Box::new(StdRng::seed_from_u64(seed)),
+            #[cfg(feature = "std")]
+            (RngKind::Modern, None) => Box::new(StdRng::from_entropy()),
+            #[cfg(not(feature = "std"))]
+            (RngKind::Modern, None) => Box::new(StdRng::seed_from_u64(0)),
+            (RngKind::Vip, seed) => Box::new(Lfsr::new(seed)),
+        }
+    }
+}
+
+impl FromStr for RngKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<RngKind, String> {
+        match s {
+            "modern" => Ok(RngKind::Modern),
+            "vip" => Ok(RngKind::Vip),
+            _ => Err(format!("Unknown RNG '{}', expected modern or vip", s)),
+        }
+    }
+}
+
+impl fmt::Display for RngKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RngKind::Modern => write!(f, "modern"),
+            RngKind::Vip => write!(f, "vip"),
+        }
+    }
+}
+
+impl RandomSource for StdRng {
+    fn next_byte(&mut self) -> u8 {
+        self.gen()
+    }
+}
+
+const LFSR_START: u16 = 0xACE1;
+// The taps of a maximal length 16-bit Galois LFSR, which runs through every state but 0
+const LFSR_TAPS: u16 = 0xB400;
+
+/// A 16-bit Galois LFSR, shifted a whole byte for every number.
+pub struct Lfsr {
+    state: u16,
+}
+
+impl Lfsr {
+    /// Start from the power-on state, or from one derived from the seed. The state is
+    /// never 0, on which the register would be stuck.
+    pub fn new(seed: Option<u64>) -> Lfsr {
+        let state = match seed {
+            Some(seed) => (seed ^ seed >> 16 ^ seed >> 32 ^ seed >> 48) as u16 ^ LFSR_START,
+            None => LFSR_START,
+        };
+
+        Lfsr { state: if state == 0 { LFSR_START } else { state } }
+    }
+}
+
+impl RandomSource for Lfsr {
+    fn next_byte(&mut self) -> u8 {
+        for _ in 0..8 {
+            let bit = self.state & 1;
+            self.state >>= 1;
+            if bit == 1 {
+                self.state ^= LFSR_TAPS;
+            }
+        }
+
+        self.state as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vip_sequence_repeats() {
+        let mut first = RngKind::Vip.generator(None);
+        let mut second = RngKind::Vip.generator(None);
+        let numbers: Vec<u8> = (0..32).map(|_| first.next_byte()).collect();
+
+        assert_eq!(numbers, (0..32).map(|_| second.next_byte()).collect::<Vec<u8>>());
+        // Not stuck on a single number
+        assert!(numbers.iter().any(|&number| number != numbers[0]));
+
+        let mut seeded = RngKind::Vip.generator(Some(7));
+        assert_ne!(numbers, (0..32).map(|_| seeded.next_byte()).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_lfsr_period() {
+        let mut lfsr = Lfsr::new(None);
+        let mut period = 0u32;
+
+        loop {
+            lfsr.next_byte();
+            period += 1;
+            if lfsr.state == LFSR_START {
+                break;
+            }
+        }
+
+        // 65535 states, gone through a byte at a time
+        assert_eq!(period, 65535);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("vip".parse(), Ok(RngKind::Vip));
+        assert_eq!(RngKind::Modern.to_string().parse(), Ok(RngKind::Modern));
+        assert!("lcg".parse::<RngKind>().is_err());
+    }
+}
@@ -0,0 +1,69 @@
+//! Loading mildly broken ROM files anyway, with `--recover`.
+//!
+//! Without it, a ROM that does not fit in memory is cut off and the machine pauses on the
+//! fault before the first instruction. In recovery mode the ROM is repaired first, with a
+//! warning for every repair:
+//!
+//! - The header HP48 transfer programs put in front of SCHIP ROMs is stripped.
+//! - What does not fit in memory after the program start is cut off.
+
+/// The header of a binary transferred from an HP48, `HPHP48-` and a version letter.
+const HP48_MAGIC: &[u8] = b"HPHP48-";
+const HP48_HEADER_LENGTH: usize = 8;
+
+/// The ROM repaired to load in `available` bytes of memory, and what was repaired.
+pub fn repair(rom: &[u8], available: usize) -> (Vec<u8>, Vec<String>) {
+    let mut warnings = Vec::new();
+
+    let rom = if rom.starts_with(HP48_MAGIC) && rom.len() > HP48_HEADER_LENGTH {
+        warnings.push(format!("Stripped the HP48 header of {} bytes", HP48_HEADER_LENGTH));
+        &rom[HP48_HEADER_LENGTH..]
+    } else {
+        rom
+    };
+
+    let rom = if rom.len() > available {
+        warnings.push(format!("Cut off the last {} bytes, which do not fit in memory",
+            rom.len() - available));
+        &rom[..available]
+    } else {
+        rom
+    };
+
+    (rom.to_vec(), warnings)
+}
+
+/// Repair the ROM, printing what was repaired.
+pub fn recover(rom: &[u8], available: usize) -> Vec<u8> {
+    let (rom, warnings) = repair(rom, available);
+
+    for warning in warnings {
+        println!("Recovering the ROM: {}", warning);
+    }
+
+    rom
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intact_rom() {
+        let rom = [0x00, 0xE0, 0x12, 0x00];
+        assert_eq!(repair(&rom, 3584), (rom.to_vec(), Vec::new()));
+    }
+
+    #[test]
+    fn test_strip_header_and_cut_off() {
+        let mut rom = b"HPHP48-E".to_vec();
+        rom.extend(&[1, 2, 3, 4, 5]);
+
+        let (repaired, warnings) = repair(&rom, 3);
+        assert_eq!(repaired, vec!(1, 2, 3));
+        assert_eq!(warnings.len(), 2);
+
+        // Only the header is left of a ROM that is nothing else
+        assert_eq!(repair(b"HPHP48-E", 3584).0, b"HPHP48-E".to_vec());
+    }
+}
@@ -0,0 +1,192 @@
+//! A second, deliberately simple CHIP-8 interpreter used as a reference by the differential
+//! tester. It follows Cowgod's technical reference and shares no decoding code with `ops.rs`,
+//! so a bug in one decode path shows up as a divergence from the other.
+
+use crate::{MEMORY, WIDTH, HEIGHT, VF, PROGRAM_START, FONT};
+
+pub struct Reference {
+    pub pc: u16,
+    pub i: u16,
+    pub v: [u8; 16],
+    pub stack: Vec<u16>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    // The sound timer was loaded while silent and only counts down from the tick after next
+    sound_pending: bool,
+    pub keys: [bool; 16],
+    pub waiting_for_key: Option<usize>,
+
+    memory: [u8; MEMORY],
+    display: [[bool; WIDTH]; HEIGHT],
+}
+
+impl Reference {
+    /// Load a ROM, which has to fit in the memory after `PROGRAM_START`.
+    pub fn new(rom: &[u8]) -> Result<Reference, String> {
+        let available = MEMORY - PROGRAM_START;
+        if rom.len() > available {
+            return Err(format!("The ROM is {} bytes, only {} fit in memory", rom.len(),
+                available));
+        }
+
+        let mut memory = [0; MEMORY];
+        memory[..FONT.len()].copy_from_slice(&FONT);
+        memory[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(rom);
+
+        Ok(Reference {
+            pc: PROGRAM_START as u16,
+            i: 0,
+            v: [0; 16],
+            stack: Vec::new(),
+            delay_timer: 0,
+            sound_timer: 0,
+            sound_pending: false,
+            keys: [false; 16],
+            waiting_for_key: None,
+
+            memory,
+            display: [[false; WIDTH]; HEIGHT],
+        })
+    }
+
+    pub fn update_timers(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        if self.sound_pending {
+            self.sound_pending = false;
+        } else {
+            self.sound_timer = self.sound_timer.saturating_sub(1);
+        }
+    }
+
+    /// Execute one instruction, returning its opcode.
+    pub fn step(&mut self) -> u16 {
+        let pc = self.pc as usize % MEMORY;
+        let opcode = (self.memory[pc] as u16) << 8 | self.memory[(pc + 1) % MEMORY] as u16;
+
+        if let Some(x) = self.waiting_for_key {
+            if let Some(key) = self.keys.iter().position(|&down| down) {
+                self.v[x] = key as u8;
+                self.waiting_for_key = None;
+            }
+
+            return opcode;
+        }
+
+        self.pc = self.pc.wrapping_add(2);
+
+        let x = ((opcode >> 8) & 0xF) as usize;
+        let y = ((opcode >> 4) & 0xF) as usize;
+        let n = opcode & 0xF;
+        let kk = (opcode & 0xFF) as u8;
+        let nnn = opcode & 0xFFF;
+
+        match (opcode >> 12, n) {
+            (0x0, _) if opcode == 0x00E0 => self.display = [[false; WIDTH]; HEIGHT],
+            (0x0, _) if opcode == 0x00EE => self.pc = self.stack.pop().unwrap_or(0),
+            (0x1, _) => self.pc = nnn,
+            (0x2, _) => {
+                self.stack.push(self.pc);
+                self.pc = nnn;
+            },
+            (0x3, _) => self.skip_if(self.v[x] == kk),
+            (0x4, _) => self.skip_if(self.v[x] != kk),
+            (0x5, 0x0) => self.skip_if(self.v[x] == self.v[y]),
+            (0x6, _) => self.v[x] = kk,
+            (0x7, _) => self.v[x] = self.v[x].wrapping_add(kk),
+            (0x8, 0x0) => self.v[x] = self.v[y],
+            (0x8, 0x1) => self.v[x] |= self.v[y],
+            (0x8, 0x2) => self.v[x] &= self.v[y],
+            (0x8, 0x3) => self.v[x] ^= self.v[y],
+            (0x8, 0x4) => {
+                let (sum, carry) = self.v[x].overflowing_add(self.v[y]);
+                self.set_with_flag(x, sum, carry as u8);
+            },
+            (0x8, 0x5) => {
+                let not_borrow = (self.v[x] >= self.v[y]) as u8;
+                self.set_with_flag(x, self.v[x].wrapping_sub(self.v[y]), not_borrow);
+            },
+            (0x8, 0x6) => self.set_with_flag(x, self.v[x] >> 1, self.v[x] & 1),
+            (0x8, 0x7) => {
+                let not_borrow = (self.v[y] >= self.v[x]) as u8;
+                self.set_with_flag(x, self.v[y].wrapping_sub(self.v[x]), not_borrow);
+            },
+            (0x8, 0xE) => self.set_with_flag(x, self.v[x] << 1, self.v[x] >> 7),
+            (0x9, 0x0) => self.skip_if(self.v[x] != self.v[y]),
+            (0xA, _) => self.i = nnn,
+            (0xB, _) => self.pc = nnn + self.v[0] as u16,
+            // The random byte is copied from the emulator by the differential tester
+            (0xC, _) => {},
+            (0xD, _) => self.draw(x, y, n as usize),
+            (0xE, _) if kk == 0x9E => self.skip_if(self.keys[self.v[x] as usize & 0xF]),
+            (0xE, _) if kk == 0xA1 => self.skip_if(!self.keys[self.v[x] as usize & 0xF]),
+            (0xF, _) => match kk {
+                0x07 => self.v[x] = self.delay_timer,
+                0x0A => self.waiting_for_key = Some(x),
+                0x15 => self.delay_timer = self.v[x],
+                0x18 => {
+                    self.sound_pending = self.v[x] > 0
+                        && (self.sound_timer == 0 || self.sound_pending);
+                    self.sound_timer = self.v[x];
+                },
+                0x1E => self.i = self.i.wrapping_add(self.v[x] as u16),
+                0x29 => self.i = (self.v[x] & 0xF) as u16 * 5,
+                0x33 => {
+                    let i = self.i as usize;
+                    self.memory[i % MEMORY] = self.v[x] / 100;
+                    self.memory[(i + 1) % MEMORY] = self.v[x] / 10 % 10;
+                    self.memory[(i + 2) % MEMORY] = self.v[x] % 10;
+                },
+                0x55 => {
+                    for r in 0..=x {
+                        self.memory[(self.i as usize + r) % MEMORY] = self.v[r];
+                    }
+                },
+                0x65 => {
+                    for r in 0..=x {
+                        self.v[r] = self.memory[(self.i as usize + r) % MEMORY];
+                    }
+                },
+                _ => {},
+            },
+            _ => {},
+        }
+
+        opcode
+    }
+
+    fn skip_if(&mut self, condition: bool) {
+        if condition {
+            self.pc = self.pc.wrapping_add(2);
+        }
+    }
+
+    /// Store an arithmetic result and set VF afterwards, so VF holds the flag even when x is F.
+    fn set_with_flag(&mut self, x: usize, value: u8, flag: u8) {
+        self.v[x] = value;
+        self.v[VF] = flag;
+    }
+
+    fn draw(&mut self, x: usize, y: usize, n: usize) {
+        let left = self.v[x] as usize % WIDTH;
+        let top = self.v[y] as usize % HEIGHT;
+        let mut collision = 0;
+
+        for row in 0..n {
+            let byte = self.memory[(self.i as usize + row) % MEMORY];
+
+            for column in 0..8 {
+                if byte >> (7 - column) & 1 == 0 {
+                    continue;
+                }
+
+                let pixel = &mut self.display[(top + row) % HEIGHT][(left + column) % WIDTH];
+                if *pixel {
+                    collision = 1;
+                }
+                *pixel = !*pixel;
+            }
+        }
+
+        self.v[VF] = collision;
+    }
+}
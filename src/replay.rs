@@ -0,0 +1,298 @@
+//! Input recordings for deterministic mode.
+//!
+//! A replay file stores the RNG seed, the keypad state at every frame where it changed and
+//! the frame at which the recording ended:
+//!
+//! ```text
+//! seed 42
+//! 0 0000
+//! 31 0020
+//! 35 0000
+//! end 600
+//! ```
+//!
+//! Keypad states are hexadecimal masks in which bit n is set while key n is held down.
+//!
+//! Instead of recording them live, the keys can also be written as a script of the keys
+//! pressed and released at a frame, separated by newlines or semicolons:
+//!
+//! ```text
+//! seed 42
+//! frame 120: press 5; frame 180: release 5
+//! frame 200: press 4 6
+//! frame 210: release 4 6; end 300
+//! ```
+//!
+//! Keys stay held until they are released, and both forms can be mixed in one file.
+
+use std::fmt::Write;
+
+use crate::Chip8;
+use crate::display::Display;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Replay {
+    /// The seed of the replay, so RND matches the run it was recorded in.
+    pub seed: u64,
+    pub end: Option<u64>,
+    events: Vec<(u64, u16)>,
+}
+
+/// How a line of a replay changes the keypad.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Change {
+    /// Hold exactly the keys in the mask.
+    Set(u16),
+    Press(u16),
+    Release(u16),
+}
+
+impl Replay {
+    pub fn parse(contents: &str) -> Result<Replay, String> {
+        let mut replay = Replay::default();
+        let mut changes = Vec::new();
+
+        let statements = contents.lines()
+            .map(|line| line.splitn(2, '#').next().unwrap())
+            .flat_map(|line| line.split(';'))
+            .map(str::trim)
+            .filter(|statement| !statement.is_empty());
+
+        for line in statements {
+            let invalid = || format!("Invalid replay line '{}'", line);
+            let mut tokens = line.split_whitespace();
+
+            match (tokens.next(), tokens.next()) {
+                (Some("seed"), Some(seed)) => replay.seed = seed.parse().map_err(|_| invalid())?,
+                (Some("end"), Some(frame)) => replay.end = Some(frame.parse().map_err(|_| invalid())?),
+                (Some("frame"), Some(_)) => changes.push(parse_script(line).ok_or_else(invalid)?),
+                (Some(frame), Some(mask)) => {
+                    let frame = frame.parse().map_err(|_| invalid())?;
+                    let mask = u16::from_str_radix(mask, 16).map_err(|_| invalid())?;
+
+                    changes.push((frame, Change::Set(mask)));
+                },
+                _ => return Err(invalid()),
+            }
+        }
+
+        // Presses and releases build on the keys held before them
+        changes.sort_by_key(|&(frame, _)| frame);
+        let mut mask = 0;
+        for (frame, change) in changes {
+            mask = match change {
+                Change::Set(keys) => keys,
+                Change::Press(keys) => mask | keys,
+                Change::Release(keys) => mask & !keys,
+            };
+            replay.events.push((frame, mask));
+        }
+
+        Ok(replay)
+    }
+
+    /// The keypad state during a frame.
+    pub fn keys_at(&self, frame: u64) -> [bool; 16] {
+        let mask = self.events.iter()
+            .rev()
+            .find(|&&(event_frame, _)| event_frame <= frame)
+            .map_or(0, |&(_, mask)| mask);
+
+        mask_to_keys(mask)
+    }
+}
+
+/// A script statement such as `frame 120: press 5 6`.
+fn parse_script(statement: &str) -> Option<(u64, Change)> {
+    let mut parts = statement["frame".len()..].splitn(2, ':');
+    let frame = parts.next()?.trim().parse().ok()?;
+
+    let mut tokens = parts.next()?.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|token| !token.is_empty());
+    let action = tokens.next()?;
+
+    let mut keys = 0;
+    for key in tokens {
+        keys |= 1 << u8::from_str_radix(key, 16).ok().filter(|&key| key < 16)?;
+    }
+    if keys == 0 {
+        return None;
+    }
+
+    match action {
+        "press" => Some((frame, Change::Press(keys))),
+        "release" => Some((frame, Change::Release(keys))),
+        _ => None,
+    }
+}
+
+/// Records the keypad state of every frame, keeping only the changes.
+pub struct Recorder {
+    replay: Replay,
+    last: Option<u16>,
+}
+
+impl Recorder {
+    pub fn new(seed: u64) -> Recorder {
+        Recorder {
+            replay: Replay { seed, ..Replay::default() },
+            last: None,
+        }
+    }
+
+    pub fn record(&mut self, frame: u64, keys: [bool; 16]) {
+        let mask = keys_to_mask(keys);
+
+        if self.last != Some(mask) {
+            self.replay.events.push((frame, mask));
+            self.last = Some(mask);
+        }
+    }
+
+    /// The contents of the replay file for a recording that ended at `end`.
+    pub fn finish(&self, end: u64) -> String {
+        let mut contents = format!("seed {}\n", self.replay.seed);
+        for (frame, mask) in &self.replay.events {
+            writeln!(contents, "{} {:04X}", frame, mask).unwrap();
+        }
+        writeln!(contents, "end {}", end).unwrap();
+
+        contents
+    }
+}
+
+fn keys_to_mask(keys: [bool; 16]) -> u16 {
+    keys.iter().enumerate().fold(0, |mask, (key, &down)| mask | (down as u16) << key)
+}
+
+fn mask_to_keys(mask: u16) -> [bool; 16] {
+    let mut keys = [false; 16];
+
+    for (key, down) in keys.iter_mut().enumerate() {
+        *down = mask >> key & 1 == 1;
+    }
+
+    keys
+}
+
+/// A 64-bit FNV-1a hash of the machine state: registers, timers, stack, memory and display.
+///
+/// Two runs that end with the same hash ended in the same state.
+pub fn state_hash(chip8: &Chip8) -> u64 {
+    let mut hash = Fnv::new();
+
+    hash.feed(&chip8.pc.to_le_bytes());
+    hash.feed(&chip8.i.to_le_bytes());
+    hash.feed(&chip8.registers);
+    hash.feed(&[chip8.timers.delay, chip8.timers.sound]);
+    hash.feed(&(chip8.stack.len() as u16).to_le_bytes());
+    for address in chip8.stack.entries() {
+        hash.feed(&address.to_le_bytes());
+    }
+    hash.feed(&chip8.memory);
+    feed_display(&mut hash, &chip8.display);
+
+    hash.finish()
+}
+
+/// The state hash folded to 32 bits, short enough to show on the game display.
+pub fn state_checksum(chip8: &Chip8) -> u32 {
+    let hash = state_hash(chip8);
+
+    (hash ^ hash >> 32) as u32
+}
+
+/// A 64-bit FNV-1a hash of the display alone.
+pub fn frame_hash(display: &Display) -> u64 {
+    let mut hash = Fnv::new();
+    feed_display(&mut hash, display);
+
+    hash.finish()
+}
+
+fn feed_display(hash: &mut Fnv, display: &Display) {
+    for row in display.rows() {
+        hash.feed(&row.to_le_bytes());
+    }
+}
+
+/// The FNV-1a hash function, which is stable across platforms and Rust versions unlike
+/// the hashers in the standard library.
+pub struct Fnv(u64);
+
+impl Fnv {
+    pub fn new() -> Fnv {
+        Fnv(0xCBF2_9CE4_8422_2325)
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01B3);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_replay() {
+        let mut keys = [false; 16];
+        let mut recorder = Recorder::new(42);
+
+        recorder.record(0, keys);
+        recorder.record(1, keys);
+        keys[5] = true;
+        recorder.record(2, keys);
+
+        let replay = Replay::parse(&recorder.finish(10)).unwrap();
+
+        assert_eq!(replay.seed, 42);
+        assert_eq!(replay.end, Some(10));
+        assert_eq!(replay.events, vec!((0, 0x0000), (2, 0x0020)));
+        assert!(!replay.keys_at(1)[5]);
+        assert!(replay.keys_at(7)[5]);
+    }
+
+    #[test]
+    fn test_parse_script() {
+        let replay = Replay::parse("seed 7\n\
+            frame 180: release 5 # let go\n\
+            frame 120: press 5; frame 150: press 4, A\n\
+            frame 200: release 4 A; end 300\n").unwrap();
+
+        assert_eq!(replay.seed, 7);
+        assert_eq!(replay.end, Some(300));
+        assert_eq!(replay.events, vec!((120, 0x0020), (150, 0x0430), (180, 0x0410), (200, 0)));
+
+        assert!(Replay::parse("frame 10: hold 5").is_err());
+        assert!(Replay::parse("frame 10: press G").is_err());
+        assert!(Replay::parse("frame 10 press 5").is_err());
+    }
+
+    #[test]
+    fn test_state_hash() {
+        let mut chip8 = Chip8::new();
+        let hash = state_hash(&chip8);
+
+        assert_eq!(state_hash(&Chip8::new()), hash);
+
+        chip8.registers[0] = 1;
+        assert_ne!(state_hash(&chip8), hash);
+    }
+
+    #[test]
+    fn test_state_checksum() {
+        let mut chip8 = Chip8::new();
+        let checksum = state_checksum(&chip8);
+
+        chip8.memory[0x300] = 1;
+        assert_ne!(state_checksum(&chip8), checksum);
+    }
+}
@@ -0,0 +1,175 @@
+//! Rotating the display for the few ROMs that were made for a display on its side.
+//!
+//! Only what is shown is rotated, the machine keeps drawing to a landscape display. The
+//! direction keys of the keypad, laid out around 5 as on the COSMAC VIP, are turned along
+//! so that up on the rotated display is still up. The rotation of a ROM is kept in its
+//! metadata directory.
+
+use std::{fmt, fs, io, path::{Path, PathBuf}, str::FromStr};
+
+use crate::screen::Buffer;
+
+/// A clockwise rotation of the display.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rotation {
+    None,
+    Quarter,
+    Half,
+    ThreeQuarters,
+}
+
+impl Rotation {
+    /// The rotation a quarter turn further, for cycling through them from the menu.
+    pub fn next(self) -> Rotation {
+        match self {
+            Rotation::None => Rotation::Quarter,
+            Rotation::Quarter => Rotation::Half,
+            Rotation::Half => Rotation::ThreeQuarters,
+            Rotation::ThreeQuarters => Rotation::None,
+        }
+    }
+
+    fn quarter_turns(self) -> usize {
+        match self {
+            Rotation::None => 0,
+            Rotation::Quarter => 1,
+            Rotation::Half => 2,
+            Rotation::ThreeQuarters => 3,
+        }
+    }
+
+    /// Whether the display is on its side, so its width and height swap.
+    pub fn is_sideways(self) -> bool {
+        self.quarter_turns() % 2 == 1
+    }
+
+    /// The buffer turned clockwise.
+    pub fn apply(self, buffer: &Buffer) -> Buffer {
+        let (width, height) = (buffer.width(), buffer.height());
+        let (rotated_width, rotated_height) =
+            if self.is_sideways() { (height, width) } else { (width, height) };
+
+        let mut pixels = vec!(0; width * height);
+        for y in 0..rotated_height {
+            for x in 0..rotated_width {
+                let (source_x, source_y) = match self {
+                    Rotation::None => (x, y),
+                    Rotation::Quarter => (y, height - 1 - x),
+                    Rotation::Half => (width - 1 - x, height - 1 - y),
+                    Rotation::ThreeQuarters => (width - 1 - y, x),
+                };
+
+                pixels[x + y * rotated_width] = buffer.pixels()[source_x + source_y * width];
+            }
+        }
+
+        Buffer::new(rotated_width, rotated_height, Some(pixels))
+    }
+
+    /// The keys the game sees for the keys pressed on the keypad. The keys around 5 turn
+    /// against the display, the others stay as they are.
+    pub fn remap_keys(self, keys: [bool; 16]) -> [bool; 16] {
+        let mut remapped = [false; 16];
+
+        for (key, &down) in keys.iter().enumerate() {
+            remapped[self.remap_key(key)] |= down;
+        }
+
+        remapped
+    }
+
+    fn remap_key(self, key: usize) -> usize {
+        if key < 1 || key > 9 {
+            return key;
+        }
+
+        // The direction from 5, with y pointing down
+        let (mut dx, mut dy) = ((key - 1) as i32 % 3 - 1, (key - 1) as i32 / 3 - 1);
+        for _ in 0..self.quarter_turns() {
+            // Up on the display is to the left in the game after a clockwise quarter turn
+            let turned = (dy, -dx);
+            dx = turned.0;
+            dy = turned.1;
+        }
+
+        ((dy + 1) * 3 + dx + 1 + 1) as usize
+    }
+
+    fn path(dir: &Path, rom_name: &str) -> PathBuf {
+        dir.join(format!("{}.rotation", rom_name))
+    }
+
+    /// The rotation stored for a ROM, none if it was never rotated.
+    pub fn load(dir: &Path, rom_name: &str) -> Rotation {
+        fs::read_to_string(Rotation::path(dir, rom_name)).ok()
+            .and_then(|contents| contents.trim().parse().ok())
+            .unwrap_or(Rotation::None)
+    }
+
+    pub fn store(self, dir: &Path, rom_name: &str) -> io::Result<()> {
+        fs::write(Rotation::path(dir, rom_name), format!("{}\n", self))
+    }
+}
+
+impl FromStr for Rotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Rotation, String> {
+        match s {
+            "0" => Ok(Rotation::None),
+            "90" => Ok(Rotation::Quarter),
+            "180" => Ok(Rotation::Half),
+            "270" => Ok(Rotation::ThreeQuarters),
+            _ => Err(format!("Unknown rotation '{}', expected 0, 90, 180 or 270", s)),
+        }
+    }
+}
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.quarter_turns() * 90)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 3x2 buffer with the pixels numbered 1 through 6
+    fn numbered() -> Buffer {
+        Buffer::new(3, 2, Some((1..=6).collect()))
+    }
+
+    #[test]
+    fn test_apply() {
+        let quarter = Rotation::Quarter.apply(&numbered());
+        assert_eq!((quarter.width(), quarter.height()), (2, 3));
+        assert_eq!(quarter.pixels(), &[4, 1, 5, 2, 6, 3]);
+
+        assert_eq!(Rotation::Half.apply(&numbered()).pixels(), &[6, 5, 4, 3, 2, 1]);
+        assert_eq!(Rotation::ThreeQuarters.apply(&numbered()).pixels(), &[3, 6, 2, 5, 1, 4]);
+    }
+
+    #[test]
+    fn test_remap_keys() {
+        let mut up = [false; 16];
+        up[2] = true;
+        let game_key = |rotation: Rotation| rotation.remap_keys(up).iter().position(|&d| d);
+
+        assert_eq!(game_key(Rotation::None), Some(2));
+        assert_eq!(game_key(Rotation::Quarter), Some(4));
+        assert_eq!(game_key(Rotation::Half), Some(8));
+        assert_eq!(game_key(Rotation::ThreeQuarters), Some(6));
+
+        assert_eq!(Rotation::Quarter.remap_key(3), 1);
+        assert_eq!(Rotation::Quarter.remap_key(5), 5);
+        assert_eq!(Rotation::Quarter.remap_key(0xA), 0xA);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("270".parse(), Ok(Rotation::ThreeQuarters));
+        assert_eq!(Rotation::Half.to_string(), "180");
+        assert!("45".parse::<Rotation>().is_err());
+    }
+}
@@ -0,0 +1,665 @@
+//! Save states: snapshots of the complete machine, kept in numbered slots for each ROM.
+
+use std::{fs, io, collections::HashMap, path::PathBuf, time::{SystemTime, UNIX_EPOCH}};
+
+use crate::{Chip8, State, MEMORY, WIDTH, HEIGHT};
+use crate::chip8x::ColorBoard;
+use crate::display::LIT;
+use crate::shutdown::ShutdownReason;
+
+pub const SLOTS: usize = 10;
+
+/// Size of the framebuffer thumbnail, a quarter of the display in each direction.
+pub const THUMBNAIL_WIDTH: usize = WIDTH / 4;
+pub const THUMBNAIL_HEIGHT: usize = HEIGHT / 4;
+
+const MAGIC: &[u8; 4] = b"C8SS";
+/// Version 1 had a fixed layout, from version 2 on the state is split into chunks.
+const VERSION: u8 = 2;
+
+const META_CHUNK: &[u8; 4] = b"META";
+const CPU_CHUNK: &[u8; 4] = b"CPU ";
+const MEMORY_CHUNK: &[u8; 4] = b"MEM ";
+const DISPLAY_CHUNK: &[u8; 4] = b"DISP";
+/// The XO-CHIP audio pattern and pitch.
+const XO_CHIP_CHUNK: &[u8; 4] = b"XOCH";
+/// The CHIP-8X colour board, only present for CHIP-8X.
+const CHIP_8X_CHUNK: &[u8; 4] = b"C8X ";
+/// Every return address on the stack, only present when there are more than the 15 the
+/// CPU chunk has room for.
+const STACK_CHUNK: &[u8; 4] = b"STCK";
+/// Slots for return addresses in the CPU chunk, of which the first is unused.
+const CPU_STACK_SLOTS: usize = 16;
+
+/// A snapshot of the machine.
+///
+/// A save state file starts with the magic bytes `C8SS` and a version byte, followed by
+/// chunks of a four byte tag, a little endian `u32` length and the contents. Chunks
+/// only ever get fields appended and unknown chunks are skipped, so states saved by a
+/// newer version still load, and version 1 states are still read.
+pub struct SaveState {
+    /// The format version the state was read from.
+    pub version: u8,
+    /// Seconds since the Unix epoch at which the state was saved.
+    pub timestamp: u64,
+    /// Downscaled framebuffer, where a pixel is lit if any pixel in its 4x4 block is.
+    pub thumbnail: Vec<bool>,
+
+    pc: u16,
+    i: u16,
+    registers: [u8; 16],
+    memory: Vec<u8>,
+    display: [u64; HEIGHT],
+    delay_timer: u8,
+    sound_timer: u8,
+    // Return addresses, the oldest first
+    stack: Vec<u16>,
+    state: State,
+    audio_pattern: Option<[u8; 16]>,
+    pitch: u8,
+    colors: Option<ColorBoard>,
+}
+
+impl SaveState {
+    pub fn capture(chip8: &Chip8) -> SaveState {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or(0);
+        let display = *chip8.display.rows();
+
+        SaveState {
+            version: VERSION,
+            timestamp,
+            thumbnail: thumbnail(&display),
+
+            pc: chip8.pc,
+            i: chip8.i,
+            registers: chip8.registers,
+            memory: chip8.memory.to_vec(),
+            display,
+            delay_timer: chip8.timers.delay,
+            sound_timer: chip8.timers.sound,
+            stack: chip8.stack.entries().to_vec(),
+            state: chip8.state,
+            audio_pattern: chip8.audio_pattern,
+            pitch: chip8.pitch,
+            colors: chip8.chip8x.as_ref().map(|chip8x| chip8x.colors.clone()),
+        }
+    }
+
+    /// Put the machine back in the saved state. The ROM, quirks and callbacks are kept.
+    pub fn restore(&self, chip8: &mut Chip8) {
+        chip8.pc = self.pc;
+        chip8.i = self.i;
+        chip8.registers = self.registers;
+        chip8.memory.copy_from_slice(&self.memory);
+        chip8.display.set_rows(self.display);
+        chip8.timers.delay = self.delay_timer;
+        chip8.restore_sound_timer(self.sound_timer);
+        chip8.stack.set_entries(&self.stack);
+        chip8.state = self.state;
+        chip8.audio_pattern = self.audio_pattern;
+        chip8.pitch = self.pitch;
+        chip8.hooks.audio_pattern_changed(self.audio_pattern, self.pitch);
+
+        if let (Some(chip8x), Some(colors)) = (chip8.chip8x.as_mut(), &self.colors) {
+            chip8x.colors = colors.clone();
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+
+        let mut meta = self.timestamp.to_le_bytes().to_vec();
+        for row in self.thumbnail.chunks(8) {
+            meta.push(row.iter().fold(0, |byte, &pixel| byte << 1 | pixel as u8));
+        }
+        write_chunk(&mut bytes, META_CHUNK, &meta);
+
+        let mut cpu = Vec::new();
+        cpu.extend(&self.pc.to_le_bytes());
+        cpu.extend(&self.i.to_le_bytes());
+        cpu.extend(&self.registers);
+        cpu.push(self.delay_timer);
+        cpu.push(self.sound_timer);
+        // The stack pointer and the stack as the interpreter used to keep them, the first
+        // return address in the second slot
+        cpu.extend(&(self.stack.len() as u16).to_le_bytes());
+        for slot in 0..CPU_STACK_SLOTS {
+            let address = slot.checked_sub(1).and_then(|idx| self.stack.get(idx));
+            cpu.extend(&address.cloned().unwrap_or(0).to_le_bytes());
+        }
+        cpu.extend(&state_bytes(self.state));
+        write_chunk(&mut bytes, CPU_CHUNK, &cpu);
+
+        write_chunk(&mut bytes, MEMORY_CHUNK, &self.memory);
+
+        let mut display = Vec::new();
+        display.extend(&(WIDTH as u16).to_le_bytes());
+        display.extend(&(HEIGHT as u16).to_le_bytes());
+        // Stored as one u32 per pixel, as in version 1
+        for row in &self.display {
+            for x in 0..WIDTH {
+                let pixel = if row >> (WIDTH - 1 - x) & 1 == 1 { LIT } else { 0 };
+                display.extend(&pixel.to_le_bytes());
+            }
+        }
+        write_chunk(&mut bytes, DISPLAY_CHUNK, &display);
+
+        let mut xo_chip = vec!(self.pitch, self.audio_pattern.is_some() as u8);
+        xo_chip.extend(&self.audio_pattern.unwrap_or([0; 16]));
+        write_chunk(&mut bytes, XO_CHIP_CHUNK, &xo_chip);
+
+        if let Some(colors) = &self.colors {
+            write_chunk(&mut bytes, CHIP_8X_CHUNK, &colors.to_bytes());
+        }
+
+        if self.stack.len() >= CPU_STACK_SLOTS {
+            let mut stack = (self.stack.len() as u16).to_le_bytes().to_vec();
+            for address in &self.stack {
+                stack.extend(&address.to_le_bytes());
+            }
+            write_chunk(&mut bytes, STACK_CHUNK, &stack);
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<SaveState> {
+        let mut reader = Reader { bytes, position: 0 };
+
+        if reader.take(4)? != MAGIC {
+            return Err(invalid("Not a save state"));
+        }
+
+        match reader.take(1)?[0] {
+            0 => Err(invalid("Unknown save state version")),
+            1 => SaveState::from_version_1(reader),
+            version => SaveState::from_chunks(version, reader),
+        }
+    }
+
+    fn from_chunks(version: u8, mut reader: Reader) -> io::Result<SaveState> {
+        let mut chunks = HashMap::new();
+        while reader.position < reader.bytes.len() {
+            let (tag, contents) = reader.chunk()?;
+            chunks.insert(tag, contents);
+        }
+
+        let chunk = |tag: &[u8; 4]| {
+            chunks.get(tag)
+                .map(|&bytes| Reader { bytes, position: 0 })
+                .ok_or_else(|| invalid(&format!("Save state has no {} chunk", tag_name(tag))))
+        };
+
+        let mut meta = chunk(META_CHUNK)?;
+        let timestamp = meta.u64()?;
+        let thumbnail = read_thumbnail(&mut meta)?;
+
+        let mut cpu = chunk(CPU_CHUNK)?;
+        let pc = cpu.u16()?;
+        let i = cpu.u16()?;
+        let mut registers = [0; 16];
+        registers.copy_from_slice(cpu.take(16)?);
+        let delay_timer = cpu.take(1)?[0];
+        let sound_timer = cpu.take(1)?[0];
+        let mut stack = read_cpu_stack(&mut cpu)?;
+        let state = read_state(&mut cpu)?;
+
+        let memory = chunk(MEMORY_CHUNK)?.take(MEMORY)?.to_vec();
+
+        let mut display = chunk(DISPLAY_CHUNK)?;
+        if (display.u16()? as usize, display.u16()? as usize) != (WIDTH, HEIGHT) {
+            return Err(invalid("Unsupported display size"));
+        }
+        let display = read_display(&mut display)?;
+
+        // Older versions may lack the chunks of later variants
+        let (pitch, audio_pattern) = match chunk(XO_CHIP_CHUNK) {
+            Ok(mut xo_chip) => {
+                let pitch = xo_chip.take(1)?[0];
+                let has_pattern = xo_chip.take(1)?[0] == 1;
+                let mut pattern = [0; 16];
+                pattern.copy_from_slice(xo_chip.take(16)?);
+
+                (pitch, if has_pattern { Some(pattern) } else { None })
+            },
+            Err(_) => (Chip8::new().pitch, None),
+        };
+
+        if let Ok(mut deep_stack) = chunk(STACK_CHUNK) {
+            let length = deep_stack.u16()?;
+            stack = (0..length).map(|_| deep_stack.u16()).collect::<io::Result<_>>()?;
+        }
+
+        let colors = match chunk(CHIP_8X_CHUNK) {
+            Ok(mut chip8x) => Some(ColorBoard::from_bytes(chip8x.take(chip8x.bytes.len())?)
+                .ok_or_else(|| invalid("Invalid CHIP-8X colours"))?),
+            Err(_) => None,
+        };
+
+        Ok(SaveState {
+            version, timestamp, thumbnail, pc, i, registers, memory, display,
+            delay_timer, sound_timer, stack, state, audio_pattern, pitch, colors,
+        })
+    }
+
+    /// Read the fixed layout of version 1.
+    fn from_version_1(mut reader: Reader) -> io::Result<SaveState> {
+        let timestamp = reader.u64()?;
+        let thumbnail = read_thumbnail(&mut reader)?;
+
+        let pc = reader.u16()?;
+        let i = reader.u16()?;
+        let mut registers = [0; 16];
+        registers.copy_from_slice(reader.take(16)?);
+        let memory = reader.take(MEMORY)?.to_vec();
+        let display = read_display(&mut reader)?;
+        let delay_timer = reader.take(1)?[0];
+        let sound_timer = reader.take(1)?[0];
+        let stack = read_cpu_stack(&mut reader)?;
+        let state = read_state(&mut reader)?;
+
+        Ok(SaveState {
+            version: 1, timestamp, thumbnail, pc, i, registers, memory, display,
+            delay_timer, sound_timer, stack, state,
+            audio_pattern: None,
+            pitch: Chip8::new().pitch,
+            colors: None,
+        })
+    }
+
+    /// A readable summary of the state, for `chip8 state inspect`.
+    pub fn describe(&self) -> String {
+        let registers: Vec<String> = self.registers.iter().map(|v| format!("{:02X}", v)).collect();
+        let stack: Vec<String> = self.stack.iter()
+            .map(|address| format!("{:#05X}", address))
+            .collect();
+
+        let mut text = format!("Version {}, saved at {} (Unix time)\n",
+            self.version, self.timestamp);
+        text += &format!("PC {:#05X}  I {:#05X}  DT {}  ST {}  {:?}\n",
+            self.pc, self.i, self.delay_timer, self.sound_timer, self.state);
+        text += &format!("V0-V7 {}\nV8-VF {}\n",
+            registers[..8].join(" "), registers[8..].join(" "));
+        text += &format!("Stack [{}]\n", stack.join(", "));
+        text += &format!("Pitch {}, audio pattern {}\n",
+            self.pitch, if self.audio_pattern.is_some() { "set" } else { "none" });
+
+        for row in self.thumbnail.chunks(THUMBNAIL_WIDTH) {
+            let row: String = row.iter().map(|&lit| if lit { '#' } else { '.' }).collect();
+            text += &format!("  {}\n", row);
+        }
+
+        text
+    }
+
+    /// The differences from another state, one per line, for finding where two runs of a
+    /// ROM diverge. Memory is compared in ranges of consecutive differing bytes.
+    pub fn diff(&self, other: &SaveState) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut compare = |name: &str, a: String, b: String| {
+            if a != b {
+                lines.push(format!("{}: {} -> {}", name, a, b));
+            }
+        };
+
+        compare("PC", format!("{:#05X}", self.pc), format!("{:#05X}", other.pc));
+        compare("I", format!("{:#05X}", self.i), format!("{:#05X}", other.i));
+        for v_x in 0..16 {
+            compare(&format!("V{:X}", v_x),
+                format!("{:02X}", self.registers[v_x]), format!("{:02X}", other.registers[v_x]));
+        }
+        compare("DT", self.delay_timer.to_string(), other.delay_timer.to_string());
+        compare("ST", self.sound_timer.to_string(), other.sound_timer.to_string());
+        compare("SP", self.stack.len().to_string(), other.stack.len().to_string());
+        compare("Stack", format!("{:X?}", self.stack), format!("{:X?}", other.stack));
+        compare("State", format!("{:?}", self.state), format!("{:?}", other.state));
+        compare("Pitch", self.pitch.to_string(), other.pitch.to_string());
+        compare("Audio pattern",
+            format!("{:X?}", self.audio_pattern), format!("{:X?}", other.audio_pattern));
+        if self.colors != other.colors {
+            lines.push(String::from("CHIP-8X colours differ"));
+        }
+
+        let pixels: u32 = self.display.iter().zip(other.display.iter())
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum();
+        if pixels > 0 {
+            lines.push(format!("Display: {} pixels differ", pixels));
+        }
+
+        let mut address = 0;
+        while address < self.memory.len().min(other.memory.len()) {
+            if self.memory[address] == other.memory[address] {
+                address += 1;
+                continue;
+            }
+
+            let start = address;
+            while address < self.memory.len().min(other.memory.len())
+                    && self.memory[address] != other.memory[address] {
+                address += 1;
+            }
+
+            lines.push(format!("Memory {:#05X}-{:#05X}: {} -> {}", start, address - 1,
+                hex_range(&self.memory[start..address]), hex_range(&other.memory[start..address])));
+        }
+
+        lines
+    }
+}
+
+/// Bytes as hexadecimal, or only their number when there are too many to read.
+fn hex_range(bytes: &[u8]) -> String {
+    if bytes.len() > 8 {
+        return format!("{} bytes", bytes.len());
+    }
+
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(" ")
+}
+
+/// The tags and lengths of the chunks in a save state, empty for version 1.
+pub fn chunk_list(bytes: &[u8]) -> io::Result<Vec<(String, usize)>> {
+    let mut reader = Reader { bytes, position: 0 };
+    if reader.take(4)? != MAGIC || reader.take(1)?[0] < 2 {
+        return Ok(Vec::new());
+    }
+
+    let mut chunks = Vec::new();
+    while reader.position < bytes.len() {
+        let (tag, contents) = reader.chunk()?;
+        chunks.push((tag_name(&tag), contents.len()));
+    }
+
+    Ok(chunks)
+}
+
+/// The `state` subcommand: `chip8 state inspect file.state` prints a save state, and
+/// `chip8 state diff a.state b.state` prints how two save states differ.
+pub fn run_command(args: &[String]) -> Result<(), String> {
+    match args {
+        [command, path] if command == "inspect" => {
+            let bytes = fs::read(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+            let state = SaveState::from_bytes(&bytes).map_err(|e| format!("{}: {}", path, e))?;
+
+            for (tag, length) in chunk_list(&bytes).map_err(|e| e.to_string())? {
+                println!("Chunk '{}': {} bytes", tag, length);
+            }
+            print!("{}", state.describe());
+        },
+        [command, a, b] if command == "diff" => {
+            let differences = read_file(a)?.diff(&read_file(b)?);
+
+            if differences.is_empty() {
+                println!("The states are the same");
+            }
+            for line in differences {
+                println!("{}", line);
+            }
+        },
+        _ => return Err(String::from(
+            "Usage: chip8 state inspect file.state | chip8 state diff a.state b.state")),
+    }
+
+    Ok(())
+}
+
+fn read_file(path: &str) -> Result<SaveState, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+    SaveState::from_bytes(&bytes).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, tag: &[u8; 4], contents: &[u8]) {
+    bytes.extend(tag);
+    bytes.extend(&(contents.len() as u32).to_le_bytes());
+    bytes.extend(contents);
+}
+
+fn tag_name(tag: &[u8; 4]) -> String {
+    String::from_utf8_lossy(tag).into_owned()
+}
+
+fn state_bytes(state: State) -> [u8; 3] {
+    match state {
+        State::Running => [0, 0, 0],
+        State::WaitingForKey(x) => [1, x as u8, 0],
+        State::WaitingForRelease(x, key) => [2, x as u8, key],
+    }
+}
+
+fn read_state(reader: &mut Reader) -> io::Result<State> {
+    match reader.take(3)? {
+        [0, _, _] => Ok(State::Running),
+        [1, x, _] => Ok(State::WaitingForKey(*x as usize & 0xF)),
+        [2, x, key] => Ok(State::WaitingForRelease(*x as usize & 0xF, *key)),
+        _ => Err(invalid("Unknown machine state")),
+    }
+}
+
+/// Read the stack pointer and the stack slots of the CPU chunk and of version 1.
+fn read_cpu_stack(reader: &mut Reader) -> io::Result<Vec<u16>> {
+    let sp = reader.u16()? as usize;
+    let mut slots = [0; CPU_STACK_SLOTS];
+    for address in slots.iter_mut() {
+        *address = reader.u16()?;
+    }
+
+    Ok(slots[1..=sp.min(CPU_STACK_SLOTS - 1)].to_vec())
+}
+
+fn read_thumbnail(reader: &mut Reader) -> io::Result<Vec<bool>> {
+    let thumbnail = reader.take(THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT / 8)?
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |bit| byte >> bit & 1 == 1))
+        .collect();
+
+    Ok(thumbnail)
+}
+
+fn read_display(reader: &mut Reader) -> io::Result<[u64; HEIGHT]> {
+    let mut rows = [0; HEIGHT];
+
+    for row in rows.iter_mut() {
+        for _ in 0..WIDTH {
+            *row = *row << 1 | (reader.u32()? > 0) as u64;
+        }
+    }
+
+    Ok(rows)
+}
+
+fn thumbnail(display: &[u64; HEIGHT]) -> Vec<bool> {
+    let mut thumbnail = vec!(false; THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT);
+
+    for (y, row) in display.iter().enumerate() {
+        for x in 0..WIDTH {
+            if row >> (WIDTH - 1 - x) & 1 == 1 {
+                thumbnail[x / 4 + y / 4 * THUMBNAIL_WIDTH] = true;
+            }
+        }
+    }
+
+    thumbnail
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        let bytes = self.bytes.get(self.position..self.position + length)
+            .ok_or_else(|| invalid("Save state is truncated"))?;
+        self.position += length;
+
+        Ok(bytes)
+    }
+
+    /// Read the tag and contents of the next chunk.
+    fn chunk(&mut self) -> io::Result<([u8; 4], &'a [u8])> {
+        let mut tag = [0; 4];
+        tag.copy_from_slice(self.take(4)?);
+        let length = self.u32()? as usize;
+
+        Ok((tag, self.take(length)?))
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let mut bytes = [0; 2];
+        bytes.copy_from_slice(self.take(2)?);
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+/// The save state files of one ROM, named `<rom>.<slot>.state`.
+pub struct SlotStore {
+    dir: PathBuf,
+    rom_name: String,
+}
+
+impl SlotStore {
+    pub fn new(dir: PathBuf, rom_name: &str) -> SlotStore {
+        SlotStore { dir, rom_name: rom_name.to_string() }
+    }
+
+    fn path(&self, slot: usize) -> PathBuf {
+        self.dir.join(format!("{}.{}.state", self.rom_name, slot))
+    }
+
+    pub fn save(&self, slot: usize, state: &SaveState) -> io::Result<()> {
+        fs::write(self.path(slot), state.to_bytes())
+    }
+
+    pub fn load(&self, slot: usize) -> io::Result<SaveState> {
+        SaveState::from_bytes(&fs::read(self.path(slot))?)
+    }
+
+    /// Save the state a run ended in, as `<rom>.<reason>.state` next to the slots, so the
+    /// last one of every kind of ending is kept. Returns where it was saved.
+    pub fn autosave(&self, reason: ShutdownReason, state: &SaveState) -> io::Result<PathBuf> {
+        let path = self.dir.join(format!("{}.{}.state", self.rom_name, reason));
+        fs::write(&path, state.to_bytes())?;
+
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::Cpu;
+    use crate::stack::Stack;
+
+    #[test]
+    fn test_save_state_round_trip() {
+        let mut chip8 = Chip8::new();
+        chip8.load_bytes(&[0x60, 0x2A]);
+        chip8.registers[3] = 7;
+        chip8.state = State::WaitingForRelease(2, 0xB);
+        chip8.display.draw_sprite(5, 9, &[0x80]);
+
+        let bytes = SaveState::capture(&chip8).to_bytes();
+        let state = SaveState::from_bytes(&bytes).unwrap();
+
+        let mut restored = Chip8::new();
+        state.restore(&mut restored);
+
+        assert_eq!(restored.registers[3], 7);
+        assert_eq!(restored.memory[0x200], 0x60);
+        assert_eq!(restored.state, State::WaitingForRelease(2, 0xB));
+        assert_eq!(restored.display.rows(), chip8.display.rows());
+        assert!(state.thumbnail[1 + 2 * THUMBNAIL_WIDTH]);
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut chip8 = Chip8::new();
+        let before = SaveState::capture(&chip8);
+        assert!(before.diff(&before).is_empty());
+
+        chip8.registers[3] = 7;
+        chip8.memory[0x300..0x303].copy_from_slice(&[1, 2, 3]);
+        chip8.memory[0x400] = 0xFF;
+        chip8.display.draw_sprite(0, 0, &[0xC0]);
+
+        assert_eq!(before.diff(&SaveState::capture(&chip8)), vec!(
+            "V3: 00 -> 07",
+            "Display: 2 pixels differ",
+            "Memory 0x300-0x302: 00 00 00 -> 01 02 03",
+            "Memory 0x400-0x400: 00 -> FF",
+        ));
+    }
+
+    #[test]
+    fn test_deep_stack() {
+        let mut chip8 = Chip8::new();
+        chip8.stack = Stack::new(32);
+        for address in 0..20 {
+            chip8.push(0x200 + address);
+        }
+
+        let state = SaveState::from_bytes(&SaveState::capture(&chip8).to_bytes()).unwrap();
+        let mut restored = Chip8::new();
+        state.restore(&mut restored);
+
+        assert_eq!(restored.stack.entries(), chip8.stack.entries());
+        assert_eq!(restored.pop(), 0x213);
+    }
+
+    #[test]
+    fn test_truncated_save_state() {
+        let bytes = SaveState::capture(&Chip8::new()).to_bytes();
+
+        assert!(SaveState::from_bytes(&bytes[..100]).is_err());
+    }
+
+    #[test]
+    fn test_unknown_chunks_are_skipped() {
+        let mut chip8 = Chip8::new();
+        chip8.pitch = 80;
+
+        let mut bytes = SaveState::capture(&chip8).to_bytes();
+        write_chunk(&mut bytes, b"NEW!", &[1, 2, 3]);
+
+        let state = SaveState::from_bytes(&bytes).unwrap();
+        assert_eq!(state.pitch, 80);
+
+        let tags: Vec<String> = chunk_list(&bytes).unwrap()
+            .into_iter()
+            .map(|(tag, _)| tag)
+            .collect();
+        assert_eq!(tags, vec!("META", "CPU ", "MEM ", "DISP", "XOCH", "NEW!"));
+    }
+
+    #[test]
+    fn test_missing_chunk() {
+        let bytes = SaveState::capture(&Chip8::new()).to_bytes();
+        let cpu = bytes.windows(4).position(|tag| tag == CPU_CHUNK).unwrap();
+
+        let mut renamed = bytes.clone();
+        renamed[cpu..cpu + 4].copy_from_slice(b"CPU?");
+
+        assert!(SaveState::from_bytes(&renamed).is_err());
+    }
+}
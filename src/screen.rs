@@ -1,4 +1,19 @@
-use minifb::{Key, WindowOptions, Window, Scale, KeyRepeat};
+use std::{cell::{Cell, RefCell}, rc::Rc};
+
+use minifb::{Key, WindowOptions, Window, Scale, InputCallback, MouseButton, MouseMode};
+use crate::accessibility::Accessibility;
+use crate::chip8x::SECOND_KEYPAD;
+use crate::display::{shift_left, shift_right};
+use crate::filters::{self, Filter};
+use crate::grid::GridOverlay;
+use crate::layout::host_key_label;
+use crate::palette::Palette;
+use crate::rotation::Rotation;
+use crate::text::{glyph, GLYPH_WIDTH, CHAR_WIDTH};
+#[cfg(feature = "image")]
+use image::{GrayImage, RgbaImage, Luma, Rgba};
+
+const TITLE: &str = "CHIP-8 - ESC for menu";
 
 const CHAR_0: [u8; 5] = [
     0b01100000,
@@ -24,7 +39,7 @@ const SPRITE: [u8; 5] = [
     0b01111110,
 ];
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Point {
     x: usize,
     y: usize,
@@ -37,13 +52,108 @@ impl Point {
             y,
         }
     }
+
+    pub fn x(&self) -> usize {
+        self.x
+    }
+
+    pub fn y(&self) -> usize {
+        self.y
+    }
+}
+
+/// A rectangle of pixels in a buffer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    /// The smallest rectangle covering both rectangles.
+    pub fn union(self, other: Rect) -> Rect {
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+
+        Rect::new(x, y, right - x, bottom - y)
+    }
+
+    /// Extend the rectangle by `n` pixels on every side, clamped to a buffer of the given size.
+    pub fn grow(self, n: usize, width: usize, height: usize) -> Rect {
+        let (x, y) = (self.x.saturating_sub(n), self.y.saturating_sub(n));
+        let right = (self.x + self.width + n).min(width);
+        let bottom = (self.y + self.height + n).min(height);
+
+        Rect::new(x, y, right - x, bottom - y)
+    }
+}
+
+/// Where a buffer is drawn in a window of a different size: scaled up by a whole number, so
+/// every pixel stays sharp and square, and centred between black bars.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Letterbox {
+    factor: usize,
+    offset: Point,
+    source: (usize, usize),
+    target: (usize, usize),
+}
+
+impl Letterbox {
+    /// The largest whole scale of a `source` sized buffer that fits in `target`. A target
+    /// smaller than the source shows its top left corner instead.
+    pub fn fit(source: (usize, usize), target: (usize, usize)) -> Letterbox {
+        let factor = (target.0 / source.0).min(target.1 / source.1).max(1);
+        let offset = Point::new(
+            target.0.saturating_sub(source.0 * factor) / 2,
+            target.1.saturating_sub(source.1 * factor) / 2);
+
+        Letterbox { factor, offset, source, target }
+    }
+
+    /// Whether the buffer fills the target exactly, so it can be shown as it is.
+    pub fn is_identity(&self) -> bool {
+        self.source == self.target
+    }
+
+    /// Draw `source` scaled up into `target`, with black bars around it.
+    pub fn present(&self, source: &Buffer, target: &mut Buffer) {
+        for y in 0..target.height {
+            for x in 0..target.width {
+                target.pixels[x + y * target.width] = match self.to_source(x, y) {
+                    Some((x, y)) => source.pixels[x + y * source.width],
+                    None => 0,
+                };
+            }
+        }
+    }
+
+    /// The pixel of the source buffer at a position in the target, or `None` on the bars.
+    pub fn to_source(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        let x = x.checked_sub(self.offset.x)? / self.factor;
+        let y = y.checked_sub(self.offset.y)? / self.factor;
+
+        if x < self.source.0 && y < self.source.1 {
+            Some((x, y))
+        } else {
+            None
+        }
+    }
 }
 
+#[derive(Debug, Clone)]
 pub struct Buffer {
     width: usize,
     height: usize,
     pixels: Vec<u32>,
-    pub dirty: bool,
+    // The part of the buffer that changed since it was last drawn, if any
+    damage: Option<Rect>,
 }
 
 impl Buffer {
@@ -60,7 +170,7 @@ impl Buffer {
                 Some(p) => p,
                 None => empty_pixels,
             },
-            dirty: true,
+            damage: Some(Rect::new(0, 0, width, height)),
         }
     }
 
@@ -79,7 +189,7 @@ impl Buffer {
                 );
             */
 
-            if offset.x + target_x > buffer.width || offset.y + target_y > buffer.height {
+            if offset.x + target_x >= self.width || offset.y + target_y >= self.height {
                 continue;
             }
 
@@ -88,7 +198,68 @@ impl Buffer {
             self.pixels[draw_idx] = *pixel;
         }
 
-        self.dirty = true;
+        if offset.x < self.width && offset.y < self.height {
+            let width = buffer.width.min(self.width - offset.x);
+            let height = buffer.height.min(self.height - offset.y);
+            self.add_damage(Rect::new(offset.x, offset.y, width, height));
+        }
+    }
+
+    /// Whether anything changed since the damage was last taken.
+    pub fn is_dirty(&self) -> bool {
+        self.damage.is_some()
+    }
+
+    /// Mark the whole buffer as changed, so it is drawn again in full.
+    pub fn mark_dirty(&mut self) {
+        self.damage = Some(Rect::new(0, 0, self.width, self.height));
+    }
+
+    /// The part of the buffer that changed since the previous call, if any.
+    pub fn take_damage(&mut self) -> Option<Rect> {
+        self.damage.take()
+    }
+
+    fn add_damage(&mut self, rect: Rect) {
+        self.damage = Some(match self.damage {
+            Some(damage) => damage.union(rect),
+            None => rect,
+        });
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    /// Set a single pixel, ignoring coordinates outside the buffer.
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: u32) {
+        if x < self.width && y < self.height {
+            self.pixels[x + y * self.width] = color;
+            self.add_damage(Rect::new(x, y, 1, 1));
+        }
+    }
+
+    /// Draw a line of text with the debug font, clipping at the edges of the buffer.
+    pub fn draw_text(&mut self, text: &str, position: Point, color: u32) {
+        for (idx, c) in text.chars().enumerate() {
+            let left = position.x + idx * CHAR_WIDTH;
+
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits >> (GLYPH_WIDTH - 1 - column) & 1 == 1 {
+                        self.set_pixel(left + column, position.y + row, color);
+                    }
+                }
+            }
+        }
     }
 
     pub fn clear(&mut self) {
@@ -98,71 +269,774 @@ impl Buffer {
         }
 
         self.pixels = empty_pixels;
+        self.mark_dirty();
+    }
+
+    /// Move every pixel `n` columns to the left, clearing the columns shifted in on the right.
+    pub fn scroll_left(&mut self, n: usize) {
+        for row in self.pixels.chunks_mut(self.width) {
+            shift_left(row, n);
+        }
+
+        self.mark_dirty();
+    }
+
+    /// Move every pixel `n` columns to the right, clearing the columns shifted in on the left.
+    pub fn scroll_right(&mut self, n: usize) {
+        for row in self.pixels.chunks_mut(self.width) {
+            shift_right(row, n);
+        }
+
+        self.mark_dirty();
+    }
+
+    /// Move every pixel `n` rows up, clearing the rows shifted in at the bottom.
+    pub fn scroll_up(&mut self, n: usize) {
+        shift_left(&mut self.pixels, n.saturating_mul(self.width));
+        self.mark_dirty();
+    }
+
+    /// Move every pixel `n` rows down, clearing the rows shifted in at the top.
+    pub fn scroll_down(&mut self, n: usize) {
+        shift_right(&mut self.pixels, n.saturating_mul(self.width));
+        self.mark_dirty();
     }
+
+    /// Show the pixels in the colours of a palette.
+    pub fn recolor(&mut self, palette: Palette) {
+        if palette != Palette::DEFAULT {
+            self.pixels.iter_mut().for_each(|pixel| *pixel = palette.color(*pixel));
+            self.mark_dirty();
+        }
+    }
+
+    /// Swap light and dark, turning every colour into its complement.
+    pub fn invert(&mut self) {
+        self.pixels.iter_mut().for_each(|pixel| *pixel ^= 0xFFFFFF);
+        self.mark_dirty();
+    }
+
+    /// Encode the buffer as a binary PGM image, where every lit pixel becomes white.
+    pub fn to_pgm(&self) -> Vec<u8> {
+        let mut pgm = format!("P5\n{} {}\n255\n", self.width, self.height).into_bytes();
+
+        pgm.extend(self.pixels.iter().map(|&pixel| if pixel > 0 { 255 } else { 0 }));
+
+        pgm
+    }
+
+    /// Convert the buffer to a grayscale image, where every lit pixel becomes white.
+    #[cfg(feature = "image")]
+    pub fn to_gray_image(&self) -> GrayImage {
+        GrayImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let pixel = self.pixels[x as usize + y as usize * self.width];
+
+            Luma([if pixel > 0 { 255 } else { 0 }])
+        })
+    }
+
+    /// Convert the buffer to an RGBA image, keeping the colours as they are shown in the window.
+    #[cfg(feature = "image")]
+    pub fn to_rgba_image(&self) -> RgbaImage {
+        RgbaImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let pixel = self.pixels[x as usize + y as usize * self.width];
+
+            Rgba([(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8, 255])
+        })
+    }
+}
+
+/// Records keypad keys typed since the last poll, so taps that are pressed and released
+/// between two frames are not lost.
+struct TapLatch {
+    // Bit n is set when keypad key n was typed
+    taps: Rc<Cell<u16>>,
+    keymap: [Key; 16],
+    // The text typed for the prompts, when they take their input from this window
+    text: Option<Rc<RefCell<String>>>,
+}
+
+impl InputCallback for TapLatch {
+    fn add_char(&mut self, uni_char: u32) {
+        let typed = match std::char::from_u32(uni_char) {
+            Some(c) => {
+                if let Some(text) = &self.text {
+                    text.borrow_mut().push(c);
+                }
+                c.to_ascii_uppercase().to_string()
+            },
+            None => return,
+        };
+
+        if let Some(key) = (0..16).find(|&key| host_key_label(self.keymap[key]) == typed) {
+            self.taps.set(self.taps.get() | 1 << key);
+        }
+    }
+}
+
+/// Records the text typed into the debugger window, or another window with prompts.
+pub struct TextLatch {
+    pub text: Rc<RefCell<String>>,
+}
+
+impl InputCallback for TextLatch {
+    fn add_char(&mut self, uni_char: u32) {
+        if let Some(c) = std::char::from_u32(uni_char) {
+            self.text.borrow_mut().push(c);
+        }
+    }
+}
+
+/// A second window showing only the game display, for capturing it while streaming or
+/// recording a video. Nothing the emulator draws over the game shows up in it, such as
+/// warnings, the pixel grid or the menus, while the upscaling filter, rotation and colours
+/// are the same as in the main window.
+struct CleanFeed {
+    window: Window,
+    // The game display before anything was drawn over it
+    game: Buffer,
+    changed: bool,
+    // The size of the window the frame was last drawn for, in pixels of the buffer
+    size: (usize, usize),
 }
 
 pub struct Screen {
     buffer: Buffer,
+    // What is shown in the window after resizing, `buffer` scaled up and letterboxed
+    presented: Buffer,
+    letterbox: Letterbox,
+    // The pixels of the window for every pixel of `presented`
+    scale: usize,
     pub game_buffer: Buffer,
     pub debug_buffer: Buffer,
 
     pub window: Window,
+    // The debug panels get their own window in multi-window mode, until it is closed
+    debug_window: Option<Window>,
+    taps: Rc<Cell<u16>>,
+    // Typed into the window the prompts take their input from, see `text_input`
+    text: Rc<RefCell<String>>,
+    // The host key of every CHIP-8 key, see `layout`
+    keymap: [Key; 16],
+    filter: Filter,
+    accessibility: Accessibility,
+    rotation: Rotation,
+    palette: Palette,
+    // Drawn over the scaled up game display, see `grid`
+    grid: Option<GridOverlay>,
+    clean_feed: Option<CleanFeed>,
+    // Shown in the title after the name, see `set_status`
+    status: Option<String>,
 }
 
 impl Screen {
+    /// Open the window, with the debug panel to the right of the game or, when
+    /// `separate_debugger` is set, in a second window.
+    ///
+    /// The window buffer has `filters::FACTOR` times the resolution of the game and debug
+    /// buffers, so the game can be drawn with an upscaling filter. When the window is
+    /// resized, the buffer is scaled up by a whole number to fit and letterboxed.
     pub fn new(
             game_width: usize, game_height: usize,
-            debug_width: usize, debug_height: usize) -> Screen {
+            debug_width: usize, debug_height: usize,
+            separate_debugger: bool, filter: Filter, keymap: [Key; 16],
+            accessibility: Accessibility) -> Screen {
 
-        let total_width = game_width + debug_width;
-        let total_height = game_height + debug_height;
+        let (total_width, total_height) = if separate_debugger {
+            (game_width, game_height)
+        } else {
+            (game_width + debug_width, game_height.max(debug_height))
+        };
+        let (total_width, total_height) =
+            (total_width * filters::FACTOR, total_height * filters::FACTOR);
 
         let buffer = Buffer::new(total_width, total_height, None);
         let game_buffer = Buffer::new(game_width, game_height, None);
         let debug_buffer = Buffer::new(debug_width, debug_height, None);
 
+        // The game has the window to itself, so it can be shown larger
+        let (scale, window_scale) = match (separate_debugger, accessibility.large_scale) {
+            (false, false) => (Scale::X2, 2),
+            (true, false) | (false, true) => (Scale::X4, 4),
+            (true, true) => (Scale::X8, 8),
+        };
+        // Other filters blur the pixels when scaled up this far
+        let filter = if accessibility.large_scale { Filter::Nearest } else { filter };
+
         // Prepare frame buffer
         let mut window = Window::new(
-            "CHIP-8 - ESC to exit",
+            TITLE,
             total_width, total_height,
             WindowOptions {
-                resize: false,
-                scale: Scale::X4,
+                resize: true,
+                scale,
                 ..WindowOptions::default()
             })
             .unwrap_or_else(|e| { panic!("{}", e); });
 
+        let text = Rc::new(RefCell::new(String::new()));
+        let debug_window = if separate_debugger {
+            let mut window = Window::new(
+                "CHIP-8 debugger",
+                debug_width, debug_height,
+                WindowOptions {
+                    resize: false,
+                    scale: Scale::X4,
+                    ..WindowOptions::default()
+                })
+                .unwrap_or_else(|e| { panic!("{}", e); });
+            window.set_input_callback(Box::new(TextLatch { text: text.clone() }));
+
+            Some(window)
+        } else {
+            None
+        };
+
+        let taps = Rc::new(Cell::new(0));
+        let latch = TapLatch {
+            taps: taps.clone(),
+            keymap,
+            text: if separate_debugger { None } else { Some(text.clone()) },
+        };
+        window.set_input_callback(Box::new(latch));
+
+        let size = (total_width, total_height);
         Screen {
+            presented: Buffer::new(total_width, total_height, None),
+            letterbox: Letterbox::fit(size, size),
+            scale: window_scale,
             buffer,
             game_buffer,
             debug_buffer,
             window,
+            debug_window,
+            taps,
+            text,
+            keymap,
+            filter,
+            accessibility,
+            rotation: Rotation::None,
+            palette: Palette::DEFAULT,
+            grid: None,
+            clean_feed: None,
+            status: None,
         }
     }
 
+    /// Show a status in the window title instead of the hint for the menu, such as the
+    /// instruction the machine is paused at.
+    pub fn set_status(&mut self, status: Option<String>) {
+        if status == self.status {
+            return;
+        }
+
+        match &status {
+            Some(status) => self.window.set_title(&format!("CHIP-8 - {}", status)),
+            None => self.window.set_title(TITLE),
+        }
+        self.status = status;
+    }
+
+    /// Open the clean feed window, see `CleanFeed`. It stays closed once the user closed it.
+    pub fn open_clean_feed(&mut self) {
+        let (width, height) = (self.game_buffer.width * filters::FACTOR,
+            self.game_buffer.height * filters::FACTOR);
+        let window = Window::new(
+            "CHIP-8 clean feed",
+            width, height,
+            WindowOptions {
+                resize: true,
+                scale: Scale::X4,
+                ..WindowOptions::default()
+            })
+            .unwrap_or_else(|e| { panic!("{}", e); });
+
+        self.clean_feed = Some(CleanFeed {
+            window,
+            game: self.game_buffer.clone(),
+            changed: true,
+            size: (width, height),
+        });
+    }
+
+    /// Take the game display for the clean feed, once the display was rendered and before
+    /// anything is drawn over it.
+    pub fn capture_clean_feed(&mut self) {
+        let dirty = self.game_buffer.is_dirty();
+        if let Some(feed) = self.clean_feed.as_mut().filter(|_| dirty) {
+            feed.game = self.game_buffer.clone();
+            feed.changed = true;
+        }
+    }
+
+    /// Switch to the next upscaling filter, returning it. The large scale always uses nearest
+    /// neighbour.
+    pub fn cycle_filter(&mut self) -> Filter {
+        if self.accessibility.large_scale {
+            return self.filter;
+        }
+
+        self.filter = self.filter.next();
+        self.game_buffer.mark_dirty();
+
+        self.filter
+    }
+
+    pub fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    /// Rotate the game display, which then fills the window's height below the game as well.
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        let region = self.game_region();
+        self.buffer.blit(&Buffer::new(region.width, region.height, None), Point::new(0, 0));
+
+        self.rotation = rotation;
+        self.game_buffer.mark_dirty();
+    }
+
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    /// Show the game display in other colours.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.game_buffer.mark_dirty();
+    }
+
+    pub fn palette(&self) -> Palette {
+        self.palette
+    }
+
+    /// Show the overlay for positioning sprites, or hide it with `None`.
+    pub fn set_grid(&mut self, grid: Option<GridOverlay>) {
+        if grid != self.grid {
+            self.grid = grid;
+            self.game_buffer.mark_dirty();
+        }
+    }
+
+    // The part of the window buffer a rotated game display is drawn in
+    fn game_region(&self) -> Rect {
+        Rect::new(0, 0, self.game_buffer.width * filters::FACTOR, self.buffer.height)
+    }
+
+    /// Whether the game window, or the debugger window if there is one, has focus.
+    pub fn is_focused(&mut self) -> bool {
+        let debugger_focused = self.debug_window.as_mut()
+            .map_or(false, |window| window.is_active());
+
+        self.window.is_active() || debugger_focused
+    }
+
+    /// The text typed for the prompts since the previous call.
+    pub fn take_text(&mut self) -> String {
+        self.text.replace(String::new())
+    }
+
+    /// The window that receives the keys for the debug panels.
+    pub fn debug_input(&self) -> &Window {
+        self.debug_window.as_ref().unwrap_or(&self.window)
+    }
+
+    /// The position of the mouse in the pixels of the debug buffer, with whether the left
+    /// button is down, or `None` when the mouse is not over the debug panels.
+    pub fn debug_mouse(&self) -> Option<(usize, usize, bool)> {
+        let window = self.debug_input();
+        let (x, y) = window.get_mouse_pos(MouseMode::Discard)?;
+        let down = window.get_mouse_down(MouseButton::Left);
+
+        // In a single window the debug panels are drawn upscaled, right of the game
+        let (left, factor) = match self.debug_window {
+            Some(_) => (0, 1),
+            None => (self.game_buffer.width * filters::FACTOR, filters::FACTOR),
+        };
+        let (x, y) = match self.debug_window {
+            Some(_) => (x as usize, y as usize),
+            None => self.letterbox.to_source(x as usize, y as usize)?,
+        };
+
+        let x = x.checked_sub(left)? / factor;
+        let y = y / factor;
+
+        if x < self.debug_buffer.width && y < self.debug_buffer.height {
+            Some((x, y, down))
+        } else {
+            None
+        }
+    }
+
+    /// Which CHIP-8 keys are held down, or were tapped since the previous call, with the
+    /// directions turned along with the display.
+    pub fn keypad(&mut self) -> [bool; 16] {
+        let mut keys = [false; 16];
+        let taps = self.taps.replace(0);
+
+        for (key, host_key) in self.keymap.iter().enumerate() {
+            keys[key] = self.window.is_key_down(*host_key) || taps >> key & 1 == 1;
+        }
+
+        self.rotation.remap_keys(keys)
+    }
+
+    /// Which keys of the CHIP-8X second keypad, on the numeric keypad, are held down.
+    pub fn second_keypad(&self) -> [bool; 16] {
+        let mut keys = [false; 16];
+
+        for (key, host_key) in SECOND_KEYPAD.iter().enumerate() {
+            keys[key] = self.window.is_key_down(*host_key);
+        }
+
+        keys
+    }
+
+    /// Draw the parts of the buffers that changed since the previous update to the windows.
     pub fn update(&mut self) {
         // Blit game_buffer and debug_buffer to buffer
-        if self.game_buffer.dirty {
+        if self.rotation != Rotation::None {
+            self.draw_rotated_game();
+        } else if let Some(damage) = self.game_buffer.take_damage() {
             println!("Draw game");
-            self.buffer.blit(&self.game_buffer, Point::new(0, 0));
-            self.game_buffer.dirty = false;
+            // Filters look at the neighbours of a pixel, so those are filtered again as well
+            let (width, height) = (self.game_buffer.width, self.game_buffer.height);
+            let region = damage.grow(1, width, height);
+            let mut scaled = self.filter.apply_region(&self.game_buffer, region);
+            scaled.recolor(self.palette);
+            if self.accessibility.invert_colors {
+                scaled.invert();
+            }
+            let offset = Point::new(region.x * filters::FACTOR, region.y * filters::FACTOR);
+            self.buffer.blit(&scaled, offset);
+
+            if let Some(grid) = &self.grid {
+                grid.draw(&mut self.buffer, Rect::new(offset.x, offset.y, scaled.width,
+                    scaled.height));
+            }
         }
 
-        if self.debug_buffer.dirty {
+        if let Some(debug_window) = self.debug_window.as_mut() {
+            if self.debug_buffer.take_damage().is_some() {
+                debug_window.update_with_buffer(&self.debug_buffer.pixels);
+            } else {
+                debug_window.update();
+            }
+
+            // Closing the debugger leaves the game running
+            if !debug_window.is_open() {
+                self.debug_window = None;
+            }
+        } else if let Some(damage) = self.debug_buffer.take_damage() {
             println!("Draw game");
-            self.buffer.blit(&self.debug_buffer, Point::new(self.game_buffer.width, 0));
-            self.debug_buffer.dirty = false;
+            let left = self.game_buffer.width * filters::FACTOR;
+            let scaled = Filter::Nearest.apply_region(&self.debug_buffer, damage);
+            let offset = Point::new(left + damage.x * filters::FACTOR, damage.y * filters::FACTOR);
+            self.buffer.blit(&scaled, offset);
         }
 
-        if self.buffer.dirty {
-            // Update window with buffer
-            self.window.update_with_buffer(&self.buffer.pixels);
+        self.update_clean_feed();
+        self.fit_to_window();
 
-            // Clear dirty flag
-            self.buffer.dirty = false;
+        if self.buffer.take_damage().is_some() {
+            // Update window with buffer
+            let pixels = if self.letterbox.is_identity() {
+                &self.buffer.pixels
+            } else {
+                self.letterbox.present(&self.buffer, &mut self.presented);
+                &self.presented.pixels
+            };
+            self.window.update_with_buffer(pixels);
         } else {
             // TODO: Update window
             self.window.update();
         }
     }
+
+    /// Draw the game display captured for the clean feed to its window, scaled up as far as
+    /// it fits.
+    fn update_clean_feed(&mut self) {
+        let feed = match self.clean_feed.as_mut() {
+            Some(feed) => feed,
+            None => return,
+        };
+
+        if !feed.window.is_open() {
+            self.clean_feed = None;
+            return;
+        }
+
+        // The window shows every pixel of the buffer four times as large
+        let (width, height) = feed.window.get_size();
+        let size = (width / 4, height / 4);
+        if (!feed.changed && size == feed.size) || size.0 == 0 || size.1 == 0 {
+            feed.window.update();
+            return;
+        }
+
+        let mut frame = if self.rotation != Rotation::None {
+            self.rotation.apply(&feed.game)
+        } else {
+            let whole = Rect::new(0, 0, feed.game.width, feed.game.height);
+            self.filter.apply_region(&feed.game, whole)
+        };
+        frame.recolor(self.palette);
+        if self.accessibility.invert_colors {
+            frame.invert();
+        }
+
+        let mut presented = Buffer::new(size.0, size.1, None);
+        Letterbox::fit((frame.width, frame.height), size).present(&frame, &mut presented);
+        // Drawn again on the next update when it failed
+        if feed.window.update_with_buffer(&presented.pixels).is_ok() {
+            feed.changed = false;
+            feed.size = size;
+        }
+    }
+
+    /// Draw all of the game display turned, scaled up as far as it fits in its region.
+    fn draw_rotated_game(&mut self) {
+        if self.game_buffer.take_damage().is_none() {
+            return;
+        }
+
+        let mut rotated = self.rotation.apply(&self.game_buffer);
+        rotated.recolor(self.palette);
+        if self.accessibility.invert_colors {
+            rotated.invert();
+        }
+
+        let region = self.game_region();
+        let mut scaled = Buffer::new(region.width, region.height, None);
+        Letterbox::fit((rotated.width, rotated.height), (region.width, region.height))
+            .present(&rotated, &mut scaled);
+        self.buffer.blit(&scaled, Point::new(region.x, region.y));
+    }
+
+    /// Handle the window events without drawing anything, for frames that are skipped. The
+    /// buffers keep their changes until the next update.
+    pub fn poll(&mut self) {
+        self.window.update();
+
+        if let Some(debug_window) = self.debug_window.as_mut() {
+            debug_window.update();
+
+            if !debug_window.is_open() {
+                self.debug_window = None;
+            }
+        }
+    }
+
+    /// Recompute the letterbox when the window was resized, redrawing all of it.
+    fn fit_to_window(&mut self) {
+        let (width, height) = self.window.get_size();
+        let size = (width / self.scale, height / self.scale);
+
+        // A minimized window has no size to fit in
+        if size.0 == 0 || size.1 == 0 || size == (self.presented.width, self.presented.height) {
+            return;
+        }
+
+        self.presented = Buffer::new(size.0, size.1, None);
+        self.letterbox = Letterbox::fit((self.buffer.width, self.buffer.height), size);
+        self.buffer.mark_dirty();
+    }
+
+    /// The full window contents, game and debug panels included, as an RGBA image.
+    #[cfg(feature = "image")]
+    pub fn frame_image(&self) -> RgbaImage {
+        self.buffer.to_rgba_image()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    // 3x3 buffer with the pixels numbered 1 through 9
+    fn numbered() -> Buffer {
+        Buffer::new(3, 3, Some((1..=9).collect()))
+    }
+
+    #[test]
+    fn test_scroll_left() {
+        let mut buffer = numbered();
+        buffer.scroll_left(1);
+
+        assert_eq!(buffer.pixels, vec!(2, 3, 0, 5, 6, 0, 8, 9, 0));
+    }
+
+    #[test]
+    fn test_scroll_right() {
+        let mut buffer = numbered();
+        buffer.scroll_right(2);
+
+        assert_eq!(buffer.pixels, vec!(0, 0, 1, 0, 0, 4, 0, 0, 7));
+    }
+
+    #[test]
+    fn test_scroll_up() {
+        let mut buffer = numbered();
+        buffer.scroll_up(1);
+
+        assert_eq!(buffer.pixels, vec!(4, 5, 6, 7, 8, 9, 0, 0, 0));
+    }
+
+    #[test]
+    fn test_scroll_down() {
+        let mut buffer = numbered();
+        buffer.scroll_down(1);
+
+        assert_eq!(buffer.pixels, vec!(0, 0, 0, 1, 2, 3, 4, 5, 6));
+    }
+
+    #[test]
+    fn test_scroll_past_edge_clears() {
+        let mut buffer = numbered();
+        buffer.scroll_left(5);
+        assert_eq!(buffer.pixels, vec!(0; 9));
+
+        let mut buffer = numbered();
+        buffer.scroll_down(4);
+        assert_eq!(buffer.pixels, vec!(0; 9));
+    }
+
+    #[test]
+    fn test_damage() {
+        let mut buffer = Buffer::new(8, 8, None);
+        assert_eq!(buffer.take_damage(), Some(Rect::new(0, 0, 8, 8)));
+        assert!(!buffer.is_dirty());
+
+        buffer.set_pixel(1, 2, 1);
+        buffer.set_pixel(4, 3, 1);
+        buffer.blit(&numbered(), Point::new(6, 6));
+
+        assert_eq!(buffer.take_damage(), Some(Rect::new(1, 2, 7, 6)));
+        assert_eq!(buffer.take_damage(), None);
+    }
+
+    #[test]
+    fn test_invert() {
+        let mut buffer = Buffer::new(2, 1, Some(vec!(0, 0x123456)));
+        buffer.invert();
+
+        assert_eq!(buffer.pixels, vec!(0xFFFFFF, 0xEDCBA9));
+    }
+
+    #[test]
+    fn test_letterbox_fit() {
+        let letterbox = Letterbox::fit((4, 2), (13, 10));
+        assert_eq!(letterbox.factor, 3);
+        assert_eq!(letterbox.offset, Point::new(0, 2));
+
+        assert_eq!(letterbox.to_source(0, 1), None);
+        assert_eq!(letterbox.to_source(5, 2), Some((1, 0)));
+        assert_eq!(letterbox.to_source(11, 7), Some((3, 1)));
+        assert_eq!(letterbox.to_source(12, 7), None);
+        assert_eq!(letterbox.to_source(11, 8), None);
+
+        assert!(Letterbox::fit((4, 2), (4, 2)).is_identity());
+        assert_eq!(Letterbox::fit((4, 2), (3, 1)).factor, 1);
+    }
+
+    #[test]
+    fn test_letterbox_present() {
+        let mut target = Buffer::new(7, 6, Some(vec!(7; 42)));
+        Letterbox::fit((3, 3), (7, 6)).present(&numbered(), &mut target);
+
+        assert_eq!(&target.pixels[..7], &[1, 1, 2, 2, 3, 3, 0]);
+        assert_eq!(&target.pixels[35..], &[7, 7, 8, 8, 9, 9, 0]);
+    }
+
+    #[test]
+    fn test_rect_grow_clamps() {
+        let rect = Rect::new(0, 3, 2, 2).grow(1, 4, 5);
+
+        assert_eq!(rect, Rect::new(0, 2, 3, 3));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_to_gray_image() {
+        let buffer = Buffer::new(2, 2, Some(vec!(0, 255, 0xFFFFFF, 0)));
+        let result = buffer.to_gray_image().into_raw();
+        let expected = vec!(0, 255, 255, 0);
+
+        assert_eq!(result, expected);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_to_rgba_image() {
+        let buffer = Buffer::new(1, 1, Some(vec!(0x123456)));
+        let result = buffer.to_rgba_image().into_raw();
+        let expected = vec!(0x12, 0x34, 0x56, 255);
+
+        assert_eq!(result, expected);
+    }
+
+    type Scroll = fn(&mut Buffer, usize);
+
+    // A buffer of up to 24x24 pixels, with every pixel set to a random colour
+    fn any_buffer() -> impl Strategy<Value = Buffer> {
+        (1..24usize, 1..24usize).prop_flat_map(|(width, height)| {
+            prop::collection::vec(any::<u32>(), width * height)
+                .prop_map(move |pixels| Buffer::new(width, height, Some(pixels)))
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_blit_only_touches_its_rect(mut target in any_buffer(), source in any_buffer(),
+                x in 0..30usize, y in 0..30usize) {
+            let before = target.pixels.clone();
+            target.blit(&source, Point::new(x, y));
+
+            for ty in 0..target.height {
+                for tx in 0..target.width {
+                    let pixel = target.pixels[tx + ty * target.width];
+                    let inside = tx >= x && tx < x + source.width
+                        && ty >= y && ty < y + source.height;
+
+                    if inside {
+                        prop_assert_eq!(pixel, source.pixels[tx - x + (ty - y) * source.width]);
+                    } else {
+                        prop_assert_eq!(pixel, before[tx + ty * target.width]);
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn prop_scroll_moves_every_pixel(buffer in any_buffer(), n in 0..30usize) {
+            let (width, height) = (buffer.width as isize, buffer.height as isize);
+            let pixel = |x: isize, y: isize| {
+                if x < 0 || x >= width || y < 0 || y >= height {
+                    0
+                } else {
+                    buffer.pixels[(x + y * width) as usize]
+                }
+            };
+            let n = n as isize;
+
+            let scrolls: [(Scroll, isize, isize); 4] = [
+                (Buffer::scroll_left, n, 0),
+                (Buffer::scroll_right, -n, 0),
+                (Buffer::scroll_up, 0, n),
+                (Buffer::scroll_down, 0, -n),
+            ];
+            for &(scroll, dx, dy) in &scrolls {
+                let mut scrolled = Buffer::new(buffer.width, buffer.height,
+                    Some(buffer.pixels.clone()));
+                scroll(&mut scrolled, n as usize);
+
+                for y in 0..height {
+                    for x in 0..width {
+                        prop_assert_eq!(scrolled.pixels[(x + y * width) as usize],
+                            pixel(x + dx, y + dy));
+                    }
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,96 @@
+//! Memory search: finding the address of a game variable, such as the number of lives, by
+//! how its value changes while playing.
+//!
+//! Take a snapshot of memory, play until the variable changes, then keep only the addresses
+//! that changed (or stayed the same, went up or went down) since the snapshot. Every filter
+//! takes a new snapshot, so repeating this narrows the candidates down to a few addresses.
+
+use crate::MEMORY;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl Filter {
+    fn keeps(self, before: u8, after: u8) -> bool {
+        match self {
+            Filter::Changed => after != before,
+            Filter::Unchanged => after == before,
+            Filter::Increased => after > before,
+            Filter::Decreased => after < before,
+        }
+    }
+}
+
+pub struct MemorySearch {
+    snapshot: Vec<u8>,
+    // None until the first snapshot is taken
+    candidates: Option<Vec<u16>>,
+}
+
+impl MemorySearch {
+    pub fn new() -> MemorySearch {
+        MemorySearch {
+            snapshot: Vec::new(),
+            candidates: None,
+        }
+    }
+
+    /// Start a new search, where every address is a candidate.
+    pub fn start(&mut self, memory: &[u8]) {
+        self.snapshot = memory.to_vec();
+        self.candidates = Some((0..MEMORY as u16).collect());
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.candidates.is_some()
+    }
+
+    /// Keep the candidates whose value changed as described since the last snapshot.
+    pub fn filter(&mut self, memory: &[u8], filter: Filter) {
+        let snapshot = &self.snapshot;
+
+        if let Some(candidates) = self.candidates.as_mut() {
+            candidates.retain(|&address| {
+                let address = address as usize;
+                filter.keeps(snapshot[address], memory[address])
+            });
+        }
+
+        self.snapshot = memory.to_vec();
+    }
+
+    pub fn candidates(&self) -> &[u16] {
+        self.candidates.as_ref().map_or(&[], Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_narrow_down_lives() {
+        let mut memory = [0; MEMORY];
+        memory[0x2F0] = 3;
+        memory[0x2F1] = 7;
+
+        let mut search = MemorySearch::new();
+        search.start(&memory);
+
+        // Lose a life while a timer elsewhere goes up
+        memory[0x2F0] = 2;
+        memory[0x2F1] = 8;
+        search.filter(&memory, Filter::Changed);
+        assert_eq!(search.candidates(), &[0x2F0, 0x2F1]);
+
+        memory[0x2F0] = 1;
+        memory[0x2F1] = 9;
+        search.filter(&memory, Filter::Decreased);
+        assert_eq!(search.candidates(), &[0x2F0]);
+    }
+}
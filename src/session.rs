@@ -0,0 +1,122 @@
+//! Resuming where the player left off.
+//!
+//! With `save_session` enabled, the ROM and a snapshot of the machine are stored in the
+//! session directory on exit. Launched again without any arguments, the emulator opens
+//! that ROM and asks on the game display whether to resume from the snapshot or to start
+//! over.
+
+use std::{fs, io, path::Path};
+
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::{archive, Chip8};
+use crate::locale::{tr, Text};
+use crate::savestate::SaveState;
+use crate::screen::{Buffer, Point};
+use crate::text::LINE_HEIGHT;
+
+const TITLE_COLOR: u32 = 0xFF4040;
+const TEXT_COLOR: u32 = 0xFFFFFF;
+const BACKGROUND_COLOR: u32 = 0x202020;
+
+/// The ROM that was running on exit, and the state it was left in.
+pub struct Session {
+    pub rom: String,
+    pub state: SaveState,
+}
+
+impl Session {
+    /// Store the session, with the ROM as an absolute path so it is found again from
+    /// another working directory.
+    pub fn save(dir: &Path, rom: &str, chip8: &Chip8) -> io::Result<()> {
+        let rom = match archive::split_entry(rom) {
+            (file, Some(entry)) => format!("{}:{}", fs::canonicalize(file)?.display(), entry),
+            (file, None) => fs::canonicalize(file)?.display().to_string(),
+        };
+
+        fs::write(dir.join("rom"), format!("{}\n", rom))?;
+        fs::write(dir.join("state"), SaveState::capture(chip8).to_bytes())
+    }
+
+    /// The stored session, if there is one that can be read.
+    pub fn load(dir: &Path) -> Option<Session> {
+        let rom = fs::read_to_string(dir.join("rom")).ok()?.trim().to_string();
+        let state = SaveState::from_bytes(&fs::read(dir.join("state")).ok()?).ok()?;
+
+        Some(Session { rom, state })
+    }
+}
+
+/// The on-screen prompt to resume a session.
+///
+/// Y restores the state the ROM was left in, N starts the ROM over.
+pub struct ResumePrompt {
+    state: Option<SaveState>,
+}
+
+impl ResumePrompt {
+    pub fn new(state: Option<SaveState>) -> ResumePrompt {
+        ResumePrompt { state }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state.is_some()
+    }
+
+    pub fn handle_input(&mut self, window: &Window, chip8: &mut Chip8) {
+        if self.state.is_none() {
+            return;
+        }
+
+        if window.is_key_pressed(Key::Y, KeyRepeat::No) {
+            self.state.take().unwrap().restore(chip8);
+            println!("Resumed the last session");
+        } else if window.is_key_pressed(Key::N, KeyRepeat::No) {
+            self.state = None;
+        }
+    }
+
+    pub fn render(&self, buffer: &mut Buffer) {
+        if self.state.is_none() {
+            return;
+        }
+
+        for y in 0..3 * LINE_HEIGHT + 1 {
+            for x in 0..buffer.width() {
+                buffer.set_pixel(x, y, BACKGROUND_COLOR);
+            }
+        }
+
+        buffer.draw_text(tr(Text::ResumeSession), Point::new(1, 1), TITLE_COLOR);
+        buffer.draw_text(tr(Text::Yes), Point::new(1, 1 + LINE_HEIGHT), TEXT_COLOR);
+        buffer.draw_text(tr(Text::StartOver), Point::new(1, 1 + 2 * LINE_HEIGHT), TEXT_COLOR);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = env::temp_dir()
+            .join(format!("chip8-session-{}-test_save_and_load", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let rom = dir.join("game.ch8");
+        fs::write(&rom, [0x60, 0x2A]).unwrap();
+
+        let mut chip8 = Chip8::new();
+        chip8.registers[4] = 9;
+        Session::save(&dir, &rom.to_string_lossy(), &chip8).unwrap();
+
+        let session = Session::load(&dir).unwrap();
+        assert_eq!(Path::new(&session.rom), fs::canonicalize(&rom).unwrap());
+
+        let mut restored = Chip8::new();
+        session.state.restore(&mut restored);
+        assert_eq!(restored.registers[4], 9);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
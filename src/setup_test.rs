@@ -0,0 +1,253 @@
+//! A screen for checking that the keyboard, the timers, the sound and the display work,
+//! opened from the menu or with `--diag`.
+//!
+//! It shows which CHIP-8 key every host key is mapped to while they are held, measures how
+//! fast the timers actually count down, plays a test tone with Space and cycles through
+//! test patterns on the game display with Tab. The machine is frozen meanwhile, and gets
+//! its display and sound timer back once the screen is closed with Escape.
+
+use std::time::{Duration, Instant};
+
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::{Chip8, HEIGHT};
+use crate::layout::{host_key_label, KEYPAD};
+use crate::screen::{Buffer, Point};
+use crate::text::{CHAR_WIDTH, LINE_HEIGHT};
+
+const TITLE_COLOR: u32 = 0xFFFFFF;
+const TEXT_COLOR: u32 = 0xA0A0A0;
+const PRESSED_COLOR: u32 = 0xFFFFFF;
+const PRESSED_BACKGROUND: u32 = 0x304060;
+const CELL_WIDTH: usize = 4 * CHAR_WIDTH;
+// Half a second of tone at 60 Hz
+const TONE_TICKS: u8 = 30;
+// The timers are measured for a while before the rate is shown, so it settles
+const MEASURE_AFTER: Duration = Duration::from_secs(1);
+
+// A test pattern, as the pixels of every row
+type Pattern = fn(usize) -> u64;
+
+const PATTERNS: [(&str, Pattern); 4] = [
+    ("BORDER", |y| if y == 0 || y == HEIGHT - 1 { !0 } else { 1 << 63 | 1 }),
+    ("CHECKERS", |y| if y % 2 == 0 { 0xAAAA_AAAA_AAAA_AAAA } else { 0x5555_5555_5555_5555 }),
+    ("GRID", |y| if y % 8 == 0 { !0 } else { 0x8080_8080_8080_8080 }),
+    ("FULL", |_| !0),
+];
+
+/// The rows of a test pattern.
+fn pattern_rows(pattern: usize) -> [u64; HEIGHT] {
+    let mut rows = [0; HEIGHT];
+    for (y, row) in rows.iter_mut().enumerate() {
+        *row = (PATTERNS[pattern].1)(y);
+    }
+
+    rows
+}
+
+/// The timer ticks per second, once they were counted for long enough.
+fn ticks_per_second(ticks: u32, elapsed: Duration) -> Option<f64> {
+    if elapsed < MEASURE_AFTER {
+        return None;
+    }
+
+    Some(ticks as f64 / elapsed.as_secs_f64())
+}
+
+/// What the machine showed and played before the screen was opened.
+struct Saved {
+    rows: [u64; HEIGHT],
+    sound: u8,
+}
+
+pub struct SetupTest {
+    saved: Option<Saved>,
+    keys: [bool; 16],
+    keymap: [Key; 16],
+    // The timer ticks counted since the screen was opened
+    ticks: u32,
+    opened_at: Instant,
+    tone: u8,
+    pattern: Option<usize>,
+}
+
+impl SetupTest {
+    pub fn new(keymap: [Key; 16]) -> SetupTest {
+        SetupTest {
+            saved: None,
+            keys: [false; 16],
+            keymap,
+            ticks: 0,
+            opened_at: Instant::now(),
+            tone: 0,
+            pattern: None,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.saved.is_some()
+    }
+
+    pub fn open(&mut self, chip8: &mut Chip8) {
+        if self.is_open() {
+            return;
+        }
+
+        self.saved = Some(Saved { rows: *chip8.display.rows(), sound: chip8.timers.sound });
+        self.ticks = 0;
+        self.opened_at = Instant::now();
+        self.tone = 0;
+        self.pattern = None;
+        chip8.restore_sound_timer(0);
+    }
+
+    pub fn close(&mut self, chip8: &mut Chip8) {
+        if let Some(saved) = self.saved.take() {
+            chip8.display.set_rows(saved.rows);
+            chip8.restore_sound_timer(saved.sound);
+        }
+    }
+
+    /// Show the game display or the next test pattern.
+    fn cycle_pattern(&mut self, chip8: &mut Chip8) {
+        let saved = match &self.saved {
+            Some(saved) => saved,
+            None => return,
+        };
+
+        self.pattern = match self.pattern {
+            Some(pattern) if pattern + 1 < PATTERNS.len() => Some(pattern + 1),
+            Some(_) => None,
+            None => Some(0),
+        };
+
+        let rows = self.pattern.map_or(saved.rows, pattern_rows);
+        chip8.display.set_rows(rows);
+    }
+
+    /// Count the timer ticks of a frame, which also run the test tone down.
+    fn record_ticks(&mut self, ticks: u32, chip8: &mut Chip8) {
+        self.ticks += ticks;
+
+        if self.tone > 0 {
+            self.tone = self.tone.saturating_sub(ticks.min(u8::MAX as u32) as u8);
+            chip8.restore_sound_timer(self.tone);
+        }
+    }
+
+    /// Handle the keys of the screen, with the CHIP-8 keys held and the timer ticks that
+    /// were due this frame.
+    pub fn handle_input(&mut self, window: &Window, keys: [bool; 16], timer_ticks: u32,
+            chip8: &mut Chip8) {
+        if !self.is_open() {
+            return;
+        }
+
+        if window.is_key_pressed(Key::Escape, KeyRepeat::No) {
+            self.close(chip8);
+            return;
+        }
+
+        self.keys = keys;
+        self.record_ticks(timer_ticks, chip8);
+
+        if window.is_key_pressed(Key::Space, KeyRepeat::No) {
+            self.tone = TONE_TICKS;
+            chip8.restore_sound_timer(TONE_TICKS);
+        }
+        if window.is_key_pressed(Key::Tab, KeyRepeat::No) {
+            self.cycle_pattern(chip8);
+        }
+    }
+
+    pub fn render(&self, buffer: &mut Buffer) {
+        buffer.clear();
+        buffer.draw_text("SETUP TEST", Point::new(0, 0), TITLE_COLOR);
+
+        for (row, keys) in KEYPAD.iter().enumerate() {
+            for (column, &key) in keys.iter().enumerate() {
+                let left = column * CELL_WIDTH;
+                let top = (row + 1) * LINE_HEIGHT + 1;
+
+                if self.keys[key] {
+                    for y in top - 1..top + LINE_HEIGHT - 1 {
+                        for x in left..left + CELL_WIDTH - 1 {
+                            buffer.set_pixel(x, y, PRESSED_BACKGROUND);
+                        }
+                    }
+                }
+
+                let color = if self.keys[key] { PRESSED_COLOR } else { TEXT_COLOR };
+                let label = format!("{:X}={}", key, host_key_label(self.keymap[key]));
+                buffer.draw_text(&label, Point::new(left, top), color);
+            }
+        }
+
+        let timers = match ticks_per_second(self.ticks, self.opened_at.elapsed()) {
+            Some(rate) => format!("TIMERS {:.1} HZ", rate),
+            None => String::from("TIMERS ..."),
+        };
+        let tone = if self.tone > 0 { "SPACE TONE ON" } else { "SPACE TONE" };
+        let pattern = self.pattern.map_or("GAME", |pattern| PATTERNS[pattern].0);
+
+        let lines = [timers, String::from(tone), format!("TAB {}", pattern),
+            String::from("ESC CLOSE")];
+        for (idx, line) in lines.iter().enumerate() {
+            buffer.draw_text(line, Point::new(0, (idx + 6) * LINE_HEIGHT), TEXT_COLOR);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patterns_and_restore() {
+        let mut chip8 = Chip8::new();
+        chip8.display.draw_sprite(0, 0, &[0xFF]);
+        chip8.timers.sound = 9;
+
+        let mut setup_test = SetupTest::new([Key::X; 16]);
+        setup_test.open(&mut chip8);
+        assert_eq!(chip8.timers.sound, 0);
+
+        setup_test.cycle_pattern(&mut chip8);
+        assert_eq!(chip8.display.rows()[0], !0);
+        assert_eq!(chip8.display.rows()[1], 1 << 63 | 1);
+
+        for _ in 0..PATTERNS.len() {
+            setup_test.cycle_pattern(&mut chip8);
+        }
+        assert_eq!(setup_test.pattern, None);
+        assert_eq!(chip8.display.rows()[0], 0xFF << 56);
+
+        setup_test.cycle_pattern(&mut chip8);
+        setup_test.close(&mut chip8);
+        assert!(!setup_test.is_open());
+        assert_eq!(chip8.display.rows()[0], 0xFF << 56);
+        assert_eq!(chip8.display.rows()[1], 0);
+        assert_eq!(chip8.timers.sound, 9);
+    }
+
+    #[test]
+    fn test_tone_runs_down() {
+        let mut chip8 = Chip8::new();
+        let mut setup_test = SetupTest::new([Key::X; 16]);
+        setup_test.open(&mut chip8);
+
+        setup_test.tone = TONE_TICKS;
+        chip8.restore_sound_timer(TONE_TICKS);
+        setup_test.record_ticks(TONE_TICKS as u32 - 1, &mut chip8);
+        assert_eq!(chip8.timers.sound, 1);
+        setup_test.record_ticks(2, &mut chip8);
+        assert_eq!(chip8.timers.sound, 0);
+        assert_eq!(setup_test.ticks, TONE_TICKS as u32 + 1);
+    }
+
+    #[test]
+    fn test_ticks_per_second() {
+        assert_eq!(ticks_per_second(30, Duration::from_millis(500)), None);
+        assert_eq!(ticks_per_second(120, Duration::from_secs(2)), Some(60.0));
+    }
+}
@@ -0,0 +1,83 @@
+//! Shutting down cleanly, whether the window was closed, Ctrl+C was pressed in the terminal
+//! or the emulator panicked.
+//!
+//! Whatever ends a run, the front end calls `Chip8::shutdown`, which runs the shutdown hooks
+//! of the machine once: the tracer flushes its file, the sound stops before the audio
+//! stream is closed, and the front end autosaves the state, next to the save state slots.
+//! Ctrl+C and termination signals only ask the front end to stop after the current frame,
+//! so nothing is cut off halfway through a write. A second one stops the process right
+//! away, for when the front end no longer responds.
+
+use core::{fmt, sync::atomic::{AtomicBool, Ordering}};
+
+// Set by the signal handler, which can do little else safely
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// What ended the run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShutdownReason {
+    /// The window was closed, or the run ended by itself.
+    Exit,
+    /// Ctrl+C or a termination signal.
+    Interrupted,
+    /// The emulator panicked.
+    Crash,
+}
+
+impl fmt::Display for ShutdownReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShutdownReason::Exit => write!(f, "exit"),
+            ShutdownReason::Interrupted => write!(f, "interrupt"),
+            ShutdownReason::Crash => write!(f, "crash"),
+        }
+    }
+}
+
+/// Whether Ctrl+C was pressed or a termination signal arrived since the handlers were
+/// installed.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+#[cfg(unix)]
+extern "C" {
+    fn signal(signal: i32, handler: extern "C" fn(i32)) -> usize;
+    fn _exit(status: i32) -> !;
+}
+
+#[cfg(unix)]
+extern "C" fn handle_signal(_: i32) {
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        // The status a shell reports for Ctrl+C
+        unsafe { _exit(130) }
+    }
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+    fn SetConsoleCtrlHandler(handler: extern "system" fn(u32) -> i32, add: i32) -> i32;
+}
+
+// Returning false hands the event to the default handler, which ends the process
+#[cfg(windows)]
+extern "system" fn handle_console_event(_: u32) -> i32 {
+    !INTERRUPTED.swap(true, Ordering::SeqCst) as i32
+}
+
+/// Catch Ctrl+C and termination signals, so `interrupted` reports them instead of the
+/// process ending on the spot.
+pub fn catch_interrupts() {
+    #[cfg(unix)]
+    unsafe {
+        // SIGINT and SIGTERM
+        signal(2, handle_signal);
+        signal(15, handle_signal);
+    }
+
+    #[cfg(windows)]
+    unsafe {
+        SetConsoleCtrlHandler(handle_console_event, 1);
+    }
+}
@@ -0,0 +1,165 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::Chip8;
+use crate::savestate::{SaveState, SlotStore, SLOTS, THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT};
+use crate::screen::{Buffer, Point};
+use crate::text::LINE_HEIGHT;
+
+const SLOT_KEYS: [Key; SLOTS] = [
+    Key::Key0, Key::Key1, Key::Key2, Key::Key3, Key::Key4,
+    Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9,
+];
+
+const SELECTED_COLOR: u32 = 0xFFFFFF;
+const LABEL_COLOR: u32 = 0x808080;
+const COLUMNS: usize = 5;
+const CELL_WIDTH: usize = THUMBNAIL_WIDTH + 3;
+const CELL_HEIGHT: usize = LINE_HEIGHT + THUMBNAIL_HEIGHT + 3;
+
+/// An overlay for saving and loading the numbered save state slots.
+///
+/// F3 opens and closes the picker. While it is open the machine is paused, the number keys
+/// select a slot, S saves to it and L loads from it. D prints how the running machine differs
+/// from the slot, to find where a run went differently than the saved one.
+pub struct SlotPicker {
+    store: SlotStore,
+    open: bool,
+    selected: usize,
+    // The saved states, read when the picker is opened
+    states: Vec<Option<SaveState>>,
+    message: String,
+}
+
+impl SlotPicker {
+    pub fn new(store: SlotStore) -> SlotPicker {
+        SlotPicker {
+            store,
+            open: false,
+            selected: 1,
+            states: Vec::new(),
+            message: String::new(),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Open the picker, reading the saved states.
+    pub fn show(&mut self) {
+        self.open = true;
+        self.message.clear();
+        self.states = (0..SLOTS).map(|slot| self.store.load(slot).ok()).collect();
+    }
+
+    pub fn handle_input(&mut self, window: &Window, chip8: &mut Chip8) {
+        if window.is_key_pressed(Key::F3, KeyRepeat::No) {
+            if self.open {
+                self.open = false;
+            } else {
+                self.show();
+            }
+        }
+
+        if !self.open {
+            return;
+        }
+
+        let pressed = SLOT_KEYS.iter().position(|&key| window.is_key_pressed(key, KeyRepeat::No));
+        if let Some(slot) = pressed {
+            self.selected = slot;
+        }
+
+        if window.is_key_pressed(Key::S, KeyRepeat::No) {
+            let state = SaveState::capture(chip8);
+
+            match self.store.save(self.selected, &state) {
+                Ok(()) => {
+                    self.message = format!("SAVED {}", self.selected);
+                    self.states[self.selected] = Some(state);
+                },
+                Err(e) => {
+                    println!("Could not save state: {}", e);
+                    self.message = String::from("SAVE FAILED");
+                },
+            }
+        }
+
+        if window.is_key_pressed(Key::L, KeyRepeat::No) {
+            match &self.states[self.selected] {
+                Some(state) => {
+                    state.restore(chip8);
+                    self.open = false;
+                },
+                None => self.message = String::from("EMPTY SLOT"),
+            }
+        }
+
+        if window.is_key_pressed(Key::D, KeyRepeat::No) {
+            match &self.states[self.selected] {
+                Some(state) => {
+                    let differences = state.diff(&SaveState::capture(chip8));
+
+                    println!("Differences from slot {}:", self.selected);
+                    for line in &differences {
+                        println!("  {}", line);
+                    }
+                    self.message = format!("{} DIFFERENCES", differences.len());
+                },
+                None => self.message = String::from("EMPTY SLOT"),
+            }
+        }
+    }
+
+    pub fn render(&self, buffer: &mut Buffer) {
+        buffer.clear();
+        buffer.draw_text("SLOTS S/L/D", Point::new(0, 0), LABEL_COLOR);
+
+        for (slot, state) in self.states.iter().enumerate() {
+            let left = (slot % COLUMNS) * CELL_WIDTH;
+            let top = LINE_HEIGHT + 1 + (slot / COLUMNS) * CELL_HEIGHT;
+            let color = if slot == self.selected { SELECTED_COLOR } else { LABEL_COLOR };
+
+            buffer.draw_text(&slot.to_string(), Point::new(left, top), color);
+
+            let thumbnail = match state {
+                Some(state) => &state.thumbnail,
+                None => continue,
+            };
+
+            for (idx, _) in thumbnail.iter().enumerate().filter(|(_, &lit)| lit) {
+                let x = left + idx % THUMBNAIL_WIDTH;
+                let y = top + LINE_HEIGHT + idx / THUMBNAIL_WIDTH;
+
+                buffer.set_pixel(x, y, color);
+            }
+        }
+
+        let info_top = LINE_HEIGHT + 1 + 2 * CELL_HEIGHT;
+        let info = match self.states.get(self.selected) {
+            Some(Some(state)) => format!("{} {}", self.selected, age(state.timestamp)),
+            _ => format!("{} EMPTY", self.selected),
+        };
+
+        buffer.draw_text(&info, Point::new(0, info_top), SELECTED_COLOR);
+        buffer.draw_text(&self.message, Point::new(0, info_top + LINE_HEIGHT), LABEL_COLOR);
+    }
+}
+
+/// How long ago a timestamp was, in the largest whole unit, such as "5M AGO".
+fn age(timestamp: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0);
+    let seconds = now.saturating_sub(timestamp);
+
+    match seconds {
+        0..=59 => format!("{}S AGO", seconds),
+        60..=3599 => format!("{}M AGO", seconds / 60),
+        3600..=86399 => format!("{}H AGO", seconds / 3600),
+        _ => format!("{}D AGO", seconds / 86400),
+    }
+}
@@ -0,0 +1,290 @@
+//! The `soak` subcommand: run a ROM without a window for hours, to find what only shows up
+//! in long runs.
+//!
+//! The machine runs in real time like in the frontend, the instructions paced by a
+//! `Ticker` and the timers by the host's clock. Every interval a sample is taken and
+//! checked for anomalies:
+//!
+//! - the state checksum, and whether a save state of the machine restores to the same one,
+//! - the memory the process holds, growing by more than `LEAK_LIMIT` since the first
+//!   sample (or the last growth reported) hints at a leak, Linux only,
+//! - the instructions and timer ticks run against the wall clock, drifting by more than
+//!   `MAX_DRIFT` of the time run means the scheduler loses or gains time.
+//!
+//! The exit status is 1 when there were anomalies.
+
+use std::{fs, thread, time::{Duration, Instant}};
+
+use crate::Chip8;
+use crate::emulator::Emulator;
+use crate::replay::state_checksum;
+use crate::savestate::SaveState;
+use crate::timing::Ticker;
+
+const USAGE: &str = "Usage: chip8 soak rom.ch8 [--hours N] [--interval SECONDS] [--cpu-hz N] \
+    [--timer-hz N]";
+
+const FRAME_TIME: Duration = Duration::from_micros(16_667);
+// Growth of the resident memory reported as a possible leak, in bytes
+const LEAK_LIMIT: u64 = 16 * 1024 * 1024;
+// Drift against the wall clock reported, as a fraction of the time run
+const MAX_DRIFT: f64 = 0.01;
+
+#[derive(Debug, PartialEq)]
+pub struct SoakOptions {
+    pub rom: String,
+    pub duration: Duration,
+    pub interval: Duration,
+    pub cpu_hz: u32,
+    pub timer_hz: u32,
+}
+
+impl SoakOptions {
+    pub fn parse(args: &[String]) -> Result<SoakOptions, String> {
+        let mut rom = None;
+        let mut options = SoakOptions {
+            rom: String::new(),
+            duration: Duration::from_secs(3600),
+            interval: Duration::from_secs(60),
+            cpu_hz: 500,
+            timer_hz: 60,
+        };
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                rom = Some(arg.clone());
+                continue;
+            }
+
+            let value = args.next().ok_or(format!("{} requires a value", arg))?;
+            let number = || value.parse::<f64>().ok()
+                .filter(|&number| number > 0.0)
+                .ok_or(format!("Invalid number '{}'", value));
+
+            match arg.as_str() {
+                "--hours" => options.duration = Duration::from_secs_f64(number()? * 3600.0),
+                "--interval" => options.interval = Duration::from_secs_f64(number()?),
+                "--cpu-hz" => options.cpu_hz = number()? as u32,
+                "--timer-hz" => options.timer_hz = number()? as u32,
+                _ => return Err(format!("Unknown argument '{}'\n{}", arg, USAGE)),
+            }
+        }
+
+        if options.cpu_hz == 0 || options.timer_hz == 0 {
+            return Err(String::from("Frequencies must be positive"));
+        }
+
+        options.rom = rom.ok_or(USAGE)?;
+
+        Ok(options)
+    }
+}
+
+/// What the machine and the process looked like at a point in the run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    /// Wall clock time since the start of the run.
+    pub time: Duration,
+    pub cycles: u64,
+    pub ticks: u64,
+    pub checksum: u32,
+    /// Whether a save state of the machine restored to the same checksum.
+    pub restores: bool,
+    /// The resident memory of the process in bytes, where it can be read.
+    pub memory: Option<u64>,
+}
+
+impl Sample {
+    fn take(chip8: &Chip8, time: Duration, cycles: u64, ticks: u64) -> Sample {
+        let checksum = state_checksum(chip8);
+
+        Sample {
+            time,
+            cycles,
+            ticks,
+            checksum,
+            restores: restored_checksum(chip8) == Some(checksum),
+            memory: resident_memory(),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let memory = self.memory.map_or(String::from("unknown"), |bytes| {
+            format!("{} KiB", bytes / 1024)
+        });
+
+        format!("{:>7.0}s: {} cycles, {} timer ticks, checksum {:08X}, memory {}",
+            self.time.as_secs_f64(), self.cycles, self.ticks, self.checksum, memory)
+    }
+}
+
+/// The checksum of a fresh machine restored from a save state of this one, going through
+/// the bytes of the file.
+fn restored_checksum(chip8: &Chip8) -> Option<u32> {
+    let state = SaveState::from_bytes(&SaveState::capture(chip8).to_bytes()).ok()?;
+    let mut restored = Chip8::new();
+    state.restore(&mut restored);
+
+    Some(state_checksum(&restored))
+}
+
+/// The resident memory of this process, from `/proc` on Linux.
+fn resident_memory() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+
+    Some(kib * 1024)
+}
+
+/// Compares the samples of a run against what they should be.
+pub struct Monitor {
+    cpu_hz: u32,
+    timer_hz: u32,
+    // The memory growth is measured from here
+    baseline: Option<u64>,
+    anomalies: usize,
+}
+
+impl Monitor {
+    pub fn new(cpu_hz: u32, timer_hz: u32) -> Monitor {
+        Monitor { cpu_hz, timer_hz, baseline: None, anomalies: 0 }
+    }
+
+    /// The anomalies in a sample.
+    pub fn check(&mut self, sample: &Sample) -> Vec<String> {
+        let mut anomalies = Vec::new();
+
+        if !sample.restores {
+            anomalies.push(String::from("A save state does not restore to the same state"));
+        }
+
+        if let Some(memory) = sample.memory {
+            let baseline = *self.baseline.get_or_insert(memory);
+            if memory > baseline + LEAK_LIMIT {
+                anomalies.push(format!("Memory grew by {} KiB to {} KiB",
+                    (memory - baseline) / 1024, memory / 1024));
+                self.baseline = Some(memory);
+            }
+        }
+
+        let time = sample.time.as_secs_f64();
+        for (name, count, hz) in [("CPU", sample.cycles, self.cpu_hz),
+                ("timers", sample.ticks, self.timer_hz)].iter() {
+            let drift = *count as f64 / *hz as f64 - time;
+            // A tick may still be in the making
+            if drift.abs() > MAX_DRIFT * time + 1.0 / *hz as f64 {
+                anomalies.push(format!("The {} drifted {:+.3}s from the wall clock", name,
+                    drift));
+            }
+        }
+
+        self.anomalies += anomalies.len();
+        anomalies
+    }
+}
+
+/// The `soak` subcommand, returning the exit status of the emulator.
+pub fn run_command(args: &[String]) -> Result<i32, String> {
+    let options = SoakOptions::parse(args)?;
+
+    let mut chip8 = Emulator::builder()
+        .cpu_hz(options.cpu_hz)
+        .timer_hz(options.timer_hz)
+        .rom_file(&options.rom)
+        .build()?
+        .chip8;
+
+    println!("Soaking {} for {}s, sampling every {}s", options.rom,
+        options.duration.as_secs_f64(), options.interval.as_secs_f64());
+
+    let mut monitor = Monitor::new(options.cpu_hz, options.timer_hz);
+    let mut ticker = Ticker::new(options.cpu_hz);
+    let (mut cycles, mut ticks) = (0, 0);
+    let start = Instant::now();
+    let mut last = start;
+    let mut next_sample = options.interval;
+
+    while last - start < options.duration {
+        thread::sleep(FRAME_TIME);
+
+        let now = Instant::now();
+        for _ in 0..ticker.advance(now - last) {
+            chip8.cycle();
+            cycles += 1;
+        }
+        ticks += chip8.run_timers() as u64;
+        last = now;
+
+        if now - start >= next_sample {
+            let sample = Sample::take(&chip8, now - start, cycles, ticks);
+            println!("{}", sample.describe());
+            for anomaly in monitor.check(&sample) {
+                println!("Anomaly: {}", anomaly);
+            }
+            next_sample += options.interval;
+        }
+    }
+
+    println!("Ran {} cycles with {} anomalies", cycles, monitor.anomalies);
+
+    Ok(if monitor.anomalies == 0 { 0 } else { 1 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(seconds: u64, cycles: u64, ticks: u64, memory: u64) -> Sample {
+        Sample {
+            time: Duration::from_secs(seconds),
+            cycles,
+            ticks,
+            checksum: 0,
+            restores: true,
+            memory: Some(memory),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let args: Vec<String> = vec!("rom.ch8", "--hours", "0.5", "--interval", "10")
+            .into_iter().map(String::from).collect();
+        let options = SoakOptions::parse(&args).unwrap();
+        assert_eq!(options.duration, Duration::from_secs(1800));
+        assert_eq!(options.interval, Duration::from_secs(10));
+
+        let args = vec!(String::from("--hours"), String::from("-1"));
+        assert!(SoakOptions::parse(&args).is_err());
+    }
+
+    #[test]
+    fn test_monitor() {
+        let mut monitor = Monitor::new(500, 60);
+        assert!(monitor.check(&sample(60, 30_000, 3600, 1 << 20)).is_empty());
+
+        // Late by a tick is not drift yet, late by two seconds is
+        assert!(monitor.check(&sample(120, 59_999, 7199, 1 << 20)).is_empty());
+        assert_eq!(monitor.check(&sample(180, 90_000, 10_680, 1 << 20)).len(), 1);
+
+        // Growth is reported once, from where it was reported
+        let grown = (1 << 20) + LEAK_LIMIT + 1;
+        assert_eq!(monitor.check(&sample(240, 120_000, 14_400, grown)).len(), 1);
+        assert!(monitor.check(&sample(300, 150_000, 18_000, grown)).is_empty());
+        assert_eq!(monitor.anomalies, 2);
+    }
+
+    #[test]
+    fn test_restores() {
+        let mut chip8 = Chip8::new();
+        // LD V0, 5; LD I, 0x300; LD [I], V0
+        chip8.load_bytes(&[0x60, 0x05, 0xA3, 0x00, 0xF0, 0x55]);
+        for _ in 0..3 {
+            chip8.cycle();
+        }
+
+        let sample = Sample::take(&chip8, Duration::from_secs(1), 3, 0);
+        assert!(sample.restores);
+    }
+}
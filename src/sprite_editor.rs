@@ -0,0 +1,282 @@
+//! A small editor for drawing sprites, for homebrew developers who write their sprites
+//! by hand in the assembler.
+//!
+//! `chip8 sprite-edit [sprite.bin] [--height N]` opens a sprite of 8 by up to 15 pixels,
+//! from the file when it exists. Clicking sets pixels and right-clicking clears them, or
+//! the arrow keys move the cursor and Space toggles the pixel under it. + and - change the
+//! height. S saves the raw bytes to the file, and the sprite is printed as `db` lines for
+//! the assembler on every save and on exit.
+
+use std::{fs, path::Path};
+
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Scale, Window, WindowOptions};
+
+use crate::screen::{Buffer, Point};
+use crate::text::LINE_HEIGHT;
+
+/// The tallest sprite DRW draws.
+pub const MAX_HEIGHT: usize = 15;
+
+const USAGE: &str = "Usage: chip8 sprite-edit [sprite.bin] [--height N]";
+
+const CELL_SIZE: usize = 6;
+const LIT_COLOR: u32 = 0xFFFFFF;
+const UNLIT_COLOR: u32 = 0x202020;
+const CURSOR_COLOR: u32 = 0xFF4040;
+const TEXT_COLOR: u32 = 0x808080;
+
+/// A sprite of 8 pixels wide, a byte per row with the leftmost pixel in the most
+/// significant bit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sprite {
+    pub rows: Vec<u8>,
+}
+
+impl Sprite {
+    pub fn new(height: usize) -> Sprite {
+        Sprite { rows: vec!(0; height.max(1).min(MAX_HEIGHT)) }
+    }
+
+    pub fn is_lit(&self, x: usize, y: usize) -> bool {
+        self.rows[y] >> (7 - x) & 1 == 1
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, lit: bool) {
+        if lit {
+            self.rows[y] |= 0x80 >> x;
+        } else {
+            self.rows[y] &= !(0x80 >> x);
+        }
+    }
+
+    /// Add or remove rows at the bottom, keeping between 1 and `MAX_HEIGHT` of them.
+    pub fn resize(&mut self, height: usize) {
+        self.rows.resize(height.max(1).min(MAX_HEIGHT), 0);
+    }
+
+    /// The sprite as assembler data, a line per row with the pixels in a comment.
+    pub fn to_assembly(&self) -> String {
+        self.rows.iter().map(|&row| {
+            let pixels: String = (0..8)
+                .map(|x| if row >> (7 - x) & 1 == 1 { '#' } else { '.' })
+                .collect();
+
+            format!("db {:#04X} ; {}\n", row, pixels)
+        }).collect()
+    }
+}
+
+/// The editor: the sprite with a cursor for editing it from the keyboard.
+pub struct SpriteEditor {
+    pub sprite: Sprite,
+    cursor: (usize, usize),
+}
+
+impl SpriteEditor {
+    pub fn new(sprite: Sprite) -> SpriteEditor {
+        SpriteEditor { sprite, cursor: (0, 0) }
+    }
+
+    /// The size of the buffer the editor renders to, with room for the tallest sprite.
+    pub fn size() -> (usize, usize) {
+        (8 * CELL_SIZE + 12, LINE_HEIGHT + 1 + MAX_HEIGHT * CELL_SIZE)
+    }
+
+    /// Move the cursor, staying on the sprite.
+    pub fn move_cursor(&mut self, dx: isize, dy: isize) {
+        let (x, y) = self.cursor;
+        let bottom = self.sprite.rows.len() as isize - 1;
+
+        self.cursor = (
+            (x as isize + dx).max(0).min(7) as usize,
+            (y as isize + dy).max(0).min(bottom) as usize,
+        );
+    }
+
+    pub fn resize(&mut self, height: usize) {
+        self.sprite.resize(height);
+        self.move_cursor(0, 0);
+    }
+
+    pub fn handle_input(&mut self, window: &Window) {
+        let moves = [(Key::Left, -1, 0), (Key::Right, 1, 0), (Key::Up, 0, -1), (Key::Down, 0, 1)];
+        for &(key, dx, dy) in moves.iter() {
+            if window.is_key_pressed(key, KeyRepeat::Yes) {
+                self.move_cursor(dx, dy);
+            }
+        }
+
+        if window.is_key_pressed(Key::Space, KeyRepeat::No) {
+            let (x, y) = self.cursor;
+            let lit = self.sprite.is_lit(x, y);
+            self.sprite.set(x, y, !lit);
+        }
+
+        let height = self.sprite.rows.len();
+        if window.is_key_pressed(Key::Equal, KeyRepeat::Yes)
+            || window.is_key_pressed(Key::NumPadPlus, KeyRepeat::Yes) {
+            self.resize(height + 1);
+        }
+        if window.is_key_pressed(Key::Minus, KeyRepeat::Yes)
+            || window.is_key_pressed(Key::NumPadMinus, KeyRepeat::Yes) {
+            self.resize(height - 1);
+        }
+
+        // Dragging with a button held paints, so strokes do not flip pixels back and forth
+        let cell = window.get_mouse_pos(MouseMode::Discard)
+            .and_then(|(x, y)| self.cell_at(x as usize, y as usize));
+        if let Some((x, y)) = cell {
+            if window.get_mouse_down(MouseButton::Left) {
+                self.sprite.set(x, y, true);
+                self.cursor = (x, y);
+            } else if window.get_mouse_down(MouseButton::Right) {
+                self.sprite.set(x, y, false);
+                self.cursor = (x, y);
+            }
+        }
+    }
+
+    /// The pixel of the sprite under a point of the buffer.
+    fn cell_at(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        let y = y.checked_sub(LINE_HEIGHT + 1)?;
+        let (x, y) = (x / CELL_SIZE, y / CELL_SIZE);
+
+        if x < 8 && y < self.sprite.rows.len() { Some((x, y)) } else { None }
+    }
+
+    /// Draw the enlarged sprite with its size above it, and the sprite at its real size
+    /// to the right.
+    pub fn render(&self, buffer: &mut Buffer) {
+        buffer.clear();
+
+        let height = self.sprite.rows.len();
+        buffer.draw_text(&format!("8X{}", height), Point::new(0, 0), TEXT_COLOR);
+
+        let top = LINE_HEIGHT + 1;
+        for y in 0..height {
+            for x in 0..8 {
+                let lit = self.sprite.is_lit(x, y);
+                let color = if lit { LIT_COLOR } else { UNLIT_COLOR };
+
+                // The cells are a pixel apart, the cursor is outlined
+                for dy in 0..CELL_SIZE {
+                    for dx in 0..CELL_SIZE {
+                        let edge = dx == CELL_SIZE - 1 || dy == CELL_SIZE - 1;
+                        let color = match (edge, (x, y) == self.cursor) {
+                            (true, true) => CURSOR_COLOR,
+                            (true, false) => 0,
+                            (false, _) => color,
+                        };
+
+                        buffer.set_pixel(x * CELL_SIZE + dx, top + y * CELL_SIZE + dy, color);
+                    }
+                }
+
+                if lit {
+                    buffer.set_pixel(8 * CELL_SIZE + 2 + x, top + y, LIT_COLOR);
+                }
+            }
+        }
+    }
+}
+
+/// The `sprite-edit` subcommand.
+pub fn run_command(args: &[String]) -> Result<(), String> {
+    let mut path = None;
+    let mut height = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--height" => {
+                let value = args.next().ok_or("--height requires a number")?;
+                height = Some(value.parse::<usize>().ok()
+                    .filter(|height| (1..=MAX_HEIGHT).contains(height))
+                    .ok_or(format!("The height must be 1 to {}, not '{}'", MAX_HEIGHT, value))?);
+            },
+            _ if arg.starts_with("--") =>
+                return Err(format!("Unknown argument '{}'\n{}", arg, USAGE)),
+            _ => path = Some(arg),
+        }
+    }
+
+    // An existing sprite is opened, a new one starts out empty
+    let mut sprite = match path.filter(|path| Path::new(path).exists()) {
+        Some(path) => {
+            let rows = fs::read(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+            Sprite { rows: rows.into_iter().take(MAX_HEIGHT).collect() }
+        },
+        None => Sprite::new(5),
+    };
+    if let Some(height) = height {
+        sprite.resize(height);
+    }
+    let mut editor = SpriteEditor::new(sprite);
+
+    let (width, height) = SpriteEditor::size();
+    let mut buffer = Buffer::new(width, height, None);
+    let mut window = Window::new(
+        "CHIP-8 sprite editor - S to save, ESC to exit",
+        width, height,
+        WindowOptions {
+            resize: false,
+            scale: Scale::X4,
+            ..WindowOptions::default()
+        })
+        .map_err(|e| e.to_string())?;
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        editor.handle_input(&window);
+
+        if window.is_key_pressed(Key::S, KeyRepeat::No) {
+            print!("{}", editor.sprite.to_assembly());
+
+            if let Some(path) = path {
+                fs::write(path, &editor.sprite.rows)
+                    .map_err(|e| format!("Could not write {}: {}", path, e))?;
+                println!("Saved {}", path);
+            }
+        }
+
+        editor.render(&mut buffer);
+        window.update_with_buffer(buffer.pixels()).map_err(|e| e.to_string())?;
+    }
+
+    print!("{}", editor.sprite.to_assembly());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_and_export() {
+        let mut sprite = Sprite::new(2);
+        sprite.set(0, 0, true);
+        sprite.set(3, 0, true);
+        sprite.set(7, 1, true);
+        sprite.set(3, 0, false);
+
+        assert_eq!(sprite.rows, vec!(0x80, 0x01));
+        assert_eq!(sprite.to_assembly(), "db 0x80 ; #.......\ndb 0x01 ; .......#\n");
+
+        sprite.resize(20);
+        assert_eq!(sprite.rows.len(), MAX_HEIGHT);
+        sprite.resize(0);
+        assert_eq!(sprite.rows, vec!(0x80));
+    }
+
+    #[test]
+    fn test_cursor_stays_on_sprite() {
+        let mut editor = SpriteEditor::new(Sprite::new(3));
+
+        editor.move_cursor(10, 10);
+        assert_eq!(editor.cursor, (7, 2));
+        editor.resize(1);
+        assert_eq!(editor.cursor, (7, 0));
+
+        assert_eq!(editor.cell_at(CELL_SIZE * 7 + 1, LINE_HEIGHT + 2), Some((7, 0)));
+        assert_eq!(editor.cell_at(1, LINE_HEIGHT + 1 + CELL_SIZE), None);
+    }
+}
@@ -0,0 +1,92 @@
+//! How DRW draws a sprite, which the variants disagree on.
+//!
+//! | Variant        | Dxy0 low res | Past the edge |
+//! |----------------|--------------|---------------|
+//! | CHIP-8         | nothing      | wraps         |
+//! | COSMAC VIP, 8X | nothing      | clipped       |
+//! | SCHIP          | 8x16         | clipped       |
+//! | XO-CHIP        | 16x16        | wraps         |
+//!
+//! A sprite always starts on the display: its position wraps around either way, and only
+//! the pixels beyond the edge are clipped or wrap to the opposite side.
+//!
+//! Only the low resolution is modelled. The display is always 64 by 32 pixels, so the high
+//! resolution of SCHIP and XO-CHIP, where Dxy0 draws 16 by 16 pixels in both, is not.
+
+use crate::variant::Variant;
+
+/// What Dxy0 draws.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ZeroHeight {
+    Nothing,
+    /// A sprite of 8 by 16 pixels, a byte per row.
+    Tall,
+    /// A sprite of 16 by 16 pixels, two bytes per row.
+    Large,
+}
+
+/// What happens to the pixels of a sprite beyond the edge of the display.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+    Wrap,
+    Clip,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpriteRules {
+    /// Dxy0 in the low resolution.
+    pub lores_zero: ZeroHeight,
+    pub edge: Edge,
+}
+
+impl Default for SpriteRules {
+    fn default() -> SpriteRules {
+        Variant::Chip8.sprite_rules()
+    }
+}
+
+impl SpriteRules {
+    /// The width in bytes and the number of rows of the sprite Dxyn draws.
+    pub fn shape(&self, n: u8) -> (usize, usize) {
+        if n > 0 {
+            return (1, n as usize);
+        }
+
+        match self.lores_zero {
+            ZeroHeight::Nothing => (1, 0),
+            ZeroHeight::Tall => (1, 16),
+            ZeroHeight::Large => (2, 16),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shape() {
+        for &variant in Variant::ALL.iter() {
+            let rules = variant.sprite_rules();
+            assert_eq!(rules.shape(5), (1, 5), "{}", variant.name());
+            assert_eq!(rules.shape(15), (1, 15), "{}", variant.name());
+        }
+
+        let shape = |variant: Variant| variant.sprite_rules().shape(0);
+        assert_eq!(shape(Variant::Chip8), (1, 0));
+        assert_eq!(shape(Variant::CosmacVip), (1, 0));
+        assert_eq!(shape(Variant::Chip8X), (1, 0));
+        assert_eq!(shape(Variant::SChip), (1, 16));
+        assert_eq!(shape(Variant::XoChip), (2, 16));
+    }
+
+    #[test]
+    fn test_edges() {
+        assert_eq!(Variant::Chip8.sprite_rules().edge, Edge::Wrap);
+        assert_eq!(Variant::CosmacVip.sprite_rules().edge, Edge::Clip);
+        assert_eq!(Variant::Chip8X.sprite_rules().edge, Edge::Clip);
+        assert_eq!(Variant::SChip.sprite_rules().edge, Edge::Clip);
+        assert_eq!(Variant::XoChip.sprite_rules().edge, Edge::Wrap);
+        assert_eq!(SpriteRules::default(), Variant::Chip8.sprite_rules());
+    }
+}
@@ -0,0 +1,109 @@
+//! The call stack of CALL and RET.
+//!
+//! The COSMAC VIP had room for 12 return addresses and most interpreters offer 16, but
+//! some modern ROMs nest their subroutines deeper. The depth is configurable, and a CALL
+//! beyond it or a RET on an empty stack is reported instead of corrupting memory.
+
+use alloc::vec::Vec;
+
+/// The depth of the stack unless configured otherwise.
+pub const DEFAULT_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StackError {
+    /// A CALL with every level of the stack in use.
+    Overflow,
+    /// A RET with nothing on the stack.
+    Underflow,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stack {
+    // Return addresses, the most recent last
+    entries: Vec<u16>,
+    depth: usize,
+    // The most return addresses there have been on the stack at once
+    high_water: usize,
+}
+
+impl Stack {
+    pub fn new(depth: usize) -> Stack {
+        Stack {
+            entries: Vec::with_capacity(depth),
+            depth,
+            high_water: 0,
+        }
+    }
+
+    pub fn push(&mut self, address: u16) -> Result<(), StackError> {
+        if self.entries.len() >= self.depth {
+            return Err(StackError::Overflow);
+        }
+
+        self.entries.push(address);
+        self.high_water = self.high_water.max(self.entries.len());
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Result<u16, StackError> {
+        self.entries.pop().ok_or(StackError::Underflow)
+    }
+
+    /// The number of return addresses on the stack.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The number of return addresses the stack can hold.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The most return addresses there have been on the stack at once.
+    pub fn high_water(&self) -> usize {
+        self.high_water
+    }
+
+    /// The return addresses, the oldest first.
+    pub fn entries(&self) -> &[u16] {
+        &self.entries
+    }
+
+    /// Replace the return addresses, e.g. from a save state, even when there are more than
+    /// the depth allows.
+    pub fn set_entries(&mut self, entries: &[u16]) {
+        self.entries = entries.to_vec();
+        self.high_water = self.high_water.max(self.entries.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_and_pop() {
+        let mut stack = Stack::new(2);
+        stack.push(0x200).unwrap();
+        stack.push(0x300).unwrap();
+
+        assert_eq!(stack.push(0x400), Err(StackError::Overflow));
+        assert_eq!(stack.entries(), &[0x200, 0x300]);
+
+        assert_eq!(stack.pop(), Ok(0x300));
+        assert_eq!(stack.pop(), Ok(0x200));
+        assert_eq!(stack.pop(), Err(StackError::Underflow));
+        assert_eq!(stack.high_water(), 2);
+    }
+
+    #[test]
+    fn test_deep_stack() {
+        let mut stack = Stack::new(64);
+        for address in 0..64 {
+            stack.push(address).unwrap();
+        }
+
+        assert_eq!(stack.len(), 64);
+        assert!(stack.push(0).is_err());
+    }
+}
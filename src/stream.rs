@@ -0,0 +1,99 @@
+//! Streaming of the display to stdout, to pipe the emulator into ffmpeg or another viewer.
+//!
+//! Frames are written back to back at 60 Hz, either as raw 1-bit frames or as binary PGM
+//! images. Raw frames pack eight pixels per byte, row by row with the leftmost pixel in the
+//! most significant bit, and a set bit for a lit pixel. That matches ffmpeg's `monob` pixel
+//! format:
+//!
+//! ```text
+//! chip8 game.ch8 --stream-fb raw | ffmpeg -f rawvideo -pix_fmt monob -s 64x32 -r 60 -i - game.mp4
+//! ```
+
+use std::{io::{self, Write}, str::FromStr, time::Duration};
+
+use crate::display::Display;
+use crate::timing::Ticker;
+
+const STREAM_HZ: u32 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamFormat {
+    Raw,
+    Pgm,
+}
+
+impl FromStr for StreamFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(StreamFormat::Raw),
+            "pgm" => Ok(StreamFormat::Pgm),
+            _ => Err(format!("Unknown stream format '{}', expected raw or pgm", s)),
+        }
+    }
+}
+
+/// Encode a single frame of the display.
+pub fn encode(display: &Display, format: StreamFormat) -> Vec<u8> {
+    match format {
+        // The rows of the display are already packed this way
+        StreamFormat::Raw => display.rows().iter()
+            .flat_map(|row| row.to_be_bytes().to_vec())
+            .collect(),
+        StreamFormat::Pgm => display.to_buffer().to_pgm(),
+    }
+}
+
+/// Writes the display to stdout at a fixed rate, independent of the emulator's frame rate.
+pub struct FrameStream {
+    format: StreamFormat,
+    ticker: Ticker,
+    out: io::Stdout,
+}
+
+impl FrameStream {
+    pub fn new(format: StreamFormat) -> FrameStream {
+        FrameStream {
+            format,
+            ticker: Ticker::new(STREAM_HZ),
+            out: io::stdout(),
+        }
+    }
+
+    /// Write the frames that are due after `elapsed`. Frames are repeated when the emulator
+    /// slept for longer than a frame, so the stream keeps a constant rate.
+    pub fn push(&mut self, display: &Display, elapsed: Duration) -> io::Result<()> {
+        let frames = self.ticker.advance(elapsed);
+
+        if frames == 0 {
+            return Ok(());
+        }
+
+        let frame = encode(display, self.format);
+        let mut out = self.out.lock();
+
+        for _ in 0..frames {
+            out.write_all(&frame)?;
+        }
+
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_raw() {
+        let mut display = Display::new();
+        display.draw_sprite(0, 0, &[0x80]);
+        display.draw_sprite(9, 1, &[0x80]);
+
+        let frame = encode(&display, StreamFormat::Raw);
+        assert_eq!(frame.len(), 256);
+        assert_eq!(frame[..2], [0x80, 0x00]);
+        assert_eq!(frame[8..10], [0x00, 0x40]);
+    }
+}
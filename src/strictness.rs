@@ -0,0 +1,283 @@
+//! Checks for what ROMs can do but almost never mean to: run from an odd address, or have
+//! DRW, Fx33, Fx55, Fx65 or F002 reach from I past the end of memory at 0xFFF. Both
+//! usually point at a bug in the ROM or in the emulator.
+//!
+//! How strict the machine is decides what happens then:
+//!
+//! - `off`: nothing, the default.
+//! - `warn`: a warning is logged.
+//! - `break`: a warning is logged and the machine pauses, like on a fault.
+//! - `strict`: like `break`, and the machine also pauses the first time the ROM relies on
+//!   behaviour interpreters disagree on, see `Ambiguity`. `--strict` is short for it, for
+//!   writing ROMs that run the same everywhere.
+
+use core::{fmt, str::FromStr};
+
+use alloc::{format, string::String, vec::Vec};
+
+use crate::MEMORY;
+use crate::sprite_rules::SpriteRules;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strictness {
+    Off,
+    Warn,
+    Break,
+    Strict,
+}
+
+impl Strictness {
+    /// Whether a failed check pauses the machine.
+    pub fn breaks(self) -> bool {
+        matches!(self, Strictness::Break | Strictness::Strict)
+    }
+}
+
+impl FromStr for Strictness {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Strictness, String> {
+        match s {
+            "off" => Ok(Strictness::Off),
+            "warn" => Ok(Strictness::Warn),
+            "break" => Ok(Strictness::Break),
+            "strict" => Ok(Strictness::Strict),
+            _ => Err(format!("Unknown strictness '{}', expected off, warn, break or strict", s)),
+        }
+    }
+}
+
+impl fmt::Display for Strictness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Strictness::Off => write!(f, "off"),
+            Strictness::Warn => write!(f, "warn"),
+            Strictness::Break => write!(f, "break"),
+            Strictness::Strict => write!(f, "strict"),
+        }
+    }
+}
+
+/// The number of bytes an opcode reads or writes from I on, if it accesses memory there.
+pub fn index_span(opcode: u16, rules: &SpriteRules) -> Option<usize> {
+    let x = (opcode >> 8 & 0xF) as usize;
+
+    match (opcode >> 12, opcode & 0xFF) {
+        (0xD, _) => {
+            let (width, rows) = rules.shape((opcode & 0xF) as u8);
+            Some(width * rows)
+        },
+        (0xF, 0x02) if x == 0 => Some(16),
+        (0xF, 0x33) => Some(3),
+        (0xF, 0x55) | (0xF, 0x65) => Some(x + 1),
+        _ => None,
+    }
+}
+
+/// Why an opcode executed with this I is suspicious, if it reaches past the end of memory.
+pub fn check_index(opcode: u16, i: u16, rules: &SpriteRules) -> Option<String> {
+    let span = index_span(opcode, rules)?;
+
+    if span > 0 && i as usize + span > MEMORY {
+        Some(format!("{:04X} accesses {} bytes from I = {:#05X}, past the end of memory",
+            opcode, span, i))
+    } else {
+        None
+    }
+}
+
+/// Behaviour interpreters disagree on, that a portable ROM does not rely on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ambiguity {
+    /// 8xy6 and 8xyE shift Vy on the COSMAC VIP and Vx on later interpreters.
+    ShiftSource,
+    /// Fx55 and Fx65 leave I past the registers on the COSMAC VIP and unchanged on later
+    /// interpreters, which matters when I is used before it is set again.
+    IndexIncrement,
+    /// Bnnn adds V0 on the COSMAC VIP, while SUPER-CHIP reads it as Bxnn and adds Vx.
+    JumpRegister,
+}
+
+impl Ambiguity {
+    fn describe(self) -> &'static str {
+        match self {
+            Ambiguity::ShiftSource => "the register shifted by 8xy6 or 8xyE",
+            Ambiguity::IndexIncrement => "I after Fx55 or Fx65",
+            Ambiguity::JumpRegister => "the register added by Bnnn",
+        }
+    }
+}
+
+/// Finds where a ROM relies on ambiguous behaviour, reporting each kind only once.
+#[derive(Debug, Default, Clone)]
+pub struct AmbiguityCheck {
+    reported: Vec<Ambiguity>,
+    // Whether I was left by Fx55 or Fx65 and not set since
+    index_ambiguous: bool,
+}
+
+impl AmbiguityCheck {
+    /// Why the opcode about to be executed is not portable, if this is the first time the
+    /// ROM relies on that behaviour.
+    pub fn check(&mut self, opcode: u16, registers: &[u8; 16]) -> Option<String> {
+        let x = (opcode >> 8 & 0xF) as usize;
+        let y = (opcode >> 4 & 0xF) as usize;
+
+        let uses_index = index_span(opcode, &SpriteRules::default()).is_some()
+            || opcode & 0xF0FF == 0xF01E;
+        let ambiguity = match opcode >> 12 {
+            0x8 if matches!(opcode & 0xF, 0x6 | 0xE) && registers[x] != registers[y] =>
+                Some(Ambiguity::ShiftSource),
+            0xB if registers[x] != registers[0] => Some(Ambiguity::JumpRegister),
+            _ if uses_index && self.index_ambiguous => Some(Ambiguity::IndexIncrement),
+            _ => None,
+        };
+
+        if matches!(opcode & 0xF0FF, 0xF055 | 0xF065) {
+            self.index_ambiguous = true;
+        } else if opcode >> 12 == 0xA || matches!(opcode & 0xF0FF, 0xF029 | 0xF030)
+                || opcode == 0xF000 {
+            self.index_ambiguous = false;
+        }
+
+        let ambiguity = ambiguity.filter(|ambiguity| !self.reported.contains(ambiguity))?;
+        self.reported.push(ambiguity);
+
+        Some(format!("{:04X} relies on {}, which interpreters disagree on", opcode,
+            ambiguity.describe()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+    use crate::variant::Variant;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("warn".parse(), Ok(Strictness::Warn));
+        assert_eq!(Strictness::Break.to_string(), "break");
+        assert_eq!("strict".parse(), Ok(Strictness::Strict));
+        assert!("pedantic".parse::<Strictness>().is_err());
+    }
+
+    #[test]
+    fn test_index_span() {
+        let rules = SpriteRules::default();
+        assert_eq!(index_span(0xD125, &rules), Some(5));
+        assert_eq!(index_span(0xD120, &rules), Some(0));
+        assert_eq!(index_span(0xD120, &Variant::XoChip.sprite_rules()), Some(32));
+        assert_eq!(index_span(0xF333, &rules), Some(3));
+        assert_eq!(index_span(0xF755, &rules), Some(8));
+        assert_eq!(index_span(0xF065, &rules), Some(1));
+        assert_eq!(index_span(0xF002, &rules), Some(16));
+        assert_eq!(index_span(0xF102, &rules), None);
+        assert_eq!(index_span(0x8124, &rules), None);
+    }
+
+    #[test]
+    fn test_check_index() {
+        let rules = SpriteRules::default();
+        assert!(check_index(0xD125, 0xFFB, &rules).is_none());
+        assert!(check_index(0xD125, 0xFFC, &rules).is_some());
+        assert!(check_index(0xFF55, 0xFF0, &rules).is_none());
+        assert!(check_index(0xFF55, 0xFF1, &rules).is_some());
+        assert!(check_index(0xD120, 0xFFF, &rules).is_none());
+        assert!(check_index(0x6012, 0xFFF, &rules).is_none());
+    }
+
+    // The machine after running the instructions, with the strictness given
+    fn run(strictness: Strictness, rom: &[u8], cycles: usize) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.strictness = strictness;
+        chip8.load_bytes(rom);
+        for _ in 0..cycles {
+            chip8.cycle();
+        }
+
+        chip8
+    }
+
+    #[test]
+    fn test_odd_pc() {
+        // JP 0x301
+        let rom = [0x13, 0x01];
+        assert_eq!(run(Strictness::Break, &rom, 1).strictness_fault, Some(0x200));
+        assert_eq!(run(Strictness::Warn, &rom, 1).strictness_fault, None);
+
+        // Reported where the program counter turns odd, not again after that
+        let mut chip8 = run(Strictness::Break, &rom, 1);
+        chip8.strictness_fault.take();
+        chip8.cycle();
+        assert_eq!(chip8.strictness_fault, None);
+    }
+
+    #[test]
+    fn test_past_the_end() {
+        // LD I, 0xFFD; DRW V0, V1, 5
+        let rom = [0xAF, 0xFD, 0xD0, 0x15];
+        assert_eq!(run(Strictness::Break, &rom, 2).strictness_fault, Some(0x202));
+        assert_eq!(run(Strictness::Off, &rom, 2).strictness_fault, None);
+    }
+
+    #[test]
+    fn test_registers_past_the_end() {
+        // LD I, 0xFFE; LD [I], V1 fits, LD [I], V2 does not
+        let chip8 = run(Strictness::Break, &[0xAF, 0xFE, 0xF1, 0x55], 2);
+        assert_eq!(chip8.strictness_fault, None);
+
+        let chip8 = run(Strictness::Break, &[0xAF, 0xFE, 0xF2, 0x55], 2);
+        assert_eq!(chip8.strictness_fault, Some(0x202));
+        assert!(chip8.bounds_fault.is_some());
+
+        // LD I, 0xFFF; LD V1, [I]
+        let chip8 = run(Strictness::Break, &[0xAF, 0xFF, 0xF1, 0x65], 2);
+        assert_eq!(chip8.strictness_fault, Some(0x202));
+    }
+
+    #[test]
+    fn test_ambiguities() {
+        let mut check = AmbiguityCheck::default();
+        let mut registers = [0; 16];
+
+        // Shifting a register by itself, or Vx and Vy holding the same value, is portable
+        assert!(check.check(0x8116, &registers).is_none());
+        assert!(check.check(0x8126, &registers).is_none());
+        registers[2] = 4;
+        assert!(check.check(0x812E, &registers).is_some());
+        // Only reported the first time
+        assert!(check.check(0x812E, &registers).is_none());
+
+        assert!(check.check(0xB300, &registers).is_none());
+        assert!(check.check(0xB200, &registers).is_some());
+
+        // I is only ambiguous when it is used after Fx55 before being set again
+        assert!(check.check(0xF155, &registers).is_none());
+        assert!(check.check(0xA300, &registers).is_none());
+        assert!(check.check(0xD125, &registers).is_none());
+        assert!(check.check(0xF165, &registers).is_none());
+        assert!(check.check(0xF11E, &registers).is_some());
+    }
+
+    #[test]
+    fn test_strict() {
+        // LD V1, 1; SHR V0, V1
+        let rom = [0x61, 0x01, 0x80, 0x16];
+        assert_eq!(run(Strictness::Strict, &rom, 2).strictness_fault, Some(0x202));
+        assert_eq!(run(Strictness::Break, &rom, 2).strictness_fault, None);
+    }
+
+    #[test]
+    fn test_strict_index_increment() {
+        // LD I, 0x300; LD [I], V1; LD V1, [I]
+        let rom = [0xA3, 0x00, 0xF1, 0x55, 0xF1, 0x65];
+        let chip8 = run(Strictness::Strict, &rom, 3);
+        assert_eq!(chip8.strictness_fault, Some(0x204));
+        assert_eq!(run(Strictness::Break, &rom, 3).strictness_fault, None);
+
+        // LD I, 0x300; LD [I], V1; LD I, 0x300; LD V1, [I]
+        let rom = [0xA3, 0x00, 0xF1, 0x55, 0xA3, 0x00, 0xF1, 0x65];
+        assert_eq!(run(Strictness::Strict, &rom, 4).strictness_fault, None);
+    }
+}
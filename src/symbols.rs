@@ -0,0 +1,115 @@
+//! Debug information written by an assembler alongside the ROM.
+//!
+//! A symbol file maps labels and source lines to addresses, one entry per line:
+//!
+//! ```text
+//! label main 0x200
+//! label draw 0x20A
+//! line 0x200 12 CLS ; start with an empty screen
+//! line 0x202 13 LD V0, 0
+//! ```
+//!
+//! A `line` entry holds the address, the line number in the source file and the source text
+//! of the instruction, including its comment. Empty lines and lines starting with `#` are
+//! ignored.
+
+use std::fs;
+
+use crate::debugger::parse_number;
+
+/// An instruction in the assembly source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLine {
+    pub number: usize,
+    pub text: String,
+}
+
+impl SourceLine {
+    /// The comment after the instruction, without the `;`.
+    pub fn comment(&self) -> Option<&str> {
+        self.text.find(';').map(|idx| self.text[idx + 1..].trim())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SymbolTable {
+    labels: Vec<(String, u16)>,
+    lines: Vec<(u16, SourceLine)>,
+}
+
+impl SymbolTable {
+    pub fn load(path: &str) -> Result<SymbolTable, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read symbol file {}: {}", path, e))?;
+
+        SymbolTable::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<SymbolTable, String> {
+        let mut symbols = SymbolTable::default();
+
+        for line in contents.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+            let invalid = || format!("Invalid symbol file line '{}'", line);
+            let mut tokens = line.splitn(4, char::is_whitespace);
+
+            match (tokens.next(), tokens.next(), tokens.next()) {
+                (Some("label"), Some(name), Some(address)) => {
+                    symbols.labels.push((name.to_string(), parse_number(address)?));
+                },
+                (Some("line"), Some(address), Some(number)) => {
+                    let number = number.parse().map_err(|_| invalid())?;
+                    let text = tokens.next().unwrap_or("").trim().to_string();
+
+                    symbols.lines.push((parse_number(address)?, SourceLine { number, text }));
+                },
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(symbols)
+    }
+
+    pub fn address_of(&self, label: &str) -> Option<u16> {
+        self.labels.iter().find(|(name, _)| name == label).map(|&(_, address)| address)
+    }
+
+    pub fn label_at(&self, address: u16) -> Option<&str> {
+        self.labels.iter().find(|&&(_, a)| a == address).map(|(name, _)| name.as_str())
+    }
+
+    /// The closest label at or before the address, and how far the address is past it.
+    pub fn label_before(&self, address: u16) -> Option<(&str, u16)> {
+        self.labels.iter()
+            .filter(|&&(_, a)| a <= address)
+            .max_by_key(|&&(_, a)| a)
+            .map(|(name, a)| (name.as_str(), address - a))
+    }
+
+    pub fn line_at(&self, address: u16) -> Option<&SourceLine> {
+        self.lines.iter().find(|&&(a, _)| a == address).map(|(_, line)| line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let symbols = SymbolTable::parse(
+            "# symbols\nlabel main 0x200\nline 0x200 12 CLS ; clear the screen\n").unwrap();
+
+        assert_eq!(symbols.address_of("main"), Some(0x200));
+        assert_eq!(symbols.label_at(0x200), Some("main"));
+        assert_eq!(symbols.line_at(0x200).unwrap().number, 12);
+        assert_eq!(symbols.line_at(0x200).unwrap().comment(), Some("clear the screen"));
+        assert_eq!(symbols.line_at(0x202), None);
+        assert_eq!(symbols.label_before(0x206), Some(("main", 6)));
+        assert_eq!(symbols.label_before(0x1FE), None);
+    }
+
+    #[test]
+    fn test_invalid_line() {
+        assert!(SymbolTable::parse("label main").is_err());
+    }
+}
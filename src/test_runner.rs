@@ -0,0 +1,303 @@
+//! A test runner for ROM authors: `chip8 test manifest.txt`, also installed as the
+//! `chip8-test` binary, runs the tests in a manifest and exits with a nonzero status when
+//! any of them fails, so it can run in continuous integration.
+//!
+//! ```text
+//! test title screen
+//! rom game.ch8
+//! frames 120
+//!
+//! test serve
+//! rom game.ch8
+//! frames 300
+//! input serve.replay
+//! screen 84A1C3E2F0B4D617
+//! memory 2F0 03
+//! register V3 07
+//! sound yes
+//! ```
+//!
+//! Every test runs its ROM in deterministic mode for a number of frames, with the keys of
+//! an optional replay file as input, and then checks the hash of the display (as written
+//! by `record-golden`), bytes in memory, registers and whether the buzzer sounded. Paths
+//! are relative to the manifest.
+//! A test without expectations passes as long as the ROM does not crash.
+
+use std::{fs, panic::{self, AssertUnwindSafe}, path::{Path, PathBuf}};
+
+use std::sync::{Arc, Mutex};
+
+use crate::audio::{self, RecordingSink};
+use crate::emulator::Emulator;
+use crate::golden::run_frame;
+use crate::patch::parse_patch;
+use crate::replay::{frame_hash, Replay};
+
+const DEFAULT_FRAMES: u64 = 60;
+const USAGE: &str = "Usage: chip8 test manifest.txt [manifest.txt ...]";
+
+#[derive(Debug, PartialEq)]
+pub struct TestCase {
+    pub name: String,
+    pub rom: Option<PathBuf>,
+    pub frames: u64,
+    /// The RNG seed, taken from the input replay when not given.
+    pub seed: Option<u64>,
+    pub input: Option<PathBuf>,
+    pub screen: Option<u64>,
+    pub memory: Vec<(usize, Vec<u8>)>,
+    pub registers: Vec<(usize, u8)>,
+    /// Whether the buzzer should have sounded at some point.
+    pub sound: Option<bool>,
+}
+
+impl TestCase {
+    fn new(name: &str) -> TestCase {
+        TestCase {
+            name: name.to_string(),
+            rom: None,
+            frames: DEFAULT_FRAMES,
+            seed: None,
+            input: None,
+            screen: None,
+            memory: Vec::new(),
+            registers: Vec::new(),
+            sound: None,
+        }
+    }
+
+    /// Run the ROM and check the expectations, describing the first one that is not met.
+    pub fn run(&self) -> Result<(), String> {
+        let rom = self.rom.as_ref().ok_or("No ROM given")?;
+        let rom = fs::read(rom).map_err(|e| format!("Could not read {}: {}", rom.display(), e))?;
+
+        let replay = match &self.input {
+            Some(path) => {
+                let contents = fs::read_to_string(path)
+                    .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+                Some(Replay::parse(&contents)?)
+            },
+            None => None,
+        };
+        let seed = self.seed.or(replay.as_ref().map(|replay| replay.seed)).unwrap_or(0);
+
+        let emulator = Emulator::builder().seed(seed).rom_bytes(&rom).build()?;
+        let cycles_per_frame = emulator.cycles_per_frame();
+        let mut chip8 = emulator.chip8;
+        let sink = Arc::new(Mutex::new(RecordingSink::default()));
+        audio::attach(sink.clone(), &mut chip8);
+
+        let frames = self.frames;
+        let ran = panic::catch_unwind(AssertUnwindSafe(|| {
+            for frame in 0..frames {
+                if let Some(replay) = &replay {
+                    chip8.set_keys(replay.keys_at(frame));
+                }
+                run_frame(&mut chip8, cycles_per_frame);
+            }
+
+            chip8
+        }));
+        let chip8 = ran.map_err(|payload| {
+            let message = payload.downcast_ref::<String>().map(String::as_str)
+                .or_else(|| payload.downcast_ref::<&str>().copied())
+                .unwrap_or("unknown error");
+
+            format!("The emulator crashed: {}", message)
+        })?;
+
+        if let Some(expected) = self.screen {
+            let hash = frame_hash(&chip8.display);
+            if hash != expected {
+                return Err(format!("Screen hash is {:016X}, expected {:016X}", hash, expected));
+            }
+        }
+
+        for (address, expected) in &self.memory {
+            let actual = &chip8.memory[*address..*address + expected.len()];
+            if actual != expected.as_slice() {
+                return Err(format!("Memory at {:#05X} is {}, expected {}",
+                    address, hex(actual), hex(expected)));
+            }
+        }
+
+        for &(v_x, expected) in &self.registers {
+            if chip8.registers[v_x] != expected {
+                return Err(format!("V{:X} is {:02X}, expected {:02X}",
+                    v_x, chip8.registers[v_x], expected));
+            }
+        }
+
+        if let Some(expected) = self.sound {
+            if sink.lock().unwrap().played_tone() != expected {
+                return Err(String::from(if expected {
+                    "The buzzer never sounded"
+                } else {
+                    "The buzzer sounded"
+                }));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+/// Parse a manifest, resolving paths relative to `base`. Lines before the first `test`
+/// line belong to a test named after its ROM.
+pub fn parse_manifest(contents: &str, base: &Path) -> Result<Vec<TestCase>, String> {
+    let mut cases: Vec<TestCase> = Vec::new();
+
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (key, value) = match line.find(' ') {
+            Some(idx) => (&line[..idx], line[idx + 1..].trim()),
+            None => return Err(format!("Invalid manifest line '{}'", line)),
+        };
+
+        if key == "test" {
+            cases.push(TestCase::new(value));
+            continue;
+        }
+
+        if cases.is_empty() {
+            cases.push(TestCase::new(""));
+        }
+        let case = cases.last_mut().unwrap();
+        let number = |value: &str| value.parse().map_err(|_| format!("Invalid number '{}'", value));
+
+        match key {
+            "rom" => case.rom = Some(base.join(value)),
+            "input" => case.input = Some(base.join(value)),
+            "frames" => case.frames = number(value)?,
+            "seed" => case.seed = Some(number(value)?),
+            "screen" => case.screen = Some(u64::from_str_radix(value, 16)
+                .map_err(|_| format!("Invalid screen hash '{}'", value))?),
+            "memory" => case.memory.push(parse_patch(value)?),
+            "register" => {
+                let register = parse_register(value)
+                    .ok_or_else(|| format!("Invalid register expectation '{}'", value))?;
+                case.registers.push(register);
+            },
+            "sound" => case.sound = match value {
+                "yes" => Some(true),
+                "no" => Some(false),
+                _ => return Err(format!("Invalid sound expectation '{}'", value)),
+            },
+            _ => return Err(format!("Unknown manifest key '{}'", key)),
+        }
+    }
+
+    for case in cases.iter_mut().filter(|case| case.name.is_empty()) {
+        case.name = case.rom.as_ref().map_or(String::new(), |rom| rom.display().to_string());
+    }
+
+    Ok(cases)
+}
+
+/// Parse a register expectation such as `V3 07`.
+fn parse_register(s: &str) -> Option<(usize, u8)> {
+    let mut fields = s.split_whitespace();
+
+    let register = fields.next()?.to_uppercase();
+    if !register.starts_with('V') || register.len() != 2 {
+        return None;
+    }
+    let v_x = usize::from_str_radix(&register[1..], 16).ok()?;
+    let value = u8::from_str_radix(fields.next()?, 16).ok()?;
+
+    Some((v_x, value))
+}
+
+/// The `test` subcommand, returning an error when any test fails.
+pub fn run_command(args: &[String]) -> Result<(), String> {
+    if args.is_empty() {
+        return Err(String::from(USAGE));
+    }
+
+    let mut cases = Vec::new();
+    for path in args {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {}", path, e))?;
+        let base = Path::new(path).parent().unwrap_or(Path::new(""));
+
+        cases.extend(parse_manifest(&contents, base).map_err(|e| format!("{}: {}", path, e))?);
+    }
+
+    // Keep crashing ROMs from printing a panic message in the middle of the report
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let results: Vec<Result<(), String>> = cases.iter().map(TestCase::run).collect();
+    panic::set_hook(hook);
+
+    let mut failed = 0;
+    for (case, result) in cases.iter().zip(results) {
+        match result {
+            Ok(()) => println!("ok      {}", case.name),
+            Err(e) => {
+                println!("FAILED  {}: {}", case.name, e);
+                failed += 1;
+            },
+        }
+    }
+
+    println!("\n{} passed, {} failed", cases.len() - failed, failed);
+
+    if failed > 0 {
+        return Err(format!("{} of {} tests failed", failed, cases.len()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = "rom a.ch8\nframes 10\n\ntest serve\nrom b.ch8\nscreen 00FF\n\
+            memory 2F0 0305\nregister V3 07\n";
+        let cases = parse_manifest(manifest, Path::new("roms")).unwrap();
+
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].name, Path::new("roms").join("a.ch8").display().to_string());
+        assert_eq!(cases[0].frames, 10);
+        assert_eq!(cases[1].name, "serve");
+        assert_eq!(cases[1].screen, Some(0xFF));
+        assert_eq!(cases[1].memory, vec!((0x2F0, vec!(0x03, 0x05))));
+        assert_eq!(cases[1].registers, vec!((3, 0x07)));
+
+        assert!(parse_manifest("register V3", Path::new("")).is_err());
+        assert!(parse_manifest("expect 1", Path::new("")).is_err());
+    }
+
+    #[test]
+    fn test_run_checks_expectations() {
+        let dir = env::temp_dir()
+            .join(format!("chip8-test-runner-{}-test_run_checks_expectations", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // LD V3, 0x07; LD I, 0x300; LD B, V3 (stores 0, 0, 7)
+        fs::write(dir.join("store.ch8"), [0x63, 0x07, 0xA3, 0x00, 0xF3, 0x33]).unwrap();
+
+        let mut case = TestCase::new("store");
+        case.rom = Some(dir.join("store.ch8"));
+        case.frames = 1;
+        case.registers.push((3, 0x07));
+        assert_eq!(case.run(), Ok(()));
+
+        case.sound = Some(true);
+        assert_eq!(case.run(), Err(String::from("The buzzer never sounded")));
+
+        case.sound = None;
+        case.memory.push((0x300, vec!(0x00, 0x00, 0x08)));
+        assert_eq!(case.run(), Err(String::from("Memory at 0x300 is 000007, expected 000008")));
+    }
+}
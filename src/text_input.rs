@@ -0,0 +1,40 @@
+//! Typing text into the prompts.
+//!
+//! The prompts take the characters the window receives, as produced by the keyboard layout
+//! of the host, rather than guessing them from the names of the keys pressed. That way
+//! shifted characters and the layouts of other countries type what is printed on the keys.
+//! Backspace and Enter are still read as keys, as not every platform sends them as
+//! characters.
+
+use minifb::{Key, KeyRepeat, Window};
+
+/// Add the characters typed since the previous frame to a line, leaving out control
+/// characters.
+pub fn append_typed(line: &mut String, typed: &str) {
+    line.extend(typed.chars().filter(|c| !c.is_control()));
+}
+
+/// Edit a line with the characters typed since the previous frame and Backspace, returning
+/// whether Enter was pressed.
+pub fn edit_line(line: &mut String, window: &Window, typed: &str) -> bool {
+    append_typed(line, typed);
+
+    if window.is_key_pressed(Key::Backspace, KeyRepeat::Yes) {
+        line.pop();
+    }
+
+    window.is_key_pressed(Key::Enter, KeyRepeat::No)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_typed() {
+        let mut line = String::from("dump");
+        append_typed(&mut line, " 2ä\r\u{8}/");
+
+        assert_eq!(line, "dump 2ä/");
+    }
+}
@@ -0,0 +1,222 @@
+//! The delay and sound timers, counting down at 60 Hz by a clock of their own.
+//!
+//! The buzzer sounds while the sound timer runs, but a ROM loading it starts the buzzer at
+//! the next tick rather than right away. A timer loaded with 6 then sounds for exactly 6
+//! ticks, instead of 5 and the part of a tick left until the first one, and the tone always
+//! starts and stops at a tick.
+//!
+//! The clock is the host's by default. Tests and the batch runner hand in a `ManualClock`
+//! instead, which only moves when it is advanced, so the timers count down in virtual time.
+//! Without std there is no clock to read, and the host counts the timers down itself.
+
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::{sync::{Arc, Mutex}, time::Instant};
+
+use alloc::boxed::Box;
+
+use crate::timing::Ticker;
+
+/// A source of time for the timers.
+pub trait Clock: Send {
+    /// The time passed since the clock was started.
+    fn elapsed(&self) -> Duration;
+}
+
+/// The host's clock.
+#[cfg(feature = "std")]
+pub struct SystemClock {
+    start: Instant,
+}
+
+#[cfg(feature = "std")]
+impl SystemClock {
+    pub fn new() -> SystemClock {
+        SystemClock { start: Instant::now() }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// A clock that stands still until it is advanced. Clones share the same time, so one
+/// can be handed to the timers while the other drives them.
+#[cfg(feature = "std")]
+#[derive(Clone, Default)]
+pub struct ManualClock {
+    now: Arc<Mutex<Duration>>,
+}
+
+#[cfg(feature = "std")]
+impl ManualClock {
+    pub fn new() -> ManualClock {
+        ManualClock::default()
+    }
+
+    pub fn advance(&self, time: Duration) {
+        *self.now.lock().unwrap() += time;
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for ManualClock {
+    fn elapsed(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// The clock without std, which never moves.
+#[cfg(not(feature = "std"))]
+struct StoppedClock;
+
+#[cfg(not(feature = "std"))]
+impl Clock for StoppedClock {
+    fn elapsed(&self) -> Duration {
+        Duration::from_secs(0)
+    }
+}
+
+pub struct Timers {
+    pub delay: u8,
+    pub sound: u8,
+    // The sound timer was loaded since the last tick, so the buzzer starts at the next one
+    sound_pending: bool,
+    clock: Box<dyn Clock>,
+    ticker: Ticker,
+    // The time on the clock when the due ticks were last taken
+    last: Duration,
+}
+
+impl Timers {
+    pub fn new(hz: u32, clock: Box<dyn Clock>) -> Timers {
+        Timers {
+            delay: 0,
+            sound: 0,
+            sound_pending: false,
+            last: clock.elapsed(),
+            clock,
+            ticker: Ticker::new(hz),
+        }
+    }
+
+    /// Drive the timers by another clock, keeping their values.
+    pub fn set_clock(&mut self, hz: u32, clock: Box<dyn Clock>) {
+        self.last = clock.elapsed();
+        self.clock = clock;
+        self.ticker = Ticker::new(hz);
+    }
+
+    /// Whether the buzzer sounds.
+    pub fn sounding(&self) -> bool {
+        self.sound > 0 && !self.sound_pending
+    }
+
+    /// Load the sound timer like a ROM does. When the buzzer is silent it starts at the
+    /// next tick, and the timer only counts down from the tick after that.
+    pub fn load_sound(&mut self, value: u8) {
+        self.sound_pending = value > 0 && (self.sound == 0 || self.sound_pending);
+        self.sound = value;
+    }
+
+    /// Set the sound timer with the buzzer following it right away, for restoring it.
+    pub fn set_sound(&mut self, value: u8) {
+        self.sound_pending = false;
+        self.sound = value;
+    }
+
+    /// Count both timers down by one, if they are running. A sound timer that was just
+    /// loaded starts the buzzer instead.
+    pub fn tick(&mut self) {
+        self.delay = self.delay.saturating_sub(1);
+
+        if self.sound_pending {
+            self.sound_pending = false;
+        } else {
+            self.sound = self.sound.saturating_sub(1);
+        }
+    }
+
+    /// The number of ticks the clock says are due since they were last taken. They are
+    /// not run, as the sound hooks have to be called for each of them.
+    pub fn due(&mut self) -> u32 {
+        let now = self.clock.elapsed();
+        let elapsed = now - self.last;
+        self.last = now;
+
+        self.ticker.advance(elapsed)
+    }
+
+    pub fn clear(&mut self) {
+        self.delay = 0;
+        self.sound = 0;
+        self.sound_pending = false;
+    }
+}
+
+impl Default for Timers {
+    fn default() -> Timers {
+        #[cfg(feature = "std")]
+        let clock = Box::new(SystemClock::new());
+        #[cfg(not(feature = "std"))]
+        let clock = Box::new(StoppedClock);
+
+        Timers::new(60, clock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_due_follows_the_clock() {
+        let clock = ManualClock::new();
+        let mut timers = Timers::new(60, Box::new(clock.clone()));
+
+        assert_eq!(timers.due(), 0);
+
+        // Three ticks and a bit, the bit is carried over
+        clock.advance(Duration::from_millis(55));
+        assert_eq!(timers.due(), 3);
+        clock.advance(Duration::from_millis(12));
+        assert_eq!(timers.due(), 1);
+        assert_eq!(timers.due(), 0);
+    }
+
+    #[test]
+    fn test_tick_stops_at_zero() {
+        let mut timers = Timers::default();
+        timers.delay = 1;
+        timers.sound = 2;
+
+        timers.tick();
+        timers.tick();
+        assert_eq!((timers.delay, timers.sound), (0, 0));
+        timers.tick();
+        assert_eq!((timers.delay, timers.sound), (0, 0));
+    }
+
+    #[test]
+    fn test_sound_for_the_ticks_loaded() {
+        let mut timers = Timers::default();
+        timers.load_sound(2);
+        assert!(!timers.sounding());
+
+        let sounding: Vec<bool> = (0..4).map(|_| {
+            timers.tick();
+            timers.sounding()
+        }).collect();
+        assert_eq!(sounding, vec!(true, true, false, false));
+
+        // Loading it again while the buzzer sounds keeps it going
+        timers.set_sound(1);
+        timers.load_sound(3);
+        assert!(timers.sounding());
+        timers.load_sound(0);
+        assert!(!timers.sounding());
+    }
+}
@@ -0,0 +1,311 @@
+use core::{str::FromStr, time::Duration};
+#[cfg(feature = "std")]
+use std::{hint, thread, time::Instant};
+
+use alloc::{format, string::String};
+
+const ACTIVE_FRAME: Duration = Duration::from_millis(16);
+const IDLE_FRAME: Duration = Duration::from_millis(100);
+// Number of quiet frames (about half a second) before the host loop slows down
+const IDLE_AFTER: u32 = 30;
+// Longest stretch of time a single frame may catch up on, longer than an idle frame
+const MAX_CATCH_UP: Duration = Duration::from_millis(250);
+// How much longer than planned a frame may take before it counts as slow
+const OVERRUN_TOLERANCE: Duration = Duration::from_millis(8);
+// Slow frames, out of the recent ones, before the emulator reports running behind
+const BEHIND_AFTER: u32 = 10;
+const MAX_SLOW_FRAMES: u32 = 30;
+// Frames auto frame skip drops in a row at most, so the picture still moves
+const MAX_AUTO_SKIP: u32 = 4;
+// The end of a sleep that is waited out by spinning, as the sleep itself may overshoot by
+// up to the resolution of the system timer. That is a millisecond on Windows once the high
+// resolution is requested, and much finer elsewhere.
+#[cfg(all(windows, feature = "std"))]
+const SPIN_TIME: Duration = Duration::from_millis(1);
+#[cfg(all(not(windows), feature = "std"))]
+const SPIN_TIME: Duration = Duration::from_micros(250);
+
+/// Converts elapsed wall-clock time into a number of ticks at a fixed frequency.
+///
+/// Time that does not add up to a whole tick is carried over to the next call, so the
+/// average rate stays exact regardless of how often the main loop runs.
+pub struct Ticker {
+    period: Duration,
+    accumulated: Duration,
+}
+
+impl Ticker {
+    pub fn new(hz: u32) -> Ticker {
+        Ticker {
+            period: Duration::from_secs(1) / hz,
+            accumulated: Duration::from_secs(0),
+        }
+    }
+
+    /// Add elapsed time and return the number of ticks that are due.
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.accumulated += elapsed;
+
+        let mut ticks = 0;
+        while self.accumulated >= self.period {
+            self.accumulated -= self.period;
+            ticks += 1;
+        }
+
+        ticks
+    }
+}
+
+/// Chooses how long the host loop sleeps between frames.
+///
+/// While the machine is quiet (paused, waiting for a key, or not drawing) and no keys are
+/// held, the loop drops to a slow poll of the window events to save power. Because the
+/// CPU and timers are driven by `Ticker`, emulation speed is unaffected; cycles are just
+/// run in larger batches. Any activity immediately returns to the full frame rate.
+pub struct IdlePacer {
+    quiet_frames: u32,
+}
+
+impl IdlePacer {
+    pub fn new() -> IdlePacer {
+        IdlePacer { quiet_frames: 0 }
+    }
+
+    pub fn frame_time(&mut self, quiet: bool) -> Duration {
+        if quiet {
+            self.quiet_frames = self.quiet_frames.saturating_add(1);
+        } else {
+            self.quiet_frames = 0;
+        }
+
+        if self.quiet_frames > IDLE_AFTER {
+            IDLE_FRAME
+        } else {
+            ACTIVE_FRAME
+        }
+    }
+}
+
+/// Decides how much emulated time a frame runs when the host cannot keep up.
+///
+/// Small delays are caught up by running more cycles in the next frame. Beyond
+/// `MAX_CATCH_UP` the extra time is dropped, so the emulator slows down instead of
+/// freezing to run a huge batch. The CPU and timer tickers are advanced by the same
+/// budgeted time, so their ratio stays correct either way.
+pub struct FrameBudget {
+    // Rises for every frame that overran its planned time and falls for every frame that did not
+    slow_frames: u32,
+}
+
+impl FrameBudget {
+    pub fn new() -> FrameBudget {
+        FrameBudget { slow_frames: 0 }
+    }
+
+    /// The emulated time to run for a frame that took `elapsed`, when `planned` was expected.
+    pub fn budget(&mut self, elapsed: Duration, planned: Duration) -> Duration {
+        if elapsed > planned + OVERRUN_TOLERANCE {
+            self.slow_frames = (self.slow_frames + 1).min(MAX_SLOW_FRAMES);
+        } else {
+            self.slow_frames = self.slow_frames.saturating_sub(1);
+        }
+
+        elapsed.min(MAX_CATCH_UP)
+    }
+
+    /// Whether the host has been missing its frame times, so the user can be warned.
+    pub fn is_behind(&self) -> bool {
+        self.slow_frames >= BEHIND_AFTER
+    }
+}
+
+/// How many frames are not shown in the window, to save time on slow hosts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameSkip {
+    /// Skip this many frames after every frame shown.
+    Fixed(u32),
+    /// Skip frames only while the host cannot keep up.
+    Auto,
+}
+
+impl FromStr for FrameSkip {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<FrameSkip, String> {
+        match s {
+            "auto" => Ok(FrameSkip::Auto),
+            _ => s.parse().map(FrameSkip::Fixed)
+                .map_err(|_| format!("frameskip must be a number or auto, not '{}'", s)),
+        }
+    }
+}
+
+/// Decides which frames are drawn to the window.
+///
+/// The CPU and timers run every frame regardless; a skipped frame only leaves the window
+/// as it was, with the changes drawn in the next frame that is shown.
+pub struct FrameSkipper {
+    mode: FrameSkip,
+    // Frames skipped since the last one shown
+    skipped: u32,
+}
+
+impl FrameSkipper {
+    pub fn new(mode: FrameSkip) -> FrameSkipper {
+        FrameSkipper { mode, skipped: 0 }
+    }
+
+    /// Whether to show this frame, given whether the host is running behind.
+    pub fn show(&mut self, behind: bool) -> bool {
+        let limit = match self.mode {
+            FrameSkip::Fixed(n) => n,
+            FrameSkip::Auto if behind => MAX_AUTO_SKIP,
+            FrameSkip::Auto => 0,
+        };
+
+        if self.skipped >= limit {
+            self.skipped = 0;
+            true
+        } else {
+            self.skipped += 1;
+            false
+        }
+    }
+}
+
+/// Wait for `duration`, without overshooting it by much more than a few microseconds.
+///
+/// `thread::sleep` wakes up whenever the system timer next fires after the duration, which
+/// on Windows is up to 15.6 ms late unless `TimerResolution` is held, and still up to a
+/// millisecond late with it. Sleeping all but the last bit and spinning through that keeps
+/// frames evenly paced, for the cost of a little CPU time.
+#[cfg(feature = "std")]
+pub fn sleep(duration: Duration) {
+    let deadline = Instant::now() + duration;
+
+    if duration > SPIN_TIME {
+        thread::sleep(duration - SPIN_TIME);
+    }
+
+    while Instant::now() < deadline {
+        hint::spin_loop();
+    }
+}
+
+#[cfg(all(windows, feature = "std"))]
+#[link(name = "winmm")]
+extern "system" {
+    fn timeBeginPeriod(period: u32) -> u32;
+    fn timeEndPeriod(period: u32) -> u32;
+}
+
+/// Raises the resolution of the system timer to a millisecond while it is held, for the
+/// sleeps between frames. This only does anything on Windows, whose timer fires every
+/// 15.6 ms by default; other systems sleep precisely enough as they are.
+#[cfg(feature = "std")]
+pub struct TimerResolution {
+    _private: (),
+}
+
+#[cfg(feature = "std")]
+impl TimerResolution {
+    pub fn request() -> TimerResolution {
+        #[cfg(windows)]
+        unsafe {
+            timeBeginPeriod(1);
+        }
+
+        TimerResolution { _private: () }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for TimerResolution {
+    fn drop(&mut self) {
+        #[cfg(windows)]
+        unsafe {
+            timeEndPeriod(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ticker_carries_remainder() {
+        let mut ticker = Ticker::new(50);
+
+        assert_eq!(ticker.advance(Duration::from_millis(30)), 1);
+        assert_eq!(ticker.advance(Duration::from_millis(30)), 2);
+        assert_eq!(ticker.advance(Duration::from_millis(5)), 0);
+    }
+
+    #[test]
+    fn test_frame_budget() {
+        let mut budget = FrameBudget::new();
+
+        assert_eq!(budget.budget(Duration::from_secs(2), ACTIVE_FRAME), MAX_CATCH_UP);
+        assert!(!budget.is_behind());
+
+        for _ in 1..BEHIND_AFTER {
+            budget.budget(Duration::from_millis(40), ACTIVE_FRAME);
+        }
+        assert!(budget.is_behind());
+
+        budget.budget(ACTIVE_FRAME, ACTIVE_FRAME);
+        assert!(!budget.is_behind());
+    }
+
+    #[test]
+    fn test_idle_pacer_resumes_on_activity() {
+        let mut pacer = IdlePacer::new();
+
+        for _ in 0..IDLE_AFTER {
+            assert_eq!(pacer.frame_time(true), ACTIVE_FRAME);
+        }
+
+        assert_eq!(pacer.frame_time(true), IDLE_FRAME);
+        assert_eq!(pacer.frame_time(false), ACTIVE_FRAME);
+    }
+
+    #[test]
+    fn test_sleep() {
+        let _resolution = TimerResolution::request();
+
+        for &millis in [0, 1, 5].iter() {
+            let duration = Duration::from_millis(millis);
+            let start = Instant::now();
+            sleep(duration);
+
+            assert!(start.elapsed() >= duration);
+        }
+    }
+
+    #[test]
+    fn test_fixed_frame_skip() {
+        let mut skipper = FrameSkipper::new(FrameSkip::Fixed(2));
+        let shown: Vec<bool> = (0..6).map(|_| skipper.show(false)).collect();
+
+        assert_eq!(shown, vec!(false, false, true, false, false, true));
+    }
+
+    #[test]
+    fn test_auto_frame_skip() {
+        let mut skipper = FrameSkipper::new(FrameSkip::Auto);
+        assert!(skipper.show(false));
+        assert!(skipper.show(false));
+
+        let shown = (0..MAX_AUTO_SKIP + 1).filter(|_| skipper.show(true)).count();
+        assert_eq!(shown, 1);
+        assert!(skipper.show(false));
+    }
+
+    #[test]
+    fn test_parse_frame_skip() {
+        assert_eq!("auto".parse(), Ok(FrameSkip::Auto));
+        assert_eq!("3".parse(), Ok(FrameSkip::Fixed(3)));
+        assert!("-1".parse::<FrameSkip>().is_err());
+    }
+}
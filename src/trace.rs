@@ -0,0 +1,227 @@
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    sync::{Arc, Mutex},
+};
+
+use alloc::{collections::VecDeque, format, string::String};
+#[cfg(feature = "std")]
+use alloc::boxed::Box;
+
+use crate::Chip8;
+#[cfg(feature = "std")]
+use crate::VF;
+#[cfg(feature = "std")]
+use crate::debugger::protocol::CpuState;
+use crate::disassembler::disassemble;
+
+/// The kind of an executed instruction, used to filter the event log.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EventKind {
+    Draw,
+    Jump,
+    Keypad,
+    TimerWrite,
+    Other,
+}
+
+/// An instruction executed by the CPU.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Event {
+    pub address: u16,
+    pub opcode: u16,
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self.opcode & 0xF000 {
+            0xD000 => EventKind::Draw,
+            0x1000 | 0x2000 | 0xB000 => EventKind::Jump,
+            0x0000 if self.opcode == 0x00EE => EventKind::Jump,
+            0xE000 => EventKind::Keypad,
+            0xF000 => match self.opcode & 0x00FF {
+                0x0A => EventKind::Keypad,
+                0x15 | 0x18 => EventKind::TimerWrite,
+                _ => EventKind::Other,
+            },
+            _ => EventKind::Other,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{:03X} {}", self.address, disassemble(self.opcode))
+    }
+}
+
+/// The most recently executed instructions, oldest first.
+pub struct TraceBuffer {
+    events: VecDeque<Event>,
+    capacity: usize,
+}
+
+impl TraceBuffer {
+    pub fn new(capacity: usize) -> TraceBuffer {
+        TraceBuffer {
+            events: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn record(&mut self, address: u16, opcode: u16) {
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+
+        self.events.push_back(Event { address, opcode });
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Event> {
+        self.events.iter()
+    }
+}
+
+/// Output format of an execution trace.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TraceFormat {
+    /// The address and mnemonic of every instruction, one per line.
+    Text,
+    /// One JSON object per instruction, for consumption by external tools.
+    Json,
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(TraceFormat::Text),
+            "json" => Ok(TraceFormat::Json),
+            _ => Err(format!("Unknown trace format '{}', expected text or json", s)),
+        }
+    }
+}
+
+/// The registers of the machine around the execution of an instruction.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Registers {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub sp: u16,
+}
+
+impl Registers {
+    pub fn of(chip8: &Chip8) -> Registers {
+        Registers {
+            v: chip8.registers,
+            i: chip8.i,
+            sp: chip8.stack.len() as u16,
+        }
+    }
+
+    fn to_json(self) -> String {
+        format!("{{\"v\":{:?},\"i\":{},\"sp\":{}}}", self.v, self.i, self.sp)
+    }
+}
+
+/// Writes every executed instruction to a file or stdout.
+#[cfg(feature = "std")]
+pub struct TraceWriter {
+    format: TraceFormat,
+    out: Box<dyn Write + Send>,
+}
+
+#[cfg(feature = "std")]
+impl TraceWriter {
+    /// Trace to the given file, or to stdout when no path is given.
+    pub fn new(format: TraceFormat, path: Option<&str>) -> io::Result<TraceWriter> {
+        let out: Box<dyn Write + Send> = match path {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(io::stdout()),
+        };
+
+        Ok(TraceWriter { format, out })
+    }
+
+    pub fn write(&mut self, pc: u16, opcode: u16, before: &Registers, after: &Registers)
+            -> io::Result<()> {
+        let line = match self.format {
+            TraceFormat::Text => Event { address: pc, opcode }.describe(),
+            TraceFormat::Json => json_line(pc, opcode, before, after),
+        };
+
+        writeln!(self.out, "{}", line)
+    }
+
+    /// Trace every instruction the machine executes from now on, through its cycle hooks.
+    pub fn attach(self, chip8: &mut Chip8) {
+        // The registers before the instruction, kept by the pre-cycle hook
+        let state = Arc::new(Mutex::new((self, None)));
+
+        let pre_state = state.clone();
+        chip8.on_pre_cycle(move |chip8| pre_state.lock().unwrap().1 = Some(Registers::of(chip8)));
+
+        let cycle_state = state.clone();
+        chip8.on_post_cycle(move |chip8, pc, opcode| {
+            let (tracer, before) = &mut *cycle_state.lock().unwrap();
+
+            if let Some(before) = before.take() {
+                tracer.write(pc, opcode, &before, &Registers::of(chip8))
+                    .expect("Could not write trace");
+            }
+        });
+
+        // The file is buffered, and a panic may not get to drop the writer
+        chip8.on_shutdown(move |_, _| {
+            if let Ok(mut state) = state.lock() {
+                if let Err(e) = state.0.out.flush() {
+                    println!("Could not write trace: {}", e);
+                }
+            }
+        });
+    }
+}
+
+/// All registers, timers and the stack of the machine as a JSON object.
+#[cfg(feature = "std")]
+pub fn registers_json(chip8: &Chip8) -> String {
+    CpuState::capture(chip8).to_json()
+}
+
+#[cfg(feature = "std")]
+fn json_line(pc: u16, opcode: u16, before: &Registers, after: &Registers) -> String {
+    format!(
+        "{{\"pc\":{},\"opcode\":{},\"mnemonic\":\"{}\",\"before\":{},\"after\":{},\"vf\":{}}}",
+        pc, opcode, disassemble(opcode), before.to_json(), after.to_json(), after.v[VF])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_buffer_capacity() {
+        let mut trace = TraceBuffer::new(2);
+        trace.record(0x200, 0x00E0);
+        trace.record(0x202, 0xD125);
+        trace.record(0x204, 0x1200);
+
+        let kinds: Vec<EventKind> = trace.iter().map(Event::kind).collect();
+
+        assert_eq!(kinds, vec!(EventKind::Draw, EventKind::Jump));
+    }
+
+    #[test]
+    fn test_json_line() {
+        let before = Registers { v: [0; 16], i: 0x300, sp: 0 };
+        let mut after = before;
+        after.v[1] = 2;
+
+        let result = json_line(0x200, 0x6102, &before, &after);
+
+        assert!(result.starts_with("{\"pc\":512,\"opcode\":24834,\"mnemonic\":\"LD V1, 0x02\""));
+        assert!(result.contains("\"after\":{\"v\":[0, 2, 0,"));
+        assert!(result.ends_with("\"vf\":0}"));
+    }
+}
@@ -0,0 +1,332 @@
+//! Finding where two JSON traces of the same ROM part ways, to bisect changes to the
+//! emulator that were not meant to change what it does.
+//!
+//! ```text
+//! chip8 game.ch8 --trace-format json --trace-file old.trace
+//! chip8 trace-diff old.trace new.trace --context 8
+//! ```
+//!
+//! The traces are compared instruction by instruction: the address, the opcode and the
+//! registers before and after it. The mnemonics are left out, so changes to the
+//! disassembler do not count as divergences. The report shows the instructions leading up
+//! to the first divergence, the diverging one of both traces and what differs between them.
+
+use std::{collections::VecDeque, fmt, fs::File, io::{BufRead, BufReader}};
+
+use crate::trace::Registers;
+
+const DEFAULT_CONTEXT: usize = 5;
+const USAGE: &str = "Usage: chip8 trace-diff old.trace new.trace [--context N]";
+
+/// An instruction of a JSON trace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub pc: u16,
+    pub opcode: u16,
+    pub mnemonic: String,
+    pub before: Registers,
+    pub after: Registers,
+}
+
+impl Step {
+    /// Read a line in the layout the trace writer uses for `--trace-format json`.
+    pub fn parse(line: &str) -> Result<Step, String> {
+        if !line.starts_with('{') {
+            return Err(String::from("Not a JSON trace, record it with --trace-format json"));
+        }
+
+        let mnemonic = value(line, "mnemonic")?;
+        let mnemonic = mnemonic.strip_prefix('"')
+            .and_then(|mnemonic| mnemonic.split('"').next())
+            .ok_or_else(|| String::from("Invalid \"mnemonic\""))?;
+
+        Ok(Step {
+            pc: number(line, "pc")?,
+            opcode: number(line, "opcode")?,
+            mnemonic: String::from(mnemonic),
+            before: registers(line, "before")?,
+            after: registers(line, "after")?,
+        })
+    }
+
+    /// What differs between the instructions of both traces, as `old / new`.
+    fn differences(&self, other: &Step) -> Vec<String> {
+        let mut differences = Vec::new();
+
+        if self.pc != other.pc {
+            differences.push(format!("PC {:03X} / {:03X}", self.pc, other.pc));
+        }
+        if self.opcode != other.opcode {
+            differences.push(format!("opcode {:04X} / {:04X}", self.opcode, other.opcode));
+        }
+
+        for &(when, old, new) in [("before", &self.before, &other.before),
+                ("after", &self.after, &other.after)].iter() {
+            for x in 0..16 {
+                if old.v[x] != new.v[x] {
+                    differences.push(format!("V{:X} {} {:02X} / {:02X}",
+                        x, when, old.v[x], new.v[x]));
+                }
+            }
+            if old.i != new.i {
+                differences.push(format!("I {} {:03X} / {:03X}", when, old.i, new.i));
+            }
+            if old.sp != new.sp {
+                differences.push(format!("SP {} {} / {}", when, old.sp, new.sp));
+            }
+        }
+
+        differences
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:03X} {:04X} {}", self.pc, self.opcode, self.mnemonic)
+    }
+}
+
+// The text following `"key":`, in the fixed layout of a trace line rather than any JSON
+fn value<'a>(json: &'a str, key: &str) -> Result<&'a str, String> {
+    let pattern = format!("\"{}\":", key);
+
+    json.find(&pattern)
+        .map(|idx| &json[idx + pattern.len()..])
+        .ok_or(format!("Missing \"{}\"", key))
+}
+
+fn number(json: &str, key: &str) -> Result<u16, String> {
+    let value = value(json, key)?;
+    let end = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+
+    value[..end].parse().map_err(|_| format!("Invalid \"{}\"", key))
+}
+
+fn registers(json: &str, key: &str) -> Result<Registers, String> {
+    let invalid = || format!("Invalid \"{}\"", key);
+    let object = value(json, key)?;
+    let object = &object[..object.find('}').ok_or_else(invalid)?];
+
+    let list = value(object, "v")?;
+    let list = list.strip_prefix('[').and_then(|list| list.split(']').next())
+        .ok_or_else(invalid)?;
+    let v: Vec<u8> = list.split(',')
+        .map(|register| register.trim().parse().map_err(|_| invalid()))
+        .collect::<Result<_, String>>()?;
+
+    let mut registers =
+        Registers { v: [0; 16], i: number(object, "i")?, sp: number(object, "sp")? };
+    if v.len() != registers.v.len() {
+        return Err(invalid());
+    }
+    registers.v.copy_from_slice(&v);
+
+    Ok(registers)
+}
+
+/// Where two traces part ways.
+#[derive(Debug, PartialEq)]
+pub struct Divergence {
+    /// The number of the diverging instruction, counting from 1.
+    pub instruction: u64,
+    /// The instructions both traces agree on right before it.
+    pub context: Vec<Step>,
+    /// The diverging instruction of either trace, or none where that trace ended.
+    pub old: Option<Step>,
+    pub new: Option<Step>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "The traces diverge at instruction {}:", self.instruction)?;
+
+        let first = self.instruction - self.context.len() as u64;
+        for (idx, step) in self.context.iter().enumerate() {
+            writeln!(f, "  {:>8} {}", first + idx as u64, step)?;
+        }
+
+        let describe = |step: &Option<Step>| match step {
+            Some(step) => step.to_string(),
+            None => String::from("(end of trace)"),
+        };
+        writeln!(f, "- {:>8} {}", self.instruction, describe(&self.old))?;
+        write!(f, "+ {:>8} {}", self.instruction, describe(&self.new))?;
+
+        if let (Some(old), Some(new)) = (&self.old, &self.new) {
+            for difference in old.differences(new) {
+                write!(f, "\n  {}", difference)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The outcome of comparing two traces.
+#[derive(Debug, PartialEq)]
+pub enum Comparison {
+    /// Both traces hold the same instructions, this many of them.
+    Identical(u64),
+    Diverged(Divergence),
+}
+
+// The next instruction of a trace, skipping blank lines
+fn next_step(lines: &mut impl Iterator<Item = std::io::Result<String>>, name: &str,
+        instruction: u64) -> Result<Option<Step>, String> {
+    for line in lines {
+        let line = line.map_err(|e| format!("Could not read {}: {}", name, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        return Step::parse(line.trim())
+            .map(Some)
+            .map_err(|e| format!("Instruction {} of {}: {}", instruction, name, e));
+    }
+
+    Ok(None)
+}
+
+/// Compare two traces, keeping `context` instructions before the first divergence.
+pub fn diff(old: impl BufRead, new: impl BufRead, context: usize)
+        -> Result<Comparison, String> {
+    let (mut old_lines, mut new_lines) = (old.lines(), new.lines());
+    let mut recent = VecDeque::with_capacity(context + 1);
+    let mut instruction = 1;
+
+    loop {
+        let old = next_step(&mut old_lines, "the old trace", instruction)?;
+        let new = next_step(&mut new_lines, "the new trace", instruction)?;
+
+        match (old, new) {
+            (None, None) => return Ok(Comparison::Identical(instruction - 1)),
+            (Some(old), Some(new)) if old.differences(&new).is_empty() => {
+                recent.push_back(old);
+                if recent.len() > context {
+                    recent.pop_front();
+                }
+            },
+            (old, new) => return Ok(Comparison::Diverged(Divergence {
+                instruction,
+                context: recent.into_iter().collect(),
+                old,
+                new,
+            })),
+        }
+
+        instruction += 1;
+    }
+}
+
+/// The `trace-diff` subcommand.
+pub fn run_command(args: &[String]) -> Result<(), String> {
+    let mut paths = Vec::new();
+    let mut context = DEFAULT_CONTEXT;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            paths.push(arg);
+            continue;
+        }
+
+        let value = args.next().ok_or(format!("{} requires a value", arg))?;
+
+        match arg.as_str() {
+            "--context" => context = value.parse()
+                .map_err(|_| format!("Invalid number '{}'", value))?,
+            _ => return Err(format!("Unknown argument '{}'\n{}", arg, USAGE)),
+        }
+    }
+
+    let (old, new) = match paths.as_slice() {
+        [old, new] => (old, new),
+        _ => return Err(String::from(USAGE)),
+    };
+    let open = |path: &str| File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| format!("Could not read {}: {}", path, e));
+
+    match diff(open(old)?, open(new)?, context)? {
+        Comparison::Identical(instructions) => {
+            println!("The traces agree on all {} instructions", instructions);
+            Ok(())
+        },
+        Comparison::Diverged(divergence) => Err(divergence.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+    use crate::trace::{TraceFormat, TraceWriter};
+
+    // A JSON trace of the first instructions of a program
+    fn trace(program: &[u8], instructions: usize) -> String {
+        let path = std::env::temp_dir()
+            .join(format!("chip8-trace-diff-{}-{:02X?}", std::process::id(), program));
+        let writer = TraceWriter::new(TraceFormat::Json, path.to_str()).unwrap();
+
+        let mut chip8 = Chip8::new();
+        chip8.load_bytes(program);
+        writer.attach(&mut chip8);
+        for _ in 0..instructions {
+            chip8.cycle();
+        }
+        // Dropping the machine flushes the trace
+        drop(chip8);
+
+        let trace = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        trace
+    }
+
+    #[test]
+    fn test_parse_step() {
+        let trace = trace(&[0x61, 0x02], 1);
+        let step = Step::parse(trace.trim()).unwrap();
+
+        assert_eq!((step.pc, step.opcode, step.mnemonic.as_str()), (0x200, 0x6102, "LD V1, 0x02"));
+        assert_eq!(step.before.v[1], 0);
+        assert_eq!(step.after.v[1], 2);
+        assert!(Step::parse("200 6102 LD V1, 0x02").is_err());
+    }
+
+    #[test]
+    fn test_first_divergence() {
+        // LD V0, 1; ADD V1, 1 repeated, and one where the third ADD adds 2
+        let old = trace(&[0x60, 0x01, 0x71, 0x01, 0x71, 0x01, 0x71, 0x01, 0x71, 0x01], 5);
+        let new = trace(&[0x60, 0x01, 0x71, 0x01, 0x71, 0x01, 0x71, 0x02, 0x71, 0x01], 5);
+
+        assert_eq!(diff(old.as_bytes(), old.as_bytes(), 2), Ok(Comparison::Identical(5)));
+
+        let divergence = match diff(old.as_bytes(), new.as_bytes(), 2).unwrap() {
+            Comparison::Diverged(divergence) => divergence,
+            comparison => panic!("{:?}", comparison),
+        };
+        assert_eq!(divergence.instruction, 4);
+        assert_eq!(divergence.context.iter().map(|step| step.pc).collect::<Vec<u16>>(),
+            vec!(0x202, 0x204));
+
+        let report = divergence.to_string();
+        assert!(report.contains("-        4 206 7101 ADD V1, 0x01"), "{}", report);
+        assert!(report.contains("opcode 7101 / 7102"), "{}", report);
+        assert!(report.contains("V1 after 03 / 04"), "{}", report);
+    }
+
+    #[test]
+    fn test_trace_ends_early() {
+        let program = [0x60, 0x01, 0x71, 0x01, 0x71, 0x01];
+        let (old, new) = (trace(&program, 3), trace(&program, 2));
+
+        match diff(old.as_bytes(), new.as_bytes(), 5).unwrap() {
+            Comparison::Diverged(divergence) => {
+                assert_eq!(divergence.instruction, 3);
+                assert!(divergence.old.is_some() && divergence.new.is_none());
+                assert!(divergence.to_string().ends_with("+        3 (end of trace)"));
+            },
+            comparison => panic!("{:?}", comparison),
+        }
+    }
+}
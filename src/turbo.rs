@@ -0,0 +1,238 @@
+//! Smart turbo: fast forward until the ROM next draws or reads the keypad.
+//!
+//! Some ROMs spend seconds in loops before showing anything, such as seeding a random
+//! generator or clearing memory. F6 runs the machine as fast as the host allows until it
+//! reaches an instruction that changes the display or polls the keys, and then returns to
+//! the normal speed. The timers tick along with the instructions, so a ROM that waits on
+//! the delay timer is fast forwarded as well. Pressing F6 again stops early.
+//!
+//! The turbo also fast forwards to a bookmarked screen, see `bookmark`, running past the
+//! draws until the display matches.
+//!
+//! The turbo is not available in deterministic mode or with authentic timing, which fix
+//! the instructions run per frame.
+
+use std::time::{Duration, Instant};
+
+use minifb::{Key, KeyRepeat, Window};
+
+use crate::display::Display;
+use crate::replay::frame_hash;
+
+// Host time a frame spends running instructions, leaving time to draw and poll input
+const FRAME_TIME: Duration = Duration::from_millis(12);
+// Frames of machine time after which the turbo gives up, a minute at 60 Hz
+const MAX_FRAMES: u32 = 3600;
+
+/// Whether the turbo stops before executing the opcode: it draws, clears the display or
+/// reads the keypad.
+fn stops_at(opcode: u16) -> bool {
+    opcode == 0x00E0
+        || opcode & 0xF000 == 0xD000
+        || matches!(opcode & 0xF0FF, 0xE09E | 0xE0A1 | 0xF00A)
+}
+
+pub struct SmartTurbo {
+    active: bool,
+    // Instructions per tick of the timers
+    cycles_per_tick: u32,
+    // Instructions since the timers last ticked
+    cycles: u32,
+    // Ticks of the timers since the turbo started
+    ticks: u32,
+    deadline: Instant,
+    // The hash of the screen to run to instead of the next draw
+    target: Option<u64>,
+}
+
+impl SmartTurbo {
+    pub fn new(cpu_hz: u32, timer_hz: u32) -> SmartTurbo {
+        SmartTurbo {
+            active: false,
+            cycles_per_tick: (cpu_hz / timer_hz).max(1),
+            cycles: 0,
+            ticks: 0,
+            deadline: Instant::now(),
+            target: None,
+        }
+    }
+
+    /// Start or stop the turbo with F6.
+    pub fn handle_input(&mut self, window: &Window) {
+        if window.is_key_pressed(Key::F6, KeyRepeat::No) {
+            if self.active {
+                self.stop("stopped");
+            } else {
+                self.start();
+            }
+        }
+    }
+
+    pub fn start(&mut self) {
+        println!("Smart turbo: running until the ROM draws or reads the keys");
+        self.active = true;
+        self.target = None;
+        self.cycles = 0;
+        self.ticks = 0;
+    }
+
+    /// Start running until the display shows the screen with the hash.
+    pub fn seek(&mut self, hash: u64) {
+        println!("Smart turbo: running to the bookmarked screen");
+        self.active = true;
+        self.target = Some(hash);
+        self.cycles = 0;
+        self.ticks = 0;
+    }
+
+    fn stop(&mut self, reason: &str) {
+        println!("Smart turbo {} after {} frames", reason, self.ticks);
+        self.active = false;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Start running the instructions of a frame.
+    pub fn begin_frame(&mut self) {
+        self.deadline = Instant::now() + FRAME_TIME;
+    }
+
+    /// Whether the frame ends before the opcode, either because the turbo stops at it or
+    /// because the frame's time is up. The turbo stops at the instruction so it runs at the
+    /// normal speed, and a frame the turbo gave up in ends as well.
+    pub fn ends_frame(&mut self, opcode: u16, waiting: bool) -> bool {
+        if !self.active {
+            return true;
+        }
+
+        if self.target.is_some() {
+            if waiting || matches!(opcode & 0xF0FF, 0xE09E | 0xE0A1 | 0xF00A) {
+                self.stop("gave up, the ROM reads the keys before the bookmarked screen");
+                return true;
+            }
+        } else if waiting || stops_at(opcode) {
+            self.stop("reached the ROM drawing or reading the keys");
+            return true;
+        }
+
+        // The clock is read once per tick of the timers, as reading it is slow compared to
+        // running an instruction
+        self.cycles == 0 && Instant::now() >= self.deadline
+    }
+
+    /// Whether the executed opcode brought up the screen the turbo runs to, stopping it.
+    pub fn reached(&mut self, opcode: u16, display: &Display) -> bool {
+        let target = match self.target {
+            Some(target) if self.active && stops_at(opcode) => target,
+            _ => return false,
+        };
+
+        if frame_hash(display) != target {
+            return false;
+        }
+
+        self.stop("reached the bookmarked screen");
+        true
+    }
+
+    /// Count an instruction executed, returning whether the timers are due to tick.
+    pub fn spend_cycle(&mut self) -> bool {
+        self.cycles += 1;
+        if self.cycles < self.cycles_per_tick {
+            return false;
+        }
+
+        self.cycles = 0;
+        self.ticks += 1;
+        if self.ticks >= MAX_FRAMES {
+            self.stop("gave up");
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    #[test]
+    fn test_stops_at_draw() {
+        let mut chip8 = Chip8::new();
+        // LD V0, 0; loop: ADD V0, 1; SE V0, 0; JP loop; DRW V0, V0, 1
+        chip8.load_bytes(&[0x60, 0x00, 0x70, 0x01, 0x30, 0x00, 0x12, 0x02, 0xD0, 0x01]);
+        let mut turbo = SmartTurbo::new(600, 60);
+        turbo.start();
+
+        let mut executed = 0;
+        let mut timer_ticks = 0;
+        while turbo.is_active() {
+            turbo.begin_frame();
+            loop {
+                let opcode = chip8.fetch();
+                if turbo.ends_frame(opcode, false) {
+                    break;
+                }
+
+                chip8.cycle();
+                executed += 1;
+                if turbo.spend_cycle() {
+                    timer_ticks += 1;
+                }
+            }
+        }
+
+        assert_eq!(chip8.fetch(), 0xD001);
+        // The last time around the loop the jump is skipped
+        assert_eq!(executed, 1 + 3 * 256 - 1);
+        assert_eq!(timer_ticks, 76);
+    }
+
+    #[test]
+    fn test_seeks_screen() {
+        let mut chip8 = Chip8::new();
+        // LD I, line; loop: DRW V0, V0, 1; ADD V0, 8; JP loop; line: a line of 8 pixels.
+        // The turbo runs past the first draws, until the fourth line is drawn.
+        let rom = [0xA2, 0x08, 0xD0, 0x01, 0x70, 0x08, 0x12, 0x02, 0xFF];
+        chip8.load_bytes(&rom);
+        let mut expected = Chip8::new();
+        expected.load_bytes(&rom);
+        for _ in 0..1 + 3 * 4 - 2 {
+            expected.cycle();
+        }
+
+        let mut turbo = SmartTurbo::new(600, 60);
+        turbo.seek(frame_hash(&expected.display));
+        turbo.begin_frame();
+        let mut executed = 0;
+        while executed < 100 {
+            let opcode = chip8.fetch();
+            if turbo.ends_frame(opcode, false) {
+                break;
+            }
+
+            chip8.cycle();
+            executed += 1;
+            if turbo.reached(opcode, &chip8.display) {
+                break;
+            }
+            turbo.spend_cycle();
+        }
+
+        assert!(!turbo.is_active());
+        assert_eq!(executed, 1 + 3 * 4 - 2);
+    }
+
+    #[test]
+    fn test_stops_at_key_poll() {
+        assert!(stops_at(0x00E0));
+        assert!(stops_at(0xE39E));
+        assert!(stops_at(0xE3A1));
+        assert!(stops_at(0xF30A));
+        assert!(!stops_at(0xF307));
+        assert!(!stops_at(0x00EE));
+    }
+}
@@ -0,0 +1,60 @@
+//! How close a ROM came to the limits of the machine, reported on exit so homebrew
+//! developers know how much room they have left.
+//!
+//! The COSMAC VIP interpreter keeps its own variables from 0xEA0 on and the display from
+//! 0xF00, so a ROM that writes there works in emulators but not on the real machine.
+
+use crate::{Chip8, MEMORY};
+
+/// Where the memory the COSMAC VIP reserves for the interpreter starts.
+const RESERVED_START: usize = 0xEA0;
+
+/// The deepest the stack got and the highest address written, with a warning for either
+/// that came close to the limit.
+pub fn report(chip8: &Chip8) -> Vec<String> {
+    let (used, depth) = (chip8.stack.high_water(), chip8.stack.depth());
+    let mut stack = format!("Stack: at most {} of {} levels in use", used, depth);
+    // Within a quarter of the depth
+    if used * 4 >= depth * 3 {
+        stack.push_str(", close to the limit");
+    }
+
+    let memory = match chip8.highest_write {
+        Some(address) if address as usize >= RESERVED_START => format!(
+            "Memory: highest write at {:#05X}, in the interpreter area of the COSMAC VIP \
+            ({:#05X}-{:#05X})", address, RESERVED_START, MEMORY - 1),
+        Some(address) => format!("Memory: highest write at {:#05X}, {} bytes below the \
+            interpreter area of the COSMAC VIP", address, RESERVED_START - address as usize),
+        None => String::from("Memory: nothing written"),
+    };
+
+    vec!(stack, memory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::Cpu;
+
+    #[test]
+    fn test_report() {
+        let mut chip8 = Chip8::new();
+        assert_eq!(report(&chip8), vec!(
+            "Stack: at most 0 of 16 levels in use",
+            "Memory: nothing written"));
+
+        for address in 0..12 {
+            chip8.push(address);
+        }
+        chip8.write_memory(0x300, 1);
+        chip8.write_memory(0x280, 1);
+
+        assert_eq!(report(&chip8), vec!(
+            "Stack: at most 12 of 16 levels in use, close to the limit",
+            "Memory: highest write at 0x300, 2976 bytes below the interpreter area of the \
+            COSMAC VIP"));
+
+        chip8.write_memory(0xF00, 1);
+        assert!(report(&chip8)[1].contains("in the interpreter area"));
+    }
+}
@@ -0,0 +1,85 @@
+//! The CHIP-8 variants a ROM can be written for.
+
+use core::str::FromStr;
+
+use alloc::{format, string::String};
+
+use crate::PROGRAM_START;
+use crate::chip8x;
+use crate::quirks::Quirks;
+use crate::sprite_rules::{Edge, SpriteRules, ZeroHeight};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variant {
+    /// CHIP-8 as implemented by modern interpreters.
+    Chip8,
+    /// The original interpreter on the COSMAC VIP.
+    CosmacVip,
+    SChip,
+    XoChip,
+    /// CHIP-8 with the VP-590 colour board and a second keypad.
+    Chip8X,
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Variant, String> {
+        match s {
+            "chip8" => Ok(Variant::Chip8),
+            "vip" => Ok(Variant::CosmacVip),
+            "schip" => Ok(Variant::SChip),
+            "xochip" | "xo-chip" => Ok(Variant::XoChip),
+            "chip8x" => Ok(Variant::Chip8X),
+            _ => Err(format!(
+                "Unknown variant '{}', expected chip8, vip, schip, xochip or chip8x", s)),
+        }
+    }
+}
+
+impl Variant {
+    pub const ALL: [Variant; 5] = [
+        Variant::Chip8, Variant::CosmacVip, Variant::SChip, Variant::XoChip, Variant::Chip8X,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Chip8 => "CHIP-8",
+            Variant::CosmacVip => "COSMAC VIP",
+            Variant::SChip => "SCHIP",
+            Variant::XoChip => "XO-CHIP",
+            Variant::Chip8X => "CHIP-8X",
+        }
+    }
+
+    /// The address ROMs for this variant are loaded at.
+    pub fn program_start(self) -> u16 {
+        match self {
+            Variant::Chip8X => chip8x::PROGRAM_START,
+            _ => PROGRAM_START as u16,
+        }
+    }
+
+    /// The quirks ROMs for this variant expect.
+    pub fn quirks(self) -> Quirks {
+        match self {
+            // CHIP-8X ran on the VIP as well
+            Variant::CosmacVip | Variant::Chip8X => {
+                Quirks { key_release: true, vf_reset: true, display_wait: true }
+            },
+            Variant::Chip8 | Variant::SChip | Variant::XoChip => Quirks::default(),
+        }
+    }
+
+    /// How DRW draws sprites for this variant.
+    pub fn sprite_rules(self) -> SpriteRules {
+        let (lores_zero, edge) = match self {
+            Variant::Chip8 => (ZeroHeight::Nothing, Edge::Wrap),
+            Variant::CosmacVip | Variant::Chip8X => (ZeroHeight::Nothing, Edge::Clip),
+            Variant::SChip => (ZeroHeight::Tall, Edge::Clip),
+            Variant::XoChip => (ZeroHeight::Large, Edge::Wrap),
+        };
+
+        SpriteRules { lores_zero, edge }
+    }
+}
@@ -0,0 +1,184 @@
+//! Instruction timing of the original COSMAC VIP interpreter.
+//!
+//! On the VIP every instruction takes a different amount of time: a register load takes a
+//! few dozen machine cycles of the 1802 CPU, while drawing a sprite or clearing the screen
+//! takes hundreds to thousands. Games were tuned to that speed, so with authentic timing
+//! the emulator runs as many instructions per frame as fit in the VIP's machine cycles,
+//! instead of a fixed number of instructions per second.
+//!
+//! The costs are approximations of the VIP interpreter routines in 1802 machine cycles
+//! (8 clock cycles each), averaged over taken and untaken skips.
+//!
+//! With the `display_wait` quirk DRW waits for the display interrupt, so at most one sprite
+//! is drawn per frame. The sprite is then drawn while the video chip shows the display, and
+//! rows the beam passes before they are written only show up in the next frame. Tall
+//! sprites near the bottom, which wrap around to the top, are cut this way.
+
+use crate::HEIGHT;
+
+/// Machine cycles between two 60 Hz interrupts on the 1.76 MHz VIP.
+pub const FRAME_CYCLES: u32 = 3668;
+
+/// Machine cycles of every frame taken by the display: the video chip reads 8 bytes by DMA
+/// for each of the 128 scan lines, and the interrupt routine counts down the timers.
+pub const DISPLAY_CYCLES: u32 = 1024 + 76;
+
+/// Fetching and dispatching an instruction, on top of the cost of the instruction itself.
+const FETCH_CYCLES: u32 = 40;
+
+/// Machine cycles the beam takes for a display row, which is 4 scan lines of 14 cycles.
+const ROW_CYCLES: u32 = 4 * 14;
+/// Of the 14 machine cycles of a scan line, the DMA leaves 6 to the CPU.
+const SCAN_LINE_CYCLES: u32 = 14;
+const CPU_CYCLES_PER_LINE: u32 = 6;
+
+/// The display rows of a sprite drawn right after the display interrupt that the beam
+/// passes before they are written, as a bit per row.
+pub fn late_rows(y: usize, height: usize) -> u64 {
+    let mut late = 0;
+
+    for row in 0..height {
+        let cpu_cycles = FETCH_CYCLES + 26 + 68 * (row as u32 + 1);
+        let written = cpu_cycles * SCAN_LINE_CYCLES / CPU_CYCLES_PER_LINE;
+        let target = (y + row) % HEIGHT;
+
+        if written >= ROW_CYCLES * target as u32 {
+            late |= 1 << target;
+        }
+    }
+
+    late
+}
+
+/// The number of machine cycles the VIP interpreter spends on an instruction.
+pub fn cost(opcode: u16) -> u32 {
+    let x = (opcode & 0x0F00) >> 8;
+    let n = opcode & 0x000F;
+
+    let execute = match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => 3078,
+            0x00EE => 10,
+            _ => 0,
+        },
+        0x1000 => 12,
+        0x2000 => 26,
+        0x3000 | 0x4000 => 12,
+        0x5000 | 0x9000 => 16,
+        0x6000 => 6,
+        0x7000 => 10,
+        0x8000 => 44,
+        0xA000 => 12,
+        0xB000 => 22,
+        0xC000 => 36,
+        // Every sprite row is shifted into place and XORed with two bytes of the display
+        0xD000 => 26 + 68 * n as u32,
+        0xE000 => 16,
+        0xF000 => match opcode & 0x00FF {
+            0x07 | 0x15 | 0x18 => 10,
+            0x0A => 18,
+            0x1E | 0x29 => 16,
+            0x33 => 84,
+            0x55 | 0x65 => 14 + 14 * (x as u32 + 1),
+            _ => 0,
+        },
+        _ => 0,
+    };
+
+    FETCH_CYCLES + execute
+}
+
+/// Machine cycles left for the interpreter in the current frame.
+///
+/// Every timer tick adds a frame worth of cycles, and every instruction spends its cost.
+/// An instruction that does not fit is still completed, borrowing from the next frame, so
+/// the average speed is exact.
+pub struct VipClock {
+    credit: i64,
+    // Cycles spent since the last tick
+    spent: u32,
+}
+
+impl VipClock {
+    pub fn new() -> VipClock {
+        VipClock { credit: 0, spent: 0 }
+    }
+
+    /// Add the cycles of a frame, called on every tick of the 60 Hz timer.
+    pub fn tick(&mut self) {
+        self.credit += (FRAME_CYCLES - DISPLAY_CYCLES) as i64;
+        self.spent = 0;
+    }
+
+    /// Whether nothing ran since the display interrupt, so a waiting DRW may go ahead.
+    pub fn at_interrupt(&self) -> bool {
+        self.spent == 0
+    }
+
+    /// Give up the rest of the frame, for an instruction that waits for the next interrupt.
+    pub fn wait_for_interrupt(&mut self) {
+        self.credit = self.credit.min(0);
+    }
+
+    /// Whether another instruction can start in this frame.
+    pub fn has_credit(&self) -> bool {
+        self.credit > 0
+    }
+
+    pub fn spend(&mut self, opcode: u16) {
+        self.credit -= cost(opcode) as i64;
+        self.spent += cost(opcode);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_costs_more_than_load() {
+        assert!(cost(0xD015) > 5 * cost(0x6012));
+        assert!(cost(0xD01F) > cost(0xD011));
+    }
+
+    #[test]
+    fn test_late_rows() {
+        // The beam overtakes the last rows of a tall sprite, and the rows wrapped to the top
+        assert_eq!(late_rows(24, 4), 0);
+        assert_eq!(late_rows(16, 15) >> 16, 0b0111_1111_1100_0000);
+        assert_eq!(late_rows(30, 4), 0b11);
+    }
+
+    #[test]
+    fn test_wait_for_interrupt() {
+        let mut clock = VipClock::new();
+        clock.tick();
+        assert!(clock.at_interrupt());
+
+        clock.spend(0x6012);
+        assert!(!clock.at_interrupt());
+        clock.wait_for_interrupt();
+        assert!(!clock.has_credit());
+
+        clock.tick();
+        assert!(clock.at_interrupt() && clock.has_credit());
+    }
+
+    #[test]
+    fn test_clock_borrows_from_next_frame() {
+        let mut clock = VipClock::new();
+        assert!(!clock.has_credit());
+
+        clock.tick();
+        let mut instructions = 0;
+        while clock.has_credit() {
+            clock.spend(0x00E0);
+            instructions += 1;
+        }
+        assert_eq!(instructions, 1);
+
+        // Clearing the screen took more than a frame, so the next frame starts in debt
+        clock.tick();
+        assert!(clock.credit < (FRAME_CYCLES - DISPLAY_CYCLES) as i64);
+    }
+}
@@ -0,0 +1,83 @@
+//! Watching the ROM file for changes, for a fast edit-assemble-run loop while developing a ROM.
+//!
+//! The directory of the ROM is watched rather than the file itself, because many assemblers
+//! write a new file and rename it over the old one.
+
+use std::path::Path;
+
+/// Reports when the watched ROM file was written, created or replaced.
+#[cfg(feature = "watch")]
+pub struct RomWatcher {
+    path: std::path::PathBuf,
+    events: std::sync::mpsc::Receiver<notify::DebouncedEvent>,
+    _watcher: notify::RecommendedWatcher,
+}
+
+#[cfg(feature = "watch")]
+impl RomWatcher {
+    pub fn new(path: &Path) -> Result<RomWatcher, String> {
+        use std::{sync::mpsc, time::Duration};
+        use notify::{RecursiveMode, Watcher};
+
+        // Wait for the assembler to finish writing before reporting a change
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::watcher(sender, Duration::from_millis(200))
+            .map_err(|e| e.to_string())?;
+
+        let path = path.canonicalize().map_err(|e| e.to_string())?;
+        let directory = path.parent().unwrap_or_else(|| Path::new("."));
+        watcher.watch(directory, RecursiveMode::NonRecursive)
+            .map_err(|e| e.to_string())?;
+
+        Ok(RomWatcher { path, events, _watcher: watcher })
+    }
+
+    /// Whether the ROM changed since the last call.
+    pub fn changed(&self) -> bool {
+        use notify::DebouncedEvent;
+
+        self.events.try_iter().fold(false, |changed, event| changed || match event {
+            DebouncedEvent::Write(path)
+            | DebouncedEvent::Create(path)
+            | DebouncedEvent::Rename(_, path) => is_rom(&self.path, &path),
+            _ => false,
+        })
+    }
+}
+
+#[cfg(not(feature = "watch"))]
+pub struct RomWatcher;
+
+#[cfg(not(feature = "watch"))]
+impl RomWatcher {
+    pub fn new(path: &Path) -> Result<RomWatcher, String> {
+        Err(format!("Cannot watch {}: built without the `watch` feature", path.display()))
+    }
+
+    pub fn changed(&self) -> bool {
+        false
+    }
+}
+
+/// Whether an event path refers to the ROM, comparing file names when the event path cannot
+/// be resolved, e.g. because the file was removed again.
+#[cfg(feature = "watch")]
+fn is_rom(rom: &Path, path: &Path) -> bool {
+    match path.canonicalize() {
+        Ok(path) => path == rom,
+        Err(_) => path.file_name() == rom.file_name(),
+    }
+}
+
+#[cfg(all(test, feature = "watch"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_rom() {
+        let rom = std::path::PathBuf::from("/nonexistent/pong.ch8");
+
+        assert!(is_rom(&rom, Path::new("/nonexistent/pong.ch8")));
+        assert!(!is_rom(&rom, Path::new("/nonexistent/pong.asm")));
+    }
+}
@@ -0,0 +1,67 @@
+//! Uses the interpreter the way an embedder does, through the types the crate exports.
+
+use std::{sync::{Arc, Mutex}, time::Duration};
+
+use chip8::{
+    attach_audio, AudioSink, Clock, Decoded, Effect, Emulator, EmulatorBuilder, Level,
+    ManualClock, ShutdownReason, Target, Variant,
+};
+
+#[derive(Default)]
+struct Recorder {
+    played: Vec<&'static str>,
+}
+
+impl AudioSink for Recorder {
+    fn start_tone(&mut self) {
+        self.played.push("start");
+    }
+
+    fn stop_tone(&mut self) {
+        self.played.push("stop");
+    }
+
+    fn queue_pattern(&mut self, _pattern: Option<[u8; 16]>, _pitch: u8) {
+        self.played.push("pattern");
+    }
+}
+
+#[test]
+fn test_embed() {
+    // CLS; LD I, 0x20C; DRW V0, V0, 1; LD V0, 5; LD ST, V0; LD V1, K; a line of 8 pixels
+    let rom = [0x00, 0xE0, 0xA2, 0x0C, 0xD0, 0x01, 0x60, 0x05, 0xF0, 0x18, 0xF1, 0x0A, 0xFF];
+    let builder: EmulatorBuilder = Emulator::builder().variant(Variant::Chip8).seed(42);
+    let mut chip8 = builder.rom_bytes(&rom).build().unwrap().chip8;
+
+    let decoded: Vec<String> = chip8.instructions_at(0x200).take(3)
+        .map(|(_, decoded): (u16, Decoded)| decoded.disassemble())
+        .collect();
+    assert_eq!(decoded, vec!("CLS", "LD I, 0x20C", "DRW V0, V0, 1"));
+
+    let logged = Arc::new(Mutex::new(Vec::new()));
+    let log = logged.clone();
+    chip8.on_log(move |target, level, _| log.lock().unwrap().push((target, level)));
+
+    let sink = Arc::new(Mutex::new(Recorder::default()));
+    attach_audio(sink.clone(), &mut chip8);
+    let clock = ManualClock::new();
+    chip8.set_clock(60, clock.clone());
+
+    let effects: Vec<Effect> = (0..6).map(|_| chip8.step()).collect();
+    assert_eq!(effects, vec!(Effect::ClearScreen, Effect::None, Effect::Draw, Effect::None,
+        Effect::Beep, Effect::WaitKey));
+    assert!(chip8.frame().is_lit(7, 0));
+    assert!(logged.lock().unwrap().contains(&(Target::Cpu, Level::Trace)));
+
+    // The buzzer starts at the first tick and sounds for the five after it
+    clock.advance(Duration::from_millis(100));
+    assert_eq!(clock.elapsed(), Duration::from_millis(100));
+    assert_eq!(chip8.run_timers(), 6);
+    assert_eq!(sink.lock().unwrap().played, vec!("start", "stop"));
+
+    let reason = Arc::new(Mutex::new(None));
+    let shutdown = reason.clone();
+    chip8.on_shutdown(move |_, reason| *shutdown.lock().unwrap() = Some(reason));
+    chip8.shutdown(ShutdownReason::Exit);
+    assert_eq!(*reason.lock().unwrap(), Some(ShutdownReason::Exit));
+}
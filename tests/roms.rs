@@ -0,0 +1,62 @@
+//! Runs the ROMs listed in `tests/roms/golden.txt` headlessly and compares the final
+//! display against stored hashes, so a regression in any opcode shows up as a failure.
+
+use std::{env, fs, path::Path, process::Command};
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+#[test]
+fn test_rom_display_hashes() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let golden_path = root.join("tests/roms/golden.txt");
+    let golden = fs::read_to_string(&golden_path).unwrap();
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+
+    let mut failures = Vec::new();
+    let mut updated = String::new();
+
+    for line in golden.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+
+        let (rom, cycles, expected) = match fields.as_slice() {
+            [rom, cycles, expected] if !rom.starts_with('#') => (*rom, *cycles, *expected),
+            _ => {
+                updated.push_str(line);
+                updated.push('\n');
+                continue;
+            },
+        };
+
+        let display = env::temp_dir().join(format!("chip8-golden-{}.pgm", rom.replace('/', "_")));
+        let output = Command::new(env!("CARGO_BIN_EXE_chip8"))
+            .current_dir(root)
+            .args(&["run", rom, "--cycles", cycles, "--dump-display"])
+            .arg(&display)
+            .output()
+            .unwrap();
+
+        if !output.status.success() {
+            failures.push(format!("{} crashed:\n{}", rom, String::from_utf8_lossy(&output.stderr)));
+            updated.push_str(line);
+            updated.push('\n');
+            continue;
+        }
+
+        let hash = format!("{:016X}", fnv1a(&fs::read(&display).unwrap()));
+        if hash != expected {
+            failures.push(format!("{} after {} cycles: expected {}, got {}", rom, cycles, expected, hash));
+        }
+
+        updated.push_str(&format!("{} {} {}\n", rom, cycles, hash));
+    }
+
+    if update {
+        fs::write(&golden_path, updated).unwrap();
+    } else {
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}